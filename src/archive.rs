@@ -0,0 +1,302 @@
+// Archive dependencies: source archives of libraries that are not in a repository, downloaded from
+// their URL and checked against the checksum given in the manifest before they are unpacked. An
+// archive is unpacked once into a directory named by its checksum as the targets depending on it
+// are registered, and that directory is then used as the path of a source dependency.
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::environment::Environment;
+use crate::parser::types::ArchiveData;
+
+/// Directory of the build directory the archives of dependencies are unpacked into.
+pub const ARCHIVES_DIRECTORY_NAME: &str = "archives";
+
+// File written into the directory of an archive once it is unpacked in full.
+const UNPACKED_MARKER_FILE_NAME: &str = ".yambs_unpacked";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("\"{0}\" is not a SHA-256 checksum. Give the 64 hexadecimal digits of the checksum")]
+    InvalidChecksum(String),
+    #[error("Failed to download {0}: {1}")]
+    Download(String, String),
+    #[error("Checksum of {url} is {actual}, but the manifest expects {expected}")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("{0} has not been downloaded yet. It is downloaded by yambs build")]
+    NotUnpacked(String),
+    #[error("Failed to unpack {0:?}: {1}")]
+    Unpack(PathBuf, String),
+    #[error("Failed to access {0:?}")]
+    Io(PathBuf, #[source] std::io::Error),
+}
+
+/// Directory the archives of dependencies are unpacked into, such as the archives directory of the
+/// build directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveDirectory {
+    path: PathBuf,
+    download: bool,
+}
+
+impl ArchiveDirectory {
+    /// Archives are downloaded and unpacked into `path` unless they are already.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            download: true,
+        }
+    }
+
+    /// Only archives already unpacked into `path` are used, by commands that do not write to the
+    /// build directory.
+    pub fn read_only(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            download: false,
+        }
+    }
+
+    /// Directory of the sources of the archive of `data`, downloading and unpacking it first unless
+    /// it has been already. An archive holding a single directory, as most release archives do, has
+    /// its sources in that directory.
+    pub fn fetch(&self, data: &ArchiveData) -> Result<PathBuf, ArchiveError> {
+        let checksum = checksum_of(data)?;
+        let directory = self.path.join(&checksum);
+        if !directory.join(UNPACKED_MARKER_FILE_NAME).is_file() {
+            if !self.download {
+                return Err(ArchiveError::NotUnpacked(data.url.clone()));
+            }
+            std::fs::create_dir_all(&self.path)
+                .map_err(|e| ArchiveError::Io(self.path.clone(), e))?;
+            let download = self.path.join(format!("{}.download", checksum));
+            download_file(&data.url, &download)?;
+            let actual = sha256_of_file(&download)?;
+            if actual != checksum {
+                let _ = std::fs::remove_file(&download);
+                return Err(ArchiveError::ChecksumMismatch {
+                    url: data.url.clone(),
+                    expected: checksum,
+                    actual,
+                });
+            }
+            let result = unpack(&data.url, &download, &directory);
+            let _ = std::fs::remove_file(&download);
+            result?;
+        }
+        sources_of(&directory)
+    }
+}
+
+/// Checksum of the archive of `data` in lowercase, which names the directory it is unpacked into.
+pub fn checksum_of(data: &ArchiveData) -> Result<String, ArchiveError> {
+    let checksum = data.sha256.to_ascii_lowercase();
+    if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ArchiveError::InvalidChecksum(data.sha256.clone()));
+    }
+    Ok(checksum)
+}
+
+fn download_file(url: &str, destination: &Path) -> Result<(), ArchiveError> {
+    log::debug!("Downloading {} to {}", url, destination.display());
+    // Downloaded with wget where curl is not installed.
+    let environment = Environment::new();
+    let output = match environment
+        .command(Path::new("curl"))
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--output",
+        ])
+        .arg(destination)
+        .arg(url)
+        .output()
+    {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => environment
+            .command(Path::new("wget"))
+            .args(["--quiet", "--output-document"])
+            .arg(destination)
+            .arg(url)
+            .output(),
+        output => output,
+    }
+    .map_err(|e| {
+        ArchiveError::Download(
+            url.to_string(),
+            format!("could not run curl or wget: {}", e),
+        )
+    })?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(destination);
+        return Err(ArchiveError::Download(
+            url.to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Unpacks `archive` into `directory`, by way of a directory of its own so that an archive failing
+// to unpack midway is not taken to be unpacked. Zip archives are unpacked with unzip and others
+// with tar, which tells the compression itself.
+fn unpack(url: &str, archive: &Path, directory: &Path) -> Result<(), ArchiveError> {
+    let partial = directory.with_extension("partial");
+    for stale in [partial.as_path(), directory] {
+        if stale.exists() {
+            std::fs::remove_dir_all(stale).map_err(|e| ArchiveError::Io(stale.to_path_buf(), e))?;
+        }
+    }
+    std::fs::create_dir_all(&partial).map_err(|e| ArchiveError::Io(partial.clone(), e))?;
+    let environment = Environment::new();
+    let output = if url.ends_with(".zip") {
+        environment
+            .command(Path::new("unzip"))
+            .arg("-q")
+            .arg(archive)
+            .arg("-d")
+            .arg(&partial)
+            .output()
+    } else {
+        environment
+            .command(Path::new("tar"))
+            .arg("-xf")
+            .arg(archive)
+            .arg("-C")
+            .arg(&partial)
+            .output()
+    }
+    .map_err(|e| ArchiveError::Unpack(archive.to_path_buf(), e.to_string()))?;
+    if !output.status.success() {
+        return Err(ArchiveError::Unpack(
+            archive.to_path_buf(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    std::fs::write(partial.join(UNPACKED_MARKER_FILE_NAME), url)
+        .map_err(|e| ArchiveError::Io(partial.clone(), e))?;
+    std::fs::rename(&partial, directory)
+        .map_err(|e| ArchiveError::Io(directory.to_path_buf(), e))?;
+    log::debug!("Unpacked {} into {}", url, directory.display());
+    Ok(())
+}
+
+fn sources_of(directory: &Path) -> Result<PathBuf, ArchiveError> {
+    let entries = std::fs::read_dir(directory)
+        .map_err(|e| ArchiveError::Io(directory.to_path_buf(), e))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| !path.ends_with(UNPACKED_MARKER_FILE_NAME))
+        .collect::<Vec<PathBuf>>();
+    match entries.as_slice() {
+        [single] if single.is_dir() && !directory.join(crate::YAMBS_MANIFEST_NAME).exists() => {
+            Ok(single.clone())
+        }
+        _ => Ok(directory.to_path_buf()),
+    }
+}
+
+fn sha256_of_file(path: &Path) -> Result<String, ArchiveError> {
+    let mut file =
+        std::fs::File::open(path).map_err(|e| ArchiveError::Io(path.to_path_buf(), e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|e| ArchiveError::Io(path.to_path_buf(), e))?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn sha256_matches_the_test_vectors_of_the_standard() {
+        let tempdir = tempdir::TempDir::new("archive").unwrap();
+        let sha256 = |content: &[u8]| {
+            let file = tempdir.path().join("content");
+            std::fs::write(&file, content).unwrap();
+            sha256_of_file(&file).unwrap()
+        };
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn archives_are_checked_against_their_checksum_before_they_are_unpacked() {
+        let tempdir = tempdir::TempDir::new("archive").unwrap();
+        let sources = tempdir.path().join("zlib-1.3");
+        std::fs::create_dir_all(&sources).unwrap();
+        std::fs::write(sources.join(crate::YAMBS_MANIFEST_NAME), "").unwrap();
+        let archive = tempdir.path().join("zlib-1.3.tar");
+        let status = std::process::Command::new("tar")
+            .arg("-cf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(tempdir.path())
+            .arg("zlib-1.3")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let checksum = sha256_of_file(&archive).unwrap();
+
+        let directory = tempdir.path().join("unpacked");
+        unpack("zlib-1.3.tar", &archive, &directory).unwrap();
+        assert_eq!(sources_of(&directory).unwrap(), directory.join("zlib-1.3"));
+
+        let data = |sha256: &str| ArchiveData {
+            url: format!("file://{}", archive.display()),
+            sha256: sha256.to_string(),
+            origin: None,
+            link_options: Default::default(),
+        };
+        let archive_directory =
+            ArchiveDirectory::new(&tempdir.path().join(ARCHIVES_DIRECTORY_NAME));
+        assert!(matches!(
+            archive_directory.fetch(&data("abc")),
+            Err(ArchiveError::InvalidChecksum(_))
+        ));
+        assert!(matches!(
+            ArchiveDirectory::read_only(&tempdir.path().join(ARCHIVES_DIRECTORY_NAME))
+                .fetch(&data(&checksum)),
+            Err(ArchiveError::NotUnpacked(_))
+        ));
+        if std::process::Command::new("curl")
+            .arg("--version")
+            .output()
+            .is_ok()
+        {
+            assert!(matches!(
+                archive_directory.fetch(&data(&"0".repeat(64))),
+                Err(ArchiveError::ChecksumMismatch { .. })
+            ));
+            assert_eq!(
+                archive_directory.fetch(&data(&checksum)).unwrap(),
+                tempdir
+                    .path()
+                    .join(ARCHIVES_DIRECTORY_NAME)
+                    .join(&checksum)
+                    .join("zlib-1.3")
+            );
+        }
+    }
+}
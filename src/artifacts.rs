@@ -0,0 +1,144 @@
+// The artifacts manifest of a build tree, listing the artifacts the build signs along with the
+// files their signatures are written to, for release pipelines to pick up what was signed.
+
+use std::path::{Path, PathBuf};
+
+use crate::errors::FsError;
+use crate::targets::Signing;
+use crate::utility;
+
+pub const ARTIFACTS_MANIFEST_FILE_NAME: &str = "artifacts.json";
+/// File listing the artifacts the build signs, which the build copies to the artifacts manifest
+/// once every signature exists.
+pub const SIGNING_PLAN_FILE_NAME: &str = "signing.json";
+
+/// Artifact signed by the build once it is linked.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SignedArtifact {
+    pub target: String,
+    pub artifact: PathBuf,
+    /// File the signature is written to. Artifacts signed in place have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<PathBuf>,
+    /// Command signing the artifact.
+    pub command: Vec<String>,
+}
+
+impl SignedArtifact {
+    pub fn new(target: &str, artifact: &Path, signing: &Signing) -> Self {
+        Self {
+            target: target.to_string(),
+            artifact: artifact.to_path_buf(),
+            signature: signing.signature_of(artifact),
+            command: signing.command_for(artifact),
+        }
+    }
+
+    /// File made by the rule signing the artifact: its signature, or a stamp touched once an
+    /// artifact signed in place is signed, so that it is signed again only when linked again.
+    pub fn rule_output(&self) -> PathBuf {
+        self.signature.clone().unwrap_or_else(|| {
+            let mut stamp = self.artifact.clone().into_os_string();
+            stamp.push(".signed");
+            PathBuf::from(stamp)
+        })
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArtifactsManifest {
+    pub artifacts: Vec<SignedArtifact>,
+}
+
+impl ArtifactsManifest {
+    /// Reads the artifacts manifest of the build tree `directory`.
+    pub fn from_directory(directory: &Path) -> std::io::Result<Self> {
+        let fh = std::fs::File::open(directory.join(ARTIFACTS_MANIFEST_FILE_NAME))?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(fh))?)
+    }
+
+    /// Writes the artifacts to sign to the signing plan of the build tree `directory`.
+    pub fn write_plan(&self, directory: &Path) -> Result<(), FsError> {
+        let content = serde_json::to_string_pretty(self).unwrap();
+        utility::write_file_atomically(&directory.join(SIGNING_PLAN_FILE_NAME), content.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::RawSigningData;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn artifacts_are_signed_into_their_signature_or_in_place() {
+        let data = RawSigningData {
+            command: vec![
+                "gpg".to_string(),
+                "--output".to_string(),
+                "{signature}".to_string(),
+                "--detach-sign".to_string(),
+                "{artifact}".to_string(),
+            ],
+            signature: Some("{artifact}.sig".to_string()),
+            targets: None,
+        };
+        let signing = Signing::new(&data, &[], &[]).unwrap();
+        let artifact = SignedArtifact::new("app", Path::new("/build/debug/app"), &signing);
+        assert_eq!(
+            artifact.command,
+            vec![
+                "gpg",
+                "--output",
+                "/build/debug/app.sig",
+                "--detach-sign",
+                "/build/debug/app"
+            ]
+        );
+        assert_eq!(
+            artifact.rule_output(),
+            PathBuf::from("/build/debug/app.sig")
+        );
+
+        let in_place = Signing::new(
+            &RawSigningData {
+                command: vec!["codesign".to_string(), "{artifact}".to_string()],
+                signature: None,
+                targets: None,
+            },
+            &[],
+            &[],
+        )
+        .unwrap();
+        let artifact = SignedArtifact::new("app", Path::new("/build/debug/app"), &in_place);
+        assert_eq!(artifact.signature, None);
+        assert_eq!(
+            artifact.rule_output(),
+            PathBuf::from("/build/debug/app.signed")
+        );
+
+        for (command, signature) in [
+            (vec!["codesign"], None),
+            (vec!["gpg", "--output", "{signature}", "{artifact}"], None),
+            (vec!["gpg", "{artifact}"], Some("app.sig")),
+        ] {
+            let data = RawSigningData {
+                command: command.iter().map(|word| word.to_string()).collect(),
+                signature: signature.map(|signature| signature.to_string()),
+                targets: None,
+            };
+            assert!(Signing::new(&data, &[], &[]).is_err());
+        }
+        assert!(matches!(
+            Signing::new(
+                &RawSigningData {
+                    targets: Some(vec!["app".to_string()]),
+                    ..data
+                },
+                &[],
+                &[]
+            ),
+            Err(crate::targets::SigningError::UnknownTarget(_))
+        ));
+    }
+}
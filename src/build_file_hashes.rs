@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::cache::Cacher;
+use crate::generator::makefile::{
+    BUILD_FILE_EXTENSION, INCLUDE_DIRECTORY_NAME, MAKEFILE_NAME, TARGET_BUILD_FILE_NAME,
+};
+use crate::generator::ninja::NINJA_BUILD_FILE_NAME;
+
+/// Flag letting a build regenerate build files that were edited since they were generated.
+pub const ALLOW_MODIFIED_BUILD_FILES_FLAG: &str = "--allow-modified-build-files";
+
+/// Hashes of the build files of the build directory, as of their most recent generation. Build
+/// files are generated again by every build, so changes made to them by hand are lost. A build
+/// file whose content no longer matches its hash has been edited since it was generated.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BuildFileHashes {
+    #[serde(default)]
+    pub files: BTreeMap<PathBuf, u64>,
+}
+
+impl Cacher for BuildFileHashes {
+    const CACHE_FILE_NAME: &'static str = "build_file_hashes";
+}
+
+impl BuildFileHashes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the hashes of the build files in `directories` with those of their current
+    /// content.
+    pub fn record(&mut self, directories: &[PathBuf]) -> std::io::Result<()> {
+        self.files.retain(|file, _| {
+            !directories
+                .iter()
+                .any(|directory| file.starts_with(directory))
+        });
+        for directory in directories {
+            for file in build_files(directory)? {
+                let hash = hash_file(&file)?;
+                self.files.insert(file, hash);
+            }
+        }
+        Ok(())
+    }
+
+    /// Build files in `directories` that were changed since they were generated. Removed build
+    /// files are not reported, as there are no changes of them left to lose.
+    pub fn modified_files(&self, directories: &[PathBuf]) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|(file, _)| {
+                directories
+                    .iter()
+                    .any(|directory| file.starts_with(directory))
+            })
+            .filter(|(file, hash)| match hash_file(file) {
+                Ok(current) => current != **hash,
+                Err(_) => false,
+            })
+            .map(|(file, _)| file.clone())
+            .collect()
+    }
+}
+
+/// Directories of the build files of the build type in `build_directory`: the include files
+/// shared by all build types, and the build trees of the target and of the host.
+pub fn build_file_directories(
+    build_directory: &Path,
+    host_directory_name: &str,
+    build_type: &str,
+) -> Vec<PathBuf> {
+    vec![
+        build_directory.join(INCLUDE_DIRECTORY_NAME),
+        build_directory.join(build_type),
+        build_directory.join(host_directory_name).join(build_type),
+    ]
+}
+
+// Build files generated in `directory`. An include directory has include files, while a build
+// tree has a Makefile and a build file for each target, or a build.ninja. Files generated by custom commands are
+// left out, even when they are makefiles.
+fn build_files(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !directory.is_dir() {
+        return Ok(files);
+    }
+    if directory
+        .file_name()
+        .is_some_and(|name| name == INCLUDE_DIRECTORY_NAME)
+    {
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == BUILD_FILE_EXTENSION)
+            {
+                files.push(path);
+            }
+        }
+    } else {
+        for build_file_name in [MAKEFILE_NAME, NINJA_BUILD_FILE_NAME] {
+            let build_file = directory.join(build_file_name);
+            if build_file.is_file() {
+                files.push(build_file);
+            }
+        }
+        let deps = directory.join("deps");
+        if deps.is_dir() {
+            for entry in std::fs::read_dir(&deps)? {
+                let build_file = entry?.path().join(TARGET_BUILD_FILE_NAME);
+                if build_file.is_file() {
+                    files.push(build_file);
+                }
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+pub fn hash_file(path: &Path) -> std::io::Result<u64> {
+    Ok(hash_content(&std::fs::read(path)?))
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+// FNV-1a, which unlike the hasher of the standard library gives the same hash with every version
+// of Rust, so that hashes cached by one version of yambs can be compared by another.
+pub fn hash_content(content: &[u8]) -> u64 {
+    fnv(FNV_OFFSET_BASIS, content)
+}
+
+fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Hash of several parts with `hash_content`, for keys persisted in caches, which must be the
+/// same for every version of Rust yambs is built with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentHasher(u64);
+
+impl ContentHasher {
+    pub fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+
+    /// Adds `part`, followed by its length so that consecutive parts can not run into each other.
+    pub fn add(&mut self, part: impl AsRef<[u8]>) -> &mut Self {
+        let part = part.as_ref();
+        self.0 = fnv(fnv(self.0, part), &(part.len() as u64).to_le_bytes());
+        self
+    }
+
+    pub fn add_path(&mut self, path: &Path) -> &mut Self {
+        self.add(path.to_string_lossy().as_bytes())
+    }
+
+    /// Adds `part`, telling it apart from an empty part when there is none.
+    pub fn add_optional(&mut self, part: Option<impl AsRef<[u8]>>) -> &mut Self {
+        match part {
+            Some(part) => self.add([1]).add(part),
+            None => self.add([0]),
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_hashes_do_not_change_between_releases() {
+        assert_eq!(hash_content(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_content(b"a"), 0xaf63_dc4c_8601_ec8c);
+        let hash = ContentHasher::new().add("ab").add("c").finish();
+        assert_ne!(hash, ContentHasher::new().add("a").add("bc").finish());
+        assert_ne!(
+            ContentHasher::new().add_optional(Some("")).finish(),
+            ContentHasher::new().add_optional(None::<&str>).finish()
+        );
+    }
+
+    #[test]
+    fn build_files_edited_after_generation_are_modified() {
+        let build_directory = tempdir::TempDir::new("build_file_hashes").unwrap();
+        let directories = build_file_directories(build_directory.path(), "host", "debug");
+        let include_file = directories[0].join("defines.mk");
+        let makefile = directories[1].join(MAKEFILE_NAME);
+        let target_build_file = directories[1]
+            .join("deps/app.dir")
+            .join(TARGET_BUILD_FILE_NAME);
+        let generated = directories[1].join("deps/app.dir/generated/rules.mk");
+        std::fs::create_dir_all(&directories[0]).unwrap();
+        std::fs::create_dir_all(generated.parent().unwrap()).unwrap();
+        for file in [&include_file, &makefile, &target_build_file, &generated] {
+            std::fs::write(file, "all:\n").unwrap();
+        }
+
+        let mut hashes = BuildFileHashes::new();
+        hashes.record(&directories).unwrap();
+        assert_eq!(hashes.files.len(), 3);
+        assert!(hashes.modified_files(&directories).is_empty());
+
+        std::fs::write(&target_build_file, "all:\n\techo edited\n").unwrap();
+        std::fs::write(&generated, "edited:\n").unwrap();
+        std::fs::remove_file(&makefile).unwrap();
+        assert_eq!(
+            hashes.modified_files(&directories),
+            vec![target_build_file.clone()]
+        );
+
+        std::fs::write(&makefile, "all:\n").unwrap();
+        hashes.record(&directories).unwrap();
+        assert!(hashes.modified_files(&directories).is_empty());
+    }
+}
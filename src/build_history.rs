@@ -0,0 +1,353 @@
+// Summaries of the builds of a build directory, kept in the build cache so that `yambs report` can
+// tell how the targets evolved over the most recent builds: how many objects they have, how long
+// they take to compile, how large their binaries grow, how many of their objects are reused and
+// how many warnings they have.
+
+use crate::cache::Cacher;
+use crate::generator::targets::{ProgressDocument, ProgressTrackingTarget};
+use crate::graph::format_duration;
+use crate::timings::CompileTimings;
+
+/// Number of builds kept in the history. Older builds are dropped as new ones are recorded.
+pub const BUILD_HISTORY_LENGTH: usize = 50;
+
+/// Builds reported by `yambs report`, unless told otherwise.
+pub const DEFAULT_REPORTED_BUILDS: usize = 10;
+
+/// The most recent builds of a build directory, oldest first.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BuildHistory {
+    #[serde(default)]
+    pub builds: Vec<BuildSummary>,
+}
+
+impl Cacher for BuildHistory {
+    const CACHE_FILE_NAME: &'static str = "build_history";
+}
+
+impl BuildHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `summary`, dropping the oldest builds beyond BUILD_HISTORY_LENGTH.
+    pub fn record(&mut self, summary: BuildSummary) {
+        self.builds.push(summary);
+        if self.builds.len() > BUILD_HISTORY_LENGTH {
+            let excess = self.builds.len() - BUILD_HISTORY_LENGTH;
+            self.builds.drain(..excess);
+        }
+    }
+
+    /// The most recent `count` builds of `build_type`, oldest first.
+    pub fn recent(&self, build_type: &str, count: usize) -> Vec<&BuildSummary> {
+        let builds = self
+            .builds
+            .iter()
+            .filter(|build| build.build_type == build_type)
+            .collect::<Vec<&BuildSummary>>();
+        builds[builds.len().saturating_sub(count)..].to_vec()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BuildSummary {
+    /// Seconds since the Unix epoch when the build started.
+    pub started: u64,
+    pub build_type: String,
+    pub success: bool,
+    /// Duration of the build in milliseconds.
+    pub duration: u64,
+    pub targets: Vec<TargetSummary>,
+}
+
+/// A target as left by a build.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TargetSummary {
+    pub target: String,
+    pub objects: usize,
+    /// Objects compiled by the build. The others were up to date.
+    pub compiled: usize,
+    /// Sum of the most recent compile durations of the objects in milliseconds, as recorded by
+    /// builds with --schedule-by-duration. None if no durations are recorded.
+    pub compile_time: Option<u64>,
+    /// Size in bytes of the file linked by the target, if it has been linked.
+    pub binary_size: Option<u64>,
+    pub warnings: usize,
+}
+
+impl TargetSummary {
+    /// Summary of `target` after a build compiling `compiled` of its objects.
+    pub fn new(
+        target: &ProgressTrackingTarget,
+        compiled: usize,
+        warnings: usize,
+        compile_timings: &CompileTimings,
+    ) -> Self {
+        let durations = target
+            .object_files
+            .iter()
+            .filter_map(|object| compile_timings.duration(object))
+            .collect::<Vec<u64>>();
+        Self {
+            target: target.target.clone(),
+            objects: target.object_files.len(),
+            compiled: compiled.min(target.object_files.len()),
+            compile_time: (!durations.is_empty()).then(|| durations.iter().sum()),
+            binary_size: target
+                .output
+                .as_deref()
+                .and_then(|output| std::fs::metadata(output).ok())
+                .map(|metadata| metadata.len()),
+            warnings,
+        }
+    }
+}
+
+/// A target over the reported builds. Its objects, compile time, binary size and warnings are
+/// those of the most recent build.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct TargetReport {
+    pub target: String,
+    /// Reported builds the target was built by.
+    pub builds: usize,
+    pub objects: usize,
+    pub compile_time: Option<u64>,
+    pub binary_size: Option<u64>,
+    /// Growth of the binary in bytes since the oldest reported build it was linked by.
+    pub binary_size_change: Option<i64>,
+    /// Fraction of the objects the builds reused instead of compiling them again. None if the
+    /// target has no objects.
+    pub reuse_rate: Option<f64>,
+    pub warnings: usize,
+}
+
+/// Report of the targets over the most recent builds, printed by `yambs report`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct BuildReport {
+    pub builds: usize,
+    pub successful_builds: usize,
+    pub targets: Vec<TargetReport>,
+}
+
+impl BuildReport {
+    /// Report of `builds`, oldest first. Targets are listed as in the most recent build they were
+    /// built by.
+    pub fn new(builds: &[&BuildSummary]) -> Self {
+        let mut names = Vec::<&str>::new();
+        for build in builds.iter().rev() {
+            for target in &build.targets {
+                if !names.contains(&target.target.as_str()) {
+                    names.push(&target.target);
+                }
+            }
+        }
+        let targets = names
+            .into_iter()
+            .map(|name| {
+                let summaries = builds
+                    .iter()
+                    .filter_map(|build| build.targets.iter().find(|target| target.target == name))
+                    .collect::<Vec<&TargetSummary>>();
+                Self::target_report(name, &summaries)
+            })
+            .collect();
+        Self {
+            builds: builds.len(),
+            successful_builds: builds.iter().filter(|build| build.success).count(),
+            targets,
+        }
+    }
+
+    // Report of a target from its summaries, oldest first. There is at least one.
+    fn target_report(name: &str, summaries: &[&TargetSummary]) -> TargetReport {
+        let latest = summaries[summaries.len() - 1];
+        let sizes = summaries
+            .iter()
+            .filter_map(|summary| summary.binary_size)
+            .collect::<Vec<u64>>();
+        let objects = summaries
+            .iter()
+            .map(|summary| summary.objects)
+            .sum::<usize>();
+        let compiled = summaries
+            .iter()
+            .map(|summary| summary.compiled)
+            .sum::<usize>();
+        TargetReport {
+            target: name.to_string(),
+            builds: summaries.len(),
+            objects: latest.objects,
+            compile_time: latest.compile_time,
+            binary_size: latest.binary_size,
+            binary_size_change: match (sizes.first(), latest.binary_size) {
+                (Some(first), Some(last)) => Some(last as i64 - *first as i64),
+                _ => None,
+            },
+            reuse_rate: (objects > 0).then(|| (objects - compiled) as f64 / objects as f64),
+            warnings: latest.warnings,
+        }
+    }
+
+    /// The report as a table with a row for each target.
+    pub fn to_text(&self) -> String {
+        let header = [
+            "Target",
+            "Objects",
+            "Compile time",
+            "Binary size",
+            "Change",
+            "Reused",
+            "Warnings",
+        ];
+        let rows = self
+            .targets
+            .iter()
+            .map(|target| {
+                [
+                    target.target.clone(),
+                    target.objects.to_string(),
+                    target.compile_time.map(format_duration).unwrap_or_default(),
+                    target.binary_size.map(format_size).unwrap_or_default(),
+                    target
+                        .binary_size_change
+                        .map(|change| {
+                            let sign = if change < 0 { "-" } else { "+" };
+                            format!("{}{}", sign, format_size(change.unsigned_abs()))
+                        })
+                        .unwrap_or_default(),
+                    target
+                        .reuse_rate
+                        .map(|rate| format!("{:.0} %", rate * 100.0))
+                        .unwrap_or_default(),
+                    target.warnings.to_string(),
+                ]
+            })
+            .collect::<Vec<[String; 7]>>();
+        let widths = (0..header.len())
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row[column].len())
+                    .chain(std::iter::once(header[column].len()))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<usize>>();
+        let format_row = |cells: Vec<&str>| {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+        let mut text = format!(
+            "Last {} build(s), {} successful\n",
+            self.builds, self.successful_builds
+        );
+        text.push_str(&format_row(header.to_vec()));
+        text.push('\n');
+        for row in &rows {
+            text.push_str(&format_row(row.iter().map(String::as_str).collect()));
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Targets of `document` built when building `targets_to_build` and their dependencies, or `all`
+/// if there are none. Targets only grouping other targets, such as `all`, are left out.
+pub fn built_targets<'a>(
+    document: &'a ProgressDocument,
+    targets_to_build: &[String],
+) -> Vec<&'a ProgressTrackingTarget> {
+    let all = ["all".to_string()];
+    let requested = if targets_to_build.is_empty() {
+        &all[..]
+    } else {
+        targets_to_build
+    };
+    let mut names = Vec::<&str>::new();
+    for target in document
+        .targets
+        .iter()
+        .filter(|target| requested.contains(&target.target))
+    {
+        for name in target
+            .dependencies
+            .iter()
+            .chain(std::iter::once(&target.target))
+        {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+    }
+    document
+        .targets
+        .iter()
+        .filter(|target| names.contains(&target.target.as_str()))
+        .filter(|target| !target.object_files.is_empty() || target.output.is_some())
+        .collect()
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn build(success: bool, compiled: usize, binary_size: u64) -> BuildSummary {
+        BuildSummary {
+            started: 0,
+            build_type: "debug".to_string(),
+            success,
+            duration: 1000,
+            targets: vec![TargetSummary {
+                target: "app".to_string(),
+                objects: 4,
+                compiled,
+                compile_time: Some(2500),
+                binary_size: Some(binary_size),
+                warnings: 1,
+            }],
+        }
+    }
+
+    #[test]
+    fn report_aggregates_reuse_and_size_change_over_the_builds() {
+        let mut history = BuildHistory::new();
+        history.record(build(true, 4, 1000));
+        history.record(build(false, 2, 1500));
+        history.record(build(true, 0, 3048));
+        let report = BuildReport::new(&history.recent("debug", 2));
+        assert_eq!(report.builds, 2);
+        assert_eq!(report.successful_builds, 1);
+        let app = &report.targets[0];
+        assert_eq!(app.builds, 2);
+        assert_eq!(app.binary_size_change, Some(1548));
+        assert_eq!(app.reuse_rate, Some(0.75));
+        assert_eq!(
+            report.to_text(),
+            "Last 2 build(s), 1 successful\n\
+             Target  Objects  Compile time  Binary size  Change    Reused  Warnings\n\
+             app     4        2.50 s        3.0 KiB      +1.5 KiB  75 %    1\n"
+        );
+    }
+}
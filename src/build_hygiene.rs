@@ -0,0 +1,225 @@
+// Checks that the build directory and the source tree stay apart. A build directory inside the
+// source tree that is not ignored by git ends up with generated files in commits, and sources
+// placed in the build directory may be overwritten or removed by a build.
+
+use std::path::{Path, PathBuf};
+
+use crate::build_target::target_registry::TargetRegistry;
+use crate::environment::Environment;
+use crate::errors::FsError;
+
+/// Name of the file ignoring the build directory it is placed in.
+pub const GITIGNORE_FILE_NAME: &str = ".gitignore";
+
+/// Whether git ignores `generated_file`, a file generated in the build directory, according to
+/// the repository `source_directory` is in. None if git could not tell, such as when the directory
+/// is not in a repository or git is not installed.
+pub fn is_ignored_by_git(source_directory: &Path, generated_file: &Path) -> Option<bool> {
+    // A build directory ignoring everything in it with a .gitignore of its own is not ignored
+    // itself, so a file in it is checked instead. The file does not have to exist.
+    let status = Environment::new()
+        .command(Path::new("git"))
+        .arg("-C")
+        .arg(source_directory)
+        .args(["check-ignore", "--quiet"])
+        .arg(generated_file)
+        .stderr(std::process::Stdio::null())
+        .status()
+        .ok()?;
+    match status.code() {
+        Some(0) => Some(true),
+        Some(1) => Some(false),
+        _ => None,
+    }
+}
+
+/// Writes a .gitignore ignoring everything in `build_directory`, including itself, unless the
+/// directory already has one. Returns the file written.
+pub fn write_gitignore(build_directory: &Path) -> Result<Option<PathBuf>, FsError> {
+    let gitignore = build_directory.join(GITIGNORE_FILE_NAME);
+    if gitignore.exists() {
+        return Ok(None);
+    }
+    crate::utility::write_file_atomically(
+        &gitignore,
+        b"# Written by yambs. Everything in the build directory is generated.\n*\n",
+    )?;
+    Ok(Some(gitignore))
+}
+
+/// Sources of the registered targets that are placed in `build_directory`, along with the name of
+/// their target. Sources generated by custom commands are not part of the targets and are left
+/// out, as are those of the archive dependencies unpacked into the build directory. Targets of
+/// manifests in the build directory, as of builds in the source directory, are not asked about.
+pub fn sources_in_build_directory(
+    registry: &TargetRegistry,
+    build_directory: &Path,
+) -> Vec<(String, PathBuf)> {
+    let archives = build_directory.join(crate::archive::ARCHIVES_DIRECTORY_NAME);
+    let mut sources = Vec::new();
+    for target in &registry.registry {
+        let borrowed_target = target.borrow();
+        if borrowed_target
+            .manifest
+            .directory
+            .starts_with(build_directory)
+        {
+            continue;
+        }
+        for source in &borrowed_target.source_files {
+            if source.file().starts_with(build_directory) && !source.file().starts_with(&archives) {
+                sources.push((borrowed_target.name(), source.file()));
+            }
+        }
+    }
+    sources
+}
+
+/// File a target generates while building, such as an output of a custom command or the source
+/// of its embedded files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedFile {
+    pub path: PathBuf,
+    pub target: String,
+    /// Manifest declaring the target.
+    pub manifest: PathBuf,
+}
+
+/// Generated file that would clobber another file when built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GeneratedFileConflict {
+    /// Two rules produce the same file.
+    DeclaredTwice(GeneratedFile, GeneratedFile),
+    /// The file is a source of a target, declared in the manifest given.
+    OverwritesSource(GeneratedFile, String, PathBuf),
+}
+
+impl std::fmt::Display for GeneratedFileConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DeclaredTwice(first, second) => write!(
+                f,
+                "{} is generated by both target \"{}\" in {} and target \"{}\" in {}",
+                first.path.display(),
+                first.target,
+                first.manifest.display(),
+                second.target,
+                second.manifest.display()
+            ),
+            Self::OverwritesSource(generated, target, manifest) => write!(
+                f,
+                "{} generated by target \"{}\" in {} would overwrite a source of target \"{}\" in {}",
+                generated.path.display(),
+                generated.target,
+                generated.manifest.display(),
+                target,
+                manifest.display()
+            ),
+        }
+    }
+}
+
+/// Generated files of `generated` that are declared more than once, or that would overwrite a
+/// source of a registered target. Sources include the inputs of custom commands, embedded files
+/// and extra files, which covers the files of the source tree the targets know of.
+pub fn generated_file_conflicts(
+    generated: &[GeneratedFile],
+    registry: &TargetRegistry,
+) -> Vec<GeneratedFileConflict> {
+    let mut sources = std::collections::HashMap::new();
+    for target in &registry.registry {
+        let borrowed_target = target.borrow();
+        let manifest = borrowed_target
+            .manifest
+            .directory
+            .join(crate::YAMBS_MANIFEST_NAME);
+        let inputs = borrowed_target
+            .custom_commands
+            .iter()
+            .flat_map(|custom_command| custom_command.inputs.iter().cloned());
+        for source in borrowed_target
+            .source_files
+            .iter()
+            .map(|source| source.file())
+            .chain(inputs)
+            .chain(borrowed_target.embed.iter().cloned())
+            .chain(borrowed_target.extra_files.iter().cloned())
+        {
+            sources
+                .entry(source)
+                .or_insert_with(|| (borrowed_target.name(), manifest.clone()));
+        }
+    }
+
+    let mut conflicts = Vec::new();
+    let mut declared = std::collections::HashMap::<&Path, &GeneratedFile>::new();
+    for file in generated {
+        if let Some(first) = declared.insert(&file.path, file) {
+            conflicts.push(GeneratedFileConflict::DeclaredTwice(
+                first.clone(),
+                file.clone(),
+            ));
+            continue;
+        }
+        if let Some((target, manifest)) = sources.get(&file.path) {
+            conflicts.push(GeneratedFileConflict::OverwritesSource(
+                file.clone(),
+                target.clone(),
+                manifest.clone(),
+            ));
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_gitignore_makes_git_ignore_the_build_directory() {
+        let tempdir = tempdir::TempDir::new("hygiene").unwrap();
+        let source_directory = tempdir.path();
+        let build_directory = source_directory.join("build");
+        std::fs::create_dir(&build_directory).unwrap();
+        let initialized = std::process::Command::new("git")
+            .args(["init", "--quiet"])
+            .current_dir(source_directory)
+            .status()
+            .unwrap();
+        assert!(initialized.success());
+
+        let makefile = build_directory.join("debug").join("Makefile");
+        assert_eq!(is_ignored_by_git(source_directory, &makefile), Some(false));
+        assert_eq!(
+            write_gitignore(&build_directory).unwrap(),
+            Some(build_directory.join(GITIGNORE_FILE_NAME))
+        );
+        assert_eq!(is_ignored_by_git(source_directory, &makefile), Some(true));
+        assert_eq!(write_gitignore(&build_directory).unwrap(), None);
+    }
+
+    #[test]
+    fn generated_file_conflicts_finds_files_declared_twice() {
+        let tempdir = tempdir::TempDir::new("hygiene").unwrap();
+        let source_directory = tempdir.path();
+        let generated_file = |path: &Path, target: &str| GeneratedFile {
+            path: path.to_path_buf(),
+            target: target.to_string(),
+            manifest: source_directory.join(target).join("yambs.toml"),
+        };
+        let first = generated_file(&source_directory.join("build/generated.cpp"), "a");
+        let second = generated_file(&source_directory.join("build/generated.cpp"), "b");
+        let other = generated_file(&source_directory.join("build/other.cpp"), "a");
+        let conflicts = generated_file_conflicts(
+            &[first.clone(), second.clone(), other],
+            &TargetRegistry::new(),
+        );
+        assert_eq!(
+            conflicts,
+            vec![GeneratedFileConflict::DeclaredTwice(first, second)]
+        );
+        assert!(conflicts[0].to_string().contains("target \"a\" in"));
+        assert!(conflicts[0].to_string().contains("and target \"b\" in"));
+    }
+}
@@ -9,11 +9,14 @@ impl SourceFiles {
         Self(Vec::new())
     }
 
-    pub fn from_paths(sources: &[std::path::PathBuf]) -> Result<Self, AssociatedFileError> {
+    pub fn from_paths(
+        sources: &[std::path::PathBuf],
+        extensions: &FileExtensions,
+    ) -> Result<Self, AssociatedFileError> {
         Ok(Self(
             sources
                 .iter()
-                .map(|source| SourceFile::new(source))
+                .map(|source| SourceFile::new(source, extensions))
                 .collect::<Result<Vec<SourceFile>, AssociatedFileError>>()?,
         ))
     }
@@ -69,28 +72,36 @@ pub enum AssociatedFileError {
 pub struct SourceFile {
     file_type: FileType,
     file: std::path::PathBuf,
+    #[serde(default)]
+    generated: bool,
 }
 
 impl SourceFile {
-    pub fn new(file: &std::path::Path) -> Result<Self, AssociatedFileError> {
+    pub fn new(
+        file: &std::path::Path,
+        extensions: &FileExtensions,
+    ) -> Result<Self, AssociatedFileError> {
         if !file.exists() {
             return Err(AssociatedFileError::FileNotExisting(file.to_path_buf()));
         }
-        let file_type = match file.extension().and_then(|extension| extension.to_str()) {
-            Some("cpp") | Some("cc") | Some("c") => FileType::Source,
-            Some("h") | Some("hpp") => FileType::Header,
-            Some(ft) => {
-                return Err(AssociatedFileError::CouldNotSpecifyFileType(ft.to_string()));
-            }
-            None => {
-                return Err(AssociatedFileError::NoFileExtension(file.to_path_buf()));
-            }
-        };
+        let file_type = FileType::from_path(file, extensions)?;
         log::debug!("Found source file {}", file.display());
 
         Ok(Self {
             file_type,
             file: file.to_path_buf(),
+            generated: false,
+        })
+    }
+
+    /// Source file produced during the build, which is why it is not required to exist yet.
+    /// Gives None if the file is neither a source nor a header.
+    pub fn generated(file: &std::path::Path, extensions: &FileExtensions) -> Option<Self> {
+        let file_type = FileType::from_path(file, extensions).ok()?;
+        Some(Self {
+            file_type,
+            file: file.to_path_buf(),
+            generated: true,
         })
     }
 
@@ -105,6 +116,10 @@ impl SourceFile {
     pub fn is_header(&self) -> bool {
         self.file_type == FileType::Header
     }
+
+    pub fn is_generated(&self) -> bool {
+        self.generated
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash, serde::Serialize, serde::Deserialize)]
@@ -113,6 +128,38 @@ pub enum FileType {
     Header,
 }
 
+impl FileType {
+    fn from_path(
+        file: &std::path::Path,
+        extensions: &FileExtensions,
+    ) -> Result<Self, AssociatedFileError> {
+        match file.extension().and_then(|extension| extension.to_str()) {
+            Some("cpp") | Some("cc") | Some("c") => Ok(FileType::Source),
+            // Header-implementation files hold the definitions of templates and inline functions,
+            // and are included by headers instead of being compiled on their own.
+            Some("h") | Some("hpp") | Some("ipp") | Some("inl") | Some("tpp") => {
+                Ok(FileType::Header)
+            }
+            Some(ft) if extensions.sources.iter().any(|extension| extension == ft) => {
+                Ok(FileType::Source)
+            }
+            Some(ft) if extensions.headers.iter().any(|extension| extension == ft) => {
+                Ok(FileType::Header)
+            }
+            Some(ft) => Err(AssociatedFileError::CouldNotSpecifyFileType(ft.to_string())),
+            None => Err(AssociatedFileError::NoFileExtension(file.to_path_buf())),
+        }
+    }
+}
+
+/// Extensions a project recognizes as sources and headers, in addition to .cpp, .cc and .c
+/// sources and .h, .hpp, .ipp, .inl and .tpp headers. Extensions are given without the dot.
+#[derive(Debug, Default, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FileExtensions {
+    pub sources: Vec<String>,
+    pub headers: Vec<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,8 +172,9 @@ mod tests {
         let expected = SourceFile {
             file_type: FileType::Source,
             file: file.clone(),
+            generated: false,
         };
-        let actual = SourceFile::new(&file).unwrap();
+        let actual = SourceFile::new(&file, &FileExtensions::default()).unwrap();
         assert_eq!(actual, expected);
     }
 
@@ -138,20 +186,70 @@ mod tests {
         let expected = SourceFile {
             file_type: FileType::Header,
             file: file.clone(),
+            generated: false,
         };
-        let actual = SourceFile::new(&file).unwrap();
+        let actual = SourceFile::new(&file, &FileExtensions::default()).unwrap();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn header_implementation_files_are_header_file_type() {
+        for file in ["file.ipp", "file.inl", "file.tpp"] {
+            let file = std::path::Path::new(file);
+            assert!(SourceFile::generated(file, &FileExtensions::default())
+                .unwrap()
+                .is_header());
+        }
+    }
+
+    #[test]
+    fn project_extensions_are_recognized() {
+        let extensions = FileExtensions {
+            sources: vec!["cxx".to_string()],
+            headers: vec!["tcc".to_string()],
+        };
+        assert!(
+            SourceFile::generated(std::path::Path::new("file.cxx"), &extensions)
+                .unwrap()
+                .is_source()
+        );
+        assert!(
+            SourceFile::generated(std::path::Path::new("file.tcc"), &extensions)
+                .unwrap()
+                .is_header()
+        );
+        assert_eq!(
+            SourceFile::generated(std::path::Path::new("file.tcc"), &FileExtensions::default()),
+            None
+        );
+    }
+
     #[test]
     fn fails_to_recognize_file_type() {
         let tempdir = tempdir::TempDir::new("test").unwrap();
         let file = tempdir.path().join("file.py");
         std::fs::File::create(&file).unwrap();
-        let actual = SourceFile::new(&file);
+        let actual = SourceFile::new(&file, &FileExtensions::default());
         assert_eq!(
             actual.unwrap_err(),
             AssociatedFileError::CouldNotSpecifyFileType(String::from("py"))
         );
     }
+
+    #[test]
+    fn generated_source_file_does_not_need_to_exist() {
+        let file = std::path::Path::new("/non/existing/generated.cpp");
+        let actual = SourceFile::generated(file, &FileExtensions::default()).unwrap();
+        assert!(actual.is_source());
+        assert!(actual.is_generated());
+    }
+
+    #[test]
+    fn generated_file_with_unknown_file_type_is_not_a_source_file() {
+        let file = std::path::Path::new("/non/existing/generated.txt");
+        assert_eq!(
+            SourceFile::generated(file, &FileExtensions::default()),
+            None
+        );
+    }
 }
@@ -0,0 +1,465 @@
+// Imports targets of a project built by CMake, so that yambs targets can link libraries of a
+// project that has not been migrated yet. The targets are discovered through the file API of
+// CMake: a query is placed in the configured build directory of the project, and CMake answers it
+// with a description of every target the next time it configures the project. The libraries are
+// left to CMake to build.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::build_target::include_directories::{IncludeDirectories, IncludeDirectory, IncludeType};
+use crate::cli::configurations::BuildType;
+use crate::environment::Environment;
+use crate::errors::FsError;
+use crate::{find_program, FindProgramOptions};
+
+/// Name of the client yambs queries the file API as.
+const CLIENT_NAME: &str = "client-yambs";
+const CODEMODEL_QUERY: &str = "codemodel-v2";
+const CMAKE_CACHE_FILE_NAME: &str = "CMakeCache.txt";
+
+#[derive(Debug, Error)]
+pub enum CMakeError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+    #[error("Could not find cmake executable")]
+    CouldNotFindCMake,
+    #[error("{0:?} is not a build directory configured by CMake. Configure the project with CMake first")]
+    NotConfigured(PathBuf),
+    #[error("Failed to run cmake")]
+    FailedToRunCMake(#[source] std::io::Error),
+    #[error("cmake failed with the following error:\n{0}")]
+    CMakeFailedWithError(String),
+    #[error("CMake did not answer the file API query in {0:?}")]
+    NoReply(PathBuf),
+    #[error("Failed to parse file API reply {0:?}")]
+    FailedToParseReply(PathBuf, #[source] serde_json::Error),
+    #[error("CMake project has no target {target}. Available libraries are: {}", available.join(", "))]
+    NoTargetWithName {
+        target: String,
+        available: Vec<String>,
+    },
+    #[error("CMake target {0} is a {1}, not a library")]
+    NotALibrary(String, String),
+    #[error("Library {1:?} of CMake target {0} does not exist. Build the CMake project first")]
+    MissingArtifact(String, PathBuf),
+}
+
+/// Library of a CMake project, as described by the file API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CMakeTarget {
+    pub cmake_target: String,
+    /// Build directory of the project the target is imported from.
+    pub build_directory: PathBuf,
+    pub include_directories: IncludeDirectories,
+    /// The library of the target, followed by the libraries of the project it links.
+    pub libraries: Vec<PathBuf>,
+}
+
+/// Configured CMake build directory of a project.
+#[derive(Debug, Clone)]
+pub struct CMakeProject {
+    build_directory: PathBuf,
+}
+
+impl CMakeProject {
+    pub fn new(build_directory: &Path) -> Result<Self, CMakeError> {
+        if !build_directory.join(CMAKE_CACHE_FILE_NAME).is_file() {
+            return Err(CMakeError::NotConfigured(build_directory.to_path_buf()));
+        }
+        Ok(Self {
+            build_directory: build_directory.to_path_buf(),
+        })
+    }
+
+    /// File whose modification time changes whenever CMake configures the project.
+    pub fn cache_file(build_directory: &Path) -> PathBuf {
+        build_directory.join(CMAKE_CACHE_FILE_NAME)
+    }
+
+    fn api_directory(&self) -> PathBuf {
+        self.build_directory.join(".cmake").join("api").join("v1")
+    }
+
+    /// Finds the library `target` of the project, in the configuration of `build_type` if CMake
+    /// generated several. The query is only answered when CMake configures the project, so CMake
+    /// is run if there is no answer yet. Once the query is in place, CMake answers it again
+    /// whenever it reconfigures the project on its own.
+    pub fn find_target(
+        &self,
+        target: &str,
+        build_type: &BuildType,
+    ) -> Result<CMakeTarget, CMakeError> {
+        let query = self
+            .api_directory()
+            .join("query")
+            .join(CLIENT_NAME)
+            .join(CODEMODEL_QUERY);
+        if !query.is_file() {
+            crate::utility::create_dir(query.parent().unwrap())?;
+            crate::utility::create_file(&query)?;
+        }
+        let reply_directory = self.api_directory().join("reply");
+        let codemodel = match latest_codemodel_reply(&reply_directory)? {
+            Some(codemodel) => codemodel,
+            None => {
+                self.configure()?;
+                latest_codemodel_reply(&reply_directory)?
+                    .ok_or_else(|| CMakeError::NoReply(reply_directory.clone()))?
+            }
+        };
+        let target = read_target(&reply_directory, &codemodel, target, build_type)?;
+        if let Some(missing) = target.libraries.iter().find(|library| !library.exists()) {
+            return Err(CMakeError::MissingArtifact(
+                target.cmake_target,
+                missing.to_path_buf(),
+            ));
+        }
+        Ok(target)
+    }
+
+    fn configure(&self) -> Result<(), CMakeError> {
+        let mut search_options = FindProgramOptions::new();
+        search_options.with_path_env();
+        let cmake = find_program(Path::new("cmake"), search_options)
+            .ok_or(CMakeError::CouldNotFindCMake)?;
+        log::debug!(
+            "Running {} to answer the file API query in {}",
+            cmake.display(),
+            self.build_directory.display()
+        );
+        let output = Environment::new()
+            .command(&cmake)
+            .arg(&self.build_directory)
+            .output()
+            .map_err(CMakeError::FailedToRunCMake)?;
+        if !output.status.success() {
+            return Err(CMakeError::CMakeFailedWithError(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Index {
+    reply: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReplyFile {
+    #[serde(rename = "jsonFile")]
+    json_file: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Codemodel {
+    paths: CodemodelPaths,
+    configurations: Vec<Configuration>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CodemodelPaths {
+    source: PathBuf,
+    build: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct Configuration {
+    name: String,
+    targets: Vec<TargetReference>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetReference {
+    name: String,
+    id: String,
+    #[serde(rename = "jsonFile")]
+    json_file: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetReply {
+    #[serde(rename = "type")]
+    target_type: String,
+    #[serde(default)]
+    artifacts: Vec<Artifact>,
+    #[serde(default, rename = "compileGroups")]
+    compile_groups: Vec<CompileGroup>,
+    #[serde(default)]
+    dependencies: Vec<TargetDependency>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artifact {
+    path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompileGroup {
+    #[serde(default)]
+    includes: Vec<Include>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Include {
+    path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetDependency {
+    id: String,
+}
+
+fn is_library(target_type: &str) -> bool {
+    matches!(target_type, "STATIC_LIBRARY" | "SHARED_LIBRARY")
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, CMakeError> {
+    let contents = crate::utility::read_file(path)?;
+    serde_json::from_str(&contents)
+        .map_err(|e| CMakeError::FailedToParseReply(path.to_path_buf(), e))
+}
+
+// Codemodel of the latest index in `reply_directory` answering the query of yambs. The names of
+// the index files sort in the order CMake wrote them.
+fn latest_codemodel_reply(reply_directory: &Path) -> Result<Option<Codemodel>, CMakeError> {
+    let latest_index = std::fs::read_dir(reply_directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with("index-") && name.ends_with(".json"))
+                })
+                .max()
+        })
+        .ok()
+        .flatten();
+    let index_path = match latest_index {
+        Some(index_path) => index_path,
+        None => return Ok(None),
+    };
+    let index = read_json::<Index>(&index_path)?;
+    let codemodel_file = index
+        .reply
+        .get(CLIENT_NAME)
+        .and_then(|client| client.get(CODEMODEL_QUERY))
+        .and_then(|reply| serde_json::from_value::<ReplyFile>(reply.clone()).ok());
+    match codemodel_file {
+        Some(codemodel_file) => Ok(Some(read_json(
+            &reply_directory.join(codemodel_file.json_file),
+        )?)),
+        None => Ok(None),
+    }
+}
+
+// Library `target` of `codemodel`, with the include directories it is compiled with and the
+// libraries of the project it depends on.
+fn read_target(
+    reply_directory: &Path,
+    codemodel: &Codemodel,
+    target: &str,
+    build_type: &BuildType,
+) -> Result<CMakeTarget, CMakeError> {
+    let build_type_name = build_type.to_string();
+    let configuration = codemodel
+        .configurations
+        .iter()
+        .find(|configuration| configuration.name.eq_ignore_ascii_case(&build_type_name))
+        .or_else(|| codemodel.configurations.first())
+        .ok_or_else(|| CMakeError::NoReply(reply_directory.to_path_buf()))?;
+    let read_target_reply = |reference: &TargetReference| -> Result<TargetReply, CMakeError> {
+        read_json(&reply_directory.join(&reference.json_file))
+    };
+    let reference = configuration
+        .targets
+        .iter()
+        .find(|reference| reference.name == target)
+        .ok_or_else(|| CMakeError::NoTargetWithName {
+            target: target.to_string(),
+            available: configuration
+                .targets
+                .iter()
+                .filter(|reference| {
+                    read_target_reply(reference)
+                        .map(|reply| is_library(&reply.target_type))
+                        .unwrap_or(false)
+                })
+                .map(|reference| reference.name.clone())
+                .collect(),
+        })?;
+    let reply = read_target_reply(reference)?;
+    if !is_library(&reply.target_type) {
+        return Err(CMakeError::NotALibrary(
+            target.to_string(),
+            reply.target_type.to_lowercase().replace('_', " "),
+        ));
+    }
+
+    let mut include_directories = IncludeDirectories::new();
+    for include in reply
+        .compile_groups
+        .iter()
+        .flat_map(|compile_group| &compile_group.includes)
+    {
+        // Headers of a project built by CMake are treated as third party code, like those of
+        // pkg-config packages, so that their warnings do not show up in ours.
+        include_directories.add(IncludeDirectory {
+            include_type: IncludeType::System,
+            path: codemodel.paths.source.join(&include.path),
+        });
+    }
+
+    let mut libraries = Vec::new();
+    let mut visited = vec![reference.id.clone()];
+    let mut pending = vec![reply];
+    while let Some(reply) = pending.pop() {
+        if is_library(&reply.target_type) {
+            for artifact in &reply.artifacts {
+                let library = codemodel.paths.build.join(&artifact.path);
+                if !libraries.contains(&library) {
+                    libraries.push(library);
+                }
+            }
+        }
+        for dependency in &reply.dependencies {
+            if visited.contains(&dependency.id) {
+                continue;
+            }
+            visited.push(dependency.id.clone());
+            if let Some(reference) = configuration
+                .targets
+                .iter()
+                .find(|reference| reference.id == dependency.id)
+            {
+                pending.push(read_target_reply(reference)?);
+            }
+        }
+    }
+
+    Ok(CMakeTarget {
+        cmake_target: target.to_string(),
+        build_directory: codemodel.paths.build.clone(),
+        include_directories,
+        libraries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn write_reply(reply_directory: &Path, file: &str, json: serde_json::Value) {
+        std::fs::write(reply_directory.join(file), json.to_string()).unwrap();
+    }
+
+    fn write_project_reply(build_directory: &Path) -> PathBuf {
+        let reply_directory = build_directory.join(".cmake/api/v1/reply");
+        std::fs::create_dir_all(&reply_directory).unwrap();
+        write_reply(
+            &reply_directory,
+            "index-2024-01-01T00-00-00-0000.json",
+            serde_json::json!({
+                "reply": {
+                    "client-yambs": {
+                        "codemodel-v2": { "jsonFile": "codemodel-v2-1.json" }
+                    }
+                }
+            }),
+        );
+        write_reply(
+            &reply_directory,
+            "codemodel-v2-1.json",
+            serde_json::json!({
+                "paths": { "source": "/project", "build": build_directory },
+                "configurations": [{
+                    "name": "Debug",
+                    "targets": [
+                        { "name": "core", "id": "core::@1", "jsonFile": "target-core.json" },
+                        { "name": "util", "id": "util::@1", "jsonFile": "target-util.json" },
+                        { "name": "tool", "id": "tool::@1", "jsonFile": "target-tool.json" }
+                    ]
+                }]
+            }),
+        );
+        write_reply(
+            &reply_directory,
+            "target-core.json",
+            serde_json::json!({
+                "type": "STATIC_LIBRARY",
+                "artifacts": [{ "path": "libcore.a" }],
+                "compileGroups": [{
+                    "includes": [{ "path": "include" }, { "path": "/opt/include" }]
+                }],
+                "dependencies": [{ "id": "util::@1" }]
+            }),
+        );
+        write_reply(
+            &reply_directory,
+            "target-util.json",
+            serde_json::json!({
+                "type": "STATIC_LIBRARY",
+                "artifacts": [{ "path": "util/libutil.a" }]
+            }),
+        );
+        write_reply(
+            &reply_directory,
+            "target-tool.json",
+            serde_json::json!({
+                "type": "EXECUTABLE",
+                "artifacts": [{ "path": "tool" }],
+                "dependencies": [{ "id": "core::@1" }]
+            }),
+        );
+        reply_directory
+    }
+
+    #[test]
+    fn read_target_finds_includes_and_libraries_of_dependencies() {
+        let tempdir = tempdir::TempDir::new("cmake").unwrap();
+        let build_directory = tempdir.path();
+        let reply_directory = write_project_reply(build_directory);
+        let codemodel = latest_codemodel_reply(&reply_directory).unwrap().unwrap();
+
+        let target = read_target(&reply_directory, &codemodel, "core", &BuildType::Debug).unwrap();
+        let mut include_directories = IncludeDirectories::new();
+        for path in ["/project/include", "/opt/include"] {
+            include_directories.add(IncludeDirectory {
+                include_type: IncludeType::System,
+                path: PathBuf::from(path),
+            });
+        }
+        assert_eq!(target.include_directories, include_directories);
+        assert_eq!(
+            target.libraries,
+            vec![
+                build_directory.join("libcore.a"),
+                build_directory.join("util/libutil.a")
+            ]
+        );
+    }
+
+    #[test]
+    fn read_target_rejects_executables_and_lists_libraries_of_unknown_target() {
+        let tempdir = tempdir::TempDir::new("cmake").unwrap();
+        let reply_directory = write_project_reply(tempdir.path());
+        let codemodel = latest_codemodel_reply(&reply_directory).unwrap().unwrap();
+
+        assert!(matches!(
+            read_target(&reply_directory, &codemodel, "tool", &BuildType::Debug),
+            Err(CMakeError::NotALibrary(..))
+        ));
+        assert_eq!(
+            read_target(&reply_directory, &codemodel, "cor", &BuildType::Debug)
+                .unwrap_err()
+                .to_string(),
+            "CMake project has no target cor. Available libraries are: core, util"
+        );
+    }
+}
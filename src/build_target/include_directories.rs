@@ -6,6 +6,11 @@ pub enum IncludeDirectoriesError {
     CouldNotFindIncludeDirectory(std::path::PathBuf),
 }
 
+/// Ordered, deduplicated set of include directories.
+///
+/// Directories are kept in the order they were added, except that system include directories
+/// always come after regular ones. Callers should add directories in order of precedence:
+/// target-local directories first, then dependency directories, then system directories.
 #[derive(Debug, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(transparent)]
 pub struct IncludeDirectories(Vec<IncludeDirectory>);
@@ -19,13 +24,14 @@ pub struct IncludeDirectory {
 impl IncludeDirectory {
     pub fn from_str(s: &str) -> Option<Self> {
         lazy_static::lazy_static! {
-            static ref INCLUDE_PATH_REGEX: Regex = Regex::new("(?P<type>(-I|-isystem))\\s*?(?P<path>.*)$").unwrap();
+            static ref INCLUDE_PATH_REGEX: Regex = Regex::new("(?P<type>(-I|-isystem|-iquote))\\s*?(?P<path>.*)$").unwrap();
         }
         if let Some(captures) = INCLUDE_PATH_REGEX.captures(s) {
             let include_type = if let Some(ty) = captures.name("type") {
                 match ty.as_str() {
                     "-I" => IncludeType::Include,
                     "-isystem" => IncludeType::System,
+                    "-iquote" => IncludeType::Quote,
                     _ => IncludeType::Include,
                 }
             } else {
@@ -42,10 +48,22 @@ impl IncludeDirectory {
     }
 
     pub fn as_include_flag(&self) -> String {
-        if self.include_type == IncludeType::System {
-            format!("-isystem {}", self.path.display())
-        } else {
-            format!("-I{}", self.path.display())
+        match self.include_type {
+            IncludeType::System => format!("-isystem {}", self.path.display()),
+            IncludeType::Quote => format!("-iquote {}", self.path.display()),
+            IncludeType::Include => format!("-I{}", self.path.display()),
+        }
+    }
+
+    /// The directory as the targets depending on its target search it. Quote directories are
+    /// only searched by quote-includes of the target itself, so dependents search them with -I.
+    pub fn for_dependents(&self) -> Self {
+        match self.include_type {
+            IncludeType::Quote => Self {
+                include_type: IncludeType::Include,
+                path: self.path.clone(),
+            },
+            _ => self.clone(),
         }
     }
 }
@@ -55,12 +73,37 @@ impl IncludeDirectories {
         Self(Vec::new())
     }
 
+    /// Adds an include directory, unless a directory with the same path already exists.
+    /// The first addition of a path wins, which keeps the precedence of earlier additions.
     pub fn add(&mut self, include_directory: IncludeDirectory) {
-        if !self.0.contains(&include_directory) {
-            self.0.push(include_directory)
+        if self.0.iter().any(|i| i.path == include_directory.path) {
+            return;
+        }
+        match include_directory.include_type {
+            IncludeType::System => self.0.push(include_directory),
+            IncludeType::Include | IncludeType::Quote => {
+                let first_system = self
+                    .0
+                    .iter()
+                    .position(|i| i.include_type == IncludeType::System)
+                    .unwrap_or(self.0.len());
+                self.0.insert(first_system, include_directory);
+            }
         }
     }
 
+    /// The include directories, all included as `include_type`.
+    pub fn with_include_type(&self, include_type: &IncludeType) -> Self {
+        let mut include_directories = Self::new();
+        for include_directory in &self.0 {
+            include_directories.add(IncludeDirectory {
+                include_type: include_type.clone(),
+                path: include_directory.path.clone(),
+            });
+        }
+        include_directories
+    }
+
     pub fn iter(&self) -> std::slice::Iter<'_, IncludeDirectory> {
         self.0.iter()
     }
@@ -88,6 +131,8 @@ impl<'a> std::iter::IntoIterator for &'a IncludeDirectories {
 pub enum IncludeType {
     Include,
     System,
+    /// Searched by quote-includes only, with -iquote.
+    Quote,
 }
 
 #[cfg(test)]
@@ -109,6 +154,65 @@ mod tests {
         );
     }
 
+    fn include(path: &str) -> IncludeDirectory {
+        IncludeDirectory {
+            include_type: IncludeType::Include,
+            path: PathBuf::from(path),
+        }
+    }
+
+    fn system(path: &str) -> IncludeDirectory {
+        IncludeDirectory {
+            include_type: IncludeType::System,
+            path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn add_ignores_duplicate_paths() {
+        let mut include_directories = IncludeDirectories::new();
+        include_directories.add(include("/target/include"));
+        include_directories.add(include("/dependency/include"));
+        include_directories.add(include("/target/include"));
+        include_directories.add(system("/dependency/include"));
+
+        assert_eq!(
+            include_directories
+                .iter()
+                .collect::<Vec<&IncludeDirectory>>(),
+            vec![&include("/target/include"), &include("/dependency/include")]
+        );
+    }
+
+    #[test]
+    fn add_places_system_include_directories_last() {
+        let mut include_directories = IncludeDirectories::new();
+        include_directories.add(include("/target/include"));
+        include_directories.add(system("/usr/include/third-party"));
+        include_directories.add(include("/dependency/include"));
+        include_directories.add(system("/opt/include"));
+
+        assert_eq!(
+            include_directories
+                .iter()
+                .collect::<Vec<&IncludeDirectory>>(),
+            vec![
+                &include("/target/include"),
+                &include("/dependency/include"),
+                &system("/usr/include/third-party"),
+                &system("/opt/include"),
+            ]
+        );
+    }
+
+    #[test]
+    fn quote_include_directory_is_searched_with_i_by_dependents() {
+        let quote = IncludeDirectory::from_str("-iquote/target/include").unwrap();
+        assert_eq!(quote.include_type, IncludeType::Quote);
+        assert_eq!(quote.as_include_flag(), "-iquote /target/include");
+        assert_eq!(quote.for_dependents(), include("/target/include"));
+    }
+
     #[test]
     fn from_str_parses_isystem_include() {
         let input = "-isystem/some/include/path";
@@ -10,22 +10,40 @@ use crate::parser;
 use crate::parser::types;
 use crate::targets;
 use crate::toolchain::NormalizedToolchain;
-use crate::YAMBS_MANIFEST_NAME;
 
 pub mod associated_files;
+pub mod cmake;
 pub mod include_directories;
 pub mod pkg_config;
 pub mod target_registry;
-use associated_files::SourceFiles;
+pub mod yambs_build;
+use associated_files::{FileExtensions, SourceFile, SourceFiles};
+use cmake::{CMakeError, CMakeProject, CMakeTarget};
+use include_directories::IncludeDirectories;
 use include_directories::IncludeDirectory;
 use include_directories::IncludeType;
 use pkg_config::{PkgConfigError, PkgConfigTarget};
+use yambs_build::{YambsBuild, YambsBuildError, YambsBuildTarget};
+
+/// Directory, relative to the output directory of a target, where outputs of custom commands
+/// are placed.
+pub const GENERATED_DIRECTORY_NAME: &str = "generated";
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct DependencySourceData {
     pub manifest: manifest::Manifest,
     pub library: PrintableLibrary,
-    pub include_directory: IncludeDirectory,
+    pub include_directories: Vec<IncludeDirectory>,
+    pub link_options: types::LinkOptions,
+    /// Headers generated by the custom commands and embedded files of the library and of the
+    /// libraries it depends on, relative to the directory the build files of the targets are in.
+    /// Dependents are compiled after they are generated.
+    #[serde(default)]
+    pub generated_headers: Vec<std::path::PathBuf>,
+    /// System libraries dependents link for the library when it is static, as an archive does not
+    /// record them: its own and those of the static libraries it depends on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub system_libraries: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -40,6 +58,8 @@ pub enum DependencySource {
     FromSource(DependencySourceData),
     FromHeaderOnly(HeaderOnlyData),
     FromPkgConfig(PkgConfigTarget),
+    FromCMake(CMakeTarget),
+    FromYambsBuild(YambsBuildTarget),
 }
 
 impl DependencySource {
@@ -49,6 +69,17 @@ impl DependencySource {
             _ => None,
         }
     }
+
+    /// Name of the library or package the dependency provides.
+    pub fn name(&self) -> String {
+        match self {
+            Self::FromSource(s) => s.library.name.clone(),
+            Self::FromHeaderOnly(h) => h.name.clone(),
+            Self::FromPkgConfig(pkg) => pkg.target.clone(),
+            Self::FromCMake(cmake) => cmake.cmake_target.clone(),
+            Self::FromYambsBuild(yambs_build) => yambs_build.yambs_target.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -81,38 +112,81 @@ pub struct SourceBuildData {
 pub struct BuildTarget {
     pub state: TargetState,
     pub target_type: TargetType,
-    pub include_directory: IncludeDirectory,
+    /// Include directories of the layout of the project of the target, which are used by the
+    /// targets depending on it as well.
+    pub project_include_directories: Vec<IncludeDirectory>,
     pub compiler_flags: CompilerFlags,
     pub manifest: manifest::Manifest,
     pub dependencies: Vec<Dependency>,
     pub source_files: SourceFiles,
     pub defines: Vec<types::Define>,
+    pub custom_commands: Vec<targets::CustomCommand>,
+    /// Context the target is built in. The same target may be registered once for each context.
+    pub build_for: types::BuildFor,
+    /// Files made from the output of an executable after it is linked.
+    #[serde(default)]
+    pub post_link: Vec<types::PostLinkArtifact>,
+    /// Files compiled into the target, see `embed`.
+    #[serde(default)]
+    pub embed: Vec<std::path::PathBuf>,
+    /// Whether the target is an example, which is only built through the examples target.
+    #[serde(default)]
+    pub example: bool,
+    /// Standard the target is compiled with in place of the standard of the build, if its own
+    /// flags set one with `-std=`.
+    #[serde(default)]
+    pub standard: Option<types::Standard>,
+    /// Extensions of sources and headers its project recognizes, which outputs of its custom
+    /// commands are classified by as well.
+    #[serde(default)]
+    pub file_extensions: FileExtensions,
+    /// Version script a shared library is linked with, which it is linked again after changes to.
+    #[serde(default)]
+    pub version_script: Option<std::path::PathBuf>,
+    /// Version of a shared library, which it is named by, with the major version as soname.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Files besides the sources the objects and output of the target are made again after
+    /// changes to.
+    #[serde(default)]
+    pub extra_files: Vec<std::path::PathBuf>,
 }
 
 impl BuildTarget {
+    /// Registers `target` and its dependencies. `build_for` is the context asked for by the
+    /// dependent of the target, if any. A target declared to be built for the host is always built
+    /// for the host, and dependencies are built in the same context as their dependent.
     pub fn target_node_from_source(
-        manifest_dir_path: &std::path::Path,
+        manifest: &manifest::ParsedManifest,
         target: &targets::Target,
+        build_for: types::BuildFor,
         registry: &mut target_registry::TargetRegistry,
         toolchain: &Rc<RefCell<NormalizedToolchain>>,
         build_type: &BuildType,
     ) -> Result<TargetNode, TargetError> {
+        let manifest_dir_path = manifest.manifest.directory.as_path();
         let target_type = TargetType::new(target);
+        let build_for = toolchain
+            .borrow()
+            .resolve_build_for(match target.build_for() {
+                types::BuildFor::Host => types::BuildFor::Host,
+                types::BuildFor::Target => build_for,
+            });
 
         if let Some(existing_node) = registry.get_target_from_predicate(|build_target| {
             build_target.manifest.directory == manifest_dir_path
                 && build_target.target_type == target_type
+                && build_target.build_for == build_for
         }) {
             return Ok(existing_node);
         }
 
         let target_node = match target {
             targets::Target::Executable(executable) => TargetNode::new(
-                BuildTarget::executable_from_source(manifest_dir_path, executable)?,
+                BuildTarget::executable_from_source(manifest, executable, build_for)?,
             ),
             targets::Target::Library(library) => TargetNode::new(BuildTarget::library_from_source(
-                manifest_dir_path,
-                library,
+                manifest, library, build_for,
             )?),
         };
 
@@ -122,9 +196,18 @@ impl BuildTarget {
         );
         registry.add_target(target_node.clone());
         target_node.borrow_mut().state = TargetState::InProcess;
-        let target_vec = target_node
-            .borrow()
-            .detect_target(registry, target, toolchain, build_type)?;
+        let third_party_as_system = manifest
+            .data
+            .project_config
+            .as_ref()
+            .and_then(|project_config| project_config.third_party_as_system);
+        let target_vec = target_node.borrow().detect_target(
+            registry,
+            target,
+            toolchain,
+            build_type,
+            third_party_as_system,
+        )?;
 
         for target in target_vec {
             match target.source {
@@ -138,14 +221,92 @@ impl BuildTarget {
                 DependencySource::FromHeaderOnly(ref h) => {
                     log::debug!("Registering header only target \"{}\"", h.name);
                 }
+                DependencySource::FromCMake(ref c) => {
+                    log::debug!("Registering CMake target \"{}\"", c.cmake_target);
+                }
+                DependencySource::FromYambsBuild(ref y) => {
+                    log::debug!(
+                        "Registering target \"{}\" of build directory {}",
+                        y.yambs_target,
+                        y.build_directory.display()
+                    );
+                }
                 _ => {}
             }
             target_node.borrow_mut().add_target(target);
         }
+        BuildTarget::register_custom_command_tools(&target_node, registry, toolchain, build_type)?;
         target_node.borrow_mut().state = TargetState::Registered;
         Ok(target_node)
     }
 
+    // Executable targets run by custom commands have to be built before the commands run, so they
+    // are registered along with the target, even when not part of the main manifest.
+    fn register_custom_command_tools(
+        target_node: &TargetNode,
+        registry: &mut target_registry::TargetRegistry,
+        toolchain: &Rc<RefCell<NormalizedToolchain>>,
+        build_type: &BuildType,
+    ) -> Result<(), TargetError> {
+        let tool_build_for = toolchain.borrow().resolve_build_for(types::BuildFor::Host);
+        let (target_name, manifest_directory, tools) = {
+            let borrowed_target = target_node.borrow();
+            let tools = borrowed_target
+                .custom_commands
+                .iter()
+                .filter_map(|custom_command| custom_command.command.target())
+                .map(str::to_string)
+                .collect::<Vec<String>>();
+            (
+                borrowed_target.name(),
+                borrowed_target.manifest.directory.clone(),
+                tools,
+            )
+        };
+
+        for tool in tools {
+            if let Some(registered_tool) = registry.get_target_from_predicate(|build_target| {
+                build_target.manifest.directory == manifest_directory
+                    && build_target.is_executable()
+                    && build_target.name() == tool
+                    && build_target.build_for == tool_build_for
+            }) {
+                if registered_tool.borrow().state == TargetState::InProcess {
+                    return Err(TargetError::ToolCirculation(tool, target_name));
+                }
+                continue;
+            }
+
+            log::debug!(
+                "Registering executable \"{}\" used by custom command of \"{}\"",
+                tool,
+                target_name
+            );
+            let manifest = registry
+                .manifest(&manifest_directory)
+                .map_err(TargetError::Parse)?;
+            let tool_target = manifest
+                .data
+                .targets
+                .iter()
+                .find(|target| {
+                    target
+                        .executable()
+                        .is_some_and(|executable| executable.name == tool)
+                })
+                .ok_or_else(|| TargetError::NoExecutableWithName(tool.clone()))?;
+            BuildTarget::target_node_from_source(
+                &manifest,
+                tool_target,
+                types::BuildFor::Host,
+                registry,
+                toolchain,
+                build_type,
+            )?;
+        }
+        Ok(())
+    }
+
     pub fn is_executable(&self) -> bool {
         match self.target_type {
             TargetType::Executable(_) => true,
@@ -178,56 +339,325 @@ impl BuildTarget {
         }
     }
 
+    /// Source files of the target, including those produced by custom commands.
+    ///
+    /// Outputs of custom commands are placed in the `generated` directory of
+    /// `output_directory`. Outputs that are neither sources nor headers are left out.
+    pub fn source_files_with_generated(&self, output_directory: &std::path::Path) -> SourceFiles {
+        let mut source_files = self.source_files.clone();
+        let generated_directory = output_directory.join(GENERATED_DIRECTORY_NAME);
+        for output in self.custom_commands.iter().flat_map(|c| &c.outputs) {
+            if let Some(source_file) =
+                SourceFile::generated(&generated_directory.join(output), &self.file_extensions)
+            {
+                source_files.push(source_file);
+            }
+        }
+        if let Some((_, source)) = self.embedded_files(output_directory) {
+            if let Some(source_file) = SourceFile::generated(&source, &self.file_extensions) {
+                source_files.push(source_file);
+            }
+        }
+        source_files
+    }
+
+    /// Header and source generated from the embedded files of the target, in the generated
+    /// directory of `output_directory`. The source has the extension of the first source of the
+    /// target, so that it is compiled like the others.
+    pub fn embedded_files(
+        &self,
+        output_directory: &std::path::Path,
+    ) -> Option<(std::path::PathBuf, std::path::PathBuf)> {
+        if self.embed.is_empty() {
+            return None;
+        }
+        let name = self.name();
+        let header = crate::embed::header_file_name(&name);
+        let extension = self
+            .source_files
+            .iter()
+            .find(|file| file.is_source())
+            .and_then(|file| {
+                file.file()
+                    .extension()
+                    .map(|extension| extension.to_string_lossy().to_string())
+            })
+            .unwrap_or_else(|| "cpp".to_string());
+        let generated_directory = output_directory.join(GENERATED_DIRECTORY_NAME);
+        Some((
+            generated_directory.join(&header),
+            generated_directory.join(&header).with_extension(extension),
+        ))
+    }
+
+    /// Headers generated by the target and by the libraries it depends on, relative to the
+    /// directory the build files of the targets are in, where the target has a directory named
+    /// `<name>.dir` of its own.
+    pub fn generated_headers(&self) -> Vec<std::path::PathBuf> {
+        let output_directory = std::path::PathBuf::from(format!("{}.dir", self.name()));
+        let generated_directory = output_directory.join(GENERATED_DIRECTORY_NAME);
+        let mut headers = self
+            .custom_commands
+            .iter()
+            .flat_map(|custom_command| &custom_command.outputs)
+            .map(|output| generated_directory.join(output))
+            .filter(|output| {
+                SourceFile::generated(output, &self.file_extensions)
+                    .is_some_and(|source_file| source_file.is_header())
+            })
+            .chain(
+                self.embedded_files(&output_directory)
+                    .map(|(header, _)| header),
+            )
+            .collect::<Vec<std::path::PathBuf>>();
+        for header in self.dependency_generated_headers() {
+            if !headers.contains(&header) {
+                headers.push(header);
+            }
+        }
+        headers
+    }
+
+    /// Headers generated by the libraries the target depends on, which its sources may include
+    /// through the headers of the libraries. They are relative to the directory the build files
+    /// of the targets are in.
+    pub fn dependency_generated_headers(&self) -> Vec<std::path::PathBuf> {
+        let mut headers = Vec::new();
+        for dependency in &self.dependencies {
+            if let DependencySource::FromSource(ref s) = dependency.source {
+                for header in &s.generated_headers {
+                    if !headers.contains(header) {
+                        headers.push(header.clone());
+                    }
+                }
+            }
+        }
+        headers
+    }
+
+    /// Generated directories of the libraries the target depends on that generate headers,
+    /// relative to the directory the build files of the targets are in. Headers in subdirectories
+    /// of them, such as `sub/config.h`, are included by their path within the directory.
+    pub fn dependency_generated_directories(&self) -> Vec<std::path::PathBuf> {
+        let mut directories = Vec::new();
+        for header in self.dependency_generated_headers() {
+            // Generated headers are within <name>.dir/generated of the library generating them.
+            let directory = header.components().take(2).collect::<std::path::PathBuf>();
+            if !directories.contains(&directory) {
+                directories.push(directory);
+            }
+        }
+        directories
+    }
+
+    /// System libraries the dependents of the target link for it. Only static libraries have any,
+    /// as the libraries linked by executables and shared libraries are recorded in them.
+    pub fn system_libraries(&self) -> Vec<String> {
+        if self.library_type() != Some(LibraryType::Static) {
+            return Vec::new();
+        }
+        let mut libraries = self.compiler_flags.libs.clone();
+        for dependency in &self.dependencies {
+            if let DependencySource::FromSource(ref s) = dependency.source {
+                for library in &s.system_libraries {
+                    if !libraries.contains(library) {
+                        libraries.push(library.clone());
+                    }
+                }
+            }
+        }
+        libraries
+    }
+
+    /// Embedded files of the target, named by their paths relative to the manifest directory.
+    pub fn embedded_file_names(&self) -> Vec<crate::embed::EmbeddedFile> {
+        self.embed
+            .iter()
+            .map(|path| crate::embed::EmbeddedFile {
+                name: path
+                    .strip_prefix(&self.manifest.directory)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string(),
+                path: path.clone(),
+            })
+            .collect()
+    }
+
+    /// Include directories used to compile the sources of this target.
+    ///
+    /// Precedence is target-local directories (the target's include directory and
+    /// `append_include_directories`), then directories of dependencies in declaration order, then
+    /// system include directories.
+    pub fn include_directories(&self) -> IncludeDirectories {
+        let mut include_directories = IncludeDirectories::new();
+        for include_directory in &self.project_include_directories {
+            include_directories.add(include_directory.clone());
+        }
+        for path in &self.compiler_flags.include_directories {
+            include_directories.add(IncludeDirectory {
+                include_type: IncludeType::Include,
+                path: path.to_path_buf(),
+            });
+        }
+        for dependency in &self.dependencies {
+            match dependency.source {
+                DependencySource::FromSource(ref s) => {
+                    for include_directory in &s.include_directories {
+                        include_directories.add(include_directory.clone());
+                    }
+                }
+                DependencySource::FromHeaderOnly(ref h) => {
+                    include_directories.add(h.include_directory.clone());
+                }
+                DependencySource::FromPkgConfig(ref pkg) => {
+                    for include_directory in &pkg.include_directories {
+                        include_directories.add(include_directory.clone());
+                    }
+                }
+                DependencySource::FromCMake(ref cmake) => {
+                    for include_directory in &cmake.include_directories {
+                        include_directories.add(include_directory.clone());
+                    }
+                }
+                DependencySource::FromYambsBuild(ref yambs_build) => {
+                    for include_directory in &yambs_build.include_directories {
+                        include_directories.add(include_directory.clone());
+                    }
+                }
+            }
+        }
+        for path in &self.compiler_flags.system_include_directories {
+            include_directories.add(IncludeDirectory {
+                include_type: IncludeType::System,
+                path: path.to_path_buf(),
+            });
+        }
+        include_directories
+    }
+
+    // Include directories of the layout that exist. Without any, the first of the layout is used,
+    // as headers may still be placed there. With `quote_includes`, the target searches them with
+    // -iquote.
+    fn project_include_directories(manifest: &manifest::ParsedManifest) -> Vec<IncludeDirectory> {
+        let include_type = if manifest
+            .data
+            .project_config
+            .as_ref()
+            .and_then(|project_config| project_config.quote_includes)
+            .unwrap_or(false)
+        {
+            IncludeType::Quote
+        } else {
+            IncludeType::Include
+        };
+        let layout = manifest.data.layout();
+        let manifest_directory = &manifest.manifest.directory;
+        let mut paths = layout.include_directories(manifest_directory);
+        if paths.is_empty() {
+            paths.extend(
+                layout
+                    .include_dirs
+                    .first()
+                    .map(|include_dir| layout.project_root(manifest_directory).join(include_dir)),
+            );
+        }
+        paths
+            .into_iter()
+            .map(|path| IncludeDirectory {
+                include_type: include_type.clone(),
+                path,
+            })
+            .collect()
+    }
+
     fn executable_from_source(
-        manifest_dir_path: &std::path::Path,
+        manifest: &manifest::ParsedManifest,
         executable: &targets::Executable,
+        build_for: types::BuildFor,
     ) -> Result<Self, TargetError> {
         let source_files = executable.sources.clone();
+        let file_extensions = manifest.data.file_extensions();
 
         Ok(Self {
             state: TargetState::NotInProcess,
             target_type: TargetType::Executable(PrintableExecutable(executable.name.to_string())),
-            include_directory: include_directories::IncludeDirectory {
-                include_type: include_directories::IncludeType::Include,
-                path: manifest_dir_path.to_path_buf().join("include"),
-            },
+            project_include_directories: BuildTarget::project_include_directories(manifest),
             compiler_flags: executable.compiler_flags.clone(),
-            manifest: manifest::Manifest::new(manifest_dir_path),
+            manifest: manifest.manifest.clone(),
             dependencies: Vec::new(),
-            source_files: SourceFiles::from_paths(&source_files)
+            source_files: SourceFiles::from_paths(&source_files, &file_extensions)
                 .map_err(TargetError::AssociatedFile)?,
             defines: executable.defines.clone(),
+            custom_commands: executable.custom_commands.clone(),
+            build_for,
+            post_link: executable.post_link.clone(),
+            embed: executable.embed.clone(),
+            example: executable.example,
+            standard: BuildTarget::compiled_standard(&executable.compiler_flags),
+            file_extensions,
+            version_script: None,
+            version: None,
+            extra_files: executable.extra_files.clone(),
         })
     }
 
     fn library_from_source(
-        manifest_dir_path: &std::path::Path,
+        manifest: &manifest::ParsedManifest,
         library: &targets::Library,
+        build_for: types::BuildFor,
     ) -> Result<Self, TargetError> {
         let source_files = library.sources.clone();
+        let file_extensions = manifest.data.file_extensions();
 
         Ok(Self {
             state: TargetState::NotInProcess,
             target_type: TargetType::Library(PrintableLibrary::from(library)),
-            include_directory: include_directories::IncludeDirectory {
-                include_type: include_directories::IncludeType::Include,
-                path: manifest_dir_path.to_path_buf().join("include"),
-            },
+            project_include_directories: BuildTarget::project_include_directories(manifest),
             compiler_flags: library.compiler_flags.clone(),
-            manifest: manifest::Manifest::new(manifest_dir_path),
+            manifest: manifest.manifest.clone(),
             dependencies: Vec::new(),
-            source_files: SourceFiles::from_paths(&source_files)
+            source_files: SourceFiles::from_paths(&source_files, &file_extensions)
                 .map_err(TargetError::AssociatedFile)?,
             defines: library.defines.clone(),
+            custom_commands: library.custom_commands.clone(),
+            build_for,
+            post_link: Vec::new(),
+            embed: library.embed.clone(),
+            example: false,
+            standard: BuildTarget::compiled_standard(&library.compiler_flags),
+            file_extensions,
+            version_script: library.version_script.clone(),
+            version: library.version.clone(),
+            extra_files: library.extra_files.clone(),
         })
     }
 
+    // The last `-std=` of the flags of the target, such as one of the `[defaults]` above it, which
+    // comes after the standard of the build on the command lines compiling its sources.
+    fn compiled_standard(compiler_flags: &CompilerFlags) -> Option<types::Standard> {
+        compiler_flags
+            .cxx_flags
+            .iter()
+            .flat_map(|flags| flags.flags())
+            .chain(
+                compiler_flags
+                    .c_flags
+                    .iter()
+                    .flat_map(|flags| flags.flags()),
+            )
+            .rev()
+            .filter_map(|flag| flag.strip_prefix("-std="))
+            .find_map(|standard| types::Standard::parse(standard).ok())
+    }
+
     fn detect_target(
         &self,
         registry: &mut target_registry::TargetRegistry,
         target: &targets::Target,
         toolchain: &Rc<RefCell<NormalizedToolchain>>,
         build_type: &BuildType,
+        third_party_as_system: Option<bool>,
     ) -> Result<Vec<Dependency>, TargetError> {
         log::debug!(
             "Checking if target \"{}\" has registered dependencies",
@@ -235,12 +665,55 @@ impl BuildTarget {
         );
         let mut target_vec = Vec::new();
         for dependency in target.dependencies() {
-            match dependency.data {
+            let include_type =
+                dependency_include_type(dependency.data.origin(), third_party_as_system);
+            // Include directories of a dependency built from source, which are its
+            // project include directories.
+            let source_include_directories = |dependency: &TargetNode| {
+                dependency
+                    .borrow()
+                    .project_include_directories
+                    .iter()
+                    .map(|include_directory| IncludeDirectory {
+                        include_type: include_type
+                            .clone()
+                            .unwrap_or_else(|| include_directory.for_dependents().include_type),
+                        path: include_directory.path.clone(),
+                    })
+                    .collect::<Vec<IncludeDirectory>>()
+            };
+            // Archives are built from the directory they are unpacked into, as dependencies
+            // from source are.
+            let unpacked_archive;
+            let dependency_data = match dependency.data {
+                types::DependencyData::Archive(ref archive_data) => {
+                    let archive_directory = registry
+                        .archive_directory()
+                        .ok_or_else(|| TargetError::ArchiveNotUnpacked(dependency.name.clone()))?;
+                    let path = archive_directory.fetch(archive_data).map_err(|e| {
+                        targets::DependencyError::Archive(dependency.name.clone(), Box::new(e))
+                    })?;
+                    unpacked_archive = targets::Dependency::from_source(
+                        &dependency.name,
+                        &types::SourceData {
+                            path,
+                            origin: archive_data.origin,
+                            link_options: archive_data.link_options.clone(),
+                        },
+                        &self.manifest.directory,
+                    )?
+                    .data;
+                    &unpacked_archive
+                }
+                ref data => data,
+            };
+            match *dependency_data {
                 types::DependencyData::Source(ref dependency_source_data) => {
                     if let Some(registered_dep) =
                         registry.get_target_from_predicate(|build_target| {
                             build_target.manifest.directory == dependency_source_data.path
                                 && build_target.name() == dependency.name
+                                && build_target.build_for == self.build_for
                         })
                     {
                         log::debug!(
@@ -261,10 +734,10 @@ impl BuildTarget {
                                     )?,
                                 },
                                 manifest: borrowed_dep.manifest.clone(),
-                                include_directory: registered_dep
-                                    .borrow()
-                                    .include_directory
-                                    .clone(),
+                                include_directories: source_include_directories(&registered_dep),
+                                link_options: dependency_source_data.link_options.clone(),
+                                generated_headers: borrowed_dep.generated_headers(),
+                                system_libraries: borrowed_dep.system_libraries(),
                             });
                         let dependency = Dependency {
                             source: dependency_source,
@@ -274,8 +747,9 @@ impl BuildTarget {
                         log::debug!(
                             "No registered dependency found. Creating dependency build target."
                         );
-                        let manifest_path = dependency_source_data.path.join(YAMBS_MANIFEST_NAME);
-                        let manifest = parser::parse(&manifest_path).map_err(TargetError::Parse)?;
+                        let manifest = registry
+                            .manifest(&dependency_source_data.path)
+                            .map_err(TargetError::Parse)?;
                         let dep_target = manifest
                             .data
                             .targets
@@ -292,8 +766,9 @@ impl BuildTarget {
                                 TargetError::NoLibraryWithName(dependency.name.clone())
                             })?;
                         let target = BuildTarget::target_node_from_source(
-                            &dependency_source_data.path,
+                            &manifest,
                             dep_target,
+                            self.build_for,
                             registry,
                             toolchain,
                             build_type,
@@ -308,19 +783,26 @@ impl BuildTarget {
                                     })?,
                                 },
                                 manifest: borrowed_target.manifest.clone(),
-                                include_directory: target.borrow().include_directory.clone(),
+                                include_directories: source_include_directories(&target),
+                                link_options: dependency_source_data.link_options.clone(),
+                                generated_headers: borrowed_target.generated_headers(),
+                                system_libraries: borrowed_target.system_libraries(),
                             });
                         target_vec.push(Dependency {
                             source: dependency_source,
                         });
                     }
                 }
+                // Archives are replaced by the directory they are unpacked into above.
+                types::DependencyData::Archive(_) => {
+                    return Err(TargetError::ArchiveNotUnpacked(dependency.name.clone()));
+                }
                 types::DependencyData::HeaderOnly(ref header_only_data) => {
                     let header_only = HeaderOnlyData {
                         name: dependency.name.to_string(),
                         include_directory: IncludeDirectory {
                             path: header_only_data.include_directory.clone(),
-                            include_type: IncludeType::System,
+                            include_type: include_type.clone().unwrap_or(IncludeType::System),
                         },
                     };
                     let header_only = DependencySource::FromHeaderOnly(header_only);
@@ -328,12 +810,70 @@ impl BuildTarget {
                         source: header_only,
                     });
                 }
+                types::DependencyData::CMake(ref cmake_data) => {
+                    let cmake_target = cmake_data.target.as_deref().unwrap_or(&dependency.name);
+                    let mut cmake_target = CMakeProject::new(&cmake_data.build_directory)
+                        .and_then(|project| project.find_target(cmake_target, build_type))
+                        .map_err(|e| {
+                            TargetError::CouldNotImportCMakeTarget(cmake_target.to_string(), e)
+                        })?;
+                    if let Some(ref include_type) = include_type {
+                        cmake_target.include_directories = cmake_target
+                            .include_directories
+                            .with_include_type(include_type);
+                    }
+                    target_vec.push(Dependency {
+                        source: DependencySource::FromCMake(cmake_target),
+                    });
+                }
+                types::DependencyData::YambsBuild(ref yambs_build_data) => {
+                    let output_extensions = {
+                        let toolchain = toolchain.borrow();
+                        match toolchain.host {
+                            Some(ref host) if self.build_for == types::BuildFor::Host => {
+                                host.borrow().output_extensions.clone()
+                            }
+                            _ => toolchain.output_extensions.clone(),
+                        }
+                    };
+                    let mut yambs_build_target = YambsBuild::new(&yambs_build_data.build_directory)
+                        .and_then(|yambs_build| {
+                            yambs_build.find_library(
+                                &dependency.name,
+                                build_type,
+                                self.build_for,
+                                &output_extensions,
+                            )
+                        })
+                        .map_err(|e| {
+                            TargetError::CouldNotImportYambsBuildTarget(dependency.name.clone(), e)
+                        })?;
+                    if let Some(ref include_type) = include_type {
+                        yambs_build_target.include_directories = yambs_build_target
+                            .include_directories
+                            .with_include_type(include_type);
+                    }
+                    target_vec.push(Dependency {
+                        source: DependencySource::FromYambsBuild(yambs_build_target),
+                    });
+                }
                 types::DependencyData::PkgConfig(ref pkg_config_data) => {
                     let mut toolchain_lock = toolchain.borrow_mut();
                     if let Some(ref mut pkg_config) = toolchain_lock.pkg_config {
-                        pkg_config.add_search_path(&pkg_config_data.search_dir);
-                        match pkg_config.find_target(&dependency.name) {
-                            Ok(pkg_config_target) => {
+                        if let Some(ref search_dir) = pkg_config_data.search_dir {
+                            pkg_config.add_search_path(search_dir);
+                        }
+                        let package = pkg_config_data
+                            .package
+                            .as_deref()
+                            .unwrap_or(&dependency.name);
+                        match pkg_config.find_target(package, &pkg_config_data.components) {
+                            Ok(mut pkg_config_target) => {
+                                if let Some(ref include_type) = include_type {
+                                    pkg_config_target.include_directories = pkg_config_target
+                                        .include_directories
+                                        .with_include_type(include_type);
+                                }
                                 let pkg_config_dep =
                                     DependencySource::FromPkgConfig(pkg_config_target);
                                 target_vec.push(Dependency {
@@ -342,7 +882,7 @@ impl BuildTarget {
                             }
                             Err(e) => {
                                 return Err(TargetError::CouldNotFindPkgConfigPackage(
-                                    dependency.name.clone(),
+                                    package.to_string(),
                                     e,
                                 ))
                             }
@@ -378,6 +918,25 @@ impl BuildTarget {
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct TargetNode(Rc<RefCell<BuildTarget>>);
 
+/// How the headers of a dependency are included, when it is not up to the kind of the dependency.
+/// The origin declared for the dependency comes first, then the `third_party_as_system` policy of
+/// the project. Otherwise, dependencies built from source are included with -I, and header only,
+/// pkg-config and CMake dependencies with -isystem.
+fn dependency_include_type(
+    origin: Option<types::IncludeSearchType>,
+    third_party_as_system: Option<bool>,
+) -> Option<IncludeType> {
+    match (origin, third_party_as_system) {
+        (Some(types::IncludeSearchType::System), _) | (None, Some(true)) => {
+            Some(IncludeType::System)
+        }
+        (Some(types::IncludeSearchType::Include), _) | (None, Some(false)) => {
+            Some(IncludeType::Include)
+        }
+        (None, None) => None,
+    }
+}
+
 impl TargetNode {
     pub fn new(target: BuildTarget) -> Self {
         Self(Rc::new(RefCell::new(target)))
@@ -539,6 +1098,14 @@ pub enum TargetError {
     AssociatedFile(#[source] associated_files::AssociatedFileError),
     #[error("Could not find any library with name {0}")]
     NoLibraryWithName(String),
+    #[error("Archive of dependency {0} has not been unpacked")]
+    ArchiveNotUnpacked(String),
+    #[error(transparent)]
+    Dependency(#[from] targets::DependencyError),
+    #[error("Could not find any executable with name {0}")]
+    NoExecutableWithName(String),
+    #[error("Executable \"{0}\" run by a custom command of \"{1}\" depends on the output of that command")]
+    ToolCirculation(String, String),
     #[error("Dependency \"{0}\" parsed is not a library, but an executable")]
     DependencyNotALibrary(String),
     #[error("Error occured when parsing include directories")]
@@ -547,4 +1114,8 @@ pub enum TargetError {
     NoPkgConfigInstance,
     #[error("Could not find any pkg-config package with name {0}")]
     CouldNotFindPkgConfigPackage(String, #[source] PkgConfigError),
+    #[error("Could not import CMake target {0}")]
+    CouldNotImportCMakeTarget(String, #[source] CMakeError),
+    #[error("Could not import {0} from another yambs build directory")]
+    CouldNotImportYambsBuildTarget(String, #[source] YambsBuildError),
 }
@@ -1,5 +1,4 @@
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -8,8 +7,9 @@ use crate::build_target::include_directories::{IncludeDirectories, IncludeDirect
 use crate::build_target::{
     LibraryType, PrintableLibrary, SHARED_LIBRARY_FILE_EXTENSION, STATIC_LIBRARY_FILE_EXTENSION,
 };
+use crate::environment::{Environment, ModifyMode};
 use crate::flags::CXXFlags;
-use crate::{find_program, EnvironmentVariable, FindProgramOptions, ModifyMode};
+use crate::{find_program, FindProgramOptions};
 
 #[derive(Debug, Error)]
 pub enum PkgConfigError {
@@ -23,23 +23,40 @@ pub enum PkgConfigError {
     FailedToGetVersion(String),
     #[error("Failed to locate library {0}")]
     CouldNotLocateLibrary(String),
+    #[error(
+        "Package {package} has no component {}. Available components are: {}",
+        unknown.join(", "),
+        available.join(", ")
+    )]
+    UnknownComponents {
+        package: String,
+        unknown: Vec<String>,
+        available: Vec<String>,
+    },
 }
 
 #[derive(PartialEq, Eq, Debug)]
 pub struct PkgConfig {
     path: PathBuf,
-    search_path_env: EnvironmentVariable,
+    environment: Environment,
 }
 
 impl PkgConfig {
     pub fn new() -> Result<Self, PkgConfigError> {
+        Self::find(&[])
+    }
+
+    /// pkg-config found in `search_paths` or PATH.
+    pub fn find(search_paths: &[PathBuf]) -> Result<Self, PkgConfigError> {
         let mut search_options = FindProgramOptions::new();
-        search_options.with_path_env();
+        search_options
+            .with_path_env()
+            .search_directories_first(search_paths);
 
         if let Some(pkg_config) = find_program(Path::new("pkg-config"), search_options) {
             Ok(Self {
                 path: pkg_config,
-                search_path_env: EnvironmentVariable::new("PKG_CONFIG_PATH"),
+                environment: Environment::new(),
             })
         } else {
             Err(PkgConfigError::CouldNotFindPkgConfig)
@@ -49,16 +66,87 @@ impl PkgConfig {
     pub fn from_path(path: &Path) -> Self {
         Self {
             path: path.to_path_buf(),
-            search_path_env: EnvironmentVariable::new("PKG_CONFIG_PATH"),
+            environment: Environment::new(),
+        }
+    }
+
+    /// pkg-config for a cross toolchain targeting `triple`. `<triple>-pkg-config` is used when
+    /// found in `search_paths` or PATH, as it is set up for the target already. Otherwise,
+    /// pkg-config is limited to the packages of the target, as with `limit_to_target`.
+    pub fn find_cross(
+        search_paths: &[PathBuf],
+        triple: &str,
+        sysroot: Option<&Path>,
+    ) -> Result<Self, PkgConfigError> {
+        let mut search_options = FindProgramOptions::new();
+        search_options
+            .with_path_env()
+            .search_directories_first(search_paths);
+        let cross_pkg_config = PathBuf::from(format!("{}-pkg-config", triple));
+        if let Some(pkg_config) = find_program(&cross_pkg_config, search_options) {
+            log::debug!("Using {} for the target", pkg_config.display());
+            return Ok(Self::from_path(&pkg_config));
+        }
+        let mut pkg_config = Self::find(search_paths)?;
+        pkg_config.limit_to_target(triple, sysroot);
+        Ok(pkg_config)
+    }
+
+    /// Keeps pkg-config from finding the packages of the host, whose libraries can not be linked
+    /// into binaries of the target. Packages are only looked up in the directories of the target
+    /// in `sysroot`, and the paths of their flags are taken to be relative to it. Without a
+    /// sysroot, the packages of the target are looked up where multiarch and cross toolchain
+    /// packages install them. PKG_CONFIG_LIBDIR and PKG_CONFIG_SYSROOT_DIR set by the user are
+    /// left as they are, as is a pkg-config made for the target.
+    pub fn limit_to_target(&mut self, triple: &str, sysroot: Option<&Path>) {
+        let is_for_target = self
+            .path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(&format!("{}-", triple)));
+        if is_for_target {
+            return;
+        }
+        if self.environment.get("PKG_CONFIG_LIBDIR").is_none() {
+            let directories = target_package_directories(triple, sysroot);
+            log::debug!(
+                "Looking up packages of the target in {}",
+                directories
+                    .iter()
+                    .map(|directory| directory.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+            for directory in directories {
+                self.environment.set(
+                    "PKG_CONFIG_LIBDIR",
+                    directory.as_os_str(),
+                    ModifyMode::Append,
+                );
+            }
+        }
+        if let Some(sysroot) = sysroot {
+            if self.environment.get("PKG_CONFIG_SYSROOT_DIR").is_none() {
+                self.environment.set(
+                    "PKG_CONFIG_SYSROOT_DIR",
+                    sysroot.as_os_str(),
+                    ModifyMode::Set,
+                );
+            }
         }
     }
 
     pub fn add_search_path(&mut self, path: &Path) {
-        self.search_path_env
-            .set(path.as_os_str(), ModifyMode::Append);
+        self.environment
+            .set("PKG_CONFIG_PATH", path.as_os_str(), ModifyMode::Append);
     }
 
-    pub fn find_target(&self, target: &str) -> Result<PkgConfigTarget, PkgConfigError> {
+    /// Finds `target` with pkg-config. If `components` are given, only the libraries of the
+    /// package that are those components are linked.
+    pub fn find_target(
+        &self,
+        target: &str,
+        components: &[String],
+    ) -> Result<PkgConfigTarget, PkgConfigError> {
         let cxx_flags = {
             let cflags = self.run(&[target, "--cflags-only-other"])?;
             let cflags = cflags.split_whitespace().collect::<Vec<&str>>();
@@ -88,17 +176,30 @@ impl PkgConfig {
             target: target.to_string(),
             include_directories,
             cxx_flags,
-            method: self.determine_provide_method(target)?,
+            method: self.determine_provide_method(target, components)?,
         })
     }
 
-    fn determine_provide_method(&self, target: &str) -> Result<ProvideMethod, PkgConfigError> {
+    fn determine_provide_method(
+        &self,
+        target: &str,
+        components: &[String],
+    ) -> Result<ProvideMethod, PkgConfigError> {
         let libs_only_l = self.run(&[target, "--libs-only-l"])?;
         let link_libs = libs_only_l.split_whitespace().collect::<Vec<&str>>();
         let library_names = link_libs
             .iter()
             .map(|s| s.replace("-l", ""))
             .collect::<Vec<String>>();
+        let library_names = if components.is_empty() {
+            library_names
+        } else {
+            select_components(target, &library_names, components)?
+        };
+        let link_libs = library_names
+            .iter()
+            .map(|lib_name| format!("-l{}", lib_name))
+            .collect::<Vec<String>>();
 
         let libs_only_capital_l = self.run(&[target, "--libs-only-L"])?;
         let link_dirs = libs_only_capital_l
@@ -130,10 +231,7 @@ impl PkgConfig {
                     );
 
                     return Ok(ProvideMethod::PkgConfigOutput(PkgConfigLDFlags {
-                        link_libs: link_libs
-                            .iter()
-                            .map(|s| s.to_string())
-                            .collect::<Vec<String>>(),
+                        link_libs,
                         link_dirs: link_dirs
                             .iter()
                             .map(|s| s.to_string())
@@ -150,7 +248,9 @@ impl PkgConfig {
     }
 
     fn run(&self, args: &[&str]) -> Result<String, PkgConfigError> {
-        let output = Command::new(&self.path)
+        let output = self
+            .environment
+            .command(&self.path)
             .args(args)
             .output()
             .map_err(PkgConfigError::FailedToRunPkgConfig)?;
@@ -168,6 +268,75 @@ impl PkgConfig {
     }
 }
 
+/// Directories pkg-config looks up the packages of `triple` in: those of the sysroot, or those
+/// multiarch and cross toolchain packages install them in without one. Packages in share are not
+/// specific to an architecture.
+pub fn target_package_directories(triple: &str, sysroot: Option<&Path>) -> Vec<PathBuf> {
+    match sysroot {
+        Some(sysroot) => vec![
+            sysroot.join("usr/lib").join(triple).join("pkgconfig"),
+            sysroot.join("usr/lib/pkgconfig"),
+            sysroot.join("usr/share/pkgconfig"),
+        ],
+        None => vec![
+            Path::new("/usr/lib").join(triple).join("pkgconfig"),
+            Path::new("/usr").join(triple).join("lib/pkgconfig"),
+            PathBuf::from("/usr/share/pkgconfig"),
+        ],
+    }
+}
+
+// Name `library` of `package` is selected by as a component. Libraries of large packages are
+// commonly prefixed with the name of the package, such as boost_filesystem of boost, which is
+// selected by filesystem.
+fn component_name<'a>(package: &str, library: &'a str) -> &'a str {
+    library
+        .strip_prefix(package)
+        .and_then(|name| name.strip_prefix('_'))
+        .filter(|name| !name.is_empty())
+        .unwrap_or(library)
+}
+
+// Libraries of `package` that are `components`, named either by their component name or by
+// their full library name.
+fn select_components(
+    package: &str,
+    libraries: &[String],
+    components: &[String],
+) -> Result<Vec<String>, PkgConfigError> {
+    let is_component = |library: &String, component: &String| {
+        library == component || component_name(package, library) == component
+    };
+    let unknown = components
+        .iter()
+        .filter(|component| {
+            !libraries
+                .iter()
+                .any(|library| is_component(library, component))
+        })
+        .cloned()
+        .collect::<Vec<String>>();
+    if !unknown.is_empty() {
+        return Err(PkgConfigError::UnknownComponents {
+            package: package.to_string(),
+            unknown,
+            available: libraries
+                .iter()
+                .map(|library| component_name(package, library).to_string())
+                .collect(),
+        });
+    }
+    Ok(libraries
+        .iter()
+        .filter(|library| {
+            components
+                .iter()
+                .any(|component| is_component(library, component))
+        })
+        .cloned()
+        .collect())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct PkgConfigTarget {
     pub target: String,
@@ -234,3 +403,82 @@ impl PkgConfigLibrary {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn libraries(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn select_components_keeps_libraries_of_selected_components() {
+        let selected = select_components(
+            "boost",
+            &libraries(&["boost_filesystem", "boost_regex", "boost_thread", "pthread"]),
+            &libraries(&["regex", "filesystem", "pthread"]),
+        )
+        .unwrap();
+        assert_eq!(
+            selected,
+            libraries(&["boost_filesystem", "boost_regex", "pthread"])
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn cross_pkg_config_only_looks_up_packages_of_target() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tempdir = tempdir::TempDir::new("pkg_config").unwrap();
+        let sysroot = tempdir.path().join("sysroot");
+        if std::env::var_os("PKG_CONFIG_LIBDIR").is_none()
+            && std::env::var_os("PKG_CONFIG_SYSROOT_DIR").is_none()
+        {
+            let mut pkg_config = PkgConfig::from_path(Path::new("/usr/bin/pkg-config"));
+            pkg_config.limit_to_target("aarch64-linux-gnu", Some(&sysroot));
+            assert_eq!(
+                pkg_config.environment.get("PKG_CONFIG_LIBDIR"),
+                Some(
+                    std::env::join_paths(target_package_directories(
+                        "aarch64-linux-gnu",
+                        Some(&sysroot)
+                    ))
+                    .unwrap()
+                )
+            );
+            assert_eq!(
+                pkg_config.environment.get("PKG_CONFIG_SYSROOT_DIR"),
+                Some(sysroot.clone().into_os_string())
+            );
+        }
+
+        let cross_pkg_config = tempdir.path().join("aarch64-linux-gnu-pkg-config");
+        std::fs::write(&cross_pkg_config, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&cross_pkg_config, std::fs::Permissions::from_mode(0o755))
+            .unwrap();
+        let pkg_config = PkgConfig::find_cross(
+            &[tempdir.path().to_path_buf()],
+            "aarch64-linux-gnu",
+            Some(&sysroot),
+        )
+        .unwrap();
+        assert_eq!(pkg_config, PkgConfig::from_path(&cross_pkg_config));
+    }
+
+    #[test]
+    fn select_components_lists_available_components_of_misspelled_one() {
+        let error = select_components(
+            "boost",
+            &libraries(&["boost_filesystem", "boost_regex"]),
+            &libraries(&["filesytem"]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Package boost has no component filesytem. Available components are: filesystem, regex"
+        );
+    }
+}
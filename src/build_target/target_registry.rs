@@ -1,23 +1,151 @@
-use crate::build_target::{BuildTarget, TargetNode};
-// LEGG TIL TESTER
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::archive::ArchiveDirectory;
+use crate::build_target::cmake::CMakeProject;
+use crate::build_target::yambs_build::YambsBuild;
+use crate::build_target::{BuildTarget, DependencySource, TargetNode};
+use crate::cache::Cacher;
+use crate::cli::configurations::BuildType;
+use crate::manifest::ParsedManifest;
+use crate::parser;
+use crate::parser::types::Standard;
+use crate::toolchain::ToolchainFingerprint;
+use crate::YAMBS_MANIFEST_NAME;
+
+// Variables that change what pkg-config finds for the dependencies of the targets.
+const PKG_CONFIG_ENVIRONMENT_VARIABLES: &[&str] = &[
+    "PKG_CONFIG_PATH",
+    "PKG_CONFIG_LIBDIR",
+    "PKG_CONFIG_SYSROOT_DIR",
+];
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(transparent)]
 pub struct TargetRegistry {
     pub registry: Vec<TargetNode>,
+    // Manifests of the registered targets and their dependencies, keyed by their directory.
+    #[serde(skip)]
+    manifests: HashMap<PathBuf, Rc<ParsedManifest>>,
+    // Directory the archives of dependencies are unpacked into as their dependents are registered.
+    #[serde(skip)]
+    archive_directory: Option<ArchiveDirectory>,
 }
 
 impl TargetRegistry {
     pub fn new() -> TargetRegistry {
         TargetRegistry {
             registry: Vec::new(),
+            manifests: HashMap::new(),
+            archive_directory: None,
         }
     }
 
+    /// Registry unpacking the archives of dependencies into `archive_directory`. Without one,
+    /// archive dependencies fail to register.
+    pub fn with_archive_directory(mut self, archive_directory: ArchiveDirectory) -> Self {
+        self.archive_directory = Some(archive_directory);
+        self
+    }
+
+    pub fn archive_directory(&self) -> Option<&ArchiveDirectory> {
+        self.archive_directory.as_ref()
+    }
+
+    /// Registry using manifests parsed ahead of time instead of parsing them when registering.
+    pub fn with_manifests(mut self, manifests: HashMap<PathBuf, ParsedManifest>) -> Self {
+        self.manifests.extend(
+            manifests
+                .into_iter()
+                .map(|(directory, manifest)| (directory, Rc::new(manifest))),
+        );
+        self
+    }
+
+    /// Manifest located in `directory`. It is only parsed the first time it is asked for.
+    pub fn manifest(
+        &mut self,
+        directory: &Path,
+    ) -> Result<Rc<ParsedManifest>, parser::ParseTomlError> {
+        if let Some(manifest) = self.manifests.get(directory) {
+            return Ok(manifest.clone());
+        }
+        let manifest = Rc::new(parser::parse(&directory.join(YAMBS_MANIFEST_NAME))?);
+        self.manifests
+            .insert(directory.to_path_buf(), manifest.clone());
+        Ok(manifest)
+    }
+
     pub fn number_of_targets(&self) -> usize {
         self.registry.len()
     }
 
+    /// Manifests of the registered targets that have changed since they were parsed.
+    pub fn changed_manifests(&self) -> BTreeSet<PathBuf> {
+        let mut manifests = HashMap::new();
+        for target in &self.registry {
+            let manifest = target.borrow().manifest.clone();
+            manifests
+                .entry(manifest.directory.clone())
+                .or_insert(manifest);
+        }
+        manifests
+            .into_values()
+            .filter(|manifest| manifest.has_changed())
+            .map(|manifest| manifest.directory.join(YAMBS_MANIFEST_NAME))
+            .collect()
+    }
+
+    /// Dependencies built from source that are compiled with a newer standard than a target
+    /// linking them. Targets are compiled with `build_standard`, the standard of the build, unless
+    /// their own flags set another.
+    pub fn standard_mismatches(&self, build_standard: &Standard) -> Vec<StandardMismatch> {
+        let standard_of = |target: &BuildTarget| {
+            target
+                .standard
+                .clone()
+                .unwrap_or_else(|| build_standard.clone())
+        };
+        let mut mismatches = Vec::new();
+        for target in &self.registry {
+            let borrowed_target = target.borrow();
+            let dependent_standard = standard_of(&borrowed_target);
+            for dependency in &borrowed_target.dependencies {
+                let source = match dependency.source.from_source() {
+                    Some(source) => source,
+                    None => continue,
+                };
+                let dependency_target = match self.get_target_from_predicate(|build_target| {
+                    build_target.manifest.directory == source.manifest.directory
+                        && build_target.is_library()
+                        && build_target.name() == source.library.name
+                }) {
+                    Some(dependency_target) => dependency_target,
+                    None => continue,
+                };
+                let dependency_standard = standard_of(&dependency_target.borrow());
+                let is_newer = match (&dependency_standard, &dependent_standard) {
+                    (Standard::CXX(dependency), Standard::CXX(dependent)) => dependency > dependent,
+                    (Standard::C(dependency), Standard::C(dependent)) => dependency > dependent,
+                    _ => false,
+                };
+                let mismatch = StandardMismatch {
+                    dependent: borrowed_target.name(),
+                    dependent_standard: dependent_standard.clone(),
+                    dependency: source.library.name.clone(),
+                    dependency_standard,
+                };
+                // Targets built for both the host and the target are registered twice.
+                if is_newer && !mismatches.contains(&mismatch) {
+                    mismatches.push(mismatch);
+                }
+            }
+        }
+        mismatches
+    }
+
     pub fn add_target(&mut self, target: TargetNode) {
         self.registry.push(target);
     }
@@ -37,3 +165,425 @@ impl TargetRegistry {
         None
     }
 }
+
+/// Dependency written for a newer standard than a target linking it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandardMismatch {
+    pub dependent: String,
+    pub dependent_standard: Standard,
+    pub dependency: String,
+    pub dependency_standard: Standard,
+}
+
+impl std::fmt::Display for StandardMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "\"{}\" ({}) links \"{}\", which is compiled with the newer standard {}",
+            self.dependent,
+            self.dependent_standard.to_string(),
+            self.dependency,
+            self.dependency_standard.to_string()
+        )
+    }
+}
+
+/// What a registry is resolved from. A registry resolved from the same inputs is reused by later
+/// builds instead of parsing the manifests and resolving the targets again.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RegistryInputs {
+    pub yambs_version: String,
+    /// Directory of the manifest the build started from.
+    #[serde(default)]
+    pub manifest_directory: PathBuf,
+    pub build_type: BuildType,
+    pub toolchain: ToolchainFingerprint,
+    pub environment: BTreeMap<String, String>,
+    /// Manifests of the targets, the manifests they include and inherit defaults from, the caches
+    /// of the CMake projects targets are imported from, the extra files of the targets, and the
+    /// directories tests are discovered in, with their modification times.
+    pub files: BTreeMap<PathBuf, Option<SystemTime>>,
+    /// Whether the tests and examples of the dependency manifests were registered.
+    #[serde(default)]
+    pub dependency_tests: bool,
+}
+
+impl RegistryInputs {
+    /// Inputs of `registry`, as of now. `manifest_directory` is the directory of the manifest the
+    /// build started from, which is an input even if it has no targets.
+    pub fn new(
+        registry: &TargetRegistry,
+        manifest_directory: &Path,
+        build_type: &BuildType,
+        toolchain: &ToolchainFingerprint,
+    ) -> Self {
+        let manifests = std::iter::once(manifest_directory.to_path_buf())
+            .chain(
+                registry
+                    .registry
+                    .iter()
+                    .map(|target| target.borrow().manifest.directory.clone()),
+            )
+            .map(|directory| directory.join(YAMBS_MANIFEST_NAME))
+            .collect::<BTreeSet<PathBuf>>();
+        // CMake writes its cache whenever it configures a project, which may change the targets
+        // imported from it.
+        let cmake_caches = registry
+            .registry
+            .iter()
+            .flat_map(|target| target.borrow().dependencies.clone())
+            .filter_map(|dependency| match dependency.source {
+                DependencySource::FromCMake(ref cmake) => {
+                    Some(CMakeProject::cache_file(&cmake.build_directory))
+                }
+                DependencySource::FromYambsBuild(ref yambs_build) => {
+                    Some(YambsBuild::registry_file(&yambs_build.build_directory))
+                }
+                _ => None,
+            })
+            .collect::<Vec<PathBuf>>();
+        // Extra files are checked to exist as the manifests are parsed.
+        let extra_files = registry
+            .registry
+            .iter()
+            .flat_map(|target| target.borrow().extra_files.clone())
+            .collect::<Vec<PathBuf>>();
+        // Dependency manifests only discover tests with --with-dep-tests.
+        let test_discovery_directories = registry
+            .manifests
+            .values()
+            .flat_map(|manifest| manifest.data.test_discovery_directories.clone())
+            .collect::<Vec<PathBuf>>();
+        let files = manifests
+            .iter()
+            .flat_map(|manifest| {
+                std::iter::once(manifest.clone())
+                    .chain(parser::include::included_manifests(manifest))
+                    .chain(parser::defaults::enclosing_manifests(
+                        manifest,
+                        manifest_directory,
+                    ))
+            })
+            .chain(cmake_caches)
+            .chain(extra_files)
+            .chain(test_discovery_directories)
+            .map(|file| {
+                let modification_time = modification_time(&file);
+                (file, modification_time)
+            })
+            .collect();
+        Self {
+            yambs_version: env!("CARGO_PKG_VERSION").to_string(),
+            manifest_directory: manifest_directory.to_path_buf(),
+            build_type: build_type.clone(),
+            toolchain: toolchain.clone(),
+            environment: pkg_config_environment(),
+            files,
+            dependency_tests: dependency_tests(),
+        }
+    }
+
+    /// Inputs along with `directories`, whose modification time changes as files are added to or
+    /// removed from them.
+    pub fn with_directories(mut self, directories: &[PathBuf]) -> Self {
+        for directory in directories {
+            self.files
+                .insert(directory.clone(), modification_time(directory));
+        }
+        self
+    }
+
+    /// Directories of the manifests of the targets.
+    pub fn manifest_directories(&self) -> BTreeSet<PathBuf> {
+        self.files
+            .keys()
+            .filter(|file| file.ends_with(YAMBS_MANIFEST_NAME))
+            .filter_map(|file| file.parent().map(Path::to_path_buf))
+            .collect()
+    }
+
+    /// Whether the inputs are the same for a build of `build_type` from the manifest in
+    /// `manifest_directory` by this version of yambs, without considering the toolchain.
+    pub fn is_unchanged(&self, manifest_directory: &Path, build_type: &BuildType) -> bool {
+        self.yambs_version == env!("CARGO_PKG_VERSION")
+            && self.manifest_directory == manifest_directory
+            && self.build_type == *build_type
+            && self.environment == pkg_config_environment()
+            && self.dependency_tests == dependency_tests()
+            && self
+                .files
+                .iter()
+                .all(|(file, recorded)| modification_time(file) == *recorded)
+    }
+}
+
+fn dependency_tests() -> bool {
+    crate::DEPENDENCY_TESTS.get().copied().unwrap_or(false)
+}
+
+fn modification_time(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn pkg_config_environment() -> BTreeMap<String, String> {
+    PKG_CONFIG_ENVIRONMENT_VARIABLES
+        .iter()
+        .filter_map(|name| Some((name.to_string(), std::env::var(name).ok()?)))
+        .collect()
+}
+
+/// Registry of the most recent build, along with what it was resolved from.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CachedRegistry {
+    pub inputs: RegistryInputs,
+    pub registry: TargetRegistry,
+}
+
+impl Cacher for CachedRegistry {
+    const CACHE_FILE_NAME: &'static str = "registry";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::CompilerFingerprint;
+
+    fn toolchain() -> ToolchainFingerprint {
+        let compiler = CompilerFingerprint {
+            path: PathBuf::from("/usr/bin/g++"),
+            compiler_type: crate::compiler::Type::Gcc,
+            version: "13.2.0".to_string(),
+            target: "x86_64-linux-gnu".to_string(),
+        };
+        ToolchainFingerprint {
+            cxx: compiler.clone(),
+            cc: compiler,
+            archiver: PathBuf::from("/usr/bin/ar"),
+            apple_platform_flags: Vec::new(),
+            cross_target_flags: Vec::new(),
+            host: None,
+        }
+    }
+
+    // Registry of the executable app, linking the library core of the manifest in the core
+    // directory of `directory`, which is compiled with the flags `core_flags`.
+    fn registry_of_app_linking_core(directory: &Path, core_flags: &str) -> TargetRegistry {
+        let parsed_manifest = |directory: &Path, content: &str| {
+            let manifest_path = directory.join(YAMBS_MANIFEST_NAME);
+            std::fs::write(&manifest_path, content).unwrap();
+            ParsedManifest {
+                manifest: crate::manifest::Manifest::new(directory),
+                data: parser::parse_toml(
+                    content,
+                    &manifest_path,
+                    &mut parser::preprocessor::Preprocessor::new(),
+                )
+                .unwrap(),
+            }
+        };
+        let core_directory = directory.join("core");
+        std::fs::create_dir_all(&core_directory).unwrap();
+        std::fs::write(core_directory.join("core.cpp"), "").unwrap();
+        std::fs::write(directory.join("main.cpp"), "").unwrap();
+        let core = parsed_manifest(
+            &core_directory,
+            &format!(
+                "[project_config]\nstd = \"c++20\"\n\n[library.core]\nsources = [\"core.cpp\"]\n{}",
+                core_flags
+            ),
+        );
+        let app = parsed_manifest(
+            directory,
+            "[executable.app]\nsources = [\"main.cpp\"]\ndependencies.core = { path = \"core\" }\n",
+        );
+        std::fs::write(
+            directory.join("toolchain.toml"),
+            "[CXX]\ncompiler = \"/usr/bin/g++\"\n\n[CC]\ncompiler = \"/usr/bin/gcc\"\n\n[common]\n",
+        )
+        .unwrap();
+        let toolchain = Rc::new(std::cell::RefCell::new(
+            crate::toolchain::NormalizedToolchain::from_file(&directory.join("toolchain.toml"))
+                .unwrap(),
+        ));
+        let mut registry = TargetRegistry::new().with_manifests(HashMap::from([
+            (core_directory, core),
+            (directory.to_path_buf(), app.clone()),
+        ]));
+        for target in &app.data.targets {
+            BuildTarget::target_node_from_source(
+                &app,
+                target,
+                parser::types::BuildFor::Target,
+                &mut registry,
+                &toolchain,
+                &BuildType::Debug,
+            )
+            .unwrap();
+        }
+        registry
+    }
+
+    #[test]
+    fn dependencies_compiled_with_a_newer_standard_are_mismatches() {
+        let tempdir = tempdir::TempDir::new("target_registry").unwrap();
+        let directory = tempdir.path().canonicalize().unwrap();
+        let build_standard = Standard::new("c++17", &parser::types::Language::CXX).unwrap();
+        let registry =
+            registry_of_app_linking_core(&directory, "cxxflags_append = [\"-std=c++20\"]\n");
+        assert_eq!(
+            registry.standard_mismatches(&build_standard),
+            vec![StandardMismatch {
+                dependent: "app".to_string(),
+                dependent_standard: build_standard,
+                dependency: "core".to_string(),
+                dependency_standard: Standard::new("c++20", &parser::types::Language::CXX).unwrap(),
+            }]
+        );
+    }
+
+    #[test]
+    fn dependencies_compiled_with_the_standard_of_the_build_are_not_mismatches() {
+        let tempdir = tempdir::TempDir::new("target_registry").unwrap();
+        let directory = tempdir.path().canonicalize().unwrap();
+        // The std of the manifest of core is not passed when it is built as a dependency.
+        let registry = registry_of_app_linking_core(&directory, "");
+        assert_eq!(
+            registry.standard_mismatches(
+                &Standard::new("c++17", &parser::types::Language::CXX).unwrap()
+            ),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn archive_dependencies_are_unpacked_as_their_dependents_are_registered() {
+        use sha2::Digest;
+
+        let tempdir = tempdir::TempDir::new("target_registry").unwrap();
+        let directory = tempdir.path().canonicalize().unwrap();
+        let core_directory = directory.join("core");
+        std::fs::create_dir_all(&core_directory).unwrap();
+        std::fs::write(core_directory.join("core.cpp"), "").unwrap();
+        let core_content = "[library.core]\nsources = [\"core.cpp\"]\n";
+        std::fs::write(core_directory.join(YAMBS_MANIFEST_NAME), core_content).unwrap();
+        let archive = directory.join("core.tar");
+        let status = std::process::Command::new("tar")
+            .arg("-cf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&directory)
+            .arg("core")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let checksum = format!(
+            "{:x}",
+            sha2::Sha256::digest(std::fs::read(&archive).unwrap())
+        );
+
+        std::fs::write(directory.join("main.cpp"), "").unwrap();
+        let manifest_path = directory.join(YAMBS_MANIFEST_NAME);
+        let content = format!(
+            "[executable.app]\nsources = [\"main.cpp\"]\ndependencies.core = {{ url = \"file://{}\", sha256 = \"{}\" }}\n",
+            archive.display(),
+            checksum
+        );
+        std::fs::write(&manifest_path, &content).unwrap();
+        let app = ParsedManifest {
+            manifest: crate::manifest::Manifest::new(&directory),
+            data: parser::parse_toml(
+                &content,
+                &manifest_path,
+                &mut parser::preprocessor::Preprocessor::new(),
+            )
+            .unwrap(),
+        };
+        let archives = directory
+            .join("build")
+            .join(crate::archive::ARCHIVES_DIRECTORY_NAME);
+        assert!(!archives.exists());
+
+        std::fs::write(
+            directory.join("toolchain.toml"),
+            "[CXX]\ncompiler = \"/usr/bin/g++\"\n\n[CC]\ncompiler = \"/usr/bin/gcc\"\n\n[common]\n",
+        )
+        .unwrap();
+        let toolchain = Rc::new(std::cell::RefCell::new(
+            crate::toolchain::NormalizedToolchain::from_file(&directory.join("toolchain.toml"))
+                .unwrap(),
+        ));
+        // The manifest of core is given for the directory it is unpacked into, as parsing it from
+        // disk needs the preset variables of a build. Its sources are those it is archived from.
+        let unpacked = archives.join(&checksum).join("core");
+        let core = ParsedManifest {
+            manifest: crate::manifest::Manifest {
+                directory: unpacked.clone(),
+                modification_time: SystemTime::now(),
+                content_hash: None,
+            },
+            data: parser::parse_toml(
+                core_content,
+                &core_directory.join(YAMBS_MANIFEST_NAME),
+                &mut parser::preprocessor::Preprocessor::new(),
+            )
+            .unwrap(),
+        };
+        let register = |archive_directory: ArchiveDirectory| {
+            let mut registry = TargetRegistry::new()
+                .with_manifests(HashMap::from([(unpacked.clone(), core.clone())]))
+                .with_archive_directory(archive_directory);
+            BuildTarget::target_node_from_source(
+                &app,
+                &app.data.targets[0],
+                parser::types::BuildFor::Target,
+                &mut registry,
+                &toolchain,
+                &BuildType::Debug,
+            )
+            .ok()
+            .map(|_| registry)
+        };
+        assert!(register(ArchiveDirectory::read_only(&archives)).is_none());
+        let registry = register(ArchiveDirectory::new(&archives)).unwrap();
+        let core = registry
+            .get_target_from_predicate(|target| target.name() == "core")
+            .unwrap();
+        assert_eq!(core.borrow().manifest.directory, unpacked);
+        assert!(register(ArchiveDirectory::read_only(&archives)).is_some());
+    }
+
+    #[test]
+    fn inputs_change_with_manifests_and_their_includes() {
+        let tempdir = tempdir::TempDir::new("target_registry").unwrap();
+        let directory = tempdir.path().canonicalize().unwrap();
+        let manifest = directory.join(YAMBS_MANIFEST_NAME);
+        let common = directory.join("common.toml");
+        std::fs::write(&manifest, "include = [\"common.toml\"]\n").unwrap();
+        std::fs::write(&common, "").unwrap();
+
+        let inputs = RegistryInputs::new(
+            &TargetRegistry::new(),
+            &directory,
+            &BuildType::Debug,
+            &toolchain(),
+        );
+        assert_eq!(
+            inputs.files.keys().collect::<Vec<&PathBuf>>(),
+            vec![&common, &manifest]
+        );
+        assert!(inputs.is_unchanged(&directory, &BuildType::Debug));
+        assert!(!inputs.is_unchanged(&directory, &BuildType::Release));
+        assert!(!inputs.is_unchanged(&directory.join("app"), &BuildType::Debug));
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        std::fs::File::options()
+            .write(true)
+            .open(&common)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(!inputs.is_unchanged(&directory, &BuildType::Debug));
+    }
+}
@@ -0,0 +1,426 @@
+// Imports libraries built by another yambs build directory, so that layered pipelines can build
+// base libraries once and link them from the builds of many downstream projects. The libraries
+// are looked up in the registry the most recent build of the directory cached, and linked from
+// the artifacts it left. They are never rebuilt by the importing build.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::build_target::include_directories::IncludeDirectories;
+use crate::build_target::pkg_config::ProvideMethod;
+use crate::build_target::target_registry::{CachedRegistry, TargetRegistry};
+use crate::build_target::{DependencySource, TargetNode};
+use crate::cache::{Cacher, YAMBS_CACHE_DIRECTORY_NAME};
+use crate::cli::configurations::BuildType;
+use crate::generator::HOST_BUILD_DIRECTORY_NAME;
+use crate::parser::types::BuildFor;
+use crate::toolchain::OutputExtensions;
+
+#[derive(Debug, Error)]
+pub enum YambsBuildError {
+    #[error("{0:?} has not been built by yambs. Build the project into it first")]
+    NotBuilt(PathBuf),
+    #[error("Failed to read the registry of {0:?}")]
+    FailedToReadRegistry(PathBuf, #[source] std::io::Error),
+    #[error("Failed to parse the registry of {0:?}. Build the project into it again with this version of yambs")]
+    FailedToParseRegistry(PathBuf, #[source] serde_json::Error),
+    #[error("Build directory has no library {target}. Available libraries are: {}", available.join(", "))]
+    NoLibraryWithName {
+        target: String,
+        available: Vec<String>,
+    },
+    #[error("Library {1:?} of {0} does not exist. Build it with --build-type {2} first")]
+    MissingArtifact(String, PathBuf, String),
+}
+
+/// Library of another yambs build directory, as resolved by its most recent build.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct YambsBuildTarget {
+    pub yambs_target: String,
+    /// Build directory the library is imported from.
+    pub build_directory: PathBuf,
+    pub include_directories: IncludeDirectories,
+    /// The library, followed by the libraries it links, each after those depending on it.
+    pub libraries: Vec<PathBuf>,
+}
+
+/// Build directory of another project built by yambs.
+#[derive(Debug, Clone)]
+pub struct YambsBuild {
+    build_directory: PathBuf,
+    registry: TargetRegistry,
+}
+
+impl YambsBuild {
+    pub fn new(build_directory: &Path) -> Result<Self, YambsBuildError> {
+        let registry_file = Self::registry_file(build_directory);
+        if !registry_file.is_file() {
+            return Err(YambsBuildError::NotBuilt(build_directory.to_path_buf()));
+        }
+        let contents = std::fs::read_to_string(&registry_file)
+            .map_err(|e| YambsBuildError::FailedToReadRegistry(registry_file.clone(), e))?;
+        let cached = serde_json::from_str::<CachedRegistry>(&contents)
+            .map_err(|e| YambsBuildError::FailedToParseRegistry(registry_file, e))?;
+        Ok(Self {
+            build_directory: build_directory.to_path_buf(),
+            registry: cached.registry,
+        })
+    }
+
+    /// File the build directory keeps its registry in, written again by every build of it.
+    pub fn registry_file(build_directory: &Path) -> PathBuf {
+        build_directory
+            .join(YAMBS_CACHE_DIRECTORY_NAME)
+            .join(CachedRegistry::CACHE_FILE_NAME)
+    }
+
+    /// Finds the library `name` built for `build_for`, along with the artifacts of `build_type`
+    /// it and its dependencies are linked from. The artifacts have to exist, as they are not
+    /// built by the importing build.
+    pub fn find_library(
+        &self,
+        name: &str,
+        build_type: &BuildType,
+        build_for: BuildFor,
+        output_extensions: &OutputExtensions,
+    ) -> Result<YambsBuildTarget, YambsBuildError> {
+        let library = self.library_node(name, build_for).ok_or_else(|| {
+            YambsBuildError::NoLibraryWithName {
+                target: name.to_string(),
+                available: self.library_names(),
+            }
+        })?;
+        let artifact_directory = match build_for {
+            BuildFor::Host => self
+                .build_directory
+                .join(HOST_BUILD_DIRECTORY_NAME)
+                .join(build_type.to_string()),
+            BuildFor::Target => self.build_directory.join(build_type.to_string()),
+        };
+        let mut libraries = Vec::new();
+        self.collect_libraries(
+            &library,
+            &artifact_directory,
+            output_extensions,
+            &mut libraries,
+        );
+        // A library linked by several others is kept after the last of them.
+        let mut deduplicated = Vec::<PathBuf>::new();
+        for library in libraries.into_iter().rev() {
+            if !deduplicated.contains(&library) {
+                deduplicated.insert(0, library);
+            }
+        }
+        if let Some(missing) = deduplicated
+            .iter()
+            .find(|library| library.starts_with(&artifact_directory) && !library.exists())
+        {
+            return Err(YambsBuildError::MissingArtifact(
+                name.to_string(),
+                missing.clone(),
+                build_type.to_string(),
+            ));
+        }
+        let mut include_directories = IncludeDirectories::new();
+        self.collect_include_directories(&library, &mut include_directories);
+        Ok(YambsBuildTarget {
+            yambs_target: name.to_string(),
+            build_directory: self.build_directory.clone(),
+            include_directories,
+            libraries: deduplicated,
+        })
+    }
+
+    fn library_node(&self, name: &str, build_for: BuildFor) -> Option<TargetNode> {
+        self.registry.get_target_from_predicate(|target| {
+            target.is_library() && target.name() == name && target.build_for == build_for
+        })
+    }
+
+    fn library_names(&self) -> Vec<String> {
+        let mut names = self
+            .registry
+            .registry
+            .iter()
+            .filter(|target| target.borrow().is_library())
+            .map(|target| target.borrow().name())
+            .collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // Include directories of `library` as its dependents search them, followed by those of the
+    // libraries it depends on, as the headers of `library` may include their headers.
+    fn collect_include_directories(
+        &self,
+        library: &TargetNode,
+        include_directories: &mut IncludeDirectories,
+    ) {
+        let borrowed_library = library.borrow();
+        for include_directory in &borrowed_library.project_include_directories {
+            include_directories.add(include_directory.for_dependents());
+        }
+        for dependency in &borrowed_library.dependencies {
+            match dependency.source {
+                DependencySource::FromSource(ref s) => {
+                    for include_directory in &s.include_directories {
+                        include_directories.add(include_directory.clone());
+                    }
+                    if let Some(dependency) =
+                        self.library_node(&s.library.name, borrowed_library.build_for)
+                    {
+                        self.collect_include_directories(&dependency, include_directories);
+                    }
+                }
+                DependencySource::FromHeaderOnly(ref h) => {
+                    include_directories.add(h.include_directory.clone());
+                }
+                DependencySource::FromPkgConfig(ref pkg) => {
+                    for include_directory in &pkg.include_directories {
+                        include_directories.add(include_directory.clone());
+                    }
+                }
+                DependencySource::FromCMake(ref cmake) => {
+                    for include_directory in &cmake.include_directories {
+                        include_directories.add(include_directory.clone());
+                    }
+                }
+                DependencySource::FromYambsBuild(ref yambs_build) => {
+                    for include_directory in &yambs_build.include_directories {
+                        include_directories.add(include_directory.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    // Libraries `library` links, depth first, with each library before the libraries it links.
+    fn collect_libraries(
+        &self,
+        library: &TargetNode,
+        artifact_directory: &Path,
+        output_extensions: &OutputExtensions,
+        libraries: &mut Vec<PathBuf>,
+    ) {
+        let borrowed_library = library.borrow();
+        if let crate::build_target::TargetType::Library(ref printable_library) =
+            borrowed_library.target_type
+        {
+            libraries
+                .push(artifact_directory.join(output_extensions.link_file_name(printable_library)));
+        }
+        for dependency in &borrowed_library.dependencies {
+            match dependency.source {
+                DependencySource::FromSource(ref s) => {
+                    if let Some(dependency) =
+                        self.library_node(&s.library.name, borrowed_library.build_for)
+                    {
+                        self.collect_libraries(
+                            &dependency,
+                            artifact_directory,
+                            output_extensions,
+                            libraries,
+                        );
+                    }
+                }
+                DependencySource::FromPkgConfig(ref pkg) => {
+                    if let ProvideMethod::Finegrained(ref libs) = pkg.method {
+                        libraries.extend(libs.iter().map(|lib| lib.path()));
+                    }
+                }
+                DependencySource::FromCMake(ref cmake) => {
+                    libraries.extend(cmake.libraries.iter().cloned());
+                }
+                DependencySource::FromYambsBuild(ref yambs_build) => {
+                    libraries.extend(yambs_build.libraries.iter().cloned());
+                }
+                DependencySource::FromHeaderOnly(_) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use crate::build_target::include_directories::{IncludeDirectory, IncludeType};
+    use crate::build_target::BuildTarget;
+    use crate::manifest::{Manifest, ParsedManifest};
+    use crate::parser::preprocessor::Preprocessor;
+    use crate::toolchain::NormalizedToolchain;
+
+    fn parsed_manifest(directory: &Path, content: &str) -> ParsedManifest {
+        let manifest_path = directory.join(crate::YAMBS_MANIFEST_NAME);
+        std::fs::write(&manifest_path, content).unwrap();
+        ParsedManifest {
+            manifest: Manifest::new(directory),
+            data: crate::parser::parse_toml(content, &manifest_path, &mut Preprocessor::new())
+                .unwrap(),
+        }
+    }
+
+    // Build directory of a project whose library core links the library base of the project in
+    // its base directory, as the most recent debug build of the directory registered and left
+    // them.
+    fn yambs_build(project_directory: &Path) -> (YambsBuild, OutputExtensions) {
+        let base_directory = project_directory.join("base");
+        std::fs::create_dir_all(&base_directory).unwrap();
+        std::fs::write(base_directory.join("base.cpp"), "").unwrap();
+        std::fs::write(project_directory.join("core.cpp"), "").unwrap();
+        let base = parsed_manifest(
+            &base_directory,
+            "[library.base]\nsources = [\"base.cpp\"]\n",
+        );
+        let core = parsed_manifest(
+            project_directory,
+            "[library.core]\nsources = [\"core.cpp\"]\ndependencies.base = { path = \"base\" }\n",
+        );
+        std::fs::write(
+            project_directory.join("toolchain.toml"),
+            "[CXX]\ncompiler = \"/usr/bin/g++\"\n\n[CC]\ncompiler = \"/usr/bin/gcc\"\n\n[common]\n",
+        )
+        .unwrap();
+        let toolchain = Rc::new(RefCell::new(
+            NormalizedToolchain::from_file(&project_directory.join("toolchain.toml")).unwrap(),
+        ));
+        let mut registry = TargetRegistry::new().with_manifests(HashMap::from([
+            (base_directory.clone(), base),
+            (project_directory.to_path_buf(), core.clone()),
+        ]));
+        for target in &core.data.targets {
+            BuildTarget::target_node_from_source(
+                &core,
+                target,
+                BuildFor::Target,
+                &mut registry,
+                &toolchain,
+                &BuildType::Debug,
+            )
+            .unwrap();
+        }
+        let artifact_directory = project_directory.join("build").join("debug");
+        std::fs::create_dir_all(&artifact_directory).unwrap();
+        std::fs::write(artifact_directory.join("libcore.a"), "").unwrap();
+        std::fs::write(artifact_directory.join("libbase.a"), "").unwrap();
+        let output_extensions = toolchain.borrow().output_extensions.clone();
+        (
+            YambsBuild {
+                build_directory: project_directory.join("build"),
+                registry,
+            },
+            output_extensions,
+        )
+    }
+
+    #[test]
+    fn find_library_links_the_libraries_the_library_depends_on_after_it() {
+        let project = tempdir::TempDir::new("yambs_build").unwrap();
+        let project_directory = project.path().canonicalize().unwrap();
+        let (yambs_build, output_extensions) = yambs_build(&project_directory);
+        let artifact_directory = project_directory.join("build").join("debug");
+        let library = yambs_build
+            .find_library(
+                "core",
+                &BuildType::Debug,
+                BuildFor::Target,
+                &output_extensions,
+            )
+            .unwrap();
+        assert_eq!(library.yambs_target, "core");
+        assert_eq!(
+            library.libraries,
+            vec![
+                artifact_directory.join("libcore.a"),
+                artifact_directory.join("libbase.a")
+            ]
+        );
+    }
+
+    #[test]
+    fn find_library_adds_include_directories_of_the_libraries_the_library_depends_on() {
+        let project = tempdir::TempDir::new("yambs_build").unwrap();
+        let project_directory = project.path().canonicalize().unwrap();
+        let (yambs_build, output_extensions) = yambs_build(&project_directory);
+        let library = yambs_build
+            .find_library(
+                "core",
+                &BuildType::Debug,
+                BuildFor::Target,
+                &output_extensions,
+            )
+            .unwrap();
+        let include_directories = library
+            .include_directories
+            .into_iter()
+            .collect::<Vec<IncludeDirectory>>();
+        assert_eq!(
+            include_directories,
+            vec![
+                IncludeDirectory {
+                    include_type: IncludeType::Include,
+                    path: project_directory.join("include"),
+                },
+                IncludeDirectory {
+                    include_type: IncludeType::Include,
+                    path: project_directory.join("base").join("include"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn find_library_requires_the_artifacts_of_the_build_type() {
+        let project = tempdir::TempDir::new("yambs_build").unwrap();
+        let project_directory = project.path().canonicalize().unwrap();
+        let (yambs_build, output_extensions) = yambs_build(&project_directory);
+        let error = yambs_build
+            .find_library(
+                "core",
+                &BuildType::Release,
+                BuildFor::Target,
+                &output_extensions,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            YambsBuildError::MissingArtifact(ref name, ref library, _)
+                if name == "core" && *library == project_directory.join("build").join("release").join("libcore.a")
+        ));
+    }
+
+    #[test]
+    fn find_library_lists_the_libraries_of_the_build_directory_without_the_library() {
+        let project = tempdir::TempDir::new("yambs_build").unwrap();
+        let project_directory = project.path().canonicalize().unwrap();
+        let (yambs_build, output_extensions) = yambs_build(&project_directory);
+        let error = yambs_build
+            .find_library(
+                "missing",
+                &BuildType::Debug,
+                BuildFor::Target,
+                &output_extensions,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            YambsBuildError::NoLibraryWithName { ref available, .. }
+                if *available == vec!["base".to_string(), "core".to_string()]
+        ));
+    }
+
+    #[test]
+    fn build_directory_without_registry_has_not_been_built() {
+        let build_directory = tempdir::TempDir::new("yambs_build").unwrap();
+        let error = YambsBuild::new(build_directory.path()).unwrap_err();
+        assert!(
+            matches!(error, YambsBuildError::NotBuilt(ref directory) if directory == build_directory.path())
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::errors::CacheError;
+
+pub const YAMBS_CACHE_DIRECTORY_NAME: &str = "cache";
+
+/// Types that can be stored in and restored from the build cache.
+pub trait Cacher: Serialize + DeserializeOwned {
+    /// Name of the file, relative to the cache directory, that the object is stored in.
+    const CACHE_FILE_NAME: &'static str;
+}
+
+#[derive(Debug, Clone)]
+pub struct Cache {
+    cache_directory: PathBuf,
+}
+
+impl Cache {
+    pub fn new(build_directory: &Path) -> Result<Self, CacheError> {
+        let cache_directory = build_directory.join(YAMBS_CACHE_DIRECTORY_NAME);
+        if !cache_directory.is_dir() {
+            std::fs::create_dir_all(&cache_directory).map_err(CacheError::FailedToCache)?;
+        }
+        Ok(Self { cache_directory })
+    }
+
+    pub fn cache<T: Cacher>(&self, cacher: &T) -> Result<(), CacheError> {
+        let path = self.cache_directory.join(T::CACHE_FILE_NAME);
+        let fh = std::fs::File::create(path).map_err(CacheError::FailedToCache)?;
+        serde_json::to_writer_pretty(fh, cacher).map_err(CacheError::FailedToWrite)
+    }
+
+    pub fn from_cache<T: Cacher>(&self) -> Option<T> {
+        let path = self.cache_directory.join(T::CACHE_FILE_NAME);
+        let fh = std::fs::File::open(&path).ok()?;
+        let reader = std::io::BufReader::new(fh);
+        match serde_json::from_reader(reader) {
+            Ok(cached) => Some(cached),
+            Err(e) => {
+                log::warn!("Failed to read cache file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Removes the cached object, if there is one.
+    pub fn remove<T: Cacher>(&self) -> Result<(), CacheError> {
+        match std::fs::remove_file(self.cache_directory.join(T::CACHE_FILE_NAME)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(CacheError::FailedToCache(e)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.cache_directory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    struct CachedValue {
+        value: String,
+    }
+
+    impl Cacher for CachedValue {
+        const CACHE_FILE_NAME: &'static str = "value";
+    }
+
+    #[test]
+    fn cached_value_can_be_restored() {
+        let build_dir = tempdir::TempDir::new("cache").unwrap();
+        let cache = Cache::new(build_dir.path()).unwrap();
+        let expected = CachedValue {
+            value: "cached".to_string(),
+        };
+        cache.cache(&expected).unwrap();
+        assert_eq!(cache.from_cache::<CachedValue>(), Some(expected));
+    }
+
+    #[test]
+    fn removed_value_is_no_longer_restored() {
+        let build_dir = tempdir::TempDir::new("cache").unwrap();
+        let cache = Cache::new(build_dir.path()).unwrap();
+        cache.remove::<CachedValue>().unwrap();
+        cache
+            .cache(&CachedValue {
+                value: "cached".to_string(),
+            })
+            .unwrap();
+        cache.remove::<CachedValue>().unwrap();
+        assert_eq!(cache.from_cache::<CachedValue>(), None);
+    }
+
+    #[test]
+    fn missing_cache_file_gives_none() {
+        let build_dir = tempdir::TempDir::new("cache").unwrap();
+        let cache = Cache::new(build_dir.path()).unwrap();
+        assert_eq!(cache.from_cache::<CachedValue>(), None);
+    }
+}
@@ -0,0 +1,169 @@
+// Removes what builds left in a build directory for `yambs clean`: the build trees of every build
+// type, the outputs of a single target, or the whole build directory.
+
+use std::path::{Path, PathBuf};
+
+use crate::cache::YAMBS_CACHE_DIRECTORY_NAME;
+use crate::cli::configurations::BuildType;
+use crate::errors::FsError;
+use crate::generator::targets::ProgressDocument;
+use crate::generator::HOST_BUILD_DIRECTORY_NAME;
+use crate::stale_outputs::remove_stale_outputs;
+use crate::YAMBS_MANIFEST_NAME;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CleanError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+    #[error("{0:?} has not been built by yambs. Refusing to clean it.")]
+    NotABuildDirectory(PathBuf),
+    #[error("{0:?} has a manifest. Refusing to remove a project directory.")]
+    ProjectDirectory(PathBuf),
+    #[error("No target \"{0}\" is built in the build directory. Built targets are: {}", .1.join(", "))]
+    UnknownTarget(String, Vec<String>),
+}
+
+/// Build trees of `build_type` in `build_directory`: the one of the target and the one of the host.
+pub fn build_trees(build_directory: &Path, build_type: &BuildType) -> [PathBuf; 2] {
+    [
+        build_directory.join(build_type.to_string()),
+        build_directory
+            .join(HOST_BUILD_DIRECTORY_NAME)
+            .join(build_type.to_string()),
+    ]
+}
+
+/// Removes the build trees of `build_type`, which hold the objects, libraries and executables of
+/// the targets and the makefiles generated for them.
+pub fn remove_build_tree(build_directory: &Path, build_type: &BuildType) -> Result<(), FsError> {
+    for output_directory in build_trees(build_directory, build_type) {
+        if !output_directory.is_dir() {
+            continue;
+        }
+        log::debug!("Removing {}", output_directory.display());
+        std::fs::remove_dir_all(&output_directory)
+            .map_err(|e| FsError::RemoveDirectory(output_directory.clone(), e))?;
+    }
+    Ok(())
+}
+
+/// Removes the build trees of every build type. The cache and the include files of make are kept,
+/// so the next build only has to generate the build trees and compile again.
+pub fn clean_build_trees(build_directory: &Path) -> Result<(), CleanError> {
+    check_is_build_directory(build_directory)?;
+    for build_type in [BuildType::Debug, BuildType::Release] {
+        remove_build_tree(build_directory, &build_type)?;
+    }
+    Ok(())
+}
+
+/// Removes the output and objects of the target `name` from the build trees it is built in, so
+/// that the next build compiles and links it again. Returns the files removed.
+pub fn clean_target(build_directory: &Path, name: &str) -> Result<Vec<PathBuf>, CleanError> {
+    check_is_build_directory(build_directory)?;
+    let mut built_targets = Vec::new();
+    let mut removed = Vec::new();
+    let mut found = false;
+    for build_type in [BuildType::Debug, BuildType::Release] {
+        for build_tree in build_trees(build_directory, &build_type) {
+            let Ok(document) = ProgressDocument::from_directory(&build_tree) else {
+                continue;
+            };
+            for target in document.targets {
+                if target.target != name {
+                    if target.output.is_some() && !built_targets.contains(&target.target) {
+                        built_targets.push(target.target);
+                    }
+                    continue;
+                }
+                found = true;
+                let outputs = target
+                    .output
+                    .into_iter()
+                    .chain(target.object_files)
+                    .collect::<Vec<PathBuf>>();
+                removed.extend(remove_stale_outputs(&outputs)?);
+            }
+        }
+    }
+    if !found {
+        built_targets.sort();
+        return Err(CleanError::UnknownTarget(name.to_string(), built_targets));
+    }
+    Ok(removed)
+}
+
+/// Removes the whole build directory: the build trees, the include files of make, the cache and
+/// the log.
+pub fn clean_all(build_directory: &Path) -> Result<(), CleanError> {
+    check_is_build_directory(build_directory)?;
+    if build_directory.join(YAMBS_MANIFEST_NAME).exists() {
+        return Err(CleanError::ProjectDirectory(build_directory.to_path_buf()));
+    }
+    log::debug!("Removing {}", build_directory.display());
+    std::fs::remove_dir_all(build_directory)
+        .map_err(|e| FsError::RemoveDirectory(build_directory.to_path_buf(), e))?;
+    Ok(())
+}
+
+// Only directories yambs has built in are cleaned, so that a mistyped build directory does not
+// lose files that yambs did not make.
+fn check_is_build_directory(build_directory: &Path) -> Result<(), CleanError> {
+    if build_directory.join(YAMBS_CACHE_DIRECTORY_NAME).is_dir() {
+        Ok(())
+    } else {
+        Err(CleanError::NotABuildDirectory(
+            build_directory.to_path_buf(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn cleaning_a_target_keeps_the_other_targets() {
+        let directory = tempdir::TempDir::new("clean").unwrap();
+        let build_directory = directory.path();
+        let build_tree = build_directory.join("debug");
+        std::fs::create_dir_all(build_directory.join(YAMBS_CACHE_DIRECTORY_NAME)).unwrap();
+        std::fs::create_dir_all(build_tree.join("app.dir")).unwrap();
+        for file in ["app", "app.dir/main.o", "app.dir/main.d", "libcore.a"] {
+            std::fs::write(build_tree.join(file), "").unwrap();
+        }
+        let progress = serde_json::json!({
+            "targets": [
+                {
+                    "target": "app",
+                    "object_files": [build_tree.join("app.dir/main.o")],
+                    "output": build_tree.join("app"),
+                },
+                { "target": "core", "output": build_tree.join("libcore.a") },
+            ]
+        });
+        std::fs::write(build_tree.join("progress.json"), progress.to_string()).unwrap();
+
+        let removed = clean_target(build_directory, "app").unwrap();
+        assert_eq!(
+            removed,
+            vec![build_tree.join("app"), build_tree.join("app.dir/main.o")]
+        );
+        assert!(!build_tree.join("app.dir/main.d").exists());
+        assert!(build_tree.join("libcore.a").is_file());
+        assert!(matches!(
+            clean_target(build_directory, "ap"),
+            Err(CleanError::UnknownTarget(_, targets)) if targets == vec!["app".to_string(), "core".to_string()]
+        ));
+
+        clean_build_trees(build_directory).unwrap();
+        assert!(!build_tree.exists());
+        clean_all(build_directory).unwrap();
+        assert!(!build_directory.exists());
+        assert!(matches!(
+            clean_all(build_directory),
+            Err(CleanError::NotABuildDirectory(_))
+        ));
+    }
+}
@@ -1,10 +1,14 @@
 use serde::{Deserialize, Serialize};
 
+use crate::build_history::DEFAULT_REPORTED_BUILDS;
 use crate::cli;
 use crate::cli::configurations;
+use crate::embed::EmbeddedFile;
 use crate::errors::{CommandLineError, FsError};
 use crate::generator::GeneratorType;
+use crate::memory_budget::MemoryBudget;
 use crate::parser::types::{Define, Standard};
+use crate::sandbox::SandboxMapping;
 
 // TODO: Need to add tests for C++ validation
 // TODO: Add default values that correctly correspond for 'configuration' when not all options are
@@ -23,6 +27,37 @@ pub struct CommandLine {
     /// Display version and exit
     #[arg(long = "version")]
     pub show_version: bool,
+    /// How errors are printed. json prints them as a JSON object with the file, line and column
+    /// of the manifest they are in, for editors to show them where they are.
+    #[arg(
+        long = "error-format",
+        value_enum,
+        default_value_t = ErrorFormat::Human,
+        global = true
+    )]
+    pub error_format: ErrorFormat,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Print errors with the line of the manifest they are in and a caret pointing at them
+    #[value(name = "human")]
+    Human,
+    /// Print errors as JSON objects
+    #[value(name = "json")]
+    Json,
+}
+
+/// What runs the commands of a build.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Run the build tool of the generated build files, make or ninja
+    #[default]
+    #[value(name = "make")]
+    Make,
+    /// Run the compiles and links of the build plan written with the Makefiles from yambs itself
+    #[value(name = "internal")]
+    Internal,
 }
 
 #[derive(Debug, Clone)]
@@ -46,6 +81,12 @@ impl std::string::ToString for ManifestDirectory {
     }
 }
 
+impl std::convert::From<&std::path::Path> for ManifestDirectory {
+    fn from(f: &std::path::Path) -> Self {
+        Self(f.to_path_buf())
+    }
+}
+
 impl std::str::FromStr for ManifestDirectory {
     type Err = CommandLineError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -59,20 +100,85 @@ impl std::str::FromStr for ManifestDirectory {
 pub enum Subcommand {
     /// Build project specified by manifest YAMBS file.
     Build(BuildOpts),
+    /// Build the project and run the tests specified by the manifest.
+    Test(TestOpts),
+    /// Check that the project configures: parse the manifests, resolve the dependencies and probe
+    /// the toolchain, without generating build files or compiling.
+    Check(CheckOpts),
     /// Print previous invocation line used and exit.
     Remake(RemakeOpts),
+    /// Remove the objects, libraries, executables and makefiles of the builds in a build
+    /// directory.
+    Clean(CleanOpts),
+    /// Inspect the build cache of a build directory.
+    Cache(CacheOpts),
+    /// Create a new project: a manifest with an executable or library, the src and include
+    /// directories and a source to start from.
+    Init(InitOpts),
+    /// Convert legacy lib.mmk / run.mmk files to yambs.toml manifests.
+    ConvertMmk(ConvertMmkOpts),
+    /// Write the dependency graph of the targets in a build directory as a Graphviz DOT file.
+    Graph(GraphOpts),
+    /// Print every chain of dependencies from the targets of a built project to a target or
+    /// external package, to tell why it is built or linked.
+    Why(WhyOpts),
+    /// Report the dependencies of the targets of a built project that they link no symbol from,
+    /// as read from the built objects and libraries with nm.
+    UnusedDeps(UnusedDepsOpts),
+    /// Parse a manifest and print the targets, tests and install artifacts it describes as JSON,
+    /// without writing anything to disk.
+    Introspect(IntrospectOpts),
+    /// Install the artifacts listed in the manifest of a built project.
+    Install(InstallOpts),
+    /// Package the manifests, configuration, toolchain, build files and log of the most recent
+    /// build into a tarball to attach to a bug report.
+    ReproBundle(ReproBundleOpts),
+    /// Write editor configuration for the targets and tests of a built project.
+    Ide(IdeOpts),
+    /// Run a command with the executables and shared libraries of a built project in PATH and
+    /// LD_LIBRARY_PATH, and the variables of the build exported.
+    Exec(ExecOpts),
+    /// Generate the API documentation of the targets of a built project with Doxygen, from their
+    /// headers and include directories.
+    Docs(DocsOpts),
+    /// Report the objects, compile time, binary size, reused objects and warnings of each target
+    /// over the most recent builds of a build directory.
+    Report(ReportOpts),
+    /// Look a program up the way yambs looks up the tools of a toolchain, and print where it was
+    /// found.
+    FindTool(FindToolOpts),
+    /// Check that the compilers of the toolchain work by compiling a sample program with each.
+    /// Builds skip the check for compilers that passed it before.
+    Doctor(DoctorOpts),
+    /// Run a custom command in a sandbox. Used by the generated build files.
+    #[command(hide = true)]
+    Sandbox(SandboxOpts),
+    /// Generate the sources of the files embedded in a target. Used by the generated build files.
+    #[command(hide = true)]
+    Embed(EmbedOpts),
+    /// Run the build plan of a build tree. Used by the internal backend.
+    #[command(hide = true)]
+    RunPlan(RunPlanOpts),
 }
 
 #[derive(clap::Args, Debug)]
-#[command(dont_delimit_trailing_values = true)]
+#[command(dont_delimit_trailing_values = true, args_override_self = true)]
 pub struct BuildOpts {
     /// Input manifest file for YAMBS. By default, Yambs searches for yambs.toml manifest in current directory.
     #[arg(default_value_t, hide_default_value(true), long = "manifest-directory")]
     pub manifest_dir: ManifestDirectory,
+    /// Use the options of a preset in yambs-presets.toml of the manifest directory. Options given on
+    /// the command line override those of the preset.
+    #[arg(long)]
+    pub preset: Option<String>,
+    /// Toolchain file to use instead of .yambs/toolchain.toml of the manifest directory or $HOME.
+    #[arg(long = "toolchain-file")]
+    pub toolchain_file: Option<std::path::PathBuf>,
     /// Set runtime configurations (build configurations, C++ standard, etc)
     #[command(flatten)]
     pub configuration: ConfigurationOpts,
-    /// Set build directory. Generated output by Yambs will be put here. Defaults to current working directory.
+    /// Set build directory. Generated output by Yambs will be put here. Defaults to build_dir of the
+    /// manifest, or the current working directory.
     #[arg(
         long,
         short = 'b',
@@ -81,16 +187,111 @@ pub struct BuildOpts {
         value_parser
     )]
     pub build_directory: cli::BuildDirectory,
-    /// Toggles verbose output.
-    #[arg(short = 'v', long = "verbose")]
-    pub verbose: bool,
+    /// Toggles verbose output. Given twice, the compile and link command of each target is printed
+    /// with the origin of each of its flags.
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    pub verbose: u8,
     /// Specific target to build
     #[arg(long)]
     pub target: Option<String>,
+    /// Pick the targets to build from a list of the targets of the project. The targets picked are
+    /// remembered in the build directory.
+    #[arg(long, conflicts_with = "target")]
+    pub interactive: bool,
+    /// Build the examples as well, which are otherwise only built with --target examples.
+    #[arg(long)]
+    pub examples: bool,
+    /// Build the tests and examples of dependency manifests as well, which are otherwise left out
+    /// along with what only they depend on.
+    #[arg(long)]
+    pub with_dep_tests: bool,
+    /// Record compile durations and start the slowest translation units first in the following builds.
+    #[arg(long = "schedule-by-duration")]
+    pub schedule_by_duration: bool,
+    /// Print the sections removed by the linker for targets with gc_sections enabled.
+    #[arg(long = "report-gc-sections")]
+    pub report_gc_sections: bool,
+    /// Report the warnings of the compiled objects, write them to warnings.json in the build files
+    /// directory and compare them with the warnings baseline of the build directory.
+    #[arg(long = "warnings-report")]
+    pub warnings_report: bool,
+    /// Fail if there are more warnings than this not in the warnings baseline. Implies
+    /// --warnings-report.
+    #[arg(long = "max-new-warnings")]
+    pub max_new_warnings: Option<usize>,
+    /// Stop starting new compiles once this many translation units failed to compile, letting
+    /// those running finish, and print a report of the failures. Make keeps going after a failure
+    /// until then.
+    #[arg(
+        long = "max-errors",
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub max_errors: Option<u32>,
+    /// Run no more compiles at once than fit in this many gigabytes of memory, as estimated from
+    /// the size of the sources and template_heavy of the targets, and link the targets using
+    /// link-time optimization one at a time.
+    #[arg(long = "max-memory", value_name = "GB")]
+    pub max_memory: Option<MemoryBudget>,
+    /// What runs the build: make, which runs the generated build files, or internal, which runs
+    /// the same compiles and links from yambs. Targets are picked and progress is reported the
+    /// same way by both.
+    #[arg(long, value_enum, default_value_t)]
+    pub backend: Backend,
+    /// Print the commands building this target in full, while the rest of the build only shows
+    /// progress. Can be given several times.
+    #[arg(long = "verbose-target", value_name = "NAME")]
+    pub verbose_targets: Vec<String>,
+    /// Print the commands mentioning this file in full, such as the one compiling it. Can be given
+    /// several times.
+    #[arg(long = "verbose-file", value_name = "PATH")]
+    pub verbose_files: Vec<std::path::PathBuf>,
+    /// Write a line of JSON to this file for every compile and link make starts and finishes,
+    /// naming the target it is for along with the progress of the build.
+    #[arg(long = "progress-json", value_name = "FILE")]
+    pub progress_json: Option<std::path::PathBuf>,
+    /// Replace the warnings baseline with the current warnings. Implies --warnings-report.
+    #[arg(long = "update-warnings-baseline")]
+    pub update_warnings_baseline: bool,
+    /// Parse the manifests and resolve the targets again, instead of reusing those of the previous
+    /// build when the manifests are unchanged.
+    #[arg(long)]
+    pub reconfigure: bool,
+    /// Write a .gitignore into a build directory inside the source tree, so that git ignores the
+    /// files generated in it.
+    #[arg(long = "write-gitignore")]
+    pub write_gitignore: bool,
+    /// Fail instead of generating build files in the source directory of the project.
+    #[arg(long = "enforce-out-of-source")]
+    pub enforce_out_of_source: bool,
+    /// Regenerate build files that were edited since they were generated, discarding the edits,
+    /// instead of failing.
+    #[arg(long = "allow-modified-build-files")]
+    pub allow_modified_build_files: bool,
     #[arg(hide = true)]
     pub make_args: Vec<String>,
 }
 
+#[derive(clap::Args, Debug)]
+pub struct TestOpts {
+    #[command(flatten)]
+    pub build_opts: BuildOpts,
+    /// Only run the test with this name. Can be given multiple times.
+    #[arg(long = "test")]
+    pub tests: Vec<String>,
+    /// Only run the tests of this shard, counting from 0, when splitting them into --shard-count
+    /// shards.
+    #[arg(long = "shard-index", requires = "shard_count")]
+    pub shard_index: Option<usize>,
+    /// Number of shards to split the tests into.
+    #[arg(long = "shard-count", requires = "shard_index")]
+    pub shard_count: Option<usize>,
+    /// Skip tests that passed before and run the same binaries with the same command and
+    /// environment, reporting them as cached passes.
+    #[arg(long = "cache-results")]
+    pub cache_results: bool,
+}
+
 #[derive(clap::Args, Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ConfigurationOpts {
     /// Build configuration to use
@@ -100,11 +301,52 @@ pub struct ConfigurationOpts {
     #[arg(long = "std",
           value_parser = clap::builder::ValueParser::new(Standard::parse))]
     pub standard: Option<Standard>,
-    #[arg(default_value_t = GeneratorType::GNUMakefiles, short = 'g', value_enum)]
+    /// Build system to generate build files for (make or ninja)
+    #[arg(default_value_t = GeneratorType::GNUMakefiles, short = 'g', long = "generator", value_enum)]
     pub generator_type: GeneratorType,
-    /// Macro definitions to be passed to the compiler upon build
+    /// Macro to define for every target, as NAME or NAME=value. Overrides the defines of the same
+    /// macro in the manifests. Can be given multiple times.
     #[arg(short = 'D', value_parser = Define::from_cli)]
     pub defines: Vec<Define>,
+    /// Sanitizer to compile and link with (address, undefined, thread, memory or leak). Can be
+    /// given multiple times.
+    #[arg(long = "sanitizer")]
+    pub sanitizers: Vec<configurations::Sanitizer>,
+    /// Optimization level (0, 1, 2, 3, s or z). Defaults to 0 for debug builds and 3 for release
+    /// builds. Targets with opt_level in the manifest use their own level.
+    #[arg(long = "opt-level")]
+    pub opt_level: Option<configurations::OptLevel>,
+}
+
+impl ConfigurationOpts {
+    /// Command line options giving this configuration.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec!["--build-type".to_string(), self.build_type.to_string()];
+        if let Some(generator) = clap::ValueEnum::to_possible_value(&self.generator_type) {
+            args.push("--generator".to_string());
+            args.push(generator.get_name().to_string());
+        }
+        if let Some(ref standard) = self.standard {
+            args.push("--std".to_string());
+            args.push(standard.to_string());
+        }
+        for define in &self.defines {
+            args.push("-D".to_string());
+            args.push(match define.value {
+                Some(ref value) => format!("{}={}", define.macro_, value),
+                None => define.macro_.clone(),
+            });
+        }
+        for sanitizer in &self.sanitizers {
+            args.push("--sanitizer".to_string());
+            args.push(sanitizer.to_string());
+        }
+        if let Some(ref opt_level) = self.opt_level {
+            args.push("--opt-level".to_string());
+            args.push(opt_level.to_string());
+        }
+        args
+    }
 }
 
 #[derive(clap::Args, Debug)]
@@ -114,6 +356,406 @@ pub struct RemakeOpts {
     pub build_directory: cli::BuildDirectory,
 }
 
+#[derive(clap::Args, Debug)]
+pub struct CleanOpts {
+    /// Build directory to clean. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Only remove the output and objects of this target, so that it is compiled and linked
+    /// again by the next build.
+    #[arg(long, value_name = "NAME", conflicts_with = "all")]
+    pub target: Option<String>,
+    /// Remove the whole build directory, including the cache and the include files of make.
+    #[arg(long)]
+    pub all: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CacheOpts {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Show the information stored in the cache, such as the toolchain fingerprint.
+    Info(CacheInfoOpts),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CacheInfoOpts {
+    /// Build directory to read the cache from. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ConvertMmkOpts {
+    /// Directory containing lib.mmk and / or run.mmk. Directories listed in MMK_REQUIRE are converted as well.
+    #[arg(value_parser)]
+    pub directory: std::path::PathBuf,
+    /// Overwrite existing yambs.toml manifests.
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct InitOpts {
+    /// Directory to create the project in. Defaults to current working directory.
+    #[arg(default_value = ".", value_parser)]
+    pub directory: std::path::PathBuf,
+    /// Name of the target. Defaults to the name of the directory.
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Create a library.
+    #[arg(long, conflicts_with = "bin")]
+    pub lib: bool,
+    /// Create an executable. This is the default.
+    #[arg(long)]
+    pub bin: bool,
+}
+
+/// Prefix artifacts are installed into, unless told otherwise.
+pub const DEFAULT_INSTALL_PREFIX: &str = "/usr/local";
+
+#[derive(clap::Args, Debug)]
+pub struct InstallOpts {
+    /// Build directory of the built project. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Build configuration to install the artifacts of
+    #[arg(default_value_t, long = "build-type")]
+    pub build_type: configurations::BuildType,
+    /// Prefix the destinations of the artifacts are relative to. Defaults to /usr/local.
+    #[arg(long)]
+    pub prefix: Option<std::path::PathBuf>,
+    /// Staging directory the prefix is placed in, for packaging.
+    #[arg(long)]
+    pub destdir: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DocsOpts {
+    /// Targets to document. Defaults to the targets of the manifest of the project.
+    pub targets: Vec<String>,
+    /// Build directory of the built project. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Program run with the generated Doxyfile as its argument. Defaults to doxygen.
+    #[arg(long)]
+    pub tool: Option<std::path::PathBuf>,
+    /// Install the HTML documentation of each target into share/doc/<target> of the prefix.
+    #[arg(long)]
+    pub install: bool,
+    /// Prefix to install the documentation into. Defaults to /usr/local.
+    #[arg(long, requires = "install")]
+    pub prefix: Option<std::path::PathBuf>,
+    /// Staging directory the prefix is placed in, for packaging.
+    #[arg(long, requires = "install")]
+    pub destdir: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ReproBundleOpts {
+    /// Build directory of the build to reproduce. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// File to write the bundle to. Defaults to yambs-repro-bundle.tar.gz in the build directory.
+    #[arg(long, short = 'o')]
+    pub output: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GraphOpts {
+    /// Build directory to read the build files from. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Build configuration to read the build files of
+    #[arg(default_value_t, long = "build-type")]
+    pub build_type: configurations::BuildType,
+    /// Annotate targets with the durations recorded by builds with --schedule-by-duration,
+    /// highlight the critical path and report the slowest targets.
+    #[arg(long = "critical-path")]
+    pub critical_path: bool,
+    /// File to write the graph to. Defaults to graph.dot in the build files directory.
+    #[arg(long, short = 'o')]
+    pub output: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ReportOpts {
+    /// Build directory to report the builds of. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Build configuration to report the builds of
+    #[arg(default_value_t, long = "build-type")]
+    pub build_type: configurations::BuildType,
+    /// Number of builds to report, counting back from the most recent one.
+    #[arg(long, short = 'n', default_value_t = DEFAULT_REPORTED_BUILDS)]
+    pub last: usize,
+    /// Print the report as JSON instead of as a table.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct WhyOpts {
+    /// Target or external package, such as one found with pkg-config, to explain.
+    pub name: String,
+    /// Build directory to read the build files from. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Build configuration to read the build files of
+    #[arg(default_value_t, long = "build-type")]
+    pub build_type: configurations::BuildType,
+    /// Also write the chains of dependencies to this file as a Graphviz DOT graph.
+    #[arg(long)]
+    pub dot: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct UnusedDepsOpts {
+    /// Build directory to read the built targets from. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Build configuration to read the built targets of
+    #[arg(default_value_t, long = "build-type")]
+    pub build_type: configurations::BuildType,
+    /// Print the report as JSON.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FindToolOpts {
+    /// Program to look for, such as clang++. Extensions of executables are tried on Windows.
+    pub name: std::path::PathBuf,
+    /// Only accept a program whose version, as printed by --version, matches the requirement,
+    /// such as ">=15".
+    #[arg(long)]
+    pub version: Option<semver::VersionReq>,
+    /// Toolchain file whose search_paths are searched before PATH, instead of
+    /// .yambs/toolchain.toml of the current directory or $HOME.
+    #[arg(long = "toolchain-file")]
+    pub toolchain_file: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct IntrospectOpts {
+    /// Directory of the manifest. Paths of the manifest are relative to it, also when it is read
+    /// from stdin. Defaults to current working directory.
+    #[arg(default_value_t, hide_default_value(true), long = "manifest-directory")]
+    pub manifest_dir: ManifestDirectory,
+    /// Read the manifest from stdin instead of yambs.toml of the manifest directory, such as the
+    /// unsaved content of an editor.
+    #[arg(long = "stdin-manifest")]
+    pub stdin_manifest: bool,
+    /// Build directory that ${YAMBS_BUILD_DIR} of the manifest expands to. Defaults to current
+    /// working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Build configuration that ${YAMBS_BUILD_TYPE} of the manifest expands to
+    #[arg(default_value_t, long = "build-type")]
+    pub build_type: configurations::BuildType,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CheckOpts {
+    /// Directory of the manifest to check. Defaults to current working directory.
+    #[arg(default_value_t, hide_default_value(true), long = "manifest-directory")]
+    pub manifest_dir: ManifestDirectory,
+    /// Toolchain file to use instead of .yambs/toolchain.toml of the manifest directory or $HOME.
+    #[arg(long = "toolchain-file")]
+    pub toolchain_file: Option<std::path::PathBuf>,
+    /// Build directory that ${YAMBS_BUILD_DIR} of the manifests expands to. Nothing is written to
+    /// it. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Build configuration to check the manifests with
+    #[arg(default_value_t, long = "build-type")]
+    pub build_type: configurations::BuildType,
+    /// C/C++ standard to check the standards of dependencies against, instead of the standard of
+    /// the manifest
+    #[arg(long = "std",
+          value_parser = clap::builder::ValueParser::new(Standard::parse))]
+    pub standard: Option<Standard>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct IdeOpts {
+    #[command(subcommand)]
+    pub command: IdeCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum IdeCommand {
+    /// Write .vscode/tasks.json with tasks building the project, its targets and running its
+    /// tests, and .vscode/launch.json with debug configurations of its executables and tests.
+    Vscode(VscodeOpts),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct VscodeOpts {
+    /// Build directory of the build to configure the editor for. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Workspace directory to write the .vscode directory to. Defaults to the manifest directory.
+    #[arg(long)]
+    pub workspace: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DoctorOpts {
+    /// Manifest directory whose .yambs/toolchain.toml is used. Defaults to current directory.
+    #[arg(default_value_t, hide_default_value(true), long = "manifest-directory")]
+    pub manifest_dir: ManifestDirectory,
+    /// Toolchain file to use instead of .yambs/toolchain.toml of the manifest directory or $HOME.
+    #[arg(long = "toolchain-file")]
+    pub toolchain_file: Option<std::path::PathBuf>,
+    /// Compile the sample programs even with compilers that passed the check before.
+    #[arg(long)]
+    pub recheck: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ExecOpts {
+    /// Build directory of the built project. Defaults to current working directory.
+    #[arg(
+        long,
+        short = 'b',
+        default_value_t,
+        hide_default_value(true),
+        value_parser
+    )]
+    pub build_directory: cli::BuildDirectory,
+    /// Build configuration to run the command with the artifacts of. Defaults to that of the most
+    /// recent build.
+    #[arg(long = "build-type")]
+    pub build_type: Option<configurations::BuildType>,
+    /// Command to run
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SandboxOpts {
+    /// Directory to run the command in. Any existing content is removed.
+    #[arg(long)]
+    pub directory: std::path::PathBuf,
+    /// Input made available in the sandbox, given as <path in sandbox>=<path>.
+    #[arg(long = "input", value_parser = SandboxMapping::parse)]
+    pub inputs: Vec<SandboxMapping>,
+    /// Output copied out of the sandbox, given as <path in sandbox>=<path>.
+    #[arg(long = "output", value_parser = SandboxMapping::parse)]
+    pub outputs: Vec<SandboxMapping>,
+    /// Command to run
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct EmbedOpts {
+    /// Target the files are embedded in, which prefixes the generated symbols.
+    #[arg(long)]
+    pub target: String,
+    /// Header to generate.
+    #[arg(long)]
+    pub header: std::path::PathBuf,
+    /// Source to generate.
+    #[arg(long)]
+    pub source: std::path::PathBuf,
+    /// File to embed, given as <name>=<path>.
+    #[arg(long = "file", value_parser = EmbeddedFile::parse)]
+    pub files: Vec<EmbeddedFile>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RunPlanOpts {
+    /// Directory of the build plan, which the commands are run in.
+    #[arg(long)]
+    pub directory: std::path::PathBuf,
+    /// Number of commands run at once.
+    #[arg(short = 'j', long)]
+    pub jobs: usize,
+    /// Go on with the steps that do not depend on a step that failed.
+    #[arg(short = 'k', long = "keep-going")]
+    pub keep_going: bool,
+    /// Targets to build. All targets except for the examples are built if none are given.
+    pub targets: Vec<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,6 +772,83 @@ mod tests {
         assert_eq!(build_opts.make_args, vec!["-j", "10", "x"]);
     }
 
+    #[test]
+    fn error_budget_has_to_allow_at_least_one_failure() {
+        let command_line = CommandLine::parse_from(["yambs", "build", "--max-errors", "3"]);
+        let build_opts = match command_line.subcommand {
+            Some(Subcommand::Build(b)) => b,
+            _ => panic!("Not build opts"),
+        };
+        assert_eq!(build_opts.max_errors, Some(3));
+        assert!(CommandLine::try_parse_from(["yambs", "build", "--max-errors", "0"]).is_err());
+    }
+
+    #[test]
+    fn sandbox_arguments_are_parsed() {
+        let args = [
+            "yambs",
+            "sandbox",
+            "--directory",
+            "/build/sandbox",
+            "--input",
+            "gen.py=/project/gen.py",
+            "--output",
+            "out.cpp=/build/generated/out.cpp",
+            "--",
+            "python3",
+            "gen.py",
+        ];
+        let command_line = CommandLine::parse_from(args);
+        let sandbox_opts = match command_line.subcommand {
+            Some(Subcommand::Sandbox(s)) => s,
+            _ => panic!("Not sandbox opts"),
+        };
+        assert_eq!(
+            sandbox_opts.inputs,
+            vec![SandboxMapping::parse("gen.py=/project/gen.py").unwrap()]
+        );
+        assert_eq!(
+            sandbox_opts.outputs,
+            vec![SandboxMapping::parse("out.cpp=/build/generated/out.cpp").unwrap()]
+        );
+        assert_eq!(sandbox_opts.command, vec!["python3", "gen.py"]);
+    }
+
+    #[test]
+    fn configuration_is_given_again_by_its_arguments() {
+        let args = [
+            "yambs",
+            "build",
+            "--build-type",
+            "release",
+            "-g",
+            "ninja",
+            "-D",
+            "FEATURE=1",
+        ];
+        let build_opts = match CommandLine::parse_from(args).subcommand {
+            Some(Subcommand::Build(b)) => b,
+            _ => panic!("Not build opts"),
+        };
+        let configuration_args = build_opts.configuration.to_args();
+        let reparsed = match CommandLine::parse_from(
+            ["yambs", "build"]
+                .iter()
+                .map(|arg| arg.to_string())
+                .chain(configuration_args),
+        )
+        .subcommand
+        {
+            Some(Subcommand::Build(b)) => b,
+            _ => panic!("Not build opts"),
+        };
+        assert_eq!(reparsed.configuration.generator_type, GeneratorType::Ninja);
+        assert_eq!(
+            reparsed.configuration.to_args(),
+            build_opts.configuration.to_args()
+        );
+    }
+
     #[test]
     fn test_cli() {
         use clap::CommandFactory;
@@ -1,12 +1,16 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::errors::CommandLineError;
+
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum ConfigurationError {
     #[error("Build configuration \"{0}\" used is not valid.")]
     InvalidBuildType(String),
     #[error("Invalid sanitizer option set: {0}")]
     InvalidSanitizerOption(String),
+    #[error("Invalid optimization level \"{0}\". Valid levels are 0, 1, 2, 3, s and z.")]
+    InvalidOptLevel(String),
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize, Default)]
@@ -39,6 +43,7 @@ impl std::string::ToString for BuildType {
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Sanitizer {
     Address,
+    Undefined,
     Thread,
     Memory,
     Leak,
@@ -50,6 +55,7 @@ impl std::str::FromStr for Sanitizer {
     fn from_str(sanitizer: &str) -> Result<Self, Self::Err> {
         match sanitizer.to_lowercase().as_str() {
             "address" => Ok(Sanitizer::Address),
+            "undefined" => Ok(Sanitizer::Undefined),
             "thread" => Ok(Sanitizer::Thread),
             "memory" => Ok(Sanitizer::Memory),
             "leak" => Ok(Sanitizer::Leak),
@@ -62,6 +68,7 @@ impl std::string::ToString for Sanitizer {
     fn to_string(&self) -> String {
         match self {
             Sanitizer::Address => "address".to_string(),
+            Sanitizer::Undefined => "undefined".to_string(),
             Sanitizer::Thread => "thread".to_string(),
             Sanitizer::Memory => "memory".to_string(),
             Sanitizer::Leak => "leak".to_string(),
@@ -69,6 +76,88 @@ impl std::string::ToString for Sanitizer {
     }
 }
 
+/// Optimization level of the compiler, as given to -O.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum OptLevel {
+    #[serde(rename = "0")]
+    O0,
+    #[serde(rename = "1")]
+    O1,
+    #[serde(rename = "2")]
+    O2,
+    #[serde(rename = "3")]
+    O3,
+    /// Optimized for size.
+    #[serde(rename = "s")]
+    Os,
+    /// Optimized for size more aggressively than `Os`.
+    #[serde(rename = "z")]
+    Oz,
+}
+
+impl OptLevel {
+    /// Level used by `build_type` unless told otherwise.
+    pub fn default_for(build_type: &BuildType) -> Self {
+        match build_type {
+            BuildType::Debug => OptLevel::O0,
+            BuildType::Release => OptLevel::O3,
+        }
+    }
+
+    /// Flag selecting the level with the compiler of `compiler_type` and `compiler_version`.
+    /// GCC only supports -Oz from version 12, and is given -Os before that.
+    pub fn flag(&self, compiler_type: &crate::compiler::Type, compiler_version: &str) -> String {
+        if *self == OptLevel::Oz && *compiler_type == crate::compiler::Type::Gcc {
+            let major = compiler_version
+                .split('.')
+                .next()
+                .and_then(|major| major.parse::<u32>().ok());
+            if major.is_some_and(|major| major < 12) {
+                return OptLevel::Os.flag(compiler_type, compiler_version);
+            }
+        }
+        format!("-O{}", self)
+    }
+}
+
+impl std::str::FromStr for OptLevel {
+    type Err = ConfigurationError;
+
+    fn from_str(opt_level: &str) -> Result<Self, Self::Err> {
+        match opt_level {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "3" => Ok(OptLevel::O3),
+            "s" => Ok(OptLevel::Os),
+            "z" => Ok(OptLevel::Oz),
+            _ => Err(Self::Err::InvalidOptLevel(opt_level.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self {
+            OptLevel::O0 => "0",
+            OptLevel::O1 => "1",
+            OptLevel::O2 => "2",
+            OptLevel::O3 => "3",
+            OptLevel::Os => "s",
+            OptLevel::Oz => "z",
+        };
+        write!(f, "{}", level)
+    }
+}
+
+/// Checks that the sanitizers can be used together.
+pub fn validate_sanitizers(sanitizers: &[Sanitizer]) -> Result<(), CommandLineError> {
+    if sanitizers.contains(&Sanitizer::Address) && sanitizers.contains(&Sanitizer::Thread) {
+        return Err(CommandLineError::IllegalSanitizerCombination);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,4 +184,25 @@ mod tests {
             ConfigurationError::InvalidBuildType("relwithdebinfo".to_string())
         );
     }
+
+    #[test]
+    fn address_and_thread_sanitizers_cannot_be_combined() {
+        assert!(validate_sanitizers(&[Sanitizer::Address, Sanitizer::Undefined]).is_ok());
+        assert!(matches!(
+            validate_sanitizers(&[Sanitizer::Thread, Sanitizer::Address]),
+            Err(CommandLineError::IllegalSanitizerCombination)
+        ));
+    }
+
+    #[test]
+    fn opt_level_z_falls_back_to_s_with_old_gcc() {
+        let opt_level = OptLevel::from_str("z").unwrap();
+        assert_eq!(opt_level.flag(&crate::compiler::Type::Gcc, "11.4.0"), "-Os");
+        assert_eq!(opt_level.flag(&crate::compiler::Type::Gcc, "13.2.0"), "-Oz");
+        assert_eq!(
+            opt_level.flag(&crate::compiler::Type::Clang, "10.0.0"),
+            "-Oz"
+        );
+        assert!(OptLevel::from_str("fast").is_err());
+    }
 }
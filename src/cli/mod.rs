@@ -1,5 +1,6 @@
 pub mod command_line;
 pub mod configurations;
+pub mod presets;
 
 use crate::errors::CommandLineError;
 
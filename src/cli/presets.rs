@@ -0,0 +1,194 @@
+// Presets are named bundles of command line options, shared through yambs-presets.toml next to the
+// manifest:
+//
+//   [preset.asan-clang]
+//   build_type = "debug"
+//   toolchain_file = "toolchains/clang.toml"
+//   sanitizers = ["address", "undefined"]
+//
+// A preset is applied by putting its options on the command line ahead of those given by the
+// user, so options given explicitly override the preset and every option is validated the same
+// way no matter where it came from.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+pub const PRESETS_FILE_NAME: &str = "yambs-presets.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PresetError {
+    #[error("Failed to read presets file {0:?}")]
+    FailedToRead(PathBuf, #[source] std::io::Error),
+    #[error("Failed to parse presets file {0:?}")]
+    FailedToParse(PathBuf, #[source] toml::de::Error),
+    #[error("No preset named \"{0}\" in {1:?}. Available presets: {2}")]
+    NotFound(String, PathBuf, String),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Presets {
+    #[serde(default, rename = "preset")]
+    pub presets: BTreeMap<String, Preset>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub build_type: Option<String>,
+    pub std: Option<String>,
+    pub generator: Option<String>,
+    /// Relative to the directory of the presets file.
+    pub toolchain_file: Option<PathBuf>,
+    /// Relative to the directory of the presets file.
+    pub build_directory: Option<PathBuf>,
+    #[serde(default)]
+    pub sanitizers: Vec<String>,
+    /// Macro definitions on the form accepted by -D, such as "NAME=VALUE".
+    #[serde(default)]
+    pub defines: Vec<String>,
+    pub opt_level: Option<String>,
+}
+
+impl Presets {
+    pub fn from_file(path: &Path) -> Result<Self, PresetError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| PresetError::FailedToRead(path.to_path_buf(), e))?;
+        toml::from_str(&content).map_err(|e| PresetError::FailedToParse(path.to_path_buf(), e))
+    }
+
+    /// Presets of the manifest in `manifest_directory`.
+    pub fn from_manifest_directory(manifest_directory: &Path) -> Result<Self, PresetError> {
+        Self::from_file(&manifest_directory.join(PRESETS_FILE_NAME))
+    }
+
+    pub fn get(&self, name: &str, presets_file: &Path) -> Result<&Preset, PresetError> {
+        self.presets.get(name).ok_or_else(|| {
+            PresetError::NotFound(
+                name.to_string(),
+                presets_file.to_path_buf(),
+                self.presets
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            )
+        })
+    }
+}
+
+impl Preset {
+    /// Command line options of the preset. Paths are made relative to the directory of the presets
+    /// file.
+    pub fn to_args(&self, presets_directory: &Path) -> Vec<OsString> {
+        let mut args = Vec::new();
+        let mut push = |option: &str, value: OsString| {
+            args.push(OsString::from(option));
+            args.push(value);
+        };
+        if let Some(ref build_type) = self.build_type {
+            push("--build-type", OsString::from(build_type));
+        }
+        if let Some(ref std) = self.std {
+            push("--std", OsString::from(std));
+        }
+        if let Some(ref generator) = self.generator {
+            push("-g", OsString::from(generator));
+        }
+        if let Some(ref toolchain_file) = self.toolchain_file {
+            push(
+                "--toolchain-file",
+                presets_directory.join(toolchain_file).into_os_string(),
+            );
+        }
+        if let Some(ref build_directory) = self.build_directory {
+            push(
+                "--build-directory",
+                presets_directory.join(build_directory).into_os_string(),
+            );
+        }
+        for sanitizer in &self.sanitizers {
+            push("--sanitizer", OsString::from(sanitizer));
+        }
+        for define in &self.defines {
+            push("-D", OsString::from(define));
+        }
+        if let Some(ref opt_level) = self.opt_level {
+            push("--opt-level", OsString::from(opt_level));
+        }
+        args
+    }
+}
+
+/// Inserts the options of `preset` into `args`, right after the subcommand, so that options
+/// following it on the command line take precedence.
+pub fn apply_preset(args: &[OsString], preset: &Preset, presets_directory: &Path) -> Vec<OsString> {
+    let subcommand_position = args.len().min(2);
+    let mut expanded = args[..subcommand_position].to_vec();
+    expanded.extend(preset.to_args(presets_directory));
+    expanded.extend_from_slice(&args[subcommand_position..]);
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn preset_options_come_before_those_of_the_command_line() {
+        let presets = toml::from_str::<Presets>(
+            r#"
+            [preset.asan-clang]
+            build_type = "debug"
+            toolchain_file = "toolchains/clang.toml"
+            sanitizers = ["address", "undefined"]
+            defines = ["CHECKED=1"]
+            "#,
+        )
+        .unwrap();
+        let preset = presets
+            .get("asan-clang", Path::new("/project/yambs-presets.toml"))
+            .unwrap();
+        let args = ["yambs", "build", "--build-type", "release"]
+            .iter()
+            .map(OsString::from)
+            .collect::<Vec<OsString>>();
+
+        let expanded = apply_preset(&args, preset, Path::new("/project"));
+        assert_eq!(
+            expanded,
+            [
+                "yambs",
+                "build",
+                "--build-type",
+                "debug",
+                "--toolchain-file",
+                "/project/toolchains/clang.toml",
+                "--sanitizer",
+                "address",
+                "--sanitizer",
+                "undefined",
+                "-D",
+                "CHECKED=1",
+                "--build-type",
+                "release",
+            ]
+            .iter()
+            .map(OsString::from)
+            .collect::<Vec<OsString>>()
+        );
+    }
+
+    #[test]
+    fn missing_preset_lists_available_presets() {
+        let presets = toml::from_str::<Presets>("[preset.a]\n[preset.b]\n").unwrap();
+        let error = presets
+            .get("c", Path::new("yambs-presets.toml"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No preset named \"c\" in \"yambs-presets.toml\". Available presets: a, b"
+        );
+    }
+}
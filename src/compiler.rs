@@ -1,17 +1,80 @@
 use std::default::Default;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use textwrap::indent;
 
+use crate::build_file_hashes::ContentHasher;
 use crate::errors;
 use crate::toolchain::{ToolchainCCData, ToolchainCXXData};
 use crate::utility;
 
 pub trait Compiler {
     fn evaluate(&self, test_dir: &Path) -> Result<(), CompilerError>;
+    /// Identifies the compiler evaluated by `evaluate`, or None if its executable can not be
+    /// found.
+    fn evaluation_key(&self) -> Option<String>;
+}
+
+/// Compilers that passed their evaluation, kept in a cache shared by the builds of the user, so
+/// that a compiler is evaluated once instead of on every configure. A compiler that is replaced or
+/// upgraded gets another key, as its modification time changes, and is evaluated again.
+#[derive(Debug, Clone)]
+pub struct EvaluationCache {
+    directory: PathBuf,
+}
+
+impl EvaluationCache {
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            directory: directory.to_path_buf(),
+        }
+    }
+
+    /// Cache shared by the builds of the user.
+    pub fn default_directory() -> Option<PathBuf> {
+        home::home_dir().map(|home| home.join(".yambs").join("compiler_evaluations"))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.directory.join(key).is_file()
+    }
+
+    pub fn insert(&self, key: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::File::create(self.directory.join(key)).map(|_| ())
+    }
+}
+
+// Key of the evaluation of `compiler_exe`, from the executable it resolves to along PATH, its
+// modification time and the flags of the sample compilation. The version of yambs is included, as
+// another version may evaluate compilers differently.
+fn evaluation_key(compiler_exe: &Path, sample_flags: &[&str]) -> Option<String> {
+    let executable = if compiler_exe.is_file() {
+        compiler_exe.to_path_buf()
+    } else {
+        let mut search_options = crate::FindProgramOptions::new();
+        search_options.with_path_env();
+        crate::find_program(compiler_exe, search_options)?
+    };
+    let executable = executable.canonicalize().ok()?;
+    let modified = std::fs::metadata(&executable)
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    let modified = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut hasher = ContentHasher::new();
+    hasher
+        .add_path(&executable)
+        .add(modified.as_nanos().to_le_bytes());
+    for flag in sample_flags {
+        hasher.add(flag);
+    }
+    hasher.add(env!("CARGO_PKG_VERSION"));
+    Some(format!("{:016x}", hasher.finish()))
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -178,6 +241,10 @@ impl Compiler for CCCompiler {
         log::debug!("Running sample build worked!");
         Ok(())
     }
+
+    fn evaluation_key(&self) -> Option<String> {
+        evaluation_key(&self.compiler_exe, &["main.c", "-o", "a.out"])
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
@@ -271,6 +338,10 @@ impl Compiler for CXXCompiler {
         log::debug!("Running sample build with compiler specified in CXX... OK");
         Ok(())
     }
+
+    fn evaluation_key(&self) -> Option<String> {
+        evaluation_key(&self.compiler_exe, &["main.cpp", "-o", "a.out"])
+    }
 }
 
 fn try_get_version(compiler_exe: &std::path::Path) -> Result<semver::Version, CompilerError> {
@@ -339,3 +410,27 @@ impl std::string::ToString for CXXCompiler {
         self.compiler_exe.display().to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluation_key_changes_when_compiler_is_replaced() {
+        let tempdir = tempdir::TempDir::new("compiler").unwrap();
+        let compiler_exe = tempdir.path().join("g++");
+        let compiler = std::fs::File::create(&compiler_exe).unwrap();
+        compiler
+            .set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let key = evaluation_key(&compiler_exe, &["main.cpp"]).unwrap();
+        let cache = EvaluationCache::new(&tempdir.path().join("evaluations"));
+        assert!(!cache.contains(&key));
+        cache.insert(&key).unwrap();
+        assert!(cache.contains(&key));
+
+        compiler.set_modified(std::time::SystemTime::now()).unwrap();
+        let replaced_key = evaluation_key(&compiler_exe, &["main.cpp"]).unwrap();
+        assert!(!cache.contains(&replaced_key));
+    }
+}
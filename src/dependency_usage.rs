@@ -0,0 +1,187 @@
+// Tells which of the dependencies a target declares it links nothing from, by comparing the
+// symbols its objects leave undefined with those the libraries of its dependencies define, as read
+// by nm from a built project.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::environment::Environment;
+use crate::generator::targets::ProgressTrackingTarget;
+use crate::link_errors::{self, LibraryDefinitions};
+
+/// Dependency of a target that defines none of the symbols the target references.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct UnusedDependency {
+    pub target: String,
+    pub dependency: String,
+    /// Library built by the dependency.
+    pub library: PathBuf,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct DependencyUsageReport {
+    pub unused: Vec<UnusedDependency>,
+    /// Targets with dependencies that could not be told about, as their objects or the libraries
+    /// of their dependencies have not been built, or nm could not read them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown: Vec<String>,
+}
+
+impl DependencyUsageReport {
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for unused in &self.unused {
+            text.push_str(&format!(
+                "{}: links nothing from {} ({}). Remove it from the dependencies of {} unless it is needed for its headers alone.\n",
+                unused.target,
+                unused.dependency,
+                unused.library.display(),
+                unused.target
+            ));
+        }
+        for target in &self.unknown {
+            text.push_str(&format!(
+                "{}: not every dependency could be checked, as not everything it links has been built\n",
+                target
+            ));
+        }
+        text
+    }
+}
+
+/// Dependencies of `targets`, as built in a build directory, that the targets link no symbol from.
+pub fn unused_dependencies(targets: &[ProgressTrackingTarget]) -> DependencyUsageReport {
+    let libraries = link_errors::library_definitions(targets);
+    let mut report = DependencyUsageReport::default();
+    // Targets such as `all` only name the targets they build.
+    let linked = targets
+        .iter()
+        .filter(|target| target.output.is_some() && !target.dependencies.is_empty());
+    for target in linked {
+        let Some(referenced) = referenced_symbols(&target.object_files) else {
+            report.unknown.push(target.target.clone());
+            continue;
+        };
+        let (unused, checked) = unused_by(target, &referenced, &libraries);
+        report.unused.extend(unused);
+        if !checked {
+            report.unknown.push(target.target.clone());
+        }
+    }
+    report
+}
+
+// Dependencies of `target` defining none of `referenced`, and whether the libraries of all of them
+// were read.
+fn unused_by(
+    target: &ProgressTrackingTarget,
+    referenced: &HashSet<String>,
+    libraries: &[LibraryDefinitions],
+) -> (Vec<UnusedDependency>, bool) {
+    let mut checked = true;
+    let mut unused = Vec::new();
+    for dependency in &target.dependencies {
+        let Some(library) = libraries
+            .iter()
+            .find(|library| &library.target == dependency)
+        else {
+            checked = false;
+            continue;
+        };
+        if !referenced.iter().any(|symbol| library.defines(symbol)) {
+            unused.push(UnusedDependency {
+                target: target.target.clone(),
+                dependency: dependency.clone(),
+                library: library.library.clone(),
+            });
+        }
+    }
+    (unused, checked)
+}
+
+// Symbols left undefined by `objects`, read with nm. None if there are no objects or any of them
+// can not be read.
+fn referenced_symbols(objects: &[PathBuf]) -> Option<HashSet<String>> {
+    if objects.is_empty() || !objects.iter().all(|object| object.is_file()) {
+        return None;
+    }
+    let output = Environment::new()
+        .command(Path::new("nm"))
+        .args(["-C", "--undefined-only"])
+        .args(objects.iter().map(PathBuf::as_path).map(Path::as_os_str))
+        .output()
+        .ok()
+        .filter(|output| output.status.success());
+    let Some(output) = output else {
+        log::debug!("Could not read the undefined symbols of the objects with nm");
+        return None;
+    };
+    Some(parse_undefined_symbols(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+// Symbols of lines such as "                 U n::util(int)", as printed by
+// `nm -C --undefined-only`. Weak references, of kind "w", are left out, as they link without a
+// definition.
+fn parse_undefined_symbols(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("U "))
+        .map(|symbol| symbol.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn dependencies_defining_no_referenced_symbol_are_unused() {
+        let referenced = parse_undefined_symbols(indoc::indoc! {"
+
+            main.o:
+                             U net::connect(int)
+                             w __gmon_start__
+                             U operator delete(void*)
+        "});
+        assert_eq!(
+            referenced,
+            HashSet::from([
+                "net::connect(int)".to_string(),
+                "operator delete(void*)".to_string()
+            ])
+        );
+
+        let target = ProgressTrackingTarget {
+            target: "app".to_string(),
+            object_files: vec![PathBuf::from("main.o")],
+            dependencies: vec!["net".to_string(), "log".to_string(), "gui".to_string()],
+            output: Some(PathBuf::from("app")),
+            packages: Vec::new(),
+        };
+        let libraries = [
+            LibraryDefinitions::new(
+                "net",
+                Path::new("libnet.a"),
+                HashSet::from(["net::connect(int)".to_string()]),
+            ),
+            LibraryDefinitions::new(
+                "log",
+                Path::new("liblog.a"),
+                HashSet::from(["log::write(char const*)".to_string()]),
+            ),
+        ];
+        let (unused, checked) = unused_by(&target, &referenced, &libraries);
+        assert_eq!(
+            unused,
+            vec![UnusedDependency {
+                target: "app".to_string(),
+                dependency: "log".to_string(),
+                library: PathBuf::from("liblog.a"),
+            }]
+        );
+        assert!(!checked);
+    }
+}
@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+use crate::build_target::BuildTarget;
+use crate::parser::types::Define;
+
+/// Directory, relative to the build directory, the documentation of each target is generated in.
+pub const DOCS_DIRECTORY_NAME: &str = "docs";
+
+pub const DOXYFILE_NAME: &str = "Doxyfile";
+
+/// Tool generating the documentation when none is given.
+pub const DEFAULT_DOCS_TOOL: &str = "doxygen";
+
+const HEADER_FILE_PATTERNS: &str = "*.h *.hh *.hpp *.hxx *.h++ *.inl *.ipp *.tpp";
+
+// Header extensions Doxygen does not parse as C++ by default.
+const HEADER_EXTENSION_MAPPING: &str = "ipp=C++ tpp=C++";
+
+/// Documentation of the headers of a target, as Doxygen sees them. The public headers are those
+/// of the include directories of the project of the target, along with any header listed in
+/// its sources. All the include directories of the target, including those of its dependencies,
+/// are searched for includes, so that the docs follow the include graph the target is built with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetDocs {
+    pub name: String,
+    pub inputs: Vec<PathBuf>,
+    pub include_paths: Vec<PathBuf>,
+    pub defines: Vec<Define>,
+    /// Header extensions the project of the target recognizes besides the common ones.
+    pub header_extensions: Vec<String>,
+}
+
+impl TargetDocs {
+    pub fn from_target(target: &BuildTarget) -> Self {
+        let mut inputs = target
+            .project_include_directories
+            .iter()
+            .map(|include_directory| include_directory.path.clone())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<PathBuf>>();
+        for header in target
+            .source_files
+            .iter()
+            .filter(|file| file.is_header())
+            .map(|file| file.file())
+        {
+            if !inputs.iter().any(|input| header.starts_with(input)) {
+                inputs.push(header);
+            }
+        }
+        Self {
+            name: target.name(),
+            inputs,
+            include_paths: target
+                .include_directories()
+                .iter()
+                .map(|include_directory| include_directory.path.clone())
+                .collect(),
+            defines: target.defines.clone(),
+            header_extensions: target.file_extensions.headers.clone(),
+        }
+    }
+
+    /// Directory the docs of the target are generated in, in `build_directory`.
+    pub fn output_directory(&self, build_directory: &Path) -> PathBuf {
+        build_directory.join(DOCS_DIRECTORY_NAME).join(&self.name)
+    }
+
+    /// Doxyfile generating HTML documentation into `output_directory`.
+    pub fn doxyfile(&self, output_directory: &Path) -> String {
+        let quoted = |paths: &[PathBuf]| {
+            paths
+                .iter()
+                .map(|path| format!("\"{}\"", path.display()))
+                .collect::<Vec<String>>()
+                .join(" \\\n    ")
+        };
+        let predefined = self
+            .defines
+            .iter()
+            .map(|define| match define.value {
+                Some(ref value) => format!("\"{}={}\"", define.macro_, value),
+                None => format!("\"{}\"", define.macro_),
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        format!(
+            "# Generated by yambs docs. Changes are overwritten.\n\
+             PROJECT_NAME           = \"{name}\"\n\
+             OUTPUT_DIRECTORY       = \"{output_directory}\"\n\
+             INPUT                  = {inputs}\n\
+             FILE_PATTERNS          = {file_patterns}\n\
+             EXTENSION_MAPPING      = {extension_mapping}\n\
+             RECURSIVE              = YES\n\
+             EXTRACT_ALL            = YES\n\
+             ENABLE_PREPROCESSING   = YES\n\
+             SEARCH_INCLUDES        = YES\n\
+             INCLUDE_PATH           = {include_paths}\n\
+             PREDEFINED             = {predefined}\n\
+             FULL_PATH_NAMES        = YES\n\
+             STRIP_FROM_PATH        = {inputs}\n\
+             GENERATE_HTML          = YES\n\
+             GENERATE_LATEX         = NO\n\
+             QUIET                  = YES\n\
+             WARN_IF_UNDOCUMENTED   = NO\n",
+            name = self.name,
+            output_directory = output_directory.display(),
+            inputs = quoted(&self.inputs),
+            file_patterns = std::iter::once(HEADER_FILE_PATTERNS.to_string())
+                .chain(
+                    self.header_extensions
+                        .iter()
+                        .map(|extension| format!("*.{}", extension))
+                )
+                .collect::<Vec<String>>()
+                .join(" "),
+            extension_mapping = std::iter::once(HEADER_EXTENSION_MAPPING.to_string())
+                .chain(
+                    self.header_extensions
+                        .iter()
+                        .map(|extension| format!("{}=C++", extension))
+                )
+                .collect::<Vec<String>>()
+                .join(" "),
+            include_paths = quoted(&self.include_paths),
+            predefined = predefined,
+        )
+    }
+}
+
+/// Copies the directory `source` into `destination`, replacing files that are already there.
+pub fn copy_directory(source: &Path, destination: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let destination = destination.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_directory(&entry.path(), &destination)?;
+        } else {
+            std::fs::copy(entry.path(), destination)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doxyfile_reads_headers_with_include_paths_and_defines_of_target() {
+        let docs = TargetDocs {
+            name: "geometry".to_string(),
+            inputs: vec![PathBuf::from("/project/include")],
+            include_paths: vec![
+                PathBuf::from("/project/include"),
+                PathBuf::from("/deps/math/include"),
+            ],
+            defines: vec![
+                Define {
+                    macro_: "GEOMETRY_API".to_string(),
+                    value: None,
+                },
+                Define {
+                    macro_: "PRECISION".to_string(),
+                    value: Some("2".to_string()),
+                },
+            ],
+            header_extensions: vec!["tcc".to_string()],
+        };
+        let doxyfile = docs.doxyfile(&docs.output_directory(Path::new("/build")));
+        assert!(doxyfile.contains("PROJECT_NAME           = \"geometry\"\n"));
+        assert!(doxyfile.contains("OUTPUT_DIRECTORY       = \"/build/docs/geometry\"\n"));
+        assert!(doxyfile.contains("INPUT                  = \"/project/include\"\n"));
+        assert!(doxyfile.contains(
+            "INCLUDE_PATH           = \"/project/include\" \\\n    \"/deps/math/include\"\n"
+        ));
+        assert!(doxyfile.contains("PREDEFINED             = \"GEOMETRY_API\" \"PRECISION=2\"\n"));
+        assert!(doxyfile.contains(
+            "FILE_PATTERNS          = *.h *.hh *.hpp *.hxx *.h++ *.inl *.ipp *.tpp *.tcc\n"
+        ));
+        assert!(doxyfile.contains("EXTENSION_MAPPING      = ipp=C++ tpp=C++ tcc=C++\n"));
+    }
+}
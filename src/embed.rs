@@ -0,0 +1,291 @@
+// Files listed in `embed` of a target are compiled into it as byte arrays. The arrays are
+// generated into a header and source pair in the generated directory of the target, by rules of
+// the generated build files running `yambs embed`, so that changes to the embedded files are
+// picked up by make like those of any other prerequisite.
+//
+// The generated code is plain C with C linkage, so that the same header works for C and C++
+// targets.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::errors::FsError;
+use crate::utility;
+
+/// Number of bytes written on each line of a generated array.
+const BYTES_PER_LINE: usize = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmbedError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+    #[error("Embedded file must be of the form <name>=<path>: {0}")]
+    InvalidResource(String),
+    #[error("Embedded files \"{0}\" and \"{1}\" have the same symbol name {2}")]
+    SymbolCollision(String, String, String),
+}
+
+/// File embedded into a target, under the name it is looked up by, which is its path relative to
+/// the manifest directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmbeddedFile {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl EmbeddedFile {
+    pub fn parse(s: &str) -> Result<Self, EmbedError> {
+        let (name, path) = s
+            .split_once('=')
+            .ok_or_else(|| EmbedError::InvalidResource(s.to_string()))?;
+        if name.is_empty() || path.is_empty() {
+            return Err(EmbedError::InvalidResource(s.to_string()));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl std::fmt::Display for EmbeddedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.path.display())
+    }
+}
+
+/// Name of the header generated for the embedded files of `target`.
+pub fn header_file_name(target: &str) -> String {
+    format!("{}_embed.h", target)
+}
+
+/// Identifier made of `parts`, with every character that can not be part of a C identifier
+/// replaced by an underscore.
+fn identifier(parts: &[&str]) -> String {
+    let mut identifier = parts
+        .join("_")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+/// Symbol of the array holding `file`. Symbols are prefixed with the name of the target, so that
+/// targets linked together can embed files of the same name.
+pub fn symbol_name(target: &str, file: &EmbeddedFile) -> String {
+    identifier(&[target, &file.name])
+}
+
+fn lookup_function_name(target: &str) -> String {
+    identifier(&[target, "embedded_file"])
+}
+
+fn string_literal(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            c if c.is_ascii_graphic() || c == ' ' => literal.push(c),
+            c => {
+                let mut buffer = [0; 4];
+                for byte in c.encode_utf8(&mut buffer).bytes() {
+                    // Octal escapes end after three digits, unlike hexadecimal ones.
+                    let _ = write!(literal, "\\{:03o}", byte);
+                }
+            }
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn symbols<'a>(
+    target: &str,
+    files: &'a [EmbeddedFile],
+) -> Result<Vec<(&'a EmbeddedFile, String)>, EmbedError> {
+    let mut names = BTreeMap::<String, &str>::new();
+    let mut symbols = Vec::new();
+    for file in files {
+        let symbol = symbol_name(target, file);
+        if let Some(other) = names.insert(symbol.clone(), &file.name) {
+            return Err(EmbedError::SymbolCollision(
+                other.to_string(),
+                file.name.clone(),
+                symbol,
+            ));
+        }
+        symbols.push((file, symbol));
+    }
+    Ok(symbols)
+}
+
+/// Header declaring the arrays of the embedded files of `target`, and a function looking them up
+/// by name.
+pub fn generate_header(target: &str, files: &[EmbeddedFile]) -> Result<String, EmbedError> {
+    let mut header = format!(
+        "// Generated by yambs from the files embedded in \"{}\". Do not edit.\n\
+         #pragma once\n\
+         \n\
+         #include <stddef.h>\n\
+         \n\
+         #ifdef __cplusplus\n\
+         extern \"C\" {{\n\
+         #endif\n\
+         \n",
+        target
+    );
+    for (file, symbol) in symbols(target, files)? {
+        let _ = writeln!(header, "// {}", file.name);
+        let _ = writeln!(header, "extern const unsigned char {}[];", symbol);
+        let _ = writeln!(header, "extern const size_t {}_size;\n", symbol);
+    }
+    let _ = write!(
+        header,
+        "// Contents of the embedded file `name`, as named in the manifest, or NULL if there is no\n\
+         // such file. The size is stored in `size` unless NULL. The contents are followed by a\n\
+         // terminating zero, which is not counted in the size.\n\
+         const unsigned char* {}(const char* name, size_t* size);\n\
+         \n\
+         #ifdef __cplusplus\n\
+         }}\n\
+         #endif\n",
+        lookup_function_name(target)
+    );
+    Ok(header)
+}
+
+/// Source defining the arrays of the embedded files of `target`, with the contents of each file
+/// read from its path.
+pub fn generate_source(target: &str, files: &[EmbeddedFile]) -> Result<String, EmbedError> {
+    let symbols = symbols(target, files)?;
+    let mut source = format!(
+        "// Generated by yambs from the files embedded in \"{}\". Do not edit.\n\
+         #include \"{}\"\n\
+         \n\
+         #include <string.h>\n\
+         \n",
+        target,
+        header_file_name(target)
+    );
+    for (file, symbol) in &symbols {
+        let contents =
+            std::fs::read(&file.path).map_err(|e| FsError::ReadFromFile(file.path.clone(), e))?;
+        let _ = writeln!(source, "// {}", file.name);
+        let _ = writeln!(source, "const unsigned char {}[] = {{", symbol);
+        for line in contents
+            .iter()
+            .chain(std::iter::once(&0))
+            .collect::<Vec<&u8>>()
+            .chunks(BYTES_PER_LINE)
+        {
+            let bytes = line
+                .iter()
+                .map(|byte| format!("0x{:02x},", byte))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let _ = writeln!(source, "    {}", bytes);
+        }
+        let _ = writeln!(source, "}};");
+        let _ = writeln!(
+            source,
+            "const size_t {}_size = {};\n",
+            symbol,
+            contents.len()
+        );
+    }
+    let _ = writeln!(
+        source,
+        "const unsigned char* {}(const char* name, size_t* size) {{",
+        lookup_function_name(target)
+    );
+    for (file, symbol) in &symbols {
+        let _ = write!(
+            source,
+            "    if (strcmp(name, {name}) == 0) {{\n\
+             \x20       if (size) {{\n\
+             \x20           *size = {symbol}_size;\n\
+             \x20       }}\n\
+             \x20       return {symbol};\n\
+             \x20   }}\n",
+            name = string_literal(&file.name),
+        );
+    }
+    let _ = write!(source, "    return NULL;\n}}\n");
+    Ok(source)
+}
+
+/// Writes the header and source of the embedded files of `target`.
+pub fn write(
+    target: &str,
+    files: &[EmbeddedFile],
+    header: &Path,
+    source: &Path,
+) -> Result<(), EmbedError> {
+    let header_content = generate_header(target, files)?;
+    let source_content = generate_source(target, files)?;
+    for path in [header, source] {
+        if let Some(parent) = path.parent() {
+            utility::create_dir(parent)?;
+        }
+    }
+    utility::write_file_atomically(header, header_content.as_bytes())?;
+    utility::write_file_atomically(source, source_content.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn embedded_file(name: &str, path: &Path) -> EmbeddedFile {
+        EmbeddedFile {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+        }
+    }
+
+    #[test]
+    fn symbol_names_are_valid_identifiers_prefixed_by_the_target() {
+        let file = embedded_file("assets/shader-1.glsl", Path::new("/project/assets"));
+        assert_eq!(symbol_name("app", &file), "app_assets_shader_1_glsl");
+        assert_eq!(symbol_name("3d", &file), "_3d_assets_shader_1_glsl");
+    }
+
+    #[test]
+    fn generated_source_holds_contents_with_terminating_zero() {
+        let tempdir = tempdir::TempDir::new("embed").unwrap();
+        let path = tempdir.path().join("greeting.txt");
+        std::fs::write(&path, "hi\n").unwrap();
+        let files = vec![embedded_file("assets/greeting.txt", &path)];
+
+        let source = generate_source("app", &files).unwrap();
+        assert!(source.contains(
+            "const unsigned char app_assets_greeting_txt[] = {\n    0x68, 0x69, 0x0a, 0x00,\n};\n\
+             const size_t app_assets_greeting_txt_size = 3;\n"
+        ));
+        assert!(source.contains("if (strcmp(name, \"assets/greeting.txt\") == 0) {"));
+        assert!(generate_header("app", &files)
+            .unwrap()
+            .contains("extern const unsigned char app_assets_greeting_txt[];"));
+    }
+
+    #[test]
+    fn files_with_the_same_symbol_name_are_rejected() {
+        let files = vec![
+            embedded_file("a-b.txt", Path::new("/project/a-b.txt")),
+            embedded_file("a_b.txt", Path::new("/project/a_b.txt")),
+        ];
+        assert!(matches!(
+            generate_header("app", &files),
+            Err(EmbedError::SymbolCollision(..))
+        ));
+    }
+}
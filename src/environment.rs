@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::process::Command;
+
+pub enum ModifyMode {
+    Set,
+    Append,
+    Prepend,
+}
+
+/// Environment that child processes are run with.
+///
+/// Modifications are kept by the environment itself and applied to the commands it creates, so
+/// they are scoped to the subprocesses of whoever owns the environment instead of changing the
+/// environment of yambs for everyone.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Environment {
+    // A value of None removes the variable from the environment of the child.
+    modifications: BTreeMap<String, Option<OsString>>,
+}
+
+impl Environment {
+    /// Environment inheriting everything from the environment of yambs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Value of `key` in the environment of the children.
+    pub fn get(&self, key: &str) -> Option<OsString> {
+        match self.modifications.get(key) {
+            Some(value) => value.clone(),
+            None => std::env::var_os(key),
+        }
+    }
+
+    /// Sets `key` to `value`, or adds `value` to the list of paths already in `key`.
+    pub fn set(&mut self, key: &str, value: &OsStr, mode: ModifyMode) {
+        let value = match (mode, self.get(key)) {
+            (ModifyMode::Append, Some(current)) if !current.is_empty() => {
+                join_paths(&[current, value.to_os_string()])
+            }
+            (ModifyMode::Prepend, Some(current)) if !current.is_empty() => {
+                join_paths(&[value.to_os_string(), current])
+            }
+            _ => value.to_os_string(),
+        };
+        self.modifications.insert(key.to_string(), Some(value));
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.modifications.insert(key.to_string(), None);
+    }
+
+    /// Copy of this environment with `key` modified, for use in a narrower scope.
+    pub fn with(&self, key: &str, value: &OsStr, mode: ModifyMode) -> Self {
+        let mut environment = self.clone();
+        environment.set(key, value, mode);
+        environment
+    }
+
+    /// Complete environment of the children.
+    pub fn child_environment(&self) -> BTreeMap<OsString, OsString> {
+        let mut environment = std::env::vars_os().collect::<BTreeMap<OsString, OsString>>();
+        for (key, value) in &self.modifications {
+            match value {
+                Some(value) => environment.insert(OsString::from(key), value.clone()),
+                None => environment.remove(OsStr::new(key)),
+            };
+        }
+        environment
+    }
+
+    /// Creates a command running `program` in this environment.
+    /// The complete environment of the command is written to the trace log.
+    pub fn command(&self, program: &Path) -> Command {
+        let mut command = Command::new(program);
+        for (key, value) in &self.modifications {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+        if !self.modifications.is_empty() {
+            log::debug!(
+                "Environment modified for {}: {}",
+                program.display(),
+                self.modifications
+                    .iter()
+                    .map(|(key, value)| match value {
+                        Some(value) => format!("{}={}", key, value.to_string_lossy()),
+                        None => format!("-{}", key),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            );
+        }
+        log::trace!(
+            "Environment of {}:\n{}",
+            program.display(),
+            self.child_environment()
+                .iter()
+                .map(|(key, value)| format!(
+                    "  {}={}",
+                    key.to_string_lossy(),
+                    value.to_string_lossy()
+                ))
+                .collect::<Vec<String>>()
+                .join("\n")
+        );
+        command
+    }
+}
+
+fn join_paths(paths: &[OsString]) -> OsString {
+    #[cfg(target_family = "unix")]
+    let separator = ":";
+    #[cfg(target_family = "windows")]
+    let separator = ";";
+
+    let mut joined = OsString::new();
+    for (i, path) in paths.iter().enumerate() {
+        if i > 0 {
+            joined.push(separator);
+        }
+        joined.push(path);
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_and_prepend_add_to_existing_paths() {
+        let mut environment = Environment::new();
+        environment.set(
+            "YAMBS_TEST_SEARCH_PATH",
+            OsStr::new("/b"),
+            ModifyMode::Append,
+        );
+        environment.set(
+            "YAMBS_TEST_SEARCH_PATH",
+            OsStr::new("/c"),
+            ModifyMode::Append,
+        );
+        environment.set(
+            "YAMBS_TEST_SEARCH_PATH",
+            OsStr::new("/a"),
+            ModifyMode::Prepend,
+        );
+        assert_eq!(
+            environment.get("YAMBS_TEST_SEARCH_PATH"),
+            Some(OsString::from("/a:/b:/c"))
+        );
+        assert_eq!(std::env::var_os("YAMBS_TEST_SEARCH_PATH"), None);
+    }
+
+    #[test]
+    fn scoped_environment_leaves_original_untouched() {
+        let environment = Environment::new();
+        let scoped = environment.with("YAMBS_TEST_SCOPED", OsStr::new("1"), ModifyMode::Set);
+        assert_eq!(scoped.get("YAMBS_TEST_SCOPED"), Some(OsString::from("1")));
+        assert_eq!(environment.get("YAMBS_TEST_SCOPED"), None);
+    }
+
+    #[test]
+    fn command_runs_with_modified_environment() {
+        let mut environment = Environment::new();
+        environment.set("YAMBS_TEST_COMMAND", OsStr::new("value"), ModifyMode::Set);
+        environment.remove("HOME");
+        let output = environment
+            .command(Path::new("sh"))
+            .args([
+                "-c",
+                "printf '%s:%s' \"$YAMBS_TEST_COMMAND\" \"${HOME-unset}\"",
+            ])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "value:unset");
+    }
+}
@@ -51,6 +51,8 @@ pub enum FsError {
     RemoveFile(std::path::PathBuf, #[source] std::io::Error),
     #[error("Error occured in creating file {0:?}")]
     CreateFile(std::path::PathBuf, #[source] std::io::Error),
+    #[error("Refusing to write {0:?} into the source tree {1:?}, which may be read-only. Generated files belong in the build directory")]
+    WriteIntoSourceTree(std::path::PathBuf, std::path::PathBuf),
     #[error("Error occured reading from file {0:?}")]
     ReadFromFile(std::path::PathBuf, #[source] std::io::Error),
     #[error("The path {0:?} does not exist")]
@@ -63,8 +65,16 @@ pub enum FsError {
     PopError,
     #[error("Failed to write to file")]
     WriteToFile(#[source] std::io::Error),
+    #[error("Failed to sync {0:?} to disk")]
+    SyncToDisk(std::path::PathBuf, #[source] std::io::Error),
+    #[error("Failed to rename {0:?} to {1:?}")]
+    Rename(
+        std::path::PathBuf,
+        std::path::PathBuf,
+        #[source] std::io::Error,
+    ),
     #[error("Failed to spawn process {0:?}")]
-    Spawn(std::process::Command),
+    Spawn(Box<std::process::Command>),
     #[error("Failed to spawn child process: {0:?}")]
     SpawnChild(#[source] std::io::Error),
     #[error("Could not access directory")]
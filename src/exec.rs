@@ -0,0 +1,120 @@
+// Environment `yambs exec` runs a command in, so that scripts and debuggers run on the artifacts of
+// the most recent build without having the environment set up by hand.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::Path;
+
+use crate::cli::configurations::BuildType;
+use crate::environment::{Environment, ModifyMode};
+use crate::generator::HOST_BUILD_DIRECTORY_NAME;
+use crate::test_runner::BUILD_DIRECTORY_VARIABLE;
+
+/// Variable the dynamic loader searches for shared libraries in.
+#[cfg(target_os = "macos")]
+pub const LIBRARY_PATH_VARIABLE: &str = "DYLD_LIBRARY_PATH";
+#[cfg(not(target_os = "macos"))]
+pub const LIBRARY_PATH_VARIABLE: &str = "LD_LIBRARY_PATH";
+
+/// Environment of the artifacts built into `build_directory` for `build_type`:
+/// * Executables are found through PATH and shared libraries through LD_LIBRARY_PATH, with those
+///   built for the host after those of the target.
+/// * YAMBS_BUILD_DIRECTORY is the directory of the artifacts, as it is for tests.
+/// * YAMBS_BUILD_DIR, YAMBS_BUILD_TYPE and YAMBS_MANIFEST_DIR have the values the variables of
+///   the manifest expanded to.
+/// * The variables the toolchain was taken from have the values they had for the build.
+pub fn build_environment(
+    build_directory: &Path,
+    build_type: &BuildType,
+    manifest_directory: &Path,
+    toolchain_environment: &BTreeMap<String, String>,
+) -> Environment {
+    let mut environment = Environment::new();
+    let artifact_directory = build_directory.join(build_type.to_string());
+    let host_artifact_directory = build_directory
+        .join(HOST_BUILD_DIRECTORY_NAME)
+        .join(build_type.to_string());
+    let mut search_directories = vec![artifact_directory.as_path()];
+    if host_artifact_directory.is_dir() {
+        search_directories.push(host_artifact_directory.as_path());
+    }
+    for directory in search_directories.iter().rev() {
+        environment.set("PATH", directory.as_os_str(), ModifyMode::Prepend);
+        environment.set(
+            LIBRARY_PATH_VARIABLE,
+            directory.as_os_str(),
+            ModifyMode::Prepend,
+        );
+    }
+    environment.set(
+        BUILD_DIRECTORY_VARIABLE,
+        artifact_directory.as_os_str(),
+        ModifyMode::Set,
+    );
+    environment.set(
+        "YAMBS_BUILD_DIR",
+        build_directory.as_os_str(),
+        ModifyMode::Set,
+    );
+    environment.set(
+        "YAMBS_BUILD_TYPE",
+        OsStr::new(&build_type.to_string()),
+        ModifyMode::Set,
+    );
+    environment.set(
+        "YAMBS_MANIFEST_DIR",
+        manifest_directory.as_os_str(),
+        ModifyMode::Set,
+    );
+    for (key, value) in toolchain_environment {
+        environment.set(key, OsStr::new(value), ModifyMode::Set);
+    }
+    environment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    #[test]
+    fn artifacts_of_target_come_before_those_of_host() {
+        let tempdir = tempdir::TempDir::new("exec").unwrap();
+        let build_directory = tempdir.path();
+        let host_directory = build_directory
+            .join(HOST_BUILD_DIRECTORY_NAME)
+            .join("release");
+        std::fs::create_dir_all(&host_directory).unwrap();
+
+        let environment = build_environment(
+            build_directory,
+            &BuildType::Release,
+            Path::new("/project"),
+            &BTreeMap::from([("CXX".to_string(), "clang++".to_string())]),
+        );
+        let artifact_directory = build_directory.join("release");
+        let library_path = environment.get(LIBRARY_PATH_VARIABLE).unwrap();
+        let library_path = std::env::split_paths(&library_path).collect::<Vec<_>>();
+        assert_eq!(
+            library_path[..2],
+            [artifact_directory.clone(), host_directory]
+        );
+        assert_eq!(
+            std::env::split_paths(&environment.get("PATH").unwrap()).next(),
+            Some(artifact_directory.clone())
+        );
+        assert_eq!(
+            environment.get(BUILD_DIRECTORY_VARIABLE),
+            Some(artifact_directory.into_os_string())
+        );
+        assert_eq!(
+            environment.get("YAMBS_BUILD_TYPE"),
+            Some(OsString::from("release"))
+        );
+        assert_eq!(
+            environment.get("YAMBS_MANIFEST_DIR"),
+            Some(OsString::from("/project"))
+        );
+        assert_eq!(environment.get("CXX"), Some(OsString::from("clang++")));
+    }
+}
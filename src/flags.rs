@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use crate::cli::configurations::{BuildType, OptLevel};
+
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
 pub struct CompilerFlags {
     #[serde(rename = "cxxflags_append")]
@@ -10,10 +12,49 @@ pub struct CompilerFlags {
     pub cpp_flags: Option<CPPFlags>,
     #[serde(rename = "ldflags_append")]
     pub ld_flags: Option<LDFlags>,
+    /// System libraries linked by name, such as "pthread" for -lpthread. Libraries that are part
+    /// of the C library of the platform are left out where they do not exist on their own.
+    #[serde(default)]
+    pub libs: Vec<String>,
     #[serde(rename = "append_include_directories", default = "Vec::new")]
     pub include_directories: Vec<PathBuf>,
     #[serde(rename = "append_system_include_directories", default = "Vec::new")]
     pub system_include_directories: Vec<PathBuf>,
+    /// Places functions and data in their own sections and removes unused sections when linking.
+    /// Overrides `gc_sections` of the project when set.
+    #[serde(default)]
+    pub gc_sections: Option<bool>,
+    /// Builds the target with the hardening options of distribution policies. Overrides
+    /// `hardened` of the project when set.
+    #[serde(default)]
+    pub hardened: Option<bool>,
+    /// Includes the standard library headers precompiled in the cache shared between builds.
+    /// Overrides `system_pch` of the project when set.
+    #[serde(default)]
+    pub system_pch: Option<bool>,
+    /// Overrides `warnings` of the project when set.
+    #[serde(default)]
+    pub warnings: Option<Warnings>,
+    /// Overrides `generated_warnings` of the project when set.
+    #[serde(default)]
+    pub generated_warnings: Option<GeneratedWarnings>,
+    /// Overrides the optimization level of the build type when set.
+    #[serde(default)]
+    pub opt_level: Option<OptLevel>,
+    /// Compiles and links the target without the CXXFLAGS or CFLAGS every target gets from the
+    /// include files, such as the standard, the flags of the build type and -fPIC, and without
+    /// the warnings of the project. Only the flags of the target itself are used.
+    #[serde(default)]
+    pub no_default_flags: bool,
+    /// The translation units of the target instantiate many templates, and take more memory to
+    /// compile than their size tells. Only used to estimate the memory of compiles for
+    /// --max-memory.
+    #[serde(default)]
+    pub template_heavy: bool,
+    /// Names of the `[flags.<name>]` groups of the manifest the target is compiled and linked
+    /// with. Their flags are added to those of the target when the manifest is parsed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flag_groups: Vec<String>,
 }
 
 impl CompilerFlags {
@@ -23,10 +64,195 @@ impl CompilerFlags {
             cxx_flags: None,
             cpp_flags: None,
             ld_flags: None,
+            libs: Vec::new(),
             include_directories: Vec::new(),
             system_include_directories: Vec::new(),
+            gc_sections: None,
+            hardened: None,
+            system_pch: None,
+            warnings: None,
+            generated_warnings: None,
+            opt_level: None,
+            no_default_flags: false,
+            template_heavy: false,
+            flag_groups: Vec::new(),
+        }
+    }
+
+    /// Whether the target is compiled or linked with link-time optimization, which makes linking
+    /// take as much memory as compiling the whole target at once.
+    pub fn uses_lto(&self) -> bool {
+        self.cxx_flags
+            .iter()
+            .flat_map(|flags| flags.flags())
+            .chain(self.c_flags.iter().flat_map(|flags| flags.flags()))
+            .chain(self.ld_flags.iter().flat_map(|flags| flags.flags()))
+            .any(|flag| flag.starts_with("-flto"))
+    }
+
+    /// The flags with those of `groups` added before the flags of the target itself, in the order
+    /// of the groups. Flags given by several groups are only added once.
+    pub fn with_flag_groups(mut self, groups: &[&FlagGroup]) -> Self {
+        fn prepended(grouped: Vec<&String>, own: Option<&Vec<String>>) -> Option<Vec<String>> {
+            let mut flags = Vec::<String>::new();
+            for flag in grouped.into_iter().chain(own.into_iter().flatten()) {
+                if !flags.contains(flag) {
+                    flags.push(flag.clone());
+                }
+            }
+            (!flags.is_empty()).then_some(flags)
+        }
+        self.cxx_flags = prepended(
+            groups.iter().flat_map(|group| &group.cxxflags).collect(),
+            self.cxx_flags.as_ref().map(|flags| flags.flags()),
+        )
+        .map(|flags| CXXFlags::from_slice(&flags));
+        self.c_flags = prepended(
+            groups.iter().flat_map(|group| &group.cflags).collect(),
+            self.c_flags.as_ref().map(|flags| flags.flags()),
+        )
+        .map(|flags| CFlags::from_slice(&flags));
+        self.cpp_flags = prepended(
+            groups.iter().flat_map(|group| &group.cppflags).collect(),
+            self.cpp_flags.as_ref().map(|flags| flags.flags()),
+        )
+        .map(|flags| CPPFlags::from_slice(&flags));
+        self.ld_flags = prepended(
+            groups.iter().flat_map(|group| &group.ldflags).collect(),
+            self.ld_flags.as_ref().map(|flags| flags.flags()),
+        )
+        .map(|flags| LDFlags::from_slice(&flags));
+        self
+    }
+}
+
+/// Bundle of flags defined once by `[flags.<name>]` of a manifest, and used by the targets listing
+/// it in `flag_groups`.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct FlagGroup {
+    #[serde(default)]
+    pub cxxflags: Vec<String>,
+    #[serde(default)]
+    pub cflags: Vec<String>,
+    #[serde(default)]
+    pub cppflags: Vec<String>,
+    #[serde(default)]
+    pub ldflags: Vec<String>,
+}
+
+impl FlagGroup {
+    /// Flags of `self` and `other` setting the same option to different values, such as
+    /// -ffast-math and -fno-fast-math, or -ffp-contract=off and -ffp-contract=fast. Only flags
+    /// of the same kind are compared, and only options whose last value wins, so
+    /// -fsanitize=address and -fsanitize=undefined do not conflict.
+    pub fn conflict_with<'a>(&'a self, other: &'a FlagGroup) -> Option<(&'a str, &'a str)> {
+        let kinds = [
+            (&self.cxxflags, &other.cxxflags),
+            (&self.cflags, &other.cflags),
+            (&self.cppflags, &other.cppflags),
+            (&self.ldflags, &other.ldflags),
+        ];
+        kinds.iter().find_map(|(flags, other_flags)| {
+            flags.iter().find_map(|flag| {
+                let (option, value) = flag_setting(flag)?;
+                other_flags
+                    .iter()
+                    .find(|other_flag| {
+                        flag_setting(other_flag).is_some_and(|(other_option, other_value)| {
+                            other_option == option && other_value != value
+                        })
+                    })
+                    .map(|other_flag| (flag.as_str(), other_flag.as_str()))
+            })
+        })
+    }
+}
+
+// Options whose values add up when they are given several times, instead of the last one winning.
+// Each of their values is an option of its own, turned off by the no- form.
+const CUMULATIVE_OPTIONS: [&str; 4] = [
+    "-fsanitize",
+    "-fsanitize-recover",
+    "-fsanitize-trap",
+    "-Werror",
+];
+
+// Option a flag sets, along with the value it sets it to. -f, -m and -W options are turned off by
+// their no- form, and -O is the same option whatever the level. Options passed on to the linker
+// are not compared, as what they set is up to the linker.
+fn flag_setting(flag: &str) -> Option<(String, String)> {
+    if flag.starts_with("-Wl,") || flag.starts_with("-Xlinker") {
+        return None;
+    }
+    if let Some(level) = flag.strip_prefix("-O") {
+        return Some(("-O".to_string(), level.to_string()));
+    }
+    if let Some(parameter) = flag.strip_prefix("--param=") {
+        return parameter
+            .split_once('=')
+            .map(|(name, value)| (format!("--param={}", name), value.to_string()));
+    }
+    for prefix in ["-f", "-m", "-W"] {
+        if let Some(option) = flag.strip_prefix(prefix) {
+            let (option, enabled) = match option.strip_prefix("no-") {
+                Some(option) => (option, "off"),
+                None => (option, "on"),
+            };
+            return Some(match option.split_once('=') {
+                Some((option, value))
+                    if CUMULATIVE_OPTIONS.contains(&format!("{}{}", prefix, option).as_str()) =>
+                {
+                    (
+                        format!("{}{}={}", prefix, option, value),
+                        enabled.to_string(),
+                    )
+                }
+                Some((option, value)) => (format!("{}{}", prefix, option), value.to_string()),
+                None => (format!("{}{}", prefix, option), enabled.to_string()),
+            });
         }
     }
+    flag.split_once('=')
+        .map(|(option, value)| (option.to_string(), value.to_string()))
+}
+
+/// Warnings enabled when compiling the sources of a target. Either one of the profiles provided
+/// by yambs, or a custom list of warning flags.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Warnings {
+    Profile(WarningProfile),
+    Custom(Vec<String>),
+}
+
+impl Default for Warnings {
+    fn default() -> Self {
+        Self::Profile(WarningProfile::default())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WarningProfile {
+    /// The default warnings, treated as errors, along with a few more.
+    Strict,
+    /// Extensive set of warnings chosen for the compiler type.
+    #[default]
+    Default,
+    /// All warnings suppressed, for code that is not ours to fix.
+    None,
+}
+
+/// Warnings the sources generated by custom commands and from embedded files are compiled with.
+/// Generated code is not ours to fix, so by default it is compiled with all warnings suppressed.
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GeneratedWarnings {
+    #[default]
+    Off,
+    /// The warnings of the target, as for the other sources.
+    On,
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
@@ -99,3 +325,234 @@ impl LDFlags {
         &self.0
     }
 }
+
+/// Where a flag of a compile or link command comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FlagOrigin {
+    /// The compiler, linker or platform of the toolchain.
+    Toolchain,
+    /// The flags of a build type.
+    Profile(BuildType),
+    /// Flags every target is compiled and linked with.
+    Default,
+    /// The `[project_config]` of the manifest.
+    Project,
+    /// The manifest entry of the target itself.
+    Target,
+    /// A dependency of the target, by name.
+    Dependency(String),
+    /// The command line, including the options of a preset.
+    CommandLine,
+}
+
+impl std::fmt::Display for FlagOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Toolchain => write!(f, "toolchain"),
+            Self::Profile(build_type) => write!(f, "{} profile", build_type.to_string()),
+            Self::Default => write!(f, "default"),
+            Self::Project => write!(f, "project"),
+            Self::Target => write!(f, "target"),
+            Self::Dependency(name) => write!(f, "dependency {}", name),
+            Self::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnnotatedFlag {
+    /// The flag as a word of the shell command, quoted where needed.
+    pub flag: String,
+    pub origin: FlagOrigin,
+}
+
+/// Flags in the order they are given to the compiler or linker, each with its origin.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnnotatedFlags(Vec<AnnotatedFlag>);
+
+impl AnnotatedFlags {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn push(&mut self, flag: impl Into<String>, origin: FlagOrigin) {
+        self.0.push(AnnotatedFlag {
+            flag: flag.into(),
+            origin,
+        });
+    }
+
+    /// Adds `flags`, all coming from `origin`. Empty flags are left out.
+    pub fn extend<I, S>(&mut self, flags: I, origin: &FlagOrigin)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for flag in flags {
+            let flag = flag.into();
+            if !flag.is_empty() {
+                self.push(flag, origin.clone());
+            }
+        }
+    }
+
+    pub fn append(&mut self, mut other: AnnotatedFlags) {
+        self.0.append(&mut other.0);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, AnnotatedFlag> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The flags as they are put on the command line, without their origins.
+    pub fn join(&self) -> String {
+        self.0
+            .iter()
+            .map(|annotated| annotated.flag.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+}
+
+/// A compile or link command with the origin of each of its flags.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnnotatedCommand {
+    pub program: PathBuf,
+    pub flags: AnnotatedFlags,
+}
+
+impl AnnotatedCommand {
+    /// The program on the first line, followed by a line for each flag with its origin.
+    pub fn render(&self) -> String {
+        let width = self
+            .flags
+            .iter()
+            .map(|annotated| annotated.flag.len())
+            .max()
+            .unwrap_or_default();
+        let mut text = self.program.display().to_string();
+        for annotated in self.flags.iter() {
+            text.push_str(&format!(
+                "\n    {:<width$}  [{}]",
+                annotated.flag,
+                annotated.origin,
+                width = width
+            ));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn annotated_flags_join_in_order_and_skip_empty_flags() {
+        let mut flags = AnnotatedFlags::new();
+        flags.extend(["-std=c++17", ""], &FlagOrigin::CommandLine);
+        flags.push("-O2", FlagOrigin::Profile(BuildType::Release));
+        flags.extend(["-fno-rtti"], &FlagOrigin::Dependency("core".to_string()));
+        assert_eq!(flags.join(), "-std=c++17 -O2 -fno-rtti");
+        assert_eq!(flags.iter().count(), 3);
+    }
+
+    #[test]
+    fn targets_use_default_flags_unless_opting_out() {
+        let flags: CompilerFlags =
+            toml::from_str("cxxflags_append = [\"-ffreestanding\"]").unwrap();
+        assert!(!flags.no_default_flags);
+        let flags: CompilerFlags = toml::from_str("no_default_flags = true").unwrap();
+        assert!(flags.no_default_flags);
+    }
+
+    #[test]
+    fn targets_use_lto_when_compiled_or_linked_with_it() {
+        let flags: CompilerFlags = toml::from_str("cxxflags_append = [\"-O2\"]").unwrap();
+        assert!(!flags.uses_lto());
+        let flags: CompilerFlags = toml::from_str("ldflags_append = [\"-flto=auto\"]").unwrap();
+        assert!(flags.uses_lto());
+    }
+
+    #[test]
+    fn flag_groups_conflict_on_options_set_to_different_values() {
+        let group = |cxxflags: &[&str]| FlagGroup {
+            cxxflags: cxxflags.iter().map(|flag| flag.to_string()).collect(),
+            ..Default::default()
+        };
+        let strict_math = group(&["-ffp-contract=off", "-fno-fast-math", "-O2"]);
+        assert_eq!(
+            strict_math.conflict_with(&group(&["-ffp-contract=fast"])),
+            Some(("-ffp-contract=off", "-ffp-contract=fast"))
+        );
+        assert_eq!(
+            strict_math.conflict_with(&group(&["-O3"])),
+            Some(("-O2", "-O3"))
+        );
+        assert_eq!(
+            strict_math.conflict_with(&group(&["-fno-fast-math", "-flto", "-O2"])),
+            None
+        );
+        assert_eq!(
+            strict_math.conflict_with(&FlagGroup {
+                cflags: vec!["-ffast-math".to_string()],
+                ..Default::default()
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn flag_groups_do_not_conflict_on_options_whose_values_add_up() {
+        let group = |cxxflags: &[&str]| FlagGroup {
+            cxxflags: cxxflags.iter().map(|flag| flag.to_string()).collect(),
+            ..Default::default()
+        };
+        let checked = group(&["-fsanitize=address", "-Werror=format", "-Wl,--defsym=a=1"]);
+        assert_eq!(
+            checked.conflict_with(&group(&[
+                "-fsanitize=undefined",
+                "-Werror=vla",
+                "-Wl,--defsym=b=2"
+            ])),
+            None
+        );
+        assert_eq!(
+            checked.conflict_with(&group(&["-fno-sanitize=address"])),
+            Some(("-fsanitize=address", "-fno-sanitize=address"))
+        );
+        assert_eq!(
+            checked.conflict_with(&group(&["-Wno-error=format"])),
+            Some(("-Werror=format", "-Wno-error=format"))
+        );
+        assert_eq!(
+            group(&["--param=max-inline-insns-single=100"])
+                .conflict_with(&group(&["--param=large-function-growth=50"])),
+            None
+        );
+    }
+
+    #[test]
+    fn annotated_command_renders_origin_of_each_flag() {
+        let mut flags = AnnotatedFlags::new();
+        flags.push("-O2", FlagOrigin::Profile(BuildType::Release));
+        flags.push("-fsanitize=address", FlagOrigin::CommandLine);
+        flags.push("-lz", FlagOrigin::Dependency("zlib".to_string()));
+        let command = AnnotatedCommand {
+            program: PathBuf::from("/usr/bin/g++"),
+            flags,
+        };
+        assert_eq!(
+            command.render(),
+            "/usr/bin/g++\n\
+             \x20   -O2                 [release profile]\n\
+             \x20   -fsanitize=address  [command line]\n\
+             \x20   -lz                 [dependency zlib]"
+        );
+    }
+}
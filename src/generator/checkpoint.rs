@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::build_file_hashes::{hash_content, hash_file};
+use crate::cache::Cacher;
+
+/// Build files of the targets written by a generation that failed partway, such as when the disk
+/// filled up or one of the targets could not be generated. The next generation resumes from it,
+/// generating only the build files of the targets that are missing or have changed since, instead
+/// of generating and writing the build files of every target again.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GenerationCheckpoint {
+    /// Fingerprints of the targets whose build files were written, by build file.
+    #[serde(default)]
+    pub build_files: BTreeMap<PathBuf, u64>,
+}
+
+impl Cacher for GenerationCheckpoint {
+    const CACHE_FILE_NAME: &'static str = "generation_checkpoint";
+}
+
+impl GenerationCheckpoint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `build_file`, generated with `content` from a target with `fingerprint`, if it was
+    /// written with that content.
+    pub fn record(&mut self, build_file: &Path, fingerprint: u64, content: &[u8]) {
+        if hash_file(build_file).is_ok_and(|written| written == hash_content(content)) {
+            self.build_files
+                .insert(build_file.to_path_buf(), fingerprint);
+        }
+    }
+
+    /// Whether `build_file` was written from a target with `fingerprint`, and is still there.
+    pub fn is_written(&self, build_file: &Path, fingerprint: u64) -> bool {
+        self.build_files.get(build_file) == Some(&fingerprint) && build_file.is_file()
+    }
+
+    /// Forgets the build files in `directory`, once they are all written.
+    pub fn complete(&mut self, directory: &Path) {
+        self.build_files
+            .retain(|build_file, _| !build_file.starts_with(directory));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.build_files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_files_are_written_until_their_targets_change() {
+        let build_directory = tempdir::TempDir::new("checkpoint").unwrap();
+        let written = build_directory.path().join("deps/app.dir/build.mk");
+        let failed = build_directory.path().join("deps/lib.dir/build.mk");
+        std::fs::create_dir_all(written.parent().unwrap()).unwrap();
+        std::fs::write(&written, "app:\n").unwrap();
+
+        let mut checkpoint = GenerationCheckpoint::new();
+        checkpoint.record(&written, 1, b"app:\n");
+        checkpoint.record(&failed, 2, b"lib:\n");
+        assert!(checkpoint.is_written(&written, 1));
+        assert!(!checkpoint.is_written(&written, 3));
+        assert!(!checkpoint.is_written(&failed, 2));
+
+        std::fs::remove_file(&written).unwrap();
+        assert!(!checkpoint.is_written(&written, 1));
+
+        checkpoint.complete(build_directory.path());
+        assert!(checkpoint.is_empty());
+    }
+}
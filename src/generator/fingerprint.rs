@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use crate::cache::Cacher;
+use crate::ProjectConfig;
+
+/// Identifies what generated the build files of a build tree: the version of yambs, and the
+/// options that end up in the build files without being tracked by them. Build files and objects
+/// made by another version or with other options can not be trusted to work with new ones.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GenerationFingerprint {
+    pub yambs_version: String,
+    pub options: BTreeMap<String, String>,
+}
+
+impl GenerationFingerprint {
+    pub fn new(project_config: &ProjectConfig) -> Self {
+        let options = vec![
+            ("std", Ok(project_config.std.to_string())),
+            ("language", serde_json::to_string(&project_config.language)),
+            (
+                "generator",
+                serde_json::to_string(&project_config.generator_type),
+            ),
+            ("defines", serde_json::to_string(&project_config.defines)),
+            (
+                "gc_sections",
+                serde_json::to_string(&project_config.gc_sections),
+            ),
+            ("hardened", serde_json::to_string(&project_config.hardened)),
+            (
+                "system_pch",
+                serde_json::to_string(&project_config.system_pch),
+            ),
+            ("warnings", serde_json::to_string(&project_config.warnings)),
+            (
+                "generated_warnings",
+                serde_json::to_string(&project_config.generated_warnings),
+            ),
+            (
+                "sanitizers",
+                serde_json::to_string(&project_config.sanitizers),
+            ),
+            (
+                "opt_level",
+                serde_json::to_string(&project_config.opt_level),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.unwrap_or_default()))
+        .collect();
+        Self {
+            yambs_version: env!("CARGO_PKG_VERSION").to_string(),
+            options,
+        }
+    }
+
+    /// Reasons the build files generated with `previous` are out of date.
+    pub fn changes_since(&self, previous: &GenerationFingerprint) -> Vec<String> {
+        let mut changes = Vec::new();
+        if self.yambs_version != previous.yambs_version {
+            changes.push(format!(
+                "yambs was upgraded from {} to {}",
+                previous.yambs_version, self.yambs_version
+            ));
+        }
+        // Options added by an upgrade are covered by the change of version.
+        for (option, value) in &self.options {
+            if let Some(previous_value) = previous.options.get(option) {
+                if previous_value != value {
+                    changes.push(format!(
+                        "{} changed from {} to {}",
+                        option, previous_value, value
+                    ));
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// Fingerprints of the generated build trees of a build directory, by build type.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GenerationFingerprints {
+    pub build_trees: BTreeMap<String, GenerationFingerprint>,
+}
+
+impl Cacher for GenerationFingerprints {
+    const CACHE_FILE_NAME: &'static str = "generation";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fingerprint(version: &str, options: &[(&str, &str)]) -> GenerationFingerprint {
+        GenerationFingerprint {
+            yambs_version: version.to_string(),
+            options: options
+                .iter()
+                .map(|(option, value)| (option.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn unchanged_fingerprint_has_no_changes() {
+        let current = fingerprint("0.1.2", &[("std", "c++17")]);
+        assert!(current.changes_since(&current.clone()).is_empty());
+    }
+
+    #[test]
+    fn changes_since_explains_upgrade_and_changed_options() {
+        let previous = fingerprint("0.1.1", &[("std", "c++17"), ("gc_sections", "false")]);
+        let current = fingerprint(
+            "0.1.2",
+            &[
+                ("std", "c++20"),
+                ("gc_sections", "false"),
+                ("sanitizers", "[]"),
+            ],
+        );
+        assert_eq!(
+            current.changes_since(&previous),
+            vec![
+                "yambs was upgraded from 0.1.1 to 0.1.2".to_string(),
+                "std changed from c++17 to c++20".to_string(),
+            ]
+        );
+    }
+}
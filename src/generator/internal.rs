@@ -0,0 +1,387 @@
+// The internal backend, which builds a build tree by running the steps of its build plan itself
+// instead of running make. `yambs build --backend internal` runs it as a process of its own,
+// `yambs run-plan`, whose output is read the same way as that of make: the markers of the steps it
+// starts and their commands on stdout, and the diagnostics of the commands on stderr, followed by a
+// line naming the output of each step that failed, as make prints it.
+
+use std::path::{Path, PathBuf};
+
+use crate::environment::Environment;
+use crate::errors::FsError;
+use crate::generator::makefile::make::BuildProcess;
+use crate::generator::plan::{BuildPlan, PlannedCompile, PlannedTarget, UnknownPlannedTarget};
+use crate::progress::{Step, StepEvent};
+use crate::warnings_report::WARNINGS_FILE_EXTENSION;
+
+/// Runs `yambs run-plan` with `args` in the current directory.
+#[derive(Debug)]
+pub struct InternalBackend {
+    args: Vec<String>,
+    executable: PathBuf,
+    environment: Environment,
+}
+
+impl InternalBackend {
+    pub fn new(args: &[String]) -> Result<Self, FsError> {
+        let executable = std::env::current_exe()
+            .map_err(|_| FsError::CouldNotFindProgram("yambs".to_string()))?;
+        Ok(Self {
+            args: args.to_vec(),
+            executable,
+            environment: Environment::new(),
+        })
+    }
+
+    pub fn run(&self) -> Result<BuildProcess, FsError> {
+        let child = self
+            .environment
+            .command(&self.executable)
+            .arg("run-plan")
+            .args(&self.args)
+            .stderr(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|_| {
+                FsError::Spawn(Box::new(std::process::Command::new(
+                    self.executable.display().to_string(),
+                )))
+            })?;
+        Ok(BuildProcess::new(child))
+    }
+}
+
+// Step of the plan, waiting for the steps making its inputs.
+struct PlannedStep<'plan> {
+    step: Step,
+    target: &'plan str,
+    output: &'plan Path,
+    command: &'plan str,
+    inputs: Vec<PathBuf>,
+    waits_for: Vec<usize>,
+    serialized: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepState {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+/// Builds `names` of `plan` from the directory of the plan, which is the current directory,
+/// running up to `jobs` commands at a time. A failing step stops new steps from starting, unless
+/// `keep_going` is given, which goes on with the steps that do not depend on it. Returns whether
+/// every step succeeded.
+pub fn execute(
+    plan: &BuildPlan,
+    names: &[String],
+    jobs: usize,
+    keep_going: bool,
+) -> Result<bool, UnknownPlannedTarget> {
+    let targets = plan.targets_to_build(names)?;
+    let steps = planned_steps(plan, &targets);
+    let mut states = vec![StepState::Pending; steps.len()];
+    let (sender, receiver) = std::sync::mpsc::channel::<(usize, bool)>();
+    let mut running = 0;
+    let mut failed = false;
+    std::thread::scope(|scope| loop {
+        while running < jobs.max(1) && (keep_going || !failed) {
+            let serialized_running = steps
+                .iter()
+                .zip(&states)
+                .any(|(step, state)| step.serialized && *state == StepState::Running);
+            let ready = (0..steps.len()).find(|&index| {
+                states[index] == StepState::Pending
+                    && steps[index]
+                        .waits_for
+                        .iter()
+                        .all(|&waited| states[waited] == StepState::Done)
+                    && !(steps[index].serialized && serialized_running)
+            });
+            let Some(index) = ready else {
+                break;
+            };
+            let step = &steps[index];
+            if is_up_to_date(step) {
+                states[index] = StepState::Done;
+                continue;
+            }
+            states[index] = StepState::Running;
+            running += 1;
+            println!(
+                "{}",
+                StepEvent::marker(step.step, step.target, &step.output.display().to_string())
+            );
+            println!("{}", step.command);
+            let sender = sender.clone();
+            scope.spawn(move || {
+                let _ = sender.send((index, run_step(step)));
+            });
+        }
+        if running == 0 {
+            break;
+        }
+        let (index, succeeded) = receiver.recv().unwrap();
+        running -= 1;
+        if succeeded {
+            let step = &steps[index];
+            println!(
+                "{}",
+                StepEvent::finished_marker(
+                    step.step,
+                    step.target,
+                    &step.output.display().to_string()
+                )
+            );
+            states[index] = StepState::Done;
+        } else {
+            states[index] = StepState::Failed;
+            failed = true;
+        }
+    });
+    Ok(!failed && states.iter().all(|state| *state == StepState::Done))
+}
+
+// The compiles and links of `targets` of `plan`. A link waits for the compiles of its objects,
+// wherever they are compiled, and for the links of the targets it links. Objects shared with a
+// target of the plan that is not built are compiled for the target linking them.
+fn planned_steps<'plan>(
+    plan: &'plan BuildPlan,
+    targets: &[&'plan PlannedTarget],
+) -> Vec<PlannedStep<'plan>> {
+    let compile_step = |target: &'plan str, compile: &'plan PlannedCompile| PlannedStep {
+        step: Step::Compile,
+        target,
+        output: &compile.object,
+        command: &compile.command,
+        inputs: std::iter::once(compile.source.clone())
+            .chain(compile.extra_files.iter().cloned())
+            .collect(),
+        waits_for: Vec::new(),
+        serialized: false,
+    };
+    let mut steps = Vec::new();
+    for target in targets {
+        for compile in &target.compiles {
+            steps.push(compile_step(&target.target, compile));
+        }
+    }
+    for target in targets {
+        for input in &target.link.inputs {
+            if steps.iter().any(|step| step.output == input.as_path()) {
+                continue;
+            }
+            let shared = plan
+                .targets
+                .iter()
+                .flat_map(|other| &other.compiles)
+                .find(|compile| compile.object == *input);
+            if let Some(compile) = shared {
+                steps.push(compile_step(&target.target, compile));
+            }
+        }
+    }
+    for target in targets {
+        let waits_for = steps
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| {
+                target
+                    .link
+                    .inputs
+                    .iter()
+                    .any(|input| input.as_path() == step.output)
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<usize>>();
+        steps.push(PlannedStep {
+            step: Step::Link,
+            target: &target.target,
+            output: &target.link.output,
+            command: &target.link.command,
+            inputs: target.link.inputs.clone(),
+            waits_for,
+            serialized: target.link.serialized,
+        });
+    }
+    steps
+}
+
+// Whether the output of `step` is newer than its inputs, as make decides it. Objects are compiled
+// again after changes to the headers they include as well, as listed in the depfile the compiler
+// writes next to them.
+fn is_up_to_date(step: &PlannedStep) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    let Ok(output_modified) = modified(step.output) else {
+        return false;
+    };
+    let mut inputs = step.inputs.clone();
+    if step.step == Step::Compile {
+        match std::fs::read_to_string(step.output.with_extension("d")) {
+            Ok(depfile) => inputs.extend(depfile_prerequisites(&depfile)),
+            Err(_) => return false,
+        }
+    }
+    inputs
+        .iter()
+        .all(|input| modified(input).is_ok_and(|input_modified| input_modified <= output_modified))
+}
+
+// Prerequisites of the rules of a depfile, such as "main.o: main.cpp util.h". The rules of the
+// headers alone, as written by -MP, have none.
+fn depfile_prerequisites(depfile: &str) -> Vec<PathBuf> {
+    const ESCAPED_SPACE: &str = "\u{0}";
+    depfile
+        .replace("\\\n", " ")
+        .replace("\\ ", ESCAPED_SPACE)
+        .lines()
+        .filter_map(|rule| {
+            rule.split_once(": ")
+                .map(|(_, prerequisites)| prerequisites)
+        })
+        .flat_map(|prerequisites| prerequisites.split_whitespace())
+        .map(|prerequisite| PathBuf::from(prerequisite.replace(ESCAPED_SPACE, " ")))
+        .collect()
+}
+
+// Runs the command of `step` by the shell. The diagnostics of a compile are kept next to its
+// object as well, as make does, so that warnings can be reported for objects that are up to date.
+fn run_step(step: &PlannedStep) -> bool {
+    let result = Environment::new()
+        .command(Path::new("sh"))
+        .arg("-c")
+        .arg(step.command)
+        .output();
+    let (code, stdout, stderr) = match result {
+        Ok(output) => (output.status.code(), output.stdout, output.stderr),
+        Err(e) => (None, Vec::new(), e.to_string().into_bytes()),
+    };
+    if step.step == Step::Compile {
+        let mut warnings_file = step.output.as_os_str().to_os_string();
+        warnings_file.push(format!(".{}", WARNINGS_FILE_EXTENSION));
+        let _ = std::fs::write(warnings_file, &stderr);
+    }
+    print!("{}", String::from_utf8_lossy(&stdout));
+    let mut diagnostics = String::from_utf8_lossy(&stderr).to_string();
+    if code != Some(0) {
+        diagnostics.push_str(&format!(
+            "yambs: *** [{}] Error {}\n",
+            step.output.display(),
+            code.unwrap_or(1)
+        ));
+    }
+    eprint!("{}", diagnostics);
+    code == Some(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::plan::PlannedLink;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn steps_run_once_their_inputs_are_made_and_only_when_out_of_date() {
+        let tempdir = tempdir::TempDir::new("internal").unwrap();
+        let directory = tempdir.path();
+        let source = directory.join("main.cpp");
+        std::fs::write(&source, "int main() {}").unwrap();
+        let object = directory.join("main.o");
+        let output = directory.join("app");
+        let log = directory.join("log");
+        let command = |step: &str, output: &Path| {
+            format!(
+                "echo {step} >> {log} && touch {output}",
+                log = log.display(),
+                output = output.display()
+            )
+        };
+        let plan = BuildPlan {
+            targets: vec![PlannedTarget {
+                target: "app".to_string(),
+                example: false,
+                dependencies: Vec::new(),
+                compiles: vec![PlannedCompile {
+                    source: source.clone(),
+                    object: object.clone(),
+                    extra_files: Vec::new(),
+                    command: format!(
+                        "{} && echo '{}: {}' > {}",
+                        command("compile", &object),
+                        object.display(),
+                        source.display(),
+                        object.with_extension("d").display()
+                    ),
+                }],
+                link: PlannedLink {
+                    output: output.clone(),
+                    inputs: vec![object.clone()],
+                    command: command("link", &output),
+                    serialized: false,
+                },
+            }],
+        };
+        assert!(execute(&plan, &[], 2, false).unwrap());
+        assert!(execute(&plan, &["app".to_string()], 2, false).unwrap());
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "compile\nlink\n");
+        assert!(execute(&plan, &["ap".to_string()], 2, false).is_err());
+
+        assert_eq!(
+            depfile_prerequisites("main.o: main.cpp my\\ dir/a.h \\\n b.h\n\nb.h:\n"),
+            vec![
+                PathBuf::from("main.cpp"),
+                PathBuf::from("my dir/a.h"),
+                PathBuf::from("b.h")
+            ]
+        );
+    }
+
+    #[test]
+    fn objects_shared_with_targets_not_built_are_compiled_for_the_target_linking_them() {
+        let tempdir = tempdir::TempDir::new("internal").unwrap();
+        let directory = tempdir.path();
+        let source = directory.join("util.cpp");
+        std::fs::write(&source, "").unwrap();
+        let object = directory.join("util.o");
+        let target = |name: &str, compiles: Vec<PlannedCompile>| {
+            let output = directory.join(name);
+            PlannedTarget {
+                target: name.to_string(),
+                example: false,
+                dependencies: Vec::new(),
+                compiles,
+                link: PlannedLink {
+                    inputs: vec![object.clone()],
+                    command: format!("test -f {} && touch {}", object.display(), output.display()),
+                    output,
+                    serialized: false,
+                },
+            }
+        };
+        let plan = BuildPlan {
+            targets: vec![
+                target(
+                    "app",
+                    vec![PlannedCompile {
+                        source: source.clone(),
+                        object: object.clone(),
+                        extra_files: Vec::new(),
+                        command: format!(
+                            "touch {} && echo '{}: {}' > {}",
+                            object.display(),
+                            object.display(),
+                            source.display(),
+                            object.with_extension("d").display()
+                        ),
+                    }],
+                ),
+                target("tool", Vec::new()),
+            ],
+        };
+        assert!(execute(&plan, &["tool".to_string()], 1, false).unwrap());
+        assert!(object.is_file());
+        assert!(!directory.join("app").exists());
+        assert!(directory.join("tool").is_file());
+    }
+}
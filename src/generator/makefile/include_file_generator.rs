@@ -1,22 +1,29 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
 
 use indoc;
 
+use crate::cli::configurations::{BuildType, OptLevel};
 use crate::compiler::Linker;
 use crate::compiler::StdLibCXX;
 use crate::compiler::Type;
-use crate::errors::FsError;
+use crate::flags::{AnnotatedFlags, FlagOrigin};
+use crate::generator::makefile::BUILD_FILE_EXTENSION;
 use crate::generator::{GeneratorError, UtilityGenerator};
+use crate::parser::types::Language;
 use crate::toolchain::NormalizedToolchain;
 use crate::utility;
 
+// Warning flags added on top of the default warnings by the strict warning profile.
+const STRICT_WARNING_FLAGS: &[&str] = &["-Wformat=2", "-Werror"];
+
 pub(crate) struct IncludeFileGenerator<'generator> {
-    file: Option<File>,
     output_directory: std::path::PathBuf,
     args: HashMap<&'generator str, String>,
     toolchain: &'generator NormalizedToolchain,
+    // Optimization level requested for a build type, in place of its default.
+    opt_level: Option<(BuildType, OptLevel)>,
+    // Where the standard comes from, the command line or the manifest.
+    std_origin: FlagOrigin,
 }
 
 impl<'generator> IncludeFileGenerator<'generator> {
@@ -27,19 +34,140 @@ impl<'generator> IncludeFileGenerator<'generator> {
         utility::create_dir(output_directory).unwrap();
 
         IncludeFileGenerator {
-            file: None,
             output_directory: output_directory.to_path_buf(),
             args: HashMap::new(),
             toolchain,
+            opt_level: None,
+            std_origin: FlagOrigin::Project,
+        }
+    }
+
+    pub fn set_opt_level(&mut self, build_type: &BuildType, opt_level: OptLevel) {
+        self.opt_level = Some((build_type.clone(), opt_level));
+    }
+
+    pub fn set_std(&mut self, std: &str, origin: FlagOrigin) {
+        self.args.insert("C++", std.to_lowercase());
+        self.std_origin = origin;
+    }
+
+    fn std_flag(&self) -> &str {
+        match self.args.get("C++").map(|std| std.as_str()) {
+            Some("c++98") => "-std=c++98",
+            Some("c++03") => "-std=c++03",
+            Some("c++11") => "-std=c++11",
+            Some("c++14") => "-std=c++14",
+            Some("c++17") => "-std=c++17",
+            Some("c++20") => "-std=c++20",
+            Some("c++23") => "-std=c++23",
+            _ => "-std=c++20",
+        }
+    }
+
+    fn opt_level_origin(&self, build_type: &BuildType) -> FlagOrigin {
+        match self.opt_level {
+            Some((ref overridden, _)) if overridden == build_type => FlagOrigin::CommandLine,
+            _ => FlagOrigin::Profile(build_type.clone()),
+        }
+    }
+
+    /// CXXFLAGS or CFLAGS given by the include files, in the order the Makefile includes them.
+    pub fn language_flags(&self, build_type: &BuildType, language: &Language) -> AnnotatedFlags {
+        let mut flags = self.toolchain_flags(language);
+        if *language == Language::CXX {
+            flags.push(self.std_flag(), self.std_origin.clone());
+        }
+        flags.append(default_language_flags(language));
+        flags.append(self.build_type_flags(build_type, language));
+        flags
+    }
+
+    /// CXX_LDFLAGS or CC_LDFLAGS given by the include files.
+    pub fn link_flags(&self, language: &Language) -> AnnotatedFlags {
+        let mut flags = AnnotatedFlags::new();
+        match language {
+            Language::CXX => flags.extend(
+                linker_flag(&self.toolchain.cxx.linker),
+                &FlagOrigin::Toolchain,
+            ),
+            Language::C => {
+                flags.extend(
+                    linker_flag(&self.toolchain.cc.linker),
+                    &FlagOrigin::Toolchain,
+                );
+                flags.extend(self.platform_flags(), &FlagOrigin::Toolchain);
+            }
+        }
+        flags
+    }
+
+    /// Default warnings of the compiler for `language`, along with those added by the strict
+    /// profile.
+    pub fn warning_flags(&self, language: &Language, strict: bool) -> Vec<&str> {
+        let mut warning_flags = match language {
+            Language::CXX => self.cxx_warning_flags_from_compiler_type(),
+            Language::C => self.c_warning_flags_from_compiler_type(),
+        };
+        if strict {
+            warning_flags.extend_from_slice(STRICT_WARNING_FLAGS);
+        }
+        warning_flags
+    }
+
+    fn toolchain_flags(&self, language: &Language) -> AnnotatedFlags {
+        let mut flags = AnnotatedFlags::new();
+        if *language == Language::CXX {
+            flags.extend([self.select_cxx_stdlib_impl()], &FlagOrigin::Toolchain);
+        }
+        flags.extend(self.platform_flags(), &FlagOrigin::Toolchain);
+        flags
+    }
+
+    // Flags selecting the platform built for, given both when compiling and linking: the SDK and
+    // deployment targets of Apple platforms, and the target and sysroot of a cross toolchain.
+    fn platform_flags(&self) -> Vec<String> {
+        let mut flags = self.toolchain.apple_platform.flags();
+        flags.extend(self.toolchain.cross_target.flags());
+        flags
+    }
+
+    fn build_type_flags(&self, build_type: &BuildType, language: &Language) -> AnnotatedFlags {
+        let profile = FlagOrigin::Profile(build_type.clone());
+        let opt_level = self.opt_level_flag(build_type);
+        let opt_level_origin = self.opt_level_origin(build_type);
+        let mut flags = AnnotatedFlags::new();
+        match (build_type, language) {
+            (BuildType::Debug, Language::CXX) => {
+                flags.push("-g", profile.clone());
+                flags.push(opt_level, opt_level_origin);
+                flags.push("-gdwarf", profile);
+            }
+            (BuildType::Release, Language::CXX) => {
+                flags.push(opt_level, opt_level_origin);
+                flags.push("-DNDEBUG", profile);
+            }
+            (_, Language::C) => flags.push(opt_level, opt_level_origin),
         }
+        flags
+    }
+
+    fn opt_level_flag(&self, build_type: &BuildType) -> String {
+        let opt_level = match self.opt_level {
+            Some((ref overridden, opt_level)) if overridden == build_type => opt_level,
+            _ => OptLevel::default_for(build_type),
+        };
+        let compiler_info = &self.toolchain.cxx.compiler.compiler_info;
+        opt_level.flag(
+            &compiler_info.compiler_type,
+            &compiler_info.compiler_version,
+        )
     }
 
-    fn create_mk_file(&mut self, filename_prefix: &str) {
+    fn write_mk_file(&self, filename_prefix: &str, data: &str) -> Result<(), GeneratorError> {
         let mut filename = std::path::PathBuf::from(filename_prefix);
-        filename.set_extension("mk");
-        let file =
-            utility::create_file(&self.output_directory.join(filename.to_str().unwrap())).unwrap();
-        self.file = Some(file);
+        filename.set_extension(BUILD_FILE_EXTENSION);
+        utility::write_file_atomically(&self.output_directory.join(filename), data.as_bytes())?;
+        Ok(())
     }
 
     pub fn print_build_directory(&self) -> &str {
@@ -115,51 +243,36 @@ impl<'generator> IncludeFileGenerator<'generator> {
     }
 
     fn generate_linker_selection(&self) -> String {
-        let cxx_linker = &self.toolchain.cxx.linker;
-
-        let mut formatted_string = String::new();
-
-        let cxx_linker_str = match cxx_linker {
-            Linker::Gold => "CXX_LDFLAGS += -fuse-ld=gold".to_string(),
-            Linker::Ld => "CXX_LDFLAGS += -fuse-ld=ld".to_string(),
-            Linker::LLD => "CXX_LDFLAGS += -fuse-ld=lld".to_string(),
-            _ => "CXX_LDFLAGS += ".to_string(),
-        };
-
-        formatted_string.push_str(&format!("{}\n\n", &cxx_linker_str));
-
-        let cc_linker = &self.toolchain.cc.linker;
-        let cc_linker_str = match cc_linker {
-            Linker::Gold => "CC_LDFLAGS += -fuse-ld=gold".to_string(),
-            Linker::Ld => "CC_LDFLAGS += -fuse-ld=ld".to_string(),
-            Linker::LLD => "CC_LDFLAGS += -fuse-ld=lld".to_string(),
-            _ => "CC_LDFLAGS += ".to_string(),
-        };
-
-        formatted_string.push_str(&format!("{}", &cc_linker_str));
-
-        formatted_string
+        format!(
+            "CXX_LDFLAGS += {}\n\nCC_LDFLAGS += {}",
+            linker_flag(&self.toolchain.cxx.linker).unwrap_or_default(),
+            linker_flag(&self.toolchain.cc.linker).unwrap_or_default(),
+        )
     }
 
     fn generate_warnings_mk(&mut self) -> Result<(), GeneratorError> {
         // TODO: Embed C standard version into file, in the same manner as
         // C++ standard version.
         //
-        self.create_mk_file("warnings");
         let data = indoc::formatdoc!("\
         #Generated by IncludeFileGenerator.generate_warnings_mk. DO NOT EDIT.
 
         include {def_directory}/defines.mk
 
         # Warning flags generated for C++ compiler type {compiler_type}
-        CXXFLAGS += \\
+        # Each target selects the warnings it is compiled with through its own <TARGET>_WARNINGS.
+        CXX_WARNINGS_DEFAULT := \\
         {cxx_warnings}
 
+        CXX_WARNINGS_STRICT := $(CXX_WARNINGS_DEFAULT) {strict_warnings}
+
         CXXFLAGS += {cpp_version}
 
-        CFLAGS += \\
+        C_WARNINGS_DEFAULT := \\
         {c_warnings}
 
+        C_WARNINGS_STRICT := $(C_WARNINGS_DEFAULT) {strict_warnings}
+
         #-Wall                     # Reasonable and standard
         #-Wextra                   # Warn if indentation implies blocks where blocks do not exist.
         #-Wmisleading-indentation  # Warn if if / else chain has duplicated conditions
@@ -182,82 +295,70 @@ impl<'generator> IncludeFileGenerator<'generator> {
         def_directory = self.print_build_directory(),
         cxx_warnings = self.cxx_warning_flags_from_compiler_type().join("\\\n"),
         c_warnings = self.c_warning_flags_from_compiler_type().join("\\\n"),
+        strict_warnings = STRICT_WARNING_FLAGS.join(" "),
         compiler_type = self.toolchain.cxx.compiler.compiler_info.compiler_type.to_string(),
         );
-        self.file
-            .as_ref()
-            .unwrap()
-            .write(data.as_bytes())
-            .map_err(|e| FsError::CreateFile(std::path::PathBuf::from("warnings.mk"), e))?;
-        Ok(())
+        self.write_mk_file("warnings", &data)
     }
 
     fn generate_debug_mk(&mut self) -> Result<(), GeneratorError> {
-        self.create_mk_file("debug");
-        let data = indoc::indoc!(
+        let data = indoc::formatdoc!(
             "\
         #Generated by IncludeFileGenerator.generate_debug_mk. DO NOT EDIT.
-        CXXFLAGS += -g \\
-                    -O0 \\
-                    -gdwarf
+        CXXFLAGS += {cxx_flags}
+
+        CFLAGS += {c_flags}
 
-        "
+        ",
+            cxx_flags = self
+                .build_type_flags(&BuildType::Debug, &Language::CXX)
+                .join(),
+            c_flags = self
+                .build_type_flags(&BuildType::Debug, &Language::C)
+                .join(),
         );
-        self.file
-            .as_ref()
-            .unwrap()
-            .write(data.as_bytes())
-            .map_err(|e| FsError::CreateFile(std::path::PathBuf::from("debug.mk"), e))?;
-        Ok(())
+        self.write_mk_file("debug", &data)
     }
 
     fn generate_release_mk(&mut self) -> Result<(), GeneratorError> {
-        self.create_mk_file("release");
-        let data = indoc::indoc!(
+        let data = indoc::formatdoc!(
             "\
         #Generated by IncludeFileGenerator.generate_release_mk. DO NOT EDIT.\n\
-        CXXFLAGS += -O3\\
-                    -DNDEBUG
-        "
-        )
-        .to_string();
-        self.file
-            .as_ref()
-            .unwrap()
-            .write(data.as_bytes())
-            .map_err(|e| FsError::CreateFile(std::path::PathBuf::from("release.mk"), e))?;
-        Ok(())
+        CXXFLAGS += {cxx_flags}
+
+        CFLAGS += {c_flags}
+        ",
+            cxx_flags = self
+                .build_type_flags(&BuildType::Release, &Language::CXX)
+                .join(),
+            c_flags = self
+                .build_type_flags(&BuildType::Release, &Language::C)
+                .join(),
+        );
+        self.write_mk_file("release", &data)
     }
 
     fn generate_default_mk(&mut self) -> Result<(), GeneratorError> {
-        self.create_mk_file("default_make");
-        let data = indoc::indoc!(
+        let data = indoc::formatdoc!(
             "\
         # Automatic dependency generation: Makes GCC generate the dependencies needed for a cpp file
         # excluding system header files.
-        CPPFLAGS +=-MMD\\
-                   -MP
+        CPPFLAGS += {cpp_flags}
 
-        # Additional CXX flags to be passed to the compiler
-        CXXFLAGS += -pthread\\
-                    -fPIC # Generate Position Independent code suitable for use in a shared library.
+        # Additional CXX flags to be passed to the compiler. -fPIC generates position independent
+        # code suitable for use in a shared library.
+        CXXFLAGS += {cxx_flags}
 
         # Additional AR flags being passed to the static library linker
         ARFLAGS = rs
-        "
-        )
-        .to_string();
-        self.file
-            .as_ref()
-            .unwrap()
-            .write(data.as_bytes())
-            .map_err(|e| FsError::CreateFile(std::path::PathBuf::from("default.mk"), e))?;
-        Ok(())
+        ",
+            cpp_flags = default_cpp_flags().join(),
+            cxx_flags = default_language_flags(&Language::CXX).join(),
+        );
+        self.write_mk_file("default_make", &data)
     }
 
     fn generate_defines_mk(&mut self) -> Result<(), GeneratorError> {
-        self.create_mk_file("defines");
-
         let data = indoc::formatdoc!(
             "\
         # Defines.mk\n\
@@ -270,28 +371,28 @@ impl<'generator> IncludeFileGenerator<'generator> {
         # Select linker if any specified in the toolchain file
         {linker_selection}
 
-        # Select stdlibc++ implementation based on toolchain file.
-        # Will be empty if not specified.
-        CXXFLAGS += {stdlib}
+        # Select stdlibc++ implementation, Apple SDK and deployment targets, and the target and
+        # sysroot of a cross toolchain based on toolchain file. Will be empty if not specified. C
+        # targets are linked without CFLAGS, so the platform flags are given to CC_LDFLAGS as well.
+        CXXFLAGS += {cxx_flags}
+        CFLAGS += {c_flags}
+        CC_LDFLAGS += {platform_flags}
         \n\
         ",
             compiler_conditional_flags = self.generate_toolchain_defines(),
             linker_selection = self.generate_linker_selection(),
-            stdlib = self.select_cxx_stdlib_impl(),
+            cxx_flags = self.toolchain_flags(&Language::CXX).join(),
+            c_flags = self.toolchain_flags(&Language::C).join(),
+            platform_flags = self.platform_flags().join(" "),
         );
-        self.file
-            .as_ref()
-            .unwrap()
-            .write(data.as_bytes())
-            .map_err(|e| FsError::CreateFile(std::path::PathBuf::from("defines.mk"), e))?;
-        Ok(())
+        self.write_mk_file("defines", &data)
     }
 
     fn generate_toolchain_defines(&self) -> String {
         let cxx = &self.toolchain.cxx.compiler.compiler_exe;
         let cc = &self.toolchain.cc.compiler.compiler_exe;
         let archiver_path = self.toolchain.archiver.path.clone();
-        indoc::formatdoc!(
+        let mut defines = indoc::formatdoc!(
             "
         # Toolchain definitions\n
         CC := {}
@@ -301,10 +402,39 @@ impl<'generator> IncludeFileGenerator<'generator> {
             cc.display(),
             cxx.display(),
             archiver_path.display(),
-        )
+        );
+        if let Some(ref objcopy) = self.toolchain.objcopy {
+            defines.push_str(&format!("OBJCOPY := {}\n", objcopy.display()));
+        }
+        defines
     }
 }
 
+// Selects the linker of the toolchain, if it names one.
+fn linker_flag(linker: &Linker) -> Option<&'static str> {
+    match linker {
+        Linker::Gold => Some("-fuse-ld=gold"),
+        Linker::Ld => Some("-fuse-ld=ld"),
+        Linker::LLD => Some("-fuse-ld=lld"),
+        _ => None,
+    }
+}
+
+// Generates the dependencies of each object, excluding system headers.
+pub(crate) fn default_cpp_flags() -> AnnotatedFlags {
+    let mut flags = AnnotatedFlags::new();
+    flags.extend(["-MMD", "-MP"], &FlagOrigin::Default);
+    flags
+}
+
+fn default_language_flags(language: &Language) -> AnnotatedFlags {
+    let mut flags = AnnotatedFlags::new();
+    if *language == Language::CXX {
+        flags.extend(["-pthread", "-fPIC"], &FlagOrigin::Default);
+    }
+    flags
+}
+
 impl<'generator> UtilityGenerator<'generator> for IncludeFileGenerator<'generator> {
     fn generate_build_files(&'generator mut self) -> Result<(), GeneratorError> {
         self.generate_warnings_mk()?;
@@ -323,19 +453,6 @@ impl<'generator> UtilityGenerator<'generator> for IncludeFileGenerator<'generato
     // ProjectConfig structure we should be able to fetch it from there and use that value
     // instead of this construct
     fn print_cpp_version(&'generator self) -> &str {
-        if self.args.contains_key("C++") {
-            match self.args.get("C++").unwrap().as_str() {
-                "c++98" => "-std=c++98",
-                "c++03" => "-std=c++03",
-                "c++11" => "-std=c++11",
-                "c++14" => "-std=c++14",
-                "c++17" => "-std=c++17",
-                "c++20" => "-std=c++20",
-                "c++23" => "-std=c++23",
-                _ => "-std=c++20",
-            }
-        } else {
-            "-std=c++20"
-        }
+        self.std_flag()
     }
 }
@@ -2,9 +2,12 @@ use std::io::{BufRead, BufReader};
 use std::process::{Command, ExitStatus};
 use std::vec::Vec;
 
+use crate::environment::Environment;
 use crate::errors::FsError;
+use crate::link_errors::LinkErrors;
 use crate::output;
 use crate::output::filter;
+use crate::progress::StepEvent;
 
 lazy_static::lazy_static! {
     static ref PROGRAM_ROOT_PATHS: Vec<std::path::PathBuf> = {
@@ -27,22 +30,183 @@ fn find_program(program: &str) -> Option<std::path::PathBuf> {
     None
 }
 
-pub struct BuildProcess(std::process::Child);
+lazy_static::lazy_static! {
+    // Line make prints when the recipe of a target fails, such as
+    // "make: *** [deps/app.dir/build.mk:40: /build/debug/deps/app.dir/main.o] Error 1". The
+    // internal backend prints the same line, prefixed by yambs instead.
+    static ref FAILED_TARGET: regex::Regex =
+        regex::Regex::new(r"^(?:make(?:\[\d+\])?|yambs): \*\*\* \[(?:.*?:\d+: )?(.+)\] Error \d+$").unwrap();
+}
+
+/// Number of translation units that may fail to compile before make is stopped from starting new
+/// jobs. Jobs already running are let finish, and the failures are collected for a report.
+#[derive(Debug)]
+pub struct ErrorBudget {
+    max_errors: usize,
+    objects: std::collections::HashSet<std::path::PathBuf>,
+    failed_objects: Vec<std::path::PathBuf>,
+    first_error: Option<String>,
+}
+
+impl ErrorBudget {
+    /// Budget of `max_errors` failures among the objects `objects`.
+    pub fn new(max_errors: usize, objects: &[std::path::PathBuf]) -> Self {
+        Self {
+            max_errors,
+            objects: objects.iter().cloned().collect(),
+            failed_objects: Vec::new(),
+            first_error: None,
+        }
+    }
+
+    /// Objects that failed to compile, in the order they failed.
+    pub fn failed_objects(&self) -> &[std::path::PathBuf] {
+        &self.failed_objects
+    }
+
+    /// First error printed by a compiler.
+    pub fn first_error(&self) -> Option<&str> {
+        self.first_error.as_deref()
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.failed_objects.len() >= self.max_errors
+    }
+
+    pub fn max_errors(&self) -> usize {
+        self.max_errors
+    }
+
+    // Records a line of the output of make. Returns true when the line exhausted the budget. Jobs
+    // that were running by then may fail as well, and are recorded too.
+    fn record(&mut self, line: &str) -> bool {
+        if self.first_error.is_none() && line.contains("error:") {
+            self.first_error = Some(line.to_string());
+        }
+        let failed_object = FAILED_TARGET
+            .captures(line)
+            .map(|captures| std::path::PathBuf::from(&captures[1]))
+            .filter(|target| self.objects.contains(target));
+        match failed_object {
+            Some(object) => {
+                self.failed_objects.push(object);
+                self.failed_objects.len() == self.max_errors
+            }
+            None => false,
+        }
+    }
+}
+
+/// Commands echoed by make that are printed in full, instead of only being logged. A command is
+/// selected when it mentions one of the paths, such as the object it compiles or its source file.
+#[derive(Debug, Default)]
+pub struct VerboseSelection {
+    paths: Vec<String>,
+}
+
+impl VerboseSelection {
+    pub fn new(paths: &[std::path::PathBuf]) -> Self {
+        Self {
+            paths: paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+        }
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        self.paths.iter().any(|path| line.contains(path.as_str()))
+    }
+}
+
+pub struct BuildProcess {
+    child: std::process::Child,
+    verbose_selection: Option<VerboseSelection>,
+    step_events: Option<std::sync::mpsc::Sender<StepEvent>>,
+    diagnostics_on_stdout: bool,
+}
 
 impl BuildProcess {
+    pub(crate) fn new(child: std::process::Child) -> Self {
+        Self {
+            child,
+            verbose_selection: None,
+            step_events: None,
+            diagnostics_on_stdout: false,
+        }
+    }
+
+    /// Prints what the build tool prints to stdout as errors, for tools such as ninja printing
+    /// the output of the commands they run there.
+    pub(crate) fn with_diagnostics_on_stdout(mut self) -> Self {
+        self.diagnostics_on_stdout = true;
+        self
+    }
+
+    /// Prints the commands selected by `selection` as make runs them.
+    pub fn with_verbose_selection(mut self, selection: VerboseSelection) -> Self {
+        self.verbose_selection = Some(selection);
+        self
+    }
+
+    /// Sends the steps make starts, as parsed from the markers of the rules, to `sender`. The
+    /// markers are only printed when make is run with `YAMBS_PROGRESS` set.
+    pub fn with_step_events(mut self, sender: std::sync::mpsc::Sender<StepEvent>) -> Self {
+        self.step_events = Some(sender);
+        self
+    }
+
     pub fn wait_and_log(&mut self, output: &output::Output) -> Option<ExitStatus> {
-        let stdout = self.0.stdout.take().unwrap();
-        let stderr = self.0.stderr.take().unwrap();
+        self.wait_and_log_within_budget(output, None).0
+    }
 
+    /// Waits for make like `wait_and_log`, and interrupts it once `budget` is exhausted. Make
+    /// starts no new jobs when interrupted, but waits for those running. The undefined references
+    /// reported by the linker are collected along the way.
+    pub fn wait_and_log_within_budget(
+        &mut self,
+        output: &output::Output,
+        mut budget: Option<ErrorBudget>,
+    ) -> (Option<ExitStatus>, Option<ErrorBudget>, LinkErrors) {
+        let stdout = self.child.stdout.take().unwrap();
+        let stderr = self.child.stderr.take().unwrap();
+        let make_pid = self.child.id();
+
+        let verbose_selection = self.verbose_selection.take();
+        let step_events = self.step_events.take();
+        let diagnostics_on_stdout = self.diagnostics_on_stdout;
+        let stdout_output = output.clone();
         let stdout_thread = std::thread::spawn(move || {
             let reader = BufReader::new(stdout);
             reader
                 .lines()
                 .filter_map(|line| line.ok())
-                .for_each(|line| log::debug!("{}", line));
+                .for_each(|line| {
+                    if let Some(event) = StepEvent::parse(&line) {
+                        log::trace!("{}", line);
+                        if let Some(ref step_events) = step_events {
+                            let _ = step_events.send(event);
+                        }
+                        return;
+                    }
+                    match verbose_selection {
+                        Some(ref selection) if selection.matches(&line) => {
+                            stdout_output.status_without_prefix(&line)
+                        }
+                        _ if diagnostics_on_stdout && !line.starts_with("ninja: ") => {
+                            let line = filter::filter_string(&line);
+                            if !line.is_empty() {
+                                filter::print_error_colored(&line, &stdout_output);
+                                log::error!("{}", line);
+                            }
+                        }
+                        _ => log::debug!("{}", line),
+                    }
+                });
         });
         let output_clone = output.clone();
         let stderr_thread = std::thread::spawn(move || {
+            let mut link_errors = LinkErrors::new();
             let reader = BufReader::new(stderr);
             reader
                 .lines()
@@ -52,14 +216,32 @@ impl BuildProcess {
                 .for_each(|line| {
                     filter::print_error_colored(&line, &output_clone);
                     log::error!("{}", line);
+                    link_errors.record(&line);
+                    if budget.as_mut().is_some_and(|budget| budget.record(&line)) {
+                        interrupt(make_pid);
+                    }
                 });
+            (budget, link_errors)
         });
 
-        let exit_status = self.0.wait().ok();
+        let exit_status = self.child.wait().ok();
 
         stdout_thread.join().unwrap();
-        stderr_thread.join().unwrap();
-        exit_status
+        let (budget, link_errors) = stderr_thread.join().unwrap();
+        (exit_status, budget, link_errors)
+    }
+}
+
+// Sends SIGINT to make only, and not to the compilers it runs.
+fn interrupt(pid: u32) {
+    log::debug!("Error budget exhausted. Interrupting make (pid {})", pid);
+    let interrupted = Environment::new()
+        .command(std::path::Path::new("kill"))
+        .args(["-INT", &pid.to_string()])
+        .status()
+        .is_ok_and(|status| status.success());
+    if !interrupted {
+        log::warn!("Could not interrupt make (pid {})", pid);
     }
 }
 
@@ -104,6 +286,7 @@ impl<'a> std::iter::IntoIterator for &'a MakeArgs {
 pub struct Make {
     args: MakeArgs,
     executable: std::path::PathBuf,
+    environment: Environment,
 }
 
 impl Make {
@@ -112,18 +295,101 @@ impl Make {
         let executable =
             find_program("make").ok_or_else(|| FsError::CouldNotFindProgram("make".to_string()))?;
 
-        Ok(Self { args, executable })
+        Ok(Self {
+            args,
+            executable,
+            environment: Environment::new(),
+        })
+    }
+
+    pub fn executable(&self) -> &std::path::Path {
+        &self.executable
     }
 
     pub fn run(&self) -> Result<BuildProcess, FsError> {
-        let child = Command::new(&self.executable)
+        let child = self
+            .environment
+            .command(&self.executable)
             .args(&self.args)
             .stderr(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
             .spawn()
-            .map_err(|_| FsError::Spawn(Command::new(self.executable.display().to_string())))?;
-        Ok(BuildProcess(child))
+            .map_err(|_| {
+                FsError::Spawn(Box::new(Command::new(
+                    self.executable.display().to_string(),
+                )))
+            })?;
+        Ok(BuildProcess::new(child))
+    }
+}
+
+lazy_static::lazy_static! {
+    // First line of `make --version`, such as "GNU Make 4.3".
+    static ref GNU_MAKE_VERSION: regex::Regex =
+        regex::Regex::new(r"^GNU Make (\d+)\.(\d+)").unwrap();
+}
+
+/// Features of GNU Make newer than 3.81 that the build files make use of when the make found
+/// supports them. Without them, the build files are written for make 3.81.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MakeFeatures {
+    version: Option<(u32, u32)>,
+}
+
+impl MakeFeatures {
+    /// Features of the make found, as told by its version. None if make can not be found or is not
+    /// GNU Make.
+    pub fn detect() -> Self {
+        let output = find_program("make").and_then(|make| {
+            Environment::new()
+                .command(&make)
+                .arg("--version")
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+        });
+        let features = output
+            .map(|output| Self::from_version_output(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_default();
+        log::debug!("Found make {:?}", features.version);
+        features
+    }
+
+    pub fn from_version_output(output: &str) -> Self {
+        let version = GNU_MAKE_VERSION.captures(output).and_then(|captures| {
+            Some((
+                captures[1].parse::<u32>().ok()?,
+                captures[2].parse::<u32>().ok()?,
+            ))
+        });
+        Self { version }
     }
+
+    /// Whether the output of each target can be kept together with --output-sync, from make 4.0.
+    pub fn output_sync(&self) -> bool {
+        self.is_at_least((4, 0))
+    }
+
+    /// Whether a rule can make several targets with a single run of its recipe, as grouped
+    /// targets (`&:`), from make 4.3.
+    pub fn grouped_targets(&self) -> bool {
+        self.is_at_least((4, 3))
+    }
+
+    /// Whether prerequisites can be listed in .EXTRA_PREREQS, which leaves them out of the
+    /// automatic variables such as `$^`, from make 4.3.
+    pub fn extra_prereqs(&self) -> bool {
+        self.is_at_least((4, 3))
+    }
+
+    fn is_at_least(&self, version: (u32, u32)) -> bool {
+        self.version.is_some_and(|found| found >= version)
+    }
+}
+
+/// Number of jobs make is run with unless given -j.
+pub fn default_jobs() -> usize {
+    Jobs::default().0
 }
 
 fn jobs_to_args(jobs: Jobs) -> [String; 2] {
@@ -145,3 +411,69 @@ impl std::default::Default for Jobs {
         Self(Jobs::calculate_heuristic())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_budget_counts_objects_that_failed_to_compile() {
+        let objects = vec![
+            std::path::PathBuf::from("/build/debug/deps/app.dir/a.o"),
+            std::path::PathBuf::from("/build/debug/deps/app.dir/b.o"),
+        ];
+        let mut budget = ErrorBudget::new(2, &objects);
+        assert!(!budget.record("src/a.cpp:3:5: error: 'x' was not declared in this scope"));
+        assert!(!budget
+            .record("make: *** [deps/app.dir/build.mk:40: /build/debug/deps/app.dir/a.o] Error 1"));
+        assert!(!budget.record("make: *** [deps/app.dir/build.mk:20: app] Error 1"));
+        assert!(budget.record("make[1]: *** [/build/debug/deps/app.dir/b.o] Error 1"));
+        assert_eq!(budget.failed_objects(), objects.as_slice());
+        assert_eq!(
+            budget.first_error(),
+            Some("src/a.cpp:3:5: error: 'x' was not declared in this scope")
+        );
+    }
+
+    #[test]
+    fn error_budget_counts_objects_the_internal_backend_failed_to_compile() {
+        let objects = vec![std::path::PathBuf::from("/build/debug/deps/app.dir/a.o")];
+        let mut budget = ErrorBudget::new(1, &objects);
+        assert!(!budget.record("yambs: *** [/build/debug/app] Error 1"));
+        assert!(budget.record("yambs: *** [/build/debug/deps/app.dir/a.o] Error 1"));
+        assert_eq!(budget.failed_objects(), objects.as_slice());
+    }
+
+    #[test]
+    fn make_features_follow_the_version_of_gnu_make() {
+        let make_3_81 = MakeFeatures::from_version_output(
+            "GNU Make 3.81\nCopyright (C) 2006  Free Software Foundation, Inc.\n",
+        );
+        assert!(!make_3_81.output_sync());
+        assert!(!make_3_81.grouped_targets());
+        let make_4_2 = MakeFeatures::from_version_output("GNU Make 4.2.1\nBuilt for x86_64\n");
+        assert!(make_4_2.output_sync());
+        assert!(!make_4_2.extra_prereqs());
+        let make_4_3 = MakeFeatures::from_version_output("GNU Make 4.3\n");
+        assert!(make_4_3.grouped_targets());
+        assert!(make_4_3.extra_prereqs());
+        assert_eq!(
+            MakeFeatures::from_version_output("bmake 20200710"),
+            MakeFeatures::default()
+        );
+    }
+
+    #[test]
+    fn verbose_selection_matches_commands_mentioning_its_paths() {
+        let selection = VerboseSelection::new(&[
+            std::path::PathBuf::from("/build/debug/deps/app.dir/main.o"),
+            std::path::PathBuf::from("/project/src/lib.cpp"),
+        ]);
+        assert!(
+            selection.matches("g++ -c -o /build/debug/deps/app.dir/main.o /project/src/main.cpp")
+        );
+        assert!(selection.matches("g++ -c -o /build/debug/deps/lib.dir/lib.o /project/src/lib.cpp"));
+        assert!(!selection
+            .matches("g++ -c -o /build/debug/deps/lib.dir/other.o /project/src/other.cpp"));
+    }
+}
@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::io::Write;
+use std::collections::{hash_map::Entry, HashMap};
 use std::rc::Rc;
 
 use indoc;
@@ -7,32 +7,105 @@ use indoc;
 mod include_file_generator;
 pub mod make;
 
+use crate::artifacts::{
+    ArtifactsManifest, SignedArtifact, ARTIFACTS_MANIFEST_FILE_NAME, SIGNING_PLAN_FILE_NAME,
+};
+use crate::build_file_hashes::hash_content;
+use crate::build_hygiene::{generated_file_conflicts, GeneratedFile};
 use crate::build_target;
 use crate::build_target::include_directories;
 use crate::build_target::{
-    associated_files::SourceFile,
-    include_directories::{IncludeDirectory, IncludeType},
-    pkg_config::ProvideMethod,
-    target_registry::TargetRegistry,
-    Dependency, DependencySource, LibraryType, TargetNode, TargetState, TargetType,
+    include_directories::IncludeType, pkg_config::ProvideMethod, target_registry::TargetRegistry,
+    DependencySource, LibraryType, PrintableLibrary, TargetNode, TargetState, TargetType,
+    GENERATED_DIRECTORY_NAME,
 };
+use crate::cache::Cache;
+use crate::cli::command_line::DEFAULT_INSTALL_PREFIX;
 use crate::cli::configurations;
+use crate::cli::configurations::OptLevel;
 use crate::cli::BuildDirectory;
+use crate::compiler;
+use crate::environment::Environment;
 use crate::errors::FsError;
+use crate::flags::{
+    AnnotatedCommand, AnnotatedFlags, FlagOrigin, GeneratedWarnings, WarningProfile, Warnings,
+};
 use crate::generator;
+use crate::generator::checkpoint::GenerationCheckpoint;
+use crate::generator::plan::{
+    BuildPlan, PlannedCompile, PlannedLink, PlannedTarget, PLAN_FILE_NAME,
+};
 use crate::generator::{
     targets::ObjectTarget, targets::ProgressDocument, targets::ProgressTrackingTarget, Generator,
-    GeneratorError, UtilityGenerator,
+    GeneratorError, TargetCommands, UtilityGenerator, EXAMPLES_TARGET_NAME,
+    HOST_BUILD_DIRECTORY_NAME, SIGN_TARGET_NAME,
 };
+use crate::hardening::{HardenedOutput, Hardening};
+use crate::memory_budget;
+use crate::memory_budget::LTO_LINK_LOCK_NAME;
 use crate::parser::types;
 use crate::parser::types::Language;
 use crate::progress;
-use crate::toolchain::NormalizedToolchain;
+use crate::sandbox::SandboxMapping;
+use crate::system_pch::SystemPch;
+use crate::targets::{Command, Install, Signing, Test, TestKind};
+use crate::timings::CompileTimings;
+use crate::toolchain::{DynamicLibraryFormat, NormalizedToolchain, OutputExtensions};
 use crate::utility;
+use crate::warnings_report::WARNINGS_FILE_EXTENSION;
 use crate::ProjectConfig;
 
 use include_file_generator::IncludeFileGenerator;
-pub use make::Make;
+pub use make::{Make, MakeFeatures};
+
+/// Name of the Makefile generated in the build tree of each build type.
+pub const MAKEFILE_NAME: &str = "Makefile";
+
+/// Name of the build file generated for each target in its own directory.
+pub const TARGET_BUILD_FILE_NAME: &str = "build.mk";
+
+/// Directory of the build directory the include files of the Makefiles are generated in.
+pub const INCLUDE_DIRECTORY_NAME: &str = "make_include";
+
+/// Extension of the include files and of the build files of the targets.
+pub const BUILD_FILE_EXTENSION: &str = "mk";
+
+// Extension of the checksum kept next to each static library.
+const ARCHIVE_HASH_EXTENSION: &str = "hash";
+
+// Flags a target compiles its sources with, as written to its build file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CompileFlags {
+    language_flags: String,
+    warnings: String,
+    /// Warnings of the generated sources, if the target has any.
+    generated_warnings: Option<String>,
+    cpp_flags: String,
+}
+
+// Flags of a target on top of those every target shares through the include files, each with
+// where it comes from. The build file of the target is written from these.
+struct AnnotatedCompileFlags {
+    language_flags: AnnotatedFlags,
+    cpp_flags: AnnotatedFlags,
+}
+
+// Directory the build file and objects of `target` are placed in, within the deps directory.
+fn target_directory_name(target: &TargetNode) -> String {
+    format!("{}.dir", target.borrow().name())
+}
+
+// CXXFLAGS or CFLAGS of the include files `target` is compiled and linked with. Targets with
+// no_default_flags are given none of them.
+fn default_flags_variable(target: &TargetNode, language: &types::Language) -> &'static str {
+    if target.borrow().compiler_flags.no_default_flags {
+        return "";
+    }
+    match language {
+        types::Language::CXX => "$(CXXFLAGS)",
+        types::Language::C => "$(CFLAGS)",
+    }
+}
 
 struct ExecutableTargetFactory;
 
@@ -41,36 +114,87 @@ impl ExecutableTargetFactory {
         target: &TargetNode,
         output_directory: &std::path::Path,
         language: &types::Language,
+        compile_timings: Option<&CompileTimings>,
+        shared_objects: &HashMap<std::path::PathBuf, std::path::PathBuf>,
+        link_settings: &LinkSettings,
     ) -> String {
         let target_name = target.borrow().name();
+        let output = link_settings
+            .output_extensions
+            .executable_file_name(&target_name);
 
-        match language {
+        let link_command = match language {
             types::Language::CXX => {
-                format!("\
-                    {target_name} : \\\n\
-                        {prerequisites}\n\
-                        \t$(strip $(CXX) $(CXXFLAGS) $(CPPFLAGS) $({target_name_capitalized}_CXXFLAGS) $({target_name_capitalized}_CPPFLAGS) $(WARNINGS) $(CXX_LDFLAGS) {dependencies} $^ $({target_name_capitalized}_LDFLAGS) -o $@)",
-                        target_name = target_name,
+                format!("$(strip $(CXX) {default_flags} $(CPPFLAGS) $({target_name_capitalized}_CXXFLAGS) $({target_name_capitalized}_CPPFLAGS) $({target_name_capitalized}_WARNINGS) $(CXX_LDFLAGS) {dependencies} {link_inputs} $({target_name_capitalized}_LDFLAGS) -o $@)",
+                        default_flags = default_flags_variable(target, language),
                         target_name_capitalized = target_name.to_uppercase(),
-                        prerequisites = generate_prerequisites(target, output_directory),
                         dependencies = generate_search_directories(target),
+                        link_inputs = generate_link_inputs(target, link_settings),
                 )
             }
             types::Language::C => {
-                format!("\
-                    {target_name} : \\\n\
-                        {prerequisites}\n\
-                        \t$(strip $(CC) $(CPPFLAGS) $({target_name_capitalized}_CFLAGS) $({target_name_capitalized}_CPPFLAGS) $(WARNINGS) $(CC_LDFLAGS) {dependencies} $^ $({target_name_capitalized}_LDFLAGS) -o $@)",
-                        target_name = target_name,
+                format!("$(strip $(CC) $(CPPFLAGS) $({target_name_capitalized}_CFLAGS) $({target_name_capitalized}_CPPFLAGS) $({target_name_capitalized}_WARNINGS) $(CC_LDFLAGS) {dependencies} {link_inputs} $({target_name_capitalized}_LDFLAGS) -o $@)",
                         target_name_capitalized = target_name.to_uppercase(),
-                        prerequisites = generate_prerequisites(target, output_directory),
                         dependencies = generate_search_directories(target),
+                        link_inputs = generate_link_inputs(target, link_settings),
                 )
             }
+        };
+        let mut formatted_string = generate_extra_prerequisites(target, &output, link_settings);
+        formatted_string.push_str(&format!(
+            "\
+            {output} : \\\n\
+                {prerequisites}\n\
+                {marker}\t{recipe}",
+            output = output,
+            marker = progress::StepEvent::marker_recipe(progress::Step::Link, &target_name),
+            prerequisites = generate_prerequisites(
+                target,
+                output_directory,
+                compile_timings,
+                shared_objects,
+                link_settings,
+            ),
+            recipe = serialize_link(link_command, link_settings.link_lock.as_deref()),
+        ));
+        formatted_string.push_str(&generate_post_link_recipe(target, &output));
+        formatted_string.push_str(&progress::StepEvent::finished_marker_recipe(
+            progress::Step::Link,
+            &target_name,
+        ));
+        if output != target_name {
+            formatted_string.push_str(&format!(
+                "\n\n# Convenience rule for \"{target_name}\"\n{target_name}: {output}",
+            ));
         }
+        formatted_string
     }
 }
 
+// Recipe lines converting the linked executable `output` into the post-link artifacts of
+// `target`, which are placed next to it. Map files are written by the linker instead.
+fn generate_post_link_recipe(target: &TargetNode, output: &str) -> String {
+    let borrowed_target = target.borrow();
+    borrowed_target
+        .post_link
+        .iter()
+        .filter_map(|artifact| {
+            artifact.objcopy_format().map(|format| {
+                format!(
+                    "\n\t$(OBJCOPY) -O {format} {output} {artifact}",
+                    artifact = post_link_artifact_name(&borrowed_target.name(), artifact),
+                )
+            })
+        })
+        .collect()
+}
+
+// Name of the post-link artifact of the executable `name`, relative to the directory of the
+// Makefile.
+fn post_link_artifact_name(name: &str, artifact: &types::PostLinkArtifact) -> String {
+    format!("{}.{}", name, artifact.extension())
+}
+
 struct LibraryTargetFactory;
 
 impl LibraryTargetFactory {
@@ -78,46 +202,93 @@ impl LibraryTargetFactory {
         target: &TargetNode,
         output_directory: &std::path::Path,
         language: &types::Language,
+        compile_timings: Option<&CompileTimings>,
+        shared_objects: &HashMap<std::path::PathBuf, std::path::PathBuf>,
+        link_settings: &LinkSettings,
     ) -> String {
         let mut formatted_string = String::new();
-        let library_name = library_name_from_target_type(&target.borrow().target_type);
-        let target_rule = match target.borrow().library_type().unwrap() {
+        let library = printable_library(target);
+        let library_name = link_settings.output_extensions.library_file_name(&library);
+        let versioned_library = versioned_library(target, &link_settings.output_extensions);
+        let mut shared_flags =
+            dynamic_library_link_flags(&library, &link_settings.output_extensions);
+        if let Some(ref versioned_library) = versioned_library {
+            shared_flags.push(link_settings.flavor.soname(&versioned_library.soname));
+        }
+        let shared_flags = shared_flags.join(" ");
+        let link_command = match target.borrow().library_type().unwrap() {
             LibraryType::Static => format!(
-                "\
-                {target_name} : \\\n\
-                    {prerequisites}\n\
-                    \t$(strip $(AR) $(ARFLAGS) $@ $?)\n\n",
-                target_name = library_name,
-                prerequisites = generate_prerequisites(target, output_directory)
+                "$(strip $(AR) $(ARFLAGS) $@ {})",
+                without_extra_files(target, "$?".to_string(), link_settings)
             ),
             LibraryType::Dynamic => match language {
                 types::Language::CXX => {
                     format!(
-                            "\
-                            {target_name} : \\\n\
-                                {prerequisites}\n\
-                                \t$(strip $(CXX) $(CXXFLAGS) $(CPPFLAGS) $({target_name_capitalized}_CXXFLAGS) $({target_name_capitalized}_CPPFLAGS) $(WARNINGS) $(CXX_LDFLAGS) -rdynamic -shared {dependencies} $^ $({target_name_capitalized}_LDFLAGS) -o $@)\n\n",
-                                target_name = library_name,
+                            "$(strip $(CXX) {default_flags} $(CPPFLAGS) $({target_name_capitalized}_CXXFLAGS) $({target_name_capitalized}_CPPFLAGS) $({target_name_capitalized}_WARNINGS) $(CXX_LDFLAGS) {shared_flags} {dependencies} {link_inputs} $({target_name_capitalized}_LDFLAGS) -o $@)",
+                                default_flags = default_flags_variable(target, language),
                                 target_name_capitalized = target.borrow().name().to_uppercase(),
-                                prerequisites = generate_prerequisites(target, output_directory),
+                                shared_flags = shared_flags,
                                 dependencies = generate_search_directories(target),
+                                link_inputs = generate_link_inputs(target, link_settings),
                         )
                 }
                 types::Language::C => {
                     format!(
-                            "\
-                            {target_name} : \\\n\
-                                {prerequisites}\n\
-                                \t$(strip $(CC) $(CPPFLAGS) $({target_name_capitalized}_CFLAGS) $({target_name_capitalized}_CPPFLAGS) $(WARNINGS) $(CC_LDFLAGS) -rdynamic -shared {dependencies} $^ $({target_name_capitalized}_LDFLAGS) -o $@)\n\n",
-                                target_name = library_name,
+                            "$(strip $(CC) $(CPPFLAGS) $({target_name_capitalized}_CFLAGS) $({target_name_capitalized}_CPPFLAGS) $({target_name_capitalized}_WARNINGS) $(CC_LDFLAGS) {shared_flags} {dependencies} {link_inputs} $({target_name_capitalized}_LDFLAGS) -o $@)",
                                 target_name_capitalized = target.borrow().name().to_uppercase(),
-                                prerequisites = generate_prerequisites(target, output_directory),
+                                shared_flags = shared_flags,
                                 dependencies = generate_search_directories(target),
+                                link_inputs = generate_link_inputs(target, link_settings),
                         )
                 }
             },
         };
+        let linked_file_name = versioned_library
+            .as_ref()
+            .map_or(&library_name, |versioned_library| {
+                &versioned_library.file_name
+            });
+        formatted_string.push_str(&generate_extra_prerequisites(
+            target,
+            linked_file_name,
+            link_settings,
+        ));
+        let target_rule = format!(
+            "\
+            {target_name} : \\\n\
+                {prerequisites}\n\
+                {marker}\t{recipe}{finished_marker}\n\n",
+            target_name = linked_file_name,
+            marker = progress::StepEvent::marker_recipe(progress::Step::Link, &library.name),
+            finished_marker =
+                progress::StepEvent::finished_marker_recipe(progress::Step::Link, &library.name),
+            prerequisites = generate_prerequisites(
+                target,
+                output_directory,
+                compile_timings,
+                shared_objects,
+                link_settings,
+            ),
+            recipe = serialize_link(link_command, link_settings.link_lock.as_deref()),
+        );
         formatted_string.push_str(&target_rule);
+        if let Some(ref versioned_library) = versioned_library {
+            for (symlink, points_to) in versioned_library.symlinks(&library_name) {
+                formatted_string.push_str(&format!(
+                    "{symlink} : {points_to}\n\tln -sfn {points_to} $@\n\n"
+                ));
+            }
+        }
+        if target.borrow().library_type() == Some(LibraryType::Static) {
+            formatted_string.push_str(&generate_archive_hash_rule(&library_name));
+        }
+        // The import library is made by the link command of the DLL.
+        if let Some(import_library) = link_settings
+            .output_extensions
+            .import_library_file_name(&library)
+        {
+            formatted_string.push_str(&format!("{} : {} ;\n\n", import_library, library_name));
+        }
 
         let convenience_rule = indoc::formatdoc!(
             "# Convenience rule for \"{target_name}\"
@@ -138,53 +309,170 @@ impl TargetRuleFactory {
         target: &TargetNode,
         output_dir: &std::path::Path,
         language: &types::Language,
+        compile_timings: Option<&CompileTimings>,
+        shared_objects: &HashMap<std::path::PathBuf, std::path::PathBuf>,
+        link_settings: &LinkSettings,
     ) -> String {
         if target.borrow().is_executable() {
-            ExecutableTargetFactory::create_rule(target, output_dir, language)
+            ExecutableTargetFactory::create_rule(
+                target,
+                output_dir,
+                language,
+                compile_timings,
+                shared_objects,
+                link_settings,
+            )
         } else {
-            LibraryTargetFactory::create_rule(target, output_dir, language)
+            LibraryTargetFactory::create_rule(
+                target,
+                output_dir,
+                language,
+                compile_timings,
+                shared_objects,
+                link_settings,
+            )
+        }
+    }
+}
+
+// Name of the file a target links, relative to the directory of the Makefile.
+fn output_name(target: &TargetNode, output_extensions: &OutputExtensions) -> String {
+    let borrowed_target = target.borrow();
+    match borrowed_target.target_type {
+        TargetType::Executable(_) => {
+            output_extensions.executable_file_name(&borrowed_target.name())
+        }
+        TargetType::Library(ref library) => output_extensions.library_file_name(library),
+    }
+}
+
+// File names of a shared library with a version: the file it is linked to, named by the full
+// version, and its soname, named by the major version, which is what the dynamic loader of the
+// dependents looks for. The output name of the library is a symlink to the soname, which is a
+// symlink to the linked file, such as libfoo.so -> libfoo.so.1 -> libfoo.so.1.2.3.
+struct VersionedLibrary {
+    file_name: String,
+    soname: String,
+}
+
+impl VersionedLibrary {
+    // Symlinks of the library named `output_name`, as the symlink and the file it points to.
+    fn symlinks(&self, output_name: &str) -> Vec<(String, String)> {
+        let mut symlinks = vec![(output_name.to_string(), self.soname.clone())];
+        if self.soname != self.file_name {
+            symlinks.push((self.soname.clone(), self.file_name.clone()));
         }
+        symlinks
+    }
+}
+
+// Only shared objects are versioned, as DLLs are looked up by their file name alone.
+fn versioned_library(
+    target: &TargetNode,
+    output_extensions: &OutputExtensions,
+) -> Option<VersionedLibrary> {
+    let version = target.borrow().version.clone()?;
+    if target.borrow().library_type() != Some(LibraryType::Dynamic)
+        || output_extensions.dynamic_library != DynamicLibraryFormat::SharedObject
+    {
+        return None;
     }
+    let output_name = output_name(target, output_extensions);
+    let major = version.split('.').next().unwrap_or(&version);
+    Some(VersionedLibrary {
+        file_name: format!("{}.{}", output_name, version),
+        soname: format!("{}.{}", output_name, major),
+    })
+}
+
+// Checksum of the archive `library_name`, which dependents depend on instead of the archive.
+fn archive_hash_file_name(library_name: &str) -> String {
+    format!("{}.{}", library_name, ARCHIVE_HASH_EXTENSION)
+}
+
+// The checksum of an archive is only written when the content of the archive changes, so that
+// dependents are not linked again when an archive is made again with the same content, as
+// deterministic archives are after recompiling sources into identical objects. The checksum is
+// computed again by every build once the archive is newer, which is cheap next to a link.
+fn generate_archive_hash_rule(library_name: &str) -> String {
+    format!(
+        "{hash_file} : {library_name}\n\
+         \t@cksum < $< > $@.tmp; if cmp -s $@.tmp $@; then rm -f $@.tmp; else mv -f $@.tmp $@; fi\n\n",
+        hash_file = archive_hash_file_name(library_name),
+        library_name = library_name,
+    )
 }
 
-fn library_name_from_target_type(target_type: &TargetType) -> String {
-    match target_type {
+fn printable_library(target: &TargetNode) -> PrintableLibrary {
+    match target.borrow().target_type {
         TargetType::Executable(_) => panic!("Not a library"),
-        TargetType::Library(lib) => lib.to_string(),
+        TargetType::Library(ref library) => library.clone(),
+    }
+}
+
+// Flags linking `library` as a dynamic library. A DLL is linked with an import library next to
+// it, which is what its dependents link.
+fn dynamic_library_link_flags(
+    library: &PrintableLibrary,
+    output_extensions: &OutputExtensions,
+) -> Vec<String> {
+    match output_extensions.import_library_file_name(library) {
+        Some(import_library) => match output_extensions.dynamic_library {
+            DynamicLibraryFormat::MsvcDll => vec![
+                "-shared".to_string(),
+                format!("-Wl,/implib:{}", import_library),
+            ],
+            _ => vec![
+                "-shared".to_string(),
+                format!("-Wl,--out-implib,{}", import_library),
+            ],
+        },
+        None => vec!["-rdynamic".to_string(), "-shared".to_string()],
     }
 }
 
-fn generate_prerequisites(target: &TargetNode, output_directory: &std::path::Path) -> String {
+// Make starts building prerequisites in the order they are listed, so the objects are sorted by
+// their compile duration from previous builds when timings are given.
+fn generate_prerequisites(
+    target: &TargetNode,
+    output_directory: &std::path::Path,
+    compile_timings: Option<&CompileTimings>,
+    shared_objects: &HashMap<std::path::PathBuf, std::path::PathBuf>,
+    link_settings: &LinkSettings,
+) -> String {
+    let output_extensions = &link_settings.output_extensions;
     let mut formatted_string = String::new();
+    let mut objects =
+        ObjectTarget::create_object_targets(target, output_directory, &output_extensions.object)
+            .into_iter()
+            .map(|object_target| {
+                shared_objects
+                    .get(&object_target.object)
+                    .cloned()
+                    .unwrap_or(object_target.object)
+            })
+            .collect::<Vec<std::path::PathBuf>>();
+    if let Some(compile_timings) = compile_timings {
+        compile_timings.sort_by_duration(&mut objects);
+    }
     let borrowed_target = target.borrow();
-    let sources = borrowed_target
-        .source_files
-        .iter()
-        .filter(|file| file.is_source())
-        .collect::<Vec<&SourceFile>>();
-    let dependency_root_path = &borrowed_target.manifest.directory;
-
-    for (i, source) in sources.iter().enumerate() {
-        let source_file = source.file();
-        let source_dir = source_file
-            .parent()
-            .and_then(|p| p.strip_prefix(dependency_root_path).ok())
-            .unwrap();
-        let object = output_directory
-            .join(source_dir)
-            .join(source_file.file_name().unwrap())
-            .with_extension("o");
 
+    for (i, object) in objects.iter().enumerate() {
         formatted_string.push_str(&format!("   {}", object.display()));
-        if i != (sources.len() - 1) {
+        if i != (objects.len() - 1) {
             formatted_string.push_str("\\\n");
         }
     }
     for dependency in &borrowed_target.dependencies {
         match dependency.source {
             build_target::DependencySource::FromSource(ref s) => {
+                let link_file_name = output_extensions.link_file_name(&s.library);
+                let prerequisite = match s.library.ty {
+                    LibraryType::Static => archive_hash_file_name(&link_file_name),
+                    LibraryType::Dynamic => link_file_name,
+                };
                 formatted_string.push_str("\\\n");
-                formatted_string.push_str(&format!("   {}", s.library));
+                formatted_string.push_str(&format!("   {}", prerequisite));
             }
             build_target::DependencySource::FromPkgConfig(ref pkg) => {
                 match pkg.method {
@@ -200,40 +488,390 @@ fn generate_prerequisites(target: &TargetNode, output_directory: &std::path::Pat
                     _ => {}
                 };
             }
+            build_target::DependencySource::FromCMake(ref cmake) => {
+                for library in &cmake.libraries {
+                    formatted_string.push_str("\\\n");
+                    formatted_string.push_str(&format!("   {}", library.display()));
+                }
+            }
+            build_target::DependencySource::FromYambsBuild(ref yambs_build) => {
+                for library in &yambs_build.libraries {
+                    formatted_string.push_str("\\\n");
+                    formatted_string.push_str(&format!("   {}", library.display()));
+                }
+            }
             _ => {}
         }
     }
+    if let Some(ref version_script) = borrowed_target.version_script {
+        formatted_string.push_str("\\\n");
+        formatted_string.push_str(&format!("   {}", version_script.display()));
+    }
+    if link_settings.make_features.extra_prereqs() {
+        return formatted_string;
+    }
+    for extra_file in &borrowed_target.extra_files {
+        formatted_string.push_str("\\\n");
+        formatted_string.push_str(&format!("   {}", extra_file.display()));
+    }
     formatted_string
 }
 
+// Linkers differ in how they are told to link every member of a static library and to keep a
+// shared library no symbol is referenced from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkerFlavor {
+    Gnu,
+    Apple,
+}
+
+impl LinkerFlavor {
+    fn from_toolchain(toolchain: &NormalizedToolchain) -> Self {
+        let apple_platform = &toolchain.apple_platform;
+        if cfg!(target_os = "macos")
+            || apple_platform.sdk.is_some()
+            || apple_platform.macos_deployment_target.is_some()
+            || apple_platform.ios_deployment_target.is_some()
+            || toolchain
+                .cross_target
+                .triple
+                .as_ref()
+                .is_some_and(|triple| triple.contains("apple"))
+        {
+            LinkerFlavor::Apple
+        } else {
+            LinkerFlavor::Gnu
+        }
+    }
+
+    fn whole_archive(&self, library: &str) -> String {
+        match self {
+            LinkerFlavor::Gnu => format!("-Wl,--whole-archive {} -Wl,--no-whole-archive", library),
+            LinkerFlavor::Apple => format!("-Wl,-force_load,{}", library),
+        }
+    }
+
+    fn no_as_needed(&self, library: &str) -> String {
+        match self {
+            // The state is pushed and popped, as --as-needed may be the default of the linker or
+            // be given by the user, and has to apply to the libraries that follow.
+            LinkerFlavor::Gnu => format!(
+                "-Wl,--push-state,--no-as-needed {} -Wl,--pop-state",
+                library
+            ),
+            LinkerFlavor::Apple => format!("-Wl,-needed_library,{}", library),
+        }
+    }
+
+    fn version_script(&self, path: &str) -> String {
+        match self {
+            LinkerFlavor::Gnu => format!("-Wl,--version-script,{}", path),
+            LinkerFlavor::Apple => format!("-Wl,-exported_symbols_list,{}", path),
+        }
+    }
+
+    fn soname(&self, soname: &str) -> String {
+        match self {
+            LinkerFlavor::Gnu => format!("-Wl,-soname,{}", soname),
+            LinkerFlavor::Apple => format!("-Wl,-install_name,@rpath/{}", soname),
+        }
+    }
+
+    fn map_file(&self, path: &str) -> String {
+        match self {
+            LinkerFlavor::Gnu => format!("-Wl,-Map={}", path),
+            LinkerFlavor::Apple => format!("-Wl,-map,{}", path),
+        }
+    }
+
+    // Link flag of a system library given by name. The C library of macOS, libSystem, provides
+    // the math, dynamic loading and threading libraries that are separate on Linux, some of which
+    // have no library of their own to link.
+    fn system_library(&self, name: &str) -> Option<String> {
+        match self {
+            LinkerFlavor::Apple if matches!(name, "c" | "m" | "dl" | "pthread" | "rt") => None,
+            _ => Some(format!("-l{}", name)),
+        }
+    }
+}
+
+// How the toolchain building a target links it and names its outputs.
+struct LinkSettings {
+    flavor: LinkerFlavor,
+    output_extensions: OutputExtensions,
+    // Lock the target is linked holding, so that it is linked one at a time with the other
+    // targets using link-time optimization.
+    link_lock: Option<std::path::PathBuf>,
+    make_features: MakeFeatures,
+}
+
+// The extra files of a target are prerequisites of its link but are not linked. From make 4.3 they
+// are listed in .EXTRA_PREREQS of the output, which leaves them out of `$^` and `$?`, and is
+// private so that the prerequisites of the output do not inherit it. Older makes list them with
+// the other prerequisites, and they are filtered out of the inputs of the link.
+fn generate_extra_prerequisites(
+    target: &TargetNode,
+    output: &str,
+    link_settings: &LinkSettings,
+) -> String {
+    let extra_files = &target.borrow().extra_files;
+    if extra_files.is_empty() || !link_settings.make_features.extra_prereqs() {
+        return String::new();
+    }
+    format!(
+        "{} : private .EXTRA_PREREQS := {}
+",
+        output,
+        join_paths(extra_files)
+    )
+}
+
+// `inputs` without the extra files of `target`, when they are listed with the prerequisites.
+fn without_extra_files(
+    target: &TargetNode,
+    inputs: String,
+    link_settings: &LinkSettings,
+) -> String {
+    let extra_files = &target.borrow().extra_files;
+    if extra_files.is_empty() || link_settings.make_features.extra_prereqs() {
+        return inputs;
+    }
+    format!("$(filter-out {},{})", join_paths(extra_files), inputs)
+}
+
+// `link_command` run while holding `link_lock`, if any. The lock is a directory, as making one is
+// atomic, and is waited for until the link holding it is done.
+fn serialize_link(link_command: String, link_lock: Option<&std::path::Path>) -> String {
+    match link_lock {
+        Some(link_lock) => format!(
+            "{{ until mkdir {lock} 2> /dev/null; do sleep 1; done; {command}; status=$$?; rmdir {lock}; test $$status -eq 0; }}",
+            lock = quote_shell_argument(&link_lock.display().to_string()),
+            command = link_command,
+        ),
+        None => link_command,
+    }
+}
+
+// Inputs of the link command of a target. The prerequisites are linked as they are listed, unless
+// the library of a dependency is linked with options of its own, in which case the libraries are
+// listed explicitly, in the same order, so that the options wrap only that library.
+fn generate_link_inputs(target: &TargetNode, link_settings: &LinkSettings) -> String {
+    let has_link_options = target.borrow().dependencies.iter().any(|dependency| {
+        dependency
+            .source
+            .from_source()
+            .is_some_and(|s| s.link_options != types::LinkOptions::default())
+    });
+    // Archives are prerequisites through their checksums, which are linked as the archives. The
+    // version script is a prerequisite so that the library is linked again when it changes, but is
+    // given to the linker by its own flag.
+    if !has_link_options {
+        let inputs = without_extra_files(
+            target,
+            format!("$(patsubst %.{0},%,$^)", ARCHIVE_HASH_EXTENSION),
+            link_settings,
+        );
+        return match target.borrow().version_script {
+            Some(ref version_script) => {
+                format!("$(filter-out {},{})", version_script.display(), inputs)
+            }
+            None => inputs,
+        };
+    }
+
+    let mut inputs = vec![format!(
+        "$(filter %.{},$^)",
+        link_settings.output_extensions.object
+    )];
+    inputs.push(annotated_link_inputs(target, link_settings).join());
+    inputs.join(" ")
+}
+
+// Libraries of the dependencies of `target`, with the link options of each applied.
+fn annotated_link_inputs(target: &TargetNode, link_settings: &LinkSettings) -> AnnotatedFlags {
+    let linker_flavor = link_settings.flavor;
+    let borrowed_target = target.borrow();
+    let mut inputs = AnnotatedFlags::new();
+    for dependency in &borrowed_target.dependencies {
+        let origin = FlagOrigin::Dependency(dependency.source.name());
+        match dependency.source {
+            build_target::DependencySource::FromSource(ref s) => {
+                let library = link_settings.output_extensions.link_file_name(&s.library);
+                let input = match s.library.ty {
+                    LibraryType::Static if s.link_options.whole_archive => {
+                        linker_flavor.whole_archive(&library)
+                    }
+                    LibraryType::Dynamic if !s.link_options.as_needed => {
+                        linker_flavor.no_as_needed(&library)
+                    }
+                    LibraryType::Static if !s.link_options.as_needed => {
+                        log::warn!(
+                            "as_needed of dependency {} of {} is ignored, as it only applies to shared libraries",
+                            s.library.name,
+                            borrowed_target.name(),
+                        );
+                        library
+                    }
+                    LibraryType::Dynamic if s.link_options.whole_archive => {
+                        log::warn!(
+                            "whole_archive of dependency {} of {} is ignored, as it only applies to static libraries",
+                            s.library.name,
+                            borrowed_target.name(),
+                        );
+                        library
+                    }
+                    _ => library,
+                };
+                inputs.push(input, origin);
+            }
+            build_target::DependencySource::FromPkgConfig(ref pkg) => {
+                if let ProvideMethod::Finegrained(ref libs) = pkg.method {
+                    inputs.extend(
+                        libs.iter().map(|lib| lib.path().display().to_string()),
+                        &origin,
+                    );
+                }
+            }
+            build_target::DependencySource::FromCMake(ref cmake) => {
+                inputs.extend(
+                    cmake
+                        .libraries
+                        .iter()
+                        .map(|library| library.display().to_string()),
+                    &origin,
+                );
+            }
+            build_target::DependencySource::FromYambsBuild(ref yambs_build) => {
+                inputs.extend(
+                    yambs_build
+                        .libraries
+                        .iter()
+                        .map(|library| library.display().to_string()),
+                    &origin,
+                );
+            }
+            _ => {}
+        }
+    }
+    inputs
+}
+
 fn generate_search_directories(target: &TargetNode) -> String {
     let borrowed_target = target.borrow();
-    let mut formatted_string = String::new();
-    formatted_string.push_str(&borrowed_target.include_directory.as_include_flag());
+    let mut include_directories = include_directories::IncludeDirectories::new();
+    for include_directory in &borrowed_target.project_include_directories {
+        include_directories.add(include_directory.clone());
+    }
 
     for dependency in &borrowed_target.dependencies {
-        match dependency.source.from_source() {
-            Some(sd) => {
-                let include_dir = &sd.include_directory;
-                formatted_string.push_str(&include_dir.as_include_flag());
+        if let Some(sd) = dependency.source.from_source() {
+            for include_directory in &sd.include_directories {
+                include_directories.add(include_directory.clone());
             }
-            None => {}
         }
     }
-    formatted_string.trim_end().to_string()
+    generate_include_directories(&include_directories)
+}
+
+// Include directories of `target`, in the order of BuildTarget::include_directories, each with the
+// dependency it comes from.
+fn annotated_include_directories(target: &TargetNode) -> AnnotatedFlags {
+    let borrowed_target = target.borrow();
+    let mut flags = AnnotatedFlags::new();
+    let mut add = |include_directory: &include_directories::IncludeDirectory,
+                   origin: &FlagOrigin| {
+        let path = quote_shell_argument(&include_directory.path.display().to_string());
+        let flag = match include_directory.include_type {
+            IncludeType::System => format!("-isystem {}", path),
+            IncludeType::Quote => format!("-iquote {}", path),
+            IncludeType::Include => format!("-I{}", path),
+        };
+        if !flags.iter().any(|annotated| annotated.flag == flag) {
+            flags.push(flag, origin.clone());
+        }
+    };
+    for include_directory in &borrowed_target.project_include_directories {
+        add(include_directory, &FlagOrigin::Target);
+    }
+    for path in &borrowed_target.compiler_flags.include_directories {
+        add(
+            &include_directories::IncludeDirectory {
+                include_type: IncludeType::Include,
+                path: path.to_path_buf(),
+            },
+            &FlagOrigin::Target,
+        );
+    }
+    for dependency in &borrowed_target.dependencies {
+        let origin = FlagOrigin::Dependency(dependency.source.name());
+        match dependency.source {
+            DependencySource::FromSource(ref s) => {
+                for include_directory in &s.include_directories {
+                    add(include_directory, &origin);
+                }
+            }
+            DependencySource::FromHeaderOnly(ref h) => add(&h.include_directory, &origin),
+            DependencySource::FromPkgConfig(ref pkg) => {
+                for include_directory in &pkg.include_directories {
+                    add(include_directory, &origin);
+                }
+            }
+            DependencySource::FromCMake(ref cmake) => {
+                for include_directory in &cmake.include_directories {
+                    add(include_directory, &origin);
+                }
+            }
+            DependencySource::FromYambsBuild(ref yambs_build) => {
+                for include_directory in &yambs_build.include_directories {
+                    add(include_directory, &origin);
+                }
+            }
+        }
+    }
+    for path in &borrowed_target.compiler_flags.system_include_directories {
+        add(
+            &include_directories::IncludeDirectory {
+                include_type: IncludeType::System,
+                path: path.to_path_buf(),
+            },
+            &FlagOrigin::Target,
+        );
+    }
+    flags
 }
 
-fn generate_defines(defines: &[types::Define]) -> String {
+// Values of defines are passed to the compiler verbatim, so that a value such as "1.2 beta", with
+// the quotes, defines a string literal. The flags are quoted for the shell.
+fn define_flags(defines: &[types::Define]) -> Vec<String> {
     defines
         .iter()
         .map(|d| {
-            if let Some(ref value) = d.value {
-                format!(" -D{}={}", d.macro_, value)
+            let define = if let Some(ref value) = d.value {
+                format!("-D{}={}", d.macro_, value)
+            } else {
+                format!("-D{}", d.macro_)
+            };
+            quote_shell_argument(&define)
+        })
+        .collect()
+}
+
+// Define of the macro `<NAME>_EXPORT` the headers of the DLL `library` declare their symbols with.
+fn export_define(library: &str, value: &str) -> types::Define {
+    let name = library
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
             } else {
-                format!(" -D{}", d.macro_,)
+                '_'
             }
         })
-        .collect::<String>()
+        .collect::<String>();
+    types::Define {
+        macro_: format!("{}_EXPORT", name),
+        value: Some(value.to_string()),
+    }
 }
 
 fn generate_include_directories(
@@ -241,17 +879,25 @@ fn generate_include_directories(
 ) -> String {
     let mut formatted_string = String::new();
     for include in include_directories {
-        if include.include_type == IncludeType::System {
-            formatted_string.push_str(&format!("-isystem {}", include.path.display()))
-        } else {
-            formatted_string.push_str(&format!("-I{}", include.path.display()))
+        let path = quote_recipe_argument(&include.path.display().to_string());
+        match include.include_type {
+            IncludeType::System => formatted_string.push_str(&format!("-isystem {}", path)),
+            IncludeType::Quote => formatted_string.push_str(&format!("-iquote {}", path)),
+            IncludeType::Include => formatted_string.push_str(&format!("-I{}", path)),
         }
         formatted_string.push(' ');
     }
     formatted_string.trim_end().to_string()
 }
 
-fn generate_object_target(object_target: &ObjectTarget, language: &types::Language) -> String {
+// Generated files are order-only prerequisites, so that sources including generated headers are not
+// compiled before the headers exist.
+fn generate_object_target(
+    object_target: &ObjectTarget,
+    generated_files: &[std::path::PathBuf],
+    language: &types::Language,
+    default_flags: &str,
+) -> String {
     let mut formatted_string = String::new();
     formatted_string.push_str(&format!(
         "# Build rule for {}\n",
@@ -261,34 +907,129 @@ fn generate_object_target(object_target: &ObjectTarget, language: &types::Langua
     formatted_string.push_str(": \\\n");
     formatted_string.push('\t');
     formatted_string.push_str(&object_target.source.display().to_string());
-    formatted_string.push('\n');
-    match language {
-        types::Language::CXX => {
-            formatted_string.push_str(&format!(
-                "\t$(strip $(CXX) $(CXXFLAGS) $(CPPFLAGS) $({target}_CXXFLAGS) $({target}_CPPFLAGS) \
-                 $(WARNINGS) {dependencies} $< -c -o $@)\n\n",
-                dependencies = generate_include_directories(&object_target.include_directories),
-                target = object_target.target.to_uppercase(),
-            ));
-        }
-        types::Language::C => {
-            formatted_string.push_str(&format!(
-                "\t$(strip $(CC) $(CFLAGS) $(CPPFLAGS) $({target}_CFLAGS) $({target}_CPPFLAGS) \
-                 $(WARNINGS) {dependencies} $< -c -o $@)\n\n",
-                dependencies = generate_include_directories(&object_target.include_directories),
-                target = object_target.target.to_uppercase(),
-            ));
-        }
+    if !object_target.extra_files.is_empty() {
+        formatted_string.push(' ');
+        formatted_string.push_str(&join_paths(&object_target.extra_files));
     }
+    if !generated_files.is_empty() {
+        formatted_string.push_str(" | ");
+        formatted_string.push_str(&join_paths(generated_files));
+    }
+    formatted_string.push('\n');
+    // Generated sources are compiled with warnings of their own, as they can not be fixed.
+    let warnings = if object_target.generated {
+        "GENERATED_WARNINGS"
+    } else {
+        "WARNINGS"
+    };
+    let compile_command = match language {
+        types::Language::CXX => format!(
+            "$(strip $(CXX) {default_flags} $(CPPFLAGS) $({target}_CXXFLAGS) $({target}_CPPFLAGS) \
+             $({target}_{warnings}) {dependencies} $< -c -o $@)",
+            dependencies = generate_include_directories(&object_target.include_directories),
+            target = object_target.target.to_uppercase(),
+        ),
+        types::Language::C => format!(
+            "$(strip $(CC) {default_flags} $(CPPFLAGS) $({target}_CFLAGS) $({target}_CPPFLAGS) \
+             $({target}_{warnings}) {dependencies} $< -c -o $@)",
+            dependencies = generate_include_directories(&object_target.include_directories),
+            target = object_target.target.to_uppercase(),
+        ),
+    };
+    // The diagnostics are kept next to the object as well, so that warnings can be reported for
+    // objects that are up to date.
+    let compile_command = format!(
+        "{{ {command} 2> $@.{extension}; status=$$?; cat $@.{extension} >&2; test $$status -eq 0; }}",
+        command = compile_command,
+        extension = WARNINGS_FILE_EXTENSION,
+    );
+    formatted_string.push_str(&progress::StepEvent::marker_recipe(
+        progress::Step::Compile,
+        &object_target.target,
+    ));
+    formatted_string.push_str(&format!("\t{}", compile_command));
+    formatted_string.push_str(&progress::StepEvent::finished_marker_recipe(
+        progress::Step::Compile,
+        &object_target.target,
+    ));
+    formatted_string.push_str("\n\n");
     formatted_string
 }
 
+// The precompiled standard library headers are made by the top-level Makefile, once for all
+// targets opting in. They are written under a name of their own first, as other builds sharing
+// the cache may be precompiling them at the same time.
+fn generate_system_pch_rule(system_pch: &SystemPch) -> String {
+    format!(
+        "\n# Standard library headers precompiled for the compiler, standard and build configuration,\n\
+         # shared with the other builds using them\n\
+         {precompiled_header}: {header}\n\
+         \t$(strip $(CXX) $(CXXFLAGS) $(CPPFLAGS) -x c++-header $< -MF /dev/null -o $@.$$$$) && mv -f $@.$$$$ $@\n",
+        precompiled_header = system_pch.precompiled_header().display(),
+        header = system_pch.header().display(),
+    )
+}
+
+fn join_paths(paths: &[std::path::PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Quotes an argument for the shell running the recipe, escaping $ for make.
+fn quote_recipe_argument(argument: &str) -> String {
+    quote_shell_argument(argument).replace('$', "$$")
+}
+
+// Escapes what make interprets in the value of a variable assignment, which is a reference to a
+// variable for $ and a comment for #. The value is still split into words by the shell.
+fn escape_variable_value(value: &str) -> String {
+    value.replace('$', "$$").replace('#', "\\#")
+}
+
+fn quote_shell_argument(argument: &str) -> String {
+    let is_safe = !argument.is_empty()
+        && argument
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./=:+,@%".contains(c));
+    if is_safe {
+        argument.to_string()
+    } else {
+        format!("'{}'", argument.replace('\'', "'\\''"))
+    }
+}
+
 pub struct MakefileGenerator {
     pub toolchain: Rc<RefCell<NormalizedToolchain>>,
     pub project_config: ProjectConfig,
     pub build_directory: BuildDirectory,
     pub output_directory: std::path::PathBuf,
     pub progress_document: ProgressDocument,
+    pub compile_timings: Option<CompileTimings>,
+    /// Artifacts installed by the install target of the top-level Makefile.
+    pub install: Vec<Install>,
+    /// Signing of the artifacts of the project, by rules of the top-level Makefile.
+    pub signing: Option<Signing>,
+    /// Compile-fail tests, compiled by rules of the top-level Makefile.
+    pub compile_fail_tests: Vec<Test>,
+    /// Context of the targets build files are generated for. Build files for targets built for
+    /// the host are generated by a separate generator, in a build tree of their own.
+    pub build_for: types::BuildFor,
+    // Objects that are compiled by another target from the same source with the same flags,
+    // mapped to the object of that target.
+    shared_objects: HashMap<std::path::PathBuf, std::path::PathBuf>,
+    // Precompiled standard library headers for the targets opting in. Only GCC falls back to
+    // parsing the headers when the precompiled header does not match a translation unit.
+    system_pch: Option<SystemPch>,
+    // Lock held by the links of targets using link-time optimization, which are then linked one
+    // at a time.
+    lto_link_lock: Option<std::path::PathBuf>,
+    // Whether the build plan is written next to the top-level Makefile, for the internal backend.
+    build_plan: bool,
+    // Features of the make the build files are written for, beyond those of make 3.81.
+    make_features: MakeFeatures,
 }
 
 impl MakefileGenerator {
@@ -298,29 +1039,169 @@ impl MakefileGenerator {
     ) -> Result<Self, GeneratorError> {
         let build_directory = project_config.build_directory.clone();
         utility::create_dir(build_directory.as_path())?;
+        let system_pch = {
+            let borrowed_toolchain = toolchain.borrow();
+            let compiler = &borrowed_toolchain.cxx.compiler;
+            match (
+                &project_config.language,
+                &compiler.compiler_info.compiler_type,
+            ) {
+                (Language::CXX, compiler::Type::Gcc) => SystemPch::default_cache_directory()
+                    .and_then(|cache| SystemPch::new(&cache, compiler, project_config)),
+                _ => None,
+            }
+        };
         Ok(Self {
             project_config: project_config.clone(),
             build_directory: build_directory.clone(),
             output_directory: build_directory.as_path().to_path_buf(),
             progress_document: ProgressDocument::new(),
             toolchain,
+            compile_timings: None,
+            install: Vec::new(),
+            signing: None,
+            compile_fail_tests: Vec::new(),
+            build_for: types::BuildFor::Target,
+            shared_objects: HashMap::new(),
+            system_pch,
+            lto_link_lock: None,
+            build_plan: false,
+            make_features: MakeFeatures::default(),
         })
     }
 
-    fn generate_all_target_for_progress_document(&mut self, object_targets: &[ObjectTarget]) {
+    /// Orders the prerequisites of targets by the compile durations of previous builds, which
+    /// yambs measures from the step markers of the rules.
+    pub fn with_compile_timings(mut self, compile_timings: CompileTimings) -> Self {
+        self.compile_timings = Some(compile_timings);
+        self
+    }
+
+    /// Links the targets using link-time optimization one at a time, as each of them may take as
+    /// much memory as compiling all of its sources at once.
+    pub fn with_serialized_lto_links(mut self) -> Self {
+        self.lto_link_lock = Some(self.build_directory.as_path().join(LTO_LINK_LOCK_NAME));
+        self
+    }
+
+    /// Generates install and uninstall targets for `install`.
+    pub fn with_install(mut self, install: Vec<Install>) -> Self {
+        self.install = install;
+        self
+    }
+
+    /// Generates rules signing the artifacts of `signing` once they are linked, which the default
+    /// target and the install target depend on.
+    pub fn with_signing(mut self, signing: Option<Signing>) -> Self {
+        self.signing = signing;
+        self
+    }
+
+    /// Writes the build plan of the targets next to the top-level Makefile, for the internal
+    /// backend to build them with the same commands as make.
+    pub fn with_build_plan(mut self) -> Self {
+        self.build_plan = true;
+        self
+    }
+
+    /// Writes the build files for a make with `make_features`, such as the one found by
+    /// `MakeFeatures::detect`, instead of for make 3.81.
+    pub fn with_make_features(mut self, make_features: MakeFeatures) -> Self {
+        self.make_features = make_features;
+        self
+    }
+
+    /// Generates rules compiling the sources of the compile-fail tests among `tests`.
+    pub fn with_compile_fail_tests(mut self, tests: Vec<Test>) -> Self {
+        self.compile_fail_tests = tests
+            .into_iter()
+            .filter(|test| matches!(test.kind, TestKind::CompileFail { .. }))
+            .collect();
+        self
+    }
+
+    // Directory of the top-level Makefile, which make is run from and where executables are linked.
+    fn makefile_directory(&self) -> std::path::PathBuf {
+        self.build_directory
+            .as_path()
+            .join(self.project_config.build_type.to_string())
+    }
+
+    // Directory of the Makefile of the host build tree.
+    fn host_makefile_directory(&self) -> std::path::PathBuf {
+        self.build_directory
+            .as_path()
+            .join(HOST_BUILD_DIRECTORY_NAME)
+            .join(self.project_config.build_type.to_string())
+    }
+
+    // Directory of the Makefile that builds `target`, which is in the host build tree for targets
+    // built for the host.
+    fn makefile_directory_of(&self, target: &TargetNode) -> std::path::PathBuf {
+        if target.borrow().build_for == self.build_for {
+            self.makefile_directory()
+        } else {
+            self.host_makefile_directory()
+        }
+    }
+
+    // Name `target` is made by from the Makefile of this generator.
+    fn make_target_name(&self, target: &TargetNode) -> String {
+        if target.borrow().build_for == self.build_for {
+            target.borrow().name()
+        } else {
+            self.host_makefile_directory()
+                .join(target.borrow().name())
+                .display()
+                .to_string()
+        }
+    }
+
+    // Extensions of the outputs of `target`, which are those of the host toolchain for targets
+    // built in the host build tree.
+    fn output_extensions_of(&self, target: &TargetNode) -> OutputExtensions {
+        let toolchain = self.toolchain.borrow();
+        match toolchain.host {
+            Some(ref host) if target.borrow().build_for != self.build_for => {
+                host.borrow().output_extensions.clone()
+            }
+            _ => toolchain.output_extensions.clone(),
+        }
+    }
+
+    fn generate_all_target_for_progress_document(
+        &mut self,
+        object_targets: &[ObjectTarget],
+        registry: &TargetRegistry,
+    ) {
+        let examples = registry
+            .registry
+            .iter()
+            .filter(|target| target.borrow().example && target.borrow().build_for == self.build_for)
+            .map(|target| target.borrow().name())
+            .collect::<Vec<String>>();
         let mut target_all = ProgressTrackingTarget {
             target: "all".to_string(),
             object_files: Vec::new(),
             dependencies: Vec::new(),
+            output: None,
+            packages: Vec::new(),
         };
 
         for object_target in object_targets {
             let target_name = object_target.target.clone();
-            if !target_all.dependencies.contains(&target_name) {
+            if !target_all.dependencies.contains(&target_name) && !examples.contains(&target_name) {
                 target_all.dependencies.push(target_name);
             }
         }
         self.progress_document.targets.push(target_all);
+        self.progress_document.targets.push(ProgressTrackingTarget {
+            target: EXAMPLES_TARGET_NAME.to_string(),
+            object_files: Vec::new(),
+            dependencies: examples,
+            output: None,
+            packages: Vec::new(),
+        });
     }
 
     fn generate_makefile(
@@ -328,127 +1209,864 @@ impl MakefileGenerator {
         writers: &mut Writers,
         registry: &TargetRegistry,
     ) -> Result<(), GeneratorError> {
-        self.generate_header(&mut writers.makefile_writer, &registry.registry)?;
-
+        // Targets built for the host are made through the host build tree, so only the host
+        // generator leaves out targets of the other context.
+        let all_targets = registry
+            .registry
+            .iter()
+            .filter(|target| {
+                self.build_for == types::BuildFor::Target
+                    || target.borrow().build_for == self.build_for
+            })
+            .cloned()
+            .collect::<Vec<TargetNode>>();
+        self.generate_header(&mut writers.makefile_writer, &all_targets)?;
+
         self.push_and_create_directory(std::path::Path::new("deps"))?;
+        let pending_targets = registry
+            .registry
+            .iter()
+            .filter(|target| {
+                target.borrow().build_for == self.build_for
+                    && target.borrow().state != TargetState::BuildFileMade
+            })
+            .cloned()
+            .collect::<Vec<TargetNode>>();
+        self.shared_objects = self.find_shared_objects(&pending_targets);
+        let conflicts =
+            generated_file_conflicts(&self.generated_files_of(&pending_targets), registry);
+        if !conflicts.is_empty() {
+            return Err(GeneratorError::GeneratedFileConflicts(conflicts));
+        }
+        let cache =
+            Cache::new(self.build_directory.as_path()).map_err(GeneratorError::Checkpoint)?;
+        let mut checkpoint = cache
+            .from_cache::<GenerationCheckpoint>()
+            .unwrap_or_default();
         for target in &registry.registry {
+            if target.borrow().build_for != self.build_for {
+                continue;
+            }
             if target.borrow().state != TargetState::BuildFileMade {
-                let borrowed_target = target.borrow();
-                let dep_dir = format!("{}.dir", &borrowed_target.name());
-                self.push_and_create_directory(std::path::Path::new(&dep_dir))?;
-                log::debug!(
-                    "Generating makefiles for target {:?} (manifest path: {})",
-                    target.borrow().name(),
-                    borrowed_target.manifest.directory.display()
+                let fingerprint = self.build_file_fingerprint(target);
+                let build_file = self
+                    .output_directory
+                    .join(target_directory_name(target))
+                    .join(TARGET_BUILD_FILE_NAME);
+                let target_writer = if checkpoint.is_written(&build_file, fingerprint) {
+                    self.resume_build_file_for_target(target)
+                } else {
+                    self.generate_build_file_for_target(target, registry)
+                };
+                match target_writer {
+                    Ok(mut target_writer) => {
+                        target_writer.fingerprint = fingerprint;
+                        writers.target_writers.push(target_writer)
+                    }
+                    Err(e) => {
+                        return self.write_target_build_files(
+                            &cache,
+                            &mut checkpoint,
+                            &writers.target_writers,
+                            Some(e),
+                        )
+                    }
+                }
+            }
+            target.borrow_mut().state = TargetState::BuildFileMade;
+        }
+        self.output_directory.pop();
+
+        writers.makefile_writer.data.push('\n');
+        for target_writer in &writers.target_writers {
+            writers
+                .makefile_writer
+                .data
+                .push_str(&format!("include {}\n", target_writer.path.display()));
+        }
+        let own_targets = registry
+            .registry
+            .iter()
+            .filter(|target| target.borrow().build_for == self.build_for)
+            .cloned()
+            .collect::<Vec<TargetNode>>();
+        if let Some(system_pch) = self.used_system_pch(&own_targets) {
+            if system_pch.needs_writing() {
+                let header = system_pch.header();
+                utility::create_dir(header.parent().unwrap())?;
+                utility::write_file_atomically(&header, system_pch.header_content().as_bytes())?;
+            }
+            writers
+                .makefile_writer
+                .data
+                .push_str(&generate_system_pch_rule(system_pch));
+        }
+        if self.build_for == types::BuildFor::Target {
+            self.generate_host_target_rules(&mut writers.makefile_writer, registry);
+            self.generate_signing_rules(&mut writers.makefile_writer, registry)?;
+            self.generate_install_rules(&mut writers.makefile_writer, registry)?;
+            self.generate_compile_fail_rules(&mut writers.makefile_writer, registry)?;
+        }
+        self.write_target_build_files(&cache, &mut checkpoint, &writers.target_writers, None)
+    }
+
+    // Writes the build files of the targets, leaving out those a failed generation already wrote.
+    // When writing them fails, or generating another target failed with `failure`, the build files
+    // written are recorded in the checkpoint, so that the next generation resumes with the others.
+    fn write_target_build_files(
+        &self,
+        cache: &Cache,
+        checkpoint: &mut GenerationCheckpoint,
+        writers: &[Writer],
+        failure: Option<GeneratorError>,
+    ) -> Result<(), GeneratorError> {
+        let pending_writers = writers
+            .iter()
+            .filter(|writer| !writer.resumed)
+            .collect::<Vec<&Writer>>();
+        if pending_writers.len() < writers.len() {
+            log::info!(
+                "Resuming generation: {} of {} build files were written by the previous generation",
+                writers.len() - pending_writers.len(),
+                writers.len()
+            );
+        }
+        let result = write_in_parallel(&pending_writers);
+        let result = match failure {
+            Some(e) => Err(e),
+            None => result,
+        };
+        match result {
+            Ok(()) => checkpoint.complete(
+                &self
+                    .build_directory
+                    .as_path()
+                    .join(self.project_config.build_type.to_string()),
+            ),
+            Err(_) => {
+                for writer in pending_writers {
+                    checkpoint.record(&writer.path, writer.fingerprint, writer.data.as_bytes());
+                }
+            }
+        }
+        if checkpoint.is_empty() {
+            cache.remove::<GenerationCheckpoint>()
+        } else {
+            cache.cache(checkpoint)
+        }
+        .map_err(GeneratorError::Checkpoint)?;
+        result
+    }
+
+    // Targets compiling the same source with the same flags and include directories would make
+    // identical objects, so only the first of them compiles it and the others link its object. The
+    // object is only shared when it is compiled again after changes to the same extra files.
+    fn find_shared_objects(
+        &self,
+        targets: &[TargetNode],
+    ) -> HashMap<std::path::PathBuf, std::path::PathBuf> {
+        let mut compiled_objects = HashMap::<_, std::path::PathBuf>::new();
+        let mut shared_objects = HashMap::new();
+        for target in targets {
+            let compile_flags = self.compile_flags(target);
+            let output_directory = self.output_directory.join(target_directory_name(target));
+            let object_extension = self.output_extensions_of(target).object;
+            for object_target in
+                ObjectTarget::create_object_targets(target, &output_directory, &object_extension)
+            {
+                let key = (
+                    object_target.source.clone(),
+                    target.borrow().compiler_flags.no_default_flags,
+                    compile_flags.clone(),
+                    generate_include_directories(&object_target.include_directories),
+                    object_target.extra_files.clone(),
                 );
+                match compiled_objects.entry(key) {
+                    Entry::Occupied(entry) => {
+                        if *entry.get() != object_target.object {
+                            shared_objects.insert(object_target.object, entry.get().clone());
+                        }
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(object_target.object);
+                    }
+                }
+            }
+        }
+        if !shared_objects.is_empty() {
+            log::debug!(
+                "{} object(s) are compiled once and shared by several targets",
+                shared_objects.len()
+            );
+        }
+        shared_objects
+    }
 
-                self.generate_rule_declaration_for_target(writers, target);
-                // Quick hack to allow each dependency / target to be placed in their own
-                // folder, without it being a subfolder of a separate target.
-                // FIXME: Need to figure out if there is a better way to solve this. It is
-                // rather clunky.
-                self.output_directory.pop();
-                self.generate_rule_for_dependencies_from_source_data(
-                    writers,
-                    &borrowed_target.name(),
-                    &borrowed_target,
-                    registry,
-                )?;
-                self.push_and_create_directory(std::path::Path::new(&dep_dir))?;
-
-                let progress_tracking_target =
-                    ProgressTrackingTarget::from_target(target, &self.output_directory);
-                self.progress_document
-                    .add_progress_tracking_target(progress_tracking_target);
-                ObjectTarget::create_object_targets(target, &self.output_directory)
-                    .into_iter()
-                    .for_each(|object_target| {
-                        if !writers
-                            .makefile_writer
-                            .object_targets
-                            .contains(&object_target)
-                        {
-                            writers.makefile_writer.object_targets.push(object_target);
+    // Artifacts are installed into $(DESTDIR)$(PREFIX), which `yambs install` sets on the command
+    // line of make. Directories are removed on uninstall only if they end up empty.
+    fn generate_install_rules(
+        &self,
+        writer: &mut Writer,
+        registry: &TargetRegistry,
+    ) -> Result<(), GeneratorError> {
+        if self.install.is_empty() {
+            return Ok(());
+        }
+        let prefixed = |path: &std::path::Path| {
+            format!(
+                "$(DESTDIR)$(PREFIX)/{}",
+                quote_recipe_argument(&path.display().to_string())
+            )
+        };
+        let mut prerequisites = Vec::new();
+        if self.signing.is_some() {
+            prerequisites.push(SIGN_TARGET_NAME.to_string());
+        }
+        let mut install_recipe = Vec::new();
+        let mut uninstall_recipe = Vec::new();
+        let mut removed_directories = Vec::new();
+        // Windows looks for DLLs next to the executables, so they are installed with the first
+        // executable installed, while their import libraries go to their own destination.
+        let runtime_destination = self
+            .install
+            .iter()
+            .find_map(|install| match install {
+                Install::Target {
+                    target,
+                    destination,
+                    ..
+                } if registry
+                    .get_target_from_predicate(|build_target| {
+                        build_target.name() == *target && build_target.is_executable()
+                    })
+                    .is_some() =>
+                {
+                    Some(destination.clone())
+                }
+                _ => None,
+            })
+            .unwrap_or_else(|| std::path::PathBuf::from("bin"));
+        for install in &self.install {
+            match install {
+                Install::Target {
+                    target,
+                    destination,
+                    mode,
+                } => {
+                    let target_node = registry
+                        .get_target_from_predicate(|build_target| {
+                            build_target.name() == *target
+                                && build_target.build_for == types::BuildFor::Target
+                        })
+                        .or_else(|| {
+                            registry.get_target_from_predicate(|build_target| {
+                                build_target.name() == *target
+                            })
+                        })
+                        .ok_or_else(|| GeneratorError::InstallTargetNotFound(target.clone()))?;
+                    let output_extensions = self.output_extensions_of(&target_node);
+                    let output_name = output_name(&target_node, &output_extensions);
+                    let default_mode = match target_node.borrow().library_type() {
+                        Some(LibraryType::Static) => 0o644,
+                        _ => 0o755,
+                    };
+                    let import_library = match target_node.borrow().target_type {
+                        TargetType::Library(ref library) => {
+                            output_extensions.import_library_file_name(library)
                         }
+                        TargetType::Executable(_) => None,
+                    };
+                    // A versioned library is installed as the file it is linked to, along with its
+                    // symlinks.
+                    let versioned_library = versioned_library(&target_node, &output_extensions);
+                    let symlinks = versioned_library
+                        .as_ref()
+                        .map(|versioned_library| versioned_library.symlinks(&output_name))
+                        .unwrap_or_default();
+                    let output_name = versioned_library
+                        .map_or(output_name, |versioned_library| versioned_library.file_name);
+                    let installed_files = match import_library {
+                        Some(import_library) => vec![
+                            (
+                                output_name,
+                                &runtime_destination,
+                                mode.unwrap_or(default_mode),
+                            ),
+                            (import_library, destination, 0o644),
+                        ],
+                        None => vec![(output_name, destination, mode.unwrap_or(default_mode))],
+                    };
+                    prerequisites.push(self.make_target_name(&target_node));
+                    for (file_name, destination, mode) in installed_files {
+                        install_recipe.push(format!("install -d {}", prefixed(destination)));
+                        install_recipe.push(format!(
+                            "install -m {:04o} {} {}",
+                            mode,
+                            quote_recipe_argument(
+                                &self
+                                    .makefile_directory_of(&target_node)
+                                    .join(&file_name)
+                                    .display()
+                                    .to_string()
+                            ),
+                            prefixed(&destination.join(&file_name))
+                        ));
+                        uninstall_recipe
+                            .push(format!("rm -f {}", prefixed(&destination.join(&file_name))));
+                    }
+                    for (symlink, points_to) in symlinks {
+                        install_recipe.push(format!(
+                            "ln -sfn {} {}",
+                            quote_recipe_argument(&points_to),
+                            prefixed(&destination.join(&symlink))
+                        ));
+                        uninstall_recipe
+                            .push(format!("rm -f {}", prefixed(&destination.join(&symlink))));
+                    }
+                }
+                Install::File {
+                    file,
+                    destination,
+                    mode,
+                } => {
+                    let file_name = std::path::PathBuf::from(file.file_name().unwrap_or_default());
+                    install_recipe.push(format!("install -d {}", prefixed(destination)));
+                    install_recipe.push(format!(
+                        "install -m {:04o} {} {}",
+                        mode.unwrap_or(0o644),
+                        quote_recipe_argument(&file.display().to_string()),
+                        prefixed(&destination.join(&file_name))
+                    ));
+                    uninstall_recipe
+                        .push(format!("rm -f {}", prefixed(&destination.join(&file_name))));
+                }
+                Install::Directory { directory, mode } => {
+                    install_recipe.push(match mode {
+                        Some(mode) => format!("install -d -m {:04o} {}", mode, prefixed(directory)),
+                        None => format!("install -d {}", prefixed(directory)),
                     });
-                self.output_directory.pop();
+                    removed_directories.push(format!("-rmdir {}", prefixed(directory)));
+                }
+                Install::Symlink { symlink, points_to } => {
+                    if let Some(parent) = symlink.parent() {
+                        install_recipe.push(format!("install -d {}", prefixed(parent)));
+                    }
+                    install_recipe.push(format!(
+                        "ln -sfn {} {}",
+                        quote_recipe_argument(&points_to.display().to_string()),
+                        prefixed(symlink)
+                    ));
+                    uninstall_recipe.push(format!("rm -f {}", prefixed(symlink)));
+                }
             }
-            target.borrow_mut().state = TargetState::BuildFileMade;
         }
-        self.output_directory.pop();
-        self.generate_object_rules(writers)?;
-        self.generate_depends_rules(&mut writers.makefile_writer);
+        uninstall_recipe.extend(removed_directories.into_iter().rev());
+
+        writer.data.push_str(&format!(
+            "\n# ----- INSTALL -----\n\
+             PREFIX ?= {default_prefix}\n\
+             \n\
+             install:{prerequisites}\n\
+             {install_recipe}\n\
+             \n\
+             uninstall:\n\
+             {uninstall_recipe}\n",
+            default_prefix = DEFAULT_INSTALL_PREFIX,
+            prerequisites = prerequisites
+                .iter()
+                .map(|prerequisite| format!(" {}", prerequisite))
+                .collect::<String>(),
+            install_recipe = install_recipe
+                .iter()
+                .map(|line| format!("\t{}", line))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            uninstall_recipe = uninstall_recipe
+                .iter()
+                .map(|line| format!("\t{}", line))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        ));
         Ok(())
     }
 
-    fn generate_rule_for_dependencies_from_source_data(
-        &mut self,
-        writers: &mut Writers,
-        target_name: &str,
-        build_target: &build_target::BuildTarget,
+    // Each artifact is signed by a rule of its own, which makes its signature from the artifact, so
+    // that it is signed again whenever it is linked again. A command that fails fails the build,
+    // and removes what it wrote of the signature so that the next build signs the artifact again.
+    // The artifacts signed are listed in the artifacts manifest of the build tree.
+    fn generate_signing_rules(
+        &self,
+        writer: &mut Writer,
         registry: &TargetRegistry,
     ) -> Result<(), GeneratorError> {
-        if !build_target.dependencies.is_empty() {
-            let dependencies = &build_target.dependencies;
-            for dependency in dependencies {
-                match dependency.source {
-                    build_target::DependencySource::FromSource(ref s) => {
-                        log::debug!("Generating build rule for dependency \"{}\" (manifest path = {}) to target \"{}\" (manifest path {})",
-                            s.library.name,
-                            s.manifest.directory.display(),
-                            target_name,
-                            build_target.manifest.directory.display());
-                        let dep_dir = format!("{}.dir", &s.library.name);
-                        self.push_and_create_directory(std::path::Path::new(&dep_dir))?;
-                        self.generate_rule_for_dependency(writers, dependency, registry);
-                        self.output_directory.pop();
-                    }
-                    _ => {}
+        let makefile_directory = self.makefile_directory();
+        let artifacts_manifest = makefile_directory.join(ARTIFACTS_MANIFEST_FILE_NAME);
+        let signing_plan = makefile_directory.join(SIGNING_PLAN_FILE_NAME);
+        let Some(ref signing) = self.signing else {
+            for file in [artifacts_manifest, signing_plan] {
+                if file.is_file() {
+                    std::fs::remove_file(&file).map_err(|e| FsError::RemoveFile(file, e))?;
                 }
             }
+            return Ok(());
+        };
+        let mut signed_artifacts = ArtifactsManifest::default();
+        let mut rules = String::new();
+        for target in &signing.targets {
+            let target_node = registry
+                .get_target_from_predicate(|build_target| {
+                    build_target.name() == *target
+                        && build_target.build_for == types::BuildFor::Target
+                })
+                .ok_or_else(|| GeneratorError::SigningTargetNotFound(target.clone()))?;
+            let output_extensions = self.output_extensions_of(&target_node);
+            let output_name = versioned_library(&target_node, &output_extensions).map_or_else(
+                || output_name(&target_node, &output_extensions),
+                |versioned_library| versioned_library.file_name,
+            );
+            let artifact =
+                SignedArtifact::new(target, &makefile_directory.join(&output_name), signing);
+            let rule_output = artifact.rule_output().display().to_string();
+            let command = artifact
+                .command
+                .iter()
+                .map(|word| quote_recipe_argument(word))
+                .collect::<Vec<String>>()
+                .join(" ");
+            // The artifacts manifest is made again once the artifact is signed again, so that it
+            // never lists a signature that is not made yet.
+            let recipe = match artifact.signature {
+                Some(_) => format!(
+                    "\trm -f {} $@\n\t{} || {{ rm -f $@; exit 1; }}\n",
+                    quote_recipe_argument(&artifacts_manifest.display().to_string()),
+                    command
+                ),
+                None => format!(
+                    "\trm -f {}\n\t{}\n\ttouch $@\n",
+                    quote_recipe_argument(&artifacts_manifest.display().to_string()),
+                    command
+                ),
+            };
+            rules.push_str(&format!(
+                "\n# Signing of target \"{}\"\n{} : {}\n{}",
+                target, rule_output, output_name, recipe
+            ));
+            signed_artifacts.artifacts.push(artifact);
         }
+        // The artifacts manifest lists the signatures once all of them are made.
+        writer.data.push_str(&format!(
+            "\n# ----- SIGNING -----\n\
+             .PHONY: {sign}\n\
+             {sign} : {artifacts_manifest}\n\
+             \n\
+             all : {sign}\n\
+             \n\
+             {artifacts_manifest} : {signing_plan}{prerequisites}\n\
+             \tcp {quoted_signing_plan} $@\n\
+             {rules}",
+            sign = SIGN_TARGET_NAME,
+            quoted_signing_plan = quote_recipe_argument(&signing_plan.display().to_string()),
+            artifacts_manifest = artifacts_manifest.display(),
+            signing_plan = signing_plan.display(),
+            prerequisites = signed_artifacts
+                .artifacts
+                .iter()
+                .map(|artifact| format!(" \\\n   {}", artifact.rule_output().display()))
+                .collect::<String>(),
+            rules = rules,
+        ));
+        utility::create_dir(&makefile_directory)?;
+        signed_artifacts.write_plan(&makefile_directory)?;
         Ok(())
     }
 
-    fn generate_rule_for_dependency(
-        &mut self,
-        writers: &mut Writers,
-        dependency: &Dependency,
-        registry: &TargetRegistry,
-    ) {
-        let dependency_target = dependency.to_build_target(registry).unwrap();
-        if dependency_target.borrow().state != TargetState::BuildFileMade {
-            self.generate_compiler_flags_for_target(
-                &dependency_target,
-                &mut writers.makefile_writer,
+    // Steps building the targets as their rules do, with the commands printed by --verbose twice.
+    // Objects compiled once for several targets are linked from where they are compiled.
+    fn build_plan_of(&self, registry: &TargetRegistry) -> Result<BuildPlan, GeneratorError> {
+        let unsupported = |feature: &'static str, target: &str| {
+            GeneratorError::UnsupportedByInternalBackend(feature, target.to_string())
+        };
+        if let Some(target) = self
+            .signing
+            .iter()
+            .flat_map(|signing| &signing.targets)
+            .next()
+        {
+            return Err(unsupported("Signing artifacts", target));
+        }
+        let toolchain = self.toolchain.borrow();
+        let include_file_generator = self.include_file_generator(&toolchain);
+        let makefile_directory = self.makefile_directory();
+        let mut build_plan = BuildPlan::default();
+        for target in &registry.registry {
+            if let Some(feature) = self.feature_needing_recipes(target) {
+                return Err(unsupported(feature, &target.borrow().name()));
+            }
+            let borrowed_target = target.borrow();
+            let commands = self.annotated_commands_of(target, &include_file_generator);
+            let output_extensions = self.output_extensions_of(target);
+            let object_targets = ObjectTarget::create_object_targets(
+                target,
+                &makefile_directory
+                    .join("deps")
+                    .join(target_directory_name(target)),
+                &output_extensions.object,
             );
-            writers.makefile_writer.data.push('\n');
-            let rule = LibraryTargetFactory::create_rule(
-                &dependency_target,
-                &self.output_directory,
-                &self.project_config.language,
+            let compile_flags = commands.compile.flags.join();
+            let compiles = object_targets
+                .iter()
+                .filter(|object_target| !self.shared_objects.contains_key(&object_target.object))
+                .map(|object_target| PlannedCompile {
+                    source: object_target.source.clone(),
+                    object: object_target.object.clone(),
+                    extra_files: object_target.extra_files.clone(),
+                    command: format!(
+                        "{} {} {} -c -o {}",
+                        quote_shell_argument(&commands.compile.program.display().to_string()),
+                        compile_flags,
+                        quote_shell_argument(&object_target.source.display().to_string()),
+                        quote_shell_argument(&object_target.object.display().to_string()),
+                    ),
+                })
+                .collect::<Vec<PlannedCompile>>();
+            let objects = object_targets
+                .iter()
+                .map(|object_target| {
+                    self.shared_objects
+                        .get(&object_target.object)
+                        .unwrap_or(&object_target.object)
+                        .clone()
+                })
+                .collect::<Vec<std::path::PathBuf>>();
+            let dependencies = borrowed_target
+                .dependencies
+                .iter()
+                .filter_map(|dependency| dependency.source.from_source())
+                .collect::<Vec<_>>();
+            let output = makefile_directory.join(output_name(target, &output_extensions));
+            let quoted_objects = objects
+                .iter()
+                .map(|object| quote_shell_argument(&object.display().to_string()))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let command = match commands.link {
+                Some(ref link) => format!(
+                    "{} {} {} -o {}",
+                    quote_shell_argument(&link.program.display().to_string()),
+                    quoted_objects,
+                    link.flags.join(),
+                    quote_shell_argument(&output.display().to_string()),
+                ),
+                None => format!(
+                    "{} rs {} {}",
+                    quote_shell_argument(&toolchain.archiver.path.display().to_string()),
+                    quote_shell_argument(&output.display().to_string()),
+                    quoted_objects,
+                ),
+            };
+            build_plan.targets.push(PlannedTarget {
+                target: borrowed_target.name(),
+                example: borrowed_target.example,
+                dependencies: dependencies
+                    .iter()
+                    .map(|dependency| dependency.library.name.clone())
+                    .collect(),
+                compiles,
+                link: PlannedLink {
+                    inputs: objects
+                        .into_iter()
+                        .chain(dependencies.iter().map(|dependency| {
+                            makefile_directory
+                                .join(output_extensions.link_file_name(&dependency.library))
+                        }))
+                        .chain(borrowed_target.version_script.iter().cloned())
+                        .chain(borrowed_target.extra_files.iter().cloned())
+                        .collect(),
+                    output,
+                    command,
+                    serialized: self.lto_link_lock.is_some() && self.links_with_lto(&commands),
+                },
+            });
+        }
+        Ok(build_plan)
+    }
+
+    // The source of a compile-fail test is compiled like the sources of its target, after the
+    // target is built so that any headers it generates exist. The rule succeeds only if the
+    // compiler fails, and no object is made.
+    fn generate_compile_fail_rules(
+        &self,
+        writer: &mut Writer,
+        registry: &TargetRegistry,
+    ) -> Result<(), GeneratorError> {
+        if self.compile_fail_tests.is_empty() {
+            return Ok(());
+        }
+        writer.data.push_str("\n# ----- COMPILE-FAIL TESTS -----\n");
+        for test in &self.compile_fail_tests {
+            let (source, target) = match test.kind {
+                TestKind::CompileFail {
+                    ref source,
+                    ref target,
+                    ..
+                } => (source, target),
+                TestKind::Run(_) => continue,
+            };
+            let target_node = registry
+                .get_target_from_predicate(|build_target| {
+                    build_target.name() == *target
+                        && build_target.build_for == types::BuildFor::Target
+                })
+                .ok_or_else(|| {
+                    GeneratorError::CompileFailTargetNotFound(test.name.clone(), target.clone())
+                })?;
+            let include_directories = ObjectTarget::include_directories(
+                &target_node,
+                &self
+                    .output_directory
+                    .join("deps")
+                    .join(target_directory_name(&target_node)),
             );
-            ObjectTarget::create_object_targets(&dependency_target, &self.output_directory)
+            let (compiler, flags) = match self.project_config.language {
+                Language::CXX => ("CXX", "CXXFLAGS"),
+                Language::C => ("CC", "CFLAGS"),
+            };
+            writer.data.push_str(&format!(
+                "\n# Compile-fail test \"{name}\"\n\
+                 .PHONY: {rule}\n\
+                 {rule}: {prerequisite}\n\
+                 \t! $(strip $({compiler}) {default_flags} $(CPPFLAGS) $({target}_{flags}) $({target}_CPPFLAGS) \
+                 $({target}_WARNINGS) {include_directories} {source} -fsyntax-only)\n",
+                name = test.name,
+                rule = test.compile_fail_rule(),
+                prerequisite = self.make_target_name(&target_node),
+                compiler = compiler,
+                default_flags = default_flags_variable(&target_node, &self.project_config.language),
+                flags = flags,
+                target = target_node.borrow().name().to_uppercase(),
+                include_directories = generate_include_directories(&include_directories),
+                source = quote_recipe_argument(&source.display().to_string()),
+            ));
+        }
+        Ok(())
+    }
+
+    // Targets built for the host are made by running make in the host build tree. Object files of
+    // host executables are made the same way, so that rules of custom commands running the
+    // executables can depend on them.
+    fn generate_host_target_rules(&self, writer: &mut Writer, registry: &TargetRegistry) {
+        let host_makefile_directory = self.host_makefile_directory();
+        for target in &registry.registry {
+            if target.borrow().build_for != types::BuildFor::Host {
+                continue;
+            }
+            let target_name = target.borrow().name();
+            let make_target_name = self.make_target_name(target);
+            writer.data.push_str(&format!(
+                "\n# Host target \"{target_name}\"\n\
+                 .PHONY: {make_target_name}\n\
+                 {make_target_name}:\n\
+                 \t$(MAKE) -C {host_makefile_directory} {target_name}\n",
+                host_makefile_directory = host_makefile_directory.display(),
+            ));
+            if target.borrow().is_executable() {
+                let objects = ObjectTarget::create_object_targets(
+                    target,
+                    &host_makefile_directory
+                        .join("deps")
+                        .join(format!("{}.dir", target_name)),
+                    &self.output_extensions_of(target).object,
+                )
+                .into_iter()
+                .map(|object_target| object_target.object)
+                .collect::<Vec<std::path::PathBuf>>();
+                if !objects.is_empty() {
+                    writer.data.push_str(&format!(
+                        "{}: {} ;\n",
+                        join_paths(&objects),
+                        make_target_name
+                    ));
+                }
+            }
+        }
+    }
+
+    fn generate_host_build_files(&self, registry: &TargetRegistry) -> Result<(), GeneratorError> {
+        let host_toolchain = match self.toolchain.borrow().host {
+            Some(ref host_toolchain) => host_toolchain.clone(),
+            None => return Ok(()),
+        };
+        if !registry
+            .registry
+            .iter()
+            .any(|target| target.borrow().build_for == types::BuildFor::Host)
+        {
+            return Ok(());
+        }
+        let mut project_config = self.project_config.clone();
+        project_config.build_directory = BuildDirectory::from(
+            self.build_directory
+                .as_path()
+                .join(HOST_BUILD_DIRECTORY_NAME),
+        );
+        log::debug!(
+            "Generating build files for host targets in {}",
+            project_config.build_directory.as_path().display()
+        );
+        let mut host_generator = MakefileGenerator::new(&project_config, host_toolchain)?;
+        host_generator.build_for = types::BuildFor::Host;
+        host_generator.lto_link_lock = self.lto_link_lock.clone();
+        host_generator.make_features = self.make_features;
+        host_generator.generate(registry)?;
+        Ok(())
+    }
+
+    // Generates the build file of a single target. Each target is placed in its own directory,
+    // deps/<target>.dir, and its build file is included by the top-level Makefile.
+    fn generate_build_file_for_target(
+        &mut self,
+        target: &TargetNode,
+        registry: &TargetRegistry,
+    ) -> Result<Writer, GeneratorError> {
+        let needs_objcopy = target
+            .borrow()
+            .post_link
+            .iter()
+            .any(|artifact| artifact.objcopy_format().is_some());
+        if needs_objcopy && self.toolchain.borrow().objcopy.is_none() {
+            return Err(GeneratorError::ObjcopyNotFound(target.borrow().name()));
+        }
+        let dep_dir = target_directory_name(target);
+        self.push_and_create_directory(std::path::Path::new(&dep_dir))?;
+        log::debug!(
+            "Generating makefiles for target {:?} (manifest path: {})",
+            target.borrow().name(),
+            target.borrow().manifest.directory.display()
+        );
+
+        let mut writer = Writer::new(&self.output_directory.join(TARGET_BUILD_FILE_NAME));
+        self.generate_rule_declaration_for_target(&mut writer, target);
+        self.track_progress_of_target(&mut writer, target);
+        let mut generated_files =
+            self.generate_custom_command_rules(&mut writer, target, registry)?;
+        generated_files.extend(self.generate_embed_rule(&mut writer, target));
+        // Headers generated by the libraries depended on may be included through their headers,
+        // so the objects wait for them as well, even as the libraries are built in parallel.
+        let build_files_directory = self.output_directory.parent().unwrap().to_path_buf();
+        generated_files.extend(
+            target
+                .borrow()
+                .dependency_generated_headers()
                 .iter()
-                .for_each(|object_target| {
-                    if !writers
-                        .makefile_writer
-                        .object_targets
-                        .contains(object_target)
-                    {
-                        writers
-                            .makefile_writer
-                            .object_targets
-                            .push(object_target.clone());
-                    }
+                .map(|header| build_files_directory.join(header)),
+        );
+        if let (Some(system_pch), Some(_)) =
+            (self.system_pch.as_ref(), self.system_pch_origin(target))
+        {
+            generated_files.push(system_pch.precompiled_header());
+        }
+        self.generate_object_rules(&mut writer, target, &generated_files);
+        self.generate_depends_rules(&mut writer);
+        self.output_directory.pop();
+        Ok(writer)
+    }
+
+    // Writer of the build file of `target` written by a generation that failed partway. The build
+    // file is left as it is, and only what the top-level Makefile needs of it is tracked again.
+    fn resume_build_file_for_target(
+        &mut self,
+        target: &TargetNode,
+    ) -> Result<Writer, GeneratorError> {
+        log::debug!(
+            "Resuming with the build file of target {:?} written by the previous generation",
+            target.borrow().name()
+        );
+        self.push_and_create_directory(std::path::Path::new(&target_directory_name(target)))?;
+        let mut writer = Writer::new(&self.output_directory.join(TARGET_BUILD_FILE_NAME));
+        writer.resumed = true;
+        self.track_progress_of_target(&mut writer, target);
+        self.output_directory.pop();
+        Ok(writer)
+    }
+
+    // Fingerprint of what the build file of `target` is generated from, besides the options of
+    // the build tree, which remove the build tree when they change.
+    fn build_file_fingerprint(&self, target: &TargetNode) -> u64 {
+        let target_directory = self.output_directory.join(target_directory_name(target));
+        let mut shared_objects = self
+            .shared_objects
+            .iter()
+            .filter(|(object, _)| object.starts_with(&target_directory))
+            .collect::<Vec<(&std::path::PathBuf, &std::path::PathBuf)>>();
+        shared_objects.sort();
+        let mut content = serde_json::to_vec(&*target.borrow()).unwrap_or_default();
+        content.extend(format!("{:?} {:?}", shared_objects, self.make_features).bytes());
+        hash_content(&content)
+    }
+
+    // Adds `target` to the progress document, and the objects it compiles to `writer`.
+    fn track_progress_of_target(&mut self, writer: &mut Writer, target: &TargetNode) {
+        let output_extensions = self.output_extensions_of(target);
+        let mut progress_tracking_target = ProgressTrackingTarget::from_target(
+            target,
+            &self.output_directory,
+            &output_extensions.object,
+        );
+        progress_tracking_target.output = Some(
+            self.makefile_directory()
+                .join(output_name(target, &output_extensions)),
+        );
+        for object in &mut progress_tracking_target.object_files {
+            if let Some(shared_object) = self.shared_objects.get(object) {
+                *object = shared_object.clone();
+            }
+        }
+        self.progress_document
+            .add_progress_tracking_target(progress_tracking_target);
+        for object_target in ObjectTarget::create_object_targets(
+            target,
+            &self.output_directory,
+            &output_extensions.object,
+        ) {
+            if self.shared_objects.contains_key(&object_target.object) {
+                continue;
+            }
+            if !writer.object_targets.contains(&object_target) {
+                writer.object_targets.push(object_target);
+            }
+        }
+    }
+
+    // Outputs of the custom commands and embedded files of `targets`, in the order they are
+    // declared, as placed by `generate_custom_command_rules` and `generate_embed_rule`.
+    fn generated_files_of(&self, targets: &[TargetNode]) -> Vec<GeneratedFile> {
+        let mut generated_files = Vec::new();
+        for target in targets {
+            let borrowed_target = target.borrow();
+            let output_directory = self.output_directory.join(target_directory_name(target));
+            let generated_directory = output_directory.join(GENERATED_DIRECTORY_NAME);
+            let embedded_files = borrowed_target
+                .embedded_files(&output_directory)
+                .map(|(header, source)| vec![header, source])
+                .unwrap_or_default();
+            for path in borrowed_target
+                .custom_commands
+                .iter()
+                .flat_map(|custom_command| &custom_command.outputs)
+                .map(|output| generated_directory.join(output))
+                .chain(embedded_files)
+            {
+                generated_files.push(GeneratedFile {
+                    path,
+                    target: borrowed_target.name(),
+                    manifest: borrowed_target
+                        .manifest
+                        .directory
+                        .join(crate::YAMBS_MANIFEST_NAME),
                 });
-            writers.makefile_writer.data.push_str(&rule);
-            let progress_tracking_target =
-                ProgressTrackingTarget::from_target(&dependency_target, &self.output_directory);
-            self.progress_document
-                .add_progress_tracking_target(progress_tracking_target);
-            dependency_target.borrow_mut().state = TargetState::BuildFileMade;
+            }
         }
+        generated_files
     }
 
     fn build_configurations_file(&self) -> &str {
@@ -474,29 +2092,37 @@ impl MakefileGenerator {
         .map_err(|err| FsError::CreateDirectory(self.output_directory.clone(), err))?)
     }
 
-    fn create_subdir(&self, dir: &std::path::Path) -> Result<(), GeneratorError> {
-        utility::create_dir(self.output_directory.join(dir)).map_err(GeneratorError::Fs)
-    }
-
     fn generate_default_all_target(&self, writer: &mut Writer, targets: &[TargetNode]) {
-        let targets_as_string = {
+        let targets_as_string = |example: bool| {
             let mut targets_as_string = String::new();
-            for target in targets {
+            for target in targets
+                .iter()
+                .filter(|target| target.borrow().example == example)
+            {
                 targets_as_string.push_str("\\\n");
-                targets_as_string.push_str(&format!("   {}", target.borrow().name()))
+                targets_as_string.push_str(&format!("   {}", self.make_target_name(target)))
             }
             targets_as_string
         };
         let text = indoc::formatdoc!(
             "\
-            # Default all target to build all targets.
-            all : {}\n",
-            targets_as_string
+            # Default all target to build all targets, except for the examples.
+            all : {}\n\n\
+            # Target to build the examples.
+            {} : {}\n",
+            targets_as_string(false),
+            EXAMPLES_TARGET_NAME,
+            targets_as_string(true)
         );
         writer.data.push_str(&text);
     }
 
+    // The name of an executable without an extension is the file it links, which is not phony,
+    // or it would be linked again by every build.
     fn generate_phony(&self, writer: &mut Writer, target: &TargetNode) {
+        if output_name(target, &self.output_extensions_of(target)) == target.borrow().name() {
+            return;
+        }
         let data = indoc::formatdoc!(
             "\n
             # Phony for target \"{target_name}\"
@@ -512,6 +2138,8 @@ impl MakefileGenerator {
         writer: &mut Writer,
         targets: &[TargetNode],
     ) -> Result<(), GeneratorError> {
+        let yambs_executable =
+            std::env::current_exe().map_err(GeneratorError::YambsExecutableNotFound)?;
         let data = format!(
             "\
   # ----- INCLUDES -----\n\
@@ -519,16 +2147,22 @@ impl MakefileGenerator {
   include {build_directory}/make_include/default_make.mk\n\
   include {build_directory}/make_include/{build_configuration_file}\n\
   \n\
+  # Used to run custom commands\n\
+  YAMBS := {yambs_executable}\n\
+  \n\
   # ----- DEFAULT PHONIES -----\n\
   \n\
   .SUFFIXES:         # We do not use suffixes on makefiles.\n\
   .PHONY: all\n\
+  .PHONY: {examples}\n\
   .PHONY: package\n\
   .PHONY: install\n\
   .PHONY: uninstall\n\
   .PHONY: clean\n",
             build_configuration_file = self.build_configurations_file(),
-            build_directory = self.build_directory.as_path().display()
+            build_directory = self.build_directory.as_path().display(),
+            yambs_executable = yambs_executable.display(),
+            examples = EXAMPLES_TARGET_NAME,
         );
 
         writer.data.push_str(&data);
@@ -536,37 +2170,323 @@ impl MakefileGenerator {
         Ok(())
     }
 
+    fn include_file_generator<'toolchain>(
+        &self,
+        toolchain: &'toolchain NormalizedToolchain,
+    ) -> IncludeFileGenerator<'toolchain> {
+        let include_output_directory = self.output_directory.join(INCLUDE_DIRECTORY_NAME);
+        let mut include_file_generator =
+            IncludeFileGenerator::new(&include_output_directory, toolchain);
+        include_file_generator.set_std(
+            &self.project_config.std.to_string(),
+            self.project_config.std_origin.clone(),
+        );
+        if let Some(opt_level) = self.project_config.opt_level {
+            include_file_generator.set_opt_level(&self.project_config.build_type, opt_level);
+        }
+        include_file_generator
+    }
+
+    // Whether `commands` use link-time optimization, or make turns it on for all targets by flags
+    // of its environment.
+    fn links_with_lto(&self, commands: &TargetCommands) -> bool {
+        commands.uses_lto() || memory_budget::environment_uses_lto(&Environment::new())
+    }
+
     fn generate_include_files(&self) -> Result<(), GeneratorError> {
-        let include_output_directory = self.output_directory.join("make_include");
         let toolchain = self.toolchain.borrow();
-        let mut include_file_generator =
-            IncludeFileGenerator::new(&include_output_directory, &toolchain);
+        self.include_file_generator(&toolchain)
+            .generate_build_files()
+    }
+
+    // Compile and link command of `target`, following the recipes of the Makefile.
+    fn annotated_commands_of(
+        &self,
+        target: &TargetNode,
+        include_file_generator: &IncludeFileGenerator,
+    ) -> TargetCommands {
+        let toolchain = self.toolchain.borrow();
+        let language = &self.project_config.language;
+        let build_type = &self.project_config.build_type;
+        let compiler = match language {
+            Language::CXX => toolchain.cxx.compiler.compiler_exe.clone(),
+            Language::C => toolchain.cc.compiler.compiler_exe.clone(),
+        };
+        let target_flags = self.annotated_compile_flags(target);
+        let warning_flags = self.annotated_warning_flags(target, include_file_generator);
 
-        let standard = &self.project_config.std;
-        let standard_str = standard.to_string();
-        include_file_generator.add_cpp_version(&standard_str);
-        include_file_generator.generate_build_files()
+        let default_flags = if target.borrow().compiler_flags.no_default_flags {
+            AnnotatedFlags::new()
+        } else {
+            include_file_generator.language_flags(build_type, language)
+        };
+        let mut compile_flags = default_flags.clone();
+        compile_flags.append(include_file_generator::default_cpp_flags());
+        compile_flags.append(target_flags.language_flags.clone());
+        compile_flags.append(target_flags.cpp_flags.clone());
+        compile_flags.append(warning_flags.clone());
+        compile_flags.append(annotated_include_directories(target));
+
+        let link_settings = LinkSettings {
+            flavor: LinkerFlavor::from_toolchain(&toolchain),
+            output_extensions: self.output_extensions_of(target),
+            link_lock: None,
+            make_features: self.make_features,
+        };
+        let borrowed_target = target.borrow();
+        let link = match borrowed_target.library_type() {
+            Some(LibraryType::Static) => None,
+            library_type => {
+                // C targets are linked without CFLAGS.
+                let mut link_flags = match language {
+                    Language::CXX => default_flags,
+                    Language::C => AnnotatedFlags::new(),
+                };
+                link_flags.append(include_file_generator::default_cpp_flags());
+                link_flags.append(target_flags.language_flags);
+                link_flags.append(target_flags.cpp_flags);
+                link_flags.append(warning_flags);
+                link_flags.append(include_file_generator.link_flags(language));
+                if library_type == Some(LibraryType::Dynamic) {
+                    link_flags.extend(
+                        dynamic_library_link_flags(
+                            &printable_library(target),
+                            &link_settings.output_extensions,
+                        ),
+                        &FlagOrigin::Default,
+                    );
+                }
+                link_flags.append(annotated_link_inputs(target, &link_settings));
+                link_flags.append(self.annotated_link_flags(target));
+                Some(AnnotatedCommand {
+                    program: compiler.clone(),
+                    flags: link_flags,
+                })
+            }
+        };
+        TargetCommands {
+            target: borrowed_target.name(),
+            compile: AnnotatedCommand {
+                program: compiler,
+                flags: compile_flags,
+            },
+            link,
+        }
     }
 
-    fn generate_object_rules(&self, writers: &mut Writers) -> Result<(), GeneratorError> {
-        for object_target in &writers.makefile_writer.object_targets {
-            let object_dir = object_target
-                .object
-                .parent()
-                .and_then(|p| p.strip_prefix(&self.output_directory).ok());
+    fn generate_object_rules(
+        &self,
+        writer: &mut Writer,
+        target: &TargetNode,
+        generated_files: &[std::path::PathBuf],
+    ) {
+        let language = &self.project_config.language;
+        for object_target in &writer.object_targets {
+            writer.data.push_str(&generate_object_target(
+                object_target,
+                generated_files,
+                language,
+                default_flags_variable(target, language),
+            ))
+        }
+    }
 
-            if let Some(object_dir) = object_dir {
-                self.create_subdir(object_dir)?;
+    // Each custom command is run through `yambs sandbox`. Make only supports a single target per
+    // rule portably, so additional outputs depend on the first one.
+    //
+    // A command running an executable of the build tree named without an extension depends on
+    // the executable, which is the file it links, so that the command is run again once the
+    // executable is linked again. Executables made through phony targets, as those with an
+    // extension and those built for the host are, would run the command on every build. Commands
+    // depend on their object files instead, and only on the phony target for order.
+    fn generate_custom_command_rules(
+        &self,
+        writer: &mut Writer,
+        target: &TargetNode,
+        registry: &TargetRegistry,
+    ) -> Result<Vec<std::path::PathBuf>, GeneratorError> {
+        let borrowed_target = target.borrow();
+        let generated_directory = self.output_directory.join(GENERATED_DIRECTORY_NAME);
+        let mut generated_files = Vec::new();
+
+        for (i, custom_command) in borrowed_target.custom_commands.iter().enumerate() {
+            let outputs = custom_command
+                .outputs
+                .iter()
+                .map(|output| SandboxMapping {
+                    sandbox_path: output.clone(),
+                    path: generated_directory.join(output),
+                })
+                .collect::<Vec<SandboxMapping>>();
+            let inputs = custom_command
+                .inputs
+                .iter()
+                .map(|input| SandboxMapping {
+                    sandbox_path: input
+                        .strip_prefix(&borrowed_target.manifest.directory)
+                        .map(std::path::Path::to_path_buf)
+                        .unwrap_or_else(|_| input.file_name().unwrap_or_default().into()),
+                    path: input.clone(),
+                })
+                .collect::<Vec<SandboxMapping>>();
+            let sandbox_directory = self.output_directory.join("sandbox").join(i.to_string());
+
+            let (command, tool_prerequisites) = match custom_command.command {
+                Command::Program(ref command) => (command.clone(), String::new()),
+                Command::Target {
+                    target: ref tool,
+                    ref args,
+                } => {
+                    let tool_build_for = match self.build_for {
+                        types::BuildFor::Host => types::BuildFor::Host,
+                        types::BuildFor::Target => self
+                            .toolchain
+                            .borrow()
+                            .resolve_build_for(types::BuildFor::Host),
+                    };
+                    let tool_node = registry
+                        .get_target_from_predicate(|build_target| {
+                            build_target.manifest.directory == borrowed_target.manifest.directory
+                                && build_target.is_executable()
+                                && build_target.name() == *tool
+                                && build_target.build_for == tool_build_for
+                        })
+                        .ok_or_else(|| GeneratorError::ToolNotFound(tool.clone()))?;
+                    let tool_makefile_directory = self.makefile_directory_of(&tool_node);
+                    let tool_output_extensions = self.output_extensions_of(&tool_node);
+                    let mut command = vec![tool_makefile_directory
+                        .join(tool_output_extensions.executable_file_name(tool))
+                        .display()
+                        .to_string()];
+                    command.extend(args.iter().cloned());
+                    let tool_prerequisites = if tool_node.borrow().build_for == self.build_for
+                        && output_name(&tool_node, &tool_output_extensions) == *tool
+                    {
+                        self.make_target_name(&tool_node)
+                    } else {
+                        let tool_objects = ObjectTarget::create_object_targets(
+                            &tool_node,
+                            &tool_makefile_directory
+                                .join("deps")
+                                .join(format!("{}.dir", tool)),
+                            &tool_output_extensions.object,
+                        )
+                        .into_iter()
+                        .map(|object_target| object_target.object)
+                        .collect::<Vec<std::path::PathBuf>>();
+                        format!(
+                            "{} | {}",
+                            join_paths(&tool_objects),
+                            self.make_target_name(&tool_node)
+                        )
+                    };
+                    (command, tool_prerequisites)
+                }
+            };
+
+            let mut arguments = vec![
+                "--directory".to_string(),
+                sandbox_directory.display().to_string(),
+            ];
+            for input in &inputs {
+                arguments.push("--input".to_string());
+                arguments.push(input.to_string());
             }
-            writers
-                .makefile_writer
-                .data
-                .push_str(&generate_object_target(
-                    object_target,
-                    &self.project_config.language,
-                ))
+            for output in &outputs {
+                arguments.push("--output".to_string());
+                arguments.push(output.to_string());
+            }
+            arguments.push("--".to_string());
+            arguments.extend(command);
+            let arguments = arguments
+                .iter()
+                .map(|a| quote_recipe_argument(a))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            let output_paths = outputs
+                .into_iter()
+                .map(|o| o.path)
+                .collect::<Vec<std::path::PathBuf>>();
+            let (first_output, other_outputs) = match output_paths.split_first() {
+                Some(split) => split,
+                None => continue,
+            };
+            // From make 4.3, the outputs are grouped targets, made together by a single run of the
+            // command. Older makes make the other outputs along with the first one.
+            let grouped = self.make_features.grouped_targets() && !other_outputs.is_empty();
+            writer.data.push_str(&format!(
+                "# Custom command \"{command}\" for target \"{target_name}\"\n\
+                 {outputs} {separator} {prerequisites}\n\
+                 \t$(YAMBS) sandbox {arguments}\n",
+                command = custom_command.command,
+                target_name = borrowed_target.name(),
+                outputs = if grouped {
+                    join_paths(&output_paths)
+                } else {
+                    first_output.display().to_string()
+                },
+                separator = if grouped { "&:" } else { ":" },
+                prerequisites = [join_paths(&custom_command.inputs), tool_prerequisites]
+                    .iter()
+                    .filter(|prerequisites| !prerequisites.is_empty())
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            ));
+            if !grouped && !other_outputs.is_empty() {
+                writer.data.push_str(&format!(
+                    "{}: {} ;\n",
+                    join_paths(other_outputs),
+                    first_output.display()
+                ));
+            }
+            writer.data.push('\n');
+            generated_files.extend(output_paths);
         }
-        Ok(())
+        Ok(generated_files)
+    }
+
+    // The source generated from the embedded files of a target is made by yambs, along with the
+    // header declaring the arrays. Returns the generated files.
+    fn generate_embed_rule(
+        &self,
+        writer: &mut Writer,
+        target: &TargetNode,
+    ) -> Vec<std::path::PathBuf> {
+        let borrowed_target = target.borrow();
+        let (header, source) = match borrowed_target.embedded_files(&self.output_directory) {
+            Some(files) => files,
+            None => return Vec::new(),
+        };
+        let mut arguments = vec![
+            "--target".to_string(),
+            borrowed_target.name(),
+            "--header".to_string(),
+            header.display().to_string(),
+            "--source".to_string(),
+            source.display().to_string(),
+        ];
+        for file in borrowed_target.embedded_file_names() {
+            arguments.push("--file".to_string());
+            arguments.push(file.to_string());
+        }
+        writer.data.push_str(&format!(
+            "# Embedded files of target \"{target_name}\"\n\
+             {source}: {prerequisites}\n\
+             \t$(YAMBS) embed {arguments}\n\
+             {header}: {source} ;\n\n",
+            target_name = borrowed_target.name(),
+            source = source.display(),
+            header = header.display(),
+            prerequisites = join_paths(&borrowed_target.embed),
+            arguments = arguments
+                .iter()
+                .map(|a| quote_recipe_argument(a))
+                .collect::<Vec<String>>()
+                .join(" "),
+        ));
+        vec![header, source]
     }
 
     fn generate_depends_rules(&self, writer: &mut Writer) {
@@ -591,141 +2511,460 @@ impl MakefileGenerator {
         }
     }
 
-    fn generate_rule_declaration_for_target(&self, writers: &mut Writers, target: &TargetNode) {
-        self.generate_phony(&mut writers.makefile_writer, target);
-        self.generate_compiler_flags_for_target(target, &mut writers.makefile_writer);
+    fn generate_rule_declaration_for_target(&self, writer: &mut Writer, target: &TargetNode) {
+        self.generate_phony(writer, target);
+        self.generate_compiler_flags_for_target(target, writer);
         let target_rule_declaration = TargetRuleFactory::create_rule(
             target,
             &self.output_directory,
             &self.project_config.language,
+            self.compile_timings.as_ref(),
+            &self.shared_objects,
+            &LinkSettings {
+                flavor: LinkerFlavor::from_toolchain(&self.toolchain.borrow()),
+                output_extensions: self.output_extensions_of(target),
+                link_lock: self.lto_link_lock.clone().filter(|_| {
+                    let toolchain = self.toolchain.borrow();
+                    let commands = self
+                        .annotated_commands_of(target, &self.include_file_generator(&toolchain));
+                    self.links_with_lto(&commands)
+                }),
+                make_features: self.make_features,
+            },
         );
-        writers.makefile_writer.data.push('\n');
-        writers.makefile_writer.data.push_str(&format!(
+        writer.data.push('\n');
+        writer.data.push_str(&format!(
             "# Rule for target \"{}\"\n",
             target.borrow().name()
         ));
-        writers
-            .makefile_writer
-            .data
-            .push_str(&target_rule_declaration);
-        writers.makefile_writer.data.push('\n');
-        writers.makefile_writer.data.push('\n');
+        writer.data.push_str(&target_rule_declaration);
+        writer.data.push('\n');
+        writer.data.push('\n');
     }
 
-    fn generate_compiler_flags_for_target(
-        &self,
-        target: &TargetNode,
-        makefile_writer: &mut Writer,
-    ) {
+    fn compile_flags(&self, target: &TargetNode) -> CompileFlags {
+        let annotated = self.annotated_compile_flags(target);
         let borrowed_target = target.borrow();
-        let target_name = borrowed_target.name();
-        let target_name_capitalized = target_name.to_uppercase();
-        let cxx_flags = &borrowed_target.compiler_flags.cxx_flags;
-        let c_flags = &borrowed_target.compiler_flags.c_flags;
-
-        match self.project_config.language {
-            Language::CXX => {
-                makefile_writer.data.push_str(&indoc::formatdoc!(
-                    "# CXXFLAGS for target \"{target_name}\"
-                    {target_name_capitalized}_CXXFLAGS +="
-                ));
-                if let Some(cxx) = cxx_flags {
-                    makefile_writer.data.push_str(&indoc::formatdoc!(
-                        "{cxx_flags}",
-                        cxx_flags = cxx.flags().join(" ")
-                    ));
-                }
-            }
-            Language::C => {
-                makefile_writer.data.push_str(&indoc::formatdoc!(
-                    "# CFLAGS for target \"{target_name}\"
-                    {target_name_capitalized}_CFLAGS +="
-                ));
-                if let Some(c) = c_flags {
-                    makefile_writer.data.push_str(&indoc::formatdoc!(
-                        "{c_flags}",
-                        c_flags = c.flags().join(" ")
-                    ));
+        let warnings = match borrowed_target.compiler_flags.warnings {
+            Some(ref warnings) => warning_flags(warnings, &self.project_config.language),
+            None if borrowed_target.compiler_flags.no_default_flags => String::new(),
+            None => warning_flags(&self.project_config.warnings, &self.project_config.language),
+        };
+        let has_generated_sources =
+            !borrowed_target.custom_commands.is_empty() || !borrowed_target.embed.is_empty();
+        let generated_warnings = has_generated_sources.then(|| {
+            match borrowed_target
+                .compiler_flags
+                .generated_warnings
+                .unwrap_or(self.project_config.generated_warnings)
+            {
+                GeneratedWarnings::Off => "-w".to_string(),
+                GeneratedWarnings::On => {
+                    format!("$({}_WARNINGS)", borrowed_target.name().to_uppercase())
                 }
             }
+        });
+        CompileFlags {
+            language_flags: escape_variable_value(&annotated.language_flags.join()),
+            warnings,
+            generated_warnings,
+            cpp_flags: escape_variable_value(&annotated.cpp_flags.join()),
         }
+    }
 
-        for include_dir in &borrowed_target.compiler_flags.include_directories {
-            let include_dir = IncludeDirectory {
-                path: include_dir.to_path_buf(),
-                include_type: IncludeType::Include,
-            };
-            makefile_writer
-                .data
-                .push_str(&include_dir.as_include_flag());
-            makefile_writer.data.push(' ');
+    // Origin of the gc_sections setting of `target`, if sections are removed.
+    fn gc_sections_origin(&self, target: &TargetNode) -> Option<FlagOrigin> {
+        match target.borrow().compiler_flags.gc_sections {
+            Some(true) => Some(FlagOrigin::Target),
+            Some(false) => None,
+            None if self.project_config.gc_sections => Some(FlagOrigin::Project),
+            None => None,
         }
+    }
 
-        for include_dir in &borrowed_target.compiler_flags.system_include_directories {
-            let include_dir = IncludeDirectory {
-                path: include_dir.to_path_buf(),
-                include_type: IncludeType::System,
-            };
-            makefile_writer
-                .data
-                .push_str(&include_dir.as_include_flag());
-            makefile_writer.data.push(' ');
-        }
+    // Hardening of `target`, with the origin of its hardened setting, if it is hardened.
+    fn hardening_of(&self, target: &TargetNode) -> Option<(Hardening, FlagOrigin)> {
+        let borrowed_target = target.borrow();
+        let origin = match borrowed_target.compiler_flags.hardened {
+            Some(true) => FlagOrigin::Target,
+            Some(false) => return None,
+            None if self.project_config.hardened => FlagOrigin::Project,
+            None => return None,
+        };
+        let output = match borrowed_target.library_type() {
+            Some(LibraryType::Static) => HardenedOutput::StaticLibrary,
+            Some(LibraryType::Dynamic) => HardenedOutput::SharedLibrary,
+            None => HardenedOutput::Executable,
+        };
+        let opt_level = borrowed_target
+            .compiler_flags
+            .opt_level
+            .or(self.project_config.opt_level)
+            .unwrap_or_else(|| OptLevel::default_for(&self.project_config.build_type));
+        let apple_linker = matches!(
+            LinkerFlavor::from_toolchain(&self.toolchain.borrow()),
+            LinkerFlavor::Apple
+        );
+        Some((
+            Hardening::new(output, opt_level != OptLevel::O0, apple_linker),
+            origin,
+        ))
+    }
 
-        makefile_writer.data.push('\n');
-        makefile_writer.data.push('\n');
+    // Origin of the system_pch setting of `target`, if the precompiled header is used.
+    fn system_pch_origin(&self, target: &TargetNode) -> Option<FlagOrigin> {
+        self.system_pch.as_ref()?;
+        // The headers are precompiled with the default flags, which have to match those of the
+        // translation units including them.
+        if target.borrow().compiler_flags.no_default_flags {
+            return None;
+        }
+        match target.borrow().compiler_flags.system_pch {
+            Some(true) => Some(FlagOrigin::Target),
+            Some(false) => None,
+            None if self.project_config.system_pch => Some(FlagOrigin::Project),
+            None => None,
+        }
+    }
 
-        let cpp_flags = &borrowed_target.compiler_flags.cpp_flags;
-        makefile_writer.data.push_str(&indoc::formatdoc!(
-            "# CPPFLAGS for target \"{target_name}\"
-    {target_name_capitalized}_CPPFLAGS +="
-        ));
+    /// Whether `target` is compiled with the precompiled standard library headers.
+    pub(crate) fn uses_system_pch(&self, target: &TargetNode) -> bool {
+        self.system_pch_origin(target).is_some()
+    }
 
-        if let Some(cpp) = cpp_flags {
-            makefile_writer.data.push_str(&indoc::formatdoc!(
-                "{cpp_flags}",
-                cpp_flags = cpp.flags().join(" ")
-            ));
+    /// What `target` needs that only the recipes of the Makefiles do, if anything. Other build
+    /// systems and the internal backend only compile and link the targets.
+    pub(crate) fn feature_needing_recipes(&self, target: &TargetNode) -> Option<&'static str> {
+        let borrowed_target = target.borrow();
+        if borrowed_target.build_for == types::BuildFor::Host {
+            Some("Building for the host")
+        } else if !borrowed_target.custom_commands.is_empty() {
+            Some("A custom command")
+        } else if !borrowed_target.embed.is_empty() {
+            Some("Embedding files")
+        } else if !borrowed_target.post_link.is_empty() {
+            Some("A post-link artifact")
+        } else if borrowed_target.version.is_some() {
+            Some("A library version")
+        } else if self.uses_system_pch(target) {
+            Some("The precompiled standard library headers")
+        } else {
+            None
         }
+    }
 
-        let defines = if !self.project_config.defines.is_empty() {
-            let defines = &self.project_config.defines;
-            generate_defines(defines)
-        } else {
-            generate_defines(&borrowed_target.defines)
+    // Precompiled standard library headers used by any of `targets`.
+    fn used_system_pch(&self, targets: &[TargetNode]) -> Option<&SystemPch> {
+        let system_pch = self.system_pch.as_ref()?;
+        targets
+            .iter()
+            .any(|target| self.system_pch_origin(target).is_some())
+            .then_some(system_pch)
+    }
+
+    fn annotated_compile_flags(&self, target: &TargetNode) -> AnnotatedCompileFlags {
+        let borrowed_target = target.borrow();
+        let compiler_flags = &borrowed_target.compiler_flags;
+        let mut language_flags = AnnotatedFlags::new();
+        let target_language_flags = match self.project_config.language {
+            Language::CXX => compiler_flags.cxx_flags.as_ref().map(|cxx| cxx.flags()),
+            Language::C => compiler_flags.c_flags.as_ref().map(|c| c.flags()),
         };
+        if let Some(flags) = target_language_flags {
+            language_flags.extend(flags.iter().cloned(), &FlagOrigin::Target);
+        }
+        if let Some(origin) = self.gc_sections_origin(target) {
+            language_flags.extend(["-ffunction-sections", "-fdata-sections"], &origin);
+        }
+        let hardening = self.hardening_of(target);
+        if let Some((ref hardening, ref origin)) = hardening {
+            language_flags.extend(hardening.language_flags(), origin);
+        }
+        if let Some(flag) = sanitize_flag(&self.project_config.sanitizers) {
+            language_flags.extend(
+                [flag.as_str(), "-fno-omit-frame-pointer"],
+                &FlagOrigin::CommandLine,
+            );
+        }
+        // Comes after the flags of the build type, so the level of the target is the one used.
+        if let Some(opt_level) = compiler_flags.opt_level {
+            let toolchain = self.toolchain.borrow();
+            let compiler_info = match self.project_config.language {
+                Language::CXX => &toolchain.cxx.compiler.compiler_info,
+                Language::C => &toolchain.cc.compiler.compiler_info,
+            };
+            language_flags.push(
+                opt_level.flag(
+                    &compiler_info.compiler_type,
+                    &compiler_info.compiler_version,
+                ),
+                FlagOrigin::Target,
+            );
+        }
+
+        let mut cpp_flags = AnnotatedFlags::new();
+        if let (Some(system_pch), Some(origin)) =
+            (self.system_pch.as_ref(), self.system_pch_origin(target))
+        {
+            cpp_flags.extend(
+                [
+                    "-include".to_string(),
+                    system_pch.header().display().to_string(),
+                ],
+                &origin,
+            );
+        }
+        if let Some((ref hardening, ref origin)) = hardening {
+            cpp_flags.extend(hardening.cpp_flags(), origin);
+        }
+        if let Some(ref cpp) = compiler_flags.cpp_flags {
+            cpp_flags.extend(cpp.flags().iter().cloned(), &FlagOrigin::Target);
+        }
+        // Defines of the command line are appended to those of the target, and override those
+        // of the same macros.
+        let target_defines = borrowed_target
+            .defines
+            .iter()
+            .filter(|define| {
+                !self
+                    .project_config
+                    .defines
+                    .iter()
+                    .any(|cli_define| cli_define.macro_ == define.macro_)
+            })
+            .cloned()
+            .collect::<Vec<types::Define>>();
+        cpp_flags.extend(define_flags(&target_defines), &FlagOrigin::Target);
+        cpp_flags.extend(
+            define_flags(&self.project_config.defines),
+            &FlagOrigin::CommandLine,
+        );
+        cpp_flags.append(self.export_define_flags(target));
 
-        makefile_writer.data.push_str(&defines);
+        AnnotatedCompileFlags {
+            language_flags,
+            cpp_flags,
+        }
+    }
 
-        makefile_writer.data.push('\n');
-        makefile_writer.data.push('\n');
-        makefile_writer.data.push_str(&indoc::formatdoc!(
-            "# LDFLAGS for target \"{target_name}\"
-                {target_name_capitalized}_LDFLAGS +="
-        ));
+    // Makes `<NAME>_EXPORT` mark the symbols of a DLL exported while it is built and imported by
+    // the targets linking it, so that its headers can declare them with the macro.
+    fn export_define_flags(&self, target: &TargetNode) -> AnnotatedFlags {
+        let mut flags = AnnotatedFlags::new();
+        if !self.output_extensions_of(target).dynamic_library.is_dll() {
+            return flags;
+        }
+        let borrowed_target = target.borrow();
+        if let TargetType::Library(ref library) = borrowed_target.target_type {
+            if library.ty == LibraryType::Dynamic {
+                flags.extend(
+                    define_flags(&[export_define(&library.name, "__declspec(dllexport)")]),
+                    &FlagOrigin::Toolchain,
+                );
+            }
+        }
+        for dependency in &borrowed_target.dependencies {
+            if let Some(s) = dependency.source.from_source() {
+                if s.library.ty == LibraryType::Dynamic {
+                    flags.extend(
+                        define_flags(&[export_define(&s.library.name, "__declspec(dllimport)")]),
+                        &FlagOrigin::Dependency(s.library.name.clone()),
+                    );
+                }
+            }
+        }
+        flags
+    }
 
-        let deps = &borrowed_target.dependencies;
-        for dep in deps {
-            match dep.source {
-                DependencySource::FromPkgConfig(ref pkg_config_target) => {
-                    match pkg_config_target.method {
-                        ProvideMethod::PkgConfigOutput(ref ld_flags) => {
-                            let search_flags = ld_flags.link_dirs.join(" ");
-                            makefile_writer.data.push_str(&search_flags);
-
-                            let libs = ld_flags.link_libs.join(" ");
-                            makefile_writer.data.push(' ');
-                            makefile_writer.data.push_str(&libs);
-                            makefile_writer.data.push(' ');
-                        }
-                        _ => {}
+    // Warning flags of `target`, with the profiles expanded to the flags of warnings.mk.
+    fn annotated_warning_flags(
+        &self,
+        target: &TargetNode,
+        include_file_generator: &IncludeFileGenerator,
+    ) -> AnnotatedFlags {
+        let borrowed_target = target.borrow();
+        let (warnings, origin) = match borrowed_target.compiler_flags.warnings {
+            Some(ref warnings) => (warnings, FlagOrigin::Target),
+            None if borrowed_target.compiler_flags.no_default_flags => {
+                return AnnotatedFlags::new()
+            }
+            None => (&self.project_config.warnings, FlagOrigin::Project),
+        };
+        let language = &self.project_config.language;
+        let mut flags = AnnotatedFlags::new();
+        match warnings {
+            Warnings::Profile(WarningProfile::Default) => flags.extend(
+                include_file_generator.warning_flags(language, false),
+                &origin,
+            ),
+            Warnings::Profile(WarningProfile::Strict) => flags.extend(
+                include_file_generator.warning_flags(language, true),
+                &origin,
+            ),
+            Warnings::Profile(WarningProfile::None) => flags.push("-w", origin),
+            Warnings::Custom(custom) => flags.extend(custom.iter().cloned(), &origin),
+        }
+        flags
+    }
+
+    // LDFLAGS of `target`, which come after the objects and libraries it links.
+    fn annotated_link_flags(&self, target: &TargetNode) -> AnnotatedFlags {
+        let borrowed_target = target.borrow();
+        let target_name = borrowed_target.name();
+        let mut flags = AnnotatedFlags::new();
+        for dep in &borrowed_target.dependencies {
+            if let DependencySource::FromPkgConfig(ref pkg_config_target) = dep.source {
+                if let ProvideMethod::PkgConfigOutput(ref ld_flags) = pkg_config_target.method {
+                    let origin = FlagOrigin::Dependency(pkg_config_target.target.clone());
+                    flags.extend(ld_flags.link_dirs.iter().cloned(), &origin);
+                    flags.extend(ld_flags.link_libs.iter().cloned(), &origin);
+                }
+            }
+        }
+        if let Some(origin) = self.gc_sections_origin(target) {
+            flags.extend(
+                gc_sections_link_flags(self.project_config.report_gc_sections),
+                &origin,
+            );
+        }
+        if let Some((hardening, origin)) = self.hardening_of(target) {
+            flags.extend(hardening.link_flags(), &origin);
+        }
+        if let Some(flag) = sanitize_flag(&self.project_config.sanitizers) {
+            flags.push(flag, FlagOrigin::CommandLine);
+        }
+        let linker_flavor = LinkerFlavor::from_toolchain(&self.toolchain.borrow());
+        if borrowed_target
+            .post_link
+            .contains(&types::PostLinkArtifact::Map)
+        {
+            let map_file = self
+                .makefile_directory()
+                .join(post_link_artifact_name(
+                    &target_name,
+                    &types::PostLinkArtifact::Map,
+                ))
+                .display()
+                .to_string();
+            flags.push(
+                linker_flavor.map_file(&quote_shell_argument(&map_file)),
+                FlagOrigin::Target,
+            );
+        }
+        if let Some(ref version_script) = borrowed_target.version_script {
+            flags.push(
+                linker_flavor
+                    .version_script(&quote_shell_argument(&version_script.display().to_string())),
+                FlagOrigin::Target,
+            );
+        }
+        // System libraries come last, after the objects and libraries that use them. Those of the
+        // static libraries the target links follow its own, as the archives do not record them.
+        let mut libs = borrowed_target.compiler_flags.libs.clone();
+        for dependency in &borrowed_target.dependencies {
+            if let DependencySource::FromSource(ref s) = dependency.source {
+                for lib in &s.system_libraries {
+                    if !libs.contains(lib) {
+                        libs.push(lib.clone());
                     }
                 }
-                _ => {}
             }
         }
-        makefile_writer.data.push('\n');
-        makefile_writer.data.push('\n');
+        for lib in &libs {
+            match linker_flavor.system_library(lib) {
+                Some(flag) => flags.push(flag, FlagOrigin::Target),
+                None => log::debug!(
+                    "Library {} of {} is part of the C library of the platform and is not linked explicitly",
+                    lib,
+                    target_name
+                ),
+            }
+        }
+        flags
+    }
+
+    fn generate_compiler_flags_for_target(
+        &self,
+        target: &TargetNode,
+        makefile_writer: &mut Writer,
+    ) {
+        let borrowed_target = target.borrow();
+        let target_name = borrowed_target.name();
+        let target_name_capitalized = target_name.to_uppercase();
+        let compile_flags = self.compile_flags(target);
+        let language_flags_variable = match self.project_config.language {
+            Language::CXX => "CXXFLAGS",
+            Language::C => "CFLAGS",
+        };
+        makefile_writer.data.push_str(&format!(
+            "# {language_flags_variable} for target \"{target_name}\"\n\
+             {target_name_capitalized}_{language_flags_variable} += {language_flags}\n\n",
+            language_flags = compile_flags.language_flags,
+        ));
+        makefile_writer.data.push_str(&format!(
+            "# Warnings for target \"{target_name}\"\n\
+             {target_name_capitalized}_WARNINGS := {warning_flags}\n\n",
+            warning_flags = compile_flags.warnings,
+        ));
+        if let Some(ref generated_warnings) = compile_flags.generated_warnings {
+            makefile_writer.data.push_str(&format!(
+                "# Warnings for the generated sources of target \"{target_name}\"\n\
+                 {target_name_capitalized}_GENERATED_WARNINGS := {generated_warnings}\n\n",
+            ));
+        }
+        makefile_writer.data.push_str(&format!(
+            "# CPPFLAGS for target \"{target_name}\"\n\
+             {target_name_capitalized}_CPPFLAGS += {cpp_flags}\n\n",
+            cpp_flags = compile_flags.cpp_flags,
+        ));
+        makefile_writer.data.push_str(&format!(
+            "# LDFLAGS for target \"{target_name}\"\n\
+             {target_name_capitalized}_LDFLAGS += {ld_flags}\n\n",
+            ld_flags = escape_variable_value(&self.annotated_link_flags(target).join()),
+        ));
+    }
+}
+
+// Sanitizers have to be given both when compiling and linking.
+fn sanitize_flag(sanitizers: &[configurations::Sanitizer]) -> Option<String> {
+    if sanitizers.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "-fsanitize={}",
+        sanitizers
+            .iter()
+            .map(|sanitizer| sanitizer.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    ))
+}
+
+// Warning flags of a profile refer to the variables defined in warnings.mk.
+fn warning_flags(warnings: &Warnings, language: &Language) -> String {
+    let language = match language {
+        Language::CXX => "CXX",
+        Language::C => "C",
+    };
+    match warnings {
+        Warnings::Profile(WarningProfile::Default) => format!("$({}_WARNINGS_DEFAULT)", language),
+        Warnings::Profile(WarningProfile::Strict) => format!("$({}_WARNINGS_STRICT)", language),
+        Warnings::Profile(WarningProfile::None) => "-w".to_string(),
+        Warnings::Custom(flags) => escape_variable_value(&flags.join(" ")),
+    }
+}
+
+// Linker flags removing unused sections, optionally reporting what was removed.
+// The linker of macOS has no report of removed sections.
+fn gc_sections_link_flags(report: bool) -> Vec<&'static str> {
+    if cfg!(target_os = "macos") {
+        vec!["-Wl,-dead_strip"]
+    } else {
+        let mut flags = vec!["-Wl,--gc-sections"];
+        if report {
+            flags.push("-Wl,--print-gc-sections");
+        }
+        flags
     }
 }
 
@@ -739,61 +2978,729 @@ impl Generator for MakefileGenerator {
             &self.project_config.build_type.to_string(),
         ))?;
         let mut writers = Writers {
-            makefile_writer: Writer::new(&self.output_directory.join("Makefile"))?,
-            progress_writer: ProgressWriter::new(&self.output_directory)?,
+            makefile_writer: Writer::new(&self.output_directory.join(MAKEFILE_NAME)),
+            target_writers: Vec::new(),
+            progress_writer: ProgressWriter::new(&self.output_directory),
         };
         self.generate_makefile(&mut writers, registry)?;
-        self.generate_all_target_for_progress_document(&writers.makefile_writer.object_targets);
+        let object_targets = writers
+            .target_writers
+            .iter()
+            .flat_map(|writer| writer.object_targets.iter().cloned())
+            .collect::<Vec<ObjectTarget>>();
+        self.generate_all_target_for_progress_document(&object_targets, registry);
         writers
             .progress_writer
-            .write_document(&self.progress_document);
+            .write_document(&self.progress_document)?;
         writers.makefile_writer.write()?;
+        if self.build_plan {
+            let build_plan = serde_json::to_string_pretty(&self.build_plan_of(registry)?).unwrap();
+            utility::write_file_atomically(
+                &self.output_directory.join(PLAN_FILE_NAME),
+                build_plan.as_bytes(),
+            )?;
+        }
+        self.generate_host_build_files(registry)?;
         Ok(self.output_directory.clone())
     }
+
+    fn annotated_commands(&self, registry: &TargetRegistry) -> Vec<TargetCommands> {
+        let toolchain = self.toolchain.borrow();
+        let include_file_generator = self.include_file_generator(&toolchain);
+        registry
+            .registry
+            .iter()
+            .filter(|target| target.borrow().build_for == self.build_for)
+            .map(|target| self.annotated_commands_of(target, &include_file_generator))
+            .collect()
+    }
+
+    fn hardened_targets(&self, registry: &TargetRegistry) -> Vec<(String, Hardening)> {
+        registry
+            .registry
+            .iter()
+            .filter(|target| target.borrow().build_for == self.build_for)
+            .filter_map(|target| {
+                let (hardening, _) = self.hardening_of(target)?;
+                Some((target.borrow().name(), hardening))
+            })
+            .collect()
+    }
 }
 
 pub(crate) struct Writers {
     makefile_writer: Writer,
+    target_writers: Vec<Writer>,
     progress_writer: ProgressWriter,
 }
 
 struct ProgressWriter {
-    file_handle: std::fs::File,
+    path: std::path::PathBuf,
 }
 
 impl ProgressWriter {
-    pub fn new(base_dir: &std::path::Path) -> Result<Self, GeneratorError> {
-        let path = base_dir.join(progress::PROGRESS_FILE_NAME);
-        let file_handle = utility::create_file(&path)?;
-        Ok(Self { file_handle })
+    pub fn new(base_dir: &std::path::Path) -> Self {
+        Self {
+            path: base_dir.join(progress::PROGRESS_FILE_NAME),
+        }
     }
 
-    pub fn write_document(&mut self, document: &generator::targets::ProgressDocument) {
+    pub fn write_document(
+        &mut self,
+        document: &generator::targets::ProgressDocument,
+    ) -> Result<(), GeneratorError> {
         let s = serde_json::to_string_pretty(document).unwrap();
-        self.file_handle.write_all(s.as_bytes()).unwrap();
+        utility::write_file_atomically(&self.path, s.as_bytes())?;
+        Ok(())
     }
 }
 
 struct Writer {
-    file_handle: std::fs::File,
+    path: std::path::PathBuf,
     data: String,
     object_targets: Vec<ObjectTarget>,
+    // Fingerprint of the target the build file is generated from, as recorded in the checkpoint.
+    fingerprint: u64,
+    // Whether the build file was written by a generation that failed partway, and is not
+    // generated again.
+    resumed: bool,
 }
 
 impl Writer {
-    pub fn new(path: &std::path::Path) -> Result<Self, GeneratorError> {
-        let file_handle = utility::create_file(path)?;
-        Ok(Self {
-            file_handle,
+    pub fn new(path: &std::path::Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
             data: String::new(),
             object_targets: Vec::new(),
-        })
+            fingerprint: 0,
+            resumed: false,
+        }
     }
 
-    pub fn write(&mut self) -> Result<(), FsError> {
-        self.file_handle
-            .write(self.data.as_bytes())
-            .map_err(FsError::WriteToFile)?;
+    // Creates the directories of the object files along with the build file itself.
+    pub fn write(&self) -> Result<(), GeneratorError> {
+        for object_target in &self.object_targets {
+            if let Some(object_dir) = object_target.object.parent() {
+                utility::create_dir(object_dir)?;
+            }
+        }
+        utility::write_file_atomically(&self.path, self.data.as_bytes())?;
         Ok(())
     }
 }
+
+// Writes the build files spread over as many threads as there are CPUs. The contents are generated
+// up front, since the target nodes can not be shared across threads.
+fn write_in_parallel(writers: &[&Writer]) -> Result<(), GeneratorError> {
+    if writers.is_empty() {
+        return Ok(());
+    }
+    let chunk_size = writers.len().div_ceil(num_cpus::get().max(1));
+    std::thread::scope(|scope| {
+        let handles = writers
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().copied().try_for_each(Writer::write)))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("Build file writer thread panicked"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // Project of `manifest`, with the empty `sources`, in a temporary directory.
+    fn project(manifest: &str, sources: &[&str]) -> tempdir::TempDir {
+        let tempdir = tempdir::TempDir::new("project").unwrap();
+        std::fs::write(tempdir.path().join(crate::YAMBS_MANIFEST_NAME), manifest).unwrap();
+        for source in sources {
+            std::fs::write(tempdir.path().join(source), "").unwrap();
+        }
+        std::fs::write(
+            tempdir.path().join("toolchain.toml"),
+            indoc::indoc! {r#"
+                [CXX]
+                compiler = "/usr/bin/g++"
+
+                [CC]
+                compiler = "/usr/bin/gcc"
+
+                [common]
+            "#},
+        )
+        .unwrap();
+        tempdir
+    }
+
+    // Generates the Makefiles of the project in `project_directory`, with the toolchain and the
+    // configuration of the project adjusted by `configure`, and returns the build tree.
+    fn generate(
+        project_directory: &std::path::Path,
+        configure: impl FnOnce(&mut NormalizedToolchain, &mut crate::ProjectConfig),
+    ) -> Result<std::path::PathBuf, GeneratorError> {
+        let project_directory = project_directory.canonicalize().unwrap();
+        let manifest_path = project_directory.join(crate::YAMBS_MANIFEST_NAME);
+        let mut toolchain =
+            NormalizedToolchain::from_file(&project_directory.join("toolchain.toml")).unwrap();
+        let manifest = crate::manifest::ParsedManifest {
+            manifest: crate::manifest::Manifest::new(&project_directory),
+            data: crate::parser::parse_toml(
+                &std::fs::read_to_string(&manifest_path).unwrap(),
+                &manifest_path,
+                &mut crate::parser::preprocessor::Preprocessor::new(),
+            )
+            .unwrap(),
+        };
+        let build_type = configurations::BuildType::Debug;
+        let mut project_config = crate::ProjectConfig {
+            std: types::Standard::new("c++17", &Language::CXX).unwrap(),
+            std_origin: FlagOrigin::Default,
+            language: Language::CXX,
+            build_directory: BuildDirectory::from(project_directory.join("build")),
+            build_type: build_type.clone(),
+            generator_type: generator::GeneratorType::GNUMakefiles,
+            defines: Vec::new(),
+            gc_sections: false,
+            report_gc_sections: false,
+            hardened: false,
+            system_pch: false,
+            warnings: Warnings::default(),
+            generated_warnings: GeneratedWarnings::default(),
+            sanitizers: Vec::new(),
+            opt_level: None,
+        };
+        configure(&mut toolchain, &mut project_config);
+        let toolchain = Rc::new(RefCell::new(toolchain));
+        let mut registry = TargetRegistry::new().with_manifests(HashMap::from([(
+            project_directory.clone(),
+            manifest.clone(),
+        )]));
+        for target in &manifest.data.targets {
+            build_target::BuildTarget::target_node_from_source(
+                &manifest,
+                target,
+                types::BuildFor::Target,
+                &mut registry,
+                &toolchain,
+                &build_type,
+            )
+            .unwrap();
+        }
+        let mut generator = MakefileGenerator::new(&project_config, toolchain)?
+            .with_signing(manifest.data.signing.clone());
+        generator.generate(&registry)?;
+        Ok(project_config
+            .build_directory
+            .as_path()
+            .join(build_type.to_string()))
+    }
+
+    // Build file of `target` in `build_tree`.
+    fn build_file(build_tree: &std::path::Path, target: &str) -> String {
+        std::fs::read_to_string(
+            build_tree
+                .join("deps")
+                .join(format!("{}.dir", target))
+                .join(TARGET_BUILD_FILE_NAME),
+        )
+        .unwrap()
+    }
+
+    // Generates the Makefiles of a project with the given manifest, and returns the build file of
+    // the target with the given name.
+    fn generate_project(manifest: &str, sources: &[&str], target: &str) -> String {
+        let project = project(manifest, sources);
+        build_file(&generate(project.path(), |_, _| {}).unwrap(), target)
+    }
+
+    // The value of a variable of the build file, as it is assigned and appended to.
+    fn variable(build_file: &str, name: &str) -> String {
+        build_file
+            .lines()
+            .filter_map(|line| {
+                line.strip_prefix(&format!("{} += ", name))
+                    .or_else(|| line.strip_prefix(&format!("{} := ", name)))
+                    .or_else(|| line.strip_prefix(&format!("{} = ", name)))
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn dependents_include_headers_generated_in_subdirectories_of_libraries() {
+        let project = project(
+            indoc::indoc! {r#"
+                [executable.app]
+                sources = ["main.cpp"]
+                dependencies.config = { path = "." }
+
+                [library.config]
+                sources = ["config.cpp"]
+
+                [[library.config.custom_command]]
+                command = ["sh", "generate.sh"]
+                inputs = ["generate.sh"]
+                outputs = ["sub/config.h"]
+            "#},
+            &["main.cpp", "config.cpp", "generate.sh"],
+        );
+        let build_tree = generate(project.path(), |_, _| {}).unwrap();
+        let build_file = build_file(&build_tree, "app");
+        let generated_directory = build_tree.join("deps").join("config.dir").join("generated");
+        assert!(build_file.contains(&format!("-I{} ", generated_directory.display())));
+        assert!(!build_file.contains(&format!("-I{}", generated_directory.join("sub").display())));
+        assert!(build_file.contains(
+            &generated_directory
+                .join("sub")
+                .join("config.h")
+                .display()
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn include_directories_are_given_once_with_system_directories_last() {
+        let project = project("", &["main.cpp"]);
+        let directory = project.path().canonicalize().unwrap();
+        for include_directory in ["include", "vendor", "third_party", "config"] {
+            std::fs::create_dir_all(directory.join(include_directory)).unwrap();
+        }
+        let path =
+            |include_directory: &str| directory.join(include_directory).display().to_string();
+        std::fs::write(
+            directory.join(crate::YAMBS_MANIFEST_NAME),
+            format!(
+                indoc::indoc! {r#"
+                    [executable.app]
+                    sources = ["main.cpp"]
+                    append_include_directories = ["{vendor}", "{include}", "{vendor}"]
+                    append_system_include_directories = ["{third_party}", "{vendor}"]
+                    dependencies.headers = {{ include_directory = "third_party", origin = "System" }}
+                    dependencies.config = {{ include_directory = "config", origin = "Include" }}
+                "#},
+                vendor = path("vendor"),
+                include = path("include"),
+                third_party = path("third_party"),
+            ),
+        )
+        .unwrap();
+        let build_file = build_file(&generate(&directory, |_, _| {}).unwrap(), "app");
+        let compile_command = build_file
+            .lines()
+            .find(|line| line.contains("-c -o $@"))
+            .unwrap();
+        // The include directory of the target comes first, then those appended to it and those
+        // of its dependencies, with the system directories after all of them.
+        assert!(compile_command.contains(&format!(
+            "$(APP_WARNINGS) -I{} -I{} -I{} -isystem {} $<",
+            path("include"),
+            path("vendor"),
+            path("config"),
+            path("third_party")
+        )));
+    }
+
+    #[test]
+    fn custom_commands_depend_on_the_executable_they_run() {
+        let project = project(
+            indoc::indoc! {r#"
+                [executable.codegen]
+                sources = ["codegen.cpp"]
+
+                [executable.app]
+                sources = ["main.cpp"]
+                custom_command = [{ command = { target = "codegen" }, outputs = ["table.cpp"] }]
+            "#},
+            &["codegen.cpp", "main.cpp"],
+        );
+        let build_tree = generate(project.path(), |_, _| {}).unwrap();
+        let table = build_tree
+            .join("deps")
+            .join("app.dir")
+            .join(GENERATED_DIRECTORY_NAME)
+            .join("table.cpp");
+        // The executable is the file it links rather than a phony target, so the command is only
+        // run again once codegen is linked again.
+        assert!(
+            build_file(&build_tree, "app").contains(&format!("\n{} : codegen\n", table.display()))
+        );
+        assert!(!build_file(&build_tree, "codegen").contains(".PHONY: codegen"));
+    }
+
+    #[test]
+    fn executables_link_the_system_libraries_of_their_static_dependencies() {
+        let build_file = generate_project(
+            indoc::indoc! {r#"
+                [executable.app]
+                sources = ["main.cpp"]
+                dependencies.geo = { path = "." }
+
+                [library.geo]
+                sources = ["geo.cpp"]
+                libs = ["m"]
+                dependencies.base = { path = "." }
+
+                [library.base]
+                sources = ["base.cpp"]
+                libs = ["z"]
+            "#},
+            &["main.cpp", "geo.cpp", "base.cpp"],
+            "app",
+        );
+        let ldflags = variable(&build_file, "APP_LDFLAGS");
+        assert!(ldflags.contains("-lm"), "{}", build_file);
+        assert!(ldflags.contains("-lz"), "{}", build_file);
+    }
+
+    #[test]
+    fn targets_without_default_flags_are_compiled_and_linked_without_cxxflags() {
+        let manifest = indoc::indoc! {r#"
+            [executable.app]
+            sources = ["main.cpp"]
+
+            [executable.vendored]
+            sources = ["vendored.cpp"]
+            cxxflags_append = ["-ffreestanding"]
+            no_default_flags = true
+        "#};
+        let project = project(manifest, &["main.cpp", "vendored.cpp"]);
+        let build_tree = generate(project.path(), |_, _| {}).unwrap();
+        let commands = |build_file: &str| {
+            build_file
+                .lines()
+                .filter(|line| line.contains("$(CXX)"))
+                .map(str::to_string)
+                .collect::<Vec<String>>()
+        };
+
+        let app = build_file(&build_tree, "app");
+        let app_commands = commands(&app);
+        assert_eq!(app_commands.len(), 2, "{}", app);
+        assert!(app_commands
+            .iter()
+            .all(|command| command.contains("$(CXX) $(CXXFLAGS) $(CPPFLAGS)")));
+
+        let vendored = build_file(&build_tree, "vendored");
+        let vendored_commands = commands(&vendored);
+        assert_eq!(vendored_commands.len(), 2, "{}", vendored);
+        assert!(
+            vendored_commands
+                .iter()
+                .all(|command| !command.contains("$(CXXFLAGS)")
+                    && command.contains("$(CPPFLAGS) $(VENDORED_CXXFLAGS)")),
+            "{}",
+            vendored
+        );
+        assert_eq!(variable(&vendored, "VENDORED_CXXFLAGS"), "-ffreestanding");
+        assert_eq!(variable(&vendored, "VENDORED_WARNINGS"), "");
+    }
+
+    #[test]
+    fn objects_are_only_shared_by_targets_with_the_same_extra_files() {
+        let manifest = |extra_file: &str| {
+            format!(
+                indoc::indoc! {r#"
+                    [executable.first]
+                    sources = ["shared.cpp"]
+                    extra_files = ["first.ld"]
+
+                    [executable.second]
+                    sources = ["shared.cpp"]
+                    extra_files = ["{}"]
+                "#},
+                extra_file
+            )
+        };
+        let sources = ["shared.cpp", "first.ld", "second.ld"];
+        assert!(!generate_project(&manifest("first.ld"), &sources, "second").contains("shared.o:"));
+        assert!(generate_project(&manifest("second.ld"), &sources, "second")
+            .contains("second.dir/shared.o:"));
+    }
+
+    #[test]
+    fn generation_resumes_without_generating_the_build_files_already_written() {
+        let project = project(
+            indoc::indoc! {r#"
+                [executable.app]
+                sources = ["app.cpp"]
+
+                [executable.firmware]
+                sources = ["firmware.cpp"]
+                post_link = ["hex"]
+            "#},
+            &["app.cpp", "firmware.cpp"],
+        );
+        let error = generate(project.path(), |toolchain, _| toolchain.objcopy = None).unwrap_err();
+        assert!(matches!(error, GeneratorError::ObjcopyNotFound(_)));
+
+        let build_tree = project.path().canonicalize().unwrap().join("build/debug");
+        let app_build_file = build_tree.join("deps/app.dir").join(TARGET_BUILD_FILE_NAME);
+        std::fs::write(&app_build_file, "# Written by the generation that failed\n").unwrap();
+        generate(project.path(), |toolchain, _| {
+            toolchain.objcopy = Some(std::path::PathBuf::from("/usr/bin/objcopy"))
+        })
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&app_build_file).unwrap(),
+            "# Written by the generation that failed\n"
+        );
+        assert!(build_file(&build_tree, "firmware").contains("firmware.hex"));
+        assert!(std::fs::read_to_string(build_tree.join("Makefile"))
+            .unwrap()
+            .contains(&format!("include {}", app_build_file.display())));
+        assert!(
+            Cache::new(&project.path().canonicalize().unwrap().join("build"))
+                .unwrap()
+                .from_cache::<GenerationCheckpoint>()
+                .is_none()
+        );
+    }
+
+    // Arguments the compiler gets for each flag of a variable, when make expands it in a recipe
+    // and the shell splits it into words.
+    fn round_trip(flags: &str) -> Vec<String> {
+        let tempdir = tempdir::TempDir::new("makefile").unwrap();
+        std::fs::write(
+            tempdir.path().join("Makefile"),
+            format!("FLAGS += {}\n\nprint:\n\t@printf '%s\\n' $(FLAGS)\n", flags),
+        )
+        .unwrap();
+        let output = std::process::Command::new("make")
+            .arg("-s")
+            .current_dir(tempdir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn defines_of_the_command_line_are_appended_to_those_of_the_target() {
+        let project = project(
+            indoc::indoc! {r#"
+                [executable.app]
+                sources = ["main.cpp"]
+                defines = [{ macro = "NAME", value = "app" }, { macro = "LOG_LEVEL", value = "1" }]
+            "#},
+            &["main.cpp"],
+        );
+        let build_tree = generate(project.path(), |_, project_config| {
+            project_config.defines = vec![
+                types::Define {
+                    macro_: "LOG_LEVEL".to_string(),
+                    value: Some("3".to_string()),
+                },
+                types::Define {
+                    macro_: "ENABLE_TRACING".to_string(),
+                    value: None,
+                },
+            ];
+        })
+        .unwrap();
+        let build_file = build_file(&build_tree, "app");
+        let cpp_flags = variable(&build_file, "APP_CPPFLAGS");
+        let defines = cpp_flags
+            .split_whitespace()
+            .filter(|flag| flag.starts_with("-D"))
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            defines,
+            vec!["-DNAME=app", "-DLOG_LEVEL=3", "-DENABLE_TRACING"],
+            "{}",
+            build_file
+        );
+    }
+
+    #[test]
+    fn defines_reach_the_compiler_verbatim() {
+        let define = |macro_: &str, value: &str| types::Define {
+            macro_: macro_.to_string(),
+            value: Some(value.to_string()),
+        };
+        let defines = vec![
+            define("VERSION", "\"1.2 beta\""),
+            define("PRICE", "$(COST) #1"),
+            define("QUOTE", "it's"),
+            define("PATH_SEP", "a\\b"),
+            types::Define {
+                macro_: "ENABLED".to_string(),
+                value: None,
+            },
+        ];
+        assert_eq!(
+            round_trip(&escape_variable_value(&define_flags(&defines).join(" "))),
+            vec![
+                "-DVERSION=\"1.2 beta\"",
+                "-DPRICE=$(COST) #1",
+                "-DQUOTE=it's",
+                "-DPATH_SEP=a\\b",
+                "-DENABLED",
+            ]
+        );
+    }
+
+    #[test]
+    fn dlls_export_their_symbols_and_make_an_import_library() {
+        let export = export_define("net-io", "__declspec(dllexport)");
+        assert_eq!(
+            round_trip(&escape_variable_value(&define_flags(&[export]).join(" "))),
+            vec!["-DNET_IO_EXPORT=__declspec(dllexport)"]
+        );
+
+        let library = PrintableLibrary {
+            name: "net-io".to_string(),
+            ty: LibraryType::Dynamic,
+        };
+        let output_extensions = OutputExtensions {
+            dynamic_library: DynamicLibraryFormat::MinGwDll,
+            ..OutputExtensions::default()
+        };
+        assert_eq!(
+            dynamic_library_link_flags(&library, &output_extensions),
+            vec!["-shared", "-Wl,--out-implib,libnet-io.dll.a"]
+        );
+        assert_eq!(
+            dynamic_library_link_flags(&library, &OutputExtensions::default()),
+            vec!["-rdynamic", "-shared"]
+        );
+    }
+
+    #[test]
+    fn versioned_libraries_are_reached_through_a_chain_of_symlinks() {
+        let library = VersionedLibrary {
+            file_name: "libfoo.so.1.2.3".to_string(),
+            soname: "libfoo.so.1".to_string(),
+        };
+        assert_eq!(
+            library.symlinks("libfoo.so"),
+            vec![
+                ("libfoo.so".to_string(), "libfoo.so.1".to_string()),
+                ("libfoo.so.1".to_string(), "libfoo.so.1.2.3".to_string()),
+            ]
+        );
+        let library = VersionedLibrary {
+            file_name: "libfoo.so.2".to_string(),
+            soname: "libfoo.so.2".to_string(),
+        };
+        assert_eq!(
+            library.symlinks("libfoo.so"),
+            vec![("libfoo.so".to_string(), "libfoo.so.2".to_string())]
+        );
+    }
+
+    #[test]
+    fn dependents_are_not_linked_again_when_archive_is_made_with_same_content() {
+        let tempdir = tempdir::TempDir::new("makefile").unwrap();
+        let directory = tempdir.path();
+        std::fs::write(
+            directory.join("Makefile"),
+            format!(
+                "app : app.o {hash_file}\n\
+                 \t@echo linking $(patsubst %.{extension},%,$^)\n\n\
+                 libgeo.a : geo.o\n\
+                 \t@cp $< $@\n\n\
+                 {hash_rule}",
+                hash_file = archive_hash_file_name("libgeo.a"),
+                extension = ARCHIVE_HASH_EXTENSION,
+                hash_rule = generate_archive_hash_rule("libgeo.a"),
+            ),
+        )
+        .unwrap();
+        let make = || {
+            let output = std::process::Command::new("make")
+                .current_dir(directory)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}", output);
+            std::fs::write(directory.join("app"), "").unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+        // Gives each file a modification time of its own, as if they were made in the order given.
+        // Files without content given keep the content they have.
+        let made_in_order = |files: &[(&str, Option<&str>)]| {
+            let start = std::time::SystemTime::now() - std::time::Duration::from_secs(100);
+            for (i, (file, content)) in files.iter().enumerate() {
+                let path = directory.join(file);
+                if let Some(content) = content {
+                    std::fs::write(&path, content).unwrap();
+                }
+                std::fs::File::options()
+                    .write(true)
+                    .open(&path)
+                    .unwrap()
+                    .set_modified(start + std::time::Duration::from_secs(i as u64))
+                    .unwrap();
+            }
+        };
+
+        made_in_order(&[("app.o", Some("")), ("geo.o", Some("geometry"))]);
+        assert_eq!(make(), "linking app.o libgeo.a\n");
+
+        made_in_order(&[
+            ("libgeo.a", Some("geometry")),
+            ("libgeo.a.hash", Some("stale")),
+            ("app", None),
+            ("geo.o", Some("geometry")),
+        ]);
+        assert_eq!(make(), "linking app.o libgeo.a\n");
+
+        made_in_order(&[
+            ("libgeo.a.hash", None),
+            ("libgeo.a", Some("geometry")),
+            ("app", None),
+            ("geo.o", Some("geometry")),
+        ]);
+        assert_eq!(make(), "");
+        assert_eq!(make(), "");
+    }
+
+    #[test]
+    fn artifacts_manifest_is_made_once_the_artifacts_are_signed() {
+        let project = project(
+            indoc::indoc! {r#"
+                [executable.app]
+                sources = ["main.cpp"]
+
+                [signing]
+                command = ["cp", "{artifact}", "{signature}"]
+                signature = "{artifact}.sig"
+            "#},
+            &["main.cpp"],
+        );
+        let build_tree = generate(project.path(), |_, _| {}).unwrap();
+        let artifacts_manifest = build_tree.join(ARTIFACTS_MANIFEST_FILE_NAME);
+        assert!(build_tree.join(SIGNING_PLAN_FILE_NAME).is_file());
+        assert!(!artifacts_manifest.exists());
+
+        // The artifact is taken as linked, so that only the signing rules are run.
+        let artifact = build_tree.join("app");
+        std::fs::write(&artifact, "app").unwrap();
+        let output = std::process::Command::new("make")
+            .args(["-o", "app"])
+            .arg(&artifacts_manifest)
+            .current_dir(&build_tree)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let signed = ArtifactsManifest::from_directory(&build_tree).unwrap();
+        assert_eq!(signed.artifacts.len(), 1);
+        assert_eq!(signed.artifacts[0].artifact, artifact);
+        assert!(signed.artifacts[0].signature.as_ref().unwrap().is_file());
+    }
+
+    #[test]
+    fn flags_are_split_into_words_by_the_shell() {
+        let flags = escape_variable_value("-O2 -DNAME='\"a b\"' -Wl,-rpath,'$ORIGIN' -DTAG=#x");
+        assert_eq!(
+            round_trip(&flags),
+            vec!["-O2", "-DNAME=\"a b\"", "-Wl,-rpath,$ORIGIN", "-DTAG=#x"]
+        );
+    }
+}
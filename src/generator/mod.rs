@@ -1,11 +1,32 @@
+use crate::build_hygiene::GeneratedFileConflict;
 use crate::build_target::{target_registry::TargetRegistry, TargetError};
-use crate::errors::FsError;
+use crate::errors::{CacheError, FsError};
+use crate::flags::AnnotatedCommand;
+use crate::hardening::Hardening;
 
+pub mod checkpoint;
+pub mod fingerprint;
+#[cfg(target_os = "linux")]
+pub mod internal;
 #[cfg(target_os = "linux")]
 pub mod makefile;
+#[cfg(target_os = "linux")]
+pub mod ninja;
+pub mod plan;
 
 #[cfg(target_os = "linux")]
 pub use makefile::MakefileGenerator;
+#[cfg(target_os = "linux")]
+pub use ninja::NinjaGenerator;
+
+/// Directory, relative to the build directory, of the build tree for targets built for the host.
+pub const HOST_BUILD_DIRECTORY_NAME: &str = "host";
+
+/// Target of the generated build files building the examples, which `all` leaves out.
+pub const EXAMPLES_TARGET_NAME: &str = "examples";
+
+/// Target of the generated build files signing the artifacts, which `all` and `install` depend on.
+pub const SIGN_TARGET_NAME: &str = "sign";
 
 #[non_exhaustive]
 #[derive(Debug, thiserror::Error)]
@@ -18,13 +39,40 @@ pub enum GeneratorError {
     CreateRule,
     #[error("Could not find any standards to use when generating build files")]
     StandardNotFound,
+    #[error("Could not determine path of the yambs executable")]
+    YambsExecutableNotFound(#[source] std::io::Error),
+    #[error("Could not find executable \"{0}\" run by custom command")]
+    ToolNotFound(String),
+    #[error("Could not find target \"{0}\" to install")]
+    InstallTargetNotFound(String),
+    #[error("Could not find target \"{0}\" to sign")]
+    SigningTargetNotFound(String),
+    #[error("Could not find target \"{1}\" to compile compile-fail test \"{0}\" with")]
+    CompileFailTargetNotFound(String, String),
+    #[error("Could not find objcopy to make the post-link artifacts of \"{0}\" with. Set objcopy in the toolchain file or add it to PATH")]
+    ObjcopyNotFound(String),
+    #[error("Generated files would clobber other files:{}", .0.iter().map(|conflict| format!("\n    {}", conflict)).collect::<String>())]
+    GeneratedFileConflicts(Vec<GeneratedFileConflict>),
+    #[error(
+        "{0} of \"{1}\" is not supported by the Ninja generator. Use --generator make instead"
+    )]
+    UnsupportedByNinja(&'static str, String),
+    #[error("{0} of \"{1}\" is not supported by the internal backend. Use --backend make instead")]
+    UnsupportedByInternalBackend(&'static str, String),
+    #[error("Failed to record which build files were written")]
+    Checkpoint(#[source] CacheError),
 }
 
 #[derive(clap::ValueEnum, Debug, Clone, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum GeneratorType {
     #[cfg(target_os = "linux")]
     /// Use GNU Makefiles
+    #[value(name = "make", alias = "gnu-makefiles")]
     GNUMakefiles,
+    #[cfg(target_os = "linux")]
+    /// Use Ninja
+    #[value(name = "ninja")]
+    Ninja,
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
@@ -39,6 +87,33 @@ pub trait Generator {
     /// Returns the directory of the main build file.
     fn generate(&mut self, registry: &TargetRegistry)
         -> Result<std::path::PathBuf, GeneratorError>;
+
+    /// Commands the targets of the registry are compiled and linked with, as put in the
+    /// generated build files, with the origin of each flag.
+    fn annotated_commands(&self, registry: &TargetRegistry) -> Vec<TargetCommands>;
+
+    /// Hardening options of the hardened targets of the registry.
+    fn hardened_targets(&self, registry: &TargetRegistry) -> Vec<(String, Hardening)>;
+}
+
+/// Compile and link command of a target. Static libraries are archived instead of linked, and
+/// have no link command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetCommands {
+    pub target: String,
+    pub compile: AnnotatedCommand,
+    pub link: Option<AnnotatedCommand>,
+}
+
+impl TargetCommands {
+    /// Whether the target is compiled or linked with link-time optimization, whether `-flto` is
+    /// given by the target itself or by the toolchain, the project or the command line.
+    pub fn uses_lto(&self) -> bool {
+        std::iter::once(&self.compile)
+            .chain(self.link.iter())
+            .flat_map(|command| command.flags.iter())
+            .any(|annotated| annotated.flag.starts_with("-flto"))
+    }
 }
 
 pub trait UtilityGenerator<'config> {
@@ -48,8 +123,10 @@ pub trait UtilityGenerator<'config> {
 }
 
 pub mod targets {
-    use crate::build_target::include_directories::IncludeDirectories;
-    use crate::build_target::{DependencySource, TargetNode};
+    use crate::build_target::include_directories::{
+        IncludeDirectories, IncludeDirectory, IncludeType,
+    };
+    use crate::build_target::{DependencySource, TargetNode, GENERATED_DIRECTORY_NAME};
 
     #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
     pub struct ProgressDocument {
@@ -66,6 +143,12 @@ pub mod targets {
         pub fn add_progress_tracking_target(&mut self, target: ProgressTrackingTarget) {
             self.targets.push(target)
         }
+
+        /// Reads the progress document of the build files in `directory`.
+        pub fn from_directory(directory: &std::path::Path) -> std::io::Result<Self> {
+            let fh = std::fs::File::open(directory.join(crate::progress::PROGRESS_FILE_NAME))?;
+            Ok(serde_json::from_reader(std::io::BufReader::new(fh))?)
+        }
     }
 
     #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
@@ -75,15 +158,28 @@ pub mod targets {
         pub object_files: Vec<std::path::PathBuf>,
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         pub dependencies: Vec<String>,
+        /// File linked by the target.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub output: Option<std::path::PathBuf>,
+        /// External packages the target depends on, such as those found with pkg-config.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub packages: Vec<String>,
     }
 
     impl ProgressTrackingTarget {
-        pub fn from_target(target_node: &TargetNode, output_directory: &std::path::Path) -> Self {
-            let target_object_targets =
-                ObjectTarget::create_object_targets(target_node, output_directory)
-                    .iter()
-                    .map(|o| o.object.to_path_buf())
-                    .collect::<Vec<std::path::PathBuf>>();
+        pub fn from_target(
+            target_node: &TargetNode,
+            output_directory: &std::path::Path,
+            object_extension: &str,
+        ) -> Self {
+            let target_object_targets = ObjectTarget::create_object_targets(
+                target_node,
+                output_directory,
+                object_extension,
+            )
+            .iter()
+            .map(|o| o.object.to_path_buf())
+            .collect::<Vec<std::path::PathBuf>>();
             let target_name = target_node.borrow().name();
             let target_dependencies = target_node
                 .borrow()
@@ -95,11 +191,31 @@ pub mod targets {
                 })
                 .map(|ds| ds.library.name.to_owned())
                 .collect::<Vec<String>>();
+            let target_packages = target_node
+                .borrow()
+                .dependencies
+                .iter()
+                .filter_map(|d| match d.source {
+                    DependencySource::FromHeaderOnly(ref header_only) => {
+                        Some(header_only.name.to_owned())
+                    }
+                    DependencySource::FromPkgConfig(ref pkg_config) => {
+                        Some(pkg_config.target.to_owned())
+                    }
+                    DependencySource::FromCMake(ref cmake) => Some(cmake.cmake_target.to_owned()),
+                    DependencySource::FromYambsBuild(ref yambs_build) => {
+                        Some(yambs_build.yambs_target.to_owned())
+                    }
+                    DependencySource::FromSource(_) => None,
+                })
+                .collect::<Vec<String>>();
 
             Self {
                 target: target_name,
                 object_files: target_object_targets,
                 dependencies: target_dependencies,
+                output: None,
+                packages: target_packages,
             }
         }
     }
@@ -110,27 +226,39 @@ pub mod targets {
         pub object: std::path::PathBuf,
         pub source: std::path::PathBuf,
         pub include_directories: IncludeDirectories,
+        /// Whether the source is produced by a custom command or from embedded files.
+        #[serde(default)]
+        pub generated: bool,
+        /// Files besides the source the object is compiled again after changes to.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub extra_files: Vec<std::path::PathBuf>,
     }
 
     impl ObjectTarget {
+        /// Objects of the sources of `target`, compiled into `output_directory` with
+        /// `object_extension`.
         pub fn create_object_targets(
             target: &TargetNode,
             output_directory: &std::path::Path,
+            object_extension: &str,
         ) -> Vec<ObjectTarget> {
             let mut object_targets = Vec::new();
             let borrowed_target = target.borrow();
-            let sources = borrowed_target
-                .source_files
-                .iter()
-                .filter(|file| file.is_source());
+            let source_files = borrowed_target.source_files_with_generated(output_directory);
+            let sources = source_files.iter().filter(|file| file.is_source());
             let dependency_root_path = &borrowed_target.manifest.directory;
             let target_name = borrowed_target.name();
+            let include_directories = Self::include_directories(target, output_directory);
 
             for source in sources {
                 let source_file = source.file();
-                let source_dir = source_file
-                    .parent()
-                    .and_then(|p| p.strip_prefix(dependency_root_path).ok());
+                let source_dir = source_file.parent().and_then(|p| {
+                    if source.is_generated() {
+                        p.strip_prefix(output_directory).ok()
+                    } else {
+                        p.strip_prefix(dependency_root_path).ok()
+                    }
+                });
 
                 let object = {
                     if let Some(dir) = source_dir {
@@ -141,40 +269,51 @@ pub mod targets {
                         output_directory.join(source_file.file_name().unwrap())
                     }
                 }
-                .with_extension("o");
-                let include_directories = {
-                    let mut include_directories = IncludeDirectories::new();
-                    include_directories.add(borrowed_target.include_directory.clone());
-                    let deps = &borrowed_target.dependencies;
-                    for dep in deps {
-                        match dep.source {
-                            DependencySource::FromSource(ref sd) => {
-                                let include_dir = sd.include_directory.clone();
-                                include_directories.add(include_dir);
-                            }
-                            DependencySource::FromHeaderOnly(ref hd) => {
-                                include_directories.add(hd.include_directory.clone());
-                            }
-                            DependencySource::FromPkgConfig(ref pkg) => {
-                                for dir in &pkg.include_directories {
-                                    include_directories.add(dir.clone());
-                                }
-                            }
-                        }
-                    }
-                    include_directories
-                };
+                .with_extension(object_extension);
 
                 let object_target = ObjectTarget {
                     target: target_name.clone(),
                     object,
                     source: source_file,
-                    include_directories,
+                    include_directories: include_directories.clone(),
+                    generated: source.is_generated(),
+                    extra_files: borrowed_target.extra_files.clone(),
                 };
 
                 object_targets.push(object_target);
             }
             object_targets
         }
+
+        /// Include directories the sources of `target` are compiled with, when its build files
+        /// are in `output_directory`.
+        pub fn include_directories(
+            target: &TargetNode,
+            output_directory: &std::path::Path,
+        ) -> IncludeDirectories {
+            let borrowed_target = target.borrow();
+            // Generated headers have to be found before any other header of the target.
+            let mut include_directories = IncludeDirectories::new();
+            if !borrowed_target.custom_commands.is_empty() || !borrowed_target.embed.is_empty() {
+                include_directories.add(IncludeDirectory {
+                    include_type: IncludeType::Include,
+                    path: output_directory.join(GENERATED_DIRECTORY_NAME),
+                });
+            }
+            // Headers generated by the libraries depended on are in the generated directories of
+            // the libraries, next to the output directory of the target.
+            if let Some(build_files_directory) = output_directory.parent() {
+                for directory in borrowed_target.dependency_generated_directories() {
+                    include_directories.add(IncludeDirectory {
+                        include_type: IncludeType::Include,
+                        path: build_files_directory.join(directory),
+                    });
+                }
+            }
+            for include_directory in &borrowed_target.include_directories() {
+                include_directories.add(include_directory.clone());
+            }
+            include_directories
+        }
     }
 }
@@ -0,0 +1,380 @@
+// Generates a build.ninja for the Ninja build system, from the same registry and toolchain as the
+// Makefile generator. The targets are compiled and linked with the commands the Makefile generator
+// puts in its build files, so that both build systems build with the same flags. Targets that need
+// recipes of their own, such as custom commands, embedded files and post-link artifacts, are only
+// built by the Makefile generator.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::build_target::pkg_config::ProvideMethod;
+use crate::build_target::{
+    target_registry::TargetRegistry, DependencySource, LibraryType, TargetNode, TargetType,
+};
+use crate::environment::{Environment, ModifyMode};
+use crate::errors::FsError;
+use crate::generator::makefile::make::BuildProcess;
+use crate::generator::targets::{ObjectTarget, ProgressDocument, ProgressTrackingTarget};
+use crate::generator::{
+    Generator, GeneratorError, MakefileGenerator, TargetCommands, EXAMPLES_TARGET_NAME,
+};
+use crate::hardening::Hardening;
+use crate::progress::{self, Step, StepEvent};
+use crate::toolchain::NormalizedToolchain;
+use crate::utility;
+use crate::ProjectConfig;
+
+pub const NINJA_BUILD_FILE_NAME: &str = "build.ninja";
+
+// Pool the links of targets using link-time optimization are run in.
+const LTO_LINK_POOL_NAME: &str = "lto_link";
+
+pub struct NinjaGenerator {
+    toolchain: Rc<RefCell<NormalizedToolchain>>,
+    project_config: ProjectConfig,
+    // Computes the commands of the targets, without generating any Makefiles.
+    commands: MakefileGenerator,
+    // Links the targets using link-time optimization one at a time, in a pool of depth one.
+    serialize_lto_links: bool,
+}
+
+impl NinjaGenerator {
+    pub fn new(
+        project_config: &ProjectConfig,
+        toolchain: Rc<RefCell<NormalizedToolchain>>,
+    ) -> Result<Self, GeneratorError> {
+        Ok(Self {
+            commands: MakefileGenerator::new(project_config, toolchain.clone())?,
+            toolchain,
+            project_config: project_config.clone(),
+            serialize_lto_links: false,
+        })
+    }
+
+    /// Links the targets using link-time optimization one at a time, as each of them may take as
+    /// much memory as compiling all of its sources at once.
+    pub fn with_serialized_lto_links(mut self) -> Self {
+        self.serialize_lto_links = true;
+        self
+    }
+
+    // Directory of build.ninja, which the outputs of the targets are placed in as well.
+    fn output_directory(&self) -> PathBuf {
+        self.project_config
+            .build_directory
+            .as_path()
+            .join(self.project_config.build_type.to_string())
+    }
+
+    // Build statements of `target`, along with the file it produces.
+    fn target_statements(
+        &self,
+        target: &TargetNode,
+        commands: &TargetCommands,
+    ) -> (String, String) {
+        let toolchain = self.toolchain.borrow();
+        let output_extensions = &toolchain.output_extensions;
+        let borrowed_target = target.borrow();
+        let name = borrowed_target.name();
+        let output_directory = self.output_directory();
+        let object_targets = ObjectTarget::create_object_targets(
+            target,
+            &object_directory(&output_directory, &name),
+            &output_extensions.object,
+        );
+
+        let mut statements = format!("# Target \"{}\"\n", name);
+        let compile_flags = escape_variable(&commands.compile.flags.join());
+        for object_target in &object_targets {
+            let extra_files = object_target
+                .extra_files
+                .iter()
+                .map(|extra_file| format!(" {}", escape_path(extra_file)))
+                .collect::<String>();
+            let extra_files = if extra_files.is_empty() {
+                extra_files
+            } else {
+                format!(" |{}", extra_files)
+            };
+            statements.push_str(&format!(
+                "build {object}: compile {source}{extra_files}\n  \
+                   compiler = {compiler}\n  \
+                   flags = {flags}\n  \
+                   target = {name}\n",
+                object = escape_path(&object_target.object),
+                source = escape_path(&object_target.source),
+                compiler = escape_path(&commands.compile.program),
+                flags = compile_flags,
+            ));
+        }
+
+        let objects = object_targets
+            .iter()
+            .map(|object_target| escape_path(&object_target.object))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let mut implicit_inputs = link_prerequisites(target, output_extensions);
+        implicit_inputs.extend(
+            borrowed_target
+                .version_script
+                .iter()
+                .chain(&borrowed_target.extra_files)
+                .map(|file| escape_path(file)),
+        );
+        let implicit_inputs = if implicit_inputs.is_empty() {
+            String::new()
+        } else {
+            format!(" | {}", implicit_inputs.join(" "))
+        };
+        let output = match borrowed_target.target_type {
+            TargetType::Executable(_) => output_extensions.executable_file_name(&name),
+            TargetType::Library(ref library) => output_extensions.library_file_name(library),
+        };
+        match (borrowed_target.library_type(), commands.link.as_ref()) {
+            (Some(LibraryType::Static), _) | (_, None) => statements.push_str(&format!(
+                "build {output}: archive {objects}\n  \
+                   target = {name}\n",
+                output = escape_path(Path::new(&output)),
+            )),
+            (_, Some(link)) => statements.push_str(&format!(
+                "build {output}: link {objects}{implicit_inputs}\n  \
+                   linker = {linker}\n  \
+                   flags = {flags}\n  \
+                   target = {name}\n{pool}",
+                output = escape_path(Path::new(&output)),
+                linker = escape_path(&link.program),
+                flags = escape_variable(&link.flags.join()),
+                pool = if self.serialize_lto_links && commands.uses_lto() {
+                    format!("  pool = {}\n", LTO_LINK_POOL_NAME)
+                } else {
+                    String::new()
+                },
+            )),
+        }
+        if output != name {
+            statements.push_str(&format!(
+                "build {}: phony {}\n",
+                escape_path(Path::new(&name)),
+                escape_path(Path::new(&output))
+            ));
+        }
+        statements.push('\n');
+        (statements, output)
+    }
+
+    fn rules(&self) -> String {
+        let archiver = escape_path(&self.toolchain.borrow().archiver.path);
+        let mut rules = format!(
+            "# Generated by yambs. Changes are overwritten by the next build.\n\
+             ninja_required_version = 1.3\n\n\
+             rule compile\n  \
+               command = $compiler $flags -MF $out.d -c $in -o $out && echo {compiled}\n  \
+               depfile = $out.d\n  \
+               deps = gcc\n  \
+               description = {compile}\n\n\
+             rule link\n  \
+               command = $linker $in $flags -o $out && echo {linked}\n  \
+               description = {link}\n\n\
+             rule archive\n  \
+               command = rm -f $out && {archiver} rs $out $in && echo {linked}\n  \
+               description = {link}\n\n",
+            compile = StepEvent::marker(Step::Compile, "$target", "$out"),
+            link = StepEvent::marker(Step::Link, "$target", "$out"),
+            // Printed by the command itself, as ninja has no description of finished steps.
+            compiled = StepEvent::finished_marker(Step::Compile, "$target", "$out"),
+            linked = StepEvent::finished_marker(Step::Link, "$target", "$out"),
+        );
+        if self.serialize_lto_links {
+            rules.push_str(&format!("pool {}\n  depth = 1\n\n", LTO_LINK_POOL_NAME));
+        }
+        rules
+    }
+}
+
+impl Generator for NinjaGenerator {
+    fn generate(&mut self, registry: &TargetRegistry) -> Result<PathBuf, GeneratorError> {
+        for target in &registry.registry {
+            if let Some(feature) = self.commands.feature_needing_recipes(target) {
+                return Err(GeneratorError::UnsupportedByNinja(
+                    feature,
+                    target.borrow().name(),
+                ));
+            }
+        }
+        let output_directory = self.output_directory();
+        utility::create_dir(&output_directory)?;
+        let commands = self.commands.annotated_commands(registry);
+        let object_extension = self.toolchain.borrow().output_extensions.object.clone();
+
+        let mut build_file = self.rules();
+        let mut progress_document = ProgressDocument::new();
+        let mut all = Vec::new();
+        let mut examples = Vec::new();
+        for (target, commands) in registry.registry.iter().zip(&commands) {
+            let (statements, output) = self.target_statements(target, commands);
+            build_file.push_str(&statements);
+            let borrowed_target = target.borrow();
+            let mut progress_target = ProgressTrackingTarget::from_target(
+                target,
+                &object_directory(&output_directory, &borrowed_target.name()),
+                &object_extension,
+            );
+            progress_target.output = Some(output_directory.join(&output));
+            progress_document.add_progress_tracking_target(progress_target);
+            if borrowed_target.example {
+                examples.push(borrowed_target.name());
+            } else {
+                all.push(borrowed_target.name());
+            }
+        }
+        for (phony, targets) in [("all", &all), (EXAMPLES_TARGET_NAME, &examples)] {
+            build_file.push_str(&format!(
+                "build {}: phony {}\n",
+                phony,
+                targets
+                    .iter()
+                    .map(|target| escape_path(Path::new(target)))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ));
+            progress_document.add_progress_tracking_target(ProgressTrackingTarget {
+                target: phony.to_string(),
+                object_files: Vec::new(),
+                dependencies: targets.to_vec(),
+                output: None,
+                packages: Vec::new(),
+            });
+        }
+        build_file.push_str("\ndefault all\n");
+
+        utility::write_file_atomically(
+            &output_directory.join(NINJA_BUILD_FILE_NAME),
+            build_file.as_bytes(),
+        )?;
+        let progress_document = serde_json::to_string_pretty(&progress_document).unwrap();
+        utility::write_file_atomically(
+            &output_directory.join(progress::PROGRESS_FILE_NAME),
+            progress_document.as_bytes(),
+        )?;
+        Ok(output_directory)
+    }
+
+    fn annotated_commands(&self, registry: &TargetRegistry) -> Vec<TargetCommands> {
+        self.commands.annotated_commands(registry)
+    }
+
+    fn hardened_targets(&self, registry: &TargetRegistry) -> Vec<(String, Hardening)> {
+        self.commands.hardened_targets(registry)
+    }
+}
+
+// Objects of each target are kept apart, as targets may compile sources of the same name.
+fn object_directory(output_directory: &Path, target: &str) -> PathBuf {
+    output_directory.join(format!("{}.dir", target))
+}
+
+// Libraries `target` links, which it is linked again after changes to. They are given to the
+// linker by the flags of its link command.
+fn link_prerequisites(
+    target: &TargetNode,
+    output_extensions: &crate::toolchain::OutputExtensions,
+) -> Vec<String> {
+    let mut prerequisites = Vec::new();
+    for dependency in &target.borrow().dependencies {
+        match dependency.source {
+            DependencySource::FromSource(ref s) => prerequisites.push(escape_path(Path::new(
+                &output_extensions.link_file_name(&s.library),
+            ))),
+            DependencySource::FromPkgConfig(ref pkg) => {
+                if let ProvideMethod::Finegrained(ref libs) = pkg.method {
+                    prerequisites.extend(libs.iter().map(|lib| escape_path(&lib.path())));
+                }
+            }
+            DependencySource::FromCMake(ref cmake) => {
+                prerequisites.extend(cmake.libraries.iter().map(|library| escape_path(library)))
+            }
+            DependencySource::FromYambsBuild(ref yambs_build) => prerequisites.extend(
+                yambs_build
+                    .libraries
+                    .iter()
+                    .map(|library| escape_path(library)),
+            ),
+            DependencySource::FromHeaderOnly(_) => {}
+        }
+    }
+    prerequisites
+}
+
+// Paths of build statements have spaces and colons escaped as well, as they separate paths.
+fn escape_path(path: &Path) -> String {
+    escape_variable(&path.display().to_string())
+        .replace(' ', "$ ")
+        .replace(':', "$:")
+}
+
+fn escape_variable(value: &str) -> String {
+    value.replace('$', "$$")
+}
+
+/// Runs ninja in the current directory. The status ninja prints for each step is left out, so
+/// that it only prints the descriptions of the steps, which are the markers of progress::StepEvent.
+/// The output of the commands is printed to stdout by ninja.
+#[derive(Debug)]
+pub struct Ninja {
+    args: Vec<String>,
+    executable: PathBuf,
+    environment: Environment,
+}
+
+impl Ninja {
+    pub fn new(args: &[String]) -> Result<Self, FsError> {
+        let executable = ["ninja", "ninja-build"]
+            .iter()
+            .find_map(|program| {
+                let mut search_options = crate::FindProgramOptions::new();
+                search_options.with_path_env();
+                crate::find_program(Path::new(program), search_options)
+            })
+            .ok_or_else(|| FsError::CouldNotFindProgram("ninja".to_string()))?;
+        Ok(Self {
+            args: args.to_vec(),
+            executable,
+            environment: Environment::new().with(
+                "NINJA_STATUS",
+                std::ffi::OsStr::new(""),
+                ModifyMode::Set,
+            ),
+        })
+    }
+
+    pub fn run(&self) -> Result<BuildProcess, FsError> {
+        let child = self
+            .environment
+            .command(&self.executable)
+            .args(&self.args)
+            .stderr(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|_| {
+                FsError::Spawn(Box::new(std::process::Command::new(
+                    self.executable.display().to_string(),
+                )))
+            })?;
+        Ok(BuildProcess::new(child).with_diagnostics_on_stdout())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_of_build_statements_are_escaped() {
+        assert_eq!(
+            escape_path(Path::new("/my project/$dir/c:main.o")),
+            "/my$ project/$$dir/c$:main.o"
+        );
+        assert_eq!(escape_variable("-DPRICE=$5 -I/a b"), "-DPRICE=$$5 -I/a b");
+    }
+}
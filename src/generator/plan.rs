@@ -0,0 +1,181 @@
+// The build plan of a build tree: the steps building each target, with the commands the Makefile
+// generator puts in its build files. The plan is computed along with the Makefiles and written
+// next to them, so that the internal backend can build the same objects and outputs as make, and a
+// build tree can be built by either of them.
+
+use std::path::{Path, PathBuf};
+
+use crate::generator::EXAMPLES_TARGET_NAME;
+
+pub const PLAN_FILE_NAME: &str = "plan.json";
+
+#[derive(Debug, thiserror::Error)]
+#[error("No target \"{0}\" in the build plan. Targets are: {}", .1.join(", "))]
+pub struct UnknownPlannedTarget(String, Vec<String>);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BuildPlan {
+    pub targets: Vec<PlannedTarget>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PlannedTarget {
+    pub target: String,
+    /// Whether the target is an example, which `all` leaves out.
+    #[serde(default)]
+    pub example: bool,
+    /// Targets of the plan the target links, which are built before it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    /// Sources compiled by the target. Objects compiled by another target from the same source
+    /// with the same flags are left out, and linked from where that target compiles them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compiles: Vec<PlannedCompile>,
+    pub link: PlannedLink,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PlannedCompile {
+    pub source: PathBuf,
+    pub object: PathBuf,
+    /// Files besides the source and the headers it includes that the object is compiled again
+    /// after changes to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_files: Vec<PathBuf>,
+    /// Command compiling the object, run by the shell in the directory of the plan.
+    pub command: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PlannedLink {
+    pub output: PathBuf,
+    /// Files the output is made from, which it is made again after changes to: the objects, the
+    /// libraries it links and the extra files of its target.
+    pub inputs: Vec<PathBuf>,
+    /// Command linking or archiving the output, run by the shell in the directory of the plan.
+    pub command: String,
+    /// Whether the link is run while no other serialized link is, as for targets using link-time
+    /// optimization within a memory budget.
+    #[serde(default)]
+    pub serialized: bool,
+}
+
+impl BuildPlan {
+    /// Reads the build plan of the build files in `directory`.
+    pub fn from_directory(directory: &Path) -> std::io::Result<Self> {
+        let fh = std::fs::File::open(directory.join(PLAN_FILE_NAME))?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(fh))?)
+    }
+
+    /// Targets built for the make targets `names`, along with the targets they link, with each
+    /// target after those it links. `all` names the targets that are not examples, and
+    /// `examples` the examples. No names build `all`, as make does.
+    pub fn targets_to_build(
+        &self,
+        names: &[String],
+    ) -> Result<Vec<&PlannedTarget>, UnknownPlannedTarget> {
+        let default = ["all".to_string()];
+        let names = if names.is_empty() {
+            &default[..]
+        } else {
+            names
+        };
+        let mut requested = Vec::new();
+        for name in names {
+            match name.as_str() {
+                "all" => requested.extend(self.targets.iter().filter(|target| !target.example)),
+                EXAMPLES_TARGET_NAME => {
+                    requested.extend(self.targets.iter().filter(|target| target.example))
+                }
+                _ => requested.push(self.find(name).ok_or_else(|| {
+                    UnknownPlannedTarget(
+                        name.clone(),
+                        self.targets
+                            .iter()
+                            .map(|target| target.target.clone())
+                            .collect(),
+                    )
+                })?),
+            }
+        }
+        let mut ordered = Vec::new();
+        for target in requested {
+            self.push_with_dependencies(target, &mut ordered);
+        }
+        Ok(ordered)
+    }
+
+    fn find(&self, name: &str) -> Option<&PlannedTarget> {
+        self.targets.iter().find(|target| target.target == name)
+    }
+
+    fn push_with_dependencies<'plan>(
+        &'plan self,
+        target: &'plan PlannedTarget,
+        ordered: &mut Vec<&'plan PlannedTarget>,
+    ) {
+        if ordered.iter().any(|pushed| pushed.target == target.target) {
+            return;
+        }
+        for dependency in target
+            .dependencies
+            .iter()
+            .filter_map(|name| self.find(name))
+        {
+            self.push_with_dependencies(dependency, ordered);
+        }
+        ordered.push(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn planned_target(name: &str, example: bool, dependencies: &[&str]) -> PlannedTarget {
+        PlannedTarget {
+            target: name.to_string(),
+            example,
+            dependencies: dependencies.iter().map(|name| name.to_string()).collect(),
+            compiles: Vec::new(),
+            link: PlannedLink {
+                output: PathBuf::from(name),
+                inputs: Vec::new(),
+                command: String::new(),
+                serialized: false,
+            },
+        }
+    }
+
+    #[test]
+    fn targets_are_built_after_the_targets_they_link() {
+        let plan = BuildPlan {
+            targets: vec![
+                planned_target("app", false, &["net"]),
+                planned_target("net", false, &["core"]),
+                planned_target("core", false, &[]),
+                planned_target("demo", true, &["net"]),
+            ],
+        };
+        let names = |targets: Vec<&PlannedTarget>| {
+            targets
+                .iter()
+                .map(|target| target.target.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            names(plan.targets_to_build(&[]).unwrap()),
+            vec!["core", "net", "app"]
+        );
+        assert_eq!(
+            names(plan.targets_to_build(&["examples".to_string()]).unwrap()),
+            vec!["core", "net", "demo"]
+        );
+        assert_eq!(
+            names(plan.targets_to_build(&["net".to_string()]).unwrap()),
+            vec!["core", "net"]
+        );
+        assert!(plan.targets_to_build(&["ap".to_string()]).is_err());
+    }
+}
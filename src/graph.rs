@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+
+use crate::generator::targets::ProgressDocument;
+use crate::timings::CompileTimings;
+
+/// File the graph is written to in the build files directory, unless told otherwise.
+pub const GRAPH_FILE_NAME: &str = "graph.dot";
+
+/// Number of targets listed in the report of the slowest targets.
+pub const BOTTLENECK_REPORT_LENGTH: usize = 5;
+
+// Pseudo target of the progress document depending on every target.
+const ALL_TARGET_NAME: &str = "all";
+
+/// A target of the dependency graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+    pub name: String,
+    pub dependencies: Vec<String>,
+    /// External packages the target depends on.
+    pub packages: Vec<String>,
+    /// Recorded duration of compiling the objects of the target and linking it, in milliseconds.
+    /// None if nothing has been recorded for the target.
+    pub duration: Option<u64>,
+}
+
+/// Dependency graph of the targets in a build tree, as described by its progress document.
+#[derive(Debug)]
+pub struct BuildGraph {
+    nodes: Vec<GraphNode>,
+}
+
+impl BuildGraph {
+    pub fn new(document: &ProgressDocument, timings: &CompileTimings) -> Self {
+        let nodes = document
+            .targets
+            .iter()
+            .filter(|target| target.target != ALL_TARGET_NAME)
+            .map(|target| {
+                let durations = target
+                    .object_files
+                    .iter()
+                    .chain(target.output.iter())
+                    .filter_map(|file| timings.duration(file))
+                    .collect::<Vec<u64>>();
+                GraphNode {
+                    name: target.target.clone(),
+                    dependencies: target.dependencies.clone(),
+                    packages: target.packages.clone(),
+                    duration: if durations.is_empty() {
+                        None
+                    } else {
+                        Some(durations.iter().sum())
+                    },
+                }
+            })
+            .collect::<Vec<GraphNode>>();
+        Self { nodes }
+    }
+
+    pub fn has_durations(&self) -> bool {
+        self.nodes.iter().any(|node| node.duration.is_some())
+    }
+
+    /// Chain of dependent targets with the longest total duration, in the order they are built.
+    /// Targets on it cannot start before the previous one is done, no matter how many jobs are
+    /// run, so it bounds the duration of the whole build.
+    pub fn critical_path(&self) -> Vec<&GraphNode> {
+        let mut path_durations = HashMap::new();
+        for node in &self.nodes {
+            self.path_duration(node, &mut path_durations, &mut Vec::new());
+        }
+
+        let mut path = Vec::new();
+        let mut next = self
+            .nodes
+            .iter()
+            .max_by_key(|node| path_durations.get(node.name.as_str()).copied());
+        while let Some(node) = next {
+            path.push(node);
+            next = node
+                .dependencies
+                .iter()
+                .filter_map(|dependency| self.node(dependency))
+                .max_by_key(|dependency| path_durations.get(dependency.name.as_str()).copied());
+        }
+        path.reverse();
+        path
+    }
+
+    /// Targets with the longest durations, slowest first.
+    pub fn bottlenecks(&self, count: usize) -> Vec<&GraphNode> {
+        let mut nodes = self
+            .nodes
+            .iter()
+            .filter(|node| node.duration.is_some())
+            .collect::<Vec<&GraphNode>>();
+        nodes.sort_by_key(|node| std::cmp::Reverse(node.duration));
+        nodes.truncate(count);
+        nodes
+    }
+
+    /// Graph in the DOT language of Graphviz, with edges from targets to their dependencies.
+    /// Given a critical path, nodes are labeled with their durations and the path is highlighted.
+    pub fn to_dot(&self, critical_path: Option<&[&GraphNode]>) -> String {
+        let on_critical_path = |name: &str| {
+            critical_path.is_some_and(|path| path.iter().any(|node| node.name == name))
+        };
+
+        let mut dot = String::from("digraph yambs {\n    node [shape=box];\n");
+        for node in &self.nodes {
+            let mut attributes = Vec::new();
+            if critical_path.is_some() {
+                let duration = node
+                    .duration
+                    .map(format_duration)
+                    .unwrap_or_else(|| "no recorded duration".to_string());
+                attributes.push(format!("label=\"{}\\n{}\"", node.name, duration));
+            }
+            if on_critical_path(&node.name) {
+                attributes.push("color=red".to_string());
+                attributes.push("penwidth=2".to_string());
+            }
+            dot.push_str(&format!("    \"{}\"", node.name));
+            if !attributes.is_empty() {
+                dot.push_str(&format!(" [{}]", attributes.join(", ")));
+            }
+            dot.push_str(";\n");
+        }
+        for node in &self.nodes {
+            for dependency in &node.dependencies {
+                dot.push_str(&format!("    \"{}\" -> \"{}\"", node.name, dependency));
+                let is_critical_edge = critical_path.is_some_and(|path| {
+                    path.windows(2)
+                        .any(|pair| pair[0].name == *dependency && pair[1].name == node.name)
+                });
+                if is_critical_edge {
+                    dot.push_str(" [color=red, penwidth=2]");
+                }
+                dot.push_str(";\n");
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Whether `name` is a target of the graph or an external package of one.
+    pub fn contains(&self, name: &str) -> bool {
+        self.nodes
+            .iter()
+            .any(|node| node.name == name || node.packages.iter().any(|package| package == name))
+    }
+
+    /// Every chain of dependencies from a target no other target depends on to `name`, which is
+    /// either a target or an external package. A target nothing depends on is a chain of its own.
+    pub fn paths_to(&self, name: &str) -> Vec<Vec<&str>> {
+        let mut paths = Vec::new();
+        for root in self.nodes.iter().filter(|node| {
+            !self
+                .nodes
+                .iter()
+                .any(|other| other.dependencies.contains(&node.name))
+        }) {
+            self.collect_paths(root, name, &mut Vec::new(), &mut paths);
+        }
+        paths
+    }
+
+    fn node(&self, name: &str) -> Option<&GraphNode> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    // Chains of dependencies from `path`, ending in `node`, to `name`. Targets already on the path
+    // are skipped, so that a cycle cannot recurse forever.
+    fn collect_paths<'graph>(
+        &'graph self,
+        node: &'graph GraphNode,
+        name: &str,
+        path: &mut Vec<&'graph str>,
+        paths: &mut Vec<Vec<&'graph str>>,
+    ) {
+        if path.contains(&node.name.as_str()) {
+            return;
+        }
+        path.push(&node.name);
+        if node.name == name {
+            paths.push(path.clone());
+        } else {
+            if let Some(package) = node.packages.iter().find(|package| *package == name) {
+                let mut package_path = path.clone();
+                package_path.push(package);
+                paths.push(package_path);
+            }
+            for dependency in &node.dependencies {
+                if let Some(dependency) = self.node(dependency) {
+                    self.collect_paths(dependency, name, path, paths);
+                }
+            }
+        }
+        path.pop();
+    }
+
+    // Total duration of the longest chain of dependencies ending in `node`. Dependencies being
+    // visited are skipped, so that a cycle cannot recurse forever.
+    fn path_duration<'graph>(
+        &'graph self,
+        node: &'graph GraphNode,
+        path_durations: &mut HashMap<&'graph str, u64>,
+        visiting: &mut Vec<&'graph str>,
+    ) -> u64 {
+        if let Some(duration) = path_durations.get(node.name.as_str()) {
+            return *duration;
+        }
+        visiting.push(&node.name);
+        let mut longest_dependency_path = 0;
+        for dependency in &node.dependencies {
+            if visiting.contains(&dependency.as_str()) {
+                continue;
+            }
+            if let Some(dependency) = self.node(dependency) {
+                longest_dependency_path = longest_dependency_path.max(self.path_duration(
+                    dependency,
+                    path_durations,
+                    visiting,
+                ));
+            }
+        }
+        visiting.pop();
+        let duration = node.duration.unwrap_or(0) + longest_dependency_path;
+        path_durations.insert(&node.name, duration);
+        duration
+    }
+}
+
+pub fn format_duration(milliseconds: u64) -> String {
+    format!("{:.2} s", milliseconds as f64 / 1000.0)
+}
+
+/// Chains of dependencies as a tree, with the beginnings chains have in common merged.
+pub fn format_paths_as_tree(paths: &[Vec<&str>]) -> String {
+    let paths = paths
+        .iter()
+        .map(|path| path.as_slice())
+        .collect::<Vec<&[&str]>>();
+    let mut tree = String::new();
+    for (name, rests) in group_by_first(&paths) {
+        tree.push_str(name);
+        tree.push('\n');
+        format_subtrees(&rests, "", &mut tree);
+    }
+    tree
+}
+
+/// Chains of dependencies to `name` in the DOT language of Graphviz, with `name` highlighted.
+pub fn paths_to_dot(name: &str, paths: &[Vec<&str>]) -> String {
+    let mut dot = format!(
+        "digraph yambs {{\n    node [shape=box];\n    \"{}\" [color=red, penwidth=2];\n",
+        name
+    );
+    let mut edges = Vec::new();
+    for path in paths {
+        for pair in path.windows(2) {
+            if !edges.contains(&(pair[0], pair[1])) {
+                edges.push((pair[0], pair[1]));
+            }
+        }
+    }
+    for (from, to) in edges {
+        dot.push_str(&format!("    \"{}\" -> \"{}\";\n", from, to));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+// Paths grouped by their first element, in the order the elements first appear, along with the
+// rest of each path.
+fn group_by_first<'path, 'name>(
+    paths: &[&'path [&'name str]],
+) -> Vec<(&'name str, Vec<&'path [&'name str]>)> {
+    let mut groups = Vec::<(&str, Vec<&[&str]>)>::new();
+    for path in paths {
+        if let Some((first, rest)) = path.split_first() {
+            match groups.iter_mut().find(|(name, _)| name == first) {
+                Some((_, rests)) => rests.push(rest),
+                None => groups.push((first, vec![rest])),
+            }
+        }
+    }
+    groups
+}
+
+fn format_subtrees(paths: &[&[&str]], indent: &str, tree: &mut String) {
+    let groups = group_by_first(paths);
+    for (i, (name, rests)) in groups.iter().enumerate() {
+        let is_last = i == groups.len() - 1;
+        tree.push_str(indent);
+        tree.push_str(if is_last { "└── " } else { "├── " });
+        tree.push_str(name);
+        tree.push('\n');
+        let indent = format!("{}{}", indent, if is_last { "    " } else { "│   " });
+        format_subtrees(rests, &indent, tree);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::targets::ProgressTrackingTarget;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn target(name: &str, dependencies: &[&str]) -> ProgressTrackingTarget {
+        ProgressTrackingTarget {
+            target: name.to_string(),
+            object_files: vec![PathBuf::from(format!("/build/{}.o", name))],
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            output: Some(PathBuf::from(format!("/build/{}", name))),
+            packages: Vec::new(),
+        }
+    }
+
+    fn graph() -> BuildGraph {
+        let document = ProgressDocument {
+            targets: vec![
+                target("app", &["fast", "slow"]),
+                target("fast", &[]),
+                target("slow", &["base"]),
+                target("base", &[]),
+                ProgressTrackingTarget {
+                    target: "all".to_string(),
+                    object_files: Vec::new(),
+                    dependencies: vec!["app".to_string()],
+                    output: None,
+                    packages: Vec::new(),
+                },
+            ],
+        };
+        let mut timings = CompileTimings::new();
+        timings.merge_log(
+            "/build/app.o 100\n/build/app 50\n/build/fast.o 300\n\
+             /build/slow.o 200\n/build/slow 10\n/build/base.o 150\n",
+        );
+        BuildGraph::new(&document, &timings)
+    }
+
+    #[test]
+    fn critical_path_is_the_slowest_chain_of_dependencies() {
+        let graph = graph();
+        let path = graph
+            .critical_path()
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(path, vec!["base", "slow", "app"]);
+    }
+
+    #[test]
+    fn bottlenecks_are_sorted_by_duration() {
+        let graph = graph();
+        let bottlenecks = graph
+            .bottlenecks(2)
+            .iter()
+            .map(|node| (node.name.as_str(), node.duration))
+            .collect::<Vec<(&str, Option<u64>)>>();
+        assert_eq!(bottlenecks, vec![("fast", Some(300)), ("slow", Some(210))]);
+    }
+
+    #[test]
+    fn dot_highlights_critical_path() {
+        let graph = graph();
+        let critical_path = graph.critical_path();
+        let dot = graph.to_dot(Some(&critical_path));
+        assert!(dot.contains("\"slow\" [label=\"slow\\n0.21 s\", color=red, penwidth=2];"));
+        assert!(dot.contains("\"fast\" [label=\"fast\\n0.30 s\"];"));
+        assert!(dot.contains("\"app\" -> \"slow\" [color=red, penwidth=2];"));
+        assert!(dot.contains("\"app\" -> \"fast\";"));
+        assert!(!dot.contains("\"all\""));
+    }
+
+    #[test]
+    fn paths_to_target_and_package_are_formatted_as_tree() {
+        let mut zlib_users = target("net", &["base"]);
+        zlib_users.packages.push("zlib".to_string());
+        let mut base = target("base", &[]);
+        base.packages.push("zlib".to_string());
+        let document = ProgressDocument {
+            targets: vec![
+                target("app", &["net", "util"]),
+                target("tool", &["base"]),
+                zlib_users,
+                target("util", &["base"]),
+                base,
+            ],
+        };
+        let graph = BuildGraph::new(&document, &CompileTimings::new());
+
+        let paths = graph.paths_to("base");
+        assert_eq!(
+            paths,
+            vec![
+                vec!["app", "net", "base"],
+                vec!["app", "util", "base"],
+                vec!["tool", "base"],
+            ]
+        );
+        assert_eq!(
+            format_paths_as_tree(&graph.paths_to("zlib")),
+            "\
+app
+├── net
+│   ├── zlib
+│   └── base
+│       └── zlib
+└── util
+    └── base
+        └── zlib
+tool
+└── base
+    └── zlib
+"
+        );
+        assert!(graph.contains("zlib"));
+        assert!(!graph.contains("openssl"));
+    }
+}
@@ -0,0 +1,175 @@
+/// Protection a hardened target is built with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HardeningOption {
+    StackProtector,
+    FortifySource,
+    PositionIndependentExecutable,
+    Relro,
+    NoExecStack,
+}
+
+impl std::fmt::Display for HardeningOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::StackProtector => "stack protector",
+            Self::FortifySource => "_FORTIFY_SOURCE=2",
+            Self::PositionIndependentExecutable => "position independent executable",
+            Self::Relro => "full RELRO",
+            Self::NoExecStack => "non-executable stack",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What a hardened target produces, which decides the options that apply to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HardenedOutput {
+    Executable,
+    SharedLibrary,
+    /// Static libraries are not linked, so only the options of compiling apply.
+    StaticLibrary,
+}
+
+/// Hardening options of a target, along with those left out and why. GCC and Clang take the same
+/// flags. The linker of Apple platforms makes position independent executables with a
+/// non-executable stack by default, and has no RELRO.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hardening {
+    pub applied: Vec<HardeningOption>,
+    pub skipped: Vec<(HardeningOption, &'static str)>,
+}
+
+impl Hardening {
+    pub fn new(output: HardenedOutput, optimized: bool, apple_linker: bool) -> Self {
+        let mut applied = vec![HardeningOption::StackProtector];
+        let mut skipped = Vec::new();
+        if optimized {
+            applied.push(HardeningOption::FortifySource);
+        } else {
+            skipped.push((
+                HardeningOption::FortifySource,
+                "it needs the sources to be compiled with optimization",
+            ));
+        }
+        let linked_options: &[HardeningOption] = match output {
+            HardenedOutput::Executable => &[
+                HardeningOption::PositionIndependentExecutable,
+                HardeningOption::Relro,
+                HardeningOption::NoExecStack,
+            ],
+            HardenedOutput::SharedLibrary => {
+                &[HardeningOption::Relro, HardeningOption::NoExecStack]
+            }
+            HardenedOutput::StaticLibrary => &[],
+        };
+        for option in linked_options {
+            match option {
+                HardeningOption::Relro if apple_linker => {
+                    skipped.push((*option, "the linker of Apple platforms has no RELRO"))
+                }
+                HardeningOption::PositionIndependentExecutable | HardeningOption::NoExecStack
+                    if apple_linker =>
+                {
+                    skipped.push((*option, "it is the default of Apple platforms"))
+                }
+                _ => applied.push(*option),
+            }
+        }
+        Self { applied, skipped }
+    }
+
+    /// Flags given to the compiler along with CXXFLAGS or CFLAGS. Objects of executables are
+    /// compiled with -fPIE instead of the -fPIC of other objects.
+    pub fn language_flags(&self) -> Vec<&'static str> {
+        let mut flags = Vec::new();
+        if self.applies(HardeningOption::StackProtector) {
+            flags.push("-fstack-protector-strong");
+        }
+        if self.applies(HardeningOption::PositionIndependentExecutable) {
+            flags.push("-fPIE");
+        }
+        flags
+    }
+
+    /// Flags given to the preprocessor. _FORTIFY_SOURCE is undefined first, as some compilers
+    /// define it by default, and defining it again would be warned about.
+    pub fn cpp_flags(&self) -> Vec<&'static str> {
+        if self.applies(HardeningOption::FortifySource) {
+            vec!["-U_FORTIFY_SOURCE", "-D_FORTIFY_SOURCE=2"]
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn link_flags(&self) -> Vec<&'static str> {
+        let mut flags = Vec::new();
+        if self.applies(HardeningOption::PositionIndependentExecutable) {
+            flags.push("-pie");
+        }
+        if self.applies(HardeningOption::Relro) {
+            flags.extend(["-Wl,-z,relro", "-Wl,-z,now"]);
+        }
+        if self.applies(HardeningOption::NoExecStack) {
+            flags.push("-Wl,-z,noexecstack");
+        }
+        flags
+    }
+
+    fn applies(&self, option: HardeningOption) -> bool {
+        self.applied.contains(&option)
+    }
+}
+
+impl std::fmt::Display for Hardening {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.applied
+                .iter()
+                .map(|option| option.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )?;
+        for (option, reason) in &self.skipped {
+            write!(f, ". No {}, as {}", option, reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn hardened_executable_is_position_independent_with_read_only_relocations() {
+        let hardening = Hardening::new(HardenedOutput::Executable, true, false);
+        assert_eq!(
+            hardening.language_flags(),
+            vec!["-fstack-protector-strong", "-fPIE"]
+        );
+        assert_eq!(
+            hardening.cpp_flags(),
+            vec!["-U_FORTIFY_SOURCE", "-D_FORTIFY_SOURCE=2"]
+        );
+        assert_eq!(
+            hardening.link_flags(),
+            vec!["-pie", "-Wl,-z,relro", "-Wl,-z,now", "-Wl,-z,noexecstack"]
+        );
+    }
+
+    #[test]
+    fn fortify_source_is_left_out_without_optimization() {
+        let hardening = Hardening::new(HardenedOutput::StaticLibrary, false, false);
+        assert_eq!(hardening.applied, vec![HardeningOption::StackProtector]);
+        assert!(hardening.cpp_flags().is_empty());
+        assert!(hardening.link_flags().is_empty());
+        assert_eq!(
+            hardening.to_string(),
+            "stack protector. No _FORTIFY_SOURCE=2, as it needs the sources to be compiled with \
+             optimization"
+        );
+    }
+}
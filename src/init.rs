@@ -0,0 +1,148 @@
+// Creates the skeleton of a new project for `yambs init`: a manifest with a single target, the
+// source and include directories of the default project layout, and a source to start from.
+
+use std::path::{Path, PathBuf};
+
+use crate::errors::FsError;
+use crate::utility;
+use crate::YAMBS_MANIFEST_NAME;
+
+/// Standard set by the manifest of a new project.
+const DEFAULT_STANDARD: &str = "c++17";
+
+#[derive(Debug, thiserror::Error)]
+pub enum InitError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+    #[error("{0:?} already exists. Refusing to overwrite it.")]
+    ManifestExists(PathBuf),
+    #[error("\"{0}\" can not be used as target name. Give one of letters, digits, '_', '-' and '.' with --name")]
+    InvalidTargetName(String),
+}
+
+/// Kind of the target of a new project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectKind {
+    Executable,
+    Library,
+}
+
+/// A project to create in a directory.
+#[derive(Debug, Clone)]
+pub struct ProjectSkeleton {
+    pub name: String,
+    pub kind: ProjectKind,
+}
+
+impl ProjectSkeleton {
+    pub fn new(name: &str, kind: ProjectKind) -> Result<Self, InitError> {
+        let is_valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !is_valid {
+            return Err(InitError::InvalidTargetName(name.to_string()));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            kind,
+        })
+    }
+
+    /// Writes the project into `directory`, which must not have a manifest yet. Sources that
+    /// exist already are left as they are. Returns the files written.
+    pub fn create(&self, directory: &Path) -> Result<Vec<PathBuf>, InitError> {
+        let manifest = directory.join(YAMBS_MANIFEST_NAME);
+        if manifest.exists() {
+            return Err(InitError::ManifestExists(manifest));
+        }
+        utility::create_dir(directory.join("src"))?;
+        utility::create_dir(directory.join("include"))?;
+        let mut written = Vec::new();
+        for (file, content) in self.sources() {
+            let file = directory.join(file);
+            if !file.exists() {
+                utility::write_file_atomically(&file, content.as_bytes())?;
+                written.push(file);
+            }
+        }
+        utility::write_file_atomically(&manifest, self.manifest().as_bytes())?;
+        written.push(manifest);
+        Ok(written)
+    }
+
+    fn manifest(&self) -> String {
+        let (table, source) = match self.kind {
+            ProjectKind::Executable => ("executable", "src/main.cpp".to_string()),
+            ProjectKind::Library => ("library", format!("src/{}.cpp", self.name)),
+        };
+        // Names with dots are quoted, so that they are not taken for nested tables.
+        let key = if self.name.contains('.') {
+            format!("\"{}\"", self.name)
+        } else {
+            self.name.clone()
+        };
+        format!(
+            "[project_config]\nstd = \"{}\"\n\n[{}.{}]\nsources = [\"{}\"]\n",
+            DEFAULT_STANDARD, table, key, source
+        )
+    }
+
+    // Sources of the project, relative to its directory.
+    fn sources(&self) -> Vec<(String, String)> {
+        match self.kind {
+            ProjectKind::Executable => vec![(
+                "src/main.cpp".to_string(),
+                "#include <iostream>\n\n\
+                 int main() {\n    std::cout << \"Hello, world!\" << std::endl;\n    return 0;\n}\n"
+                    .to_string(),
+            )],
+            ProjectKind::Library => {
+                let namespace = self.name.replace(['-', '.'], "_");
+                vec![
+                    (
+                        format!("include/{}.hpp", self.name),
+                        format!(
+                            "#pragma once\n\nnamespace {} {{\nint answer();\n}}\n",
+                            namespace
+                        ),
+                    ),
+                    (
+                        format!("src/{}.cpp", self.name),
+                        format!(
+                            "#include \"{}.hpp\"\n\nnamespace {} {{\nint answer() {{ return 42; }}\n}}\n",
+                            self.name, namespace
+                        ),
+                    ),
+                ]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::RawManifestData;
+
+    #[test]
+    fn library_project_is_created_once() {
+        let directory = tempdir::TempDir::new("init").unwrap();
+        let skeleton = ProjectSkeleton::new("my-lib", ProjectKind::Library).unwrap();
+        let written = skeleton.create(directory.path()).unwrap();
+        assert_eq!(written.len(), 3);
+        let manifest = std::fs::read_to_string(directory.path().join(YAMBS_MANIFEST_NAME)).unwrap();
+        let data: RawManifestData = toml::from_str(&manifest).unwrap();
+        assert!(data.libraries.unwrap().contains_key("my-lib"));
+        assert!(directory.path().join("include/my-lib.hpp").is_file());
+
+        assert!(matches!(
+            skeleton.create(directory.path()),
+            Err(InitError::ManifestExists(_))
+        ));
+        assert!(matches!(
+            ProjectSkeleton::new("my lib", ProjectKind::Executable),
+            Err(InitError::InvalidTargetName(_))
+        ));
+    }
+}
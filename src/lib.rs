@@ -1,27 +1,53 @@
-use std::env;
-use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
 
+pub mod archive;
+pub mod artifacts;
+pub mod build_file_hashes;
+pub mod build_history;
+pub mod build_hygiene;
 pub mod build_target;
+pub mod cache;
+pub mod clean;
 pub mod cli;
 pub mod compiler;
+pub mod dependency_usage;
+pub mod docs;
+pub mod embed;
+pub mod environment;
 pub mod errors;
+pub mod exec;
 pub mod flags;
 pub mod generator;
+pub mod graph;
+pub mod hardening;
+pub mod init;
+pub mod link_errors;
 pub mod logger;
 pub mod manifest;
+pub mod memory_budget;
+pub mod mmk_parser;
 pub mod output;
 pub mod parser;
 pub mod progress;
+pub mod repro_bundle;
+pub mod sandbox;
+pub mod stale_outputs;
+pub mod system_pch;
+pub mod target_picker;
 pub mod targets;
+pub mod test_runner;
+pub mod timings;
 pub mod toolchain;
 pub mod utility;
+pub mod vscode;
+pub mod warnings_report;
 
 use once_cell::sync::OnceCell;
 
 use crate::cli::command_line::ManifestDirectory;
-use crate::cli::configurations::BuildType;
+use crate::cli::configurations::{BuildType, OptLevel, Sanitizer};
 use crate::cli::BuildDirectory;
+use crate::flags::{FlagOrigin, GeneratedWarnings, Warnings};
 use crate::generator::GeneratorType;
 use crate::parser::types::{Define, Language, Standard};
 
@@ -29,63 +55,33 @@ pub const YAMBS_MANIFEST_NAME: &str = "yambs.toml";
 pub static YAMBS_BUILD_DIR_VAR: OnceCell<BuildDirectory> = OnceCell::new();
 pub static YAMBS_MANIFEST_DIR: OnceCell<ManifestDirectory> = OnceCell::new();
 pub static YAMBS_BUILD_TYPE: OnceCell<BuildType> = OnceCell::new();
+pub static YAMBS_TARGET_TRIPLE: OnceCell<String> = OnceCell::new();
+/// Whether the tests and examples of dependency manifests are resolved and built along with their
+/// libraries, as with --with-dep-tests. They are left out unless set.
+pub static DEPENDENCY_TESTS: OnceCell<bool> = OnceCell::new();
 
 #[derive(Clone, Debug)]
 pub struct ProjectConfig {
     pub std: Standard,
+    /// Whether the standard is given on the command line or in the manifest.
+    pub std_origin: FlagOrigin,
     pub language: Language,
     pub build_directory: BuildDirectory,
     pub build_type: BuildType,
     pub generator_type: GeneratorType,
     pub defines: Vec<Define>,
-}
-
-pub enum ModifyMode {
-    Set,
-    Append,
-}
-
-#[derive(PartialEq, Eq, Debug)]
-struct EnvironmentVariable {
-    captured_val: Option<OsString>,
-    key: String,
-    val: Option<OsString>,
-}
-
-impl EnvironmentVariable {
-    pub fn new(key: &str) -> Self {
-        let captured_val = env::var_os(key);
-
-        Self {
-            captured_val: captured_val.clone(),
-            key: key.to_string(),
-            val: captured_val,
-        }
-    }
-
-    pub fn set(&mut self, value: &OsStr, mode: ModifyMode) {
-        match mode {
-            ModifyMode::Set => {
-                self.val = Some(value.to_os_string());
-                env::set_var(&self.key, value);
-            }
-            ModifyMode::Append => {
-                if let Some(ref mut val) = self.val {
-                    #[cfg(target_family = "unix")]
-                    {
-                        val.push(format!(":{}", value.to_string_lossy()));
-                    }
-                    #[cfg(target_family = "windows")]
-                    {
-                        val.push(format!(";{}", value.to_string_lossy()));
-                    }
-                } else {
-                    self.val = Some(value.to_os_string());
-                }
-                env::set_var(&self.key, self.val.as_ref().unwrap());
-            }
-        }
-    }
+    pub gc_sections: bool,
+    pub report_gc_sections: bool,
+    /// Default of `hardened` for all targets.
+    pub hardened: bool,
+    /// Default of `system_pch` for all targets.
+    pub system_pch: bool,
+    pub warnings: Warnings,
+    /// Default of `generated_warnings` for all targets.
+    pub generated_warnings: GeneratedWarnings,
+    pub sanitizers: Vec<Sanitizer>,
+    /// Optimization level given on the command line, overriding the default of the build type.
+    pub opt_level: Option<OptLevel>,
 }
 
 // FIXME: Should have check for absolute path. Perhaps better check?
@@ -107,10 +103,27 @@ lazy_static::lazy_static! {
     };
 }
 
-#[derive(Debug, Clone)]
+/// Extensions tried for a program given without one, such as `cl` for `cl.exe`.
+#[cfg(windows)]
+pub const EXECUTABLE_EXTENSIONS: &[&str] = &["exe", "bat", "cmd"];
+#[cfg(not(windows))]
+pub const EXECUTABLE_EXTENSIONS: &[&str] = &[];
+
+lazy_static::lazy_static! {
+    // Results of find_program, which may run each candidate to tell its version.
+    static ref FOUND_PROGRAMS: std::sync::Mutex<std::collections::HashMap<(PathBuf, FindProgramOptions), Option<PathBuf>>> =
+        std::sync::Mutex::new(std::collections::HashMap::new());
+    // First version number printed by a program, such as "15.0.7" of "clang version 15.0.7".
+    static ref VERSION_REGEX: regex::Regex =
+        regex::Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?").unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FindProgramOptions {
     search_directories: Vec<PathBuf>,
     look_in_subdirectories: bool,
+    extensions: Vec<String>,
+    version_requirement: Option<semver::VersionReq>,
 }
 
 impl FindProgramOptions {
@@ -118,6 +131,11 @@ impl FindProgramOptions {
         Self {
             search_directories: vec![],
             look_in_subdirectories: false,
+            extensions: EXECUTABLE_EXTENSIONS
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+            version_requirement: None,
         }
     }
 
@@ -131,22 +149,126 @@ impl FindProgramOptions {
         self
     }
 
+    /// Searches `dirs` before the directories given so far, such as the search paths of a
+    /// toolchain file ahead of PATH.
+    pub fn search_directories_first(&mut self, dirs: &[PathBuf]) -> &mut Self {
+        self.search_directories.splice(0..0, dirs.iter().cloned());
+        self
+    }
+
     pub fn look_in_subdirectories(&mut self, look_in_subdirectories: bool) -> &mut Self {
         self.look_in_subdirectories = look_in_subdirectories;
         self
     }
+
+    /// Extensions tried for a program given without one. Defaults to those of executables on
+    /// the platform.
+    pub fn extensions(&mut self, extensions: &[&str]) -> &mut Self {
+        self.extensions = extensions
+            .iter()
+            .map(|extension| extension.to_string())
+            .collect();
+        self
+    }
+
+    /// Skips programs whose version, as printed by `--version`, does not match `requirement`.
+    pub fn version_requirement(&mut self, requirement: semver::VersionReq) -> &mut Self {
+        self.version_requirement = Some(requirement);
+        self
+    }
+
+    /// Directories searched, in order.
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.search_directories
+    }
+
+    // Names `program` may have in a directory.
+    fn candidates(&self, program: &Path) -> Vec<PathBuf> {
+        let mut candidates = vec![program.to_path_buf()];
+        if program.extension().is_none() {
+            candidates.extend(
+                self.extensions
+                    .iter()
+                    .map(|extension| program.with_extension(extension)),
+            );
+        }
+        candidates
+    }
+
+    fn accepts(&self, executable_path: &Path) -> bool {
+        let requirement = match self.version_requirement {
+            Some(ref requirement) => requirement,
+            None => return true,
+        };
+        match program_version(executable_path) {
+            Some(version) if requirement.matches(&version) => true,
+            version => {
+                log::debug!(
+                    "Skipping {} of version {:?}, as it does not match {}",
+                    executable_path.display(),
+                    version.map(|version| version.to_string()),
+                    requirement
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Version of `program`, from the first version number it prints with `--version`. A version
+/// without a patch number, such as "15.0", gets patch 0.
+pub fn program_version(program: &Path) -> Option<semver::Version> {
+    let output = environment::Environment::new()
+        .command(program)
+        .arg("--version")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let captures = VERSION_REGEX.captures(&text)?;
+    let number = |index: usize| {
+        captures
+            .get(index)
+            .map_or(Some(0), |number| number.as_str().parse::<u64>().ok())
+    };
+    Some(semver::Version::new(number(1)?, number(2)?, number(3)?))
 }
 
+/// Looks `program` up in the directories of `search_options`, and in their subdirectories if
+/// asked to. Results are remembered for the rest of the run.
 pub fn find_program(
     program: &Path,
     search_options: FindProgramOptions,
 ) -> Option<std::path::PathBuf>
 where
 {
-    for dir in search_options.search_directories {
+    let key = (program.to_path_buf(), search_options);
+    if let Some(found) = FOUND_PROGRAMS.lock().unwrap().get(&key) {
+        log::debug!("Using earlier result of looking for {}", program.display());
+        return found.clone();
+    }
+    let found = search_for_program(&key.0, &key.1);
+    FOUND_PROGRAMS.lock().unwrap().insert(key, found.clone());
+    found
+}
+
+fn search_for_program(program: &Path, search_options: &FindProgramOptions) -> Option<PathBuf> {
+    let find_in = |dir: &Path| {
         log::debug!("Looking for {} in {}", program.display(), dir.display());
-        let executable_path = dir.join(program);
-        if executable_path.is_file() {
+        search_options
+            .candidates(program)
+            .into_iter()
+            .map(|candidate| dir.join(candidate))
+            .find(|executable_path| {
+                executable_path.is_file() && search_options.accepts(executable_path)
+            })
+    };
+    for dir in &search_options.search_directories {
+        if let Some(executable_path) = find_in(dir) {
             log::debug!(
                 "Found {} as {}",
                 program.display(),
@@ -155,7 +277,7 @@ where
             return Some(executable_path);
         }
         if search_options.look_in_subdirectories {
-            let read_dir = std::fs::read_dir(&dir).ok()?;
+            let read_dir = std::fs::read_dir(dir).ok()?;
             let subdirectories = read_dir
                 .into_iter()
                 .filter_map(|entry| {
@@ -170,13 +292,7 @@ where
                 .collect::<Vec<PathBuf>>();
 
             for subdirectory in subdirectories {
-                log::debug!(
-                    "Looking for {} in {}",
-                    program.display(),
-                    subdirectory.display()
-                );
-                let executable_path = subdirectory.join(program);
-                if executable_path.is_file() {
+                if let Some(executable_path) = find_in(&subdirectory) {
                     log::debug!(
                         "Found {} as {}",
                         program.display(),
@@ -192,6 +308,67 @@ where
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    // Executable script in `dir` printing `version` like a compiler.
+    #[cfg(unix)]
+    fn fake_tool(dir: &Path, name: &str, version: &str) {
+        let path = dir.join(name);
+        std::fs::write(
+            &path,
+            format!("#!/bin/sh\necho \"{} version {}\"\n", name, version),
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn find_program_skips_programs_not_matching_version_requirement() {
+        let tempdir = tempdir::TempDir::new("find_program").unwrap();
+        let old = tempdir.path().join("old");
+        let new = tempdir.path().join("new");
+        std::fs::create_dir_all(&old).unwrap();
+        std::fs::create_dir_all(&new).unwrap();
+        fake_tool(&old, "fakecc", "14.0.6");
+        fake_tool(&new, "fakecc", "15.0");
+
+        let mut search_options = FindProgramOptions::new();
+        search_options.search_directory(&old).search_directory(&new);
+        assert_eq!(
+            find_program(Path::new("fakecc"), search_options.clone()),
+            Some(old.join("fakecc"))
+        );
+        search_options.version_requirement(semver::VersionReq::parse(">=15").unwrap());
+        assert_eq!(
+            find_program(Path::new("fakecc"), search_options.clone()),
+            Some(new.join("fakecc"))
+        );
+        assert_eq!(
+            program_version(&new.join("fakecc")),
+            Some(semver::Version::new(15, 0, 0))
+        );
+        search_options.version_requirement(semver::VersionReq::parse(">=16").unwrap());
+        assert_eq!(find_program(Path::new("fakecc"), search_options), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn find_program_tries_extensions_of_programs_without_one() {
+        let tempdir = tempdir::TempDir::new("find_program").unwrap();
+        fake_tool(tempdir.path(), "tool.bat", "1.0");
+        let mut search_options = FindProgramOptions::new();
+        search_options
+            .search_directory(tempdir.path())
+            .extensions(&["exe", "bat"]);
+        assert_eq!(
+            find_program(Path::new("tool"), search_options),
+            Some(tempdir.path().join("tool.bat"))
+        );
+    }
+
     lazy_static::lazy_static! {
         static ref ENV_LOCK_MUTEX: std::sync::Mutex<()> = std::sync::Mutex::new(());
     }
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::environment::Environment;
+use crate::generator::targets::ProgressTrackingTarget;
+
+lazy_static::lazy_static! {
+    // Function GNU ld reports the undefined references following it in, such as
+    // "/usr/bin/ld: /build/debug/deps/app.dir/main.o: in function `main':".
+    static ref IN_FUNCTION: regex::Regex =
+        regex::Regex::new(r"^(?:\S*ld(?:\.\w+)?: )?(?P<object>.+?): in function `(?P<function>.+)':$").unwrap();
+    // Undefined reference reported by GNU ld, such as
+    // "main.cpp:(.text+0x10): undefined reference to `S::go()'".
+    static ref UNDEFINED_REFERENCE: regex::Regex =
+        regex::Regex::new(r"^(?:\S*ld(?:\.\w+)?: )?(?P<location>.+?): undefined reference to `(?P<symbol>.+)'$").unwrap();
+    // Undefined symbol reported by lld, such as "ld.lld: error: undefined symbol: S::go()".
+    static ref UNDEFINED_SYMBOL: regex::Regex =
+        regex::Regex::new(r"^\S*ld(?:\.\w+)?: error: undefined symbol: (?P<symbol>.+)$").unwrap();
+    // Reference lld lists below an undefined symbol, such as ">>> referenced by main.cpp:12".
+    static ref REFERENCED_BY: regex::Regex =
+        regex::Regex::new(r"^>>> referenced by (?P<location>.+)$").unwrap();
+}
+
+/// Place a symbol is referenced from.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reference {
+    /// Object file or source file the reference is in, as the linker tells.
+    pub location: String,
+    /// Function the reference is in, if the linker tells.
+    pub function: Option<String>,
+}
+
+/// Symbol the linker could not find a definition of, with every place it is referenced from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndefinedSymbol {
+    pub symbol: String,
+    pub references: Vec<Reference>,
+}
+
+/// Collects the undefined references reported by GNU ld and lld from the output of a build.
+#[derive(Debug, Default)]
+pub struct LinkErrors {
+    undefined: BTreeMap<String, Vec<Reference>>,
+    // Object and function of the last "in function" line of GNU ld.
+    current: Option<(String, String)>,
+    // Symbol of the last "undefined symbol" line of lld.
+    current_symbol: Option<String>,
+}
+
+impl LinkErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a line of the output of make. Lines that are not from a linker are ignored.
+    pub fn record(&mut self, line: &str) {
+        if let Some(captures) = IN_FUNCTION.captures(line) {
+            self.current = Some((
+                captures["object"].to_string(),
+                captures["function"].to_string(),
+            ));
+        } else if let Some(captures) = UNDEFINED_REFERENCE.captures(line) {
+            let location = captures["location"].to_string();
+            let reference = match self.current {
+                Some((ref object, ref function)) if !location.contains(".o:(") => Reference {
+                    location: object.clone(),
+                    function: Some(function.clone()),
+                },
+                _ => Reference {
+                    location: strip_section(&location).to_string(),
+                    function: None,
+                },
+            };
+            self.add(&captures["symbol"], reference);
+        } else if let Some(captures) = UNDEFINED_SYMBOL.captures(line) {
+            self.current_symbol = Some(captures["symbol"].to_string());
+        } else if let Some(captures) = REFERENCED_BY.captures(line) {
+            if let Some(symbol) = self.current_symbol.clone() {
+                let reference = Reference {
+                    location: captures["location"].to_string(),
+                    function: None,
+                };
+                self.add(&symbol, reference);
+            }
+        } else if !line.starts_with(">>>") {
+            self.current = None;
+            self.current_symbol = None;
+        }
+    }
+
+    fn add(&mut self, symbol: &str, reference: Reference) {
+        let references = self.undefined.entry(symbol.to_string()).or_default();
+        if !references.contains(&reference) {
+            references.push(reference);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.undefined.is_empty()
+    }
+
+    /// The undefined symbols, demangled with c++filt when the linker left them mangled, in the
+    /// order of their names.
+    pub fn undefined_symbols(&self) -> Vec<UndefinedSymbol> {
+        let mangled = self
+            .undefined
+            .keys()
+            .filter(|symbol| symbol.starts_with("_Z"))
+            .cloned()
+            .collect::<Vec<String>>();
+        let demangled = demangle(&mangled);
+        let mut symbols = self
+            .undefined
+            .iter()
+            .map(|(symbol, references)| UndefinedSymbol {
+                symbol: demangled.get(symbol).unwrap_or(symbol).clone(),
+                references: references.clone(),
+            })
+            .collect::<Vec<UndefinedSymbol>>();
+        symbols.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        symbols
+    }
+}
+
+// "main.cpp:(.text+0x1a)" is "main.cpp".
+fn strip_section(location: &str) -> &str {
+    location
+        .find(":(")
+        .map_or(location, |index| &location[..index])
+}
+
+// Demangled names of `symbols` by c++filt. Symbols are left out if c++filt can not be run.
+fn demangle(symbols: &[String]) -> BTreeMap<String, String> {
+    if symbols.is_empty() {
+        return BTreeMap::new();
+    }
+    match Environment::new()
+        .command(Path::new("c++filt"))
+        .args(symbols)
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            let demangled = String::from_utf8_lossy(&output.stdout);
+            symbols
+                .iter()
+                .cloned()
+                .zip(demangled.lines().map(|line| line.to_string()))
+                .collect()
+        }
+        _ => {
+            log::debug!("Could not run c++filt. Undefined symbols are left mangled");
+            BTreeMap::new()
+        }
+    }
+}
+
+/// Library built by a target, with the symbols it defines.
+#[derive(Debug)]
+pub struct LibraryDefinitions {
+    pub target: String,
+    pub library: PathBuf,
+    symbols: std::collections::HashSet<String>,
+}
+
+impl LibraryDefinitions {
+    pub fn new(target: &str, library: &Path, symbols: std::collections::HashSet<String>) -> Self {
+        Self {
+            target: target.to_string(),
+            library: library.to_path_buf(),
+            symbols,
+        }
+    }
+
+    pub fn defines(&self, symbol: &str) -> bool {
+        self.symbols.contains(symbol)
+    }
+}
+
+/// Symbols defined by the libraries built by `targets`, read with nm. Libraries that have not
+/// been built, or that nm can not read, are left out.
+pub fn library_definitions(targets: &[ProgressTrackingTarget]) -> Vec<LibraryDefinitions> {
+    targets
+        .iter()
+        .filter_map(|target| Some((target, target.output.as_ref()?)))
+        .filter(|(_, library)| is_library(library) && library.is_file())
+        .filter_map(|(target, library)| {
+            let output = Environment::new()
+                .command(Path::new("nm"))
+                .args(["-C", "--defined-only"])
+                .arg(library)
+                .output()
+                .ok()
+                .filter(|output| output.status.success());
+            let Some(output) = output else {
+                log::debug!(
+                    "Could not read the symbols of {} with nm",
+                    library.display()
+                );
+                return None;
+            };
+            Some(LibraryDefinitions::new(
+                &target.target,
+                library,
+                parse_nm_output(&String::from_utf8_lossy(&output.stdout)),
+            ))
+        })
+        .collect()
+}
+
+fn is_library(path: &Path) -> bool {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy())
+        .unwrap_or_default();
+    file_name.ends_with(".a") || file_name.ends_with(".so") || file_name.contains(".so.")
+}
+
+// Global symbols of lines such as "0000000000000000 T n::missing(int)", as printed by
+// `nm -C --defined-only`.
+fn parse_nm_output(output: &str) -> std::collections::HashSet<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let _address = fields.next()?;
+            let kind = fields.next()?;
+            let symbol = fields.next()?;
+            (kind.len() == 1 && kind.chars().all(|kind| kind.is_ascii_uppercase()))
+                .then(|| symbol.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undefined_references_of_gnu_ld_and_lld_are_grouped_by_symbol() {
+        let mut link_errors = LinkErrors::new();
+        for line in indoc::indoc! {"
+            /usr/bin/ld: /build/debug/deps/app.dir/main.o: in function `main':
+            main.cpp:(.text+0x10): undefined reference to `lib::missing(int)'
+            /usr/bin/ld: main.cpp:(.text+0x1a): undefined reference to `S::go()'
+            /usr/bin/ld: /build/debug/deps/app.dir/other.o: in function `other()':
+            other.cpp:(.text+0x8): undefined reference to `lib::missing(int)'
+            collect2: error: ld returned 1 exit status
+            ld.lld: error: undefined symbol: S::go()
+            >>> referenced by util.cpp:12
+            >>>               /build/debug/deps/app.dir/util.o:(util())
+        "}
+        .lines()
+        {
+            link_errors.record(line);
+        }
+        assert_eq!(
+            link_errors.undefined_symbols(),
+            vec![
+                UndefinedSymbol {
+                    symbol: "S::go()".to_string(),
+                    references: vec![
+                        Reference {
+                            location: "/build/debug/deps/app.dir/main.o".to_string(),
+                            function: Some("main".to_string()),
+                        },
+                        Reference {
+                            location: "util.cpp:12".to_string(),
+                            function: None,
+                        },
+                    ],
+                },
+                UndefinedSymbol {
+                    symbol: "lib::missing(int)".to_string(),
+                    references: vec![
+                        Reference {
+                            location: "/build/debug/deps/app.dir/main.o".to_string(),
+                            function: Some("main".to_string()),
+                        },
+                        Reference {
+                            location: "/build/debug/deps/app.dir/other.o".to_string(),
+                            function: Some("other()".to_string()),
+                        },
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_nm_output_keeps_global_symbols() {
+        let symbols = parse_nm_output(indoc::indoc! {"
+
+            missing.o:
+            0000000000000000 T lib::missing(int)
+            0000000000000010 t local_helper()
+            0000000000000000 W lib::Widget::size() const
+        "});
+        assert!(symbols.contains("lib::missing(int)"));
+        assert!(symbols.contains("lib::Widget::size() const"));
+        assert!(!symbols.contains("local_helper()"));
+        assert_eq!(symbols.len(), 2);
+    }
+}
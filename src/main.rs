@@ -1,36 +1,110 @@
 use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
 use std::rc::Rc;
 
 use anyhow::Context;
 use clap::CommandFactory;
-use clap::Parser;
+use clap::FromArgMatches;
 use colored::Colorize;
 use regex::Regex;
-use std::io::BufRead;
+use std::io::{BufRead, IsTerminal, Read, Write};
 use std::path::Path;
 use yambs::toolchain::ToolchainError;
 
-use parser::types::Language;
-use yambs::build_target::{target_registry::TargetRegistry, BuildTarget};
-use yambs::cli::command_line::{BuildOpts, CommandLine, ManifestDirectory, RemakeOpts, Subcommand};
-use yambs::cli::configurations::BuildType;
-use yambs::compiler::Compiler;
+use parser::types::{BuildFor, Language};
+use yambs::archive::{ArchiveDirectory, ARCHIVES_DIRECTORY_NAME};
+use yambs::build_file_hashes::{
+    build_file_directories, BuildFileHashes, ALLOW_MODIFIED_BUILD_FILES_FLAG,
+};
+use yambs::build_history::{self, BuildHistory, BuildReport, BuildSummary, TargetSummary};
+use yambs::build_hygiene;
+use yambs::build_target::{
+    target_registry::{CachedRegistry, RegistryInputs, TargetRegistry},
+    BuildTarget,
+};
+use yambs::cache::{Cache, YAMBS_CACHE_DIRECTORY_NAME};
+use yambs::clean;
+use yambs::cli::command_line::{
+    Backend, BuildOpts, CacheCommand, CacheOpts, CheckOpts, CleanOpts, CommandLine, ConvertMmkOpts,
+    DocsOpts, DoctorOpts, EmbedOpts, ErrorFormat, ExecOpts, FindToolOpts, GraphOpts, IdeCommand,
+    IdeOpts, InitOpts, InstallOpts, IntrospectOpts, ManifestDirectory, RemakeOpts, ReportOpts,
+    ReproBundleOpts, RunPlanOpts, SandboxOpts, Subcommand, TestOpts, UnusedDepsOpts, WhyOpts,
+    DEFAULT_INSTALL_PREFIX,
+};
+use yambs::cli::configurations::{validate_sanitizers, BuildType};
+use yambs::cli::presets::{apply_preset, Presets, PRESETS_FILE_NAME};
+use yambs::cli::BuildDirectory;
+use yambs::compiler::{Compiler, EvaluationCache};
+use yambs::docs;
+use yambs::environment::Environment;
+use yambs::errors::FsError;
+use yambs::flags::FlagOrigin;
+use yambs::generator::checkpoint::GenerationCheckpoint;
+use yambs::generator::fingerprint::{GenerationFingerprint, GenerationFingerprints};
+use yambs::generator::targets::ProgressDocument;
 use yambs::generator::{
-    makefile::make::BuildProcess, makefile::Make, Generator, GeneratorType, MakefileGenerator,
+    internal::InternalBackend, makefile::make::BuildProcess, makefile::make::ErrorBudget,
+    makefile::make::VerboseSelection, makefile::Make, makefile::MakeFeatures,
+    makefile::BUILD_FILE_EXTENSION, makefile::INCLUDE_DIRECTORY_NAME, makefile::MAKEFILE_NAME,
+    ninja::Ninja, ninja::NINJA_BUILD_FILE_NAME, plan::BuildPlan, Generator, GeneratorType,
+    MakefileGenerator, NinjaGenerator, EXAMPLES_TARGET_NAME, HOST_BUILD_DIRECTORY_NAME,
+};
+use yambs::graph::{
+    format_duration, format_paths_as_tree, paths_to_dot, BuildGraph, BOTTLENECK_REPORT_LENGTH,
+    GRAPH_FILE_NAME,
 };
+use yambs::init::{ProjectKind, ProjectSkeleton};
+use yambs::link_errors;
 use yambs::logger;
 use yambs::manifest;
+use yambs::memory_budget::{self, MemoryBudget};
+use yambs::mmk_parser::convert::MmkConverter;
+use yambs::mmk_parser::{MMK_EXECUTABLE_FILE_NAME, MMK_LIBRARY_FILE_NAME};
 use yambs::output;
 use yambs::output::Output;
 use yambs::parser;
+use yambs::parser::diagnostic::Diagnostic;
+use yambs::parser::prefetch::{ManifestCacheStatistics, ParsedManifests};
+use yambs::parser::types::{Standard, StandardMismatchPolicy};
 use yambs::progress;
-use yambs::toolchain::{NormalizedToolchain, TOOLCHAIN_FILE_NAME};
+use yambs::repro_bundle::{
+    bundle_path, BuildRecord, Redactor, ReproBundle, REPRO_BUNDLE_FILE_NAME,
+};
+use yambs::sandbox::Sandbox;
+use yambs::stale_outputs::{remove_stale_outputs, ExpectedOutputs};
+use yambs::system_pch::SystemPch;
+use yambs::target_picker;
+use yambs::targets;
+use yambs::test_runner;
+use yambs::test_runner::{
+    TestOutcome, TestReport, TestResultsCache, TestRunner, TEST_REPORT_FILE_NAME,
+};
+use yambs::timings::{CompileTimings, StepTimer};
+use yambs::toolchain;
+use yambs::toolchain::{
+    NormalizedToolchain, OutputExtensions, ToolchainFingerprint, TOOLCHAIN_ENVIRONMENT_VARIABLES,
+    TOOLCHAIN_FILE_NAME,
+};
+use yambs::utility::{self, SourceTreeGuard};
+use yambs::vscode::VscodeWorkspace;
+use yambs::warnings_report::{WarningsBaselines, WarningsReport, WARNINGS_REPORT_FILE_NAME};
 use yambs::ProjectConfig;
 use yambs::YAMBS_MANIFEST_NAME;
-use yambs::{YAMBS_BUILD_DIR_VAR, YAMBS_BUILD_TYPE, YAMBS_MANIFEST_DIR};
+use yambs::{find_program, FindProgramOptions};
+use yambs::{
+    DEPENDENCY_TESTS, YAMBS_BUILD_DIR_VAR, YAMBS_BUILD_TYPE, YAMBS_MANIFEST_DIR,
+    YAMBS_TARGET_TRIPLE,
+};
 
 fn main() -> anyhow::Result<()> {
-    let command_line = CommandLine::parse();
+    let matches = CommandLine::command().get_matches();
+    let command_line = CommandLine::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let error_format = command_line.error_format;
+    run(command_line, &matches).or_else(|error| report_error(error, error_format))
+}
+
+fn run(command_line: CommandLine, matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    let command_line = parse_command_line(command_line, matches)?;
     let output = Output::new();
 
     if command_line.show_version {
@@ -42,7 +116,30 @@ fn main() -> anyhow::Result<()> {
     if let Some(subcommand) = command_line.subcommand {
         match subcommand {
             Subcommand::Build(mut build_opts) => do_build(&mut build_opts, &output)?,
+            Subcommand::Test(ref test_opts) => do_test(test_opts, &output)?,
+            Subcommand::Check(ref check_opts) => do_check(check_opts, &output)?,
             Subcommand::Remake(ref remake_opts) => do_remake(remake_opts)?,
+            Subcommand::Clean(ref clean_opts) => do_clean(clean_opts, &output)?,
+            Subcommand::Cache(ref cache_opts) => do_cache(cache_opts, &output)?,
+            Subcommand::Init(ref init_opts) => do_init(init_opts, &output)?,
+            Subcommand::ConvertMmk(ref convert_opts) => do_convert_mmk(convert_opts, &output)?,
+            Subcommand::Graph(ref graph_opts) => do_graph(graph_opts, &output)?,
+            Subcommand::Why(ref why_opts) => do_why(why_opts, &output)?,
+            Subcommand::UnusedDeps(ref unused_deps_opts) => {
+                do_unused_deps(unused_deps_opts, &output)?
+            }
+            Subcommand::Introspect(ref introspect_opts) => do_introspect(introspect_opts)?,
+            Subcommand::Install(ref install_opts) => do_install(install_opts, &output)?,
+            Subcommand::ReproBundle(ref bundle_opts) => do_repro_bundle(bundle_opts, &output)?,
+            Subcommand::Ide(ref ide_opts) => do_ide(ide_opts, &output)?,
+            Subcommand::Exec(ref exec_opts) => do_exec(exec_opts)?,
+            Subcommand::Docs(ref docs_opts) => do_docs(docs_opts, &output)?,
+            Subcommand::Report(ref report_opts) => do_report(report_opts, &output)?,
+            Subcommand::FindTool(ref find_tool_opts) => do_find_tool(find_tool_opts)?,
+            Subcommand::Doctor(ref doctor_opts) => do_doctor(doctor_opts, &output)?,
+            Subcommand::Sandbox(ref sandbox_opts) => do_sandbox(sandbox_opts)?,
+            Subcommand::Embed(ref embed_opts) => do_embed(embed_opts)?,
+            Subcommand::RunPlan(ref run_plan_opts) => do_run_plan(run_plan_opts)?,
         }
     } else {
         CommandLine::command().print_help()?;
@@ -52,6 +149,87 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+// Prints `error` as a diagnostic when it is located in the manifest or JSON is asked for, and
+// exits. Other errors are returned to be printed as usual.
+fn report_error(error: anyhow::Error, error_format: ErrorFormat) -> anyhow::Result<()> {
+    let location = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<parser::ParseTomlError>())
+        .and_then(parser::ParseTomlError::location)
+        .cloned();
+    let message = error
+        .chain()
+        .map(ToString::to_string)
+        .collect::<Vec<String>>()
+        .join(": ");
+    let diagnostic = Diagnostic::error(&message, location);
+    match error_format {
+        ErrorFormat::Json => eprintln!("{}", serde_json::to_string(&diagnostic)?),
+        ErrorFormat::Human if diagnostic.location.is_some() => eprint!("{}", diagnostic.render()),
+        ErrorFormat::Human => return Err(error),
+    }
+    std::process::exit(1)
+}
+
+// Completes the parsed command line, parsing it again with the options of the preset if one is
+// given. `matches` are those `command_line` is parsed from.
+fn parse_command_line(
+    command_line: CommandLine,
+    matches: &clap::ArgMatches,
+) -> anyhow::Result<CommandLine> {
+    let build_opts = match command_line.subcommand {
+        Some(Subcommand::Build(ref build_opts)) => build_opts,
+        Some(Subcommand::Test(ref test_opts)) => &test_opts.build_opts,
+        _ => return Ok(command_line),
+    };
+    if let Some(ref preset_name) = build_opts.preset {
+        let args = std::env::args_os().collect::<Vec<std::ffi::OsString>>();
+        let manifest_directory = build_opts.manifest_dir.as_path();
+        let presets_file = manifest_directory.join(PRESETS_FILE_NAME);
+        let presets = Presets::from_file(&presets_file)?;
+        let preset = presets.get(preset_name, &presets_file)?;
+        let matches = CommandLine::command().get_matches_from(apply_preset(
+            &args,
+            preset,
+            manifest_directory,
+        ));
+        let command_line = CommandLine::from_arg_matches(&matches)?;
+        return apply_manifest_build_directory(command_line, &matches);
+    }
+    apply_manifest_build_directory(command_line, matches)
+}
+
+// Builds in the directory named by `build_dir` of the manifest, unless a build directory is given
+// on the command line or by a preset.
+fn apply_manifest_build_directory(
+    mut command_line: CommandLine,
+    matches: &clap::ArgMatches,
+) -> anyhow::Result<CommandLine> {
+    let build_opts = match command_line.subcommand {
+        Some(Subcommand::Build(ref mut build_opts)) => build_opts,
+        Some(Subcommand::Test(ref mut test_opts)) => &mut test_opts.build_opts,
+        _ => return Ok(command_line),
+    };
+    let build_directory_given = matches
+        .subcommand()
+        .and_then(|(_, matches)| matches.value_source("build_directory"))
+        == Some(clap::parser::ValueSource::CommandLine);
+    let manifest_path = build_opts.manifest_dir.as_path().join(YAMBS_MANIFEST_NAME);
+    if build_directory_given || !manifest_path.is_file() {
+        return Ok(command_line);
+    }
+    if let Some(build_directory) =
+        parser::parse_build_dir(&manifest_path, &build_opts.configuration.build_type)?
+    {
+        log::debug!(
+            "Using build directory {} from the manifest",
+            build_directory.display()
+        );
+        build_opts.build_directory = BuildDirectory::from(build_directory);
+    }
+    Ok(command_line)
+}
+
 fn log_invoked_command() {
     log::info!(
         "Command line: {}",
@@ -65,18 +243,70 @@ fn log_invoked_command() {
 }
 
 fn initialize_preset_variables(opts: &BuildOpts) -> anyhow::Result<()> {
+    DEPENDENCY_TESTS
+        .set(opts.with_dep_tests)
+        .map_err(|_| anyhow::anyhow!("Error occured setting whether to build dependency tests"))?;
+    initialize_preset_variables_with(
+        &opts.build_directory,
+        &opts.manifest_dir,
+        &opts.configuration.build_type,
+        opts.toolchain_file.as_deref(),
+    )
+}
+
+fn initialize_preset_variables_with(
+    build_directory: &BuildDirectory,
+    manifest_dir: &ManifestDirectory,
+    build_type: &BuildType,
+    toolchain_file: Option<&Path>,
+) -> anyhow::Result<()> {
     YAMBS_BUILD_DIR_VAR
-        .set(opts.build_directory.clone())
+        .set(build_directory.clone())
         .map_err(|_| anyhow::anyhow!("Error occured fetching build directory"))?;
     YAMBS_MANIFEST_DIR
-        .set(opts.manifest_dir.clone())
+        .set(manifest_dir.clone())
         .map_err(|_| anyhow::anyhow!("Error occurred fetching manifest directory"))?;
     YAMBS_BUILD_TYPE
-        .set(opts.configuration.build_type.clone())
+        .set(build_type.clone())
         .map_err(|_| anyhow::anyhow!("Error occured fetching build type"))?;
+    YAMBS_TARGET_TRIPLE
+        .set(preset_target_triple(toolchain_file, manifest_dir.as_path()))
+        .map_err(|_| anyhow::anyhow!("Error occured fetching target triple"))?;
     Ok(())
 }
 
+// Machine the toolchain builds for, which ${YAMBS_TARGET_TRIPLE} of the manifests expands to. The
+// manifests are parsed before the toolchain is evaluated, so the triple is read from the toolchain
+// file, or asked of CXX without one. Empty if neither tells.
+fn preset_target_triple(toolchain_file: Option<&Path>, manifest_dir: &Path) -> String {
+    let triple = match locate_toolchain_file(toolchain_file, manifest_dir) {
+        Some(toolchain_file) => std::fs::read_to_string(toolchain_file)
+            .ok()
+            .and_then(|content| toolchain::target_triple_of_file(&content)),
+        None => std::env::var_os("CXX").and_then(|cxx| toolchain::target_triple(Path::new(&cxx))),
+    };
+    triple.unwrap_or_default()
+}
+
+// `toolchain_file`, or the toolchain file of the manifest directory or $HOME, if there is one.
+fn locate_toolchain_file(
+    toolchain_file: Option<&Path>,
+    manifest_dir: &Path,
+) -> Option<std::path::PathBuf> {
+    if let Some(toolchain_file) = toolchain_file {
+        return Some(toolchain_file.to_path_buf());
+    }
+    let candidates = [
+        Some(manifest_dir.join(".yambs").join(TOOLCHAIN_FILE_NAME)),
+        home::home_dir().map(|home| home.join(".yambs").join(TOOLCHAIN_FILE_NAME)),
+    ];
+    candidates
+        .iter()
+        .flatten()
+        .find(|file| file.is_file())
+        .cloned()
+}
+
 fn evaluate_compiler(
     toolchain: &Rc<RefCell<NormalizedToolchain>>,
     project_config: &ProjectConfig,
@@ -89,9 +319,175 @@ fn evaluate_compiler(
     };
     log::trace!("evaluate_compiler");
     let test_dir = project_config.build_directory.as_path().join("sample");
+    evaluate_compiler_cached(compiler.as_ref(), &test_dir, false)?;
+    Ok(())
+}
+
+// Evaluates `compiler` unless a previous evaluation of it passed, or `recheck` is given. Returns
+// whether the evaluation was skipped. A cache that can not be written only costs evaluating the
+// compiler again.
+fn evaluate_compiler_cached(
+    compiler: &dyn Compiler,
+    test_dir: &Path,
+    recheck: bool,
+) -> anyhow::Result<bool> {
+    let cache =
+        EvaluationCache::default_directory().map(|directory| EvaluationCache::new(&directory));
+    let key = compiler.evaluation_key();
+    if let (Some(ref cache), Some(ref key)) = (&cache, &key) {
+        if !recheck && cache.contains(key) {
+            log::debug!("Compiler passed its evaluation before. Skipping the sample build");
+            return Ok(true);
+        }
+    }
     log::debug!("Evaluating compiler by doing a sample build...");
-    compiler.evaluate(&test_dir)?;
+    compiler.evaluate(test_dir)?;
     log::debug!("Evaluating compiler by doing a sample build... done");
+    if let (Some(cache), Some(key)) = (cache, key) {
+        if let Err(e) = cache.insert(&key) {
+            log::warn!("Failed to cache the evaluation of the compiler: {}", e);
+        }
+    }
+    Ok(false)
+}
+
+fn remove_build_outputs(build_directory: &BuildDirectory) -> anyhow::Result<()> {
+    for build_type in [BuildType::Debug, BuildType::Release] {
+        clean::remove_build_tree(build_directory.as_path(), &build_type)?;
+    }
+    Ok(())
+}
+
+// Build trees generated by another version of yambs or with other options are removed, so that
+// everything is regenerated and rebuilt, as make does not know to rebuild anything for either.
+fn detect_generation_changes(
+    cache: &Cache,
+    project_config: &ProjectConfig,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let fingerprint = GenerationFingerprint::new(project_config);
+    let build_type = project_config.build_type.to_string();
+    let mut fingerprints = cache
+        .from_cache::<GenerationFingerprints>()
+        .unwrap_or_default();
+    let changes = match fingerprints.build_trees.get(&build_type) {
+        Some(previous) => fingerprint.changes_since(previous),
+        None if project_config
+            .build_directory
+            .as_path()
+            .join(&build_type)
+            .is_dir() =>
+        {
+            vec!["they were generated by an earlier version of yambs".to_string()]
+        }
+        None => Vec::new(),
+    };
+    if !changes.is_empty() {
+        output.warning(&format!(
+            "Regenerating the {} build files from scratch, as {}.",
+            build_type,
+            changes.join(" and ")
+        ));
+        clean::remove_build_tree(
+            project_config.build_directory.as_path(),
+            &project_config.build_type,
+        )?;
+    }
+    fingerprints.build_trees.insert(build_type, fingerprint);
+    cache
+        .cache(&fingerprints)
+        .with_context(|| "Failed to cache generation fingerprint")?;
+    Ok(())
+}
+
+// Flags assigned on the command line of make, such as CXXFLAGS=-DFEATURE, are not in the generated
+// build files, so objects are not compiled again when they change.
+fn warn_about_defines_given_to_make(opts: &BuildOpts, output: &Output) {
+    for arg in &opts.make_args {
+        let assigns_flags = ["CPPFLAGS=", "CXXFLAGS=", "CFLAGS="]
+            .iter()
+            .any(|variable| arg.starts_with(variable));
+        if assigns_flags && arg.contains("-D") {
+            output.warning(&format!(
+                "Defines of {} are not tracked by yambs, and objects are not rebuilt when they change. Give them with -D instead",
+                arg
+            ));
+        }
+    }
+}
+
+// Build files are generated again by every build, so edits made to them by hand would be lost
+// without a word. The build fails instead, unless asked to regenerate the files anyway.
+fn check_build_files_unmodified(opts: &BuildOpts, output: &Output) -> anyhow::Result<()> {
+    let cache = Cache::new(opts.build_directory.as_path())?;
+    let Some(hashes) = cache.from_cache::<BuildFileHashes>() else {
+        return Ok(());
+    };
+    let modified = hashes.modified_files(&build_file_directories(
+        opts.build_directory.as_path(),
+        HOST_BUILD_DIRECTORY_NAME,
+        &opts.configuration.build_type.to_string(),
+    ));
+    if modified.is_empty() {
+        return Ok(());
+    }
+    let files = modified
+        .iter()
+        .map(|file| format!("    {}", file.display()))
+        .collect::<Vec<String>>()
+        .join("\n");
+    if opts.allow_modified_build_files {
+        output.warning(&format!(
+            "Regenerating build files edited since they were generated, discarding the edits:\n{}",
+            files
+        ));
+        return Ok(());
+    }
+    anyhow::bail!(
+        "Build files were edited since they were generated:\n{}\nThe edits would be lost when \
+         the build files are generated again. Make the changes in the manifest instead, or run \
+         again with {} to regenerate the build files, discarding the edits.",
+        files,
+        ALLOW_MODIFIED_BUILD_FILES_FLAG
+    )
+}
+
+fn record_build_file_hashes(cache: &Cache, opts: &BuildOpts) -> anyhow::Result<()> {
+    let mut hashes = cache.from_cache::<BuildFileHashes>().unwrap_or_default();
+    hashes
+        .record(&build_file_directories(
+            opts.build_directory.as_path(),
+            HOST_BUILD_DIRECTORY_NAME,
+            &opts.configuration.build_type.to_string(),
+        ))
+        .with_context(|| "Failed to hash the generated build files")?;
+    cache
+        .cache(&hashes)
+        .with_context(|| "Failed to cache hashes of the generated build files")?;
+    Ok(())
+}
+
+fn detect_toolchain_changes(
+    cache: &Cache,
+    toolchain: &Rc<RefCell<NormalizedToolchain>>,
+    build_directory: &BuildDirectory,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let fingerprint = toolchain
+        .borrow()
+        .fingerprint()
+        .with_context(|| "Failed to fingerprint toolchain")?;
+    if let Some(cached_fingerprint) = cache.from_cache::<ToolchainFingerprint>() {
+        if cached_fingerprint != fingerprint {
+            output.warning("Toolchain has changed since the previous run. Forcing a full rebuild.");
+            log::info!("Previous toolchain:\n{}", cached_fingerprint);
+            log::info!("Current toolchain:\n{}", fingerprint);
+            remove_build_outputs(build_directory)?;
+        }
+    }
+    cache
+        .cache(&fingerprint)
+        .with_context(|| "Failed to cache toolchain fingerprint")?;
     Ok(())
 }
 
@@ -109,6 +505,22 @@ fn locate_manifest(manifest_dir: &ManifestDirectory) -> anyhow::Result<std::path
     let manifest_file = manifest_dir.as_path().join(YAMBS_MANIFEST_NAME);
 
     if !manifest_file.is_file() {
+        let mmk_directory = vec![
+            manifest_dir.as_path().to_path_buf(),
+            manifest_dir.as_path().join("src"),
+        ]
+        .into_iter()
+        .find(|directory| {
+            directory.join(MMK_LIBRARY_FILE_NAME).is_file()
+                || directory.join(MMK_EXECUTABLE_FILE_NAME).is_file()
+        });
+        if let Some(mmk_directory) = mmk_directory {
+            anyhow::bail!(
+                "Could not locate manifest file in {}, but found MMK files. Convert them with `yambs convert-mmk {}`",
+                manifest_dir.as_path().display(),
+                mmk_directory.display()
+            );
+        }
         anyhow::bail!(
             "Could not locate manifest file in {}",
             manifest_dir.as_path().display()
@@ -120,81 +532,91 @@ fn locate_manifest(manifest_dir: &ManifestDirectory) -> anyhow::Result<std::path
 pub fn construct_generator(
     project_config: &ProjectConfig,
     toolchain: &Rc<RefCell<NormalizedToolchain>>,
+    compile_timings: Option<CompileTimings>,
+    manifest_data: &manifest::ManifestData,
+    serialize_lto_links: bool,
+    backend: Backend,
+    make_features: MakeFeatures,
 ) -> anyhow::Result<Box<dyn Generator>> {
+    let install = manifest_data.install.clone();
+    let signing = manifest_data.signing.clone();
+    let tests = manifest_data.tests.clone();
     let generator_type = &project_config.generator_type;
     log::info!("Using {:?} as generator.", generator_type);
+    if backend == Backend::Internal && *generator_type != GeneratorType::GNUMakefiles {
+        anyhow::bail!(
+            "--backend internal builds with the plan of the Makefile generator. Leave out --generator ninja"
+        );
+    }
     match generator_type {
-        GeneratorType::GNUMakefiles => Ok(Box::new(MakefileGenerator::new(
-            &project_config,
-            toolchain.clone(),
-        )?) as Box<dyn Generator>),
+        GeneratorType::GNUMakefiles => {
+            let mut generator = MakefileGenerator::new(&project_config, toolchain.clone())?
+                .with_install(install)
+                .with_signing(signing)
+                .with_compile_fail_tests(tests)
+                .with_make_features(make_features);
+            if let Some(compile_timings) = compile_timings {
+                generator = generator.with_compile_timings(compile_timings);
+            }
+            if serialize_lto_links {
+                generator = generator.with_serialized_lto_links();
+            }
+            if backend == Backend::Internal {
+                generator = generator.with_build_plan();
+            }
+            Ok(Box::new(generator) as Box<dyn Generator>)
+        }
+        GeneratorType::Ninja => {
+            if !tests.is_empty() {
+                anyhow::bail!(
+                    "Compile-fail tests are not supported by the Ninja generator. Use --generator make instead"
+                );
+            }
+            if signing.is_some() {
+                anyhow::bail!(
+                    "Signing artifacts is not supported by the Ninja generator. Use --generator make instead"
+                );
+            }
+            if compile_timings.is_some() {
+                log::warn!("Ninja schedules the build itself. Ignoring --schedule-by-duration");
+            }
+            let mut generator = NinjaGenerator::new(project_config, toolchain.clone())?;
+            if serialize_lto_links {
+                generator = generator.with_serialized_lto_links();
+            }
+            Ok(Box::new(generator) as Box<dyn Generator>)
+        }
     }
 }
 
-fn do_build(opts: &BuildOpts, output: &Output) -> anyhow::Result<()> {
-    let logger = logger::Logger::init(opts.build_directory.as_path(), log::LevelFilter::Trace)?;
-    log_invoked_command();
-
-    initialize_preset_variables(opts)?;
-    log::trace!("do_build");
-
-    let mut dependency_registry = TargetRegistry::new();
-    let manifest_path = locate_manifest(&opts.manifest_dir)?;
-    let manifest = parser::parse(&manifest_path).with_context(|| "Failed to parse manifest")?;
-
-    // override the command line settings if there are configurations set in the manifest
-    let std = if let Some(ref std) = opts.configuration.standard {
-        log::info!("Using standard {} given on command line", std.to_string());
-        std.clone()
-    } else {
-        if let Some(std) = manifest
-            .data
-            .project_config
-            .as_ref()
-            .and_then(|pc| pc.std.clone())
-        {
-            log::info!("Using standard {} found in manifest", std.to_string());
-            std
-        } else {
-            anyhow::bail!(
-                "No standard is set! Please set one either through command line or in manifest."
-            );
-        }
-    };
-
-    let language = if let Some(language) = manifest
-        .data
-        .project_config
-        .as_ref()
-        .and_then(|pc| pc.language.clone())
-    {
-        log::info!("Using language {} found in manifest", language.to_string());
-        language
-    } else {
-        log::warn!("No language specified. Using C++");
-        Language::CXX
-    };
+// Locates the toolchain, evaluates its compiler and checks if it has changed since the previous run.
+fn configure_toolchain(
+    opts: &BuildOpts,
+    project_config: &ProjectConfig,
+    cache: &Cache,
+    output: &Output,
+) -> anyhow::Result<Rc<RefCell<NormalizedToolchain>>> {
+    let toolchain = locate_toolchain(opts.toolchain_file.as_deref(), opts.manifest_dir.as_path())?;
+    let toolchain = Rc::new(RefCell::new(toolchain));
 
-    let project_config = ProjectConfig {
-        std,
-        language,
-        build_directory: opts.build_directory.clone(),
-        build_type: opts.configuration.build_type.clone(),
-        generator_type: opts.configuration.generator_type.clone(),
-        defines: opts.configuration.defines.clone(),
-    };
+    evaluate_compiler(&toolchain, project_config)?;
+    detect_toolchain_changes(cache, &toolchain, &opts.build_directory, output)?;
+    Ok(toolchain)
+}
 
+// Toolchain of `toolchain_file`, or of the toolchain file of the manifest directory or $HOME,
+// falling back to CXX and CC.
+fn locate_toolchain(
+    toolchain_file: Option<&Path>,
+    manifest_dir: &Path,
+) -> anyhow::Result<NormalizedToolchain> {
     // FIXME: The logic here is quirky. It is easy to mess up and understand the flow.
     // Can it be simplified?
     // There should be made an integration test for this to check if it is working as intended.
-    let toolchain = {
-        match detect_toolchain_file(
-            &opts
-                .manifest_dir
-                .as_path()
-                .join(".yambs")
-                .join(TOOLCHAIN_FILE_NAME),
-        ) {
+    let toolchain = if let Some(toolchain_file) = toolchain_file {
+        Ok(detect_toolchain_file(toolchain_file)?)
+    } else {
+        match detect_toolchain_file(&manifest_dir.join(".yambs").join(TOOLCHAIN_FILE_NAME)) {
             Ok(tc) => Ok(tc),
             Err(e) => {
                 let tc_err = e.downcast::<ToolchainError>().unwrap();
@@ -239,149 +661,2260 @@ fn do_build(opts: &BuildOpts, output: &Output) -> anyhow::Result<()> {
             }
         }
     };
-
-    let toolchain = Rc::new(RefCell::new(toolchain));
-
-    evaluate_compiler(&toolchain, &project_config)?;
-
-    let mut generator = construct_generator(&project_config, &toolchain)?;
-    parse_and_register_dependencies(
-        &manifest,
-        output,
-        &mut dependency_registry,
-        &toolchain,
-        &opts.configuration.build_type,
-    )
-    .with_context(|| "An error occured when registering project dependencies")?;
-
-    let buildfile_directory = generate_build_files(&mut generator, &dependency_registry, opts)?;
-
-    build_project(&buildfile_directory, output, opts, &logger)?;
-    Ok(())
+    Ok(toolchain)
 }
 
-fn do_remake(opts: &RemakeOpts) -> anyhow::Result<()> {
-    let log_file = &opts.build_directory.as_path().join(logger::YAMBS_LOG_FILE);
-    let log_fh = std::fs::File::open(log_file).context("Failed to find log file")?;
-    let mut reader = std::io::BufReader::new(log_fh);
-    let mut line = String::new();
-    let line_length = reader
-        .read_line(&mut line)
-        .context("Failed to read line from log file")?;
-    if line_length == 0 {
-        anyhow::bail!("Could not find first line of log file");
+// The standard given on the command line overrides the one set in the manifest.
+fn project_standard(
+    standard: Option<&Standard>,
+    manifest: &manifest::ParsedManifest,
+) -> anyhow::Result<(Standard, FlagOrigin)> {
+    if let Some(std) = standard {
+        log::info!("Using standard {} given on command line", std.to_string());
+        Ok((std.clone(), FlagOrigin::CommandLine))
+    } else if let Some(std) = manifest
+        .data
+        .project_config
+        .as_ref()
+        .and_then(|pc| pc.std.clone())
+    {
+        log::info!("Using standard {} found in manifest", std.to_string());
+        Ok((std, FlagOrigin::Project))
+    } else {
+        anyhow::bail!(
+            "No standard is set! Please set one either through command line or in manifest."
+        );
     }
-
-    let command_line_regex = Regex::new(r"Command line:\s(?P<cmd>.*)").unwrap();
-    let caps = command_line_regex.captures(&line).unwrap();
-    let invoked_command = caps.name("cmd").unwrap().as_str();
-    println!("{}", invoked_command);
-    Ok(())
 }
 
-fn generate_build_files(
-    generator: &mut Box<dyn Generator>,
-    registry: &TargetRegistry,
-    opts: &BuildOpts,
-) -> anyhow::Result<std::path::PathBuf> {
-    log::trace!("generate_build_files");
-    let buildfile_directory = generator.generate(registry)?;
-    log::debug!(
-        "Build files generated in {}",
-        opts.build_directory.as_path().display()
-    );
-    Ok(buildfile_directory)
-}
+fn do_build(opts: &BuildOpts, output: &Output) -> anyhow::Result<()> {
+    if opts.enforce_out_of_source {
+        enforce_out_of_source(opts)?;
+    }
+    check_build_directory_writable(opts)?;
+    let logger = logger::Logger::init(opts.build_directory.as_path(), log::LevelFilter::Trace)?;
+    log_invoked_command();
 
-fn parse_and_register_dependencies(
-    manifest: &manifest::ParsedManifest,
-    output: &Output,
-    dep_registry: &mut TargetRegistry,
-    toolchain: &Rc<RefCell<NormalizedToolchain>>,
-    build_type: &BuildType,
-) -> anyhow::Result<()> {
-    log::trace!("parse_and_register_dependencies");
-    let manifest_path = manifest.manifest.directory.join(YAMBS_MANIFEST_NAME);
-    for build_target in &manifest.data.targets {
-        if let Some(lib) = build_target.library() {
-            log::debug!(
-                "Creating build target for library {} in manifest {}",
-                lib.name,
-                manifest_path.display()
-            );
-        }
-        if let Some(exe) = build_target.executable() {
-            log::debug!(
-                "Creating build target for executable {} in manifest {}",
-                exe.name,
-                manifest_path.display()
-            );
-        }
-        BuildTarget::target_node_from_source(
-            &manifest.manifest.directory,
-            build_target,
-            dep_registry,
-            toolchain,
-            build_type,
-        )?;
+    initialize_preset_variables(opts)?;
+    log::trace!("do_build");
+    if let Some(ref preset) = opts.preset {
+        log::info!("Using preset \"{}\"", preset);
     }
-    let number_of_targets = dep_registry.number_of_targets();
-    output.status(&format!("Registered {} build targets", number_of_targets));
-    Ok(())
-}
 
-fn run_make(args: &[String], makefile_directory: &std::path::Path) -> anyhow::Result<BuildProcess> {
-    std::env::set_current_dir(makefile_directory).with_context(|| {
-        format!(
-            "Could not access directory {}",
-            makefile_directory.display()
-        )
-    })?;
-    let make = Make::new(args)?;
+    let manifest_path = locate_manifest(&opts.manifest_dir)?;
+    let manifest = parser::parse(&manifest_path).with_context(|| "Failed to parse manifest")?;
+    check_build_directory(opts, &manifest.manifest.directory, output)?;
+    check_build_files_unmodified(opts, output)?;
+    warn_about_defines_given_to_make(opts, output);
 
-    log::debug!("Running make in directory {}", makefile_directory.display());
-    let build_process = make.run()?;
-    Ok(build_process)
-}
+    let (std, std_origin) = project_standard(opts.configuration.standard.as_ref(), &manifest)?;
+
+    let language = if let Some(language) = manifest
+        .data
+        .project_config
+        .as_ref()
+        .and_then(|pc| pc.language.clone())
+    {
+        log::info!("Using language {} found in manifest", language.to_string());
+        language
+    } else {
+        log::warn!("No language specified. Using C++");
+        Language::CXX
+    };
+
+    validate_sanitizers(&opts.configuration.sanitizers)?;
+    let project_config = ProjectConfig {
+        std,
+        std_origin,
+        language,
+        build_directory: opts.build_directory.clone(),
+        build_type: opts.configuration.build_type.clone(),
+        generator_type: opts.configuration.generator_type.clone(),
+        defines: opts.configuration.defines.clone(),
+        gc_sections: manifest
+            .data
+            .project_config
+            .as_ref()
+            .and_then(|pc| pc.gc_sections)
+            .unwrap_or(false),
+        report_gc_sections: opts.report_gc_sections,
+        hardened: manifest
+            .data
+            .project_config
+            .as_ref()
+            .and_then(|pc| pc.hardened)
+            .unwrap_or(false),
+        system_pch: manifest
+            .data
+            .project_config
+            .as_ref()
+            .and_then(|pc| pc.system_pch)
+            .unwrap_or(false),
+        warnings: manifest
+            .data
+            .project_config
+            .as_ref()
+            .and_then(|pc| pc.warnings.clone())
+            .unwrap_or_default(),
+        generated_warnings: manifest
+            .data
+            .project_config
+            .as_ref()
+            .and_then(|pc| pc.generated_warnings)
+            .unwrap_or_default(),
+        sanitizers: opts.configuration.sanitizers.clone(),
+        opt_level: opts.configuration.opt_level,
+    };
+
+    // The targets resolved by the previous build are reused when resolved from unchanged
+    // inputs. Otherwise, dependency manifests are parsed while the toolchain is located and
+    // evaluated, as the two do not depend on each other.
+    let cache = Cache::new(opts.build_directory.as_path())?;
+    let cached_registry = cache.from_cache::<CachedRegistry>().filter(|cached| {
+        !opts.reconfigure
+            && cached
+                .inputs
+                .is_unchanged(&manifest.manifest.directory, &project_config.build_type)
+    });
+    let reuses_registry = cached_registry.is_some();
+    let mut parsed_manifests = cache.from_cache::<ParsedManifests>().unwrap_or_default();
+    let (toolchain, (dependency_manifests, manifest_cache_statistics)) =
+        std::thread::scope(|scope| {
+            let dependency_manifests = scope.spawn(|| {
+                if reuses_registry {
+                    (HashMap::new(), ManifestCacheStatistics::default())
+                } else {
+                    parser::prefetch::parse_dependency_manifests_cached(
+                        &manifest,
+                        &mut parsed_manifests,
+                    )
+                }
+            });
+            let toolchain = configure_toolchain(opts, &project_config, &cache, output);
+            let dependency_manifests = dependency_manifests
+                .join()
+                .expect("Dependency manifest parser panicked");
+            toolchain.map(|toolchain| (toolchain, dependency_manifests))
+        })?;
+    let toolchain_fingerprint = toolchain
+        .borrow()
+        .fingerprint()
+        .with_context(|| "Failed to fingerprint toolchain")?;
+    let cached_registry =
+        cached_registry.filter(|cached| cached.inputs.toolchain == toolchain_fingerprint);
+    let manifest_directories = match cached_registry {
+        Some(ref cached) => cached.inputs.manifest_directories(),
+        None => {
+            // Dependencies of the parsed manifests include those whose manifests failed to parse.
+            let mut manifest_directories = BTreeSet::from([manifest.manifest.directory.clone()]);
+            for parsed in std::iter::once(&manifest).chain(dependency_manifests.values()) {
+                manifest_directories.extend(parser::prefetch::dependency_directories(parsed));
+            }
+            manifest_directories
+        }
+    };
+    // Nothing is written into the source trees from here on, as they may be read-only, except for
+    // the build directories of the CMake projects libraries are imported from.
+    let cmake_build_directories = std::iter::once(&manifest)
+        .chain(dependency_manifests.values())
+        .flat_map(parser::prefetch::cmake_build_directories)
+        .collect::<Vec<std::path::PathBuf>>();
+    utility::guard_source_trees(
+        SourceTreeGuard::new(
+            &manifest_directories
+                .iter()
+                .cloned()
+                .collect::<Vec<std::path::PathBuf>>(),
+            &build_directory(opts),
+        )
+        .with_external_build_directories(&cmake_build_directories),
+    );
+    record_build(
+        &cache,
+        opts,
+        manifest_directories,
+        &toolchain.borrow().output_extensions,
+    )?;
+    detect_generation_changes(&cache, &project_config, output)?;
+
+    let compile_timings = if opts.schedule_by_duration {
+        Some(cache.from_cache::<CompileTimings>().unwrap_or_default())
+    } else {
+        None
+    };
+    // Detected once, for both the build files and the arguments make is run with.
+    let make_features = if project_config.generator_type == GeneratorType::GNUMakefiles {
+        MakeFeatures::detect()
+    } else {
+        MakeFeatures::default()
+    };
+    let mut generator = construct_generator(
+        &project_config,
+        &toolchain,
+        compile_timings,
+        &manifest.data,
+        opts.max_memory.is_some(),
+        opts.backend,
+        make_features,
+    )?;
+    let dependency_registry = match cached_registry {
+        Some(cached) => {
+            log::debug!("Reusing the targets registered by the previous build");
+            output.status(&format!(
+                "Registered {} build targets (unchanged since the previous build)",
+                cached.registry.number_of_targets()
+            ));
+            cached.registry
+        }
+        None => {
+            let mut dependency_registry = TargetRegistry::new()
+                .with_manifests(dependency_manifests)
+                .with_archive_directory(ArchiveDirectory::new(
+                    &build_directory(opts).join(ARCHIVES_DIRECTORY_NAME),
+                ));
+            parse_and_register_dependencies(
+                &manifest,
+                output,
+                &mut dependency_registry,
+                &toolchain,
+                &opts.configuration.build_type,
+                manifest_cache_statistics,
+            )
+            .with_context(|| "An error occured when registering project dependencies")?;
+            cache
+                .cache(&parsed_manifests)
+                .with_context(|| "Failed to cache parsed manifests")?;
+            cache
+                .cache(&CachedRegistry {
+                    inputs: RegistryInputs::new(
+                        &dependency_registry,
+                        &manifest.manifest.directory,
+                        &project_config.build_type,
+                        &toolchain_fingerprint,
+                    )
+                    .with_directories(&manifest.data.test_discovery_directories),
+                    registry: dependency_registry.clone(),
+                })
+                .with_context(|| "Failed to cache registered targets")?;
+            dependency_registry
+        }
+    };
+
+    for (target, source) in
+        build_hygiene::sources_in_build_directory(&dependency_registry, &build_directory(opts))
+    {
+        output.warning(&format!(
+            "Source {} of \"{}\" is inside the build directory, where builds may overwrite or remove it",
+            source.display(),
+            target
+        ));
+    }
+
+    check_standards(&manifest, &dependency_registry, &project_config.std, output)?;
+    check_system_pch(&dependency_registry, &project_config, &toolchain, output);
+
+    if let Some(checkpoint) = cache.from_cache::<GenerationCheckpoint>() {
+        output.status(&format!(
+            "Resuming the generation of build files that failed, with {} build file(s) already written",
+            checkpoint.build_files.len()
+        ));
+    }
+    let buildfile_directory = generate_build_files(&mut generator, &dependency_registry, opts)?;
+    record_build_file_hashes(&cache, opts)?;
+    print_hardening(generator.as_ref(), &dependency_registry, output);
+    if opts.verbose >= 2 {
+        print_annotated_commands(generator.as_ref(), &dependency_registry, output);
+    }
+    clean_stale_outputs(&cache, &buildfile_directory, opts, output)?;
+
+    let targets_to_build = match targets_to_build(&cache, &buildfile_directory, opts)? {
+        Some(targets_to_build) => targets_to_build,
+        None => {
+            output.status("No targets picked. Nothing is built.");
+            return Ok(());
+        }
+    };
+    check_manifests_unchanged(&manifest, &dependency_registry, &cache)?;
+    let jobs = match opts.max_memory {
+        Some(ref budget) => Some(jobs_within_memory_budget(
+            budget,
+            &dependency_registry,
+            opts,
+            output,
+        )?),
+        None => None,
+    };
+    let outcome = build_project(
+        &buildfile_directory,
+        output,
+        opts,
+        &targets_to_build,
+        jobs,
+        make_features,
+        &logger,
+    )?;
+    if opts.schedule_by_duration {
+        record_compile_timings(&cache, &buildfile_directory)?;
+    }
+    record_build_history(
+        &cache,
+        &buildfile_directory,
+        opts,
+        &targets_to_build,
+        &outcome,
+    )?;
+    // The tests of yambs test are not run against the executables left by a previous build.
+    if !outcome.success {
+        anyhow::bail!("Build failed");
+    }
+    if opts.warnings_report || opts.max_new_warnings.is_some() || opts.update_warnings_baseline {
+        report_warnings(&cache, &buildfile_directory, opts, output)?;
+    }
+    Ok(())
+}
+
+// Make and ninja only limit the number of jobs, so the build is run with as many jobs as the
+// heaviest compile fits in the memory budget, as estimated from the size of its source. Links
+// using link-time optimization are serialized by the build files instead.
+fn jobs_within_memory_budget(
+    budget: &MemoryBudget,
+    registry: &TargetRegistry,
+    opts: &BuildOpts,
+    output: &Output,
+) -> anyhow::Result<usize> {
+    memory_budget::remove_stale_link_lock(opts.build_directory.as_path())?;
+    let max_jobs = yambs::generator::makefile::make::default_jobs();
+    let Some(heaviest) = memory_budget::heaviest_compile(registry) else {
+        return Ok(max_jobs);
+    };
+    let jobs = budget.jobs(heaviest.memory, max_jobs);
+    output.status(&format!(
+        "Running at most {} job(s) at once within {} MB of memory. The heaviest compile is {} of \"{}\", estimated at {} MB",
+        jobs,
+        budget.megabytes(),
+        heaviest.source.display(),
+        heaviest.target,
+        heaviest.megabytes()
+    ));
+    Ok(jobs)
+}
+
+// The shared precompiled standard library headers are only used with GCC, which parses the headers
+// as usual when a precompiled header does not match a translation unit.
+fn check_system_pch(
+    registry: &TargetRegistry,
+    project_config: &ProjectConfig,
+    toolchain: &Rc<RefCell<NormalizedToolchain>>,
+    output: &Output,
+) {
+    let requested = project_config.system_pch
+        || registry
+            .registry
+            .iter()
+            .any(|target| target.borrow().compiler_flags.system_pch == Some(true));
+    if !requested {
+        return;
+    }
+    let is_gcc = matches!(
+        toolchain.borrow().cxx.compiler.compiler_info.compiler_type,
+        yambs::compiler::Type::Gcc
+    );
+    if project_config.language != Language::CXX || !is_gcc {
+        output.warning(
+            "system_pch is only supported when compiling C++ with GCC. Targets are compiled without precompiled headers",
+        );
+    } else if SystemPch::default_cache_directory().is_none() {
+        output.warning(
+            "Could not locate the home directory to keep precompiled headers in. Targets are compiled without them",
+        );
+    }
+}
+
+// Dependencies compiled with a newer standard than the targets linking them are reported as the
+// `[project_config]` of the manifest asks, with a warning by default.
+fn check_standards(
+    manifest: &manifest::ParsedManifest,
+    registry: &TargetRegistry,
+    standard: &Standard,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let policy = manifest
+        .data
+        .project_config
+        .as_ref()
+        .and_then(|project_config| project_config.standard_mismatch)
+        .unwrap_or_default();
+    let mismatches = registry.standard_mismatches(standard);
+    match policy {
+        StandardMismatchPolicy::Warn => {
+            for mismatch in &mismatches {
+                output.warning(&format!("{}", mismatch));
+            }
+        }
+        StandardMismatchPolicy::Error if !mismatches.is_empty() => {
+            anyhow::bail!(
+                "Dependencies are compiled with newer standards than the targets linking them:\n{}",
+                mismatches
+                    .iter()
+                    .map(|mismatch| format!("    {}", mismatch))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            );
+        }
+        StandardMismatchPolicy::Error => {}
+    }
+    Ok(())
+}
+
+// Manifests changed after they were parsed leave the build files generated from a mix of old and
+// new content, and the registry cached as if it was resolved from the new content. The cached
+// registry is dropped, so that the next build configures the project again.
+fn check_manifests_unchanged(
+    manifest: &manifest::ParsedManifest,
+    registry: &TargetRegistry,
+    cache: &Cache,
+) -> anyhow::Result<()> {
+    let mut changed = registry.changed_manifests();
+    if manifest.manifest.has_changed() {
+        changed.insert(manifest.manifest.directory.join(YAMBS_MANIFEST_NAME));
+    }
+    if changed.is_empty() {
+        return Ok(());
+    }
+    cache
+        .remove::<CachedRegistry>()
+        .with_context(|| "Failed to remove cached targets")?;
+    anyhow::bail!(
+        "Manifest changed during configuration: {}. Re-run the build to configure the project \
+         from the current manifests.",
+        changed
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+// Build directory of `opts` as resolved by the file system, to be compared with the canonical
+// paths of manifests and sources.
+fn build_directory(opts: &BuildOpts) -> std::path::PathBuf {
+    let build_directory = opts.build_directory.as_path();
+    build_directory
+        .canonicalize()
+        .unwrap_or_else(|_| build_directory.to_path_buf())
+}
+
+// Source trees may be read-only, such as a checkout in the Nix store or a mounted snapshot, in
+// which case the build directory has to be outside of them.
+fn check_build_directory_writable(opts: &BuildOpts) -> anyhow::Result<()> {
+    let build_directory = opts.build_directory.as_path();
+    let probe = build_directory.join(format!(".yambs-write-test-{}", std::process::id()));
+    let result = std::fs::create_dir_all(build_directory)
+        .and_then(|_| std::fs::File::create(&probe))
+        .map(|_| ());
+    let _ = std::fs::remove_file(&probe);
+    if let Err(error) = result {
+        let source_directory = opts.manifest_dir.as_path();
+        if build_directory.starts_with(source_directory) {
+            anyhow::bail!(
+                "Could not write to the build directory {} in the source tree {}, which may be read-only ({}). \
+                 Build into a directory outside of it with -b",
+                build_directory.display(),
+                source_directory.display(),
+                error
+            );
+        }
+        anyhow::bail!(
+            "Could not write to the build directory {} ({})",
+            build_directory.display(),
+            error
+        );
+    }
+    Ok(())
+}
+
+// Fails before anything is written when the build directory is the source directory.
+fn enforce_out_of_source(opts: &BuildOpts) -> anyhow::Result<()> {
+    let source_directory = opts.manifest_dir.as_path();
+    let source_directory = source_directory
+        .canonicalize()
+        .unwrap_or_else(|_| source_directory.to_path_buf());
+    if build_directory(opts) == source_directory {
+        anyhow::bail!(
+            "Refusing to generate build files in the source directory {} (--enforce-out-of-source). \
+             Give them a directory of their own with -b or build_dir in the manifest",
+            source_directory.display()
+        );
+    }
+    Ok(())
+}
+
+// Warns about a build directory inside the source tree that git does not ignore, whose generated
+// files would otherwise end up in commits, and ignores it when asked to. Build directories git
+// ignores, or that are not in a repository, are not warned about.
+fn check_build_directory(
+    opts: &BuildOpts,
+    source_directory: &Path,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let build_directory = build_directory(opts);
+    if !build_directory.starts_with(source_directory) {
+        return Ok(());
+    }
+    let in_source = build_directory == source_directory;
+    if opts.write_gitignore {
+        if in_source {
+            output.warning("--write-gitignore is ignored, as it would ignore the sources as well");
+        } else {
+            if let Some(gitignore) = build_hygiene::write_gitignore(&build_directory)? {
+                output.status(&format!("Wrote {}", gitignore.display()));
+            }
+            return Ok(());
+        }
+    }
+    let makefile = build_directory
+        .join(opts.configuration.build_type.to_string())
+        .join(MAKEFILE_NAME);
+    if build_hygiene::is_ignored_by_git(source_directory, &makefile) != Some(false) {
+        return Ok(());
+    }
+    if in_source {
+        output.warning(
+            "Build files are generated in the source directory and not ignored by git, so they may end up in commits. \
+             Give them a directory of their own with -b",
+        );
+    } else {
+        output.warning(&format!(
+            "Build directory {} is inside the source tree but not ignored by git, so generated files may end up in commits. \
+             Pass --write-gitignore to ignore it",
+            build_directory.display()
+        ));
+    }
+    Ok(())
+}
+
+// Records the configuration of the build for repro bundles, before anything that may fail to build.
+fn record_build(
+    cache: &Cache,
+    opts: &BuildOpts,
+    manifest_directories: BTreeSet<std::path::PathBuf>,
+    output_extensions: &OutputExtensions,
+) -> anyhow::Result<()> {
+    let manifest_directory = opts.manifest_dir.as_path();
+    let toolchain_file = locate_toolchain_file(opts.toolchain_file.as_deref(), manifest_directory);
+    let record = BuildRecord {
+        command_line: std::env::args().collect(),
+        preset: opts.preset.clone(),
+        configuration: opts.configuration.clone(),
+        toolchain_file,
+        manifest_directory: manifest_directory.to_path_buf(),
+        manifest_directories: manifest_directories.into_iter().collect(),
+        toolchain_environment: TOOLCHAIN_ENVIRONMENT_VARIABLES
+            .iter()
+            .filter_map(|name| Some((name.to_string(), std::env::var(name).ok()?)))
+            .collect(),
+        output_extensions: output_extensions.clone(),
+    };
+    cache
+        .cache(&record)
+        .with_context(|| "Failed to cache build record")?;
+    Ok(())
+}
+
+// Removes outputs of the previous generation that the targets no longer produce, in both the
+// build tree of the target and the one of the host.
+fn clean_stale_outputs(
+    cache: &Cache,
+    buildfile_directory: &Path,
+    opts: &BuildOpts,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let mut expected_outputs = cache.from_cache::<ExpectedOutputs>().unwrap_or_default();
+    let host_buildfile_directory = opts
+        .build_directory
+        .as_path()
+        .join(HOST_BUILD_DIRECTORY_NAME)
+        .join(opts.configuration.build_type.to_string());
+    for directory in [buildfile_directory, host_buildfile_directory.as_path()] {
+        let progress_document = match ProgressDocument::from_directory(directory) {
+            Ok(progress_document) => progress_document,
+            Err(_) => continue,
+        };
+        let stale = expected_outputs.update(
+            directory,
+            ExpectedOutputs::from_progress_document(&progress_document),
+        );
+        for removed in remove_stale_outputs(&stale)? {
+            output.warning(&format!("Removed stale output {}", removed.display()));
+        }
+    }
+    cache
+        .cache(&expected_outputs)
+        .with_context(|| "Failed to cache expected outputs")?;
+    Ok(())
+}
+
+// Compares the warnings of the objects in the build trees with the baseline of the build type,
+// which is created by the first report and ratcheted down as warnings are fixed.
+fn report_warnings(
+    cache: &Cache,
+    buildfile_directory: &Path,
+    opts: &BuildOpts,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let host_buildfile_directory = opts
+        .build_directory
+        .as_path()
+        .join(HOST_BUILD_DIRECTORY_NAME)
+        .join(opts.configuration.build_type.to_string());
+    let mut objects = Vec::new();
+    for directory in [buildfile_directory, host_buildfile_directory.as_path()] {
+        if let Ok(progress_document) = ProgressDocument::from_directory(directory) {
+            objects.extend(
+                progress_document
+                    .targets
+                    .into_iter()
+                    .flat_map(|target| target.object_files),
+            );
+        }
+    }
+    let report = WarningsReport::from_objects(&objects)?;
+    let report_path = buildfile_directory.join(WARNINGS_REPORT_FILE_NAME);
+    std::fs::write(&report_path, serde_json::to_string_pretty(&report)?)
+        .map_err(|e| FsError::CreateFile(report_path.clone(), e))?;
+
+    output.status(&format!("{} warnings", report.warnings.len()));
+    for (flag, count) in report.count_by_flag() {
+        output.status(&format!("  {:>5} {}", count, flag));
+    }
+    output.status(&format!("Warnings written to {}", report_path.display()));
+
+    let build_type = opts.configuration.build_type.to_string();
+    let mut baselines = cache.from_cache::<WarningsBaselines>().unwrap_or_default();
+    let baseline = match baselines.baselines.get(&build_type) {
+        Some(baseline) if !opts.update_warnings_baseline => baseline.clone(),
+        _ => {
+            output.status("Using the current warnings as the warnings baseline");
+            report.clone()
+        }
+    };
+    let new_warnings = report.new_warnings(&baseline);
+    for warning in &new_warnings {
+        output.warning(&format!(
+            "New warning: {}:{}: {}{}",
+            warning.file.display(),
+            warning.line,
+            warning.message,
+            warning
+                .flag
+                .as_ref()
+                .map(|flag| format!(" [{}]", flag))
+                .unwrap_or_default()
+        ));
+    }
+    output.status(&format!(
+        "{} new warnings compared to the baseline of {} warnings",
+        new_warnings.len(),
+        baseline.warnings.len()
+    ));
+
+    if let Some(max_new_warnings) = opts.max_new_warnings {
+        if new_warnings.len() > max_new_warnings {
+            anyhow::bail!(
+                "{} new warnings exceed the maximum of {} new warnings",
+                new_warnings.len(),
+                max_new_warnings
+            );
+        }
+    }
+    baselines
+        .baselines
+        .insert(build_type, report.ratchet(&baseline));
+    cache
+        .cache(&baselines)
+        .with_context(|| "Failed to cache warnings baseline")?;
+    Ok(())
+}
+
+fn record_compile_timings(cache: &Cache, buildfile_directory: &Path) -> anyhow::Result<()> {
+    let mut compile_timings = cache.from_cache::<CompileTimings>().unwrap_or_default();
+    compile_timings
+        .merge_log_file(buildfile_directory)
+        .with_context(|| "Failed to read compile timings")?;
+    cache
+        .cache(&compile_timings)
+        .with_context(|| "Failed to cache compile timings")?;
+    Ok(())
+}
+
+fn do_test(opts: &TestOpts, output: &Output) -> anyhow::Result<()> {
+    if let (Some(index), Some(count)) = (opts.shard_index, opts.shard_count) {
+        if index >= count {
+            anyhow::bail!(
+                "--shard-index {} is out of range for --shard-count {}. Shards are counted from 0",
+                index,
+                count
+            );
+        }
+    }
+    do_build(&opts.build_opts, output)?;
+
+    let manifest_path = locate_manifest(&opts.build_opts.manifest_dir)?;
+    let manifest = parser::parse(&manifest_path).with_context(|| "Failed to parse manifest")?;
+    for name in &opts.tests {
+        if !manifest.data.tests.iter().any(|test| &test.name == name) {
+            anyhow::bail!("No test named \"{}\" in {}", name, manifest_path.display());
+        }
+    }
+    let tests = manifest
+        .data
+        .tests
+        .iter()
+        .filter(|test| opts.tests.is_empty() || opts.tests.contains(&test.name))
+        .collect::<Vec<_>>();
+    for test in &tests {
+        for dependency in &test.depends_on {
+            let is_target = manifest.data.targets.iter().any(|target| match target {
+                targets::Target::Executable(executable) => &executable.name == dependency,
+                targets::Target::Library(library) => &library.name == dependency,
+            });
+            if !is_target {
+                anyhow::bail!(
+                    "Test \"{}\" depends on target \"{}\", which is not in {}",
+                    test.name,
+                    dependency,
+                    manifest_path.display()
+                );
+            }
+        }
+    }
+
+    let tests = match (opts.shard_index, opts.shard_count) {
+        (Some(index), Some(count)) => {
+            let total = tests.len();
+            let tests = test_runner::shard(tests, index, count);
+            output.status(&format!(
+                "Running shard {} of {}: {} of {} tests",
+                index,
+                count,
+                tests.len(),
+                total
+            ));
+            tests
+        }
+        _ => tests,
+    };
+
+    let executable_directory = opts
+        .build_opts
+        .build_directory
+        .as_path()
+        .join(opts.build_opts.configuration.build_type.to_string());
+    let cache = Cache::new(opts.build_opts.build_directory.as_path())?;
+    let output_extensions = cache
+        .from_cache::<BuildRecord>()
+        .map(|record| record.output_extensions)
+        .unwrap_or_default();
+    let runner = TestRunner::new(&executable_directory).with_output_extensions(output_extensions);
+    let mut results_cache = if opts.cache_results {
+        cache.from_cache::<TestResultsCache>().unwrap_or_default()
+    } else {
+        TestResultsCache::default()
+    };
+    // Files linked by the targets of the build tree, which the tests depending on them are
+    // hashed with.
+    let target_outputs = ProgressDocument::from_directory(&executable_directory)
+        .map(|progress_document| {
+            progress_document
+                .targets
+                .into_iter()
+                .filter_map(|target| Some((target.target, target.output?)))
+                .collect::<HashMap<String, std::path::PathBuf>>()
+        })
+        .unwrap_or_default();
+
+    let mut failed = Vec::new();
+    let mut report = TestReport::default();
+    for test in &tests {
+        let hash = if opts.cache_results {
+            let inputs = test
+                .depends_on
+                .iter()
+                .filter_map(|dependency| target_outputs.get(dependency).cloned())
+                .collect::<Vec<std::path::PathBuf>>();
+            runner.hash(test, &inputs)?
+        } else {
+            None
+        };
+        if hash.is_some() && results_cache.passed.get(&test.name) == hash.as_ref() {
+            output.status(&format!("Test {} ... cached pass", test.name));
+            report.add_cached(&test.name);
+            continue;
+        }
+
+        let result = runner.run(test)?;
+        report.add_result(&result);
+        let duration = format_duration(result.duration.as_millis() as u64);
+        if result.is_flaky() {
+            // Flaky passes are not cached, so that the test is run again by the next build.
+            output.warning(&format!(
+                "Test {} ... ok ({}), flaky: passed on attempt {} of {}",
+                result.name,
+                duration,
+                result.attempts,
+                test.retries + 1
+            ));
+            results_cache.passed.remove(&result.name);
+        } else if result.passed {
+            output.status(&format!("Test {} ... ok ({})", result.name, duration));
+            if let Some(hash) = hash {
+                results_cache.passed.insert(result.name, hash);
+            }
+        } else {
+            output.error(&format!("Test {} ... FAILED ({})", result.name, duration));
+            if let Some(ref reason) = result.reason {
+                output.error(reason);
+            }
+            results_cache.passed.remove(&result.name);
+            failed.push(result.name);
+        }
+    }
+    if opts.cache_results {
+        cache
+            .cache(&results_cache)
+            .with_context(|| "Failed to cache test results")?;
+    }
+    let report_path = executable_directory.join(TEST_REPORT_FILE_NAME);
+    std::fs::write(&report_path, serde_json::to_string_pretty(&report)?)
+        .map_err(|e| FsError::CreateFile(report_path.clone(), e))?;
+
+    let details = [
+        (report.count(TestOutcome::Cached), "cached"),
+        (report.count(TestOutcome::Flaky), "flaky"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, outcome)| format!("{} {}", count, outcome))
+    .collect::<Vec<String>>();
+    let passed = if details.is_empty() {
+        format!("{} passed", tests.len() - failed.len())
+    } else {
+        format!(
+            "{} passed ({})",
+            tests.len() - failed.len(),
+            details.join(", ")
+        )
+    };
+    output.status(&format!("Test result: {}, {} failed", passed, failed.len()));
+    if !failed.is_empty() {
+        anyhow::bail!("Failed tests: {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+fn do_remake(opts: &RemakeOpts) -> anyhow::Result<()> {
+    let log_file = &opts.build_directory.as_path().join(logger::YAMBS_LOG_FILE);
+    let log_fh = std::fs::File::open(log_file).context("Failed to find log file")?;
+    let mut reader = std::io::BufReader::new(log_fh);
+    let mut line = String::new();
+    let line_length = reader
+        .read_line(&mut line)
+        .context("Failed to read line from log file")?;
+    if line_length == 0 {
+        anyhow::bail!("Could not find first line of log file");
+    }
+
+    let command_line_regex = Regex::new(r"Command line:\s(?P<cmd>.*)").unwrap();
+    let caps = command_line_regex.captures(&line).unwrap();
+    let invoked_command = caps.name("cmd").unwrap().as_str();
+    println!("{}", invoked_command);
+    Ok(())
+}
+
+fn do_cache(opts: &CacheOpts, output: &Output) -> anyhow::Result<()> {
+    match opts.command {
+        CacheCommand::Info(ref info_opts) => {
+            let build_directory = info_opts.build_directory.as_path();
+            if !build_directory.join(YAMBS_CACHE_DIRECTORY_NAME).is_dir() {
+                anyhow::bail!("No cache found in {}", build_directory.display());
+            }
+            let cache = Cache::new(build_directory)?;
+            output.status(&format!("Cache directory: {}", cache.directory().display()));
+            match cache.from_cache::<ToolchainFingerprint>() {
+                Some(fingerprint) => {
+                    output.status("Toolchain fingerprint:");
+                    output.status_without_prefix(&fingerprint.to_string());
+                }
+                None => output.warning("No toolchain fingerprint stored in cache"),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn do_convert_mmk(opts: &ConvertMmkOpts, output: &Output) -> anyhow::Result<()> {
+    let manifests = MmkConverter::new(opts.force)
+        .convert(&opts.directory)
+        .with_context(|| {
+            format!(
+                "Failed to convert MMK files in {}",
+                opts.directory.display()
+            )
+        })?;
+    for converted in manifests {
+        output.status(&format!("Wrote {}", converted.manifest.display()));
+        for keyword in &converted.ignored_keywords {
+            output.warning(&format!(
+                "{} has no equivalent in {} and was ignored",
+                keyword, YAMBS_MANIFEST_NAME
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn do_clean(opts: &CleanOpts, output: &Output) -> anyhow::Result<()> {
+    let build_directory = opts.build_directory.as_path();
+    if opts.all {
+        clean::clean_all(build_directory)?;
+        output.status(&format!("Removed {}", build_directory.display()));
+    } else if let Some(ref target) = opts.target {
+        let removed = clean::clean_target(build_directory, target)?;
+        output.status(&format!(
+            "Removed {} file(s) of target \"{}\"",
+            removed.len(),
+            target
+        ));
+    } else {
+        clean::clean_build_trees(build_directory)?;
+        output.status(&format!(
+            "Removed the build trees of {}",
+            build_directory.display()
+        ));
+    }
+    Ok(())
+}
+
+fn do_init(opts: &InitOpts, output: &Output) -> anyhow::Result<()> {
+    let name = match opts.name {
+        Some(ref name) => name.clone(),
+        None => {
+            let directory = match opts.directory.canonicalize() {
+                Ok(directory) => directory,
+                Err(_) => std::env::current_dir()?.join(&opts.directory),
+            };
+            directory
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        }
+    };
+    let kind = if opts.lib {
+        ProjectKind::Library
+    } else {
+        ProjectKind::Executable
+    };
+    let written = ProjectSkeleton::new(&name, kind)?
+        .create(&opts.directory)
+        .with_context(|| format!("Failed to create project in {}", opts.directory.display()))?;
+    for file in written {
+        output.status(&format!("Wrote {}", file.display()));
+    }
+    Ok(())
+}
+
+fn do_report(opts: &ReportOpts, output: &Output) -> anyhow::Result<()> {
+    let build_directory = opts.build_directory.as_path();
+    if !build_directory.join(YAMBS_CACHE_DIRECTORY_NAME).is_dir() {
+        anyhow::bail!(
+            "{} has not been built by yambs. Build the project first.",
+            build_directory.display()
+        );
+    }
+    let history = Cache::new(build_directory)?
+        .from_cache::<BuildHistory>()
+        .unwrap_or_default();
+    let builds = history.recent(&opts.build_type.to_string(), opts.last);
+    if builds.is_empty() && !opts.json {
+        output.status(&format!(
+            "No {} builds recorded in {}",
+            opts.build_type.to_string(),
+            build_directory.display()
+        ));
+        return Ok(());
+    }
+    let report = BuildReport::new(&builds);
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &report)?;
+        writeln!(stdout)?;
+    } else {
+        print!("{}", report.to_text());
+    }
+    Ok(())
+}
+
+fn do_graph(opts: &GraphOpts, output: &Output) -> anyhow::Result<()> {
+    let build_directory = opts.build_directory.as_path();
+    let buildfile_directory = build_directory.join(opts.build_type.to_string());
+    let progress_document =
+        ProgressDocument::from_directory(&buildfile_directory).with_context(|| {
+            format!(
+                "No build files found in {}. Build the project first.",
+                buildfile_directory.display()
+            )
+        })?;
+
+    let compile_timings =
+        if opts.critical_path && build_directory.join(YAMBS_CACHE_DIRECTORY_NAME).is_dir() {
+            Cache::new(build_directory)?
+                .from_cache::<CompileTimings>()
+                .unwrap_or_default()
+        } else {
+            CompileTimings::new()
+        };
+    let graph = BuildGraph::new(&progress_document, &compile_timings);
+
+    let critical_path = if opts.critical_path {
+        if !graph.has_durations() {
+            output.warning(
+                "No durations recorded for the targets. Build with --schedule-by-duration to record them.",
+            );
+        }
+        Some(graph.critical_path())
+    } else {
+        None
+    };
+
+    let graph_file = opts
+        .output
+        .clone()
+        .unwrap_or_else(|| buildfile_directory.join(GRAPH_FILE_NAME));
+    std::fs::write(&graph_file, graph.to_dot(critical_path.as_deref()))
+        .map_err(|e| FsError::CreateFile(graph_file.clone(), e))?;
+    output.status(&format!("Wrote {}", graph_file.display()));
+
+    if let Some(critical_path) = critical_path {
+        if graph.has_durations() {
+            output.status(&format!(
+                "Critical path ({}): {}",
+                format_duration(critical_path.iter().filter_map(|node| node.duration).sum()),
+                critical_path
+                    .iter()
+                    .map(|node| node.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(" -> ")
+            ));
+            output.status("Slowest targets:");
+            for node in graph.bottlenecks(BOTTLENECK_REPORT_LENGTH) {
+                output.status_without_prefix(&format!(
+                    "  {}: {}",
+                    node.name,
+                    format_duration(node.duration.unwrap_or(0))
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn do_why(opts: &WhyOpts, output: &Output) -> anyhow::Result<()> {
+    let buildfile_directory = opts
+        .build_directory
+        .as_path()
+        .join(opts.build_type.to_string());
+    let progress_document =
+        ProgressDocument::from_directory(&buildfile_directory).with_context(|| {
+            format!(
+                "No build files found in {}. Build the project first.",
+                buildfile_directory.display()
+            )
+        })?;
+    let graph = BuildGraph::new(&progress_document, &CompileTimings::new());
+    if !graph.contains(&opts.name) {
+        anyhow::bail!(
+            "No target or external package named \"{}\" in {}",
+            opts.name,
+            buildfile_directory.display()
+        );
+    }
+
+    let paths = graph.paths_to(&opts.name);
+    print!("{}", format_paths_as_tree(&paths));
+    if let Some(ref dot_file) = opts.dot {
+        std::fs::write(dot_file, paths_to_dot(&opts.name, &paths))
+            .map_err(|e| FsError::CreateFile(dot_file.clone(), e))?;
+        output.status(&format!("Wrote {}", dot_file.display()));
+    }
+    Ok(())
+}
+
+fn do_unused_deps(opts: &UnusedDepsOpts, output: &Output) -> anyhow::Result<()> {
+    let buildfile_directory = opts
+        .build_directory
+        .as_path()
+        .join(opts.build_type.to_string());
+    let progress_document =
+        ProgressDocument::from_directory(&buildfile_directory).with_context(|| {
+            format!(
+                "No build files found in {}. Build the project first.",
+                buildfile_directory.display()
+            )
+        })?;
+    let report = yambs::dependency_usage::unused_dependencies(&progress_document.targets);
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &report)?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+    print!("{}", report.to_text());
+    if report.unused.is_empty() {
+        output.status("Every dependency is linked from by the targets depending on it");
+    } else {
+        output.status(&format!(
+            "{} dependencies are not linked from by the targets depending on them",
+            report.unused.len()
+        ));
+    }
+    Ok(())
+}
+
+// Documents the targets resolved by the most recent build, as cached in the build directory.
+fn do_docs(opts: &DocsOpts, output: &Output) -> anyhow::Result<()> {
+    let build_directory = opts.build_directory.as_path();
+    let cache = if build_directory.join(YAMBS_CACHE_DIRECTORY_NAME).is_dir() {
+        Some(Cache::new(build_directory)?)
+    } else {
+        None
+    };
+    let (record, cached_registry) = match cache {
+        Some(ref cache) => (
+            cache.from_cache::<BuildRecord>(),
+            cache.from_cache::<CachedRegistry>(),
+        ),
+        None => (None, None),
+    };
+    let (record, cached_registry) = record.zip(cached_registry).ok_or_else(|| {
+        anyhow::anyhow!(
+            "No build found in {}. Build the project first.",
+            build_directory.display()
+        )
+    })?;
+
+    let mut targets = Vec::new();
+    for target in &cached_registry.registry.registry {
+        let target = target.borrow();
+        let selected = if opts.targets.is_empty() {
+            target.manifest.directory == record.manifest_directory
+        } else {
+            opts.targets.contains(&target.name())
+        };
+        if selected && target.build_for == BuildFor::Target {
+            targets.push(docs::TargetDocs::from_target(&target));
+        }
+    }
+    if let Some(missing) = opts
+        .targets
+        .iter()
+        .find(|name| !targets.iter().any(|target| &target.name == *name))
+    {
+        anyhow::bail!(
+            "No target named \"{}\" in {}",
+            missing,
+            build_directory.display()
+        );
+    }
+
+    let tool = opts
+        .tool
+        .clone()
+        .unwrap_or_else(|| std::path::PathBuf::from(docs::DEFAULT_DOCS_TOOL));
+    let tool = if tool.components().count() > 1 {
+        tool
+    } else {
+        let mut search_options = FindProgramOptions::new();
+        search_options.with_path_env();
+        find_program(&tool, search_options).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Could not find {} in PATH. Install it, or give the tool to run with --tool",
+                tool.display()
+            )
+        })?
+    };
+    let prefix = opts
+        .prefix
+        .clone()
+        .unwrap_or_else(|| std::path::PathBuf::from(DEFAULT_INSTALL_PREFIX));
+
+    for target in &targets {
+        if target.inputs.is_empty() {
+            output.warning(&format!(
+                "Target {} has no headers to document",
+                target.name
+            ));
+            continue;
+        }
+        let output_directory = target.output_directory(build_directory);
+        std::fs::create_dir_all(&output_directory)
+            .map_err(|e| FsError::CreateDirectory(output_directory.clone(), e))?;
+        let doxyfile = output_directory.join(docs::DOXYFILE_NAME);
+        std::fs::write(&doxyfile, target.doxyfile(&output_directory))
+            .map_err(|e| FsError::CreateFile(doxyfile.clone(), e))?;
+        let status = Environment::new()
+            .command(&tool)
+            .arg(&doxyfile)
+            .current_dir(&output_directory)
+            .status()
+            .with_context(|| format!("Failed to run {}", tool.display()))?;
+        if !status.success() {
+            anyhow::bail!(
+                "{} failed to document target {} with {}",
+                tool.display(),
+                target.name,
+                doxyfile.display()
+            );
+        }
+        let html_directory = output_directory.join("html");
+        output.status(&format!(
+            "Documented {} in {}",
+            target.name,
+            html_directory.display()
+        ));
+
+        if opts.install {
+            let relative_prefix = prefix.strip_prefix("/").unwrap_or(&prefix);
+            let destination = match opts.destdir {
+                Some(ref destdir) => destdir.join(relative_prefix),
+                None => prefix.clone(),
+            }
+            .join("share")
+            .join("doc")
+            .join(&target.name);
+            docs::copy_directory(&html_directory, &destination).with_context(|| {
+                format!(
+                    "Failed to install the documentation of {} to {}",
+                    target.name,
+                    destination.display()
+                )
+            })?;
+            output.status(&format!("Installed to {}", destination.display()));
+        }
+    }
+    Ok(())
+}
+
+// The sample programs are compiled in a temporary directory, as there is no build directory.
+fn do_doctor(opts: &DoctorOpts, output: &Output) -> anyhow::Result<()> {
+    let toolchain = locate_toolchain(opts.toolchain_file.as_deref(), opts.manifest_dir.as_path())?;
+    let test_dir = std::env::temp_dir().join(format!("yambs-doctor-{}", std::process::id()));
+    let compilers: [(&str, &dyn Compiler, &Path); 2] = [
+        (
+            "CXX",
+            &toolchain.cxx.compiler,
+            &toolchain.cxx.compiler.compiler_exe,
+        ),
+        (
+            "CC",
+            &toolchain.cc.compiler,
+            &toolchain.cc.compiler.compiler_exe,
+        ),
+    ];
+    let result = compilers
+        .iter()
+        .try_for_each(|(variable, compiler, compiler_exe)| {
+            let cached = evaluate_compiler_cached(*compiler, &test_dir, opts.recheck)
+                .with_context(|| {
+                    format!("{} {} does not work", variable, compiler_exe.display())
+                })?;
+            output.status(&format!(
+                "{} {}: ok{}",
+                variable,
+                compiler_exe.display(),
+                if cached { " (passed before)" } else { "" }
+            ));
+            anyhow::Ok(())
+        });
+    let _ = std::fs::remove_dir_all(&test_dir);
+    result
+}
+
+// Searches the search paths of the toolchain file, then PATH. Nothing is logged, as there is no
+// build directory to write the log to.
+fn do_find_tool(opts: &FindToolOpts) -> anyhow::Result<()> {
+    let toolchain_file = locate_toolchain_file(opts.toolchain_file.as_deref(), Path::new(""));
+    let search_paths = match toolchain_file {
+        Some(ref toolchain_file) => {
+            let content = std::fs::read_to_string(toolchain_file)
+                .map_err(|e| FsError::ReadFromFile(toolchain_file.clone(), e))?;
+            toolchain::search_paths(&content).with_context(|| {
+                format!(
+                    "Failed to parse toolchain file {}",
+                    toolchain_file.display()
+                )
+            })?
+        }
+        None => Vec::new(),
+    };
+
+    let mut search_options = FindProgramOptions::new();
+    search_options
+        .with_path_env()
+        .search_directories_first(&search_paths);
+    if let Some(ref requirement) = opts.version {
+        search_options.version_requirement(requirement.clone());
+    }
+    let directories = search_options.directories().to_vec();
+    match find_program(&opts.name, search_options) {
+        Some(program) => {
+            match yambs::program_version(&program) {
+                Some(version) => println!("{} (version {})", program.display(), version),
+                None => println!("{}", program.display()),
+            }
+            Ok(())
+        }
+        None => {
+            let requirement = opts
+                .version
+                .as_ref()
+                .map(|requirement| format!(" matching {}", requirement))
+                .unwrap_or_default();
+            anyhow::bail!(
+                "Could not find {}{} in any of\n{}",
+                opts.name.display(),
+                requirement,
+                directories
+                    .iter()
+                    .map(|directory| format!("    {}", directory.display()))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            )
+        }
+    }
+}
+
+// Configures the project as a build would, up to where the build files are generated, so that it
+// fails for the same manifests and toolchains. Nothing is written to the build directory, and the
+// targets and toolchain cached in it by previous builds are not used. The archives unpacked into it
+// are, as are the evaluations of compilers that passed before, which are cached outside of it.
+fn do_check(opts: &CheckOpts, output: &Output) -> anyhow::Result<()> {
+    initialize_preset_variables_with(
+        &opts.build_directory,
+        &opts.manifest_dir,
+        &opts.build_type,
+        opts.toolchain_file.as_deref(),
+    )?;
+    let manifest_path = locate_manifest(&opts.manifest_dir)?;
+    let manifest = parser::parse(&manifest_path).with_context(|| "Failed to parse manifest")?;
+    let (std, _) = project_standard(opts.standard.as_ref(), &manifest)?;
+    let language = manifest
+        .data
+        .project_config
+        .as_ref()
+        .and_then(|pc| pc.language.clone())
+        .unwrap_or(Language::CXX);
+    std.verify_from_language(&language)
+        .with_context(|| "The standard does not fit the language of the manifest")?;
+
+    let (toolchain, dependency_manifests) = std::thread::scope(|scope| {
+        let dependency_manifests =
+            scope.spawn(|| parser::prefetch::parse_dependency_manifests(&manifest));
+        let toolchain = check_toolchain(opts, &language);
+        let dependency_manifests = dependency_manifests
+            .join()
+            .expect("Dependency manifest parser panicked");
+        toolchain.map(|toolchain| (toolchain, dependency_manifests))
+    })?;
+    // Archives of dependencies are not downloaded, as nothing is written to the build directory.
+    let mut registry = TargetRegistry::new()
+        .with_manifests(dependency_manifests)
+        .with_archive_directory(ArchiveDirectory::read_only(
+            &opts.build_directory.as_path().join(ARCHIVES_DIRECTORY_NAME),
+        ));
+    parse_and_register_dependencies(
+        &manifest,
+        output,
+        &mut registry,
+        &toolchain,
+        &opts.build_type,
+        ManifestCacheStatistics::default(),
+    )
+    .with_context(|| "An error occured when registering project dependencies")?;
+    check_standards(&manifest, &registry, &std, output)?;
+
+    let manifests = registry
+        .registry
+        .iter()
+        .map(|target| target.borrow().manifest.directory.clone())
+        .chain(std::iter::once(manifest.manifest.directory.clone()))
+        .collect::<BTreeSet<std::path::PathBuf>>();
+    output.status(&format!(
+        "No problems found in {} target(s) of {} manifest(s)",
+        registry.number_of_targets(),
+        manifests.len()
+    ));
+    Ok(())
+}
+
+// Locates the toolchain and checks that the compiler of `language` works. The sample program is
+// compiled in a temporary directory, and only when the compiler has not passed the check before.
+fn check_toolchain(
+    opts: &CheckOpts,
+    language: &Language,
+) -> anyhow::Result<Rc<RefCell<NormalizedToolchain>>> {
+    let toolchain = locate_toolchain(opts.toolchain_file.as_deref(), opts.manifest_dir.as_path())?;
+    let test_dir = std::env::temp_dir().join(format!("yambs-check-{}", std::process::id()));
+    let result = match language {
+        Language::CXX => evaluate_compiler_cached(&toolchain.cxx.compiler, &test_dir, false),
+        Language::C => evaluate_compiler_cached(&toolchain.cc.compiler, &test_dir, false),
+    };
+    let _ = std::fs::remove_dir_all(&test_dir);
+    result.with_context(|| "The compiler of the toolchain does not work")?;
+    Ok(Rc::new(RefCell::new(toolchain)))
+}
+
+// Nothing is logged, as the log is written to the build directory.
+fn do_introspect(opts: &IntrospectOpts) -> anyhow::Result<()> {
+    initialize_preset_variables_with(
+        &opts.build_directory,
+        &opts.manifest_dir,
+        &opts.build_type,
+        None,
+    )?;
+    let manifest_path = opts.manifest_dir.as_path().join(YAMBS_MANIFEST_NAME);
+    let content = if opts.stdin_manifest {
+        let mut content = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut content)
+            .with_context(|| "Failed to read manifest from stdin")?;
+        content
+    } else {
+        std::fs::read(&manifest_path)
+            .map_err(|e| FsError::ReadFromFile(manifest_path.clone(), e))?
+    };
+    let manifest_data = parser::parse_content(&content, &manifest_path)
+        .with_context(|| "Failed to parse manifest")?;
+    writeln!(
+        std::io::stdout().lock(),
+        "{}",
+        parser::introspect(&manifest_data)
+    )?;
+    Ok(())
+}
+
+fn do_install(opts: &InstallOpts, output: &Output) -> anyhow::Result<()> {
+    let buildfile_directory = opts
+        .build_directory
+        .as_path()
+        .join(opts.build_type.to_string());
+    if !buildfile_directory.join(MAKEFILE_NAME).is_file() {
+        anyhow::bail!(
+            "Could not find build files in {}. Build the project with yambs build first.",
+            buildfile_directory.display()
+        );
+    }
+    let prefix = opts
+        .prefix
+        .clone()
+        .unwrap_or_else(|| std::path::PathBuf::from(DEFAULT_INSTALL_PREFIX));
+    let mut make_args = vec![
+        "install".to_string(),
+        format!("PREFIX={}", prefix.display()),
+    ];
+    if let Some(ref destdir) = opts.destdir {
+        make_args.push(format!("DESTDIR={}", destdir.display()));
+    }
+    let exit_status = run_make(&make_args, &buildfile_directory)?.wait_and_log(output);
+    if !exit_status.is_some_and(|status| status.success()) {
+        anyhow::bail!("Failed to install to {}", prefix.display());
+    }
+    output.status(&format!("Installed to {}", prefix.display()));
+    Ok(())
+}
+
+fn do_repro_bundle(opts: &ReproBundleOpts, output: &Output) -> anyhow::Result<()> {
+    let build_directory = opts.build_directory.as_path();
+    if !build_directory.join(YAMBS_CACHE_DIRECTORY_NAME).is_dir() {
+        anyhow::bail!(
+            "No build found in {}. Build the project first.",
+            build_directory.display()
+        );
+    }
+    let cache = Cache::new(build_directory)?;
+    let redactor = Redactor::from_environment();
+    let staging_directory =
+        std::env::temp_dir().join(format!("yambs-repro-bundle-{}", std::process::id()));
+    let bundle = ReproBundle::new(&staging_directory, redactor.clone())?;
+
+    let is_build_file = |file: &Path| {
+        file.extension()
+            .is_some_and(|extension| extension == BUILD_FILE_EXTENSION)
+            || file.file_name().is_some_and(|name| {
+                name == MAKEFILE_NAME
+                    || name == NINJA_BUILD_FILE_NAME
+                    || name == progress::PROGRESS_FILE_NAME
+            })
+    };
+    let build_trees = match cache.from_cache::<BuildRecord>() {
+        Some(record) => {
+            bundle.add_text(
+                Path::new("configuration.json"),
+                &serde_json::to_string_pretty(&record)?,
+            )?;
+            for directory in &record.manifest_directories {
+                for file_name in &[YAMBS_MANIFEST_NAME, PRESETS_FILE_NAME] {
+                    let file = directory.join(file_name);
+                    if file.is_file() {
+                        bundle.add_file(&bundle_path("manifests", &file), &file)?;
+                    }
+                }
+            }
+            if let Some(ref toolchain_file) = record.toolchain_file {
+                if toolchain_file.is_file() {
+                    bundle.add_file(&bundle_path("toolchain", toolchain_file), toolchain_file)?;
+                }
+            }
+            let build_type = record.configuration.build_type.to_string();
+            vec![
+                std::path::PathBuf::from(&build_type),
+                Path::new(HOST_BUILD_DIRECTORY_NAME).join(&build_type),
+                std::path::PathBuf::from(INCLUDE_DIRECTORY_NAME),
+            ]
+        }
+        None => {
+            output.warning(
+                "No build recorded in the build directory. The bundle only contains build files and logs.",
+            );
+            vec![std::path::PathBuf::new()]
+        }
+    };
+    for tree in build_trees {
+        let directory = build_directory.join(&tree);
+        if directory.is_dir() {
+            bundle.add_directory(&bundle_path("build", &tree), &directory, &is_build_file)?;
+        }
+    }
+
+    if let Some(fingerprint) = cache.from_cache::<ToolchainFingerprint>() {
+        bundle.add_text(Path::new("toolchain.txt"), &format!("{}\n", fingerprint))?;
+    }
+    let log_file = build_directory.join(logger::YAMBS_LOG_FILE);
+    if log_file.is_file() {
+        bundle.add_file(Path::new(logger::YAMBS_LOG_FILE), &log_file)?;
+    }
+    bundle.add_text(Path::new("environment.txt"), &redactor.environment())?;
+
+    let archive = opts
+        .output
+        .clone()
+        .unwrap_or_else(|| build_directory.join(REPRO_BUNDLE_FILE_NAME));
+    bundle.write_archive(&archive)?;
+    output.status(&format!("Wrote {}", archive.display()));
+    output.status(
+        "Values of environment variables that look like secrets are redacted. Review the bundle before sharing it.",
+    );
+    Ok(())
+}
+
+fn do_exec(opts: &ExecOpts) -> anyhow::Result<()> {
+    let build_directory = opts.build_directory.as_path();
+    let record = if build_directory.join(YAMBS_CACHE_DIRECTORY_NAME).is_dir() {
+        Cache::new(build_directory)?.from_cache::<BuildRecord>()
+    } else {
+        None
+    };
+    let record = record.ok_or_else(|| {
+        anyhow::anyhow!(
+            "No build found in {}. Build the project first.",
+            build_directory.display()
+        )
+    })?;
+    let build_type = opts
+        .build_type
+        .clone()
+        .unwrap_or_else(|| record.configuration.build_type.clone());
+    let build_type_name = build_type.to_string();
+    if !build_directory.join(&build_type_name).is_dir() {
+        anyhow::bail!(
+            "No {} build found in {}. Build the project with --build-type {} first.",
+            build_type_name,
+            build_directory.display(),
+            build_type_name
+        );
+    }
+
+    let environment = yambs::exec::build_environment(
+        build_directory,
+        &build_type,
+        &record.manifest_directory,
+        &record.toolchain_environment,
+    );
+    let (program, arguments) = opts.command.split_first().expect("clap requires a command");
+    let status = environment
+        .command(std::path::Path::new(program))
+        .args(arguments)
+        .status()
+        .with_context(|| format!("Failed to run {}", program))?;
+    // yambs exits as the command did, so that scripts can check its status.
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+fn do_ide(opts: &IdeOpts, output: &Output) -> anyhow::Result<()> {
+    match opts.command {
+        IdeCommand::Vscode(ref vscode_opts) => {
+            let build_directory = vscode_opts.build_directory.as_path();
+            let record = if build_directory.join(YAMBS_CACHE_DIRECTORY_NAME).is_dir() {
+                Cache::new(build_directory)?.from_cache::<BuildRecord>()
+            } else {
+                None
+            };
+            let record = record.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No build found in {}. Build the project first.",
+                    build_directory.display()
+                )
+            })?;
+            let buildfile_directory =
+                build_directory.join(record.configuration.build_type.to_string());
+            let progress_document = ProgressDocument::from_directory(&buildfile_directory)
+                .with_context(|| {
+                    format!(
+                        "No build files found in {}. Build the project first.",
+                        buildfile_directory.display()
+                    )
+                })?;
+
+            initialize_preset_variables_with(
+                &vscode_opts.build_directory,
+                &ManifestDirectory::from(record.manifest_directory.as_path()),
+                &record.configuration.build_type,
+                record.toolchain_file.as_deref(),
+            )?;
+            let manifest = parser::parse(&record.manifest_directory.join(YAMBS_MANIFEST_NAME))
+                .with_context(|| "Failed to parse manifest")?;
+            let yambs = std::env::current_exe().context("Failed to locate yambs executable")?;
+            let workspace = VscodeWorkspace::new(
+                &yambs,
+                record.build_args(build_directory),
+                record.toolchain_environment.clone(),
+                &progress_document,
+                &manifest,
+                &buildfile_directory,
+                &record.output_extensions,
+            );
+            let workspace_directory = vscode_opts
+                .workspace
+                .clone()
+                .unwrap_or_else(|| record.manifest_directory.clone());
+            for file in workspace.write(&workspace_directory)? {
+                output.status(&format!("Wrote {}", file.display()));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Runs as part of the build, where only stderr of make is shown to the user.
+fn do_sandbox(opts: &SandboxOpts) -> anyhow::Result<()> {
+    let sandbox = Sandbox::new(&opts.directory)?;
+    for input in &opts.inputs {
+        sandbox.add_input(input)?;
+    }
+    sandbox.run(&opts.command)?;
+    for file in sandbox.undeclared_outputs(&opts.outputs) {
+        eprintln!(
+            "Warning: \"{}\" produced {} which is not a declared output and is discarded",
+            opts.command.join(" "),
+            file.display()
+        );
+    }
+    for declared_output in &opts.outputs {
+        sandbox.collect_output(declared_output)?;
+    }
+    Ok(())
+}
+
+fn do_embed(opts: &EmbedOpts) -> anyhow::Result<()> {
+    yambs::embed::write(&opts.target, &opts.files, &opts.header, &opts.source)?;
+    Ok(())
+}
+
+fn do_run_plan(opts: &RunPlanOpts) -> anyhow::Result<()> {
+    std::env::set_current_dir(&opts.directory)
+        .with_context(|| format!("Could not access directory {}", opts.directory.display()))?;
+    let plan = BuildPlan::from_directory(&opts.directory)
+        .with_context(|| "Failed to read the build plan")?;
+    if !yambs::generator::internal::execute(&plan, &opts.targets, opts.jobs, opts.keep_going)? {
+        // The failures are reported by the steps that failed, and the exit code is that of make.
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
+fn generate_build_files(
+    generator: &mut Box<dyn Generator>,
+    registry: &TargetRegistry,
+    opts: &BuildOpts,
+) -> anyhow::Result<std::path::PathBuf> {
+    log::trace!("generate_build_files");
+    let buildfile_directory = generator.generate(registry)?;
+    log::debug!(
+        "Build files generated in {}",
+        opts.build_directory.as_path().display()
+    );
+    Ok(buildfile_directory)
+}
+
+// Prints the compile and link command of each target, with the origin of each flag.
+fn print_annotated_commands(generator: &dyn Generator, registry: &TargetRegistry, output: &Output) {
+    for commands in generator.annotated_commands(registry) {
+        output.status(&format!(
+            "Compile command of {}:\n{}",
+            commands.target,
+            commands.compile.render()
+        ));
+        match commands.link {
+            Some(ref link) => output.status(&format!(
+                "Link command of {}:\n{}",
+                commands.target,
+                link.render()
+            )),
+            None => output.status(&format!(
+                "{} is a static library, which is archived without link flags",
+                commands.target
+            )),
+        }
+    }
+}
+
+// Reports the hardening options applied to the hardened targets. Targets hardened alike are
+// reported together.
+fn print_hardening(generator: &dyn Generator, registry: &TargetRegistry, output: &Output) {
+    let mut targets_by_hardening = std::collections::BTreeMap::<String, Vec<String>>::new();
+    for (target, hardening) in generator.hardened_targets(registry) {
+        targets_by_hardening
+            .entry(hardening.to_string())
+            .or_default()
+            .push(target);
+    }
+    for (hardening, targets) in targets_by_hardening {
+        output.status(&format!("Hardened {}: {}", targets.join(", "), hardening));
+    }
+}
+
+fn parse_and_register_dependencies(
+    manifest: &manifest::ParsedManifest,
+    output: &Output,
+    dep_registry: &mut TargetRegistry,
+    toolchain: &Rc<RefCell<NormalizedToolchain>>,
+    build_type: &BuildType,
+    manifest_cache_statistics: ManifestCacheStatistics,
+) -> anyhow::Result<()> {
+    log::trace!("parse_and_register_dependencies");
+    let manifest_path = manifest.manifest.directory.join(YAMBS_MANIFEST_NAME);
+    for build_target in &manifest.data.targets {
+        if let Some(lib) = build_target.library() {
+            log::debug!(
+                "Creating build target for library {} in manifest {}",
+                lib.name,
+                manifest_path.display()
+            );
+        }
+        if let Some(exe) = build_target.executable() {
+            log::debug!(
+                "Creating build target for executable {} in manifest {}",
+                exe.name,
+                manifest_path.display()
+            );
+        }
+        BuildTarget::target_node_from_source(
+            manifest,
+            build_target,
+            BuildFor::Target,
+            dep_registry,
+            toolchain,
+            build_type,
+        )?;
+    }
+    if DEPENDENCY_TESTS.get().copied().unwrap_or(false) {
+        register_dependency_tests(manifest, dep_registry, toolchain, build_type)?;
+    }
+    let number_of_targets = dep_registry.number_of_targets();
+    let ManifestCacheStatistics { reused, parsed } = manifest_cache_statistics;
+    if reused + parsed == 0 {
+        output.status(&format!("Registered {} build targets", number_of_targets));
+    } else {
+        output.status(&format!(
+            "Registered {} build targets ({} of {} dependency manifests reused from the cache)",
+            number_of_targets,
+            reused,
+            reused + parsed
+        ));
+    }
+    Ok(())
+}
+
+// Registers the executables run by the tests of the manifests of the registered targets, other than
+// that of the project, and their examples, along with what they depend on in turn, until no other
+// manifest is reached.
+fn register_dependency_tests(
+    manifest: &manifest::ParsedManifest,
+    dep_registry: &mut TargetRegistry,
+    toolchain: &Rc<RefCell<NormalizedToolchain>>,
+    build_type: &BuildType,
+) -> anyhow::Result<()> {
+    let mut visited = BTreeSet::from([manifest.manifest.directory.clone()]);
+    loop {
+        let directories = dep_registry
+            .registry
+            .iter()
+            .map(|target| target.borrow().manifest.directory.clone())
+            .filter(|directory| !visited.contains(directory))
+            .collect::<BTreeSet<std::path::PathBuf>>();
+        if directories.is_empty() {
+            return Ok(());
+        }
+        for directory in directories {
+            visited.insert(directory.clone());
+            let dependency_manifest = dep_registry.manifest(&directory)?;
+            let tested = dependency_manifest
+                .data
+                .tests
+                .iter()
+                .flat_map(|test| &test.depends_on)
+                .collect::<BTreeSet<&String>>();
+            for target in &dependency_manifest.data.targets {
+                let Some(executable) = target.executable() else {
+                    continue;
+                };
+                if !executable.example && !tested.contains(&executable.name) {
+                    continue;
+                }
+                log::debug!(
+                    "Creating build target for {} {} of dependency manifest {}",
+                    if executable.example {
+                        "example"
+                    } else {
+                        "test executable"
+                    },
+                    executable.name,
+                    directory.display()
+                );
+                BuildTarget::target_node_from_source(
+                    &dependency_manifest,
+                    target,
+                    BuildFor::Target,
+                    dep_registry,
+                    toolchain,
+                    build_type,
+                )?;
+            }
+        }
+    }
+}
+
+// Failures of a build with --max-errors, repeated after the output of make so that they are not
+// lost in it.
+fn print_failure_report(error_budget: &ErrorBudget, output: &Output) {
+    let failed_objects = error_budget.failed_objects();
+    if failed_objects.is_empty() {
+        return;
+    }
+    output.error(&format!(
+        "{} translation unit(s) failed to compile:",
+        failed_objects.len()
+    ));
+    for object in failed_objects {
+        output.error_without_prefix(&format!("    {}", object.display()));
+    }
+    if let Some(first_error) = error_budget.first_error() {
+        output.error_without_prefix(&format!("First error: {}", first_error));
+    }
+    if error_budget.is_exhausted() {
+        output.error(&format!(
+            "Make was stopped from starting new compiles after {} failed (--max-errors {})",
+            error_budget.max_errors(),
+            error_budget.max_errors()
+        ));
+    }
+}
+
+// Undefined references of a failed link, grouped by symbol. Libraries built by the project that
+// define a symbol are suggested as dependencies of the targets referencing it.
+fn print_link_errors(
+    buildfile_directory: &std::path::Path,
+    link_errors: &link_errors::LinkErrors,
+    output: &Output,
+) {
+    let targets = ProgressDocument::from_directory(buildfile_directory)
+        .map(|progress_document| progress_document.targets)
+        .unwrap_or_default();
+    let libraries = link_errors::library_definitions(&targets);
+    let undefined_symbols = link_errors.undefined_symbols();
+    output.error(&format!(
+        "{} undefined symbol(s) when linking:",
+        undefined_symbols.len()
+    ));
+    for undefined in &undefined_symbols {
+        output.error_without_prefix(&format!("    {}", undefined.symbol));
+        let mut referencing_targets = BTreeSet::new();
+        for reference in &undefined.references {
+            let referencing_target = targets.iter().find(|target| {
+                target
+                    .object_files
+                    .iter()
+                    .any(|object| object.as_path() == std::path::Path::new(&reference.location))
+            });
+            let mut line = format!("        referenced in {}", reference.location);
+            if let Some(ref function) = reference.function {
+                line.push_str(&format!(" by {}", function));
+            }
+            if let Some(target) = referencing_target {
+                line.push_str(&format!(" (target {})", target.target));
+                referencing_targets.insert(target.target.as_str());
+            }
+            output.error_without_prefix(&line);
+        }
+        for library in libraries
+            .iter()
+            .filter(|library| library.defines(&undefined.symbol))
+        {
+            let missing_from = targets
+                .iter()
+                .filter(|target| referencing_targets.contains(target.target.as_str()))
+                .filter(|target| {
+                    target.target != library.target
+                        && !target.dependencies.contains(&library.target)
+                })
+                .map(|target| target.target.as_str())
+                .collect::<Vec<&str>>();
+            let mut line = format!(
+                "        defined in {} of target {}",
+                library.library.display(),
+                library.target
+            );
+            if !missing_from.is_empty() {
+                line.push_str(&format!(
+                    ", which {} not depend on",
+                    if missing_from.len() == 1 {
+                        format!("{} does", missing_from[0])
+                    } else {
+                        format!("{} do", missing_from.join(", "))
+                    }
+                ));
+            }
+            output.error_without_prefix(&line);
+        }
+    }
+}
+
+// Runs the build tool of the build files `generator_type` generated in `buildfile_directory`, or
+// the build plan written along with them for the internal backend.
+fn run_build_tool(
+    generator_type: &GeneratorType,
+    backend: Backend,
+    args: &[String],
+    buildfile_directory: &std::path::Path,
+) -> anyhow::Result<BuildProcess> {
+    if backend == Backend::Internal {
+        let mut args = args.to_vec();
+        args.splice(
+            0..0,
+            [
+                "--directory".to_string(),
+                buildfile_directory.display().to_string(),
+            ],
+        );
+        log::debug!(
+            "Running the build plan in directory {}",
+            buildfile_directory.display()
+        );
+        return Ok(InternalBackend::new(&args)?.run()?);
+    }
+    match generator_type {
+        GeneratorType::GNUMakefiles => run_make(args, buildfile_directory),
+        GeneratorType::Ninja => {
+            std::env::set_current_dir(buildfile_directory).with_context(|| {
+                format!(
+                    "Could not access directory {}",
+                    buildfile_directory.display()
+                )
+            })?;
+            let ninja = Ninja::new(args)?;
+            log::debug!(
+                "Running ninja in directory {}",
+                buildfile_directory.display()
+            );
+            Ok(ninja.run()?)
+        }
+    }
+}
+
+fn run_make(args: &[String], makefile_directory: &std::path::Path) -> anyhow::Result<BuildProcess> {
+    std::env::set_current_dir(makefile_directory).with_context(|| {
+        format!(
+            "Could not access directory {}",
+            makefile_directory.display()
+        )
+    })?;
+    let make = Make::new(args)?;
+
+    log::debug!("Running make in directory {}", makefile_directory.display());
+    let build_process = make.run()?;
+    Ok(build_process)
+}
+
+// Targets to build, picked from the generated targets with --interactive. All targets are built if
+// there are none. None if nothing is picked.
+fn targets_to_build(
+    cache: &Cache,
+    buildfile_directory: &std::path::Path,
+    opts: &BuildOpts,
+) -> anyhow::Result<Option<Vec<String>>> {
+    // The examples are left out of the default target, which is then given along with them.
+    let with_examples = |mut targets: Vec<String>| {
+        if opts.examples {
+            if targets.is_empty() {
+                targets.push("all".to_string());
+            }
+            targets.push(EXAMPLES_TARGET_NAME.to_string());
+        }
+        targets
+    };
+    if !opts.interactive {
+        return Ok(Some(with_examples(opts.target.iter().cloned().collect())));
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("--interactive needs a terminal to pick the targets from");
+    }
+    let targets = ProgressDocument::from_directory(buildfile_directory)
+        .with_context(|| "Failed to read the generated targets")?
+        .targets
+        .into_iter()
+        .map(|target| target.target)
+        .filter(|target| target != "all" && target != EXAMPLES_TARGET_NAME)
+        .collect::<BTreeSet<String>>();
+    let previous = cache
+        .from_cache::<target_picker::TargetSelection>()
+        .unwrap_or_default();
+    let picker = target_picker::TargetPicker::new(targets.into_iter().collect(), &previous);
+    let selection = picker.run(&mut std::io::stdin().lock(), &mut std::io::stdout())?;
+    Ok(match selection {
+        Some(selection) => {
+            cache
+                .cache(&selection)
+                .with_context(|| "Failed to remember the picked targets")?;
+            Some(with_examples(selection.targets.into_iter().collect()))
+        }
+        None => None,
+    })
+}
+
+// Paths mentioned by the commands to print in full: the objects and output of each target given
+// with --verbose-target, and each file given with --verbose-file.
+fn verbose_selection(
+    buildfile_directory: &std::path::Path,
+    opts: &BuildOpts,
+) -> anyhow::Result<VerboseSelection> {
+    let mut paths = Vec::new();
+    if !opts.verbose_targets.is_empty() {
+        let targets = ProgressDocument::from_directory(buildfile_directory)
+            .with_context(|| "Failed to read the generated targets")?
+            .targets;
+        for name in &opts.verbose_targets {
+            let target = targets
+                .iter()
+                .find(|target| &target.target == name)
+                .ok_or_else(|| anyhow::anyhow!("--verbose-target: no target named {}", name))?;
+            paths.extend(target.object_files.iter().cloned());
+            paths.extend(target.output.iter().cloned());
+        }
+    }
+    for file in &opts.verbose_files {
+        let file = file
+            .canonicalize()
+            .with_context(|| format!("--verbose-file: could not find {}", file.display()))?;
+        paths.push(file);
+    }
+    Ok(VerboseSelection::new(&paths))
+}
+
+// Keeps track of the steps running, the objects compiled of each target and the durations of the
+// steps as `event` starts or finishes a step, and writes the event to the JSON progress log.
+fn record_step_event(
+    event: &progress::StepEvent,
+    running_steps: &mut Vec<progress::StepEvent>,
+    compiled: &mut HashMap<String, usize>,
+    progress: &mut progress::Progress,
+    step_timer: Option<&mut StepTimer>,
+    progress_json: Option<&mut std::io::BufWriter<std::fs::File>>,
+) -> anyhow::Result<()> {
+    match event.state {
+        progress::StepState::Started => {
+            if event.step == progress::Step::Compile {
+                *compiled.entry(event.target.clone()).or_default() += 1;
+            }
+            running_steps.push(event.clone());
+            if let Some(step_timer) = step_timer {
+                step_timer.started(&event.output);
+            }
+        }
+        progress::StepState::Finished => {
+            running_steps.retain(|running_step| !running_step.is_step_of(event));
+            progress.update()?;
+            if let Some(step_timer) = step_timer {
+                step_timer.finished(&event.output);
+            }
+        }
+    }
+    if let Some(progress_json) = progress_json {
+        let record = progress::ProgressRecord {
+            event,
+            current: progress.current,
+            total: progress.total,
+        };
+        serde_json::to_writer(&mut *progress_json, &record)?;
+        progress_json.write_all(b"\n")?;
+        progress_json.flush()?;
+    }
+    Ok(())
+}
 
 fn build_project(
     buildfile_directory: &std::path::Path,
     output: &Output,
     opts: &BuildOpts,
+    targets_to_build: &[String],
+    jobs: Option<usize>,
+    make_features: MakeFeatures,
     logger: &logger::Logger,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<BuildOutcome> {
     log::trace!("build_project");
+    let started = std::time::SystemTime::now();
     let output_clone = output.clone();
     let progress_path = buildfile_directory.to_path_buf();
-    let owned_buildfile_directory = buildfile_directory.to_path_buf();
+    let generator_type = opts.configuration.generator_type.clone();
     let mut make_args = opts.make_args.clone();
-    if let Some(ref target) = opts.target {
+    if opts.backend == Backend::Internal && !make_args.is_empty() {
+        log::warn!("The internal backend does not run make. Ignoring the arguments given for it");
+        make_args.clear();
+    }
+    // Given after the arguments of the command line, so that they override -j given there.
+    if let Some(jobs) = jobs {
+        make_args.extend(["-j".to_string(), jobs.to_string()]);
+    } else if opts.backend == Backend::Internal {
+        make_args.extend([
+            "-j".to_string(),
+            yambs::generator::makefile::make::default_jobs().to_string(),
+        ]);
+    }
+    match generator_type {
+        // Make keeps going after a failure, until the error budget is exhausted, and so does the
+        // internal backend.
+        GeneratorType::GNUMakefiles if opts.max_errors.is_some() => {
+            make_args.push("-k".to_string());
+        }
+        // Ninja keeps count of the failures itself.
+        GeneratorType::Ninja => {
+            if let Some(max_errors) = opts.max_errors {
+                make_args.extend(["-k".to_string(), max_errors.to_string()]);
+            }
+        }
+        _ => {}
+    }
+    for target in targets_to_build {
         log::debug!("Found specified target. \"{}\" will be built.", target);
         make_args.push(target.clone());
     }
-    let target = opts.target.clone();
+    // The rules print which target each step they start and finish is for. Those of build.ninja
+    // and the internal backend always do.
+    if generator_type == GeneratorType::GNUMakefiles && opts.backend == Backend::Make {
+        make_args.push(format!("{}=1", progress::STEP_MARKER_VARIABLE));
+        // The output of each target is printed once it is made, instead of interleaved with that
+        // of the jobs running along with it, unless -O is given for make.
+        let output_sync_given = opts
+            .make_args
+            .iter()
+            .any(|arg| arg.starts_with("-O") || arg.starts_with("--output-sync"));
+        if !output_sync_given && make_features.output_sync() {
+            make_args.push("--output-sync=target".to_string());
+        }
+    }
+    let mut progress_json = opts
+        .progress_json
+        .as_ref()
+        .map(|path| {
+            std::fs::File::create(path)
+                .map(std::io::BufWriter::new)
+                .with_context(|| format!("Could not create {}", path.display()))
+        })
+        .transpose()?;
 
-    let make_thread = std::thread::spawn(move || {
-        let mut build_process = run_make(&make_args, &owned_buildfile_directory).unwrap();
+    let mut progress = progress::Progress::new(&progress_path, targets_to_build)?;
+    let error_budget = opts
+        .max_errors
+        .filter(|_| generator_type == GeneratorType::GNUMakefiles)
+        .map(|max_errors| ErrorBudget::new(max_errors as usize, &progress.targets_to_build));
+    let verbose_selection = verbose_selection(buildfile_directory, opts)?;
 
-        build_process.wait_and_log(&output_clone)
+    let (step_sender, step_receiver) = std::sync::mpsc::channel();
+    let mut build_process = run_build_tool(
+        &generator_type,
+        opts.backend,
+        &make_args,
+        buildfile_directory,
+    )?
+    .with_verbose_selection(verbose_selection)
+    .with_step_events(step_sender);
+    let make_thread = std::thread::spawn(move || {
+        build_process.wait_and_log_within_budget(&output_clone, error_budget)
     });
 
-    let mut progress = progress::Progress::new(&progress_path, target)?;
-
     let pb = output::ProgressBar::new(progress.total);
 
+    // Steps started and not finished yet, of which the latest is shown.
+    let mut running_steps = Vec::<progress::StepEvent>::new();
+    let mut step_timer = opts
+        .schedule_by_duration
+        .then(|| StepTimer::new(buildfile_directory));
+    let mut compiled = HashMap::<String, usize>::new();
     let mut joinable = make_thread.is_finished();
     while !joinable {
-        let msg = format!("[{}/{}] Building...", progress.current, progress.total);
+        while let Ok(event) = step_receiver.try_recv() {
+            record_step_event(
+                &event,
+                &mut running_steps,
+                &mut compiled,
+                &mut progress,
+                step_timer.as_mut(),
+                progress_json.as_mut(),
+            )?;
+        }
+        let step_message = running_steps
+            .last()
+            .map_or_else(|| "Building...".to_string(), |step| step.message());
+        let msg = format!("[{}/{}] {}", progress.current, progress.total, step_message);
         pb.bar.set_message(msg);
         pb.bar.set_position(progress.current);
         progress.update()?;
         joinable = make_thread.is_finished();
     }
 
-    let exit_status = make_thread.join().unwrap().unwrap();
-    let process_code = exit_status.code();
+    let (exit_status, error_budget, link_errors) = make_thread.join().unwrap();
+    for event in step_receiver.try_iter() {
+        record_step_event(
+            &event,
+            &mut running_steps,
+            &mut compiled,
+            &mut progress,
+            step_timer.as_mut(),
+            progress_json.as_mut(),
+        )?;
+    }
+    if let Some(ref step_timer) = step_timer {
+        step_timer.append_to_log()?;
+    }
+    let process_code = exit_status.unwrap().code();
     match process_code {
         Some(0) => {
             let msg = format!("{}", "Build SUCCESS".green());
@@ -392,7 +2925,66 @@ fn build_project(
             pb.fail_with_message(msg);
         }
     }
+    if let Some(ref error_budget) = error_budget {
+        print_failure_report(error_budget, output);
+    }
+    if process_code != Some(0) && !link_errors.is_empty() {
+        print_link_errors(buildfile_directory, &link_errors, output);
+    }
     let log_path = logger.path();
     output.status(&format!("Build log available at {:?}", log_path.display()));
+    Ok(BuildOutcome {
+        started,
+        duration: started.elapsed().unwrap_or_default(),
+        success: process_code == Some(0),
+        compiled,
+    })
+}
+
+// What build_project saw of the build, for the build history.
+struct BuildOutcome {
+    started: std::time::SystemTime,
+    duration: std::time::Duration,
+    success: bool,
+    // Objects compiled by the build, for each target.
+    compiled: HashMap<String, usize>,
+}
+
+// Records the targets as left by the build in the build history read by `yambs report`.
+fn record_build_history(
+    cache: &Cache,
+    buildfile_directory: &Path,
+    opts: &BuildOpts,
+    targets_to_build: &[String],
+    outcome: &BuildOutcome,
+) -> anyhow::Result<()> {
+    let progress_document = ProgressDocument::from_directory(buildfile_directory)
+        .with_context(|| "Failed to read the targets of the build")?;
+    let compile_timings = cache.from_cache::<CompileTimings>().unwrap_or_default();
+    let mut targets = Vec::new();
+    for target in build_history::built_targets(&progress_document, targets_to_build) {
+        let warnings = WarningsReport::from_objects(&target.object_files)?;
+        targets.push(TargetSummary::new(
+            target,
+            outcome.compiled.get(&target.target).copied().unwrap_or(0),
+            warnings.warnings.len(),
+            &compile_timings,
+        ));
+    }
+    let mut history = cache.from_cache::<BuildHistory>().unwrap_or_default();
+    history.record(BuildSummary {
+        started: outcome
+            .started
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        build_type: opts.configuration.build_type.to_string(),
+        success: outcome.success,
+        duration: outcome.duration.as_millis() as u64,
+        targets,
+    });
+    cache
+        .cache(&history)
+        .with_context(|| "Failed to cache the build history")?;
     Ok(())
 }
@@ -1,7 +1,12 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
+use crate::build_file_hashes::ContentHasher;
+use crate::build_target::associated_files::{FileExtensions, SourceFile};
+use crate::flags::{CompilerFlags, FlagGroup};
 use crate::parser::types;
 use crate::targets;
+use crate::utility::ProjectLayout;
 use crate::YAMBS_MANIFEST_NAME;
 use types::ParseStandardError;
 
@@ -9,6 +14,9 @@ use types::ParseStandardError;
 pub struct Manifest {
     pub directory: std::path::PathBuf,
     pub modification_time: std::time::SystemTime,
+    /// Hash of the manifest and the manifests it includes, as they were when it was parsed.
+    #[serde(default)]
+    pub content_hash: Option<String>,
 }
 
 impl Manifest {
@@ -20,77 +28,587 @@ impl Manifest {
             modification_time: metadata
                 .modified()
                 .expect("Could not fetch last modified time of manifest"),
+            content_hash: content_hash(&directory.join(YAMBS_MANIFEST_NAME)),
         }
     }
+
+    /// Whether the manifest or one of the manifests it includes has changed since it was parsed.
+    /// Manifests parsed without a hash are taken as unchanged.
+    pub fn has_changed(&self) -> bool {
+        match self.content_hash {
+            Some(ref parsed) => {
+                content_hash(&self.directory.join(YAMBS_MANIFEST_NAME)).as_ref() != Some(parsed)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Hash of the manifest at `manifest_path` and the manifests it includes, as they are on disk.
+/// None if one of them can not be read.
+pub fn content_hash(manifest_path: &Path) -> Option<String> {
+    let content = std::fs::read(manifest_path).ok()?;
+    content_hash_of(manifest_path, &content)
 }
 
-#[derive(Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+/// Hash of `content`, read from the manifest at `manifest_path`, and of the manifests it includes
+/// and inherits `[defaults]` from as they are on disk.
+pub fn content_hash_of(manifest_path: &Path, content: &[u8]) -> Option<String> {
+    let mut hasher = ContentHasher::new();
+    hasher.add(content);
+    let enclosing = crate::YAMBS_MANIFEST_DIR
+        .get()
+        .map_or_else(Vec::new, |project_directory| {
+            crate::parser::defaults::enclosing_manifests(manifest_path, project_directory.as_path())
+        });
+    for other in crate::parser::include::included_manifests(manifest_path)
+        .into_iter()
+        .chain(enclosing)
+    {
+        hasher.add_path(&other).add(std::fs::read(&other).ok()?);
+    }
+    Some(format!("{:016x}", hasher.finish()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct ParsedManifest {
     #[serde(flatten)]
     pub manifest: Manifest,
     pub data: ManifestData,
 }
 
-#[derive(Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct ManifestData {
     pub project_config: Option<types::ProjectConfig>,
     pub targets: Vec<targets::Target>,
+    #[serde(default)]
+    pub tests: Vec<targets::Test>,
+    #[serde(default)]
+    pub install: Vec<targets::Install>,
+    /// Directories searched for the sources of discovered tests. A test is discovered or lost
+    /// when a source is added to or removed from one of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub test_discovery_directories: Vec<PathBuf>,
+    /// Signing of the artifacts of the manifest after they are linked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing: Option<targets::Signing>,
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum ParseManifestError {
-    #[error("Failed to parse dependency")]
-    FailedToParseDependency(#[source] targets::DependencyError),
+    #[error("Failed to parse dependency declared by {0} in {1:?}")]
+    FailedToParseDependency(String, PathBuf, #[source] targets::DependencyError),
     #[error("Failed to canonicalize {1:?}")]
     FailedToCanonicalizePath(#[source] std::io::Error, PathBuf),
     #[error("Failed to parse standard in manifest")]
     FailedToParseStandard(#[source] ParseStandardError),
+    #[error("Failed to parse custom command")]
+    FailedToParseCustomCommand(#[source] targets::CustomCommandError),
+    #[error("Failed to parse test")]
+    FailedToParseTest(#[source] targets::TestError),
+    #[error("Failed to parse install")]
+    FailedToParseInstall(#[source] targets::InstallError),
+    #[error("Failed to parse signing")]
+    FailedToParseSigning(#[source] targets::SigningError),
+    #[error(
+        "Example \"{0}\" must specify the library it links, as the manifest has {1} libraries"
+    )]
+    ExampleWithoutLibrary(String, usize),
+    #[error("Example \"{0}\" links library \"{1}\", which is not a library of the manifest")]
+    UnknownExampleLibrary(String, String),
+    #[error(
+        "{target} uses flag group \"{group}\", which is not defined. Defined flag groups are: {}",
+        defined.join(", ")
+    )]
+    UnknownFlagGroup {
+        target: String,
+        group: String,
+        defined: Vec<String>,
+    },
+    #[error(
+        "{} uses flag groups that conflict: {} of \"{}\" and {} of \"{}\"",
+        .0.target,
+        .0.first_flag,
+        .0.first_group,
+        .0.second_flag,
+        .0.second_group
+    )]
+    ConflictingFlagGroups(Box<FlagGroupConflict>),
+    #[error("Failed to discover tests matching \"{1}\"")]
+    FailedToDiscoverTests(#[source] std::io::Error, String),
+    #[error(
+        "Discovered test {1:?} is named \"{0}\", which is already the name of a target or test"
+    )]
+    DiscoveredTestConflict(String, PathBuf),
+    #[error("Discovered tests link library \"{0}\", which is not a library of the manifest")]
+    UnknownTestLibrary(String),
+    #[error("Version script {1:?} of library \"{0}\" does not exist")]
+    VersionScriptNotFound(String, PathBuf),
+    #[error("Extra file {1:?} of {0} does not exist")]
+    ExtraFileNotFound(String, PathBuf),
+    #[error("Library \"{0}\" has a version script, but only shared libraries are linked with one")]
+    VersionScriptOfStaticLibrary(String),
+    #[error("Library \"{0}\" has a version, but only shared libraries are versioned")]
+    VersionOfStaticLibrary(String),
+    #[error(
+        "Version \"{1}\" of library \"{0}\" is not a version. Give up to three numbers separated \
+         by dots, such as \"1.2.3\""
+    )]
+    InvalidLibraryVersion(String, String),
+    #[error(
+        "{0} in {1:?} has no sources to compile. List at least one .cpp, .cc or .c file in \
+         sources, or generate one with a custom command or embed"
+    )]
+    NoSources(String, PathBuf),
+}
+
+/// Options of two flag groups used by the same target that are set to different values.
+#[derive(Debug)]
+pub struct FlagGroupConflict {
+    pub target: String,
+    pub first_group: String,
+    pub first_flag: String,
+    pub second_group: String,
+    pub second_flag: String,
+}
+
+impl ParseManifestError {
+    /// Dotted key of the manifest the error is about, such as `library.core.version_script`.
+    pub fn key(&self) -> Option<String> {
+        match self {
+            Self::FailedToParseDependency(key, ..) | Self::NoSources(key, _) => Some(key.clone()),
+            Self::FailedToParseStandard(_) => Some("project_config.std".to_string()),
+            Self::ExampleWithoutLibrary(name, _) => Some(format!("example.{}", name)),
+            Self::UnknownExampleLibrary(name, _) => Some(format!("example.{}.library", name)),
+            Self::UnknownFlagGroup { target, .. } => Some(format!("{}.flag_groups", target)),
+            Self::ConflictingFlagGroups(conflict) => {
+                Some(format!("{}.flag_groups", conflict.target))
+            }
+            Self::FailedToDiscoverTests(..) | Self::DiscoveredTestConflict(..) => {
+                Some("tests.auto_discover".to_string())
+            }
+            Self::UnknownTestLibrary(_) => Some("tests.library".to_string()),
+            Self::VersionScriptNotFound(name, _) | Self::VersionScriptOfStaticLibrary(name) => {
+                Some(format!("library.{}.version_script", name))
+            }
+            Self::VersionOfStaticLibrary(name) | Self::InvalidLibraryVersion(name, _) => {
+                Some(format!("library.{}.version", name))
+            }
+            Self::ExtraFileNotFound(key, _) => Some(format!("{}.extra_files", key)),
+            Self::FailedToCanonicalizePath(..)
+            | Self::FailedToParseCustomCommand(_)
+            | Self::FailedToParseTest(_)
+            | Self::FailedToParseInstall(_) => None,
+            Self::FailedToParseSigning(_) => Some("signing".to_string()),
+        }
+    }
+}
+
+// Extra files of `project` followed by those `own` by the target declared by `table`, relative to
+// `manifest_dir`. Files listed by both are only kept once.
+fn extra_files(
+    table: &str,
+    own: &[PathBuf],
+    project: &[PathBuf],
+    manifest_dir: &std::path::Path,
+) -> Result<Vec<PathBuf>, ParseManifestError> {
+    let mut extra_files = project.to_vec();
+    for file in own {
+        let path = manifest_dir.join(file);
+        if !path.is_file() {
+            return Err(ParseManifestError::ExtraFileNotFound(
+                table.to_string(),
+                file.clone(),
+            ));
+        }
+        if !extra_files.contains(&path) {
+            extra_files.push(path);
+        }
+    }
+    Ok(extra_files)
+}
+
+// Checks that the target declared by `table` compiles at least one source, as a target without
+// sources has nothing to link or archive. Sources are listed, produced by custom commands or
+// generated from the embedded files.
+fn check_has_sources(
+    table: &str,
+    data: &types::RawCommonData,
+    manifest_dir: &std::path::Path,
+    extensions: &FileExtensions,
+) -> Result<(), ParseManifestError> {
+    let is_source = |path: &PathBuf| {
+        SourceFile::generated(path, extensions).is_some_and(|source_file| source_file.is_source())
+    };
+    let has_sources = data.sources.iter().any(is_source)
+        || data
+            .custom_commands
+            .iter()
+            .flat_map(|custom_command| &custom_command.outputs)
+            .any(is_source)
+        || !data.embed.is_empty();
+    if has_sources {
+        Ok(())
+    } else {
+        Err(ParseManifestError::NoSources(
+            table.to_string(),
+            manifest_dir.join(YAMBS_MANIFEST_NAME),
+        ))
+    }
+}
+
+// Versions of shared libraries are one to three numbers separated by dots, the first of which
+// is the major version, as they name the files of the library.
+fn is_library_version(version: &str) -> bool {
+    let numbers = version.split('.').collect::<Vec<&str>>();
+    numbers.len() <= 3
+        && numbers
+            .iter()
+            .all(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+// Executable of the example `name`, depending on the library of the manifest it links unless it
+// already declares the dependency itself.
+fn example_with_library(
+    name: &str,
+    example: types::RawExampleData,
+    library_names: &[String],
+) -> Result<types::RawExecutableData, ParseManifestError> {
+    let library = match example.library {
+        Some(library) if library_names.contains(&library) => library,
+        Some(library) => {
+            return Err(ParseManifestError::UnknownExampleLibrary(
+                name.to_string(),
+                library,
+            ))
+        }
+        None if library_names.len() == 1 => library_names[0].clone(),
+        None => {
+            return Err(ParseManifestError::ExampleWithoutLibrary(
+                name.to_string(),
+                library_names.len(),
+            ))
+        }
+    };
+    let mut executable = example.executable;
+    executable
+        .common_raw
+        .dependencies
+        .entry(library)
+        .or_insert_with(|| {
+            types::DependencyData::Source(types::SourceData {
+                path: PathBuf::from("."),
+                origin: None,
+                link_options: types::LinkOptions::default(),
+            })
+        });
+    Ok(executable)
+}
+
+// Extensions may be written with or without the leading dot.
+fn file_extensions(project_config: Option<&types::ProjectConfig>) -> FileExtensions {
+    let without_dot = |extensions: &Option<Vec<String>>| {
+        extensions
+            .iter()
+            .flatten()
+            .map(|extension| extension.trim_start_matches('.').to_string())
+            .collect::<Vec<String>>()
+    };
+    project_config
+        .map(|project_config| FileExtensions {
+            sources: without_dot(&project_config.source_extensions),
+            headers: without_dot(&project_config.header_extensions),
+        })
+        .unwrap_or_default()
+}
+
+// Executables and tests of the sources matched by `discovery`, along with the directories searched
+// for them. A test is named after the file name of its source, without the extension.
+#[allow(clippy::type_complexity)]
+fn discover_tests(
+    discovery: &types::RawTestDiscoveryData,
+    manifest_dir: &Path,
+    taken_names: &[String],
+    library_names: &[String],
+) -> Result<
+    (
+        Vec<(String, types::RawExecutableData)>,
+        Vec<(String, types::RawTestData)>,
+        Vec<PathBuf>,
+    ),
+    ParseManifestError,
+> {
+    let matches = crate::utility::glob(manifest_dir, &discovery.auto_discover).map_err(|e| {
+        ParseManifestError::FailedToDiscoverTests(e, discovery.auto_discover.clone())
+    })?;
+    if matches.files.is_empty() {
+        log::warn!(
+            "No sources match \"{}\" of [tests] in {}",
+            discovery.auto_discover,
+            manifest_dir.join(YAMBS_MANIFEST_NAME).display()
+        );
+    }
+    let mut dependencies = discovery.dependencies.clone();
+    if let Some(ref library) = discovery.library {
+        if !library_names.contains(library) {
+            return Err(ParseManifestError::UnknownTestLibrary(library.clone()));
+        }
+        dependencies.entry(library.clone()).or_insert_with(|| {
+            types::DependencyData::Source(types::SourceData {
+                path: PathBuf::from("."),
+                origin: None,
+                link_options: types::LinkOptions::default(),
+            })
+        });
+    }
+    let mut executables = Vec::new();
+    let mut tests = Vec::new();
+    for source in matches.files {
+        let name = source
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if taken_names.contains(&name) || executables.iter().any(|(taken, _)| *taken == name) {
+            return Err(ParseManifestError::DiscoveredTestConflict(name, source));
+        }
+        executables.push((
+            name.clone(),
+            types::RawExecutableData {
+                common_raw: types::RawCommonData {
+                    sources: vec![source],
+                    dependencies: dependencies.clone(),
+                    compiler_flags: CompilerFlags::new(),
+                    defines: Vec::new(),
+                    custom_commands: Vec::new(),
+                    build_for: types::BuildFor::default(),
+                    embed: Vec::new(),
+                    extra_files: Vec::new(),
+                },
+                post_link: Vec::new(),
+            },
+        ));
+        tests.push((
+            name.clone(),
+            types::RawTestData {
+                command: Some(types::TestCommandData::Command(types::CommandData::Target(
+                    types::TargetCommandData {
+                        target: name,
+                        args: Vec::new(),
+                    },
+                ))),
+                expect: types::TestExpectation::default(),
+                source: None,
+                target: None,
+                diagnostic: None,
+                depends_on: Vec::new(),
+                working_directory: None,
+                env: BTreeMap::new(),
+                timeout: None,
+                retries: 0,
+            },
+        ));
+    }
+    Ok((executables, tests, matches.directories))
+}
+
+// Flags of the target declared by `table` with the flag groups it uses. Groups setting the same
+// option to different values are an error, as either would silently override the other.
+fn with_flag_groups(
+    table: &str,
+    compiler_flags: CompilerFlags,
+    flag_groups: &BTreeMap<String, FlagGroup>,
+) -> Result<CompilerFlags, ParseManifestError> {
+    let mut groups = Vec::<(&String, &FlagGroup)>::new();
+    for name in &compiler_flags.flag_groups {
+        let group = flag_groups
+            .get(name)
+            .ok_or_else(|| ParseManifestError::UnknownFlagGroup {
+                target: table.to_string(),
+                group: name.clone(),
+                defined: flag_groups.keys().cloned().collect(),
+            })?;
+        for (other_name, other_group) in &groups {
+            if let Some((first_flag, second_flag)) = other_group.conflict_with(group) {
+                return Err(ParseManifestError::ConflictingFlagGroups(Box::new(
+                    FlagGroupConflict {
+                        target: table.to_string(),
+                        first_group: other_name.to_string(),
+                        first_flag: first_flag.to_string(),
+                        second_group: name.clone(),
+                        second_flag: second_flag.to_string(),
+                    },
+                )));
+            }
+        }
+        groups.push((name, group));
+    }
+    let groups = groups
+        .into_iter()
+        .map(|(_, group)| group)
+        .collect::<Vec<&FlagGroup>>();
+    Ok(compiler_flags.with_flag_groups(&groups))
 }
 
 impl ManifestData {
+    /// Layout of the project, as configured by `[project_config]`.
+    pub fn layout(&self) -> ProjectLayout {
+        let mut layout = ProjectLayout::default();
+        if let Some(ref project_config) = self.project_config {
+            if let Some(ref source_dirs) = project_config.source_dirs {
+                layout.source_dirs = source_dirs.clone();
+            }
+            if let Some(ref include_dirs) = project_config.include_dirs {
+                layout.include_dirs = include_dirs.clone();
+            }
+        }
+        layout
+    }
+
+    /// Extensions of sources and headers recognized by the project, as configured by
+    /// `[project_config]`.
+    pub fn file_extensions(&self) -> FileExtensions {
+        file_extensions(self.project_config.as_ref())
+    }
+
     pub fn from_raw(
         contents: types::RawManifestData,
         manifest_dir: &std::path::Path,
     ) -> Result<Self, ParseManifestError> {
         let mut targets = Vec::<targets::Target>::new();
+        let library_names = contents
+            .libraries
+            .iter()
+            .flat_map(|libraries| libraries.keys().cloned())
+            .collect::<Vec<String>>();
+        let extensions = file_extensions(contents.project_config.as_ref());
+        let project_extra_files = extra_files(
+            "project_config",
+            contents
+                .project_config
+                .as_ref()
+                .and_then(|pc| pc.extra_files.as_deref())
+                .unwrap_or_default(),
+            &[],
+            manifest_dir,
+        )?;
+        let flag_groups = contents.flags.unwrap_or_default();
+        let defaults = contents.defaults.unwrap_or_default();
+        let mut raw_executables = contents
+            .executables
+            .into_iter()
+            .flatten()
+            .map(|(name, data)| (name, data, false))
+            .collect::<Vec<(String, types::RawExecutableData, bool)>>();
+        for (name, example) in contents.examples.into_iter().flatten() {
+            let data = example_with_library(&name, example, &library_names)?;
+            raw_executables.push((name, data, true));
+        }
+        let mut raw_tests = contents
+            .tests
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<Vec<(String, types::RawTestData)>>();
+        let mut test_discovery_directories = Vec::new();
+        if let Some(ref discovery) = contents.test_discovery {
+            let taken_names = raw_executables
+                .iter()
+                .map(|(name, _, _)| name.clone())
+                .chain(library_names.iter().cloned())
+                .chain(raw_tests.iter().map(|(name, _)| name.clone()))
+                .collect::<Vec<String>>();
+            let (executables, tests, directories) =
+                discover_tests(discovery, manifest_dir, &taken_names, &library_names)?;
+            raw_executables.extend(
+                executables
+                    .into_iter()
+                    .map(|(name, data)| (name, data, false)),
+            );
+            raw_tests.extend(tests);
+            test_discovery_directories = directories;
+        }
         let mut executables = {
             let mut target_executables = Vec::new();
-            if let Some(executables) = contents.executables {
-                for executable in executables {
-                    let name = executable.0;
-                    let data = executable.1;
+            for (name, mut data, example) in raw_executables {
+                defaults.apply(&mut data.common_raw);
+                let table = if example { "example" } else { "executable" };
+                check_has_sources(
+                    &format!("{}.{}", table, name),
+                    &data.common_raw,
+                    manifest_dir,
+                    &extensions,
+                )?;
 
-                    let dependencies = data.common_raw.dependencies;
-                    let mut parsed_dependencies = Vec::new();
-                    for dependency in dependencies {
-                        let dep_name = dependency.0;
-                        let dep_data = dependency.1;
-                        let parsed_dependency =
-                            targets::Dependency::new(&dep_name, &dep_data, manifest_dir)
-                                .map_err(ParseManifestError::FailedToParseDependency)?;
-                        parsed_dependencies.push(parsed_dependency);
-                    }
-                    let canonicalized_sources = {
-                        let mut canonicalized_sources = Vec::new();
-                        let sources = data.common_raw.sources;
-                        for source in sources {
-                            let canonicalized_source =
-                                crate::canonicalize_source(manifest_dir, &source).map_err(|e| {
-                                    ParseManifestError::FailedToCanonicalizePath(e, source)
-                                })?;
-                            canonicalized_sources.push(canonicalized_source);
-                        }
-                        Ok(canonicalized_sources)
-                    }?;
-                    let target_executable = targets::Target::Executable(targets::Executable {
-                        name,
-                        sources: canonicalized_sources,
-                        dependencies: parsed_dependencies,
-                        compiler_flags: data.common_raw.compiler_flags,
-                        defines: data.common_raw.defines,
-                    });
-                    target_executables.push(target_executable);
+                let dependencies = data.common_raw.dependencies;
+                let mut parsed_dependencies = Vec::new();
+                for dependency in dependencies {
+                    let dep_name = dependency.0;
+                    let dep_data = dependency.1;
+                    let parsed_dependency =
+                        targets::Dependency::new(&dep_name, &dep_data, manifest_dir).map_err(
+                            |e| {
+                                ParseManifestError::FailedToParseDependency(
+                                    format!("{}.{}.dependencies.{}", table, name, dep_name),
+                                    manifest_dir.join(YAMBS_MANIFEST_NAME),
+                                    e,
+                                )
+                            },
+                        )?;
+                    parsed_dependencies.push(parsed_dependency);
                 }
+                let canonicalized_sources = {
+                    let mut canonicalized_sources = Vec::new();
+                    let sources = data.common_raw.sources;
+                    for source in sources {
+                        let canonicalized_source =
+                            crate::canonicalize_source(manifest_dir, &source).map_err(|e| {
+                                ParseManifestError::FailedToCanonicalizePath(e, source)
+                            })?;
+                        canonicalized_sources.push(canonicalized_source);
+                    }
+                    Ok(canonicalized_sources)
+                }?;
+                let embed = data
+                    .common_raw
+                    .embed
+                    .iter()
+                    .map(|file| {
+                        crate::canonicalize_source(manifest_dir, file).map_err(|e| {
+                            ParseManifestError::FailedToCanonicalizePath(e, file.clone())
+                        })
+                    })
+                    .collect::<Result<Vec<PathBuf>, _>>()?;
+                let custom_commands = data
+                    .common_raw
+                    .custom_commands
+                    .iter()
+                    .map(|c| targets::CustomCommand::new(c, manifest_dir))
+                    .collect::<Result<Vec<targets::CustomCommand>, _>>()
+                    .map_err(ParseManifestError::FailedToParseCustomCommand)?;
+                let compiler_flags = with_flag_groups(
+                    &format!("{}.{}", table, name),
+                    data.common_raw.compiler_flags,
+                    &flag_groups,
+                )?;
+                let extra_files = extra_files(
+                    &format!("{}.{}", table, name),
+                    &data.common_raw.extra_files,
+                    &project_extra_files,
+                    manifest_dir,
+                )?;
+                let target_executable = targets::Target::Executable(targets::Executable {
+                    name,
+                    sources: canonicalized_sources,
+                    dependencies: parsed_dependencies,
+                    compiler_flags,
+                    defines: data.common_raw.defines,
+                    custom_commands,
+                    build_for: data.common_raw.build_for,
+                    post_link: data.post_link,
+                    embed,
+                    extra_files,
+                    example,
+                });
+                target_executables.push(target_executable);
             }
             Ok(target_executables)
         }?;
@@ -99,7 +617,14 @@ impl ManifestData {
             if let Some(libraries) = contents.libraries {
                 for library in libraries {
                     let name = library.0;
-                    let data = library.1;
+                    let mut data = library.1;
+                    defaults.apply(&mut data.common_raw);
+                    check_has_sources(
+                        &format!("library.{}", name),
+                        &data.common_raw,
+                        manifest_dir,
+                        &extensions,
+                    )?;
 
                     let dependencies = data.common_raw.dependencies;
                     let mut parsed_dependencies = Vec::new();
@@ -107,8 +632,15 @@ impl ManifestData {
                         let dep_name = dependency.0;
                         let dep_data = dependency.1;
                         let parsed_dependency =
-                            targets::Dependency::new(&dep_name, &dep_data, manifest_dir)
-                                .map_err(ParseManifestError::FailedToParseDependency)?;
+                            targets::Dependency::new(&dep_name, &dep_data, manifest_dir).map_err(
+                                |e| {
+                                    ParseManifestError::FailedToParseDependency(
+                                        format!("library.{}.dependencies.{}", name, dep_name),
+                                        manifest_dir.join(YAMBS_MANIFEST_NAME),
+                                        e,
+                                    )
+                                },
+                            )?;
                         parsed_dependencies.push(parsed_dependency);
                     }
                     let canonicalized_sources = {
@@ -123,13 +655,74 @@ impl ManifestData {
                         }
                         Ok(canonicalized_sources)
                     }?;
+                    let embed = data
+                        .common_raw
+                        .embed
+                        .iter()
+                        .map(|file| {
+                            crate::canonicalize_source(manifest_dir, file).map_err(|e| {
+                                ParseManifestError::FailedToCanonicalizePath(e, file.clone())
+                            })
+                        })
+                        .collect::<Result<Vec<PathBuf>, _>>()?;
+                    let custom_commands = data
+                        .common_raw
+                        .custom_commands
+                        .iter()
+                        .map(|c| targets::CustomCommand::new(c, manifest_dir))
+                        .collect::<Result<Vec<targets::CustomCommand>, _>>()
+                        .map_err(ParseManifestError::FailedToParseCustomCommand)?;
+                    let compiler_flags = with_flag_groups(
+                        &format!("library.{}", name),
+                        data.common_raw.compiler_flags,
+                        &flag_groups,
+                    )?;
+                    let extra_files = extra_files(
+                        &format!("library.{}", name),
+                        &data.common_raw.extra_files,
+                        &project_extra_files,
+                        manifest_dir,
+                    )?;
+                    let version_script = match data.version_script {
+                        Some(_) if data.lib_type == types::LibraryType::Static => {
+                            return Err(ParseManifestError::VersionScriptOfStaticLibrary(name))
+                        }
+                        Some(ref version_script) => {
+                            let path = manifest_dir.join(version_script);
+                            if !path.is_file() {
+                                return Err(ParseManifestError::VersionScriptNotFound(
+                                    name,
+                                    version_script.clone(),
+                                ));
+                            }
+                            Some(path)
+                        }
+                        None => None,
+                    };
+                    if let Some(ref version) = data.version {
+                        if data.lib_type == types::LibraryType::Static {
+                            return Err(ParseManifestError::VersionOfStaticLibrary(name));
+                        }
+                        if !is_library_version(version) {
+                            return Err(ParseManifestError::InvalidLibraryVersion(
+                                name,
+                                version.clone(),
+                            ));
+                        }
+                    }
                     let target_library = targets::Target::Library(targets::Library {
                         name,
                         sources: canonicalized_sources,
                         dependencies: parsed_dependencies,
-                        compiler_flags: data.common_raw.compiler_flags,
+                        compiler_flags,
                         lib_type: data.lib_type,
                         defines: data.common_raw.defines,
+                        custom_commands,
+                        build_for: data.common_raw.build_for,
+                        embed,
+                        version_script,
+                        version: data.version,
+                        extra_files,
                     });
                     target_libraries.push(target_library);
                 }
@@ -138,6 +731,23 @@ impl ManifestData {
         }?;
         targets.append(&mut executables);
         targets.append(&mut libraries);
+        let tests = raw_tests
+            .iter()
+            .map(|(name, data)| targets::Test::new(name, data, manifest_dir))
+            .collect::<Result<Vec<targets::Test>, _>>()
+            .map_err(ParseManifestError::FailedToParseTest)?;
+        let install = contents
+            .install
+            .unwrap_or_default()
+            .iter()
+            .map(|data| targets::Install::new(data, manifest_dir))
+            .collect::<Result<Vec<targets::Install>, _>>()
+            .map_err(ParseManifestError::FailedToParseInstall)?;
+        let signing = contents
+            .signing
+            .map(|data| targets::Signing::new(&data, &targets, &tests))
+            .transpose()
+            .map_err(ParseManifestError::FailedToParseSigning)?;
         let project_config = contents.project_config;
 
         if let Some(ref pc) = project_config {
@@ -152,6 +762,36 @@ impl ManifestData {
         Ok(Self {
             project_config,
             targets,
+            tests,
+            install,
+            test_discovery_directories,
+            signing,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_changes_with_its_content_and_that_of_its_includes() {
+        let tempdir = tempdir::TempDir::new("manifest").unwrap();
+        let directory = tempdir.path().canonicalize().unwrap();
+        std::fs::write(
+            directory.join(YAMBS_MANIFEST_NAME),
+            "include = [\"common.toml\"]\n",
+        )
+        .unwrap();
+        std::fs::write(directory.join("common.toml"), "").unwrap();
+
+        let manifest = Manifest::new(&directory);
+        assert!(!manifest.has_changed());
+        std::fs::write(directory.join("common.toml"), "[project_config]\n").unwrap();
+        assert!(manifest.has_changed());
+
+        let manifest = Manifest::new(&directory);
+        std::fs::write(directory.join(YAMBS_MANIFEST_NAME), "").unwrap();
+        assert!(manifest.has_changed());
+    }
+}
@@ -0,0 +1,204 @@
+// Estimates of the memory compiles take, for `--max-memory` to run no more compiles at once than
+// fit in the memory given, on machines where parallel compiles would run out of it.
+
+use std::path::{Path, PathBuf};
+
+use crate::build_target::target_registry::TargetRegistry;
+use crate::environment::Environment;
+use crate::errors::FsError;
+
+/// Lock, in the build directory, held by the links of targets using link-time optimization.
+pub const LTO_LINK_LOCK_NAME: &str = ".lto-link.lock";
+
+/// Variables the generated Makefiles add their flags to, so that flags set in them by the
+/// environment make runs in are given to every compile and link as well.
+const MAKE_FLAG_VARIABLES: &[&str] = &["CXXFLAGS", "CFLAGS", "CXX_LDFLAGS", "CC_LDFLAGS"];
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+/// Memory a compile takes whatever it compiles, for the headers of the standard library and the
+/// data structures of the compiler.
+const BASE_COMPILE_MEMORY: u64 = 256 * MEGABYTE;
+
+/// Memory a compile takes for each byte of the source it compiles.
+const COMPILE_MEMORY_PER_SOURCE_BYTE: u64 = 2048;
+
+/// Factor the memory of compiles of template-heavy targets is multiplied by.
+const TEMPLATE_HEAVY_FACTOR: u64 = 3;
+
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "\"{0}\" is not an amount of memory. Give a positive number of gigabytes, such as 4 or 1.5"
+)]
+pub struct InvalidMemoryBudget(String);
+
+/// Memory the compiles running at the same time may take in total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget(u64);
+
+impl MemoryBudget {
+    pub fn from_gigabytes(gigabytes: f64) -> Self {
+        Self((gigabytes * (1024 * MEGABYTE) as f64) as u64)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.0
+    }
+
+    pub fn megabytes(&self) -> u64 {
+        self.0 / MEGABYTE
+    }
+
+    /// Number of compiles taking `compile_memory` each that fit in the budget, at least one so that
+    /// the build can go on and at most `max_jobs`.
+    pub fn jobs(&self, compile_memory: u64, max_jobs: usize) -> usize {
+        let fitting = (self.0 / compile_memory.max(1)) as usize;
+        fitting.clamp(1, max_jobs.max(1))
+    }
+}
+
+impl std::str::FromStr for MemoryBudget {
+    type Err = InvalidMemoryBudget;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(gigabytes) if gigabytes.is_finite() && gigabytes > 0.0 => {
+                Ok(Self::from_gigabytes(gigabytes))
+            }
+            _ => Err(InvalidMemoryBudget(s.to_string())),
+        }
+    }
+}
+
+/// Removes the lock of the links using link-time optimization, left behind by a build that was
+/// interrupted while linking.
+pub fn remove_stale_link_lock(build_directory: &Path) -> Result<(), FsError> {
+    let link_lock = build_directory.join(LTO_LINK_LOCK_NAME);
+    if link_lock.is_dir() {
+        std::fs::remove_dir(&link_lock).map_err(|e| FsError::RemoveDirectory(link_lock, e))?;
+    }
+    Ok(())
+}
+
+/// Whether the flags make takes from `environment` turn on link-time optimization for every target.
+pub fn environment_uses_lto(environment: &Environment) -> bool {
+    MAKE_FLAG_VARIABLES
+        .iter()
+        .filter_map(|variable| environment.get(variable))
+        .any(|flags| {
+            flags
+                .to_string_lossy()
+                .split_whitespace()
+                .any(|flag| flag.starts_with("-flto"))
+        })
+}
+
+/// Estimated memory of the compile of a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileEstimate {
+    pub target: String,
+    pub source: PathBuf,
+    pub memory: u64,
+}
+
+impl CompileEstimate {
+    pub fn megabytes(&self) -> u64 {
+        self.memory.div_ceil(MEGABYTE)
+    }
+}
+
+/// Estimated memory of compiling a source of `source_size` bytes. The estimate grows with the size
+/// of the source, and is tripled for template-heavy targets, whose instantiations take far more
+/// memory than their sources tell.
+pub fn estimate_compile_memory(source_size: u64, template_heavy: bool) -> u64 {
+    let memory = BASE_COMPILE_MEMORY + source_size * COMPILE_MEMORY_PER_SOURCE_BYTE;
+    if template_heavy {
+        memory * TEMPLATE_HEAVY_FACTOR
+    } else {
+        memory
+    }
+}
+
+/// The compile of the targets of `registry` estimated to take the most memory. Generated sources
+/// that do not exist yet are estimated as empty.
+pub fn heaviest_compile(registry: &TargetRegistry) -> Option<CompileEstimate> {
+    registry
+        .registry
+        .iter()
+        .flat_map(|target| {
+            let borrowed_target = target.borrow();
+            let template_heavy = borrowed_target.compiler_flags.template_heavy;
+            let name = borrowed_target.name();
+            borrowed_target
+                .source_files
+                .iter()
+                .filter(|source| source.is_source())
+                .map(|source| {
+                    let source = source.file();
+                    let size = std::fs::metadata(&source).map_or(0, |metadata| metadata.len());
+                    CompileEstimate {
+                        target: name.clone(),
+                        source,
+                        memory: estimate_compile_memory(size, template_heavy),
+                    }
+                })
+                .collect::<Vec<CompileEstimate>>()
+        })
+        .max_by_key(|estimate| estimate.memory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::ModifyMode;
+    use crate::flags::{AnnotatedCommand, AnnotatedFlags, FlagOrigin};
+    use crate::generator::TargetCommands;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn jobs_are_limited_by_the_heaviest_compile() {
+        let budget = "4".parse::<MemoryBudget>().unwrap();
+        assert_eq!(budget.bytes(), 4096 * MEGABYTE);
+        assert_eq!(budget.jobs(estimate_compile_memory(0, false), 16), 16);
+        let heavy = estimate_compile_memory(100 * 1024, true);
+        assert_eq!(heavy, 3 * (256 + 200) * MEGABYTE);
+        assert_eq!(budget.jobs(heavy, 16), 2);
+        assert_eq!(budget.jobs(8192 * MEGABYTE, 16), 1);
+        assert!("0".parse::<MemoryBudget>().is_err());
+        assert!("4GB".parse::<MemoryBudget>().is_err());
+    }
+
+    #[test]
+    fn flags_of_the_make_environment_can_turn_on_lto() {
+        let environment = Environment::new()
+            .with("CXXFLAGS", OsStr::new("-O2"), ModifyMode::Set)
+            .with("CXX_LDFLAGS", OsStr::new(""), ModifyMode::Set)
+            .with("CFLAGS", OsStr::new("-fno-lto"), ModifyMode::Set)
+            .with("CC_LDFLAGS", OsStr::new("-fno-lto"), ModifyMode::Set);
+        assert!(!environment_uses_lto(&environment));
+        let environment =
+            environment.with("CXXFLAGS", OsStr::new("-O2 -flto=auto"), ModifyMode::Set);
+        assert!(environment_uses_lto(&environment));
+    }
+
+    #[test]
+    fn targets_use_lto_given_by_the_toolchain() {
+        let mut flags = AnnotatedFlags::new();
+        flags.push("-O2", FlagOrigin::Target);
+        let mut commands = TargetCommands {
+            target: "app".to_string(),
+            compile: AnnotatedCommand {
+                program: PathBuf::from("g++"),
+                flags: flags.clone(),
+            },
+            link: None,
+        };
+        assert!(!commands.uses_lto());
+        flags.push("-flto", FlagOrigin::Toolchain);
+        commands.link = Some(AnnotatedCommand {
+            program: PathBuf::from("g++"),
+            flags,
+        });
+        assert!(commands.uses_lto());
+    }
+}
@@ -0,0 +1,594 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use toml::map::Map;
+use toml::Value;
+
+use crate::errors::FsError;
+use crate::mmk_parser::{
+    Keyword, Mmk, ParseMmkError, MMK_EXECUTABLE_FILE_NAME, MMK_LIBRARY_FILE_NAME,
+};
+use crate::parser::types::{Define, LibraryType, RawManifestData};
+use crate::utility;
+use crate::YAMBS_MANIFEST_NAME;
+
+// Keywords that map directly to a list of flags in a yambs.toml manifest. Defines given among
+// the compiler flags are lowered to defines of the target instead.
+const FLAG_KEYWORDS: &[(&str, &str)] = &[
+    ("MMK_CXXFLAGS_APPEND", "cxxflags_append"),
+    ("MMK_CFLAGS_APPEND", "cflags_append"),
+    ("MMK_CPPFLAGS_APPEND", "cppflags_append"),
+    ("MMK_LDFLAGS_APPEND", "ldflags_append"),
+];
+
+// Options of MMK_REQUIRE deciding how the library of the requirement is linked.
+const LINK_OPTIONS: &[&str] = &["STATIC", "WHOLE_ARCHIVE", "AS_NEEDED"];
+
+// Keywords that are either used as target names or converted separately.
+const HANDLED_KEYWORDS: &[&str] = &[
+    "MMK_REQUIRE",
+    "MMK_SOURCES",
+    "MMK_HEADERS",
+    "MMK_SYS_INCLUDE",
+    "MMK_EXECUTABLE",
+    "MMK_LIBRARY_LABEL",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConvertMmkError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+    #[error(transparent)]
+    Parse(#[from] ParseMmkError),
+    #[error("Could not find lib.mmk or run.mmk in {0:?}")]
+    NoMmkFile(PathBuf),
+    #[error("Dependency {0:?} does not contain a lib.mmk file")]
+    DependencyIsNotLibrary(PathBuf),
+    #[error("{0:?} already exists. Use --force to overwrite it.")]
+    ManifestExists(PathBuf),
+    #[error("Failed to serialize converted manifest")]
+    Serialize(#[source] toml::ser::Error),
+    #[error("The MMK files in {0:?} do not make a valid manifest")]
+    Lowering(PathBuf, #[source] toml::de::Error),
+    #[error("Library in {0:?} is required both as static and as shared library")]
+    ConflictingLibraryType(PathBuf),
+    #[error("Header only dependency {0:?} is not linked and can not be given {1}")]
+    LinkOptionOfHeaderOnly(PathBuf, String),
+}
+
+/// A yambs.toml manifest written from MMK files.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConvertedManifest {
+    pub manifest: PathBuf,
+    /// Keywords found in the MMK files that have no equivalent in yambs.toml.
+    pub ignored_keywords: Vec<String>,
+}
+
+/// Converts lib.mmk and run.mmk files to yambs.toml manifests.
+///
+/// Directories listed in MMK_REQUIRE are converted as well, so that the dependencies of the
+/// converted targets can be found by yambs.
+pub struct MmkConverter {
+    overwrite: bool,
+    // Library names of the directories converted so far.
+    converted: BTreeMap<PathBuf, String>,
+    // Types of the libraries converted so far. Libraries are static unless required with STATIC=0.
+    library_types: BTreeMap<PathBuf, LibraryType>,
+    manifests: Vec<ConvertedManifest>,
+}
+
+impl MmkConverter {
+    pub fn new(overwrite: bool) -> Self {
+        Self {
+            overwrite,
+            converted: BTreeMap::new(),
+            library_types: BTreeMap::new(),
+            manifests: Vec::new(),
+        }
+    }
+
+    pub fn convert(mut self, directory: &Path) -> Result<Vec<ConvertedManifest>, ConvertMmkError> {
+        let directory = directory.canonicalize().map_err(FsError::Canonicalize)?;
+        self.convert_directory(&directory, None)?;
+        Ok(self.manifests)
+    }
+
+    // Converts the MMK files of a directory and returns the name of the library it provides, if
+    // any. The library is given `library_type` when required with STATIC.
+    fn convert_directory(
+        &mut self,
+        directory: &Path,
+        library_type: Option<LibraryType>,
+    ) -> Result<Option<String>, ConvertMmkError> {
+        if let Some(library_name) = self.converted.get(directory) {
+            if let Some(library_type) = library_type {
+                if self.library_types.get(directory) != Some(&library_type) {
+                    return Err(ConvertMmkError::ConflictingLibraryType(
+                        directory.to_path_buf(),
+                    ));
+                }
+            }
+            return Ok(Some(library_name.clone()));
+        }
+
+        let library_file = directory.join(MMK_LIBRARY_FILE_NAME);
+        let executable_file = directory.join(MMK_EXECUTABLE_FILE_NAME);
+        if !library_file.is_file() && !executable_file.is_file() {
+            return Err(ConvertMmkError::NoMmkFile(directory.to_path_buf()));
+        }
+
+        let manifest_directory = manifest_directory_of(directory);
+        let manifest = manifest_directory.join(YAMBS_MANIFEST_NAME);
+        if manifest.exists() && !self.overwrite {
+            return Err(ConvertMmkError::ManifestExists(manifest));
+        }
+
+        let mut ignored_keywords = Vec::new();
+        let mut library_name = None;
+        let mut library_table = Map::new();
+        if library_file.is_file() {
+            let mmk = Mmk::from_file(&library_file)?;
+            let name = mmk
+                .get("MMK_LIBRARY_LABEL")
+                .first()
+                .map(|k| k.argument().to_string())
+                .unwrap_or_else(|| directory_name(&manifest_directory));
+            // Register before converting dependencies, to cope with cyclic requirements.
+            self.converted.insert(directory.to_path_buf(), name.clone());
+            self.library_types.insert(
+                directory.to_path_buf(),
+                library_type.clone().unwrap_or_default(),
+            );
+            library_name = Some(name.clone());
+            let mut target = self.convert_target(&mmk, directory, &manifest_directory)?;
+            if let Some(library_type) = library_type {
+                // Moved behind the type, as tables have to be emitted after plain values.
+                let dependencies = target.remove("dependencies");
+                target.insert(
+                    "type".to_string(),
+                    Value::try_from(library_type).map_err(ConvertMmkError::Serialize)?,
+                );
+                if let Some(dependencies) = dependencies {
+                    target.insert("dependencies".to_string(), dependencies);
+                }
+            }
+            library_table.insert(name, Value::Table(target));
+            collect_ignored_keywords(&mmk, &mut ignored_keywords);
+        }
+
+        let mut executable_table = Map::new();
+        if executable_file.is_file() {
+            let mmk = Mmk::from_file(&executable_file)?;
+            let target = self.convert_target(&mmk, directory, &manifest_directory)?;
+            for executable in mmk.get("MMK_EXECUTABLE") {
+                executable_table.insert(
+                    executable.argument().to_string(),
+                    Value::Table(target.clone()),
+                );
+            }
+            collect_ignored_keywords(&mmk, &mut ignored_keywords);
+        }
+
+        let mut root = Map::new();
+        if !executable_table.is_empty() {
+            root.insert("executable".to_string(), Value::Table(executable_table));
+        }
+        if !library_table.is_empty() {
+            root.insert("library".to_string(), Value::Table(library_table));
+        }
+        // The manifest is checked against the same target model the TOML front-end deserializes
+        // manifests into, so that converted targets behave as if they were written by hand.
+        let root = Value::Table(root);
+        root.clone()
+            .try_into::<RawManifestData>()
+            .map_err(|e| ConvertMmkError::Lowering(directory.to_path_buf(), e))?;
+        let content = toml::to_string(&root).map_err(ConvertMmkError::Serialize)?;
+        utility::write_file_atomically(&manifest, content.as_bytes())?;
+
+        log::debug!(
+            "Converted MMK files in {} to {}",
+            directory.display(),
+            manifest.display()
+        );
+        self.manifests.push(ConvertedManifest {
+            manifest,
+            ignored_keywords,
+        });
+        Ok(library_name)
+    }
+
+    fn convert_target(
+        &mut self,
+        mmk: &Mmk,
+        directory: &Path,
+        manifest_directory: &Path,
+    ) -> Result<Map<String, Value>, ConvertMmkError> {
+        let mut target = Map::new();
+
+        let source_prefix = directory
+            .strip_prefix(manifest_directory)
+            .unwrap_or_else(|_| Path::new(""));
+        let sources = mmk
+            .get("MMK_SOURCES")
+            .iter()
+            .chain(mmk.get("MMK_HEADERS"))
+            .map(|k| path_value(&source_prefix.join(k.argument())))
+            .collect::<Vec<Value>>();
+        target.insert("sources".to_string(), Value::Array(sources));
+
+        let mut defines = Vec::new();
+        for (keyword, manifest_key) in FLAG_KEYWORDS {
+            let (flag_defines, flags): (Vec<Keyword>, Vec<Keyword>) = mmk
+                .get(keyword)
+                .iter()
+                .cloned()
+                .partition(|k| *keyword != "MMK_LDFLAGS_APPEND" && define_of(k).is_some());
+            defines.extend(flag_defines.iter().filter_map(define_of));
+            if !flags.is_empty() {
+                target.insert(manifest_key.to_string(), string_array(&flags));
+            }
+        }
+        if !defines.is_empty() {
+            let defines = defines
+                .into_iter()
+                .map(Value::try_from)
+                .collect::<Result<Vec<Value>, toml::ser::Error>>()
+                .map_err(ConvertMmkError::Serialize)?;
+            target.insert("defines".to_string(), Value::Array(defines));
+        }
+
+        let system_include_directories = mmk.get("MMK_SYS_INCLUDE");
+        if !system_include_directories.is_empty() {
+            target.insert(
+                "append_system_include_directories".to_string(),
+                string_array(system_include_directories),
+            );
+        }
+
+        let mut dependencies = Map::new();
+        for requirement in mmk.get("MMK_REQUIRE") {
+            let required_directory = directory
+                .join(requirement.argument())
+                .canonicalize()
+                .map_err(FsError::Canonicalize)?;
+            let required_manifest_directory = manifest_directory_of(&required_directory);
+            let mut dependency = Map::new();
+            let name = if requirement.is_enabled("HEADERS_ONLY") == Some(true) {
+                if let Some(option) = LINK_OPTIONS
+                    .iter()
+                    .find(|option| requirement.option(option).is_some())
+                {
+                    return Err(ConvertMmkError::LinkOptionOfHeaderOnly(
+                        required_directory,
+                        option.to_string(),
+                    ));
+                }
+                let include_directory = relative_path(
+                    manifest_directory,
+                    &required_manifest_directory.join("include"),
+                );
+                dependency.insert(
+                    "include_directory".to_string(),
+                    path_value(&include_directory),
+                );
+                directory_name(&required_manifest_directory)
+            } else {
+                if !required_directory.join(MMK_LIBRARY_FILE_NAME).is_file() {
+                    return Err(ConvertMmkError::DependencyIsNotLibrary(required_directory));
+                }
+                let library_type =
+                    requirement
+                        .is_enabled("STATIC")
+                        .map(|is_static| match is_static {
+                            true => LibraryType::Static,
+                            false => LibraryType::Dynamic,
+                        });
+                let name = self
+                    .convert_directory(&required_directory, library_type)?
+                    .unwrap_or_default();
+                let path = relative_path(manifest_directory, &required_manifest_directory);
+                dependency.insert("path".to_string(), path_value(&path));
+                if let Some(whole_archive) = requirement.is_enabled("WHOLE_ARCHIVE") {
+                    dependency.insert("whole_archive".to_string(), Value::Boolean(whole_archive));
+                }
+                if let Some(as_needed) = requirement.is_enabled("AS_NEEDED") {
+                    dependency.insert("as_needed".to_string(), Value::Boolean(as_needed));
+                }
+                name
+            };
+            match requirement.is_enabled("SYSTEM") {
+                Some(true) => {
+                    dependency.insert("origin".to_string(), Value::String("System".to_string()));
+                }
+                Some(false) => {
+                    dependency.insert("origin".to_string(), Value::String("Include".to_string()));
+                }
+                None => {}
+            }
+            dependencies.insert(name, Value::Table(dependency));
+        }
+        // Tables have to be emitted after plain values in TOML, so this goes last.
+        if !dependencies.is_empty() {
+            target.insert("dependencies".to_string(), Value::Table(dependencies));
+        }
+        Ok(target)
+    }
+}
+
+// MMK files were commonly placed in the source directory of a project, whereas yambs.toml is placed
+// in the project root next to the include directory.
+fn manifest_directory_of(directory: &Path) -> PathBuf {
+    if utility::is_source_directory(directory, &utility::ProjectLayout::default()) {
+        directory.parent().unwrap_or(directory).to_path_buf()
+    } else {
+        directory.to_path_buf()
+    }
+}
+
+fn directory_name(directory: &Path) -> String {
+    directory
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn collect_ignored_keywords(mmk: &Mmk, ignored_keywords: &mut Vec<String>) {
+    for keyword in mmk.data().keys() {
+        let is_handled = HANDLED_KEYWORDS.contains(&keyword.as_str())
+            || FLAG_KEYWORDS.iter().any(|(k, _)| k == keyword);
+        if !is_handled && !ignored_keywords.contains(keyword) {
+            ignored_keywords.push(keyword.clone());
+        }
+    }
+}
+
+// Define given as a -D flag, such as -DNDEBUG or -DVERSION=2.
+fn define_of(keyword: &Keyword) -> Option<Define> {
+    let define = keyword.argument().strip_prefix("-D")?;
+    let (macro_, value) = match define.split_once('=') {
+        Some((macro_, value)) => (macro_, Some(value.to_string())),
+        None => (define, None),
+    };
+    if macro_.is_empty() {
+        return None;
+    }
+    Some(Define {
+        macro_: macro_.to_string(),
+        value,
+    })
+}
+
+fn string_array(keywords: &[Keyword]) -> Value {
+    Value::Array(
+        keywords
+            .iter()
+            .map(|k| Value::String(k.argument().to_string()))
+            .collect(),
+    )
+}
+
+fn path_value(path: &Path) -> Value {
+    Value::String(path.display().to_string())
+}
+
+// Path to `to` relative to `from`. Both paths are expected to be absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from_components = from.components().collect::<Vec<Component>>();
+    let to_components = to.components().collect::<Vec<Component>>();
+    let common = from_components
+        .iter()
+        .zip(&to_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut path = PathBuf::new();
+    for _ in common..from_components.len() {
+        path.push("..");
+    }
+    for component in &to_components[common..] {
+        path.push(component);
+    }
+    if path.as_os_str().is_empty() {
+        path.push(".");
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn relative_path_walks_up_to_common_ancestor() {
+        assert_eq!(
+            relative_path(Path::new("/project/app"), Path::new("/project/lib")),
+            PathBuf::from("../lib")
+        );
+        assert_eq!(
+            relative_path(Path::new("/project"), Path::new("/project")),
+            PathBuf::from(".")
+        );
+    }
+
+    #[test]
+    fn convert_writes_manifests_for_executable_and_required_library() {
+        let root = tempdir::TempDir::new("convert").unwrap();
+        let app = root.path().join("app");
+        let lib = root.path().join("lib");
+        write(
+            &app.join("src").join(MMK_EXECUTABLE_FILE_NAME),
+            indoc::indoc!(
+                "
+                MMK_REQUIRE:
+                   ../../lib/src SYSTEM
+
+                MMK_SOURCES:
+                   main.cpp
+
+                MMK_CXXFLAGS_APPEND:
+                   -pthread
+
+                MMK_CPPFLAGS_APPEND:
+                   -DNDEBUG -DVERSION=2 -Iextra
+
+                MMK_EXECUTABLE:
+                   x
+                "
+            ),
+        );
+        write(
+            &lib.join("src").join(MMK_LIBRARY_FILE_NAME),
+            indoc::indoc!(
+                "
+                MMK_SOURCES:
+                   lib.cpp
+
+                MMK_LIBRARY_LABEL:
+                   mylib
+
+                MMK_DEPEND:
+                   something
+                "
+            ),
+        );
+
+        let manifests = MmkConverter::new(false).convert(&app.join("src")).unwrap();
+        let lib = lib.canonicalize().unwrap();
+        let app = app.canonicalize().unwrap();
+        assert_eq!(
+            manifests,
+            vec![
+                ConvertedManifest {
+                    manifest: lib.join(YAMBS_MANIFEST_NAME),
+                    ignored_keywords: vec!["MMK_DEPEND".to_string()],
+                },
+                ConvertedManifest {
+                    manifest: app.join(YAMBS_MANIFEST_NAME),
+                    ignored_keywords: vec![],
+                }
+            ]
+        );
+
+        let app_manifest = std::fs::read_to_string(app.join(YAMBS_MANIFEST_NAME)).unwrap();
+        let app_data: RawManifestData = toml::from_str(&app_manifest).unwrap();
+        let x = &app_data.executables.unwrap()["x"];
+        assert_eq!(x.common_raw.sources, vec![PathBuf::from("src/main.cpp")]);
+        assert_eq!(
+            x.common_raw.compiler_flags.cxx_flags,
+            Some(crate::flags::CXXFlags::new(&["-pthread"]))
+        );
+        assert_eq!(
+            x.common_raw.compiler_flags.cpp_flags,
+            Some(crate::flags::CPPFlags::from_slice(&["-Iextra".to_string()]))
+        );
+        assert_eq!(
+            x.common_raw.defines,
+            vec![
+                Define {
+                    macro_: "NDEBUG".to_string(),
+                    value: None,
+                },
+                Define {
+                    macro_: "VERSION".to_string(),
+                    value: Some("2".to_string()),
+                }
+            ]
+        );
+        assert_eq!(
+            x.common_raw.dependencies["mylib"],
+            crate::parser::types::DependencyData::Source(crate::parser::types::SourceData {
+                path: PathBuf::from("../lib"),
+                origin: Some(crate::parser::types::IncludeSearchType::System),
+                link_options: crate::parser::types::LinkOptions::default(),
+            })
+        );
+
+        let lib_manifest = std::fs::read_to_string(lib.join(YAMBS_MANIFEST_NAME)).unwrap();
+        let lib_data: RawManifestData = toml::from_str(&lib_manifest).unwrap();
+        assert_eq!(
+            lib_data.libraries.unwrap()["mylib"].common_raw.sources,
+            vec![PathBuf::from("src/lib.cpp")]
+        );
+    }
+
+    #[test]
+    fn convert_lowers_options_of_requirements() {
+        let root = tempdir::TempDir::new("convert").unwrap();
+        write(
+            &root.path().join("app").join(MMK_EXECUTABLE_FILE_NAME),
+            indoc::indoc!(
+                "
+                MMK_REQUIRE:
+                   ../plugins STATIC=0 WHOLE_ARCHIVE=1
+                   ../headers HEADERS_ONLY=1 SYSTEM=0
+
+                MMK_SOURCES:
+                   main.cpp
+
+                MMK_EXECUTABLE:
+                   x
+                "
+            ),
+        );
+        write(
+            &root.path().join("plugins").join(MMK_LIBRARY_FILE_NAME),
+            "MMK_SOURCES:\n   plugin.cpp\n",
+        );
+        std::fs::create_dir_all(root.path().join("headers/include")).unwrap();
+
+        MmkConverter::new(false)
+            .convert(&root.path().join("app"))
+            .unwrap();
+        let root = root.path().canonicalize().unwrap();
+        let app_manifest =
+            std::fs::read_to_string(root.join("app").join(YAMBS_MANIFEST_NAME)).unwrap();
+        let app_data: RawManifestData = toml::from_str(&app_manifest).unwrap();
+        let dependencies = &app_data.executables.unwrap()["x"].common_raw.dependencies;
+        assert_eq!(
+            dependencies["plugins"],
+            crate::parser::types::DependencyData::Source(crate::parser::types::SourceData {
+                path: PathBuf::from("../plugins"),
+                origin: None,
+                link_options: crate::parser::types::LinkOptions {
+                    whole_archive: true,
+                    as_needed: true,
+                },
+            })
+        );
+        assert_eq!(
+            dependencies["headers"],
+            crate::parser::types::DependencyData::HeaderOnly(
+                crate::parser::types::HeaderOnlyData {
+                    include_directory: PathBuf::from("../headers/include"),
+                    origin: Some(crate::parser::types::IncludeSearchType::Include),
+                }
+            )
+        );
+
+        let plugins_manifest =
+            std::fs::read_to_string(root.join("plugins").join(YAMBS_MANIFEST_NAME)).unwrap();
+        let plugins_data: RawManifestData = toml::from_str(&plugins_manifest).unwrap();
+        assert_eq!(
+            plugins_data.libraries.unwrap()["plugins"].lib_type,
+            LibraryType::Dynamic
+        );
+    }
+
+    #[test]
+    fn convert_does_not_overwrite_existing_manifest() {
+        let root = tempdir::TempDir::new("convert").unwrap();
+        write(
+            &root.path().join(MMK_EXECUTABLE_FILE_NAME),
+            "MMK_SOURCES:\n   main.cpp\nMMK_EXECUTABLE:\n   x\n",
+        );
+        write(&root.path().join(YAMBS_MANIFEST_NAME), "");
+        let result = MmkConverter::new(false).convert(root.path());
+        assert!(matches!(result, Err(ConvertMmkError::ManifestExists(_))));
+    }
+}
@@ -0,0 +1,301 @@
+// Parser for the legacy MMK format (lib.mmk / run.mmk files) used before yambs.toml manifests.
+// An MMK file consists of keywords followed by a colon, with the arguments of the keyword listed
+// on the following lines:
+//
+// MMK_REQUIRE:
+//    ../some/dependency SYSTEM
+//    ../other/dependency STATIC=0 WHOLE_ARCHIVE=1
+//
+// MMK_SOURCES:
+//    main.cpp
+//    other.cpp
+//
+// MMK_EXECUTABLE:
+//    x
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::errors::FsError;
+use crate::utility;
+
+pub mod convert;
+
+pub const MMK_LIBRARY_FILE_NAME: &str = "lib.mmk";
+pub const MMK_EXECUTABLE_FILE_NAME: &str = "run.mmk";
+
+// Options that can be given after an argument, as NAME=value or, for SYSTEM, as NAME alone, by
+// the keywords that take them. Every option is either 0 or 1.
+const KEYWORD_OPTIONS: &[(&str, &[&str])] = &[(
+    "MMK_REQUIRE",
+    &[
+        "SYSTEM",
+        "STATIC",
+        "HEADERS_ONLY",
+        "WHOLE_ARCHIVE",
+        "AS_NEEDED",
+    ],
+)];
+
+// Options that can be given without a value, which then is 1.
+const BARE_OPTIONS: &[&str] = &["SYSTEM"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseMmkError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+    #[error("{0:?}, line {1}: Argument \"{2}\" is not placed under any keyword")]
+    ArgumentWithoutKeyword(PathBuf, usize, String),
+    #[error("{0:?}, line {1}: Option \"{2}\" is not preceded by an argument")]
+    OptionWithoutArgument(PathBuf, usize, String),
+    #[error("{0:?}, line {1}: Unknown option \"{2}\" of {3}")]
+    UnknownOption(PathBuf, usize, String, String),
+    #[error("{0:?}, line {1}: Option \"{2}\" is either 0 or 1, not \"{3}\"")]
+    InvalidOptionValue(PathBuf, usize, String, String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keyword {
+    argument: String,
+    options: BTreeMap<String, String>,
+}
+
+impl Keyword {
+    pub fn from(argument: &str) -> Self {
+        Self {
+            argument: argument.to_string(),
+            options: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_option(mut self, option: &str, value: &str) -> Self {
+        self.options.insert(option.to_string(), value.to_string());
+        self
+    }
+
+    pub fn argument(&self) -> &str {
+        &self.argument
+    }
+
+    /// Value of `option`, if it was given.
+    pub fn option(&self, option: &str) -> Option<&str> {
+        self.options.get(option).map(String::as_str)
+    }
+
+    /// Whether `option` was given as 1 rather than 0, if it was given.
+    pub fn is_enabled(&self, option: &str) -> Option<bool> {
+        self.option(option).map(|value| value == "1")
+    }
+}
+
+// Option and its value, if `token` is an option rather than an argument: a bare option, or
+// NAME=value where NAME is made of upper-case letters, digits and underscores.
+fn option_of(token: &str) -> Option<(&str, &str)> {
+    if BARE_OPTIONS.contains(&token) {
+        return Some((token, "1"));
+    }
+    let (name, value) = token.split_once('=')?;
+    let is_option_name = name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    is_option_name.then_some((name, value))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mmk {
+    data: BTreeMap<String, Vec<Keyword>>,
+    file: PathBuf,
+}
+
+impl Mmk {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            data: BTreeMap::new(),
+            file: path.to_path_buf(),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ParseMmkError> {
+        let content = utility::read_file(path)?;
+        let mut mmk = Self::new(path);
+        mmk.parse(&content)?;
+        Ok(mmk)
+    }
+
+    pub fn parse(&mut self, content: &str) -> Result<(), ParseMmkError> {
+        let file = self.file.clone();
+        let mut current_keyword: Option<String> = None;
+
+        for (line_number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(keyword) = line.strip_suffix(':') {
+                let keyword = keyword.trim().to_string();
+                self.data.entry(keyword.clone()).or_default();
+                current_keyword = Some(keyword);
+                continue;
+            }
+
+            let keyword = current_keyword.as_ref().ok_or_else(|| {
+                ParseMmkError::ArgumentWithoutKeyword(
+                    file.clone(),
+                    line_number + 1,
+                    line.to_string(),
+                )
+            })?;
+            let known_options = KEYWORD_OPTIONS
+                .iter()
+                .find(|(options_keyword, _)| options_keyword == keyword)
+                .map(|(_, options)| *options)
+                .unwrap_or_default();
+            let arguments = self.data.get_mut(keyword).unwrap();
+            for token in line.split_whitespace() {
+                let Some((option, value)) = option_of(token) else {
+                    arguments.push(Keyword::from(token));
+                    continue;
+                };
+                if !known_options.contains(&option) {
+                    return Err(ParseMmkError::UnknownOption(
+                        file.clone(),
+                        line_number + 1,
+                        option.to_string(),
+                        keyword.clone(),
+                    ));
+                }
+                if value != "0" && value != "1" {
+                    return Err(ParseMmkError::InvalidOptionValue(
+                        file.clone(),
+                        line_number + 1,
+                        option.to_string(),
+                        value.to_string(),
+                    ));
+                }
+                let last = arguments.last_mut().ok_or_else(|| {
+                    ParseMmkError::OptionWithoutArgument(
+                        file.clone(),
+                        line_number + 1,
+                        token.to_string(),
+                    )
+                })?;
+                last.options.insert(option.to_string(), value.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, keyword: &str) -> &[Keyword] {
+        self.data
+            .get(keyword)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn data(&self) -> &BTreeMap<String, Vec<Keyword>> {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut BTreeMap<String, Vec<Keyword>> {
+        &mut self.data
+    }
+
+    pub fn file(&self) -> PathBuf {
+        self.file.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse(content: &str) -> Result<Mmk, ParseMmkError> {
+        let mut mmk = Mmk::new(Path::new("run.mmk"));
+        mmk.parse(content)?;
+        Ok(mmk)
+    }
+
+    #[test]
+    fn parse_reads_arguments_of_keywords() {
+        let mmk = parse(indoc::indoc!(
+            "
+            MMK_SOURCES:
+               main.cpp
+               other.cpp
+
+            MMK_EXECUTABLE:
+               x
+            "
+        ))
+        .unwrap();
+        assert_eq!(
+            mmk.get("MMK_SOURCES"),
+            &[Keyword::from("main.cpp"), Keyword::from("other.cpp")]
+        );
+        assert_eq!(mmk.get("MMK_EXECUTABLE"), &[Keyword::from("x")]);
+    }
+
+    #[test]
+    fn parse_attaches_option_to_preceding_argument() {
+        let mmk = parse(indoc::indoc!(
+            "
+            MMK_REQUIRE:
+               /some/dependency SYSTEM
+               /other/dependency STATIC=0 WHOLE_ARCHIVE=1
+               /header/dependency
+            "
+        ))
+        .unwrap();
+        assert_eq!(
+            mmk.get("MMK_REQUIRE"),
+            &[
+                Keyword::from("/some/dependency").with_option("SYSTEM", "1"),
+                Keyword::from("/other/dependency")
+                    .with_option("STATIC", "0")
+                    .with_option("WHOLE_ARCHIVE", "1"),
+                Keyword::from("/header/dependency")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_fails_on_unknown_or_invalid_option() {
+        assert!(matches!(
+            parse("MMK_REQUIRE:\n   /dependency SHARED=1\n"),
+            Err(ParseMmkError::UnknownOption(_, 2, _, _))
+        ));
+        assert!(matches!(
+            parse("MMK_SOURCES:\n   main.cpp SYSTEM\n"),
+            Err(ParseMmkError::UnknownOption(_, 2, _, _))
+        ));
+        assert!(matches!(
+            parse("MMK_REQUIRE:\n   /dependency STATIC=yes\n"),
+            Err(ParseMmkError::InvalidOptionValue(_, 2, _, _))
+        ));
+    }
+
+    #[test]
+    fn parse_ignores_comments() {
+        let mmk = parse(indoc::indoc!(
+            "
+            # Flags used by all sources
+            MMK_CXXFLAGS_APPEND:
+               -pthread # Needed for std::thread
+            "
+        ))
+        .unwrap();
+        assert_eq!(mmk.get("MMK_CXXFLAGS_APPEND"), &[Keyword::from("-pthread")]);
+    }
+
+    #[test]
+    fn parse_fails_on_argument_without_keyword() {
+        let result = parse("main.cpp\n");
+        assert!(matches!(
+            result,
+            Err(ParseMmkError::ArgumentWithoutKeyword(_, 1, _))
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+// Resolves the `[defaults]` of the manifests of a project tree. Defaults set by a manifest apply to
+// its own targets and to the targets of the manifests in the directories below it, such as the
+// libraries of a repository depended on by path, unless a target sets the option itself. Defaults
+// of a manifest closer to the targets override those of the manifests above it. Only manifests up
+// to the directory of the project being built are searched, so that projects depended on from
+// elsewhere are built as their own manifests say.
+
+use std::path::{Path, PathBuf};
+
+use super::encoding::{self, EncodingError};
+use super::include::{self, IncludeError};
+use super::preprocessor::{Preprocessor, PreprocessorError};
+use super::types::{Define, RawCommonData, Standard};
+use crate::flags::{CFlags, CXXFlags, Warnings};
+use crate::YAMBS_MANIFEST_NAME;
+
+pub const DEFAULTS_KEY: &str = "defaults";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DefaultsError {
+    #[error("Failed to read manifest {0:?}")]
+    FailedToRead(PathBuf, #[source] std::io::Error),
+    #[error("Failed to decode manifest {0:?}")]
+    InvalidEncoding(PathBuf, #[source] EncodingError),
+    #[error("Preprocessor failed on manifest {0:?}")]
+    Preprocessor(PathBuf, #[source] PreprocessorError),
+    #[error("Failed to parse the defaults of manifest {0:?}")]
+    FailedToParse(PathBuf, #[source] toml::de::Error),
+    #[error("Failed to include manifest in {0:?}")]
+    Include(PathBuf, #[source] IncludeError),
+}
+
+/// Settings of `[defaults]`, given to the targets of a manifest and of the manifests below it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectoryDefaults {
+    /// Standard the targets are compiled with, unless they give one in their flags.
+    pub std: Option<Standard>,
+    /// Warnings of the targets that do not set `warnings`.
+    pub warnings: Option<Warnings>,
+    /// Defines of the targets, unless a target defines the same macro itself.
+    #[serde(default)]
+    pub defines: Vec<Define>,
+}
+
+impl DirectoryDefaults {
+    /// The defaults with those set by `nearer`, of a manifest closer to the targets, overriding
+    /// them.
+    pub fn overridden_by(mut self, nearer: DirectoryDefaults) -> Self {
+        self.defines
+            .retain(|define| !nearer.defines.iter().any(|d| d.macro_ == define.macro_));
+        self.defines.extend(nearer.defines);
+        self.std = nearer.std.or(self.std);
+        self.warnings = nearer.warnings.or(self.warnings);
+        self
+    }
+
+    /// Gives the defaults to the target declared by `common` where it does not set them itself.
+    /// The standard is passed before the flags of the target, so that a `-std` of its own wins.
+    pub fn apply(&self, common: &mut RawCommonData) {
+        let defines = self
+            .defines
+            .iter()
+            .filter(|define| !common.defines.iter().any(|d| d.macro_ == define.macro_))
+            .cloned()
+            .collect::<Vec<Define>>();
+        common.defines.splice(0..0, defines);
+        let flags = &mut common.compiler_flags;
+        if flags.warnings.is_none() {
+            flags.warnings = self.warnings.clone();
+        }
+
+        let sets_standard = |own: Option<&Vec<String>>| {
+            own.into_iter()
+                .flatten()
+                .any(|flag| flag.starts_with("-std="))
+        };
+        let with_standard = |own: Option<&Vec<String>>, flag: String| {
+            std::iter::once(flag)
+                .chain(own.into_iter().flatten().cloned())
+                .collect::<Vec<String>>()
+        };
+        match self.std {
+            Some(Standard::CXX(ref standard)) => {
+                let own = flags.cxx_flags.as_ref().map(|f| f.flags());
+                if !sets_standard(own) {
+                    let cxx_flags = with_standard(own, format!("-std={}", standard.to_string()));
+                    flags.cxx_flags = Some(CXXFlags::from_slice(&cxx_flags));
+                }
+            }
+            Some(Standard::C(ref standard)) => {
+                let own = flags.c_flags.as_ref().map(|f| f.flags());
+                if !sets_standard(own) {
+                    let c_flags = with_standard(own, format!("-std={}", standard.to_string()));
+                    flags.c_flags = Some(CFlags::from_slice(&c_flags));
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/// Manifests in the directories above the one of `manifest_path`, up to and including
+/// `project_directory`, from the outermost. Empty when the manifest is not below the project.
+pub fn enclosing_manifests(manifest_path: &Path, project_directory: &Path) -> Vec<PathBuf> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let project_directory = canonical(project_directory);
+    let manifest_path = canonical(manifest_path);
+    let mut manifests = manifest_path
+        .ancestors()
+        .skip(2)
+        .take_while(|directory| directory.starts_with(&project_directory))
+        .map(|directory| directory.join(YAMBS_MANIFEST_NAME))
+        .filter(|manifest| manifest.is_file())
+        .collect::<Vec<PathBuf>>();
+    manifests.reverse();
+    manifests
+}
+
+/// Defaults the manifest at `manifest_path` inherits from the manifests above it within
+/// `project_directory`. The manifests are run through `preprocessor`, and their includes are
+/// resolved, the same way as when they are parsed themselves.
+pub fn inherited_defaults(
+    manifest_path: &Path,
+    project_directory: &Path,
+    preprocessor: &mut Preprocessor,
+) -> Result<DirectoryDefaults, DefaultsError> {
+    let mut defaults = DirectoryDefaults::default();
+    for manifest in enclosing_manifests(manifest_path, project_directory) {
+        let content = std::fs::read(&manifest)
+            .map_err(|e| DefaultsError::FailedToRead(manifest.clone(), e))?;
+        let content = encoding::decode(&content)
+            .map_err(|e| DefaultsError::InvalidEncoding(manifest.clone(), e))?;
+        let content = preprocessor
+            .parse(&content)
+            .map_err(|e| DefaultsError::Preprocessor(manifest.clone(), e))?;
+        let value = toml::from_str::<toml::Value>(&content)
+            .map_err(|e| DefaultsError::FailedToParse(manifest.clone(), e))?;
+        let value = include::resolve_includes(value, &manifest, preprocessor)
+            .map_err(|e| DefaultsError::Include(manifest.clone(), e))?;
+        if let Some(table) = value.get(DEFAULTS_KEY).cloned() {
+            let enclosing = table
+                .try_into::<DirectoryDefaults>()
+                .map_err(|e| DefaultsError::FailedToParse(manifest.clone(), e))?;
+            defaults = defaults.overridden_by(enclosing);
+        }
+    }
+    Ok(defaults)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flags::WarningProfile;
+    use pretty_assertions::assert_eq;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn nearer_defaults_override_those_above_and_targets_override_both() {
+        let tempdir = tempdir::TempDir::new("defaults").unwrap();
+        let project = tempdir.path().canonicalize().unwrap();
+        write(
+            &project,
+            "yambs.toml",
+            indoc::indoc! {r#"
+                [defaults]
+                std = "c++17"
+                warnings = "strict"
+                defines = [{ macro = "PLATFORM", value = "linux" }, { macro = "LOG" }]
+            "#},
+        );
+        write(
+            &project,
+            "libs/yambs.toml",
+            "[defaults]\ndefines = [{ macro = \"LOG\", value = \"0\" }]\n",
+        );
+        let manifest = write(&project, "libs/net/yambs.toml", "");
+
+        assert_eq!(
+            enclosing_manifests(&manifest, &project),
+            vec![project.join("yambs.toml"), project.join("libs/yambs.toml")]
+        );
+        assert_eq!(
+            enclosing_manifests(&project.join("yambs.toml"), &project),
+            Vec::<PathBuf>::new()
+        );
+        let defaults = inherited_defaults(&manifest, &project, &mut Preprocessor::new()).unwrap();
+        assert_eq!(
+            defaults.defines,
+            vec![
+                Define {
+                    macro_: "PLATFORM".to_string(),
+                    value: Some("linux".to_string()),
+                },
+                Define {
+                    macro_: "LOG".to_string(),
+                    value: Some("0".to_string()),
+                },
+            ]
+        );
+
+        let mut target = toml::from_str::<RawCommonData>(indoc::indoc! {r#"
+            sources = ["net.cpp"]
+            cxxflags_append = ["-O1"]
+            defines = [{ macro = "PLATFORM", value = "embedded" }]
+        "#})
+        .unwrap();
+        defaults.apply(&mut target);
+        assert_eq!(
+            target.compiler_flags.cxx_flags.unwrap().flags(),
+            &vec!["-std=c++17".to_string(), "-O1".to_string()]
+        );
+        assert_eq!(
+            target.compiler_flags.warnings,
+            Some(Warnings::Profile(WarningProfile::Strict))
+        );
+        assert_eq!(
+            target
+                .defines
+                .iter()
+                .map(|define| define.value.clone().unwrap_or_default())
+                .collect::<Vec<String>>(),
+            vec!["0", "embedded"]
+        );
+
+        let mut target = toml::from_str::<RawCommonData>(
+            "sources = [\"net.cpp\"]\ncxxflags_append = [\"-std=c++20\"]\nwarnings = \"none\"\n",
+        )
+        .unwrap();
+        defaults.apply(&mut target);
+        assert_eq!(
+            target.compiler_flags.cxx_flags.unwrap().flags(),
+            &vec!["-std=c++20".to_string()]
+        );
+        assert_eq!(
+            target.compiler_flags.warnings,
+            Some(Warnings::Profile(WarningProfile::None))
+        );
+    }
+}
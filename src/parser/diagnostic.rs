@@ -0,0 +1,213 @@
+// Locations of the errors of a manifest, so that they can be reported Rust-style with a caret
+// pointing into yambs.toml, or as JSON for editors to show them where they are.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Where in a manifest an error is. Lines and columns are counted from 1.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    /// Number of characters pointed at, starting at `column`.
+    pub length: usize,
+    /// The line of the manifest the error is on.
+    pub source_line: String,
+}
+
+impl SourceLocation {
+    /// Location of the character at byte `start` of `source`, spanning up to byte `end`.
+    pub fn from_offsets(file: &Path, source: &str, start: usize, end: usize) -> Self {
+        let start = start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let end = end.clamp(start, line_end);
+        Self {
+            file: file.to_path_buf(),
+            line: source[..start].matches('\n').count() + 1,
+            column: source[line_start..start].chars().count() + 1,
+            length: source[start..end].chars().count().max(1),
+            source_line: source[line_start..line_end].trim_end().to_string(),
+        }
+    }
+
+    /// Location of the error toml gave when parsing `source`, if it knows where it is.
+    pub fn of_toml_error(file: &Path, source: &str, error: &toml::de::Error) -> Option<Self> {
+        let (line, column) = error.line_col()?;
+        let line_start = source
+            .split_inclusive('\n')
+            .take(line)
+            .map(str::len)
+            .sum::<usize>();
+        let offset = source[line_start..]
+            .char_indices()
+            .nth(column)
+            .map_or(source.len() - line_start, |(i, _)| i);
+        Some(Self::from_offsets(
+            file,
+            source,
+            line_start + offset,
+            line_start + offset,
+        ))
+    }
+
+    /// Location of the dotted `key` in `source`, such as `executable.x.dependencies.fmt`. If the
+    /// key is not written out in full, the deepest of its tables that is is pointed at.
+    pub fn of_key(file: &Path, source: &str, key: &str) -> Option<Self> {
+        let tree = toml::from_str::<KeyTree>(source).ok()?;
+        let (start, end) = tree.find(&key.split('.').collect::<Vec<&str>>())?;
+        Some(Self::from_offsets(file, source, start, end))
+    }
+}
+
+/// An error to report, located in a manifest if it is about one.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Diagnostic {
+    pub severity: &'static str,
+    pub message: String,
+    pub location: Option<SourceLocation>,
+}
+
+impl Diagnostic {
+    pub fn error(message: &str, location: Option<SourceLocation>) -> Self {
+        Self {
+            severity: "error",
+            message: message.to_string(),
+            location,
+        }
+    }
+
+    /// The diagnostic as rustc prints them, with the line of the manifest and a caret under
+    /// where the error is.
+    pub fn render(&self) -> String {
+        let mut text = format!("{}: {}\n", self.severity, self.message);
+        if let Some(ref location) = self.location {
+            let gutter = " ".repeat(location.line.to_string().len());
+            text.push_str(&format!(
+                "{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+                gutter,
+                location.file.display(),
+                location.line,
+                location.column,
+                gutter,
+                location.line,
+                location.source_line,
+                gutter,
+                " ".repeat(location.column - 1),
+                "^".repeat(location.length),
+            ));
+        }
+        text
+    }
+}
+
+// Keys of a manifest with their byte offsets, nested as the tables they are keys of. Arrays are
+// not descended into.
+#[derive(Debug, Default)]
+struct KeyTree(BTreeMap<String, ((usize, usize), KeyTree)>);
+
+impl KeyTree {
+    // Offsets of the key made of `segments`. Names with dots are quoted in the manifest, so the
+    // segments are joined with the following ones until the key of a table is found.
+    fn find(&self, segments: &[&str]) -> Option<(usize, usize)> {
+        (1..=segments.len()).rev().find_map(|taken| {
+            let (span, subtree) = self.0.get(&segments[..taken].join("."))?;
+            if taken == segments.len() {
+                Some(*span)
+            } else {
+                subtree.find(&segments[taken..]).or(Some(*span))
+            }
+        })
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for KeyTree {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KeyTreeVisitor)
+    }
+}
+
+struct KeyTreeVisitor;
+
+impl<'de> serde::de::Visitor<'de> for KeyTreeVisitor {
+    type Value = KeyTree;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a TOML value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(KeyTree::default())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(KeyTree::default())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(KeyTree::default())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(KeyTree::default())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+        Ok(KeyTree::default())
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        while seq.next_element::<serde::de::IgnoredAny>()?.is_some() {}
+        Ok(KeyTree::default())
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut keys = BTreeMap::new();
+        while let Some(key) = map.next_key::<toml::Spanned<String>>()? {
+            let subtree = map.next_value::<KeyTree>()?;
+            let span = (key.start(), key.end());
+            keys.insert(key.into_inner(), (span, subtree));
+        }
+        Ok(KeyTree(keys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn keys_and_syntax_errors_are_located_in_the_manifest() {
+        let file = Path::new("yambs.toml");
+        let source = "[executable.x]\nsources = ['x.cpp']\n\n[library.\"core.io\"]\nsources = ['io.cpp']\ndependencies = { fmt = { path = \"fmt\" } }\n";
+        let location =
+            SourceLocation::of_key(file, source, "library.core.io.dependencies.fmt").unwrap();
+        assert_eq!(
+            (location.line, location.column, location.length),
+            (6, 18, 3)
+        );
+        let location = SourceLocation::of_key(file, source, "executable.x.flag_groups").unwrap();
+        assert_eq!(
+            (location.line, location.column, location.length),
+            (1, 13, 1)
+        );
+        assert_eq!(
+            Diagnostic::error("x has no flag group", Some(location)).render(),
+            "error: x has no flag group\n \
+             --> yambs.toml:1:13\n  \
+             |\n\
+             1 | [executable.x]\n  \
+             |             ^\n"
+        );
+
+        let source = "[executable.x]\nsources = ['x.cpp' 'y.cpp']\n";
+        let error = toml::from_str::<toml::Value>(source).unwrap_err();
+        let location = SourceLocation::of_toml_error(file, source, &error).unwrap();
+        assert_eq!(location.line, 2);
+        assert_eq!(location.source_line, "sources = ['x.cpp' 'y.cpp']");
+    }
+}
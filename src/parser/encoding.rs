@@ -0,0 +1,107 @@
+// Manifests are UTF-8. Editors on Windows commonly save files with a byte order mark and CRLF line
+// endings, which are normalized away so that such manifests parse the same as any other. Anything
+// that is not UTF-8 is rejected with the location of the first invalid byte, as the manifest would
+// otherwise fail to parse somewhere further on with a far less helpful error.
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EncodingError {
+    #[error(
+        "Invalid UTF-8 at byte offset {offset} (line {line}, column {column}). \
+         Manifests must be UTF-8 encoded, but this looks like another encoding, such as Windows-1252 or ISO-8859-1. \
+         Save the file as UTF-8."
+    )]
+    InvalidUtf8 {
+        offset: usize,
+        line: usize,
+        column: usize,
+    },
+    #[error(
+        "The file is encoded as {0}. Manifests must be UTF-8 encoded. Save the file as UTF-8."
+    )]
+    UnsupportedEncoding(&'static str),
+}
+
+/// Decodes the content of a manifest file, normalized with `normalize`.
+pub fn decode(bytes: &[u8]) -> Result<String, EncodingError> {
+    // UTF-32 LE starts with the BOM of UTF-16 LE, so it is checked first.
+    let byte_order_marks: [(&[u8], &'static str); 4] = [
+        (b"\xFF\xFE\x00\x00", "UTF-32 (little endian)"),
+        (b"\x00\x00\xFE\xFF", "UTF-32 (big endian)"),
+        (b"\xFF\xFE", "UTF-16 (little endian)"),
+        (b"\xFE\xFF", "UTF-16 (big endian)"),
+    ];
+    for (byte_order_mark, encoding) in byte_order_marks.iter() {
+        if bytes.starts_with(byte_order_mark) {
+            return Err(EncodingError::UnsupportedEncoding(encoding));
+        }
+    }
+
+    let (bom_length, content) = match bytes.strip_prefix(UTF8_BOM) {
+        Some(content) => (UTF8_BOM.len(), content),
+        None => (0, bytes),
+    };
+    let content = std::str::from_utf8(content).map_err(|e| {
+        let valid = &content[..e.valid_up_to()];
+        let line_start = valid
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |newline| newline + 1);
+        EncodingError::InvalidUtf8 {
+            offset: bom_length + e.valid_up_to(),
+            line: valid.iter().filter(|byte| **byte == b'\n').count() + 1,
+            column: String::from_utf8_lossy(&valid[line_start..])
+                .chars()
+                .count()
+                + 1,
+        }
+    })?;
+    Ok(normalize(content))
+}
+
+/// Strips a leading byte order mark and converts CRLF line endings to LF.
+pub fn normalize(content: &str) -> String {
+    content
+        .strip_prefix('\u{feff}')
+        .unwrap_or(content)
+        .replace("\r\n", "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn decode_strips_bom_and_normalizes_line_endings() {
+        let bytes = b"\xEF\xBB\xBF[executable.x]\r\nsources = ['main.cpp']\r\n";
+        assert_eq!(
+            decode(bytes).unwrap(),
+            "[executable.x]\nsources = ['main.cpp']\n"
+        );
+    }
+
+    #[test]
+    fn decode_reports_location_of_invalid_utf8() {
+        // "café" saved as Windows-1252, after a BOM that is counted in the offset.
+        let bytes = b"\xEF\xBB\xBF[executable.x]\r\n# \xC3\xA5 caf\xE9\r\n";
+        assert_eq!(
+            decode(bytes).unwrap_err(),
+            EncodingError::InvalidUtf8 {
+                offset: 27,
+                line: 2,
+                column: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn decode_rejects_utf16() {
+        let bytes = b"\xFF\xFE[\x00e\x00";
+        assert_eq!(
+            decode(bytes).unwrap_err(),
+            EncodingError::UnsupportedEncoding("UTF-16 (little endian)")
+        );
+    }
+}
@@ -0,0 +1,340 @@
+// Resolves the `include` key of a manifest, which lists other manifests whose settings are shared
+// by the including manifest. Includes are merged before the manifest itself, in the order they are
+// listed, so that:
+//   * settings in the including manifest override those of any included manifest, and
+//   * settings in a later include override those of an earlier include.
+// Tables are merged key by key, while any other value (including arrays) replaces the value it
+// overrides. Included manifests may include other manifests, as long as no manifest ends up
+// including itself.
+
+use std::path::{Path, PathBuf};
+
+use super::encoding::{self, EncodingError};
+use super::preprocessor::{Preprocessor, PreprocessorError};
+
+pub const INCLUDE_KEY: &str = "include";
+
+#[derive(Debug, thiserror::Error)]
+pub enum IncludeError {
+    #[error("Failed to read included manifest {0:?}")]
+    FailedToRead(PathBuf, #[source] std::io::Error),
+    #[error("Failed to decode included manifest {0:?}")]
+    InvalidEncoding(PathBuf, #[source] EncodingError),
+    #[error("Failed to parse included manifest {0:?}")]
+    FailedToParse(PathBuf, #[source] toml::de::Error),
+    #[error("Preprocessor failed on included manifest {0:?}")]
+    Preprocessor(PathBuf, #[source] PreprocessorError),
+    #[error("\"include\" in {0:?} must be an array of paths")]
+    InvalidInclude(PathBuf),
+    #[error("Manifest includes itself: {}", format_cycle(.0))]
+    Cycle(Vec<PathBuf>),
+}
+
+fn format_cycle(cycle: &[PathBuf]) -> String {
+    cycle
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<String>>()
+        .join(" -> ")
+}
+
+/// Merges the manifests included by `manifest`, located at `manifest_path`, into it.
+/// Included manifests are run through `preprocessor` the same way the including manifest is.
+pub fn resolve_includes(
+    manifest: toml::Value,
+    manifest_path: &Path,
+    preprocessor: &mut Preprocessor,
+) -> Result<toml::Value, IncludeError> {
+    let manifest_path = manifest_path
+        .canonicalize()
+        .unwrap_or_else(|_| manifest_path.to_path_buf());
+    let mut include_stack = Vec::new();
+    resolve(manifest, manifest_path, preprocessor, &mut include_stack)
+}
+
+/// Manifests included by the manifest at `manifest_path`, directly or through other includes.
+/// Includes are found without preprocessing the manifests, and manifests that can not be read
+/// or parsed are left out along with what they include.
+pub fn included_manifests(manifest_path: &Path) -> Vec<PathBuf> {
+    let manifest_path = manifest_path
+        .canonicalize()
+        .unwrap_or_else(|_| manifest_path.to_path_buf());
+    let mut included = Vec::new();
+    let mut pending = vec![manifest_path.clone()];
+    while let Some(path) = pending.pop() {
+        let includes = std::fs::read(&path)
+            .ok()
+            .and_then(|content| encoding::decode(&content).ok())
+            .and_then(|content| toml::from_str::<toml::Value>(&content).ok())
+            .and_then(|manifest| manifest.get(INCLUDE_KEY).cloned());
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        if let Some(toml::Value::Array(includes)) = includes {
+            for include in includes.iter().filter_map(toml::Value::as_str) {
+                let include_path = directory.join(include);
+                let include_path = include_path.canonicalize().unwrap_or(include_path);
+                if include_path != manifest_path && !included.contains(&include_path) {
+                    included.push(include_path.clone());
+                    pending.push(include_path);
+                }
+            }
+        }
+    }
+    included
+}
+
+fn resolve(
+    manifest: toml::Value,
+    manifest_path: PathBuf,
+    preprocessor: &mut Preprocessor,
+    include_stack: &mut Vec<PathBuf>,
+) -> Result<toml::Value, IncludeError> {
+    let mut table = match manifest {
+        toml::Value::Table(table) => table,
+        other => return Ok(other),
+    };
+    let includes = match table.remove(INCLUDE_KEY) {
+        None => return Ok(toml::Value::Table(table)),
+        Some(toml::Value::Array(includes)) => includes,
+        Some(_) => return Err(IncludeError::InvalidInclude(manifest_path)),
+    };
+
+    let directory = manifest_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    include_stack.push(manifest_path);
+
+    let mut merged = toml::map::Map::new();
+    for include in includes {
+        let include = include
+            .as_str()
+            .ok_or_else(|| IncludeError::InvalidInclude(include_stack.last().unwrap().clone()))?;
+        let include_path = directory.join(include);
+        let include_path = include_path
+            .canonicalize()
+            .map_err(|e| IncludeError::FailedToRead(include_path, e))?;
+
+        if include_stack.contains(&include_path) {
+            let mut cycle = include_stack.clone();
+            cycle.push(include_path);
+            return Err(IncludeError::Cycle(cycle));
+        }
+
+        log::debug!("Including manifest {}", include_path.display());
+        let content = std::fs::read(&include_path)
+            .map_err(|e| IncludeError::FailedToRead(include_path.clone(), e))?;
+        let content = encoding::decode(&content)
+            .map_err(|e| IncludeError::InvalidEncoding(include_path.clone(), e))?;
+        let content = preprocessor
+            .parse(&content)
+            .map_err(|e| IncludeError::Preprocessor(include_path.clone(), e))?;
+        let included = toml::from_str::<toml::Value>(&content)
+            .map_err(|e| IncludeError::FailedToParse(include_path.clone(), e))?;
+        if let toml::Value::Table(included) =
+            resolve(included, include_path, preprocessor, include_stack)?
+        {
+            merge(&mut merged, included);
+        }
+    }
+    include_stack.pop();
+
+    merge(&mut merged, table);
+    Ok(toml::Value::Table(merged))
+}
+
+fn merge(
+    base: &mut toml::map::Map<String, toml::Value>,
+    overrides: toml::map::Map<String, toml::Value>,
+) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(override_table)) => {
+                merge(base_table, override_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn resolve_file(path: &Path) -> Result<toml::Value, IncludeError> {
+        let content = std::fs::read_to_string(path).unwrap();
+        resolve_includes(
+            toml::from_str(&content).unwrap(),
+            path,
+            &mut Preprocessor::new(),
+        )
+    }
+
+    #[test]
+    fn included_manifests_are_found_through_nested_includes() {
+        let tempdir = tempdir::TempDir::new("include").unwrap();
+        let directory = tempdir.path().canonicalize().unwrap();
+        let second = write(&directory, "common/second.toml", "");
+        let first = write(
+            &directory,
+            "common/first.toml",
+            r#"include = ["second.toml", "../project/yambs.toml"]"#,
+        );
+        let manifest = write(
+            &directory,
+            "project/yambs.toml",
+            r#"include = ["../common/first.toml"]"#,
+        );
+        assert_eq!(included_manifests(&manifest), vec![first, second]);
+    }
+
+    #[test]
+    fn manifest_overrides_later_include_which_overrides_earlier_include() {
+        let tempdir = tempdir::TempDir::new("include").unwrap();
+        write(
+            tempdir.path(),
+            "common/first.toml",
+            r#"
+            [project_config]
+            std = "c++14"
+            language = "C++"
+
+            [executable.x]
+            cxxflags_append = ["-O1"]
+            "#,
+        );
+        write(
+            tempdir.path(),
+            "common/second.toml",
+            r#"
+            [project_config]
+            std = "c++17"
+            "#,
+        );
+        let manifest = write(
+            tempdir.path(),
+            "project/yambs.toml",
+            r#"
+            include = ["../common/first.toml", "../common/second.toml"]
+
+            [executable.x]
+            sources = ["main.cpp"]
+            "#,
+        );
+
+        let expected = toml::from_str::<toml::Value>(
+            r#"
+            [project_config]
+            std = "c++17"
+            language = "C++"
+
+            [executable.x]
+            cxxflags_append = ["-O1"]
+            sources = ["main.cpp"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(resolve_file(&manifest).unwrap(), expected);
+    }
+
+    #[test]
+    fn nested_includes_are_relative_to_the_including_manifest() {
+        let tempdir = tempdir::TempDir::new("include").unwrap();
+        write(
+            tempdir.path(),
+            "common/base/base.toml",
+            "[project_config]\nlanguage = \"C\"\n",
+        );
+        write(
+            tempdir.path(),
+            "common/shared.toml",
+            "include = [\"base/base.toml\"]\n",
+        );
+        let manifest = write(
+            tempdir.path(),
+            "yambs.toml",
+            "include = [\"common/shared.toml\"]\n",
+        );
+
+        let expected =
+            toml::from_str::<toml::Value>("[project_config]\nlanguage = \"C\"\n").unwrap();
+        assert_eq!(resolve_file(&manifest).unwrap(), expected);
+    }
+
+    #[test]
+    fn include_cycle_is_an_error() {
+        let tempdir = tempdir::TempDir::new("include").unwrap();
+        write(tempdir.path(), "a.toml", "include = [\"b.toml\"]\n");
+        write(tempdir.path(), "b.toml", "include = [\"a.toml\"]\n");
+        let manifest = write(tempdir.path(), "yambs.toml", "include = [\"a.toml\"]\n");
+
+        match resolve_file(&manifest) {
+            Err(IncludeError::Cycle(cycle)) => {
+                let names = cycle
+                    .iter()
+                    .map(|p| p.file_name().unwrap().to_str().unwrap().to_string())
+                    .collect::<Vec<String>>();
+                assert_eq!(names, vec!["yambs.toml", "a.toml", "b.toml", "a.toml"]);
+            }
+            other => panic!("Expected include cycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn same_manifest_included_twice_is_not_a_cycle() {
+        let tempdir = tempdir::TempDir::new("include").unwrap();
+        write(
+            tempdir.path(),
+            "base.toml",
+            "[project_config]\nlanguage = \"C\"\n",
+        );
+        write(tempdir.path(), "a.toml", "include = [\"base.toml\"]\n");
+        write(tempdir.path(), "b.toml", "include = [\"base.toml\"]\n");
+        let manifest = write(
+            tempdir.path(),
+            "yambs.toml",
+            "include = [\"a.toml\", \"b.toml\"]\n",
+        );
+        assert!(resolve_file(&manifest).is_ok());
+    }
+
+    #[test]
+    fn included_manifest_saved_with_bom_and_crlf_is_normalized() {
+        let tempdir = tempdir::TempDir::new("include").unwrap();
+        write(
+            tempdir.path(),
+            "base.toml",
+            "\u{feff}[executable.x]\r\ndescription = \"\"\"\r\nline\"\"\"\r\n",
+        );
+        let manifest = write(tempdir.path(), "yambs.toml", "include = [\"base.toml\"]\n");
+        let expected =
+            toml::from_str::<toml::Value>("[executable.x]\ndescription = \"\"\"\nline\"\"\"\n")
+                .unwrap();
+        assert_eq!(resolve_file(&manifest).unwrap(), expected);
+    }
+
+    #[test]
+    fn included_manifest_with_invalid_utf8_reports_its_path() {
+        let tempdir = tempdir::TempDir::new("include").unwrap();
+        std::fs::write(tempdir.path().join("base.toml"), b"# caf\xE9\n").unwrap();
+        let manifest = write(tempdir.path(), "yambs.toml", "include = [\"base.toml\"]\n");
+        match resolve_file(&manifest) {
+            Err(IncludeError::InvalidEncoding(path, EncodingError::InvalidUtf8 { offset, .. })) => {
+                assert_eq!(path.file_name().unwrap(), "base.toml");
+                assert_eq!(offset, 5);
+            }
+            other => panic!("Expected invalid encoding, got {:?}", other),
+        }
+    }
+}
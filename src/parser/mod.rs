@@ -1,20 +1,117 @@
 use crate::manifest;
 
+pub mod defaults;
+pub mod diagnostic;
+pub mod encoding;
+pub mod include;
+pub mod prefetch;
 pub mod preprocessor;
 pub mod types;
 
+use crate::DEPENDENCY_TESTS;
 use crate::YAMBS_BUILD_DIR_VAR;
 use crate::YAMBS_BUILD_TYPE;
 use crate::YAMBS_MANIFEST_DIR;
+use crate::YAMBS_TARGET_TRIPLE;
+use defaults::DefaultsError;
+use diagnostic::SourceLocation;
+use encoding::EncodingError;
+use include::IncludeError;
 use preprocessor::{Preprocessor, PreprocessorError, Variable};
 
 // FIXME: Write tests!
 pub fn parse(manifest_path: &std::path::Path) -> Result<manifest::ParsedManifest, ParseTomlError> {
+    let content = std::fs::read(manifest_path).map_err(ParseTomlError::FailedToRead)?;
+    let toml_content = encoding::decode(&content)
+        .map_err(|e| ParseTomlError::InvalidEncoding(manifest_path.to_path_buf(), e))?;
+    let mut preprocessor = preset_preprocessor();
+    let manifest_parsed = preprocessor
+        .parse(&toml_content)
+        .map_err(ParseTomlError::Preprocessor)?;
+    let metadata =
+        std::fs::metadata(manifest_path).expect("Could not fetch metadata from yambs.json");
+    let manifest_directory = manifest_path.parent().unwrap();
+    Ok(manifest::ParsedManifest {
+        manifest: manifest::Manifest {
+            directory: manifest_directory.to_path_buf(),
+            modification_time: metadata
+                .modified()
+                .expect("Could not fetch last modified time of manifest"),
+            content_hash: manifest::content_hash_of(manifest_path, &content),
+        },
+        data: parse_toml(&manifest_parsed, manifest_path, &mut preprocessor)?,
+    })
+}
+
+/// Parses `content` as if it was the manifest at `manifest_path`, which does not have to exist.
+/// Files it includes and refers to are still read from disk.
+pub fn parse_content(
+    content: &[u8],
+    manifest_path: &std::path::Path,
+) -> Result<manifest::ManifestData, ParseTomlError> {
+    parse_content_with(content, manifest_path, preset_preprocessor())
+}
+
+fn parse_content_with(
+    content: &[u8],
+    manifest_path: &std::path::Path,
+    mut preprocessor: Preprocessor,
+) -> Result<manifest::ManifestData, ParseTomlError> {
+    let toml_content = encoding::decode(content)
+        .map_err(|e| ParseTomlError::InvalidEncoding(manifest_path.to_path_buf(), e))?;
+    let manifest_parsed = preprocessor
+        .parse(&toml_content)
+        .map_err(ParseTomlError::Preprocessor)?;
+    parse_toml(&manifest_parsed, manifest_path, &mut preprocessor)
+}
+
+/// The JSON `yambs introspect` prints for `manifest_data`.
+pub fn introspect(manifest_data: &manifest::ManifestData) -> String {
+    serde_json::to_string_pretty(manifest_data).expect("Manifest data is always valid JSON")
+}
+
+/// `build_dir` of the project in the manifest at `manifest_path`, relative to the manifest
+/// directory. The build directory is needed before the manifest can be parsed in full, so only
+/// `YAMBS_BUILD_TYPE`, `YAMBS_MANIFEST_DIR` and environment variables can be used in it.
+pub fn parse_build_dir(
+    manifest_path: &std::path::Path,
+    build_type: &crate::cli::configurations::BuildType,
+) -> Result<Option<std::path::PathBuf>, ParseTomlError> {
+    let content = std::fs::read(manifest_path).map_err(ParseTomlError::FailedToRead)?;
+    let toml_content = encoding::decode(&content)
+        .map_err(|e| ParseTomlError::InvalidEncoding(manifest_path.to_path_buf(), e))?;
+    let manifest_value = toml::from_str::<toml::Value>(&toml_content).map_err(|e| {
+        let location = SourceLocation::of_toml_error(manifest_path, &toml_content, &e);
+        ParseTomlError::FailedToParse(e, location.map(Box::new))
+    })?;
+    let build_dir = match manifest_value
+        .get("project_config")
+        .and_then(|project_config| project_config.get("build_dir"))
+        .and_then(toml::Value::as_str)
+    {
+        Some(build_dir) => build_dir,
+        None => return Ok(None),
+    };
+    let manifest_directory = manifest_path.parent().unwrap();
+    let mut preprocessor = Preprocessor::new()
+        .with_var(Variable {
+            key: "YAMBS_MANIFEST_DIR".to_string(),
+            value: manifest_directory.display().to_string(),
+        })
+        .with_var(Variable {
+            key: "YAMBS_BUILD_TYPE".to_string(),
+            value: build_type.to_string(),
+        });
+    let build_dir = preprocessor
+        .parse(build_dir)
+        .map_err(ParseTomlError::Preprocessor)?;
+    Ok(Some(manifest_directory.join(build_dir)))
+}
+
+// Preprocessor with the variables preset by yambs, which have to be initialized first.
+fn preset_preprocessor() -> Preprocessor {
     unsafe {
-        let toml_content =
-            String::from_utf8(std::fs::read(manifest_path).map_err(ParseTomlError::FailedToRead)?)
-                .map_err(ParseTomlError::FailedToConvertUtf8)?;
-        let mut preprocessor = Preprocessor::new()
+        Preprocessor::new()
             .with_var(Variable {
                 key: "YAMBS_BUILD_DIR".to_string(),
                 value: YAMBS_BUILD_DIR_VAR
@@ -34,58 +131,121 @@ pub fn parse(manifest_path: &std::path::Path) -> Result<manifest::ParsedManifest
             .with_var(Variable {
                 key: "YAMBS_BUILD_TYPE".to_string(),
                 value: YAMBS_BUILD_TYPE.get_unchecked().to_string(),
-            });
-
-        let manifest_parsed = preprocessor
-            .parse(&toml_content)
-            .map_err(ParseTomlError::Preprocessor)?;
-        let metadata =
-            std::fs::metadata(manifest_path).expect("Could not fetch metadata from yambs.json");
-        let manifest_directory = manifest_path.parent().unwrap();
-        Ok(manifest::ParsedManifest {
-            manifest: manifest::Manifest {
-                directory: manifest_directory.to_path_buf(),
-                modification_time: metadata
-                    .modified()
-                    .expect("Could not fetch last modified time of manifest"),
-            },
-            data: parse_toml(&manifest_parsed, manifest_directory)?,
-        })
+            })
+            .with_var(Variable {
+                key: "YAMBS_TARGET_TRIPLE".to_string(),
+                value: YAMBS_TARGET_TRIPLE.get_unchecked().clone(),
+            })
     }
 }
 
-fn parse_toml(
+pub(crate) fn parse_toml(
     toml: &str,
-    manifest_dir: &std::path::Path,
+    manifest_path: &std::path::Path,
+    preprocessor: &mut Preprocessor,
 ) -> Result<manifest::ManifestData, ParseTomlError> {
-    let manifest_contents =
-        toml::from_str::<types::RawManifestData>(toml).map_err(ParseTomlError::FailedToParse)?;
-    manifest::ManifestData::from_raw(manifest_contents, manifest_dir)
-        .map_err(ParseTomlError::FailedToCreateManifestData)
+    let manifest_value = toml::from_str::<toml::Value>(toml).map_err(|e| {
+        let location = SourceLocation::of_toml_error(manifest_path, toml, &e);
+        ParseTomlError::FailedToParse(e, location.map(Box::new))
+    })?;
+    let manifest_value = include::resolve_includes(manifest_value, manifest_path, preprocessor)
+        .map_err(ParseTomlError::Include)?;
+    let mut manifest_contents = manifest_value
+        .try_into::<types::RawManifestData>()
+        .map_err(|e| {
+            let location = locate_data_error(toml, manifest_path, &e);
+            ParseTomlError::FailedToParse(e, location.map(Box::new))
+        })?;
+    if let Some(project_directory) = YAMBS_MANIFEST_DIR.get() {
+        let inherited =
+            defaults::inherited_defaults(manifest_path, project_directory.as_path(), preprocessor)
+                .map_err(ParseTomlError::Defaults)?;
+        manifest_contents.defaults =
+            Some(inherited.overridden_by(manifest_contents.defaults.unwrap_or_default()));
+    }
+    let manifest_dir = manifest_path.parent().unwrap();
+    if is_dependency_manifest(manifest_dir) && !DEPENDENCY_TESTS.get().copied().unwrap_or(false) {
+        manifest_contents = manifest_contents.without_tests_and_examples();
+    }
+    manifest::ManifestData::from_raw(manifest_contents, manifest_dir).map_err(|e| {
+        let location = e
+            .key()
+            .and_then(|key| SourceLocation::of_key(manifest_path, toml, &key));
+        ParseTomlError::FailedToCreateManifestData(e, location.map(Box::new))
+    })
+}
+
+// Whether the manifest in `manifest_dir` is that of a dependency, rather than of the project being
+// built, which is in YAMBS_MANIFEST_DIR.
+fn is_dependency_manifest(manifest_dir: &std::path::Path) -> bool {
+    YAMBS_MANIFEST_DIR
+        .get()
+        .is_some_and(|project_directory| project_directory.as_path() != manifest_dir)
+}
+
+// Location of `error`, given when the manifest with its includes resolved did not fit the manifest
+// data. Errors of the values are only located when deserializing straight from the source, which
+// gives the same error unless it is in an included manifest.
+fn locate_data_error(
+    toml: &str,
+    manifest_path: &std::path::Path,
+    error: &toml::de::Error,
+) -> Option<SourceLocation> {
+    let located_error = toml::from_str::<types::RawManifestData>(toml).err()?;
+    if !located_error.to_string().starts_with(&error.to_string()) {
+        return None;
+    }
+    SourceLocation::of_toml_error(manifest_path, toml, &located_error)
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum ParseTomlError {
     #[error("Failed to parse TOML manifest file.")]
-    FailedToParse(#[source] toml::de::Error),
+    FailedToParse(#[source] toml::de::Error, Option<Box<SourceLocation>>),
     #[error("Failed to read TOML manifest file.")]
     FailedToRead(#[source] std::io::Error),
-    #[error("Failed to convert UTF-8 bytes to string")]
-    FailedToConvertUtf8(#[source] std::string::FromUtf8Error),
+    #[error("Failed to decode manifest {0:?}")]
+    InvalidEncoding(std::path::PathBuf, #[source] EncodingError),
     #[error("Failed to create manifest data")]
-    FailedToCreateManifestData(#[source] manifest::ParseManifestError),
+    FailedToCreateManifestData(
+        #[source] manifest::ParseManifestError,
+        Option<Box<SourceLocation>>,
+    ),
     #[error("Preprocessor failed")]
     Preprocessor(#[source] PreprocessorError),
+    #[error("Failed to include manifest")]
+    Include(#[source] IncludeError),
+    #[error("Failed to read the defaults of the manifests above")]
+    Defaults(#[source] DefaultsError),
+}
+
+impl ParseTomlError {
+    /// Where in the manifest the error is, if it is known.
+    pub fn location(&self) -> Option<&SourceLocation> {
+        match self {
+            Self::FailedToParse(_, location) | Self::FailedToCreateManifestData(_, location) => {
+                location.as_deref()
+            }
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use crate::flags::CompilerFlags;
+    use crate::flags::{CompilerFlags, GeneratedWarnings, WarningProfile, Warnings};
     use crate::manifest::ManifestData;
-    use crate::targets::{Dependency, Executable, Library, Target};
-    use types::{Define, DependencyData, IncludeSearchType, LibraryType, SourceData};
+    use crate::targets::{
+        Command, CustomCommand, Dependency, DependencyError, Executable, Install, InstallError,
+        Library, Target, Test, TestKind,
+    };
+    use crate::YAMBS_MANIFEST_NAME;
+    use types::{
+        BuildFor, CMakeData, Define, DependencyData, IncludeSearchType, LibraryType, LinkOptions,
+        PkgConfigData, SourceData, YambsBuildData,
+    };
 
     struct TestFixture {
         pub tempdir: tempdir::TempDir,
@@ -103,6 +263,85 @@ mod tests {
             std::fs::File::create(&path).unwrap();
             path
         }
+
+        pub fn create_dummy_project(&self, path_postfix: &std::path::Path) -> std::path::PathBuf {
+            let path = self.tempdir.path().join(path_postfix);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::File::create(path.join(YAMBS_MANIFEST_NAME)).unwrap();
+            path
+        }
+    }
+
+    #[test]
+    fn parse_content_does_not_need_the_manifest_on_disk() {
+        let fixture = TestFixture::new();
+        let source = fixture.create_dummy_file(&std::path::PathBuf::from("x.cpp"));
+        let manifest_path = fixture.tempdir.path().join(YAMBS_MANIFEST_NAME);
+        let manifest_data = parse_content_with(
+            b"[executable.x]\nsources = [\"x.cpp\"]\n",
+            &manifest_path,
+            Preprocessor::new(),
+        )
+        .unwrap();
+        assert!(!manifest_path.exists());
+        assert_eq!(manifest_data.targets.len(), 1);
+        let executable = manifest_data.targets[0].executable().unwrap();
+        assert_eq!(executable.name, "x");
+        assert_eq!(executable.sources, vec![source]);
+    }
+
+    #[test]
+    fn parse_content_reports_errors_in_the_manifest_at_the_given_path() {
+        let fixture = TestFixture::new();
+        let manifest_path = fixture.tempdir.path().join(YAMBS_MANIFEST_NAME);
+        let error = parse_content_with(
+            b"[executable.x]\nsources = \n",
+            &manifest_path,
+            Preprocessor::new(),
+        )
+        .unwrap_err();
+        assert!(matches!(error, ParseTomlError::FailedToParse(..)));
+    }
+
+    #[test]
+    fn introspect_prints_manifest_data_as_json() {
+        let fixture = TestFixture::new();
+        let source = fixture.create_dummy_file(&std::path::PathBuf::from("x.cpp"));
+        let manifest_path = fixture.tempdir.path().join(YAMBS_MANIFEST_NAME);
+        let manifest_data = parse_content_with(
+            b"[executable.x]\nsources = [\"x.cpp\"]\n",
+            &manifest_path,
+            Preprocessor::new(),
+        )
+        .unwrap();
+        let introspection = introspect(&manifest_data);
+        let json = serde_json::from_str::<serde_json::Value>(&introspection).unwrap();
+        assert_eq!(json["project_config"], serde_json::Value::Null);
+        assert_eq!(json["targets"][0]["name"], "x");
+        assert_eq!(
+            json["targets"][0]["sources"],
+            serde_json::json!([source.display().to_string()])
+        );
+        assert_eq!(json["tests"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn parse_build_dir_substitutes_build_type_relative_to_manifest() {
+        let fixture = TestFixture::new();
+        let manifest_path = fixture.tempdir.path().join(YAMBS_MANIFEST_NAME);
+        std::fs::write(
+            &manifest_path,
+            "[project_config]\nbuild_dir = \"build/${YAMBS_BUILD_TYPE}\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            parse_build_dir(
+                &manifest_path,
+                &crate::cli::configurations::BuildType::Release
+            )
+            .unwrap(),
+            Some(fixture.tempdir.path().join("build/release"))
+        );
     }
 
     #[test]
@@ -120,7 +359,12 @@ mod tests {
     sources = ['x.cpp', 'y.cpp', 'z.cpp', 'main.cpp']
     "#;
         {
-            let manifest = parse_toml(input, &manifest_dir).unwrap();
+            let manifest = parse_toml(
+                input,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            )
+            .unwrap();
             let executable = Executable {
                 name: "x".to_string(),
                 sources: vec![
@@ -131,11 +375,21 @@ mod tests {
                 ],
                 dependencies: Vec::new(),
                 defines: Vec::new(),
+                custom_commands: Vec::new(),
+                build_for: BuildFor::Target,
+                embed: Vec::new(),
+                extra_files: Vec::new(),
+                post_link: Vec::new(),
+                example: false,
                 compiler_flags: CompilerFlags::new(),
             };
             let expected = ManifestData {
                 project_config: None,
                 targets: vec![Target::Executable(executable)],
+                tests: Vec::new(),
+                install: Vec::new(),
+                test_discovery_directories: Vec::new(),
+                signing: None,
             };
             assert_eq!(manifest, expected);
         }
@@ -157,7 +411,12 @@ mod tests {
     cxxflags_append = ["-g", "-O2"]
     "#;
         {
-            let manifest = parse_toml(input, &manifest_dir).unwrap();
+            let manifest = parse_toml(
+                input,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            )
+            .unwrap();
             let executable = Executable {
                 name: "x".to_string(),
                 sources: vec![
@@ -168,6 +427,12 @@ mod tests {
                 ],
                 dependencies: Vec::new(),
                 defines: Vec::new(),
+                custom_commands: Vec::new(),
+                build_for: BuildFor::Target,
+                embed: Vec::new(),
+                extra_files: Vec::new(),
+                post_link: Vec::new(),
+                example: false,
                 compiler_flags: crate::flags::CompilerFlags {
                     c_flags: None,
                     cxx_flags: Some(crate::flags::CXXFlags::from_slice(&[
@@ -176,13 +441,27 @@ mod tests {
                     ])),
                     cpp_flags: None,
                     ld_flags: None,
+                    libs: vec![],
                     include_directories: vec![],
                     system_include_directories: vec![],
+                    gc_sections: None,
+                    hardened: None,
+                    system_pch: None,
+                    warnings: None,
+                    generated_warnings: None,
+                    opt_level: None,
+                    no_default_flags: false,
+                    template_heavy: false,
+                    flag_groups: vec![],
                 },
             };
             let expected = ManifestData {
                 project_config: None,
                 targets: vec![Target::Executable(executable)],
+                tests: Vec::new(),
+                install: Vec::new(),
+                test_discovery_directories: Vec::new(),
+                signing: None,
             };
             assert_eq!(manifest, expected);
         }
@@ -206,7 +485,12 @@ mod tests {
     sources = ['x.cpp', 'y.cpp', 'z.cpp', 'main.cpp']
     "#;
         {
-            let manifest = parse_toml(input, &manifest_dir).unwrap();
+            let manifest = parse_toml(
+                input,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            )
+            .unwrap();
             let executable_x = Executable {
                 name: "x".to_string(),
                 sources: vec![
@@ -217,6 +501,12 @@ mod tests {
                 ],
                 dependencies: Vec::new(),
                 defines: Vec::new(),
+                custom_commands: Vec::new(),
+                build_for: BuildFor::Target,
+                embed: Vec::new(),
+                extra_files: Vec::new(),
+                post_link: Vec::new(),
+                example: false,
                 compiler_flags: CompilerFlags::new(),
             };
             let executable_y = Executable {
@@ -229,6 +519,12 @@ mod tests {
                 ],
                 dependencies: Vec::new(),
                 defines: Vec::new(),
+                custom_commands: Vec::new(),
+                build_for: BuildFor::Target,
+                embed: Vec::new(),
+                extra_files: Vec::new(),
+                post_link: Vec::new(),
+                example: false,
                 compiler_flags: CompilerFlags::new(),
             };
             let expected = ManifestData {
@@ -237,6 +533,10 @@ mod tests {
                     Target::Executable(executable_x),
                     Target::Executable(executable_y),
                 ],
+                tests: Vec::new(),
+                install: Vec::new(),
+                test_discovery_directories: Vec::new(),
+                signing: None,
             };
             assert_eq!(manifest, expected);
         }
@@ -257,7 +557,12 @@ mod tests {
     sources = ['x.cpp', 'y.cpp', 'z.cpp', 'generator.cpp']
     "#;
 
-        let manifest = parse_toml(input, &manifest_dir).unwrap();
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
         let library = Library {
             name: "MyLibraryData".to_string(),
             sources: vec![
@@ -268,12 +573,22 @@ mod tests {
             ],
             dependencies: Vec::new(),
             defines: Vec::new(),
+            custom_commands: Vec::new(),
+            build_for: BuildFor::Target,
+            embed: Vec::new(),
+            extra_files: Vec::new(),
             compiler_flags: CompilerFlags::new(),
             lib_type: LibraryType::default(),
+            version_script: None,
+            version: None,
         };
         let expected = ManifestData {
             project_config: None,
             targets: vec![Target::Library(library)],
+            tests: Vec::new(),
+            install: Vec::new(),
+            test_discovery_directories: Vec::new(),
+            signing: None,
         };
         assert_eq!(manifest, expected);
     }
@@ -288,9 +603,10 @@ mod tests {
         fixture.create_dummy_file(&std::path::PathBuf::from("y.cpp"));
         fixture.create_dummy_file(&std::path::PathBuf::from("z.cpp"));
 
-        let dep_project_path = fixture.create_dummy_file(&std::path::PathBuf::from("SomeProject"));
+        let dep_project_path =
+            fixture.create_dummy_project(&std::path::PathBuf::from("SomeProject"));
         let second_dep_project_path =
-            fixture.create_dummy_file(&std::path::PathBuf::from("SomeSecondProject"));
+            fixture.create_dummy_project(&std::path::PathBuf::from("SomeSecondProject"));
         let toml_with_require_recipe = format!(
             r#"
     [library.MyLibraryData]
@@ -304,7 +620,12 @@ mod tests {
             second_dep_project_path.display()
         );
 
-        let manifest = parse_toml(&toml_with_require_recipe, &manifest_dir).unwrap();
+        let manifest = parse_toml(
+            &toml_with_require_recipe,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
         let library = Library {
             name: "MyLibraryData".to_string(),
             sources: vec![
@@ -318,28 +639,444 @@ mod tests {
                     name: "SomeProject".to_string(),
                     data: DependencyData::Source(SourceData {
                         path: dep_project_path,
-                        origin: IncludeSearchType::Include,
+                        origin: None,
+                        link_options: LinkOptions::default(),
                     }),
                 },
                 Dependency {
                     name: "SomeSecondProject".to_string(),
                     data: DependencyData::Source(SourceData {
                         path: second_dep_project_path,
-                        origin: IncludeSearchType::Include,
+                        origin: None,
+                        link_options: LinkOptions::default(),
                     }),
                 },
             ],
             defines: Vec::new(),
+            custom_commands: Vec::new(),
+            build_for: BuildFor::Target,
+            embed: Vec::new(),
+            extra_files: Vec::new(),
             compiler_flags: CompilerFlags::new(),
             lib_type: LibraryType::default(),
+            version_script: None,
+            version: None,
         };
         let expected = ManifestData {
             project_config: None,
             targets: vec![Target::Library(library)],
+            tests: Vec::new(),
+            install: Vec::new(),
+            test_discovery_directories: Vec::new(),
+            signing: None,
         };
         assert_eq!(manifest, expected);
     }
 
+    #[test]
+    fn parse_produces_manifest_with_dependencies_with_link_options() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        let plugins_path = fixture.create_dummy_project(&std::path::PathBuf::from("Plugins"));
+        let preload_path = fixture.create_dummy_project(&std::path::PathBuf::from("Preload"));
+        let input = format!(
+            r#"
+    [executable.x]
+    sources = ['main.cpp']
+
+    [executable.x.dependencies]
+    Plugins = {{ path = "{}", whole_archive = true }}
+    Preload = {{ path = "{}", as_needed = false }}
+    "#,
+            plugins_path.display(),
+            preload_path.display()
+        );
+
+        let manifest = parse_toml(
+            &input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        let link_options = manifest.targets[0]
+            .dependencies()
+            .iter()
+            .map(|dependency| match dependency.data {
+                DependencyData::Source(ref source_data) => {
+                    (dependency.name.as_str(), source_data.link_options.clone())
+                }
+                _ => panic!("Not a source dependency"),
+            })
+            .collect::<Vec<(&str, LinkOptions)>>();
+        assert_eq!(
+            link_options,
+            vec![
+                (
+                    "Plugins",
+                    LinkOptions {
+                        whole_archive: true,
+                        as_needed: true,
+                    }
+                ),
+                (
+                    "Preload",
+                    LinkOptions {
+                        whole_archive: false,
+                        as_needed: false,
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_pkg_config_dependency_components() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        let input = r#"
+    [executable.x]
+    sources = ['main.cpp']
+
+    [executable.x.dependencies]
+    Boost = { pkg_config = "boost", components = ["filesystem", "regex"] }
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.targets[0].dependencies(),
+            &[Dependency {
+                name: "Boost".to_string(),
+                data: DependencyData::PkgConfig(PkgConfigData {
+                    package: Some("boost".to_string()),
+                    search_dir: None,
+                    components: vec!["filesystem".to_string(), "regex".to_string()],
+                    origin: None,
+                }),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_include_policy_and_dependency_origin() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        let input = r#"
+    [project_config]
+    third_party_as_system = true
+
+    [executable.x]
+    sources = ['main.cpp']
+
+    [executable.x.dependencies]
+    zlib = { pkg_config = "zlib", origin = "Include" }
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest
+                .project_config
+                .as_ref()
+                .and_then(|project_config| project_config.third_party_as_system),
+            Some(true)
+        );
+        assert_eq!(
+            manifest.targets[0].dependencies()[0].data.origin(),
+            Some(IncludeSearchType::Include)
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_cmake_dependencies() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        let input = r#"
+    [executable.x]
+    sources = ['main.cpp']
+
+    [executable.x.dependencies]
+    core = { cmake_build_dir = "../legacy/build", cmake_target = "legacy_core" }
+    fmt = { cmake_build_dir = "../fmt/build" }
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.targets[0].dependencies(),
+            &[
+                Dependency {
+                    name: "core".to_string(),
+                    data: DependencyData::CMake(CMakeData {
+                        build_directory: manifest_dir.join("../legacy/build"),
+                        target: Some("legacy_core".to_string()),
+                        origin: None,
+                    }),
+                },
+                Dependency {
+                    name: "fmt".to_string(),
+                    data: DependencyData::CMake(CMakeData {
+                        build_directory: manifest_dir.join("../fmt/build"),
+                        target: None,
+                        origin: None,
+                    }),
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_yambs_build_dependency() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        let input = r#"
+    [executable.x]
+    sources = ['main.cpp']
+
+    [executable.x.dependencies]
+    core = { yambs_build = "../base/build", origin = "System" }
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.targets[0].dependencies(),
+            &[Dependency {
+                name: "core".to_string(),
+                data: DependencyData::YambsBuild(YambsBuildData {
+                    build_directory: manifest_dir.join("../base/build"),
+                    origin: Some(IncludeSearchType::System),
+                }),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_target_opt_level() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        let input = r#"
+    [executable.x]
+    sources = ['main.cpp']
+    opt_level = "s"
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        match manifest.targets[0] {
+            Target::Executable(ref executable) => assert_eq!(
+                executable.compiler_flags.opt_level,
+                Some(crate::cli::configurations::OptLevel::Os)
+            ),
+            _ => panic!("Expected an executable"),
+        }
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_post_link_artifacts() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        let input = r#"
+    [executable.firmware]
+    sources = ['main.cpp']
+    post_link = ["hex", "bin", "map"]
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        match manifest.targets[0] {
+            Target::Executable(ref executable) => assert_eq!(
+                executable.post_link,
+                vec![
+                    types::PostLinkArtifact::Hex,
+                    types::PostLinkArtifact::Bin,
+                    types::PostLinkArtifact::Map
+                ]
+            ),
+            _ => panic!("Expected an executable"),
+        }
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_embedded_files() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        std::fs::create_dir(manifest_dir.join("assets")).unwrap();
+        fixture.create_dummy_file(&std::path::PathBuf::from("assets/shader.glsl"));
+        let input = r#"
+    [library.shaders]
+    sources = ['main.cpp']
+    embed = ["assets/shader.glsl"]
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        match manifest.targets[0] {
+            Target::Library(ref library) => assert_eq!(
+                library.embed,
+                vec![manifest_dir
+                    .join("assets/shader.glsl")
+                    .canonicalize()
+                    .unwrap()]
+            ),
+            _ => panic!("Expected a library"),
+        }
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_extra_files_of_project_and_target() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        fixture.create_dummy_file(&std::path::PathBuf::from("version.txt"));
+        fixture.create_dummy_file(&std::path::PathBuf::from("app.ld"));
+        let input = r#"
+    [project_config]
+    extra_files = ["version.txt"]
+
+    [executable.app]
+    sources = ['main.cpp']
+    extra_files = ["app.ld", "version.txt"]
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        match manifest.targets[0] {
+            Target::Executable(ref executable) => assert_eq!(
+                executable.extra_files,
+                vec![
+                    manifest_dir.join("version.txt"),
+                    manifest_dir.join("app.ld")
+                ]
+            ),
+            _ => panic!("Expected an executable"),
+        }
+
+        let input = r#"
+    [executable.app]
+    sources = ['main.cpp']
+    extra_files = ["missing.ld"]
+    "#;
+        match parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap_err()
+        {
+            ParseTomlError::FailedToCreateManifestData(
+                manifest::ParseManifestError::ExtraFileNotFound(table, file),
+                _,
+            ) => {
+                assert_eq!(table, "executable.app");
+                assert_eq!(file, std::path::PathBuf::from("missing.ld"));
+            }
+            error => panic!("Unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_configured_layout() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        let input = r#"
+    [project_config]
+    source_dirs = ['lib']
+    include_dirs = ['public', 'include']
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.layout(),
+            crate::utility::ProjectLayout {
+                source_dirs: vec![std::path::PathBuf::from("lib")],
+                include_dirs: vec![
+                    std::path::PathBuf::from("public"),
+                    std::path::PathBuf::from("include")
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_executable_linking_system_libraries() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("x.cpp"));
+
+        let input = r#"
+    [executable.x]
+    sources = ['x.cpp']
+    libs = ['pthread', 'dl', 'm']
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        let executable = manifest.targets[0].executable().unwrap();
+        assert_eq!(executable.compiler_flags.libs, vec!["pthread", "dl", "m"]);
+        assert_eq!(executable.compiler_flags.cxx_flags, None);
+    }
+
     #[test]
     fn parse_produces_manifest_with_executable_with_custom_defines() {
         let fixture = TestFixture::new();
@@ -359,7 +1096,12 @@ mod tests {
     value = '0'
     "#;
         {
-            let manifest = parse_toml(input, &manifest_dir).unwrap();
+            let manifest = parse_toml(
+                input,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            )
+            .unwrap();
             let executable = Executable {
                 name: "x".to_string(),
                 sources: vec![manifest_dir.join(std::path::PathBuf::from("x.cpp"))],
@@ -375,10 +1117,20 @@ mod tests {
                     },
                 ],
                 compiler_flags: CompilerFlags::new(),
+                custom_commands: Vec::new(),
+                build_for: BuildFor::Target,
+                embed: Vec::new(),
+                extra_files: Vec::new(),
+                post_link: Vec::new(),
+                example: false,
             };
             let expected = ManifestData {
                 project_config: None,
                 targets: vec![Target::Executable(executable)],
+                tests: Vec::new(),
+                install: Vec::new(),
+                test_discovery_directories: Vec::new(),
+                signing: None,
             };
             assert_eq!(manifest, expected);
         }
@@ -404,7 +1156,12 @@ mod tests {
     value = '0'
     "#;
 
-        let manifest = parse_toml(input, &manifest_dir).unwrap();
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
         let library = Library {
             name: "MyLibraryData".to_string(),
             sources: vec![manifest_dir.join(std::path::PathBuf::from("x.cpp"))],
@@ -421,11 +1178,858 @@ mod tests {
             ],
             compiler_flags: CompilerFlags::new(),
             lib_type: LibraryType::default(),
+            custom_commands: Vec::new(),
+            build_for: BuildFor::Target,
+            embed: Vec::new(),
+            extra_files: Vec::new(),
+            version_script: None,
+            version: None,
         };
         let expected = ManifestData {
             project_config: None,
             targets: vec![Target::Library(library)],
+            tests: Vec::new(),
+            install: Vec::new(),
+            test_discovery_directories: Vec::new(),
+            signing: None,
         };
         assert_eq!(manifest, expected);
     }
+
+    #[test]
+    fn parse_produces_manifest_with_executable_with_custom_command() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("x.cpp"));
+
+        let input = r#"
+    [executable.x]
+    sources = ['x.cpp']
+
+    [[executable.x.custom_command]]
+    command = ['python3', 'generate.py', 'generated.cpp']
+    inputs = ['generate.py']
+    outputs = ['generated.cpp']
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        let executable = Executable {
+            name: "x".to_string(),
+            sources: vec![manifest_dir.join(std::path::PathBuf::from("x.cpp"))],
+            dependencies: Vec::new(),
+            defines: Vec::new(),
+            compiler_flags: CompilerFlags::new(),
+            custom_commands: vec![CustomCommand {
+                command: Command::Program(vec![
+                    "python3".to_string(),
+                    "generate.py".to_string(),
+                    "generated.cpp".to_string(),
+                ]),
+                inputs: vec![manifest_dir.join("generate.py")],
+                outputs: vec![std::path::PathBuf::from("generated.cpp")],
+            }],
+            build_for: BuildFor::Target,
+            embed: Vec::new(),
+            extra_files: Vec::new(),
+            post_link: Vec::new(),
+            example: false,
+        };
+        let expected = ManifestData {
+            project_config: None,
+            targets: vec![Target::Executable(executable)],
+            tests: Vec::new(),
+            install: Vec::new(),
+            test_discovery_directories: Vec::new(),
+            signing: None,
+        };
+        assert_eq!(manifest, expected);
+    }
+
+    #[test]
+    fn parse_fails_on_dependency_path_without_manifest_and_suggests_nearby_projects() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("x.cpp"));
+        let project_path =
+            fixture.create_dummy_project(&std::path::PathBuf::from("libs/SomeProject"));
+        std::fs::create_dir(manifest_dir.join("libs/Empty")).unwrap();
+
+        let parse_with_path = |path: &str| {
+            let input = format!(
+                r#"
+    [executable.x]
+    sources = ['x.cpp']
+
+    [executable.x.dependencies]
+    SomeProject = {{ path = "{}" }}
+    "#,
+                path
+            );
+            let error = parse_toml(
+                &input,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            )
+            .unwrap_err();
+            match error {
+                ParseTomlError::FailedToCreateManifestData(
+                    manifest::ParseManifestError::FailedToParseDependency(key, manifest, error),
+                    _,
+                ) => {
+                    assert_eq!(key, "executable.x.dependencies.SomeProject");
+                    assert_eq!(manifest, manifest_dir.join(YAMBS_MANIFEST_NAME));
+                    error
+                }
+                error => panic!("Unexpected error {:?}", error),
+            }
+        };
+
+        match parse_with_path("libs/SomeProjekt") {
+            DependencyError::PathNotFound(path, suggestions) => {
+                assert_eq!(path, std::path::PathBuf::from("libs/SomeProjekt"));
+                assert_eq!(suggestions, vec![project_path.canonicalize().unwrap()]);
+            }
+            error => panic!("Unexpected error {:?}", error),
+        }
+        assert!(matches!(
+            parse_with_path("libs/Empty"),
+            DependencyError::NoManifest(..)
+        ));
+    }
+
+    #[test]
+    fn parse_fails_on_targets_without_sources_naming_their_table() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("x.cpp"));
+        fixture.create_dummy_file(&std::path::PathBuf::from("y.h"));
+
+        let parse = |input: &str| {
+            parse_toml(
+                input,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            )
+        };
+        let table_without_sources = |input: &str| match parse(input).unwrap_err() {
+            ParseTomlError::FailedToCreateManifestData(
+                manifest::ParseManifestError::NoSources(table, manifest),
+                _,
+            ) => {
+                assert_eq!(manifest, manifest_dir.join(YAMBS_MANIFEST_NAME));
+                table
+            }
+            error => panic!("Unexpected error {:?}", error),
+        };
+
+        assert_eq!(
+            table_without_sources(
+                r#"
+    [executable.x]
+    sources = []
+    "#
+            ),
+            "executable.x"
+        );
+        assert_eq!(
+            table_without_sources(
+                r#"
+    [library.y]
+    sources = ['y.h']
+    "#
+            ),
+            "library.y"
+        );
+        assert_eq!(
+            table_without_sources(
+                r#"
+    [executable.x]
+    "#
+            ),
+            "executable.x"
+        );
+        assert!(parse(
+            r#"
+    [executable.x]
+    sources = []
+
+    [[executable.x.custom_command]]
+    command = ['touch', 'generated.cpp']
+    outputs = ['generated.cpp']
+    "#
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn parse_fails_on_custom_command_output_outside_of_sandbox() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("x.cpp"));
+
+        let input = r#"
+    [executable.x]
+    sources = ['x.cpp']
+
+    [[executable.x.custom_command]]
+    command = ['touch', '../generated.cpp']
+    outputs = ['../generated.cpp']
+    "#;
+
+        let result = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_custom_command_running_executable_target() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("x.cpp"));
+
+        let input = r#"
+    [executable.x]
+    sources = ['x.cpp']
+
+    [[executable.x.custom_command]]
+    command = { target = 'codegen_tool', args = ['generated.cpp'] }
+    outputs = ['generated.cpp']
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        let executable = manifest.targets[0].executable().unwrap();
+        assert_eq!(
+            executable.custom_commands,
+            vec![CustomCommand {
+                command: Command::Target {
+                    target: "codegen_tool".to_string(),
+                    args: vec!["generated.cpp".to_string()],
+                },
+                inputs: Vec::new(),
+                outputs: vec![std::path::PathBuf::from("generated.cpp")],
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_executable_built_for_host() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("generator.cpp"));
+
+        let input = r#"
+    [executable.generator]
+    sources = ['generator.cpp']
+    build_for = 'host'
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(manifest.targets[0].build_for(), BuildFor::Host);
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_example_linking_library_of_manifest() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from(YAMBS_MANIFEST_NAME));
+        fixture.create_dummy_file(&std::path::PathBuf::from("core.cpp"));
+        fixture.create_dummy_file(&std::path::PathBuf::from("hello.cpp"));
+
+        let input = r#"
+    [library.core]
+    sources = ['core.cpp']
+
+    [example.hello]
+    sources = ['hello.cpp']
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        let example = manifest
+            .targets
+            .iter()
+            .find_map(|target| target.executable())
+            .unwrap();
+        assert!(example.example);
+        assert_eq!(
+            example.dependencies,
+            vec![Dependency {
+                name: "core".to_string(),
+                data: DependencyData::Source(SourceData {
+                    path: manifest_dir.clone(),
+                    origin: None,
+                    link_options: LinkOptions::default(),
+                }),
+            }]
+        );
+
+        let input_with_two_libraries = format!(
+            "{}\n    [library.util]\n    sources = ['core.cpp']\n",
+            input
+        );
+        assert!(matches!(
+            parse_toml(
+                &input_with_two_libraries,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            ),
+            Err(ParseTomlError::FailedToCreateManifestData(
+                manifest::ParseManifestError::ExampleWithoutLibrary(..),
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_flags_of_flag_groups() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        let input = r#"
+    [flags.strict_math]
+    cxxflags = ["-ffp-contract=off", "-fno-fast-math"]
+
+    [flags.lto]
+    cxxflags = ["-flto"]
+    ldflags = ["-flto"]
+
+    [executable.x]
+    sources = ['main.cpp']
+    flag_groups = ["strict_math", "lto"]
+    cxxflags_append = ["-fno-rtti", "-flto"]
+    "#;
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        let compiler_flags = &manifest.targets[0].executable().unwrap().compiler_flags;
+        assert_eq!(
+            compiler_flags.cxx_flags.as_ref().unwrap().flags(),
+            &vec![
+                "-ffp-contract=off".to_string(),
+                "-fno-fast-math".to_string(),
+                "-flto".to_string(),
+                "-fno-rtti".to_string(),
+            ]
+        );
+        assert_eq!(
+            compiler_flags.ld_flags.as_ref().unwrap().flags(),
+            &vec!["-flto".to_string()]
+        );
+
+        let conflicting = input.replace(
+            "[executable.x]",
+            "[flags.fast]\n    cxxflags = [\"-ffast-math\"]\n\n    [executable.x]",
+        );
+        let conflicting = conflicting.replace(
+            "flag_groups = [\"strict_math\", \"lto\"]",
+            "flag_groups = [\"strict_math\", \"fast\"]",
+        );
+        match parse_toml(
+            &conflicting,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        ) {
+            Err(ParseTomlError::FailedToCreateManifestData(
+                manifest::ParseManifestError::ConflictingFlagGroups(conflict),
+                _,
+            )) => {
+                assert_eq!(conflict.first_flag, "-fno-fast-math");
+                assert_eq!(conflict.second_flag, "-ffast-math");
+            }
+            other => panic!("Expected conflicting flag groups, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_version_script_of_shared_library() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("lib.cpp"));
+        fixture.create_dummy_file(&std::path::PathBuf::from("libcore.map"));
+        let input = r#"
+    [library.core]
+    sources = ['lib.cpp']
+    type = "shared"
+    version_script = "libcore.map"
+    "#;
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.targets[0].library().unwrap().version_script,
+            Some(manifest_dir.join("libcore.map"))
+        );
+
+        let missing = input.replace("libcore.map", "libmissing.map");
+        assert!(matches!(
+            parse_toml(
+                &missing,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            ),
+            Err(ParseTomlError::FailedToCreateManifestData(
+                manifest::ParseManifestError::VersionScriptNotFound(..),
+                _
+            ))
+        ));
+        let static_library = input.replace("type = \"shared\"", "type = \"static\"");
+        assert!(matches!(
+            parse_toml(
+                &static_library,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            ),
+            Err(ParseTomlError::FailedToCreateManifestData(
+                manifest::ParseManifestError::VersionScriptOfStaticLibrary(_),
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_test_executable_of_each_discovered_source() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from(YAMBS_MANIFEST_NAME));
+        fixture.create_dummy_file(&std::path::PathBuf::from("lib.cpp"));
+        fixture.create_dummy_project(&std::path::PathBuf::from("tests"));
+        fixture.create_dummy_file(&std::path::PathBuf::from("tests/parser.cpp"));
+        fixture.create_dummy_file(&std::path::PathBuf::from("tests/lexer.cpp"));
+        fixture.create_dummy_file(&std::path::PathBuf::from("tests/helpers.h"));
+        let input = r#"
+    [library.core]
+    sources = ['lib.cpp']
+
+    [tests]
+    auto_discover = "tests/*.cpp"
+    library = "core"
+    "#;
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        let executables = manifest
+            .targets
+            .iter()
+            .filter_map(|target| target.executable())
+            .collect::<Vec<&Executable>>();
+        assert_eq!(
+            executables
+                .iter()
+                .map(|executable| executable.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["lexer", "parser"]
+        );
+        assert_eq!(
+            executables[0].sources,
+            vec![manifest_dir.join("tests").join("lexer.cpp")]
+        );
+        assert_eq!(executables[0].dependencies[0].name, "core");
+        assert_eq!(
+            manifest
+                .tests
+                .iter()
+                .map(|test| (test.name.as_str(), test.depends_on.clone()))
+                .collect::<Vec<(&str, Vec<String>)>>(),
+            vec![
+                ("lexer", vec!["lexer".to_string()]),
+                ("parser", vec!["parser".to_string()])
+            ]
+        );
+        assert_eq!(
+            manifest.test_discovery_directories,
+            vec![manifest_dir.clone(), manifest_dir.join("tests")]
+        );
+
+        let conflicting = input.replace(
+            "[library.core]",
+            "[executable.parser]\n    sources = ['lib.cpp']\n\n    [library.core]",
+        );
+        assert!(matches!(
+            parse_toml(
+                &conflicting,
+                &manifest_dir.join(YAMBS_MANIFEST_NAME),
+                &mut Preprocessor::new(),
+            ),
+            Err(ParseTomlError::FailedToCreateManifestData(
+                manifest::ParseManifestError::DiscoveredTestConflict(ref name, _), _
+            )) if name == "parser"
+        ));
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_gc_sections_overridden_by_target() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+
+        let input = r#"
+    [project_config]
+    gc_sections = true
+
+    [executable.x]
+    sources = ['main.cpp']
+    gc_sections = false
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(manifest.project_config.unwrap().gc_sections, Some(true));
+        assert_eq!(
+            manifest.targets[0]
+                .executable()
+                .unwrap()
+                .compiler_flags
+                .gc_sections,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_hardened_target() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+
+        let input = r#"
+    [project_config]
+    hardened = false
+
+    [executable.x]
+    sources = ['main.cpp']
+    hardened = true
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(manifest.project_config.unwrap().hardened, Some(false));
+        assert_eq!(
+            manifest.targets[0]
+                .executable()
+                .unwrap()
+                .compiler_flags
+                .hardened,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_warning_profiles_and_custom_warnings() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+        fixture.create_dummy_file(&std::path::PathBuf::from("vendored.cpp"));
+
+        let input = r#"
+    [project_config]
+    warnings = "strict"
+    generated_warnings = "on"
+
+    [executable.x]
+    sources = ['main.cpp']
+    warnings = ["-Wall", "-Wextra"]
+    generated_warnings = "off"
+
+    [library.vendored]
+    sources = ['vendored.cpp']
+    warnings = "none"
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        let project_config = manifest.project_config.unwrap();
+        assert_eq!(
+            project_config.warnings,
+            Some(Warnings::Profile(WarningProfile::Strict))
+        );
+        assert_eq!(
+            project_config.generated_warnings,
+            Some(GeneratedWarnings::On)
+        );
+        assert_eq!(
+            manifest.targets[0]
+                .executable()
+                .unwrap()
+                .compiler_flags
+                .generated_warnings,
+            Some(GeneratedWarnings::Off)
+        );
+        assert_eq!(
+            manifest.targets[0]
+                .executable()
+                .unwrap()
+                .compiler_flags
+                .warnings,
+            Some(Warnings::Custom(vec![
+                "-Wall".to_string(),
+                "-Wextra".to_string()
+            ]))
+        );
+        assert_eq!(
+            manifest.targets[1]
+                .library()
+                .unwrap()
+                .compiler_flags
+                .warnings,
+            Some(Warnings::Profile(WarningProfile::None))
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_script_and_executable_tests() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+
+        let input = r#"
+    [executable.unit_tests]
+    sources = ['main.cpp']
+
+    [test.integration]
+    command = "scripts/run_integration.sh"
+    depends_on = ["unit_tests"]
+    working_directory = "tests"
+    env = { DATA = "data.txt" }
+    timeout = 30
+    retries = 2
+
+    [test.unit]
+    command = { target = "unit_tests", args = ["--verbose"] }
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.tests,
+            vec![
+                Test {
+                    name: "integration".to_string(),
+                    kind: TestKind::Run(Command::Program(vec![manifest_dir
+                        .join("scripts/run_integration.sh")
+                        .display()
+                        .to_string()])),
+                    depends_on: vec!["unit_tests".to_string()],
+                    working_directory: manifest_dir.join("tests"),
+                    env: std::collections::BTreeMap::from([(
+                        "DATA".to_string(),
+                        "data.txt".to_string()
+                    )]),
+                    timeout: Some(std::time::Duration::from_secs(30)),
+                    retries: 2,
+                },
+                Test {
+                    name: "unit".to_string(),
+                    kind: TestKind::Run(Command::Target {
+                        target: "unit_tests".to_string(),
+                        args: vec!["--verbose".to_string()],
+                    }),
+                    depends_on: vec!["unit_tests".to_string()],
+                    working_directory: manifest_dir.clone(),
+                    env: std::collections::BTreeMap::new(),
+                    timeout: None,
+                    retries: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_compile_fail_test() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("lib.cpp"));
+
+        let input = r#"
+    [library.checked]
+    sources = ['lib.cpp']
+
+    [test.rejects_negative_size]
+    expect = "compile_fail"
+    source = "tests/negative_size.cpp"
+    target = "checked"
+    diagnostic = "size must be positive"
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.tests,
+            vec![Test {
+                name: "rejects_negative_size".to_string(),
+                kind: TestKind::CompileFail {
+                    source: manifest_dir.join("tests/negative_size.cpp"),
+                    target: "checked".to_string(),
+                    diagnostic: Some("size must be positive".to_string()),
+                },
+                depends_on: vec!["checked".to_string()],
+                working_directory: manifest_dir.clone(),
+                env: std::collections::BTreeMap::new(),
+                timeout: None,
+                retries: 0,
+            }]
+        );
+
+        let input = r#"
+    [test.incomplete]
+    expect = "compile_fail"
+    source = "tests/negative_size.cpp"
+    "#;
+        assert!(parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parse_produces_manifest_with_install_artifacts() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        fixture.create_dummy_file(&std::path::PathBuf::from("main.cpp"));
+
+        let input = r#"
+    [executable.x]
+    sources = ['main.cpp']
+
+    [[install]]
+    target = "x"
+    destination = "bin"
+
+    [[install]]
+    file = "conf/x.conf"
+    destination = "etc/x"
+    mode = "0640"
+
+    [[install]]
+    directory = "var/lib/x"
+    mode = "750"
+
+    [[install]]
+    symlink = "bin/x-latest"
+    points_to = "x"
+    "#;
+
+        let manifest = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.install,
+            vec![
+                Install::Target {
+                    target: "x".to_string(),
+                    destination: std::path::PathBuf::from("bin"),
+                    mode: None,
+                },
+                Install::File {
+                    file: manifest_dir.join("conf/x.conf"),
+                    destination: std::path::PathBuf::from("etc/x"),
+                    mode: Some(0o640),
+                },
+                Install::Directory {
+                    directory: std::path::PathBuf::from("var/lib/x"),
+                    mode: Some(0o750),
+                },
+                Install::Symlink {
+                    symlink: std::path::PathBuf::from("bin/x-latest"),
+                    points_to: std::path::PathBuf::from("x"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_fails_on_install_with_invalid_mode() {
+        let fixture = TestFixture::new();
+        let manifest_dir = fixture.tempdir.path().to_path_buf();
+
+        let input = r#"
+    [[install]]
+    directory = "var/lib/x"
+    mode = "0789"
+    "#;
+
+        let error = parse_toml(
+            input,
+            &manifest_dir.join(YAMBS_MANIFEST_NAME),
+            &mut Preprocessor::new(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            ParseTomlError::FailedToCreateManifestData(
+                manifest::ParseManifestError::FailedToParseInstall(InstallError::InvalidMode(_)),
+                _
+            )
+        ));
+    }
 }
@@ -0,0 +1,340 @@
+// Parses the manifests of source dependencies ahead of registering the targets, so that parsing
+// can run concurrently with probing the toolchain. Manifests are parsed a level of the dependency
+// tree at a time, with a thread per manifest, as the dependencies of a manifest are not known
+// until it is parsed.
+// Manifests that fail to parse are left out. Registering the dependency parses them again and
+// reports the error along with the target depending on it.
+// Manifests parsed by the previous configure can be reused, so that only the manifests that
+// changed are parsed again.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::ParseTomlError;
+use crate::build_file_hashes::ContentHasher;
+use crate::cache::Cacher;
+use crate::manifest::ParsedManifest;
+use crate::parser::types::DependencyData;
+use crate::YAMBS_MANIFEST_NAME;
+
+lazy_static::lazy_static! {
+    static ref ENV_REFERENCE: regex::Regex =
+        regex::Regex::new(r"\$\{env:(?P<env>[^}]*)\}").unwrap();
+}
+
+/// Parses the manifests of the source dependencies of `manifest`, and of their dependencies in
+/// turn. The parsed manifests are keyed by the directory they are located in.
+pub fn parse_dependency_manifests(manifest: &ParsedManifest) -> HashMap<PathBuf, ParsedManifest> {
+    parse_dependency_manifests_with(manifest, super::parse)
+}
+
+/// Dependency manifests parsed by the previous configure, keyed by the directory they are located
+/// in. A manifest is reused as long as it parses to the same result, which is when its cache key
+/// is unchanged.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ParsedManifests {
+    #[serde(default)]
+    pub manifests: BTreeMap<PathBuf, CachedManifest>,
+}
+
+impl Cacher for ParsedManifests {
+    const CACHE_FILE_NAME: &'static str = "parsed_manifests";
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CachedManifest {
+    pub key: String,
+    pub manifest: ParsedManifest,
+}
+
+/// How many dependency manifests were reused from the cache, and how many were parsed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ManifestCacheStatistics {
+    pub reused: usize,
+    pub parsed: usize,
+}
+
+/// Like `parse_dependency_manifests`, reusing the manifests of `cache` whose cache key is
+/// unchanged instead of parsing them again. The cache is left with the manifests of this parse.
+pub fn parse_dependency_manifests_cached(
+    manifest: &ParsedManifest,
+    cache: &mut ParsedManifests,
+) -> (HashMap<PathBuf, ParsedManifest>, ManifestCacheStatistics) {
+    parse_dependency_manifests_cached_with(manifest, cache, super::parse)
+}
+
+fn parse_dependency_manifests_cached_with<F>(
+    manifest: &ParsedManifest,
+    cache: &mut ParsedManifests,
+    parse: F,
+) -> (HashMap<PathBuf, ParsedManifest>, ManifestCacheStatistics)
+where
+    F: Fn(&Path) -> Result<ParsedManifest, ParseTomlError> + Sync,
+{
+    let previous = std::mem::take(&mut cache.manifests);
+    let keys = Mutex::new(HashMap::new());
+    let statistics = Mutex::new(ManifestCacheStatistics::default());
+    let parsed = parse_dependency_manifests_with(manifest, |manifest_path| {
+        let directory = manifest_path.parent().unwrap().to_path_buf();
+        let Some(key) = cache_key(manifest_path) else {
+            statistics.lock().unwrap().parsed += 1;
+            return parse(manifest_path);
+        };
+        let cached = previous
+            .get(&directory)
+            .filter(|cached| cached.key == key)
+            .map(|cached| cached.manifest.clone());
+        keys.lock().unwrap().insert(directory.clone(), key);
+        match cached {
+            Some(mut manifest) => {
+                log::debug!("Reusing parsed manifest in {}", directory.display());
+                statistics.lock().unwrap().reused += 1;
+                // The manifest may have been touched with its content left as it was.
+                if let Ok(modified) =
+                    std::fs::metadata(manifest_path).and_then(|metadata| metadata.modified())
+                {
+                    manifest.manifest.modification_time = modified;
+                }
+                Ok(manifest)
+            }
+            None => {
+                statistics.lock().unwrap().parsed += 1;
+                parse(manifest_path)
+            }
+        }
+    });
+
+    let mut keys = keys.into_inner().unwrap();
+    for (directory, manifest) in &parsed {
+        if let Some(key) = keys.remove(directory) {
+            cache.manifests.insert(
+                directory.clone(),
+                CachedManifest {
+                    key,
+                    manifest: manifest.clone(),
+                },
+            );
+        }
+    }
+    (parsed, statistics.into_inner().unwrap())
+}
+
+/// Key identifying what the manifest at `manifest_path` parses to: its content and that of the
+/// manifests it includes, the values of the variables of yambs and of the environment variables
+/// they refer to, and whether the tests of dependencies are parsed. None if one of the manifests can not be read.
+pub fn cache_key(manifest_path: &Path) -> Option<String> {
+    let content = std::fs::read(manifest_path).ok()?;
+    let mut hasher = ContentHasher::new();
+    hasher
+        .add(crate::manifest::content_hash_of(manifest_path, &content)?)
+        .add_optional(
+            crate::YAMBS_BUILD_DIR_VAR
+                .get()
+                .map(|build_directory| build_directory.as_path().to_string_lossy().into_owned()),
+        )
+        .add_optional(
+            crate::YAMBS_MANIFEST_DIR.get().map(|manifest_directory| {
+                manifest_directory.as_path().to_string_lossy().into_owned()
+            }),
+        )
+        .add_optional(
+            crate::YAMBS_BUILD_TYPE
+                .get()
+                .map(|build_type| build_type.to_string()),
+        )
+        .add_optional(crate::YAMBS_TARGET_TRIPLE.get())
+        .add_optional(crate::DEPENDENCY_TESTS.get().map(|tests| tests.to_string()));
+    let mut contents = vec![content];
+    for included in super::include::included_manifests(manifest_path) {
+        contents.push(std::fs::read(included).ok()?);
+    }
+    for content in &contents {
+        for captures in ENV_REFERENCE.captures_iter(&String::from_utf8_lossy(content)) {
+            let env = &captures["env"];
+            hasher.add(env).add_optional(
+                std::env::var_os(env).map(|value| value.to_string_lossy().into_owned()),
+            );
+        }
+    }
+    Some(format!("{:016x}", hasher.finish()))
+}
+
+fn parse_dependency_manifests_with<F>(
+    manifest: &ParsedManifest,
+    parse: F,
+) -> HashMap<PathBuf, ParsedManifest>
+where
+    F: Fn(&Path) -> Result<ParsedManifest, ParseTomlError> + Sync,
+{
+    let mut parsed = HashMap::new();
+    let mut visited = BTreeSet::from([manifest.manifest.directory.clone()]);
+    let mut pending = dependency_directories(manifest)
+        .into_iter()
+        .filter(|directory| visited.insert(directory.clone()))
+        .collect::<Vec<PathBuf>>();
+
+    while !pending.is_empty() {
+        let results = std::thread::scope(|scope| {
+            let handles = pending
+                .iter()
+                .map(|directory| {
+                    let parse = &parse;
+                    scope.spawn(move || parse(&directory.join(YAMBS_MANIFEST_NAME)))
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Manifest parser thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        let mut next = Vec::new();
+        for (directory, result) in pending.into_iter().zip(results) {
+            match result {
+                Ok(dependency_manifest) => {
+                    next.extend(
+                        dependency_directories(&dependency_manifest)
+                            .into_iter()
+                            .filter(|directory| visited.insert(directory.clone())),
+                    );
+                    parsed.insert(directory, dependency_manifest);
+                }
+                Err(e) => log::debug!(
+                    "Failed to parse manifest in {} ahead of registering it: {}",
+                    directory.display(),
+                    e
+                ),
+            }
+        }
+        pending = next;
+    }
+    log::debug!("Parsed {} dependency manifests", parsed.len());
+    parsed
+}
+
+/// Directories of the source dependencies of the targets of `manifest`.
+pub fn dependency_directories(manifest: &ParsedManifest) -> BTreeSet<PathBuf> {
+    manifest
+        .data
+        .targets
+        .iter()
+        .flat_map(|target| target.dependencies())
+        .filter_map(|dependency| match dependency.data {
+            DependencyData::Source(ref source_data) => Some(source_data.path.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Build directories of the CMake projects the targets of `manifest` import libraries from, which
+/// yambs writes its queries for the CMake file API into.
+pub fn cmake_build_directories(manifest: &ParsedManifest) -> BTreeSet<PathBuf> {
+    manifest
+        .data
+        .targets
+        .iter()
+        .flat_map(|target| target.dependencies())
+        .filter_map(|dependency| match dependency.data {
+            DependencyData::CMake(ref cmake_data) => Some(cmake_data.build_directory.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+    use crate::parser::preprocessor::Preprocessor;
+    use pretty_assertions::assert_eq;
+
+    fn write_manifest(directory: &Path, library: &str, dependencies: &[&Path]) {
+        std::fs::create_dir_all(directory).unwrap();
+        std::fs::write(directory.join("lib.cpp"), "").unwrap();
+        let mut manifest = format!("[library.{}]\nsources = ['lib.cpp']\n", library);
+        for dependency in dependencies {
+            let name = dependency.file_name().unwrap().to_str().unwrap();
+            manifest.push_str(&format!(
+                "[library.{}.dependencies.{}]\npath = '{}'\n",
+                library,
+                name,
+                dependency.display()
+            ));
+        }
+        std::fs::write(directory.join(YAMBS_MANIFEST_NAME), manifest).unwrap();
+    }
+
+    fn parse(manifest_path: &Path) -> Result<ParsedManifest, ParseTomlError> {
+        let toml = std::fs::read_to_string(manifest_path).map_err(ParseTomlError::FailedToRead)?;
+        Ok(ParsedManifest {
+            manifest: Manifest::new(manifest_path.parent().unwrap()),
+            data: super::super::parse_toml(&toml, manifest_path, &mut Preprocessor::new())?,
+        })
+    }
+
+    #[test]
+    fn parses_manifests_of_transitive_dependencies_once() {
+        let tempdir = tempdir::TempDir::new("prefetch").unwrap();
+        let root = tempdir.path().join("root");
+        let left = tempdir.path().join("left");
+        let right = tempdir.path().join("right");
+        let base = tempdir.path().join("base");
+        let broken = tempdir.path().join("broken");
+        write_manifest(&base, "base", &[]);
+        write_manifest(&left, "left", &[&base]);
+        write_manifest(&right, "right", &[&base, &broken]);
+        write_manifest(&root, "root", &[&left, &right]);
+        std::fs::create_dir_all(&broken).unwrap();
+        std::fs::write(broken.join(YAMBS_MANIFEST_NAME), "[library.broken").unwrap();
+
+        let manifest = parse(&root.join(YAMBS_MANIFEST_NAME)).unwrap();
+        let parsed = parse_dependency_manifests_with(&manifest, parse);
+        let mut directories = parsed.keys().cloned().collect::<Vec<PathBuf>>();
+        directories.sort();
+        assert_eq!(directories, vec![base, left, right]);
+    }
+
+    #[test]
+    fn unchanged_manifests_are_reused_from_cache() {
+        let tempdir = tempdir::TempDir::new("prefetch").unwrap();
+        let root = tempdir.path().join("root");
+        let left = tempdir.path().join("left");
+        let right = tempdir.path().join("right");
+        write_manifest(&left, "left", &[]);
+        write_manifest(&right, "right", &[]);
+        write_manifest(&root, "root", &[&left, &right]);
+        let manifest = parse(&root.join(YAMBS_MANIFEST_NAME)).unwrap();
+
+        let mut cache = ParsedManifests::default();
+        let (first, statistics) =
+            parse_dependency_manifests_cached_with(&manifest, &mut cache, parse);
+        assert_eq!(
+            statistics,
+            ManifestCacheStatistics {
+                reused: 0,
+                parsed: 2
+            }
+        );
+
+        // The cache is written to and read from the build directory between configures.
+        let mut cache =
+            serde_json::from_str::<ParsedManifests>(&serde_json::to_string(&cache).unwrap())
+                .unwrap();
+        write_manifest(&right, "changed", &[]);
+        let (second, statistics) =
+            parse_dependency_manifests_cached_with(&manifest, &mut cache, parse);
+        assert_eq!(
+            statistics,
+            ManifestCacheStatistics {
+                reused: 1,
+                parsed: 1
+            }
+        );
+        assert_eq!(second[&left].data, first[&left].data);
+        assert_eq!(
+            second[&right].data.targets[0].library().unwrap().name,
+            "changed"
+        );
+    }
+}
@@ -9,8 +9,9 @@ use std::ffi::OsString;
 use regex::Regex;
 
 lazy_static::lazy_static! {
-    static ref ENV_VAR_REGEX: Regex = Regex::new(r"\$\{env:(?P<env>.*)\}").unwrap();
-    static ref VAR_REGEX: Regex = Regex::new(r"\$\{(?P<var>.*)\}").unwrap();
+    // Variables end at the first brace, so that they can be used in inline tables.
+    static ref ENV_VAR_REGEX: Regex = Regex::new(r"\$\{env:(?P<env>[^}]*)\}").unwrap();
+    static ref VAR_REGEX: Regex = Regex::new(r"\$\{(?P<var>[^}]*)\}").unwrap();
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -50,8 +51,10 @@ impl Preprocessor {
         self
     }
 
+    /// Substitutes the variables in `manifest_content`. The content is normalized first, so that a
+    /// byte order mark or CRLF line endings do not end up in the substituted values.
     pub fn parse(&mut self, manifest_content: &str) -> Result<String, PreprocessorError> {
-        let mut manifest_content = manifest_content.to_string();
+        let mut manifest_content = super::encoding::normalize(manifest_content);
 
         if let Some(env_captures) = ENV_VAR_REGEX.captures(&manifest_content) {
             let env = EnvironmentVariable::parse(env_captures.name("env").unwrap().as_str())
@@ -160,6 +163,13 @@ mod tests {
 
         let actual = fixture.preprocessor.parse(input).unwrap();
         assert_eq!(actual.as_str(), expected);
+
+        let input = "defines = [{ macro = \"ROOT\", value = \"${YAMBS_MANIFEST_DIR}\" }]";
+        let actual = fixture.preprocessor.parse(input).unwrap();
+        assert_eq!(
+            actual.as_str(),
+            "defines = [{ macro = \"ROOT\", value = \"manifest-dir\" }]"
+        );
     }
 
     #[test]
@@ -198,4 +208,15 @@ mod tests {
         let actual = fixture.preprocessor.parse(input).unwrap();
         assert_eq!(actual.as_str(), expected);
     }
+
+    #[test]
+    fn preprocessor_normalizes_bom_and_line_endings() {
+        let mut fixture = Fixture::new();
+        let input =
+            "\u{feff}[executable.factory]\r\nsources = [\"${YAMBS_MANIFEST_DIR}/main.cpp\"]\r\n";
+        let expected = "[executable.factory]\nsources = [\"manifest-dir/main.cpp\"]\n";
+
+        let actual = fixture.preprocessor.parse(input).unwrap();
+        assert_eq!(actual.as_str(), expected);
+    }
 }
@@ -5,7 +5,7 @@ use std::str::FromStr;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::flags::CompilerFlags;
+use crate::flags::{CompilerFlags, GeneratedWarnings, Warnings};
 
 #[derive(Debug, Error)]
 pub enum ParseStandardError {
@@ -17,11 +17,56 @@ pub enum ParseStandardError {
     UnrecognizedStandard(String),
 }
 
-#[derive(Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct ProjectConfig {
     pub std: Option<Standard>,
     pub language: Option<Language>,
+    /// Default of `gc_sections` for all targets.
+    pub gc_sections: Option<bool>,
+    /// Default of `hardened` for all targets.
+    pub hardened: Option<bool>,
+    /// Default of `system_pch` for all targets.
+    pub system_pch: Option<bool>,
+    /// Default of `warnings` for all targets.
+    pub warnings: Option<Warnings>,
+    /// Default of `generated_warnings` for all targets.
+    pub generated_warnings: Option<GeneratedWarnings>,
+    /// Directories sources are placed in, relative to the manifest. Defaults to src and source.
+    pub source_dirs: Option<Vec<std::path::PathBuf>>,
+    /// Include directories of the targets, relative to the manifest, also used by targets
+    /// depending on them. Defaults to include.
+    pub include_dirs: Option<Vec<std::path::PathBuf>>,
+    /// What a dependency written for a newer standard than its dependent is reported as.
+    /// Defaults to a warning.
+    pub standard_mismatch: Option<StandardMismatchPolicy>,
+    /// Build directory used when none is given with -b, relative to the manifest. Can contain
+    /// `${YAMBS_BUILD_TYPE}`.
+    pub build_dir: Option<String>,
+    /// Whether the headers of all dependencies are included with -isystem, keeping their warnings
+    /// out of those of the project, or with -I. Overridden by `origin` of a dependency.
+    pub third_party_as_system: Option<bool>,
+    /// Extensions of sources besides .cpp, .cc and .c, such as "cxx".
+    pub source_extensions: Option<Vec<String>>,
+    /// Extensions of headers besides .h, .hpp, .ipp, .inl and .tpp, such as "tcc".
+    pub header_extensions: Option<Vec<String>>,
+    /// Whether the targets of the project search its include directories with -iquote, so that
+    /// its own headers are only found by quote-includes, while headers of dependencies are
+    /// included with angle brackets. Dependents search the directories with -I regardless.
+    pub quote_includes: Option<bool>,
+    /// Files every target of the project is built again after changes to, relative to the
+    /// manifest, along with the `extra_files` of each target.
+    pub extra_files: Option<Vec<std::path::PathBuf>>,
+}
+
+/// How a dependency written for a newer standard than the target linking it is reported. Objects
+/// compiled with different standards may disagree on the layout of standard library types.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StandardMismatchPolicy {
+    #[default]
+    Warn,
+    Error,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
@@ -60,8 +105,9 @@ pub enum ParseLanguageError {
     InvalidLanguage(String),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
-#[serde(untagged)]
+// Standards are written as they are passed to the compiler, such as "c++17".
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum Standard {
     CXX(CXXStandard),
     C(CStandard),
@@ -105,6 +151,20 @@ impl Standard {
     }
 }
 
+impl std::convert::TryFrom<String> for Standard {
+    type Error = ParseStandardError;
+
+    fn try_from(standard: String) -> Result<Self, Self::Error> {
+        Self::parse(&standard)
+    }
+}
+
+impl From<Standard> for String {
+    fn from(standard: Standard) -> Self {
+        standard.to_string()
+    }
+}
+
 impl std::string::ToString for Standard {
     fn to_string(&self) -> String {
         match self {
@@ -114,7 +174,8 @@ impl std::string::ToString for Standard {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+// Standards are ordered from oldest to newest.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Deserialize, Serialize)]
 pub enum CStandard {
     C89,
     C90,
@@ -146,7 +207,8 @@ impl std::string::ToString for CStandard {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+// Standards are ordered from oldest to newest.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Deserialize, Serialize)]
 pub enum CXXStandard {
     CXX98,
     CXX03,
@@ -201,13 +263,115 @@ pub struct RawManifestData {
     pub executables: Option<std::collections::BTreeMap<String, RawExecutableData>>,
     #[serde(rename = "library")]
     pub libraries: Option<std::collections::BTreeMap<String, RawLibraryData>>,
+    #[serde(rename = "test")]
+    pub tests: Option<std::collections::BTreeMap<String, RawTestData>>,
+    #[serde(rename = "tests")]
+    pub test_discovery: Option<RawTestDiscoveryData>,
+    #[serde(rename = "example")]
+    pub examples: Option<std::collections::BTreeMap<String, RawExampleData>>,
+    pub install: Option<Vec<RawInstallData>>,
+    /// Flag groups, by name.
+    pub flags: Option<std::collections::BTreeMap<String, crate::flags::FlagGroup>>,
+    /// Defaults of the targets of the manifest and of the manifests below it. Holds those
+    /// inherited from the manifests above it as well once the manifest is parsed.
+    pub defaults: Option<super::defaults::DirectoryDefaults>,
+    /// Signing of the executables and libraries of the manifest after they are linked.
+    pub signing: Option<RawSigningData>,
+}
+
+impl RawManifestData {
+    /// The manifest as depended on for its libraries: without its tests and examples, along with
+    /// the executables its tests run or depend on, so that neither they nor their dependencies are
+    /// resolved. Executables run by the custom commands of the other targets are kept. The signing
+    /// of the manifest is left out as well, as it may name the targets left out, and only that of
+    /// the project is used.
+    pub fn without_tests_and_examples(mut self) -> Self {
+        let tested = self
+            .tests
+            .iter()
+            .flatten()
+            .flat_map(|(_, test)| {
+                let command_target = match test.command {
+                    Some(TestCommandData::Command(CommandData::Target(ref command))) => {
+                        Some(command.target.clone())
+                    }
+                    _ => None,
+                };
+                test.depends_on.iter().cloned().chain(command_target)
+            })
+            .collect::<std::collections::BTreeSet<String>>();
+        let tools = self
+            .executables
+            .iter()
+            .flat_map(|executables| executables.values().map(|data| &data.common_raw))
+            .chain(
+                self.libraries
+                    .iter()
+                    .flat_map(|libraries| libraries.values().map(|data| &data.common_raw)),
+            )
+            .flat_map(|common_raw| &common_raw.custom_commands)
+            .filter_map(|custom_command| match custom_command.command {
+                CommandData::Target(ref command) => Some(command.target.clone()),
+                CommandData::Program(_) => None,
+            })
+            .collect::<std::collections::BTreeSet<String>>();
+        if let Some(ref mut executables) = self.executables {
+            executables.retain(|name, _| !tested.contains(name) || tools.contains(name));
+        }
+        self.tests = None;
+        self.test_discovery = None;
+        self.examples = None;
+        self.signing = None;
+        self
+    }
 }
 
 #[derive(Debug, serde::Deserialize, PartialEq, Eq)]
-#[serde(transparent)]
 pub struct RawExecutableData {
     #[serde(flatten)]
     pub common_raw: RawCommonData,
+    #[serde(default)]
+    pub post_link: Vec<PostLinkArtifact>,
+}
+
+/// Example program of a library of the manifest, which is only built when asked for.
+#[derive(Debug, serde::Deserialize, PartialEq, Eq)]
+pub struct RawExampleData {
+    #[serde(flatten)]
+    pub executable: RawExecutableData,
+    /// Library of the manifest the example links. Defaults to the only library of the manifest.
+    pub library: Option<String>,
+}
+
+/// File made from an executable after it is linked, next to the executable.
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PostLinkArtifact {
+    /// Intel HEX image, made with `objcopy -O ihex`.
+    Hex,
+    /// Raw binary image, made with `objcopy -O binary`.
+    Bin,
+    /// Map file written by the linker.
+    Map,
+}
+
+impl PostLinkArtifact {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PostLinkArtifact::Hex => "hex",
+            PostLinkArtifact::Bin => "bin",
+            PostLinkArtifact::Map => "map",
+        }
+    }
+
+    /// Output format of objcopy, for artifacts converted from the executable.
+    pub fn objcopy_format(&self) -> Option<&'static str> {
+        match self {
+            PostLinkArtifact::Hex => Some("ihex"),
+            PostLinkArtifact::Bin => Some("binary"),
+            PostLinkArtifact::Map => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
@@ -226,10 +390,17 @@ pub struct RawLibraryData {
     pub common_raw: RawCommonData,
     #[serde(default, rename = "type")]
     pub lib_type: LibraryType,
+    /// Version script of a shared library, relative to the manifest directory, which decides the
+    /// symbols the library exports and their versions.
+    pub version_script: Option<std::path::PathBuf>,
+    /// Version of a shared library, such as "1.2.3". The library is named by it and given the
+    /// major version as soname.
+    pub version: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize, PartialEq, Eq)]
 pub struct RawCommonData {
+    #[serde(default)]
     pub sources: Vec<std::path::PathBuf>,
     #[serde(default)]
     pub dependencies: std::collections::BTreeMap<String, DependencyData>,
@@ -237,6 +408,164 @@ pub struct RawCommonData {
     pub compiler_flags: CompilerFlags,
     #[serde(default)]
     pub defines: Vec<Define>,
+    #[serde(default, rename = "custom_command")]
+    pub custom_commands: Vec<CustomCommandData>,
+    #[serde(default)]
+    pub build_for: BuildFor,
+    /// Files compiled into the target as byte arrays, relative to the manifest directory.
+    #[serde(default)]
+    pub embed: Vec<std::path::PathBuf>,
+    /// Files besides the sources the target is built again after changes to, such as linker
+    /// scripts and configuration templates, relative to the manifest directory.
+    #[serde(default)]
+    pub extra_files: Vec<std::path::PathBuf>,
+}
+
+/// Machine a target is built to run on when cross compiling.
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildFor {
+    /// The machine running the build, such as code generators and test helpers.
+    Host,
+    /// The machine the project is cross compiled for.
+    #[default]
+    Target,
+}
+
+/// Command run before the sources of a target are compiled, typically to generate sources.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CustomCommandData {
+    pub command: CommandData,
+    /// Files the command reads, relative to the manifest directory.
+    #[serde(default)]
+    pub inputs: Vec<PathBuf>,
+    /// Files the command produces, relative to the directory the command is run in.
+    pub outputs: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum CommandData {
+    /// Program and arguments of the command.
+    Program(Vec<String>),
+    /// Executable target of the same manifest, which is built before the command is run.
+    Target(TargetCommandData),
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TargetCommandData {
+    pub target: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Test run by `yambs test`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RawTestData {
+    /// Command of tests expected to pass.
+    pub command: Option<TestCommandData>,
+    #[serde(default)]
+    pub expect: TestExpectation,
+    /// Source of a compile-fail test, relative to the manifest directory.
+    pub source: Option<PathBuf>,
+    /// Target whose flags and include directories the source of a compile-fail test is compiled
+    /// with.
+    pub target: Option<String>,
+    /// Text expected in the diagnostics of a compile-fail test.
+    pub diagnostic: Option<String>,
+    /// Targets of the same manifest the test needs to be built before it is run.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Directory the test is run in, relative to the manifest directory.
+    pub working_directory: Option<PathBuf>,
+    /// Environment variables set for the test.
+    #[serde(default)]
+    pub env: std::collections::BTreeMap<String, String>,
+    /// Seconds the command of the test may run before it is killed and the test fails.
+    pub timeout: Option<u64>,
+    /// Times a failing test is run again. A test passing on a retry is reported as flaky.
+    #[serde(default)]
+    pub retries: u32,
+}
+
+/// Tests discovered from sources. Each source matched by `auto_discover` is compiled into an
+/// executable of its own, named after the source, which is run as a test of the same name.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RawTestDiscoveryData {
+    /// Pattern of the sources, relative to the manifest directory, such as "tests/*.cpp".
+    pub auto_discover: String,
+    /// Library of the manifest each test links.
+    pub library: Option<String>,
+    /// Dependencies of each test, such as its test framework.
+    #[serde(default)]
+    pub dependencies: std::collections::BTreeMap<String, DependencyData>,
+}
+
+/// Artifact installed by `yambs install`. Destinations are relative to the installation prefix,
+/// and modes are given in octal.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum RawInstallData {
+    /// Output of a target of the manifest, installed into the directory `destination`.
+    Target {
+        target: String,
+        destination: PathBuf,
+        mode: Option<String>,
+    },
+    /// File relative to the manifest directory, installed into the directory `destination`.
+    File {
+        file: PathBuf,
+        destination: PathBuf,
+        mode: Option<String>,
+    },
+    /// Directory created even if nothing is installed into it.
+    Directory {
+        directory: PathBuf,
+        mode: Option<String>,
+    },
+    /// Symbolic link `symlink`, pointing to `points_to`.
+    Symlink {
+        symlink: PathBuf,
+        points_to: PathBuf,
+    },
+}
+
+/// Command signing each artifact of the manifest once it is linked, such as
+/// `["codesign", "--sign", "Developer ID", "{artifact}"]`. `{artifact}` is replaced by the path of
+/// the artifact, and `{signature}` by the path of its signature when one is written.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RawSigningData {
+    pub command: Vec<String>,
+    /// File the command writes the signature of an artifact to, such as "{artifact}.sig". Left
+    /// out for commands signing the artifacts in place.
+    pub signature: Option<String>,
+    /// Targets of the manifest that are signed. Defaults to the executables and shared libraries
+    /// that are not examples, tests or built for the host.
+    pub targets: Option<Vec<String>>,
+}
+
+/// Outcome a test is expected to have for it to pass.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TestExpectation {
+    /// The command of the test exits successfully.
+    #[default]
+    Pass,
+    /// The source of the test fails to compile, such as by tripping a static_assert.
+    CompileFail,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum TestCommandData {
+    /// Script relative to the manifest directory.
+    Script(PathBuf),
+    Command(CommandData),
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
@@ -249,16 +578,32 @@ pub struct Define {
 
 #[derive(Debug, thiserror::Error)]
 pub enum ParseDefineError {
-    #[error("Incorrect syntax. Must be <key>=<value>")]
+    #[error("Incorrect syntax. Must be <name> or <name>=<value>")]
     IncorrectSyntax,
+    #[error("\"{0}\" is not a valid macro name")]
+    InvalidMacroName(String),
 }
 
 impl Define {
+    /// Parses a define given as NAME or NAME=value, like the -D option of the compilers.
     pub fn from_cli(s: &str) -> Result<Self, ParseDefineError> {
-        let (macro_, value) = s.split_once('=').ok_or(ParseDefineError::IncorrectSyntax)?;
+        let (macro_, value) = match s.split_once('=') {
+            Some((macro_, value)) => (macro_, Some(value.to_string())),
+            None => (s, None),
+        };
+        if macro_.is_empty() {
+            return Err(ParseDefineError::IncorrectSyntax);
+        }
+        let is_identifier = !macro_.starts_with(|c: char| c.is_ascii_digit())
+            && macro_
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(ParseDefineError::InvalidMacroName(macro_.to_string()));
+        }
         Ok(Self {
             macro_: macro_.to_string(),
-            value: Some(value.to_string()),
+            value,
         })
     }
 }
@@ -266,33 +611,146 @@ impl Define {
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
 pub struct SourceData {
     pub path: std::path::PathBuf,
+    /// How the headers of the dependency are included, overriding `third_party_as_system`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<IncludeSearchType>,
+    #[serde(flatten)]
+    pub link_options: LinkOptions,
+}
+
+/// How the library of a dependency is linked into the targets depending on it.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub struct LinkOptions {
+    /// Link every object of a static library, including those no symbol is referenced from, such
+    /// as objects registering plugins from their static initializers.
     #[serde(default)]
-    pub origin: IncludeSearchType,
+    pub whole_archive: bool,
+    /// Whether a shared library is only recorded as needed if a symbol is referenced from it.
+    /// False keeps a shared library loaded for the side effects of loading it.
+    #[serde(default = "default_as_needed")]
+    pub as_needed: bool,
+}
+
+fn default_as_needed() -> bool {
+    true
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        Self {
+            whole_archive: false,
+            as_needed: default_as_needed(),
+        }
+    }
+}
+
+/// Library of a source archive, such as a release tarball, downloaded from `url` and checked
+/// against `sha256` before it is unpacked into the build directory and built like a source
+/// dependency.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub struct ArchiveData {
+    pub url: String,
+    /// SHA-256 checksum of the archive, as hexadecimal digits.
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<IncludeSearchType>,
+    #[serde(flatten)]
+    pub link_options: LinkOptions,
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
 pub struct HeaderOnlyData {
     pub include_directory: std::path::PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<IncludeSearchType>,
 }
 
+// Unknown fields are denied, as a pkg-config dependency has no required field and would
+// otherwise accept any table.
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
 pub struct PkgConfigData {
-    #[serde(rename = "pkg_config_search_dir")]
-    pub search_dir: PathBuf,
+    /// Package looked up with pkg-config. Defaults to the name of the dependency.
+    #[serde(
+        rename = "pkg_config",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub package: Option<String>,
+    #[serde(
+        rename = "pkg_config_search_dir",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub search_dir: Option<PathBuf>,
+    /// Components of the package that are linked, instead of every library it provides.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<IncludeSearchType>,
+}
+
+/// Library of a project built by CMake, found through the file API in the build directory of the
+/// project.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CMakeData {
+    /// Build directory of the project, configured by CMake.
+    #[serde(rename = "cmake_build_dir")]
+    pub build_directory: PathBuf,
+    /// CMake target of the library. Defaults to the name of the dependency.
+    #[serde(
+        rename = "cmake_target",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<IncludeSearchType>,
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum DependencyData {
     Source(SourceData),
+    Archive(ArchiveData),
     HeaderOnly(HeaderOnlyData),
+    CMake(CMakeData),
+    YambsBuild(YambsBuildData),
     PkgConfig(PkgConfigData),
 }
 
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq, Default)]
+impl DependencyData {
+    /// How the headers of the dependency are included, if declared.
+    pub fn origin(&self) -> Option<IncludeSearchType> {
+        match self {
+            Self::Source(source) => source.origin,
+            Self::Archive(archive) => archive.origin,
+            Self::HeaderOnly(header_only) => header_only.origin,
+            Self::CMake(cmake) => cmake.origin,
+            Self::YambsBuild(yambs_build) => yambs_build.origin,
+            Self::PkgConfig(pkg_config) => pkg_config.origin,
+        }
+    }
+}
+
+/// Library of another project built by yambs, linked from the build directory it was built in
+/// without being built again.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct YambsBuildData {
+    /// Build directory of the project, given to `yambs build -b`.
+    #[serde(rename = "yambs_build")]
+    pub build_directory: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<IncludeSearchType>,
+}
+
+/// How the headers of a dependency are included: "System" with -isystem, which keeps the
+/// compiler from warning about them, or "Include" with -I.
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
 pub enum IncludeSearchType {
     System,
-    #[default]
     Include,
 }
 
@@ -300,6 +758,90 @@ pub enum IncludeSearchType {
 mod tests {
     use super::*;
 
+    #[test]
+    fn standards_are_read_as_written_and_ordered_from_oldest_to_newest() {
+        let config =
+            toml::from_str::<ProjectConfig>("std = \"c++20\"\nstandard_mismatch = \"error\"")
+                .unwrap();
+        assert_eq!(config.std, Some(Standard::CXX(CXXStandard::CXX20)));
+        assert_eq!(
+            config.standard_mismatch,
+            Some(StandardMismatchPolicy::Error)
+        );
+        assert!(CXXStandard::CXX98 < CXXStandard::CXX03 && CXXStandard::CXX20 < CXXStandard::CXX23);
+        assert!(CStandard::C17 > CStandard::C89);
+    }
+
+    #[test]
+    fn dependency_manifests_leave_out_their_tests_and_examples() {
+        let manifest = toml::from_str::<RawManifestData>(indoc::indoc! {r#"
+            [library.core]
+            sources = ["core.cpp"]
+            custom_command = [{ command = { target = "codegen" }, outputs = ["table.cpp"] }]
+
+            [executable.cli]
+            sources = ["cli.cpp"]
+
+            [executable.core_tests]
+            sources = ["core_tests.cpp"]
+            dependencies.gtest = { path = "../googletest" }
+
+            [executable.codegen]
+            sources = ["codegen.cpp"]
+
+            [test.unit]
+            command = { target = "core_tests" }
+
+            [test.generated]
+            command = ["sh", "-c", "true"]
+            depends_on = ["codegen"]
+
+            [tests]
+            auto_discover = "tests/*.cpp"
+
+            [example.hello]
+            sources = ["hello.cpp"]
+        "#})
+        .unwrap()
+        .without_tests_and_examples();
+        assert_eq!(
+            manifest
+                .executables
+                .unwrap()
+                .into_keys()
+                .collect::<Vec<String>>(),
+            vec!["cli", "codegen"]
+        );
+        assert!(manifest.libraries.is_some());
+        assert!(manifest.tests.is_none());
+        assert!(manifest.test_discovery.is_none());
+        assert!(manifest.examples.is_none());
+    }
+
+    #[test]
+    fn defines_of_the_command_line_may_leave_out_the_value() {
+        assert_eq!(
+            Define::from_cli("VERSION=1.2").unwrap(),
+            Define {
+                macro_: "VERSION".to_string(),
+                value: Some("1.2".to_string()),
+            }
+        );
+        assert_eq!(Define::from_cli("NDEBUG").unwrap().value, None);
+        assert_eq!(
+            Define::from_cli("EMPTY=").unwrap().value,
+            Some(String::new())
+        );
+        assert!(matches!(
+            Define::from_cli("2FAST=1"),
+            Err(ParseDefineError::InvalidMacroName(_))
+        ));
+        assert!(matches!(
+            Define::from_cli("=1"),
+            Err(ParseDefineError::IncorrectSyntax)
+        ));
+    }
+
     #[test]
     fn cxxstandard_parse_cpp98_test() {
         let cpp_version = CXXStandard::parse("c++98").unwrap();
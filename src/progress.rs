@@ -10,7 +10,8 @@ pub struct Progress {
 }
 
 impl Progress {
-    pub fn new(path: &std::path::Path, target: Option<String>) -> std::io::Result<Self> {
+    /// Progress of building `targets`, or all targets if there are none.
+    pub fn new(path: &std::path::Path, targets_to_build: &[String]) -> std::io::Result<Self> {
         let progress_file = path.join(PROGRESS_FILE_NAME);
 
         let fh = std::fs::File::open(progress_file)?;
@@ -20,10 +21,18 @@ impl Progress {
             serde_json::from_reader(reader)?;
         let targets = progress_document.targets;
 
-        let object_files = if let Some(target) = target {
-            Progress::object_files_from_target(&targets, &target)
-        } else {
+        let object_files = if targets_to_build.is_empty() {
             Progress::object_files_from_target(&targets, "all")
+        } else {
+            let mut object_files = Vec::<std::path::PathBuf>::new();
+            for target in targets_to_build {
+                for object_file in Progress::object_files_from_target(&targets, target) {
+                    if !object_files.contains(&object_file) {
+                        object_files.push(object_file);
+                    }
+                }
+            }
+            object_files
         };
 
         let total = object_files.len() as u64;
@@ -73,3 +82,265 @@ impl Progress {
         object_files
     }
 }
+
+/// Variable given to make by yambs, which makes the rules print a step marker as they start.
+pub const STEP_MARKER_VARIABLE: &str = "YAMBS_PROGRESS";
+
+const STEP_MARKER: &str = "yambs-step";
+
+// Word following the step marker of a step that finished.
+const FINISHED_MARKER: &str = "done";
+
+/// What a rule of a target does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Step {
+    Compile,
+    Link,
+}
+
+impl Step {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Compile => "compile",
+            Self::Link => "link",
+        }
+    }
+}
+
+// The output of a marker, as the target make expands $@ to is only known when the recipe runs.
+// Its ' are escaped for the single quotes of the echoed marker, as make substitutes them before
+// the shell runs the line.
+const QUOTED_OUTPUT: &str = "$(subst ','\\'',$@)";
+
+// Escapes a word of a marker for the single quotes it is echoed in, and $ for make.
+fn quote_marker_word(word: &str) -> String {
+    word.replace('\'', "'\\''").replace('$', "$$")
+}
+
+/// Whether a step is started or finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepState {
+    Started,
+    Finished,
+}
+
+/// Step of a target make started or finished, as printed by the markers of its rule. Make
+/// interleaves the output of the jobs it runs, so the markers are what attributes the build to
+/// targets.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct StepEvent {
+    pub step: Step,
+    pub state: StepState,
+    pub target: String,
+    pub output: std::path::PathBuf,
+}
+
+impl StepEvent {
+    /// Recipe line printing the marker of `step` of `target`, as the first line of its rule. The
+    /// line expands to nothing unless yambs runs make, so running make directly prints nothing.
+    /// It is marked with `+`, as make prints the output of such lines right away even when it
+    /// holds back the output of the recipe with --output-sync.
+    pub fn marker_recipe(step: Step, target: &str) -> String {
+        format!(
+            "\t$(if $({variable}),+@echo '{marker}')\n",
+            variable = STEP_MARKER_VARIABLE,
+            marker = Self::marker(step, &quote_marker_word(target), QUOTED_OUTPUT),
+        )
+    }
+
+    /// Recipe line printing the marker of `step` of `target` finishing, to follow the last line
+    /// of its rule. A recipe that fails stops before the line, so only steps that succeed finish.
+    pub fn finished_marker_recipe(step: Step, target: &str) -> String {
+        format!(
+            "\n\t$(if $({variable}),+@echo '{marker}')",
+            variable = STEP_MARKER_VARIABLE,
+            marker = Self::finished_marker(step, &quote_marker_word(target), QUOTED_OUTPUT),
+        )
+    }
+
+    /// The marker of `step` of `target` producing `output`, as parsed by `StepEvent::parse`.
+    pub fn marker(step: Step, target: &str, output: &str) -> String {
+        format!("{} {} {} {}", STEP_MARKER, step.as_str(), target, output)
+    }
+
+    /// The marker of `step` of `target` having produced `output`, as parsed by
+    /// `StepEvent::parse`.
+    pub fn finished_marker(step: Step, target: &str, output: &str) -> String {
+        format!(
+            "{} {} {} {} {}",
+            STEP_MARKER,
+            FINISHED_MARKER,
+            step.as_str(),
+            target,
+            output
+        )
+    }
+
+    /// The event of a line make printed, if it is a marker.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut marker = line.strip_prefix(STEP_MARKER)?.trim_start();
+        let state = match marker.strip_prefix(FINISHED_MARKER) {
+            Some(rest) => {
+                marker = rest.trim_start();
+                StepState::Finished
+            }
+            None => StepState::Started,
+        };
+        let mut words = marker.splitn(3, ' ');
+        let step = match words.next()? {
+            "compile" => Step::Compile,
+            "link" => Step::Link,
+            _ => return None,
+        };
+        let target = words.next()?.to_string();
+        let output = std::path::PathBuf::from(words.next()?);
+        Some(Self {
+            step,
+            state,
+            target,
+            output,
+        })
+    }
+
+    /// Whether `other` is the same step of the same target as this event.
+    pub fn is_step_of(&self, other: &StepEvent) -> bool {
+        self.step == other.step && self.target == other.target && self.output == other.output
+    }
+
+    /// Message of the progress bar while the step runs.
+    pub fn message(&self) -> String {
+        let step = match self.step {
+            Step::Compile => "Compiling",
+            Step::Link => "Linking",
+        };
+        format!(
+            "{} {} ({})",
+            step,
+            self.target,
+            self.output
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        )
+    }
+}
+
+/// Line of the JSON progress log, written for every step started and finished.
+#[derive(Debug, serde::Serialize)]
+pub struct ProgressRecord<'a> {
+    #[serde(flatten)]
+    pub event: &'a StepEvent,
+    /// Objects compiled of those to build, when the step started or finished.
+    pub current: u64,
+    pub total: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_event_is_parsed_from_marker_with_spaces_in_output() {
+        assert_eq!(
+            StepEvent::parse("yambs-step compile geometry /build/my dir/geometry.dir/shape.o"),
+            Some(StepEvent {
+                step: Step::Compile,
+                state: StepState::Started,
+                target: "geometry".to_string(),
+                output: std::path::PathBuf::from("/build/my dir/geometry.dir/shape.o"),
+            })
+        );
+        assert_eq!(
+            StepEvent::parse(&StepEvent::finished_marker(
+                Step::Link,
+                "geometry",
+                "/build/my dir/libgeometry.a"
+            )),
+            Some(StepEvent {
+                step: Step::Link,
+                state: StepState::Finished,
+                target: "geometry".to_string(),
+                output: std::path::PathBuf::from("/build/my dir/libgeometry.a"),
+            })
+        );
+        assert_eq!(StepEvent::parse("g++ -c shape.cpp -o shape.o"), None);
+        assert_eq!(StepEvent::parse("yambs-step test geometry out"), None);
+    }
+
+    #[test]
+    fn step_markers_are_printed_as_the_step_starts_and_finishes_with_output_sync() {
+        use std::io::BufRead;
+
+        let tempdir = tempdir::TempDir::new("progress").unwrap();
+        std::fs::write(
+            tempdir.path().join("Makefile"),
+            format!(
+                "shape.o:\n{}\t@sleep 1; touch $@{}\n",
+                StepEvent::marker_recipe(Step::Compile, "geometry"),
+                StepEvent::finished_marker_recipe(Step::Compile, "geometry")
+            ),
+        )
+        .unwrap();
+        let mut make = std::process::Command::new("make")
+            .args(["--jobs=2", "--output-sync=target"])
+            .arg(format!("{}=1", STEP_MARKER_VARIABLE))
+            .current_dir(tempdir.path())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdout = std::io::BufReader::new(make.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        assert!(!tempdir.path().join("shape.o").exists());
+        let started = StepEvent::parse(line.trim_end()).unwrap();
+        assert_eq!(
+            started,
+            StepEvent {
+                step: Step::Compile,
+                state: StepState::Started,
+                target: "geometry".to_string(),
+                output: std::path::PathBuf::from("shape.o"),
+            }
+        );
+        line.clear();
+        stdout.read_line(&mut line).unwrap();
+        assert!(tempdir.path().join("shape.o").exists());
+        let finished = StepEvent::parse(line.trim_end()).unwrap();
+        assert_eq!(finished.state, StepState::Finished);
+        assert!(finished.is_step_of(&started));
+        assert!(make.wait().unwrap().success());
+    }
+
+    #[test]
+    fn step_markers_are_printed_for_outputs_with_quotes() {
+        let tempdir = tempdir::TempDir::new("progress").unwrap();
+        std::fs::write(
+            tempdir.path().join("Makefile"),
+            format!(
+                "it's.o:\n{}\t@touch \"$@\"{}\n",
+                StepEvent::marker_recipe(Step::Compile, "geometry"),
+                StepEvent::finished_marker_recipe(Step::Compile, "geometry")
+            ),
+        )
+        .unwrap();
+        let output = std::process::Command::new("make")
+            .arg(format!("{}=1", STEP_MARKER_VARIABLE))
+            .current_dir(tempdir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(tempdir.path().join("it's.o").exists());
+        let events = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| StepEvent::parse(line).unwrap())
+            .collect::<Vec<StepEvent>>();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].output, std::path::PathBuf::from("it's.o"));
+        assert_eq!(events[0].state, StepState::Started);
+        assert_eq!(events[1].state, StepState::Finished);
+        assert!(events[1].is_step_of(&events[0]));
+    }
+}
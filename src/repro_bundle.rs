@@ -0,0 +1,367 @@
+// A repro bundle packages what it takes to reproduce a build for a bug report: the manifests, the
+// resolved configuration and toolchain of the most recent build, the generated build files and the
+// log of the build. Logs and build files can contain values of the environment, so values of
+// variables that look like secrets are redacted from every file in the bundle.
+
+use std::path::{Path, PathBuf};
+
+use crate::cache::Cacher;
+use crate::cli::command_line::ConfigurationOpts;
+use crate::environment::Environment;
+use crate::errors::FsError;
+use crate::toolchain::OutputExtensions;
+
+/// Archive written to the build directory, unless told otherwise.
+pub const REPRO_BUNDLE_FILE_NAME: &str = "yambs-repro-bundle.tar.gz";
+
+// Directory the contents of the bundle are placed in, both while staged and in the archive.
+const BUNDLE_DIRECTORY_NAME: &str = "yambs-repro-bundle";
+
+// Values shorter than this are too likely to show up by accident to be redacted.
+const MIN_SECRET_LENGTH: usize = 4;
+
+lazy_static::lazy_static! {
+    static ref SECRET_NAME_REGEX: regex::Regex = regex::Regex::new(
+        r"(?i)(^|_)(token|secret|passw(or)?d|credential|auth|cookie|api_?key|private_?key|key)s?(_|$)"
+    )
+    .unwrap();
+    static ref ASSIGNMENT_REGEX: regex::Regex =
+        regex::Regex::new(r"\b(?P<name>[A-Za-z_][A-Za-z0-9_]*)=(?P<value>[^\s'\x22]+)").unwrap();
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReproBundleError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+    #[error("Failed to run tar to create {0:?}")]
+    FailedToRunTar(PathBuf, #[source] std::io::Error),
+    #[error("tar failed to create {0:?} ({1})")]
+    TarFailed(PathBuf, std::process::ExitStatus),
+}
+
+/// Configuration of the most recent build of a build directory, recorded for repro bundles.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BuildRecord {
+    pub command_line: Vec<String>,
+    pub preset: Option<String>,
+    pub configuration: ConfigurationOpts,
+    pub toolchain_file: Option<PathBuf>,
+    pub manifest_directory: PathBuf,
+    /// Directories of the manifests of the project and its source dependencies.
+    pub manifest_directories: Vec<PathBuf>,
+    /// Values of the environment variables the toolchain is taken from, of those that were set.
+    #[serde(default)]
+    pub toolchain_environment: std::collections::BTreeMap<String, String>,
+    /// Extensions of the files made by the toolchain.
+    #[serde(default)]
+    pub output_extensions: OutputExtensions,
+}
+
+impl Cacher for BuildRecord {
+    const CACHE_FILE_NAME: &'static str = "build_record";
+}
+
+impl BuildRecord {
+    /// Options of `yambs build` building the same way into `build_directory`, with absolute paths.
+    pub fn build_args(&self, build_directory: &Path) -> Vec<String> {
+        let mut args = vec![
+            "--manifest-directory".to_string(),
+            self.manifest_directory.display().to_string(),
+            "--build-directory".to_string(),
+            build_directory.display().to_string(),
+        ];
+        args.extend(self.configuration.to_args());
+        if let Some(ref toolchain_file) = self.toolchain_file {
+            args.push("--toolchain-file".to_string());
+            args.push(toolchain_file.display().to_string());
+        }
+        args
+    }
+}
+
+/// Redacts the values of environment variables whose names look like they hold secrets, such as
+/// API_TOKEN or DB_PASSWORD, along with the values of such variables assigned on a command line.
+#[derive(Debug, Default, Clone)]
+pub struct Redactor {
+    secrets: Vec<(String, String)>,
+}
+
+impl Redactor {
+    pub fn new<I>(variables: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut secrets = variables
+            .into_iter()
+            .filter(|(name, value)| Self::is_secret(name) && value.len() >= MIN_SECRET_LENGTH)
+            .collect::<Vec<(String, String)>>();
+        // Longer values first, so that a secret containing another one is redacted as a whole.
+        secrets.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+        Self { secrets }
+    }
+
+    pub fn from_environment() -> Self {
+        Self::new(std::env::vars())
+    }
+
+    pub fn is_secret(name: &str) -> bool {
+        SECRET_NAME_REGEX.is_match(name)
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut redacted = text.to_string();
+        for (name, value) in &self.secrets {
+            redacted = redacted.replace(value.as_str(), &format!("<redacted ${}>", name));
+        }
+        ASSIGNMENT_REGEX
+            .replace_all(&redacted, |captures: &regex::Captures| {
+                let name = &captures["name"];
+                if Self::is_secret(name) && !captures["value"].starts_with("<redacted") {
+                    format!("{}=<redacted>", name)
+                } else {
+                    captures[0].to_string()
+                }
+            })
+            .into_owned()
+    }
+
+    /// The environment of yambs, one variable per line, with the values of secrets redacted.
+    pub fn environment(&self) -> String {
+        let mut variables = std::env::vars().collect::<Vec<(String, String)>>();
+        variables.sort();
+        let environment = variables
+            .into_iter()
+            .map(|(name, value)| {
+                if Self::is_secret(&name) {
+                    format!("{}=<redacted>\n", name)
+                } else {
+                    format!("{}={}\n", name, value)
+                }
+            })
+            .collect::<String>();
+        self.redact(&environment)
+    }
+}
+
+/// Files staged in a directory and then archived with tar. Every file added is redacted.
+pub struct ReproBundle {
+    staging_directory: PathBuf,
+    redactor: Redactor,
+}
+
+impl ReproBundle {
+    /// Stages the bundle in `directory`, which is removed once the bundle is archived.
+    pub fn new(directory: &Path, redactor: Redactor) -> Result<Self, ReproBundleError> {
+        if directory.exists() {
+            std::fs::remove_dir_all(directory)
+                .map_err(|e| FsError::RemoveDirectory(directory.to_path_buf(), e))?;
+        }
+        let staging_directory = directory.join(BUNDLE_DIRECTORY_NAME);
+        std::fs::create_dir_all(&staging_directory)
+            .map_err(|e| FsError::CreateDirectory(staging_directory.clone(), e))?;
+        Ok(Self {
+            staging_directory,
+            redactor,
+        })
+    }
+
+    /// Adds `text` as the file `path` of the bundle.
+    pub fn add_text(&self, path: &Path, text: &str) -> Result<(), ReproBundleError> {
+        let file = self.staging_directory.join(path);
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| FsError::CreateDirectory(parent.to_path_buf(), e))?;
+        }
+        std::fs::write(&file, self.redactor.redact(text))
+            .map_err(|e| FsError::CreateFile(file.clone(), e))?;
+        Ok(())
+    }
+
+    /// Adds the text file `source` as the file `path` of the bundle.
+    pub fn add_file(&self, path: &Path, source: &Path) -> Result<(), ReproBundleError> {
+        let content =
+            std::fs::read(source).map_err(|e| FsError::ReadFromFile(source.to_path_buf(), e))?;
+        self.add_text(path, &String::from_utf8_lossy(&content))
+    }
+
+    /// Adds the files below `directory` accepted by `filter`, placed below `path` of the bundle.
+    /// Returns the number of files added.
+    pub fn add_directory<F>(
+        &self,
+        path: &Path,
+        directory: &Path,
+        filter: &F,
+    ) -> Result<usize, ReproBundleError>
+    where
+        F: Fn(&Path) -> bool,
+    {
+        let mut added = 0;
+        let entries = std::fs::read_dir(directory)
+            .map_err(|e| FsError::ReadFromFile(directory.to_path_buf(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| FsError::ReadFromFile(directory.to_path_buf(), e))?;
+            let entry_path = entry.path();
+            let bundle_path = path.join(entry.file_name());
+            if entry_path.is_dir() {
+                added += self.add_directory(&bundle_path, &entry_path, filter)?;
+            } else if filter(&entry_path) {
+                self.add_file(&bundle_path, &entry_path)?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Archives the bundle as a gzipped tarball at `archive` and removes the staged files.
+    pub fn write_archive(self, archive: &Path) -> Result<(), ReproBundleError> {
+        let staging_root = self.staging_directory.parent().unwrap().to_path_buf();
+        let status = Environment::new()
+            .command(Path::new("tar"))
+            .arg("-czf")
+            .arg(archive)
+            .arg("-C")
+            .arg(&staging_root)
+            .arg(BUNDLE_DIRECTORY_NAME)
+            .status()
+            .map_err(|e| ReproBundleError::FailedToRunTar(archive.to_path_buf(), e))?;
+        if !status.success() {
+            return Err(ReproBundleError::TarFailed(archive.to_path_buf(), status));
+        }
+        std::fs::remove_dir_all(&staging_root)
+            .map_err(|e| FsError::RemoveDirectory(staging_root.clone(), e))?;
+        Ok(())
+    }
+}
+
+/// Path of `path` in a bundle, below `prefix`, with the root of absolute paths stripped.
+pub fn bundle_path(prefix: &str, path: &Path) -> PathBuf {
+    let relative = path
+        .components()
+        .filter(|component| matches!(component, std::path::Component::Normal(_)))
+        .collect::<PathBuf>();
+    Path::new(prefix).join(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn variables(variables: &[(&str, &str)]) -> Vec<(String, String)> {
+        variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn secrets_are_told_by_whole_segments_of_the_name() {
+        for name in [
+            "GITHUB_TOKEN",
+            "TOKEN",
+            "AWS_SECRET_ACCESS_KEY",
+            "db_password",
+            "NPM_AUTH",
+            "API_KEY",
+            "APIKEY",
+            "SSH_KEYS",
+            "GOOGLE_CREDENTIALS",
+        ] {
+            assert!(Redactor::is_secret(name), "{}", name);
+        }
+        for name in [
+            "XDG_SESSION_TYPE",
+            "AUTHOR",
+            "KEYBOARD_LAYOUT",
+            "MONKEY",
+            "TOKENIZER_PATH",
+            "PWD",
+        ] {
+            assert!(!Redactor::is_secret(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn redacts_values_of_secret_variables_and_secret_assignments() {
+        let redactor = Redactor::new(variables(&[
+            ("GITHUB_TOKEN", "ghp_abcdef123456"),
+            ("DB_PASSWORD", "hunter22"),
+            ("SSH_KEY", "abc"),
+            ("CXX", "g++"),
+        ]));
+        let text = "g++ -DTOKEN=ghp_abcdef123456 main.cpp\n\
+                    make API_KEY=s3cr3t CXX=g++ PASSWORD=hunter22\n";
+        assert_eq!(
+            redactor.redact(text),
+            "g++ -DTOKEN=<redacted $GITHUB_TOKEN> main.cpp\n\
+             make API_KEY=<redacted> CXX=g++ PASSWORD=<redacted $DB_PASSWORD>\n"
+        );
+    }
+
+    #[test]
+    fn bundle_is_archived_with_redacted_files() {
+        let tempdir = tempdir::TempDir::new("repro_bundle").unwrap();
+        let build_directory = tempdir.path().join("build");
+        std::fs::create_dir_all(build_directory.join("debug/deps/x.dir")).unwrap();
+        std::fs::write(build_directory.join("debug/Makefile"), "all: x\n").unwrap();
+        std::fs::write(
+            build_directory.join("debug/deps/x.dir/build.mk"),
+            "X_LDFLAGS += -Lsecret-token-value\n",
+        )
+        .unwrap();
+        std::fs::write(
+            build_directory.join("debug/deps/x.dir/main.o"),
+            [0xff, 0x00],
+        )
+        .unwrap();
+
+        let redactor = Redactor::new(variables(&[("NPM_TOKEN", "secret-token-value")]));
+        let bundle = ReproBundle::new(&tempdir.path().join("staging"), redactor).unwrap();
+        let added = bundle
+            .add_directory(
+                &bundle_path("build", Path::new("debug")),
+                &build_directory.join("debug"),
+                &|file: &Path| file.extension().is_none_or(|extension| extension == "mk"),
+            )
+            .unwrap();
+        assert_eq!(added, 2);
+        bundle
+            .add_text(Path::new("configuration.json"), "{}")
+            .unwrap();
+        let archive = tempdir.path().join(REPRO_BUNDLE_FILE_NAME);
+        bundle.write_archive(&archive).unwrap();
+        assert!(!tempdir.path().join("staging").exists());
+
+        let listing = std::process::Command::new("tar")
+            .arg("-tzf")
+            .arg(&archive)
+            .output()
+            .unwrap();
+        let mut files = String::from_utf8(listing.stdout)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.ends_with('/'))
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "yambs-repro-bundle/build/debug/Makefile",
+                "yambs-repro-bundle/build/debug/deps/x.dir/build.mk",
+                "yambs-repro-bundle/configuration.json",
+            ]
+        );
+
+        let build_file = std::process::Command::new("tar")
+            .arg("-xzOf")
+            .arg(&archive)
+            .arg("yambs-repro-bundle/build/debug/deps/x.dir/build.mk")
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(build_file.stdout).unwrap(),
+            "X_LDFLAGS += -L<redacted $NPM_TOKEN>\n"
+        );
+    }
+}
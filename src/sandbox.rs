@@ -0,0 +1,238 @@
+// Runs custom commands in an isolated working directory. Only the declared inputs are made
+// available in the directory, and only the declared outputs are copied back out when the command
+// has finished. A command reading an input it has not declared therefore fails straight away,
+// instead of silently depending on a file the build system does not know of.
+
+use std::path::{Path, PathBuf};
+
+use crate::environment::Environment;
+use crate::errors::FsError;
+use crate::utility;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SandboxError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+    #[error("No command given to run in sandbox")]
+    EmptyCommand,
+    #[error("Failed to run \"{0}\"")]
+    FailedToRun(String, #[source] std::io::Error),
+    #[error("\"{0}\" failed with {1}")]
+    CommandFailed(String, std::process::ExitStatus),
+    #[error("Declared output {0:?} was not produced by the command")]
+    MissingOutput(PathBuf),
+    #[error("Failed to copy {0:?} to {1:?}")]
+    FailedToCopy(PathBuf, PathBuf, #[source] std::io::Error),
+    #[error("Mapping must be of the form <path in sandbox>=<path>: {0}")]
+    InvalidMapping(String),
+}
+
+/// Maps a path in the sandbox to a path outside of it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SandboxMapping {
+    pub sandbox_path: PathBuf,
+    pub path: PathBuf,
+}
+
+impl SandboxMapping {
+    pub fn parse(s: &str) -> Result<Self, SandboxError> {
+        let (sandbox_path, path) = s
+            .split_once('=')
+            .ok_or_else(|| SandboxError::InvalidMapping(s.to_string()))?;
+        Ok(Self {
+            sandbox_path: PathBuf::from(sandbox_path),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl std::fmt::Display for SandboxMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.sandbox_path.display(), self.path.display())
+    }
+}
+
+pub struct Sandbox {
+    directory: PathBuf,
+}
+
+impl Sandbox {
+    /// Creates an empty sandbox, removing anything left behind by a previous run.
+    pub fn new(directory: &Path) -> Result<Self, SandboxError> {
+        if directory.exists() {
+            std::fs::remove_dir_all(directory)
+                .map_err(|e| FsError::RemoveDirectory(directory.to_path_buf(), e))?;
+        }
+        utility::create_dir(directory)?;
+        Ok(Self {
+            directory: directory.to_path_buf(),
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn add_input(&self, input: &SandboxMapping) -> Result<(), SandboxError> {
+        let destination = self.directory.join(&input.sandbox_path);
+        if let Some(parent) = destination.parent() {
+            utility::create_dir(parent)?;
+        }
+        utility::create_symlink(&input.path, &destination)?;
+        Ok(())
+    }
+
+    pub fn run(&self, command: &[String]) -> Result<(), SandboxError> {
+        let command_line = command.join(" ");
+        let (program, args) = command.split_first().ok_or(SandboxError::EmptyCommand)?;
+        log::debug!(
+            "Running \"{}\" in sandbox {}",
+            command_line,
+            self.directory.display()
+        );
+        let status = Environment::new()
+            .command(Path::new(program))
+            .args(args)
+            .current_dir(&self.directory)
+            .status()
+            .map_err(|e| SandboxError::FailedToRun(command_line.clone(), e))?;
+        if !status.success() {
+            return Err(SandboxError::CommandFailed(command_line, status));
+        }
+        Ok(())
+    }
+
+    pub fn collect_output(&self, output: &SandboxMapping) -> Result<(), SandboxError> {
+        let produced = self.directory.join(&output.sandbox_path);
+        if !produced.is_file() {
+            return Err(SandboxError::MissingOutput(output.sandbox_path.clone()));
+        }
+        if let Some(parent) = output.path.parent() {
+            utility::create_dir(parent)?;
+        }
+        std::fs::copy(&produced, &output.path)
+            .map_err(|e| SandboxError::FailedToCopy(produced.clone(), output.path.clone(), e))?;
+        Ok(())
+    }
+
+    /// Files written to the sandbox that are neither inputs nor declared outputs.
+    pub fn undeclared_outputs(&self, outputs: &[SandboxMapping]) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        collect_files(&self.directory, &mut files);
+        files
+            .into_iter()
+            .filter_map(|file| {
+                file.strip_prefix(&self.directory)
+                    .ok()
+                    .map(Path::to_path_buf)
+            })
+            .filter(|file| !outputs.iter().any(|o| &o.sandbox_path == file))
+            .collect()
+    }
+}
+
+// Inputs are symlinks, so these are skipped along with anything beneath them.
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+        if file_type.is_dir() {
+            collect_files(&entry.path(), files);
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn declared_inputs_are_available_and_outputs_are_collected() {
+        let tempdir = tempdir::TempDir::new("sandbox").unwrap();
+        let input = tempdir.path().join("input.txt");
+        std::fs::write(&input, "content").unwrap();
+        let sandbox = Sandbox::new(&tempdir.path().join("sandbox")).unwrap();
+        sandbox
+            .add_input(&SandboxMapping {
+                sandbox_path: PathBuf::from("data/input.txt"),
+                path: input,
+            })
+            .unwrap();
+        sandbox
+            .run(&command(&["cp", "data/input.txt", "output.txt"]))
+            .unwrap();
+
+        let output = SandboxMapping {
+            sandbox_path: PathBuf::from("output.txt"),
+            path: tempdir.path().join("generated").join("output.txt"),
+        };
+        sandbox.collect_output(&output).unwrap();
+        assert_eq!(std::fs::read_to_string(&output.path).unwrap(), "content");
+        assert!(sandbox.undeclared_outputs(&[output]).is_empty());
+    }
+
+    #[test]
+    fn undeclared_input_is_not_available() {
+        let tempdir = tempdir::TempDir::new("sandbox").unwrap();
+        std::fs::write(tempdir.path().join("input.txt"), "content").unwrap();
+        let sandbox = Sandbox::new(&tempdir.path().join("sandbox")).unwrap();
+        let result = sandbox.run(&command(&["cat", "input.txt"]));
+        assert!(matches!(result, Err(SandboxError::CommandFailed(..))));
+    }
+
+    #[test]
+    fn undeclared_outputs_are_reported() {
+        let tempdir = tempdir::TempDir::new("sandbox").unwrap();
+        let sandbox = Sandbox::new(&tempdir.path().join("sandbox")).unwrap();
+        sandbox
+            .run(&command(&["touch", "declared.txt", "undeclared.txt"]))
+            .unwrap();
+        let declared = SandboxMapping {
+            sandbox_path: PathBuf::from("declared.txt"),
+            path: tempdir.path().join("declared.txt"),
+        };
+        assert_eq!(
+            sandbox.undeclared_outputs(&[declared]),
+            vec![PathBuf::from("undeclared.txt")]
+        );
+    }
+
+    #[test]
+    fn missing_output_is_an_error() {
+        let tempdir = tempdir::TempDir::new("sandbox").unwrap();
+        let sandbox = Sandbox::new(&tempdir.path().join("sandbox")).unwrap();
+        let output = SandboxMapping {
+            sandbox_path: PathBuf::from("output.txt"),
+            path: tempdir.path().join("output.txt"),
+        };
+        assert!(matches!(
+            sandbox.collect_output(&output),
+            Err(SandboxError::MissingOutput(_))
+        ));
+    }
+
+    #[test]
+    fn mapping_is_parsed_from_command_line_form() {
+        let mapping = SandboxMapping::parse("gen/out.cpp=/build/out.cpp").unwrap();
+        assert_eq!(
+            mapping,
+            SandboxMapping {
+                sandbox_path: PathBuf::from("gen/out.cpp"),
+                path: PathBuf::from("/build/out.cpp"),
+            }
+        );
+        assert_eq!(mapping.to_string(), "gen/out.cpp=/build/out.cpp");
+    }
+}
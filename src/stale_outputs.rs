@@ -0,0 +1,173 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::cache::Cacher;
+use crate::errors::FsError;
+use crate::generator::targets::ProgressDocument;
+use crate::warnings_report::warnings_file;
+
+/// Files the targets of the build trees are expected to produce, as of the most recent generation
+/// of their build files. Outputs expected by an earlier generation but not by the current one,
+/// such as the object of a source removed from the manifest, are stale and would otherwise linger
+/// in the build tree and could still get linked.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExpectedOutputs {
+    /// Object files of each target, keyed by the file the target links.
+    #[serde(default)]
+    pub targets: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+impl Cacher for ExpectedOutputs {
+    const CACHE_FILE_NAME: &'static str = "expected_outputs";
+}
+
+impl ExpectedOutputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_progress_document(document: &ProgressDocument) -> Self {
+        let targets = document
+            .targets
+            .iter()
+            .filter_map(|target| {
+                let output = target.output.clone()?;
+                Some((output, target.object_files.iter().cloned().collect()))
+            })
+            .collect();
+        Self { targets }
+    }
+
+    /// Replaces the expected outputs of the build tree in `buildfile_directory` with `current`,
+    /// and returns the outputs that are no longer expected. A target that lost objects is stale
+    /// itself as well, as a static library would keep the removed objects as members and a
+    /// shared library would not be relinked without them.
+    pub fn update(&mut self, buildfile_directory: &Path, current: ExpectedOutputs) -> Vec<PathBuf> {
+        let still_expected = current
+            .targets
+            .iter()
+            .flat_map(|(output, objects)| std::iter::once(output).chain(objects))
+            .collect::<BTreeSet<&PathBuf>>();
+
+        let mut stale = BTreeSet::new();
+        for (output, objects) in &self.targets {
+            if !output.starts_with(buildfile_directory) {
+                continue;
+            }
+            let removed_objects = objects
+                .iter()
+                .filter(|object| !still_expected.contains(object))
+                .collect::<Vec<&PathBuf>>();
+            if !removed_objects.is_empty() || !current.targets.contains_key(output) {
+                stale.insert(output.clone());
+                stale.extend(removed_objects.into_iter().cloned());
+            }
+        }
+
+        self.targets
+            .retain(|output, _| !output.starts_with(buildfile_directory));
+        self.targets.extend(current.targets);
+        stale.into_iter().collect()
+    }
+}
+
+/// Removes the stale outputs that exist, along with the dependency and warnings files made when
+/// compiling stale objects. Returns the outputs removed.
+pub fn remove_stale_outputs(stale: &[PathBuf]) -> Result<Vec<PathBuf>, FsError> {
+    let mut removed = Vec::new();
+    for output in stale {
+        // Objects may have any extension the toolchain gives them, while other outputs have no
+        // such files next to them.
+        for file in [output.with_extension("d"), warnings_file(output)] {
+            if file.is_file() {
+                std::fs::remove_file(&file).map_err(|e| FsError::RemoveFile(file.clone(), e))?;
+            }
+        }
+        if output.is_file() {
+            log::debug!("Removing stale output {}", output.display());
+            std::fs::remove_file(output).map_err(|e| FsError::RemoveFile(output.clone(), e))?;
+            removed.push(output.clone());
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn expected(targets: &[(&str, &[&str])]) -> ExpectedOutputs {
+        ExpectedOutputs {
+            targets: targets
+                .iter()
+                .map(|(output, objects)| {
+                    (
+                        PathBuf::from(output),
+                        objects.iter().map(PathBuf::from).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn removed_source_makes_its_object_and_target_stale() {
+        let mut outputs = expected(&[
+            (
+                "/build/debug/libcore.a",
+                &["/build/debug/core/a.o", "/build/debug/core/b.o"],
+            ),
+            ("/build/debug/app", &["/build/debug/app/main.o"]),
+        ]);
+        let stale = outputs.update(
+            Path::new("/build/debug"),
+            expected(&[
+                ("/build/debug/libcore.a", &["/build/debug/core/a.o"]),
+                ("/build/debug/app", &["/build/debug/app/main.o"]),
+            ]),
+        );
+        assert_eq!(
+            stale,
+            vec![
+                PathBuf::from("/build/debug/core/b.o"),
+                PathBuf::from("/build/debug/libcore.a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_target_is_stale_and_other_build_trees_are_kept() {
+        let mut outputs = expected(&[
+            ("/build/debug/libold.a", &["/build/debug/old/a.o"]),
+            ("/build/release/libold.a", &["/build/release/old/a.o"]),
+        ]);
+        let stale = outputs.update(Path::new("/build/debug"), ExpectedOutputs::new());
+        assert_eq!(
+            stale,
+            vec![
+                PathBuf::from("/build/debug/libold.a"),
+                PathBuf::from("/build/debug/old/a.o"),
+            ]
+        );
+        assert_eq!(
+            outputs,
+            expected(&[("/build/release/libold.a", &["/build/release/old/a.o"])])
+        );
+    }
+
+    #[test]
+    fn remove_stale_outputs_removes_objects_with_their_dependency_files() {
+        let tempdir = tempdir::TempDir::new("stale_outputs").unwrap();
+        let object = tempdir.path().join("a.o");
+        std::fs::write(&object, "").unwrap();
+        std::fs::write(tempdir.path().join("a.d"), "").unwrap();
+        std::fs::write(tempdir.path().join("a.o.warnings"), "").unwrap();
+        let missing = tempdir.path().join("missing.o");
+
+        let removed = remove_stale_outputs(&[object.clone(), missing]).unwrap();
+        assert_eq!(removed, vec![object]);
+        assert!(!tempdir.path().join("a.d").exists());
+        assert!(!tempdir.path().join("a.o.warnings").exists());
+    }
+}
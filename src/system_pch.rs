@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+
+use crate::build_file_hashes::ContentHasher;
+use crate::compiler::CXXCompiler;
+use crate::parser::types::{CXXStandard, Standard};
+use crate::ProjectConfig;
+
+pub const SYSTEM_HEADER_FILE_NAME: &str = "yambs_system.hpp";
+
+// Standard library headers included by the precompiled header, with the standard they came with.
+const STANDARD_HEADERS: &[(CXXStandard, &str)] = &[
+    (CXXStandard::CXX98, "algorithm"),
+    (CXXStandard::CXX98, "cstddef"),
+    (CXXStandard::CXX98, "cstdint"),
+    (CXXStandard::CXX98, "cstring"),
+    (CXXStandard::CXX98, "deque"),
+    (CXXStandard::CXX98, "functional"),
+    (CXXStandard::CXX98, "iostream"),
+    (CXXStandard::CXX98, "iterator"),
+    (CXXStandard::CXX98, "list"),
+    (CXXStandard::CXX98, "map"),
+    (CXXStandard::CXX98, "memory"),
+    (CXXStandard::CXX98, "set"),
+    (CXXStandard::CXX98, "sstream"),
+    (CXXStandard::CXX98, "stdexcept"),
+    (CXXStandard::CXX98, "string"),
+    (CXXStandard::CXX98, "utility"),
+    (CXXStandard::CXX98, "vector"),
+    (CXXStandard::CXX11, "array"),
+    (CXXStandard::CXX11, "atomic"),
+    (CXXStandard::CXX11, "chrono"),
+    (CXXStandard::CXX11, "mutex"),
+    (CXXStandard::CXX11, "thread"),
+    (CXXStandard::CXX11, "tuple"),
+    (CXXStandard::CXX11, "type_traits"),
+    (CXXStandard::CXX11, "unordered_map"),
+    (CXXStandard::CXX11, "unordered_set"),
+    (CXXStandard::CXX17, "filesystem"),
+    (CXXStandard::CXX17, "optional"),
+    (CXXStandard::CXX17, "string_view"),
+    (CXXStandard::CXX17, "variant"),
+    (CXXStandard::CXX20, "concepts"),
+    (CXXStandard::CXX20, "span"),
+];
+
+/// Header including the common standard library headers, precompiled once into a cache shared by
+/// all builds using the same compiler, standard and build configuration.
+///
+/// Targets opting in have the header included with `-include`, which makes GCC use the
+/// precompiled header next to it. A precompiled header that does not match the flags of a
+/// translation unit is ignored by GCC, and the header is then parsed as usual.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemPch {
+    directory: PathBuf,
+    standard: CXXStandard,
+}
+
+impl SystemPch {
+    /// Precompiled header for building with `compiler` as configured by `project_config`, kept in
+    /// `cache_directory`. There is none for C.
+    pub fn new(
+        cache_directory: &Path,
+        compiler: &CXXCompiler,
+        project_config: &ProjectConfig,
+    ) -> Option<Self> {
+        let standard = match project_config.std {
+            Standard::CXX(ref standard) => standard.clone(),
+            Standard::C(_) => return None,
+        };
+        let mut hasher = ContentHasher::new();
+        hasher
+            .add_path(&compiler.compiler_exe)
+            .add(&compiler.compiler_info.compiler_version)
+            .add(project_config.std.to_string())
+            .add(project_config.build_type.to_string())
+            .add(serde_json::to_string(&project_config.sanitizers).unwrap_or_default())
+            .add(serde_json::to_string(&project_config.opt_level).unwrap_or_default())
+            .add(serde_json::to_string(&project_config.defines).unwrap_or_default());
+        Some(Self {
+            directory: cache_directory.join(format!("{:016x}", hasher.finish())),
+            standard,
+        })
+    }
+
+    /// Cache shared by the builds of the user.
+    pub fn default_cache_directory() -> Option<PathBuf> {
+        home::home_dir().map(|home| home.join(".yambs").join("pch"))
+    }
+
+    pub fn header(&self) -> PathBuf {
+        self.directory.join(SYSTEM_HEADER_FILE_NAME)
+    }
+
+    pub fn precompiled_header(&self) -> PathBuf {
+        self.directory
+            .join(format!("{}.gch", SYSTEM_HEADER_FILE_NAME))
+    }
+
+    pub fn header_content(&self) -> String {
+        let mut content = String::from(
+            "// Generated by yambs. Standard library headers precompiled once and shared by builds.\n",
+        );
+        for (_, header) in STANDARD_HEADERS
+            .iter()
+            .filter(|(standard, _)| *standard <= self.standard)
+        {
+            content.push_str(&format!("#include <{}>\n", header));
+        }
+        content
+    }
+
+    /// Whether the header in the cache differs from the one to precompile. It is only written
+    /// then, since rewriting it makes every build sharing the cache precompile it again.
+    pub fn needs_writing(&self) -> bool {
+        std::fs::read_to_string(self.header()).ok() != Some(self.header_content())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_content_includes_headers_of_the_standard_only() {
+        let mut pch = SystemPch {
+            directory: PathBuf::from("/cache/0123"),
+            standard: CXXStandard::CXX14,
+        };
+        let content = pch.header_content();
+        assert!(content.contains("#include <vector>\n"));
+        assert!(content.contains("#include <unordered_map>\n"));
+        assert!(!content.contains("#include <optional>\n"));
+
+        pch.standard = CXXStandard::CXX20;
+        assert!(pch.header_content().contains("#include <span>\n"));
+        assert_eq!(
+            pch.precompiled_header(),
+            PathBuf::from("/cache/0123/yambs_system.hpp.gch")
+        );
+    }
+}
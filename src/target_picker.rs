@@ -0,0 +1,263 @@
+// Picks the targets `yambs build --interactive` builds. The targets are listed with a checkbox
+// each, and are toggled by their numbers on the list. Typing `/text` narrows the list down to the
+// targets whose names contain the text. The selection is remembered in the build directory and
+// preselected the next time.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use crate::cache::Cacher;
+
+/// Targets selected the last time the picker was used for a build directory.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TargetSelection {
+    pub targets: BTreeSet<String>,
+}
+
+impl Cacher for TargetSelection {
+    const CACHE_FILE_NAME: &'static str = "target_selection";
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PickerAction {
+    /// Show the list again and wait for the next command.
+    Continue,
+    /// Build the selected targets.
+    Build,
+    /// Leave without building anything.
+    Quit,
+}
+
+#[derive(Debug)]
+pub struct TargetPicker {
+    targets: Vec<String>,
+    selected: BTreeSet<String>,
+    filter: String,
+    message: Option<String>,
+}
+
+impl TargetPicker {
+    /// Picker listing `targets`, with the targets of `previous` that still exist selected.
+    pub fn new(targets: Vec<String>, previous: &TargetSelection) -> Self {
+        let selected = previous
+            .targets
+            .iter()
+            .filter(|target| targets.contains(target))
+            .cloned()
+            .collect();
+        Self {
+            targets,
+            selected,
+            filter: String::new(),
+            message: None,
+        }
+    }
+
+    pub fn selection(&self) -> TargetSelection {
+        TargetSelection {
+            targets: self.selected.clone(),
+        }
+    }
+
+    fn visible_targets(&self) -> Vec<&String> {
+        self.targets
+            .iter()
+            .filter(|target| target.contains(&self.filter))
+            .collect()
+    }
+
+    /// List of the visible targets and the commands of the picker.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        if !self.filter.is_empty() {
+            text.push_str(&format!("Targets containing \"{}\":\n", self.filter));
+        }
+        let visible_targets = self.visible_targets();
+        if visible_targets.is_empty() {
+            text.push_str("  (no targets)\n");
+        }
+        for (i, target) in visible_targets.iter().enumerate() {
+            let checkbox = if self.selected.contains(*target) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            text.push_str(&format!("{:>4} {} {}\n", i + 1, checkbox, target));
+        }
+        text.push_str(&format!(
+            "{} of {} targets selected\n",
+            self.selected.len(),
+            self.targets.len()
+        ));
+        if let Some(ref message) = self.message {
+            text.push_str(message);
+            text.push('\n');
+        }
+        text.push_str(
+            "Toggle with numbers or ranges (1 3 5-7), filter with /text, select (a)ll or (n)one, \
+             build with an empty line or (q)uit: ",
+        );
+        text
+    }
+
+    /// Applies a line of input.
+    pub fn apply(&mut self, command: &str) -> PickerAction {
+        self.message = None;
+        let command = command.trim();
+        if let Some(filter) = command.strip_prefix('/') {
+            self.filter = filter.trim().to_string();
+            return PickerAction::Continue;
+        }
+        match command {
+            "" => {
+                if self.selected.is_empty() {
+                    self.message = Some("No targets selected".to_string());
+                    PickerAction::Continue
+                } else {
+                    PickerAction::Build
+                }
+            }
+            "q" => PickerAction::Quit,
+            "a" => {
+                let visible_targets = self
+                    .visible_targets()
+                    .into_iter()
+                    .cloned()
+                    .collect::<Vec<String>>();
+                self.selected.extend(visible_targets);
+                PickerAction::Continue
+            }
+            "n" => {
+                for target in self
+                    .visible_targets()
+                    .into_iter()
+                    .cloned()
+                    .collect::<Vec<String>>()
+                {
+                    self.selected.remove(&target);
+                }
+                PickerAction::Continue
+            }
+            _ => {
+                match self.parse_numbers(command) {
+                    Ok(targets) => {
+                        for target in targets {
+                            if !self.selected.remove(&target) {
+                                self.selected.insert(target);
+                            }
+                        }
+                    }
+                    Err(message) => self.message = Some(message),
+                }
+                PickerAction::Continue
+            }
+        }
+    }
+
+    // Visible targets numbered by `command`, such as "1 3 5-7".
+    fn parse_numbers(&self, command: &str) -> Result<Vec<String>, String> {
+        let visible_targets = self.visible_targets();
+        let number = |s: &str| -> Result<usize, String> {
+            match s.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= visible_targets.len() => Ok(n),
+                _ => Err(format!("No target numbered {}", s)),
+            }
+        };
+        let mut targets = Vec::new();
+        for word in command.split(|c: char| c.is_whitespace() || c == ',') {
+            if word.is_empty() {
+                continue;
+            }
+            let (first, last) = match word.split_once('-') {
+                Some((first, last)) => (number(first)?, number(last)?),
+                None => (number(word)?, number(word)?),
+            };
+            for n in first.min(last)..=first.max(last) {
+                targets.push(visible_targets[n - 1].clone());
+            }
+        }
+        Ok(targets)
+    }
+
+    /// Runs the picker until targets are chosen for building, with commands read from `input`.
+    /// Returns None if the picker is left without building, or the input ends.
+    pub fn run(
+        mut self,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> std::io::Result<Option<TargetSelection>> {
+        loop {
+            write!(output, "{}", self.render())?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            match self.apply(&line) {
+                PickerAction::Continue => writeln!(output)?,
+                PickerAction::Build => return Ok(Some(self.selection())),
+                PickerAction::Quit => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn picker(previous: &[&str]) -> TargetPicker {
+        TargetPicker::new(
+            vec![
+                "app".to_string(),
+                "core".to_string(),
+                "core_tests".to_string(),
+                "tools".to_string(),
+            ],
+            &TargetSelection {
+                targets: previous.iter().map(|target| target.to_string()).collect(),
+            },
+        )
+    }
+
+    fn selected(picker: &TargetPicker) -> Vec<String> {
+        picker.selection().targets.into_iter().collect()
+    }
+
+    #[test]
+    fn previous_selection_is_kept_for_targets_that_still_exist() {
+        let picker = picker(&["core", "removed"]);
+        assert_eq!(selected(&picker), vec!["core"]);
+    }
+
+    #[test]
+    fn numbers_toggle_targets_of_the_filtered_list() {
+        let mut picker = picker(&["app"]);
+        assert_eq!(picker.apply("/core"), PickerAction::Continue);
+        assert!(picker.render().contains("   2 [ ] core_tests\n"));
+        picker.apply("1-2");
+        assert_eq!(selected(&picker), vec!["app", "core", "core_tests"]);
+        picker.apply("2");
+        assert_eq!(selected(&picker), vec!["app", "core"]);
+        picker.apply("/");
+        picker.apply("n");
+        assert!(selected(&picker).is_empty());
+        assert_eq!(picker.apply(""), PickerAction::Continue);
+        assert!(picker.render().contains("No targets selected"));
+    }
+
+    #[test]
+    fn run_builds_the_selection_made_from_input() {
+        let mut input = std::io::Cursor::new("9\n1 4\n\n");
+        let mut output = Vec::new();
+        let selection = picker(&[]).run(&mut input, &mut output).unwrap();
+        assert_eq!(
+            selection.unwrap().targets.into_iter().collect::<Vec<_>>(),
+            vec!["app", "tools"]
+        );
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("No target numbered 9"));
+    }
+}
@@ -4,7 +4,7 @@ use crate::flags::CompilerFlags;
 use crate::parser::types;
 use crate::parser::types::PkgConfigData;
 
-#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum Target {
     Executable(Executable),
@@ -26,6 +26,13 @@ impl Target {
         }
     }
 
+    pub fn build_for(&self) -> types::BuildFor {
+        match self {
+            Target::Executable(exec) => exec.build_for,
+            Target::Library(lib) => lib.build_for,
+        }
+    }
+
     pub fn dependencies(&self) -> &Vec<Dependency> {
         match self {
             Target::Executable(exec) => &exec.dependencies,
@@ -41,6 +48,17 @@ pub struct Executable {
     pub dependencies: Vec<Dependency>,
     pub compiler_flags: CompilerFlags,
     pub defines: Vec<types::Define>,
+    pub custom_commands: Vec<CustomCommand>,
+    pub build_for: types::BuildFor,
+    pub post_link: Vec<types::PostLinkArtifact>,
+    pub embed: Vec<std::path::PathBuf>,
+    /// Files besides the sources the executable is built again after changes to, including those
+    /// of `[project_config]`.
+    #[serde(default)]
+    pub extra_files: Vec<std::path::PathBuf>,
+    /// Whether the executable is an example, which is left out of the targets built by default.
+    #[serde(default)]
+    pub example: bool,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
@@ -51,12 +69,565 @@ pub struct Library {
     pub compiler_flags: CompilerFlags,
     pub lib_type: types::LibraryType,
     pub defines: Vec<types::Define>,
+    pub custom_commands: Vec<CustomCommand>,
+    pub build_for: types::BuildFor,
+    pub embed: Vec<std::path::PathBuf>,
+    /// Version script the library is linked with, if it is a shared library.
+    pub version_script: Option<std::path::PathBuf>,
+    /// Version of the library, if it is a shared library.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Files besides the sources the library is built again after changes to, including those of
+    /// `[project_config]`.
+    #[serde(default)]
+    pub extra_files: Vec<std::path::PathBuf>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CustomCommandError {
+    #[error("Custom command has no program to run")]
+    EmptyCommand,
+    #[error("Custom command output {0:?} must be a relative path inside the directory the command is run in")]
+    OutputOutsideSandbox(std::path::PathBuf),
+}
+
+/// Custom command with inputs resolved to absolute paths.
+///
+/// Outputs stay relative, as they are placed in the build directory of the target by the
+/// generator.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub struct CustomCommand {
+    pub command: Command,
+    pub inputs: Vec<std::path::PathBuf>,
+    pub outputs: Vec<std::path::PathBuf>,
+}
+
+impl CustomCommand {
+    pub fn new(
+        data: &types::CustomCommandData,
+        manifest_dir: &Path,
+    ) -> Result<Self, CustomCommandError> {
+        let command = match data.command {
+            types::CommandData::Program(ref command) => Command::Program(command.clone()),
+            types::CommandData::Target(ref target_command) => Command::Target {
+                target: target_command.target.clone(),
+                args: target_command.args.clone(),
+            },
+        };
+        if command.is_empty() {
+            return Err(CustomCommandError::EmptyCommand);
+        }
+        for output in &data.outputs {
+            let escapes_sandbox = output.components().any(|c| {
+                !matches!(
+                    c,
+                    std::path::Component::Normal(_) | std::path::Component::CurDir
+                )
+            });
+            if escapes_sandbox {
+                return Err(CustomCommandError::OutputOutsideSandbox(output.clone()));
+            }
+        }
+        Ok(Self {
+            command,
+            inputs: data
+                .inputs
+                .iter()
+                .map(|input| manifest_dir.join(input))
+                .collect(),
+            outputs: data.outputs.clone(),
+        })
+    }
+}
+
+/// Program run by a custom command.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub enum Command {
+    /// Program, looked up in PATH unless given as a path, followed by its arguments.
+    Program(Vec<String>),
+    /// Executable target of the same manifest, built before the command is run.
+    Target { target: String, args: Vec<String> },
+}
+
+impl Command {
+    /// Name of the executable target used as the program, if any.
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            Self::Program(_) => None,
+            Self::Target { target, .. } => Some(target),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Program(command) => command.is_empty(),
+            Self::Target { target, .. } => target.is_empty(),
+        }
+    }
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Program(command) => write!(f, "{}", command.join(" ")),
+            Self::Target { target, args } => {
+                write!(
+                    f,
+                    "{}",
+                    std::iter::once(target)
+                        .chain(args)
+                        .cloned()
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                )
+            }
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TestError {
+    #[error("Test \"{0}\" has no program to run")]
+    EmptyCommand(String),
+    #[error("Test \"{0}\" expects a compile failure, but does not give the source and target to compile")]
+    IncompleteCompileFail(String),
+    #[error("Test \"{0}\" gives {1}, which is only used by tests expecting a compile failure")]
+    CompileFailField(String, &'static str),
+}
+
+/// What a test checks to pass.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub enum TestKind {
+    /// Runs the command, which has to exit successfully.
+    Run(Command),
+    /// Compiles `source` with the flags and include directories of `target`, which has to fail
+    /// with diagnostics containing `diagnostic`, if given.
+    CompileFail {
+        source: std::path::PathBuf,
+        target: String,
+        diagnostic: Option<String>,
+    },
+}
+
+impl std::fmt::Display for TestKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Run(command) => write!(f, "{}", command),
+            Self::CompileFail { source, target, .. } => write!(
+                f,
+                "compile {} with the flags of {}, expecting it to fail",
+                source.display(),
+                target
+            ),
+        }
+    }
+}
+
+/// Test run by `yambs test`, with paths resolved against the manifest directory.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub struct Test {
+    pub name: String,
+    pub kind: TestKind,
+    /// Targets built before the test is run, including the target run by the command.
+    pub depends_on: Vec<String>,
+    pub working_directory: std::path::PathBuf,
+    pub env: std::collections::BTreeMap<String, String>,
+    /// How long the command of the test may run.
+    pub timeout: Option<std::time::Duration>,
+    /// Times the test is run again after failing.
+    pub retries: u32,
+}
+
+impl Test {
+    pub fn new(
+        name: &str,
+        data: &types::RawTestData,
+        manifest_dir: &Path,
+    ) -> Result<Self, TestError> {
+        let kind = match data.expect {
+            types::TestExpectation::Pass => {
+                if data.source.is_some() {
+                    return Err(TestError::CompileFailField(name.to_string(), "a source"));
+                }
+                if data.target.is_some() {
+                    return Err(TestError::CompileFailField(name.to_string(), "a target"));
+                }
+                if data.diagnostic.is_some() {
+                    return Err(TestError::CompileFailField(
+                        name.to_string(),
+                        "a diagnostic",
+                    ));
+                }
+                TestKind::Run(Self::command(name, data, manifest_dir)?)
+            }
+            types::TestExpectation::CompileFail => {
+                if data.command.is_some() {
+                    return Err(TestError::CompileFailField(name.to_string(), "a command"));
+                }
+                match (&data.source, &data.target) {
+                    (Some(source), Some(target)) => TestKind::CompileFail {
+                        source: manifest_dir.join(source),
+                        target: target.clone(),
+                        diagnostic: data.diagnostic.clone(),
+                    },
+                    _ => return Err(TestError::IncompleteCompileFail(name.to_string())),
+                }
+            }
+        };
+        let mut depends_on = data.depends_on.clone();
+        let target = match kind {
+            TestKind::Run(ref command) => command.target(),
+            TestKind::CompileFail { ref target, .. } => Some(target.as_str()),
+        };
+        if let Some(target) = target {
+            if !depends_on.iter().any(|dependency| dependency == target) {
+                depends_on.push(target.to_string());
+            }
+        }
+        Ok(Self {
+            name: name.to_string(),
+            kind,
+            depends_on,
+            working_directory: data
+                .working_directory
+                .as_ref()
+                .map(|directory| manifest_dir.join(directory))
+                .unwrap_or_else(|| manifest_dir.to_path_buf()),
+            env: data.env.clone(),
+            timeout: data.timeout.map(std::time::Duration::from_secs),
+            retries: data.retries,
+        })
+    }
+
+    /// Rule of the top-level Makefile compiling the source of a compile-fail test.
+    pub fn compile_fail_rule(&self) -> String {
+        format!("compile_fail.{}", self.name)
+    }
+
+    fn command(
+        name: &str,
+        data: &types::RawTestData,
+        manifest_dir: &Path,
+    ) -> Result<Command, TestError> {
+        let command = match data.command {
+            None => return Err(TestError::EmptyCommand(name.to_string())),
+            Some(types::TestCommandData::Script(ref script)) => {
+                Command::Program(vec![manifest_dir.join(script).display().to_string()])
+            }
+            Some(types::TestCommandData::Command(types::CommandData::Program(ref command))) => {
+                Command::Program(command.clone())
+            }
+            Some(types::TestCommandData::Command(types::CommandData::Target(
+                ref target_command,
+            ))) => Command::Target {
+                target: target_command.target.clone(),
+                args: target_command.args.clone(),
+            },
+        };
+        if command.is_empty() {
+            return Err(TestError::EmptyCommand(name.to_string()));
+        }
+        Ok(command)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum InstallError {
+    #[error("Invalid mode \"{0}\". Modes are given in octal, such as \"0755\".")]
+    InvalidMode(String),
+    #[error("Install destination {0:?} has to be relative to the installation prefix")]
+    AbsoluteDestination(std::path::PathBuf),
+}
+
+/// Artifact installed by `yambs install`, with files resolved against the manifest directory.
+/// Destinations are relative to the installation prefix. Artifacts without a mode get the
+/// default mode of `install`.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub enum Install {
+    Target {
+        target: String,
+        destination: std::path::PathBuf,
+        mode: Option<u32>,
+    },
+    File {
+        file: std::path::PathBuf,
+        destination: std::path::PathBuf,
+        mode: Option<u32>,
+    },
+    Directory {
+        directory: std::path::PathBuf,
+        mode: Option<u32>,
+    },
+    Symlink {
+        symlink: std::path::PathBuf,
+        points_to: std::path::PathBuf,
+    },
+}
+
+impl Install {
+    pub fn new(data: &types::RawInstallData, manifest_dir: &Path) -> Result<Self, InstallError> {
+        let install = match data {
+            types::RawInstallData::Target {
+                target,
+                destination,
+                mode,
+            } => Self::Target {
+                target: target.clone(),
+                destination: relative_destination(destination)?,
+                mode: parse_mode(mode.as_deref())?,
+            },
+            types::RawInstallData::File {
+                file,
+                destination,
+                mode,
+            } => Self::File {
+                file: manifest_dir.join(file),
+                destination: relative_destination(destination)?,
+                mode: parse_mode(mode.as_deref())?,
+            },
+            types::RawInstallData::Directory { directory, mode } => Self::Directory {
+                directory: relative_destination(directory)?,
+                mode: parse_mode(mode.as_deref())?,
+            },
+            types::RawInstallData::Symlink { symlink, points_to } => Self::Symlink {
+                symlink: relative_destination(symlink)?,
+                points_to: points_to.clone(),
+            },
+        };
+        Ok(install)
+    }
+}
+
+fn relative_destination(destination: &Path) -> Result<std::path::PathBuf, InstallError> {
+    if destination.is_absolute() {
+        return Err(InstallError::AbsoluteDestination(destination.to_path_buf()));
+    }
+    Ok(destination.to_path_buf())
+}
+
+fn parse_mode(mode: Option<&str>) -> Result<Option<u32>, InstallError> {
+    mode.map(|mode| {
+        u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|parsed| *parsed <= 0o7777)
+            .ok_or_else(|| InstallError::InvalidMode(mode.to_string()))
+    })
+    .transpose()
+}
+
+/// Placeholder of the signing command replaced by the path of the artifact signed.
+pub const ARTIFACT_PLACEHOLDER: &str = "{artifact}";
+/// Placeholder of the signing command replaced by the path the signature is written to.
+pub const SIGNATURE_PLACEHOLDER: &str = "{signature}";
+
+#[derive(thiserror::Error, Debug)]
+pub enum SigningError {
+    #[error("The signing command has to name the artifact it signs by {ARTIFACT_PLACEHOLDER}")]
+    CommandWithoutArtifact,
+    #[error(
+        "The signing command writes to {SIGNATURE_PLACEHOLDER}, but no signature is given. Add \
+         signature = \"{ARTIFACT_PLACEHOLDER}.sig\" or similar"
+    )]
+    MissingSignature,
+    #[error(
+        "Signature \"{0}\" has to be named after the artifact by {ARTIFACT_PLACEHOLDER}, such as \
+         \"{ARTIFACT_PLACEHOLDER}.sig\""
+    )]
+    SignatureWithoutArtifact(String),
+    #[error("Target \"{0}\" to sign is not an executable or library of the manifest")]
+    UnknownTarget(String),
+    #[error("Target \"{0}\" to sign is built for the host, which is not signed")]
+    HostTarget(String),
+}
+
+/// Signing of the artifacts of the targets of a manifest, by a command run on each of them once
+/// it is linked.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub struct Signing {
+    pub command: Vec<String>,
+    pub signature: Option<String>,
+    pub targets: Vec<String>,
+}
+
+impl Signing {
+    pub fn new(
+        data: &types::RawSigningData,
+        targets: &[Target],
+        tests: &[Test],
+    ) -> Result<Self, SigningError> {
+        let uses = |placeholder: &str| data.command.iter().any(|word| word.contains(placeholder));
+        if !uses(ARTIFACT_PLACEHOLDER) {
+            return Err(SigningError::CommandWithoutArtifact);
+        }
+        match data.signature {
+            None if uses(SIGNATURE_PLACEHOLDER) => return Err(SigningError::MissingSignature),
+            Some(ref signature) if !signature.contains(ARTIFACT_PLACEHOLDER) => {
+                return Err(SigningError::SignatureWithoutArtifact(signature.clone()))
+            }
+            _ => {}
+        }
+        let signed_targets = match data.targets {
+            Some(ref names) => {
+                for name in names {
+                    let target = targets
+                        .iter()
+                        .find(|target| target_name(target) == name)
+                        .ok_or_else(|| SigningError::UnknownTarget(name.clone()))?;
+                    if target.build_for() == types::BuildFor::Host {
+                        return Err(SigningError::HostTarget(name.clone()));
+                    }
+                }
+                names.clone()
+            }
+            None => targets
+                .iter()
+                .filter(|target| target.build_for() == types::BuildFor::Target)
+                .filter(|target| match target {
+                    Target::Executable(executable) => {
+                        !executable.example
+                            && !tests.iter().any(|test| test.name == executable.name)
+                    }
+                    Target::Library(library) => library.lib_type == types::LibraryType::Dynamic,
+                })
+                .map(|target| target_name(target).to_string())
+                .collect(),
+        };
+        Ok(Self {
+            command: data.command.clone(),
+            signature: data.signature.clone(),
+            targets: signed_targets,
+        })
+    }
+
+    /// File the signature of `artifact` is written to, unless the command signs it in place.
+    pub fn signature_of(&self, artifact: &Path) -> Option<std::path::PathBuf> {
+        self.signature.as_ref().map(|signature| {
+            std::path::PathBuf::from(
+                signature.replace(ARTIFACT_PLACEHOLDER, &artifact.display().to_string()),
+            )
+        })
+    }
+
+    /// The command signing `artifact`, with the placeholders replaced.
+    pub fn command_for(&self, artifact: &Path) -> Vec<String> {
+        let signature = self
+            .signature_of(artifact)
+            .map(|signature| signature.display().to_string())
+            .unwrap_or_default();
+        self.command
+            .iter()
+            .map(|word| {
+                word.replace(ARTIFACT_PLACEHOLDER, &artifact.display().to_string())
+                    .replace(SIGNATURE_PLACEHOLDER, &signature)
+            })
+            .collect()
+    }
+}
+
+fn target_name(target: &Target) -> &str {
+    match target {
+        Target::Executable(executable) => &executable.name,
+        Target::Library(library) => &library.name,
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum DependencyError {
     #[error("Failed to canonicalize path \"{0}\"")]
     FailedToCanonicalizePath(std::path::PathBuf, #[source] std::io::Error),
+    #[error("Failed to fetch the archive of dependency \"{0}\"")]
+    Archive(String, #[source] Box<crate::archive::ArchiveError>),
+    #[error("Dependency \"{0}\" must specify one of path, url, include_directory, cmake_build_dir, pkg_config or pkg_config_search_dir")]
+    NotPkgConfigDependency(String),
+    #[error("Path \"{0}\" of the dependency does not exist{}", suggestions(.1))]
+    PathNotFound(std::path::PathBuf, Vec<std::path::PathBuf>),
+    #[error(
+        "Path \"{0}\" of the dependency has no {}{}",
+        crate::YAMBS_MANIFEST_NAME,
+        suggestions(.1)
+    )]
+    NoManifest(std::path::PathBuf, Vec<std::path::PathBuf>),
+}
+
+fn suggestions(directories: &[std::path::PathBuf]) -> String {
+    if directories.is_empty() {
+        return String::new();
+    }
+    format!(
+        ". Directories nearby with a {}: {}",
+        crate::YAMBS_MANIFEST_NAME,
+        directories
+            .iter()
+            .map(|directory| format!("\"{}\"", directory.display()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+/// Maximum number of directories suggested for a dependency path without a manifest.
+const MAX_SUGGESTIONS: usize = 3;
+
+// Directories with a manifest close to `path`, which is missing or has no manifest: those in it, and
+// those next to it or to its nearest existing ancestor. The directories with names most like that
+// of `path` come first.
+fn directories_with_manifest_near(path: &Path) -> Vec<std::path::PathBuf> {
+    let has_manifest = |directory: &Path| directory.join(crate::YAMBS_MANIFEST_NAME).is_file();
+    let subdirectories = |directory: &Path| -> Vec<std::path::PathBuf> {
+        std::fs::read_dir(directory)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|entry| entry.is_dir())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut candidates = Vec::new();
+    if path.is_dir() {
+        candidates.extend(subdirectories(path));
+    }
+    if let Some(ancestor) = path.ancestors().skip(1).find(|ancestor| ancestor.is_dir()) {
+        candidates.push(ancestor.to_path_buf());
+        candidates.extend(subdirectories(ancestor));
+    }
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut directories = candidates
+        .into_iter()
+        .filter(|candidate| candidate != path && has_manifest(candidate))
+        .filter_map(|candidate| candidate.canonicalize().ok())
+        .collect::<Vec<std::path::PathBuf>>();
+    directories.sort_by_key(|directory| {
+        let candidate_name = directory
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        (edit_distance(&name, &candidate_name), directory.clone())
+    });
+    directories.dedup();
+    directories.truncate(MAX_SUGGESTIONS);
+    directories
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut distances = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut previous = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let current = distances[j + 1];
+            distances[j + 1] = if a_char == *b_char {
+                previous
+            } else {
+                1 + previous.min(current).min(distances[j])
+            };
+            previous = current;
+        }
+    }
+    distances[b.len()]
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
@@ -83,6 +654,16 @@ impl Dependency {
                 );
                 dependency = Dependency::from_source(name, source_data, manifest_dir);
             }
+            types::DependencyData::Archive(ref archive_data) => {
+                log::debug!("Found dependency {} in archive {}", name, archive_data.url);
+                // The archive is downloaded as the targets depending on it are registered.
+                crate::archive::checksum_of(archive_data)
+                    .map_err(|e| DependencyError::Archive(name.to_string(), Box::new(e)))?;
+                dependency = Ok(Self {
+                    name: name.to_string(),
+                    data: data.clone(),
+                });
+            }
             types::DependencyData::HeaderOnly(ref header_only_data) => {
                 log::debug!(
                     "Found header only dependency {} with include directory \"{}\"",
@@ -91,6 +672,35 @@ impl Dependency {
                 );
                 dependency = Dependency::from_header_only(name, header_only_data, manifest_dir);
             }
+            types::DependencyData::CMake(ref cmake_data) => {
+                log::debug!(
+                    "Found CMake dependency {} in build directory {}",
+                    name,
+                    cmake_data.build_directory.display()
+                );
+                dependency = Ok(Self {
+                    name: name.to_string(),
+                    data: types::DependencyData::CMake(types::CMakeData {
+                        build_directory: manifest_dir.join(&cmake_data.build_directory),
+                        target: cmake_data.target.clone(),
+                        origin: cmake_data.origin,
+                    }),
+                });
+            }
+            types::DependencyData::YambsBuild(ref yambs_build_data) => {
+                log::debug!(
+                    "Found dependency {} built in yambs build directory {}",
+                    name,
+                    yambs_build_data.build_directory.display()
+                );
+                dependency = Ok(Self {
+                    name: name.to_string(),
+                    data: types::DependencyData::YambsBuild(types::YambsBuildData {
+                        build_directory: manifest_dir.join(&yambs_build_data.build_directory),
+                        origin: yambs_build_data.origin,
+                    }),
+                });
+            }
             types::DependencyData::PkgConfig(ref pkgconfig_data) => {
                 log::debug!("Found pkgconfig dependency {}", name);
                 dependency = Dependency::from_pkgconfig_data(name, pkgconfig_data, manifest_dir);
@@ -104,13 +714,27 @@ impl Dependency {
         source_data: &types::SourceData,
         manifest_dir: &std::path::Path,
     ) -> Result<Self, DependencyError> {
+        let path = manifest_dir.join(&source_data.path);
+        if !path.exists() {
+            return Err(DependencyError::PathNotFound(
+                source_data.path.clone(),
+                directories_with_manifest_near(&path),
+            ));
+        }
+        if !path.join(crate::YAMBS_MANIFEST_NAME).is_file() {
+            return Err(DependencyError::NoManifest(
+                source_data.path.clone(),
+                directories_with_manifest_near(&path),
+            ));
+        }
         let canonicalized_path = crate::canonicalize_source(manifest_dir, &source_data.path)
             .map_err(|err| {
                 DependencyError::FailedToCanonicalizePath(source_data.path.clone(), err)
             })?;
         let canonicalized_data = types::DependencyData::Source(types::SourceData {
             path: canonicalized_path,
-            origin: source_data.origin.clone(),
+            origin: source_data.origin,
+            link_options: source_data.link_options.clone(),
         });
         Ok(Self {
             name: name.to_string(),
@@ -132,8 +756,10 @@ impl Dependency {
                     )
                 },
             )?;
-        let canonicalized_data =
-            types::DependencyData::HeaderOnly(types::HeaderOnlyData { include_directory });
+        let canonicalized_data = types::DependencyData::HeaderOnly(types::HeaderOnlyData {
+            include_directory,
+            origin: header_only_data.origin,
+        });
         Ok(Self {
             name: name.to_string(),
             data: canonicalized_data,
@@ -145,14 +771,27 @@ impl Dependency {
         pkgconfig_data: &types::PkgConfigData,
         manifest_dir: &Path,
     ) -> Result<Self, DependencyError> {
-        let search_dir = crate::canonicalize_source(manifest_dir, &pkgconfig_data.search_dir)
-            .map_err(|err| {
-                DependencyError::FailedToCanonicalizePath(pkgconfig_data.search_dir.clone(), err)
-            })?;
+        if pkgconfig_data.package.is_none() && pkgconfig_data.search_dir.is_none() {
+            return Err(DependencyError::NotPkgConfigDependency(name.to_string()));
+        }
+        let search_dir = pkgconfig_data
+            .search_dir
+            .as_ref()
+            .map(|search_dir| {
+                crate::canonicalize_source(manifest_dir, search_dir).map_err(|err| {
+                    DependencyError::FailedToCanonicalizePath(search_dir.clone(), err)
+                })
+            })
+            .transpose()?;
 
         Ok(Self {
             name: name.to_string(),
-            data: types::DependencyData::PkgConfig(PkgConfigData { search_dir }),
+            data: types::DependencyData::PkgConfig(PkgConfigData {
+                package: pkgconfig_data.package.clone(),
+                search_dir,
+                components: pkgconfig_data.components.clone(),
+                origin: pkgconfig_data.origin,
+            }),
         })
     }
 }
@@ -0,0 +1,540 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::build_file_hashes::ContentHasher;
+use crate::cache::Cacher;
+use crate::environment::{Environment, ModifyMode};
+use crate::exec::LIBRARY_PATH_VARIABLE;
+use crate::generator::makefile::Make;
+use crate::targets::{Command, Test, TestKind};
+use crate::toolchain::OutputExtensions;
+
+/// Variable set to the directory executables are linked in, for tests to find what they test.
+pub const BUILD_DIRECTORY_VARIABLE: &str = "YAMBS_BUILD_DIRECTORY";
+
+pub const TEST_REPORT_FILE_NAME: &str = "test_report.json";
+
+// How often a test with a timeout is checked for having finished.
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum TestRunnerError {
+    #[error("Failed to run test \"{0}\"")]
+    FailedToRun(String, #[source] std::io::Error),
+    #[error("Failed to read {1} to hash test \"{0}\"")]
+    FailedToHash(String, PathBuf, #[source] std::io::Error),
+    #[error("Could not find make, which compile-fail tests are compiled with")]
+    CouldNotFindMake,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    pub duration: Duration,
+    /// Why the test failed, when its own output does not tell.
+    pub reason: Option<String>,
+    /// Times the test was run, which is more than once if it was retried after failing.
+    pub attempts: u32,
+}
+
+impl TestResult {
+    /// Whether the test passed only after being retried.
+    pub fn is_flaky(&self) -> bool {
+        self.passed && self.attempts > 1
+    }
+}
+
+/// Outcome of a test in the test report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestOutcome {
+    Passed,
+    /// Passed after failing at least once.
+    Flaky,
+    Failed,
+    /// Skipped with `--cache-results`, as it passed before.
+    Cached,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TestReportEntry {
+    pub name: String,
+    pub outcome: TestOutcome,
+    pub duration_ms: u64,
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Results of a run of `yambs test`, written to test_report.json in the build files directory.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TestReport {
+    pub tests: Vec<TestReportEntry>,
+}
+
+impl TestReport {
+    pub fn add_result(&mut self, result: &TestResult) {
+        let outcome = if result.is_flaky() {
+            TestOutcome::Flaky
+        } else if result.passed {
+            TestOutcome::Passed
+        } else {
+            TestOutcome::Failed
+        };
+        self.tests.push(TestReportEntry {
+            name: result.name.clone(),
+            outcome,
+            duration_ms: result.duration.as_millis() as u64,
+            attempts: result.attempts,
+            reason: result.reason.clone(),
+        });
+    }
+
+    pub fn add_cached(&mut self, name: &str) {
+        self.tests.push(TestReportEntry {
+            name: name.to_string(),
+            outcome: TestOutcome::Cached,
+            duration_ms: 0,
+            attempts: 0,
+            reason: None,
+        });
+    }
+
+    pub fn count(&self, outcome: TestOutcome) -> usize {
+        self.tests
+            .iter()
+            .filter(|entry| entry.outcome == outcome)
+            .count()
+    }
+}
+
+/// Hashes of the tests that passed, by name. A test with the same hash as when it passed is
+/// skipped with `--cache-results`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TestResultsCache {
+    pub passed: BTreeMap<String, String>,
+}
+
+impl Cacher for TestResultsCache {
+    const CACHE_FILE_NAME: &'static str = "test_results";
+}
+
+/// Tests of shard `index` when splitting `tests` into `count` shards. Tests are dealt to the
+/// shards in turn, so that each shard gets about as many tests.
+pub fn shard<T>(tests: Vec<T>, index: usize, count: usize) -> Vec<T> {
+    tests
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| i % count == index)
+        .map(|(_, test)| test)
+        .collect()
+}
+
+/// Runs tests, whether they are executables of the manifest or scripts, after the project is
+/// built. The directory executables are linked in is put first in PATH, so that scripts can run
+/// them by name.
+pub struct TestRunner {
+    executable_directory: PathBuf,
+    output_extensions: OutputExtensions,
+    environment: Environment,
+}
+
+impl TestRunner {
+    pub fn new(executable_directory: &Path) -> Self {
+        let environment = Environment::new()
+            .with(
+                "PATH",
+                executable_directory.as_os_str(),
+                ModifyMode::Prepend,
+            )
+            // Shared libraries with a soname are looked up by it, not by the path they were
+            // linked from.
+            .with(
+                LIBRARY_PATH_VARIABLE,
+                executable_directory.as_os_str(),
+                ModifyMode::Prepend,
+            )
+            .with(
+                BUILD_DIRECTORY_VARIABLE,
+                executable_directory.as_os_str(),
+                ModifyMode::Set,
+            );
+        Self {
+            executable_directory: executable_directory.to_path_buf(),
+            output_extensions: OutputExtensions::default(),
+            environment,
+        }
+    }
+
+    /// Runner of tests whose target executables are named with `output_extensions`.
+    pub fn with_output_extensions(mut self, output_extensions: OutputExtensions) -> Self {
+        self.output_extensions = output_extensions;
+        self
+    }
+
+    /// Runs `test` in its working directory, with its output going to the output of yambs. A
+    /// failing test is run again up to the number of retries it allows. The duration is that of
+    /// all attempts.
+    pub fn run(&self, test: &Test) -> Result<TestResult, TestRunnerError> {
+        let mut environment = self.environment.clone();
+        for (key, value) in &test.env {
+            environment.set(key, OsStr::new(value), ModifyMode::Set);
+        }
+        let start = Instant::now();
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            log::debug!(
+                "Running test \"{}\" (attempt {}): {}",
+                test.name,
+                attempts,
+                test.kind
+            );
+            let (passed, reason) = self.run_once(test, &environment)?;
+            if passed || attempts > test.retries {
+                return Ok(TestResult {
+                    name: test.name.clone(),
+                    passed,
+                    duration: start.elapsed(),
+                    reason,
+                    attempts,
+                });
+            }
+            log::info!(
+                "Test \"{}\" failed on attempt {} of {}",
+                test.name,
+                attempts,
+                test.retries + 1
+            );
+        }
+    }
+
+    fn run_once(
+        &self,
+        test: &Test,
+        environment: &Environment,
+    ) -> Result<(bool, Option<String>), TestRunnerError> {
+        match test.kind {
+            TestKind::Run(_) => {
+                let (program, args) = self.program_and_args(test);
+                let mut child = environment
+                    .command(&program)
+                    .args(args)
+                    .current_dir(&test.working_directory)
+                    .spawn()
+                    .map_err(|e| TestRunnerError::FailedToRun(test.name.clone(), e))?;
+                let status = match test.timeout {
+                    Some(timeout) => wait_with_timeout(&mut child, timeout),
+                    None => child.wait().map(Some),
+                }
+                .map_err(|e| TestRunnerError::FailedToRun(test.name.clone(), e))?;
+                Ok(match status {
+                    Some(status) => (status.success(), None),
+                    None => (
+                        false,
+                        Some(format!(
+                            "Timed out after {} s",
+                            test.timeout.unwrap_or_default().as_secs()
+                        )),
+                    ),
+                })
+            }
+            TestKind::CompileFail {
+                ref source,
+                ref diagnostic,
+                ..
+            } => self.compile_fail(test, environment, source, diagnostic.as_deref()),
+        }
+    }
+
+    // The rule compiling the source succeeds only if the compiler fails, so that a missing rule
+    // or a source that compiles is never taken for a pass. The diagnostics are given along with
+    // the reason the test failed.
+    fn compile_fail(
+        &self,
+        test: &Test,
+        environment: &Environment,
+        source: &Path,
+        diagnostic: Option<&str>,
+    ) -> Result<(bool, Option<String>), TestRunnerError> {
+        let make = Make::new(&[]).map_err(|_| TestRunnerError::CouldNotFindMake)?;
+        let output = environment
+            .command(make.executable())
+            .arg("-s")
+            .arg(test.compile_fail_rule())
+            .current_dir(&self.executable_directory)
+            .output()
+            .map_err(|e| TestRunnerError::FailedToRun(test.name.clone(), e))?;
+        let diagnostics = String::from_utf8_lossy(&output.stderr);
+        let reason = if !output.status.success() {
+            Some(format!(
+                "{} compiled, but was expected to fail",
+                source.display()
+            ))
+        } else {
+            match diagnostic {
+                Some(diagnostic) if !diagnostics.contains(diagnostic) => Some(format!(
+                    "{} failed to compile, but without a diagnostic containing \"{}\"",
+                    source.display(),
+                    diagnostic
+                )),
+                _ => None,
+            }
+        };
+        Ok((
+            reason.is_none(),
+            reason.map(|reason| format!("{}\n{}", reason, diagnostics.trim_end())),
+        ))
+    }
+
+    /// Hash of what the result of `test` depends on: its command, working directory and
+    /// environment, and the content of the program it runs along with `inputs`, such as the
+    /// outputs of the targets it depends on. Tests running a program found through PATH can not
+    /// be hashed, as what they run is not known, and neither can compile-fail tests, as the
+    /// headers they include are not known.
+    pub fn hash(&self, test: &Test, inputs: &[PathBuf]) -> Result<Option<String>, TestRunnerError> {
+        if let TestKind::CompileFail { .. } = test.kind {
+            return Ok(None);
+        }
+        let (program, args) = self.program_and_args(test);
+        if !program.is_absolute() {
+            return Ok(None);
+        }
+        let mut hasher = ContentHasher::new();
+        hasher.add(args.len().to_string());
+        for arg in args {
+            hasher.add(arg);
+        }
+        hasher.add_path(&test.working_directory);
+        for (key, value) in &test.env {
+            hasher.add(key).add(value);
+        }
+        hasher.add_path(&self.executable_directory);
+        for file in std::iter::once(&program).chain(inputs) {
+            let content = std::fs::read(file)
+                .map_err(|e| TestRunnerError::FailedToHash(test.name.clone(), file.clone(), e))?;
+            hasher.add_path(file).add(content);
+        }
+        Ok(Some(format!("{:016x}", hasher.finish())))
+    }
+
+    fn program_and_args<'a>(&self, test: &'a Test) -> (PathBuf, &'a [String]) {
+        match test.kind {
+            TestKind::Run(Command::Program(ref command)) => {
+                (PathBuf::from(&command[0]), &command[1..])
+            }
+            TestKind::Run(Command::Target {
+                ref target,
+                ref args,
+            }) => (
+                self.executable_directory
+                    .join(self.output_extensions.executable_file_name(target)),
+                &args[..],
+            ),
+            TestKind::CompileFail { ref source, .. } => (source.clone(), &[]),
+        }
+    }
+}
+
+// Waits for `child` to exit within `timeout`, and kills it otherwise. Processes started by the
+// child are not killed along with it.
+fn wait_with_timeout(
+    child: &mut std::process::Child,
+    timeout: Duration,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(TIMEOUT_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_test_runs_in_working_directory_with_environment() {
+        let tempdir = tempdir::TempDir::new("test_runner").unwrap();
+        let working_directory = tempdir.path().join("work");
+        std::fs::create_dir(&working_directory).unwrap();
+        std::fs::write(working_directory.join("expected"), "").unwrap();
+
+        let test = |expected_value: &str| Test {
+            name: "integration".to_string(),
+            kind: TestKind::Run(Command::Program(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "test -f expected && test \"$VALUE\" = {} && test \"${}\" = {}",
+                    expected_value,
+                    BUILD_DIRECTORY_VARIABLE,
+                    tempdir.path().display()
+                ),
+            ])),
+            depends_on: Vec::new(),
+            working_directory: working_directory.clone(),
+            env: std::collections::BTreeMap::from([("VALUE".to_string(), "1".to_string())]),
+            timeout: None,
+            retries: 0,
+        };
+
+        let runner = TestRunner::new(tempdir.path());
+        assert!(runner.run(&test("1")).unwrap().passed);
+        assert!(!runner.run(&test("2")).unwrap().passed);
+    }
+
+    #[test]
+    fn hash_changes_with_test_binary() {
+        let tempdir = tempdir::TempDir::new("test_runner").unwrap();
+        std::fs::write(tempdir.path().join("unit_tests"), "first").unwrap();
+        let test = Test {
+            name: "unit".to_string(),
+            kind: TestKind::Run(Command::Target {
+                target: "unit_tests".to_string(),
+                args: Vec::new(),
+            }),
+            depends_on: vec!["unit_tests".to_string()],
+            working_directory: tempdir.path().to_path_buf(),
+            env: std::collections::BTreeMap::new(),
+            timeout: None,
+            retries: 0,
+        };
+
+        let runner = TestRunner::new(tempdir.path());
+        let first = runner.hash(&test, &[]).unwrap().unwrap();
+        assert_eq!(runner.hash(&test, &[]).unwrap().unwrap(), first);
+        std::fs::write(tempdir.path().join("unit_tests"), "second").unwrap();
+        assert_ne!(runner.hash(&test, &[]).unwrap().unwrap(), first);
+    }
+
+    #[test]
+    fn target_executables_are_named_with_the_executable_extension() {
+        let tempdir = tempdir::TempDir::new("test_runner").unwrap();
+        std::fs::write(tempdir.path().join("unit_tests.exe"), "").unwrap();
+        let test = Test {
+            name: "unit".to_string(),
+            kind: TestKind::Run(Command::Target {
+                target: "unit_tests".to_string(),
+                args: Vec::new(),
+            }),
+            depends_on: vec!["unit_tests".to_string()],
+            working_directory: tempdir.path().to_path_buf(),
+            env: std::collections::BTreeMap::new(),
+            timeout: None,
+            retries: 0,
+        };
+
+        let runner = TestRunner::new(tempdir.path()).with_output_extensions(OutputExtensions {
+            executable: Some("exe".to_string()),
+            ..OutputExtensions::default()
+        });
+        assert_eq!(
+            runner.program_and_args(&test).0,
+            tempdir.path().join("unit_tests.exe")
+        );
+        assert!(runner.hash(&test, &[]).unwrap().is_some());
+    }
+
+    #[test]
+    fn compile_fail_test_passes_on_failure_with_expected_diagnostic() {
+        let tempdir = tempdir::TempDir::new("test_runner").unwrap();
+        // Stands in for the rule generated for the test, with a compiler that always fails.
+        std::fs::write(
+            tempdir.path().join("Makefile"),
+            "compile_fail.negative:\n\t! sh -c 'echo \"error: size must be positive\" >&2; exit 1'\n",
+        )
+        .unwrap();
+        let test = |diagnostic: &str| Test {
+            name: "negative".to_string(),
+            kind: TestKind::CompileFail {
+                source: tempdir.path().join("negative.cpp"),
+                target: "checked".to_string(),
+                diagnostic: Some(diagnostic.to_string()),
+            },
+            depends_on: vec!["checked".to_string()],
+            working_directory: tempdir.path().to_path_buf(),
+            env: std::collections::BTreeMap::new(),
+            timeout: None,
+            retries: 0,
+        };
+
+        let runner = TestRunner::new(tempdir.path());
+        assert!(runner.run(&test("size must be positive")).unwrap().passed);
+        let result = runner.run(&test("size must be even")).unwrap();
+        assert!(!result.passed);
+        assert!(result
+            .reason
+            .unwrap()
+            .contains("error: size must be positive"));
+    }
+
+    #[test]
+    fn failing_test_is_retried_and_reported_flaky_when_it_passes() {
+        let tempdir = tempdir::TempDir::new("test_runner").unwrap();
+        // Fails on the first attempt only, leaving a marker behind for the next one.
+        let test = Test {
+            name: "flaky".to_string(),
+            kind: TestKind::Run(Command::Program(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "test -f marker || { touch marker; exit 1; }".to_string(),
+            ])),
+            depends_on: Vec::new(),
+            working_directory: tempdir.path().to_path_buf(),
+            env: std::collections::BTreeMap::new(),
+            timeout: None,
+            retries: 2,
+        };
+
+        let runner = TestRunner::new(tempdir.path());
+        let result = runner.run(&test).unwrap();
+        assert!(result.is_flaky());
+        assert_eq!(result.attempts, 2);
+
+        let mut report = TestReport::default();
+        report.add_result(&result);
+        assert_eq!(report.tests[0].outcome, TestOutcome::Flaky);
+    }
+
+    #[test]
+    fn test_running_past_its_timeout_fails() {
+        let tempdir = tempdir::TempDir::new("test_runner").unwrap();
+        let test = Test {
+            name: "slow".to_string(),
+            kind: TestKind::Run(Command::Program(vec![
+                "sleep".to_string(),
+                "10".to_string(),
+            ])),
+            depends_on: Vec::new(),
+            working_directory: tempdir.path().to_path_buf(),
+            env: std::collections::BTreeMap::new(),
+            timeout: Some(Duration::from_millis(50)),
+            retries: 1,
+        };
+
+        let result = TestRunner::new(tempdir.path()).run(&test).unwrap();
+        assert!(!result.passed);
+        assert_eq!(result.attempts, 2);
+        assert!(result.duration < Duration::from_secs(5));
+        assert!(result.reason.unwrap().starts_with("Timed out"));
+    }
+
+    #[test]
+    fn shards_split_tests_between_them() {
+        let tests = vec!["a", "b", "c", "d", "e"];
+        assert_eq!(shard(tests.clone(), 0, 2), vec!["a", "c", "e"]);
+        assert_eq!(shard(tests.clone(), 1, 2), vec!["b", "d"]);
+        assert_eq!(shard(tests, 2, 3), vec!["c"]);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::cache::Cacher;
+use crate::errors::FsError;
+use crate::utility;
+
+/// Log, relative to the build files directory, that compile and link durations are appended to
+/// by a build. Each line consists of the file made followed by the duration in milliseconds.
+pub const TIMINGS_LOG_FILE_NAME: &str = "timings.log";
+
+/// Durations of the steps of a build, measured between the markers the steps print as they start
+/// and finish. The shell of the recipes has no portable clock finer than seconds, so the steps are
+/// timed by yambs instead.
+#[derive(Debug)]
+pub struct StepTimer {
+    directory: PathBuf,
+    started: HashMap<PathBuf, Instant>,
+    durations: Vec<(PathBuf, u64)>,
+}
+
+impl StepTimer {
+    /// Times the steps run in the build files directory `directory`, which the outputs of the
+    /// steps are relative to.
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            directory: directory.to_path_buf(),
+            started: HashMap::new(),
+            durations: Vec::new(),
+        }
+    }
+
+    pub fn started(&mut self, output: &Path) {
+        self.started
+            .insert(self.directory.join(output), Instant::now());
+    }
+
+    /// Records the duration of the step making `output`. Steps that are not started are skipped.
+    pub fn finished(&mut self, output: &Path) {
+        let output = self.directory.join(output);
+        if let Some(started) = self.started.remove(&output) {
+            self.durations
+                .push((output, started.elapsed().as_millis() as u64));
+        }
+    }
+
+    /// Appends the durations of the finished steps to the timings log of the build files
+    /// directory.
+    pub fn append_to_log(&self) -> Result<(), FsError> {
+        if self.durations.is_empty() {
+            return Ok(());
+        }
+        let log_file = self.directory.join(TIMINGS_LOG_FILE_NAME);
+        let mut log = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_file)
+            .map_err(|e| FsError::CreateFile(log_file, e))?;
+        let entries = self
+            .durations
+            .iter()
+            .map(|(output, duration)| format!("{} {}\n", output.display(), duration))
+            .collect::<String>();
+        log.write_all(entries.as_bytes())
+            .map_err(FsError::WriteToFile)
+    }
+}
+
+/// Compile duration of each object file and link duration of each target, as measured in the most
+/// recent build it was made in.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CompileTimings {
+    #[serde(default)]
+    pub durations: BTreeMap<PathBuf, u64>,
+}
+
+impl Cacher for CompileTimings {
+    const CACHE_FILE_NAME: &'static str = "compile_timings";
+}
+
+impl CompileTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile duration of an object file in milliseconds.
+    pub fn duration(&self, object: &Path) -> Option<u64> {
+        self.durations.get(object).copied()
+    }
+
+    /// Merges the durations in a timings log, replacing older durations of the same object files.
+    /// Malformed lines are skipped.
+    pub fn merge_log(&mut self, log: &str) {
+        for line in log.lines() {
+            let parsed = line.rsplit_once(' ').and_then(|(object, duration)| {
+                Some((object, duration.trim().parse::<u64>().ok()?))
+            });
+            match parsed {
+                Some((object, duration)) => {
+                    self.durations.insert(PathBuf::from(object), duration);
+                }
+                None => log::debug!("Skipping malformed timing entry \"{}\"", line),
+            }
+        }
+    }
+
+    /// Merges the timings log in `buildfile_directory`, if any, and removes it.
+    pub fn merge_log_file(&mut self, buildfile_directory: &Path) -> Result<(), FsError> {
+        let log_file = buildfile_directory.join(TIMINGS_LOG_FILE_NAME);
+        if !log_file.is_file() {
+            return Ok(());
+        }
+        self.merge_log(&utility::read_file(&log_file)?);
+        std::fs::remove_file(&log_file).map_err(|e| FsError::RemoveFile(log_file, e))
+    }
+
+    /// Orders object files so that the slowest ones to compile come first, which lets make start
+    /// them early. Objects without a recorded duration are treated as the slowest, as nothing is
+    /// known about them yet. The order is otherwise kept.
+    pub fn sort_by_duration(&self, objects: &mut [PathBuf]) {
+        objects.sort_by_key(|object| std::cmp::Reverse(self.duration(object).unwrap_or(u64::MAX)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn merge_log_replaces_older_durations() {
+        let mut timings = CompileTimings::new();
+        timings.merge_log("/build/a.o 100\n/build/b.o 20\n");
+        timings.merge_log("/build/a.o 50\nnot valid\n");
+        assert_eq!(timings.duration(Path::new("/build/a.o")), Some(50));
+        assert_eq!(timings.duration(Path::new("/build/b.o")), Some(20));
+        assert_eq!(timings.durations.len(), 2);
+    }
+
+    #[test]
+    fn step_timer_logs_finished_steps_by_their_absolute_outputs() {
+        let tempdir = tempdir::TempDir::new("timings").unwrap();
+        let directory = tempdir.path();
+        let mut timer = StepTimer::new(directory);
+        timer.started(Path::new("app"));
+        timer.started(&directory.join("deps/app.dir/main.o"));
+        timer.finished(&directory.join("deps/app.dir/main.o"));
+        timer.finished(Path::new("app"));
+        timer.finished(Path::new("never_started.o"));
+        timer.append_to_log().unwrap();
+
+        let mut timings = CompileTimings::new();
+        timings.merge_log_file(directory).unwrap();
+        assert!(timings.duration(&directory.join("app")).is_some());
+        assert!(timings
+            .duration(&directory.join("deps/app.dir/main.o"))
+            .is_some());
+        assert_eq!(timings.durations.len(), 2);
+    }
+
+    #[test]
+    fn sort_by_duration_puts_slowest_and_unknown_first() {
+        let mut timings = CompileTimings::new();
+        timings.merge_log("/build/fast.o 10\n/build/slow.o 1000\n/build/medium.o 100\n");
+        let mut objects = vec![
+            PathBuf::from("/build/fast.o"),
+            PathBuf::from("/build/medium.o"),
+            PathBuf::from("/build/new.o"),
+            PathBuf::from("/build/slow.o"),
+        ];
+        timings.sort_by_duration(&mut objects);
+        assert_eq!(
+            objects,
+            vec![
+                PathBuf::from("/build/new.o"),
+                PathBuf::from("/build/slow.o"),
+                PathBuf::from("/build/medium.o"),
+                PathBuf::from("/build/fast.o"),
+            ]
+        );
+    }
+}
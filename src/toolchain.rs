@@ -1,16 +1,25 @@
+use std::cell::RefCell;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::build_target::pkg_config::PkgConfig;
-use crate::compiler::{CCCompiler, CXXCompiler, CompilerError, Linker, StdLibCC, StdLibCXX};
+use crate::build_target::{LibraryType, PrintableLibrary};
+use crate::cache::Cacher;
+use crate::compiler::{CCCompiler, CXXCompiler, CompilerError, Linker, StdLibCC, StdLibCXX, Type};
+use crate::parser::types::BuildFor;
+use crate::utility;
 use crate::{find_program, FindProgramOptions};
 
 pub const TOOLCHAIN_FILE_NAME: &str = "toolchain.toml";
 
+/// Environment variables the toolchain is taken from when there is no toolchain file.
+pub const TOOLCHAIN_ENVIRONMENT_VARIABLES: &[&str] = &["CXX", "CC", "AR"];
+
 #[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
 pub struct Archiver {
     pub path: PathBuf,
@@ -26,6 +35,11 @@ pub enum ArchiverError {
 
 impl Archiver {
     pub fn new() -> Result<Self, ArchiverError> {
+        Self::find(&[])
+    }
+
+    /// Archiver from $AR, or 'ar' found in `search_paths` or PATH.
+    pub fn find(search_paths: &[PathBuf]) -> Result<Self, ArchiverError> {
         let archiver_exe = {
             if let Some(archiver_from_env) = Self::try_from_environment_variable() {
                 log::debug!("Found archiver in $AR. Using this.");
@@ -33,7 +47,9 @@ impl Archiver {
             } else {
                 log::debug!("Did not find archiver in $AR. Will try to find 'ar' in common installation places.");
                 let mut search_options = FindProgramOptions::new();
-                search_options.with_path_env();
+                search_options
+                    .with_path_env()
+                    .search_directories_first(search_paths);
                 if let Some(archiver) = find_program(Path::new("ar"), search_options) {
                     Ok(archiver)
                 } else {
@@ -138,6 +154,18 @@ struct Toolchain {
     #[serde(rename = "CC")]
     pub cc: ToolchainCCData,
     pub common: CommonToolchainData,
+    /// Toolchain for targets built for the host when cross compiling.
+    pub host: Option<HostToolchain>,
+}
+
+#[derive(PartialEq, Eq, Debug, Deserialize)]
+struct HostToolchain {
+    #[serde(rename = "CXX")]
+    pub cxx: ToolchainCXXData,
+    #[serde(rename = "CC")]
+    pub cc: ToolchainCCData,
+    #[serde(default)]
+    pub common: CommonToolchainData,
 }
 
 impl Toolchain {
@@ -162,39 +190,460 @@ impl Toolchain {
     }
 
     fn to_toolchain(&self) -> Result<NormalizedToolchain, ToolchainError> {
-        let archiver = {
-            if let Some(ref archiver) = self.common.archiver {
-                log::debug!("Using archiver found from toolchain file");
-                Archiver::from_path(archiver)
-            } else {
-                Archiver::new()
-            }
+        let mut toolchain = normalize(&self.cxx, &self.cc, &self.common)?;
+        if let Some(ref host) = self.host {
+            log::debug!("Using host toolchain found from toolchain file");
+            toolchain.host = Some(Rc::new(RefCell::new(normalize(
+                &host.cxx,
+                &host.cc,
+                &host.common,
+            )?)));
+        }
+        // A compiler such as aarch64-linux-gnu-g++ cross compiles without any of the keys of a
+        // cross toolchain file, which is told by the machine it builds for.
+        let triple = self
+            .common
+            .target_triple
+            .clone()
+            .or_else(|| target_triple(&toolchain.cxx.compiler.compiler_exe));
+        if self.host.is_some()
+            || self.common.sysroot.is_some()
+            || self.common.target_triple.is_some()
+            || triple
+                .as_deref()
+                .is_some_and(|triple| !is_host_target(triple))
+        {
+            toolchain.pkg_config = cross_pkg_config(
+                &toolchain.cxx.compiler.compiler_exe,
+                triple,
+                &self.common,
+                toolchain.pkg_config.take(),
+            );
         }
-        .map_err(ToolchainError::Archiver)?;
+        Ok(toolchain)
+    }
+}
 
-        let pkg_config = {
-            if let Some(ref pkg_config) = self.common.pkg_config {
-                log::debug!("Using pkg_config found from toolchain file");
-                Some(PkgConfig::from_path(pkg_config))
-            } else {
-                PkgConfig::new().ok()
-            }
-        };
+// pkg-config of a cross toolchain, which must not find the packages of the host. A sysroot given
+// by the toolchain file is where the packages are, even if there is a pkg-config for the target.
+fn cross_pkg_config(
+    compiler: &Path,
+    triple: Option<String>,
+    common: &CommonToolchainData,
+    pkg_config: Option<PkgConfig>,
+) -> Option<PkgConfig> {
+    let Some(triple) = triple else {
+        return pkg_config;
+    };
+    if common.pkg_config.is_none() && common.sysroot.is_none() {
+        let sysroot = compiler_sysroot(compiler);
+        return PkgConfig::find_cross(&common.search_paths, &triple, sysroot.as_deref()).ok();
+    }
+    let mut pkg_config = pkg_config?;
+    pkg_config.limit_to_target(&triple, common.sysroot.as_deref());
+    Some(pkg_config)
+}
 
-        Ok(NormalizedToolchain {
-            cxx: ToolchainCXX::from_toolchain_cxx_data(&self.cxx)?,
-            cc: ToolchainCC::from_toolchain_cc_data(&self.cc)?,
-            archiver,
-            pkg_config,
-        })
+/// Machine `compiler` makes binaries for, such as `aarch64-linux-gnu`.
+pub fn target_triple(compiler: &Path) -> Option<String> {
+    match utility::shell::execute_get_stdout(compiler, ["-dumpmachine"]) {
+        Ok(triple) if !triple.trim().is_empty() => Some(triple.trim().to_string()),
+        Ok(_) => None,
+        Err(e) => {
+            log::debug!("Could not get the target of {}: {}", compiler.display(), e);
+            None
+        }
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Deserialize)]
+// Sysroot `compiler` was configured with. Native compilers have none.
+fn compiler_sysroot(compiler: &Path) -> Option<PathBuf> {
+    let sysroot = utility::shell::execute_get_stdout(compiler, ["-print-sysroot"]).ok()?;
+    let sysroot = PathBuf::from(sysroot.trim());
+    (!sysroot.as_os_str().is_empty() && sysroot.is_dir()).then_some(sysroot)
+}
+
+fn normalize(
+    cxx: &ToolchainCXXData,
+    cc: &ToolchainCCData,
+    common: &CommonToolchainData,
+) -> Result<NormalizedToolchain, ToolchainError> {
+    let archiver = {
+        if let Some(ref archiver) = common.archiver {
+            log::debug!("Using archiver found from toolchain file");
+            Archiver::from_path(archiver)
+        } else {
+            Archiver::find(&common.search_paths)
+        }
+    }
+    .map_err(ToolchainError::Archiver)?;
+
+    let pkg_config = {
+        if let Some(ref pkg_config) = common.pkg_config {
+            log::debug!("Using pkg_config found from toolchain file");
+            Some(PkgConfig::from_path(pkg_config))
+        } else {
+            PkgConfig::find(&common.search_paths).ok()
+        }
+    };
+
+    let objcopy = match common.objcopy {
+        Some(ref objcopy) => {
+            log::debug!("Using objcopy found from toolchain file");
+            Some(objcopy.clone())
+        }
+        None => find_objcopy(&common.search_paths),
+    };
+
+    let cxx = ToolchainCXX::from_toolchain_cxx_data(cxx)?;
+    let dynamic_library = match common.target_triple {
+        Some(ref triple) => DynamicLibraryFormat::from_target_triple(triple),
+        None => DynamicLibraryFormat::from_compiler(&cxx.compiler.compiler_exe),
+    };
+    Ok(NormalizedToolchain {
+        cross_target: CrossTarget::new(common, &cxx.compiler)?,
+        cxx,
+        cc: ToolchainCC::from_toolchain_cc_data(cc)?,
+        archiver,
+        pkg_config,
+        apple_platform: ApplePlatform::from_common_toolchain_data(common)?,
+        output_extensions: OutputExtensions::from_common_toolchain_data(common, dynamic_library),
+        objcopy,
+        host: None,
+    })
+}
+
+fn find_objcopy(search_paths: &[PathBuf]) -> Option<PathBuf> {
+    let mut search_options = FindProgramOptions::new();
+    search_options
+        .with_path_env()
+        .search_directories_first(search_paths);
+    find_program(Path::new("objcopy"), search_options)
+}
+
+#[derive(PartialEq, Eq, Debug, Default, Deserialize)]
 struct CommonToolchainData {
     pub archiver: Option<PathBuf>,
     #[serde(rename = "pkg-config")]
     pub pkg_config: Option<PathBuf>,
+    /// SDK of an Apple platform, such as "iphoneos" or "macosx", located with xcrun.
+    pub sdk: Option<String>,
+    pub macos_deployment_target: Option<String>,
+    pub ios_deployment_target: Option<String>,
+    pub object_extension: Option<String>,
+    pub executable_extension: Option<String>,
+    /// objcopy converting executables to the formats of their post-link artifacts.
+    pub objcopy: Option<PathBuf>,
+    /// Directories searched before PATH for the tools not given by the toolchain file.
+    #[serde(default)]
+    pub search_paths: Vec<PathBuf>,
+    /// Root of the headers and libraries of the target when cross compiling. The compilers are
+    /// given it with --sysroot, and pkg-config looks packages up in it. Defaults to the sysroot of
+    /// the compiler.
+    pub sysroot: Option<PathBuf>,
+    /// Machine the compilers build for, such as "aarch64-linux-gnu". Clang is given it with
+    /// --target, while GCC has to be a compiler configured for it. Defaults to the machine the
+    /// compiler builds for.
+    pub target_triple: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SearchPathsData {
+    #[serde(default)]
+    common: CommonToolchainData,
+}
+
+/// Search paths of the target toolchain in the toolchain file with `content`.
+pub fn search_paths(content: &str) -> Result<Vec<PathBuf>, toml::de::Error> {
+    toml::from_str::<SearchPathsData>(content).map(|data| data.common.search_paths)
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetTripleData {
+    #[serde(rename = "CXX")]
+    cxx: Option<ToolchainCXXData>,
+    #[serde(default)]
+    common: CommonToolchainData,
+}
+
+/// Machine the toolchain file with `content` builds for, as given by its `target_triple` or as
+/// told by its compiler. Read before the manifests are parsed, without evaluating the toolchain.
+pub fn target_triple_of_file(content: &str) -> Option<String> {
+    let TargetTripleData { cxx, common } = toml::from_str(content).ok()?;
+    common
+        .target_triple
+        .or_else(|| cxx.and_then(|cxx| target_triple(&cxx.compiler)))
+}
+
+/// How a toolchain makes dynamic libraries, and what dependents of them link.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub enum DynamicLibraryFormat {
+    /// Shared objects, such as `libfoo.so`, which dependents link directly.
+    #[default]
+    SharedObject,
+    /// DLLs of MinGW toolchains, such as `foo.dll`, which dependents link through the import
+    /// library `libfoo.dll.a`.
+    MinGwDll,
+    /// DLLs of toolchains targeting the MSVC ABI, such as `foo.dll`, which dependents link through
+    /// the import library `foo.lib`.
+    MsvcDll,
+}
+
+impl DynamicLibraryFormat {
+    /// Format of the dynamic libraries of a target triple, such as `x86_64-w64-mingw32`.
+    pub fn from_target_triple(triple: &str) -> Self {
+        if triple.contains("msvc") {
+            Self::MsvcDll
+        } else if ["windows", "mingw", "cygwin"]
+            .iter()
+            .any(|system| triple.contains(system))
+        {
+            Self::MinGwDll
+        } else {
+            Self::SharedObject
+        }
+    }
+
+    fn from_compiler(compiler: &Path) -> Self {
+        match target_triple(compiler) {
+            Some(triple) => Self::from_target_triple(&triple),
+            None => {
+                log::debug!("Assuming {} makes shared objects", compiler.display());
+                Self::SharedObject
+            }
+        }
+    }
+
+    pub fn is_dll(&self) -> bool {
+        *self != Self::SharedObject
+    }
+}
+
+/// Extensions of the files compiled and linked by a toolchain. Some embedded toolchains expect
+/// objects such as `main.obj` and executables such as `firmware.elf`, and toolchains for Windows
+/// make executables such as `app.exe` and DLLs.
+#[derive(PartialEq, Eq, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OutputExtensions {
+    pub object: String,
+    pub executable: Option<String>,
+    pub dynamic_library: DynamicLibraryFormat,
+}
+
+impl Default for OutputExtensions {
+    fn default() -> Self {
+        Self {
+            object: "o".to_string(),
+            executable: None,
+            dynamic_library: DynamicLibraryFormat::default(),
+        }
+    }
+}
+
+impl OutputExtensions {
+    fn from_common_toolchain_data(
+        common: &CommonToolchainData,
+        dynamic_library: DynamicLibraryFormat,
+    ) -> Self {
+        let default = Self::default();
+        let default_executable = dynamic_library.is_dll().then(|| "exe".to_string());
+        Self {
+            object: common.object_extension.clone().unwrap_or(default.object),
+            executable: common.executable_extension.clone().or(default_executable),
+            dynamic_library,
+        }
+    }
+
+    /// File name of the executable `name`.
+    pub fn executable_file_name(&self, name: &str) -> String {
+        match self.executable {
+            Some(ref extension) => format!("{}.{}", name, extension),
+            None => name.to_string(),
+        }
+    }
+
+    /// File name of the library `library`.
+    pub fn library_file_name(&self, library: &PrintableLibrary) -> String {
+        match library.ty {
+            LibraryType::Dynamic if self.dynamic_library.is_dll() => {
+                format!("{}.dll", library.name)
+            }
+            _ => library.to_string(),
+        }
+    }
+
+    /// File name of the import library made next to `library`, if it is a DLL.
+    pub fn import_library_file_name(&self, library: &PrintableLibrary) -> Option<String> {
+        match (&library.ty, self.dynamic_library) {
+            (LibraryType::Dynamic, DynamicLibraryFormat::MinGwDll) => {
+                Some(format!("lib{}.dll.a", library.name))
+            }
+            (LibraryType::Dynamic, DynamicLibraryFormat::MsvcDll) => {
+                Some(format!("{}.lib", library.name))
+            }
+            _ => None,
+        }
+    }
+
+    /// File name of what dependents of `library` link, which is the import library of a DLL.
+    pub fn link_file_name(&self, library: &PrintableLibrary) -> String {
+        self.import_library_file_name(library)
+            .unwrap_or_else(|| self.library_file_name(library))
+    }
+}
+
+/// SDK and minimum OS versions of Apple platforms, turned into the flags that otherwise would have
+/// to be written by hand for every target.
+#[derive(PartialEq, Eq, Debug, Default, Clone)]
+pub struct ApplePlatform {
+    pub sdk: Option<String>,
+    pub sdk_path: Option<PathBuf>,
+    pub macos_deployment_target: Option<String>,
+    pub ios_deployment_target: Option<String>,
+}
+
+impl ApplePlatform {
+    fn from_common_toolchain_data(common: &CommonToolchainData) -> Result<Self, ToolchainError> {
+        let sdk_path = match common.sdk {
+            Some(ref sdk) => Some(Self::locate_sdk(sdk)?),
+            None => None,
+        };
+        Ok(Self {
+            sdk: common.sdk.clone(),
+            sdk_path,
+            macos_deployment_target: common.macos_deployment_target.clone(),
+            ios_deployment_target: common.ios_deployment_target.clone(),
+        })
+    }
+
+    fn locate_sdk(sdk: &str) -> Result<PathBuf, ToolchainError> {
+        let sdk_path = utility::shell::execute_get_stdout(
+            Path::new("xcrun"),
+            ["--sdk", sdk, "--show-sdk-path"],
+        )
+        .map_err(|e| ToolchainError::FailedToLocateSdk(sdk.to_string(), e))?;
+        let sdk_path = sdk_path.trim();
+        if sdk_path.is_empty() {
+            return Err(ToolchainError::SdkNotFound(sdk.to_string()));
+        }
+        log::debug!("Using SDK {} located at {}", sdk, sdk_path);
+        Ok(PathBuf::from(sdk_path))
+    }
+
+    /// Flags for both compiling and linking.
+    pub fn flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if let Some(ref sdk_path) = self.sdk_path {
+            flags.push(format!("-isysroot {}", sdk_path.display()));
+        }
+        if let Some(ref version) = self.macos_deployment_target {
+            flags.push(format!("-mmacosx-version-min={}", version));
+        }
+        if let Some(ref version) = self.ios_deployment_target {
+            let is_simulator = self
+                .sdk
+                .as_ref()
+                .is_some_and(|sdk| sdk.contains("simulator"));
+            if is_simulator {
+                flags.push(format!("-mios-simulator-version-min={}", version));
+            } else {
+                flags.push(format!("-miphoneos-version-min={}", version));
+            }
+        }
+        flags
+    }
+}
+
+/// Machine and sysroot a cross toolchain builds for, turned into the flags of the compilers and
+/// linkers.
+#[derive(PartialEq, Eq, Debug, Default, Clone)]
+pub struct CrossTarget {
+    pub triple: Option<String>,
+    pub sysroot: Option<PathBuf>,
+    // Whether the compiler is told the triple with --target. Only Clang builds for several
+    // targets.
+    pass_triple: bool,
+}
+
+impl CrossTarget {
+    fn new(common: &CommonToolchainData, cxx: &CXXCompiler) -> Result<Self, ToolchainError> {
+        let pass_triple = match (&common.target_triple, &cxx.compiler_info.compiler_type) {
+            (Some(_), Type::Clang) => true,
+            (Some(triple), Type::Gcc) => {
+                if let Some(compiler_triple) = target_triple(&cxx.compiler_exe) {
+                    if !is_same_target(triple, &compiler_triple) {
+                        return Err(ToolchainError::TargetTripleMismatch(
+                            triple.clone(),
+                            cxx.compiler_exe.clone(),
+                            compiler_triple,
+                        ));
+                    }
+                }
+                false
+            }
+            (None, _) => false,
+        };
+        Ok(Self {
+            triple: common.target_triple.clone(),
+            sysroot: common.sysroot.clone(),
+            pass_triple,
+        })
+    }
+
+    /// Flags for both compiling and linking.
+    pub fn flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if let Some(ref triple) = self.triple.as_ref().filter(|_| self.pass_triple) {
+            flags.push(format!("--target={}", triple));
+        }
+        if let Some(ref sysroot) = self.sysroot {
+            flags.push(format!("--sysroot={}", sysroot.display()));
+        }
+        flags
+    }
+}
+
+// Triples name the same machine with or without a vendor, such as "aarch64-linux-gnu" and
+// "aarch64-unknown-linux-gnu".
+fn is_same_target(triple: &str, other: &str) -> bool {
+    fn without_vendor(triple: &str) -> Vec<&str> {
+        triple
+            .split('-')
+            .filter(|part| !matches!(*part, "unknown" | "pc" | "none"))
+            .collect()
+    }
+    without_vendor(triple) == without_vendor(other)
+}
+
+// Whether binaries for the machine named by `triple` run on the machine yambs runs on.
+fn is_host_target(triple: &str) -> bool {
+    runs_on(triple, std::env::consts::ARCH, std::env::consts::OS)
+}
+
+// Whether `triple` names a machine with the architecture `arch` and operating system `os`, named
+// like those of std::env::consts. Triples without a known operating system, such as
+// "arm-none-eabi", only need the same architecture.
+fn runs_on(triple: &str, arch: &str, os: &str) -> bool {
+    let triple_arch = match triple.split('-').next().unwrap_or_default() {
+        "amd64" => "x86_64",
+        "arm64" => "aarch64",
+        "i386" | "i486" | "i586" | "i686" => "x86",
+        machine if machine.starts_with("arm") => "arm",
+        machine => machine,
+    };
+    let triple_os = if triple.contains("android") {
+        Some("android")
+    } else if triple.contains("linux") {
+        Some("linux")
+    } else if triple.contains("darwin") || triple.contains("apple") {
+        Some("macos")
+    } else if triple.contains("mingw") || triple.contains("windows") || triple.contains("cygwin") {
+        Some("windows")
+    } else if triple.contains("freebsd") {
+        Some("freebsd")
+    } else {
+        None
+    };
+    triple_arch == arch && triple_os.is_none_or(|triple_os| triple_os == os)
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -203,18 +652,44 @@ pub struct NormalizedToolchain {
     pub cc: ToolchainCC,
     pub archiver: Archiver,
     pub pkg_config: Option<PkgConfig>,
+    pub apple_platform: ApplePlatform,
+    pub cross_target: CrossTarget,
+    pub output_extensions: OutputExtensions,
+    /// Not set when it is neither given by the toolchain file nor found in PATH.
+    pub objcopy: Option<PathBuf>,
+    /// Toolchain used for targets built for the host. Only set when cross compiling.
+    pub host: Option<Rc<RefCell<NormalizedToolchain>>>,
 }
 
 impl NormalizedToolchain {
     pub fn new() -> Result<Self, ToolchainError> {
+        let cxx = ToolchainCXX::new()?;
+        let dynamic_library = DynamicLibraryFormat::from_compiler(&cxx.compiler.compiler_exe);
         Ok(Self {
-            cxx: ToolchainCXX::new()?,
+            cxx,
             cc: ToolchainCC::new()?,
             archiver: Archiver::new().map_err(ToolchainError::Archiver)?,
             pkg_config: PkgConfig::new().ok(),
+            apple_platform: ApplePlatform::default(),
+            cross_target: CrossTarget::default(),
+            output_extensions: OutputExtensions::from_common_toolchain_data(
+                &CommonToolchainData::default(),
+                dynamic_library,
+            ),
+            objcopy: find_objcopy(&[]),
+            host: None,
         })
     }
 
+    /// Context a target asking to be built for `build_for` ends up in. Without a host toolchain,
+    /// the host and the target are the same machine, so everything is built for the target.
+    pub fn resolve_build_for(&self, build_for: BuildFor) -> BuildFor {
+        match build_for {
+            BuildFor::Host if self.host.is_some() => BuildFor::Host,
+            _ => BuildFor::Target,
+        }
+    }
+
     pub fn from_file(path: &Path) -> Result<Self, ToolchainError> {
         if path.exists() {
             log::debug!("Parsing toolchain at {}", path.display());
@@ -224,6 +699,106 @@ impl NormalizedToolchain {
             Err(ToolchainError::ToolchainNotFound(path.to_path_buf()))
         }
     }
+
+    pub fn fingerprint(&self) -> Result<ToolchainFingerprint, ToolchainError> {
+        Ok(ToolchainFingerprint {
+            cxx: CompilerFingerprint::new(
+                &self.cxx.compiler.compiler_exe,
+                &self.cxx.compiler.compiler_info.compiler_type,
+                &self.cxx.compiler.compiler_info.compiler_version,
+            )?,
+            cc: CompilerFingerprint::new(
+                &self.cc.compiler.compiler_exe,
+                &self.cc.compiler.compiler_info.compiler_type,
+                &self.cc.compiler.compiler_info.compiler_version,
+            )?,
+            archiver: self.archiver.path.clone(),
+            apple_platform_flags: self.apple_platform.flags(),
+            cross_target_flags: self.cross_target.flags(),
+            host: match self.host {
+                Some(ref host) => Some(Box::new(host.borrow().fingerprint()?)),
+                None => None,
+            },
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Deserialize, Serialize)]
+pub struct CompilerFingerprint {
+    pub path: PathBuf,
+    pub compiler_type: Type,
+    pub version: String,
+    pub target: String,
+}
+
+impl CompilerFingerprint {
+    fn new(path: &Path, compiler_type: &Type, version: &str) -> Result<Self, ToolchainError> {
+        let target = utility::shell::execute_get_stdout(path, ["-dumpmachine"])
+            .map_err(|e| ToolchainError::FailedToGetTarget(path.to_path_buf(), e))?
+            .trim()
+            .to_string();
+        Ok(Self {
+            path: path.to_path_buf(),
+            compiler_type: compiler_type.clone(),
+            version: version.to_string(),
+            target,
+        })
+    }
+}
+
+impl std::fmt::Display for CompilerFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} {}, target {})",
+            self.path.display(),
+            self.compiler_type.to_string(),
+            self.version,
+            self.target
+        )
+    }
+}
+
+/// Identifies the tools that produced the objects in a build directory.
+/// If the fingerprint changes between two runs, objects from the previous run can not be trusted
+/// to be compatible with the new ones.
+#[derive(PartialEq, Eq, Debug, Clone, Deserialize, Serialize)]
+pub struct ToolchainFingerprint {
+    pub cxx: CompilerFingerprint,
+    pub cc: CompilerFingerprint,
+    pub archiver: PathBuf,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub apple_platform_flags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cross_target_flags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<Box<ToolchainFingerprint>>,
+}
+
+impl Cacher for ToolchainFingerprint {
+    const CACHE_FILE_NAME: &'static str = "toolchain";
+}
+
+impl std::fmt::Display for ToolchainFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "CXX: {}", self.cxx)?;
+        writeln!(f, "CC: {}", self.cc)?;
+        write!(f, "AR: {}", self.archiver.display())?;
+        if !self.apple_platform_flags.is_empty() {
+            write!(
+                f,
+                "\nApple platform: {}",
+                self.apple_platform_flags.join(" ")
+            )?;
+        }
+        if !self.cross_target_flags.is_empty() {
+            write!(f, "\nCross target: {}", self.cross_target_flags.join(" "))?;
+        }
+        if let Some(ref host) = self.host {
+            write!(f, "\nHost:\n{}", host)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Error)]
@@ -249,4 +824,160 @@ pub enum ToolchainError {
     FailedToConvertUtf8(#[source] std::string::FromUtf8Error),
     #[error("Toolchain not found at {0}")]
     ToolchainNotFound(PathBuf),
+    #[error("Failed to retrieve target machine from\n\n\t{0} -dumpmachine")]
+    FailedToGetTarget(PathBuf, #[source] crate::errors::FsError),
+    #[error("Failed to locate SDK \"{0}\" with\n\n\txcrun --sdk {0} --show-sdk-path")]
+    FailedToLocateSdk(String, #[source] crate::errors::FsError),
+    #[error("xcrun did not find SDK \"{0}\"")]
+    SdkNotFound(String),
+    #[error("The toolchain file builds for {0}, but {1} builds for {2}. GCC only builds for the machine it was configured for, so give a cross compiler such as {0}-g++")]
+    TargetTripleMismatch(String, PathBuf, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn apple_platform_flags_select_sysroot_and_version_minimum() {
+        let platform = ApplePlatform {
+            sdk: Some("iphonesimulator".to_string()),
+            sdk_path: Some(PathBuf::from("/sdks/iPhoneSimulator.sdk")),
+            macos_deployment_target: None,
+            ios_deployment_target: Some("15.0".to_string()),
+        };
+        assert_eq!(
+            platform.flags(),
+            vec![
+                "-isysroot /sdks/iPhoneSimulator.sdk",
+                "-mios-simulator-version-min=15.0"
+            ]
+        );
+
+        let platform = ApplePlatform {
+            macos_deployment_target: Some("12.0".to_string()),
+            ..ApplePlatform::default()
+        };
+        assert_eq!(platform.flags(), vec!["-mmacosx-version-min=12.0"]);
+    }
+
+    #[test]
+    fn cross_targets_are_given_by_triple_and_sysroot() {
+        let content = indoc::indoc! {r#"
+            [CXX]
+            compiler = "/opt/llvm/bin/clang++"
+
+            [common]
+            target_triple = "aarch64-linux-gnu"
+            sysroot = "/opt/sysroots/aarch64"
+        "#};
+        assert_eq!(
+            target_triple_of_file(content),
+            Some("aarch64-linux-gnu".to_string())
+        );
+
+        let target = CrossTarget {
+            triple: Some("aarch64-linux-gnu".to_string()),
+            sysroot: Some(PathBuf::from("/opt/sysroots/aarch64")),
+            pass_triple: true,
+        };
+        assert_eq!(
+            target.flags(),
+            vec![
+                "--target=aarch64-linux-gnu",
+                "--sysroot=/opt/sysroots/aarch64"
+            ]
+        );
+        assert!(is_same_target(
+            "aarch64-linux-gnu",
+            "aarch64-unknown-linux-gnu"
+        ));
+        assert!(!is_same_target("aarch64-linux-gnu", "x86_64-linux-gnu"));
+    }
+
+    #[test]
+    fn compilers_for_other_machines_cross_compile_without_cross_keys() {
+        assert!(runs_on("x86_64-linux-gnu", "x86_64", "linux"));
+        assert!(runs_on("x86_64-pc-linux-gnu", "x86_64", "linux"));
+        assert!(runs_on("i686-linux-gnu", "x86", "linux"));
+        assert!(runs_on("arm64-apple-darwin23.1.0", "aarch64", "macos"));
+        assert!(!runs_on("aarch64-linux-gnu", "x86_64", "linux"));
+        assert!(!runs_on("x86_64-w64-mingw32", "x86_64", "linux"));
+        assert!(!runs_on("aarch64-linux-android", "aarch64", "linux"));
+        assert!(!runs_on("arm-none-eabi", "x86_64", "linux"));
+    }
+
+    #[test]
+    fn search_paths_are_read_from_common_table() {
+        let content = indoc::indoc! {r#"
+            [CXX]
+            compiler = "/opt/llvm/bin/clang++"
+
+            [common]
+            search_paths = ["/opt/llvm/bin", "/opt/tools"]
+        "#};
+        assert_eq!(
+            search_paths(content).unwrap(),
+            vec![PathBuf::from("/opt/llvm/bin"), PathBuf::from("/opt/tools")]
+        );
+        assert_eq!(search_paths("").unwrap(), Vec::<PathBuf>::new());
+    }
+
+    #[test]
+    fn output_extensions_are_taken_from_common_toolchain_data() {
+        let common = CommonToolchainData {
+            object_extension: Some("obj".to_string()),
+            executable_extension: Some("elf".to_string()),
+            ..CommonToolchainData::default()
+        };
+        let output_extensions =
+            OutputExtensions::from_common_toolchain_data(&common, DynamicLibraryFormat::default());
+        assert_eq!(output_extensions.object, "obj");
+        assert_eq!(
+            output_extensions.executable_file_name("firmware"),
+            "firmware.elf"
+        );
+
+        let output_extensions = OutputExtensions::from_common_toolchain_data(
+            &CommonToolchainData::default(),
+            DynamicLibraryFormat::default(),
+        );
+        assert_eq!(output_extensions.object, "o");
+        assert_eq!(output_extensions.executable_file_name("app"), "app");
+    }
+
+    #[test]
+    fn dlls_are_linked_through_import_libraries() {
+        let library = PrintableLibrary {
+            name: "net".to_string(),
+            ty: LibraryType::Dynamic,
+        };
+        let format = DynamicLibraryFormat::from_target_triple("x86_64-w64-mingw32");
+        assert_eq!(format, DynamicLibraryFormat::MinGwDll);
+        let output_extensions =
+            OutputExtensions::from_common_toolchain_data(&CommonToolchainData::default(), format);
+        assert_eq!(output_extensions.library_file_name(&library), "net.dll");
+        assert_eq!(output_extensions.link_file_name(&library), "libnet.dll.a");
+        assert_eq!(output_extensions.executable_file_name("app"), "app.exe");
+
+        let format = DynamicLibraryFormat::from_target_triple("x86_64-pc-windows-msvc");
+        let output_extensions =
+            OutputExtensions::from_common_toolchain_data(&CommonToolchainData::default(), format);
+        assert_eq!(output_extensions.link_file_name(&library), "net.lib");
+
+        let format = DynamicLibraryFormat::from_target_triple("x86_64-pc-linux-gnu");
+        let output_extensions =
+            OutputExtensions::from_common_toolchain_data(&CommonToolchainData::default(), format);
+        assert_eq!(output_extensions.library_file_name(&library), "libnet.so");
+        assert_eq!(output_extensions.import_library_file_name(&library), None);
+        let static_library = PrintableLibrary {
+            name: "net".to_string(),
+            ty: LibraryType::Static,
+        };
+        assert_eq!(
+            output_extensions.link_file_name(&static_library),
+            "libnet.a"
+        );
+    }
 }
@@ -1,20 +1,64 @@
 use std::fs::File;
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::errors::FsError;
 pub mod shell;
 
-pub fn get_include_directory_from_path<P: AsRef<Path>>(path: P) -> Result<PathBuf, FsError> {
-    if path.as_ref().join("include").is_dir() {
-        return Ok(path.as_ref().join("include"));
-    } else {
-        let parent = path.as_ref().parent().unwrap();
-        if parent.join("include").is_dir() {
-            Ok(parent.join("include"))
-        } else {
-            Err(FsError::NoIncludeDirectory(parent.into()))
+/// Where a project places its sources and headers, relative to the root of the project.
+/// Defaults to the conventional `src` or `source` and `include` directories.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ProjectLayout {
+    pub source_dirs: Vec<PathBuf>,
+    pub include_dirs: Vec<PathBuf>,
+}
+
+impl Default for ProjectLayout {
+    fn default() -> Self {
+        Self {
+            source_dirs: vec![PathBuf::from("src"), PathBuf::from("source")],
+            include_dirs: vec![PathBuf::from("include")],
+        }
+    }
+}
+
+impl ProjectLayout {
+    /// Root of the project `path` belongs to. That is the directory a source directory is placed
+    /// in when `path` is one, and `path` itself otherwise.
+    pub fn project_root<'a>(&self, path: &'a Path) -> &'a Path {
+        for source_dir in &self.source_dirs {
+            if !source_dir.as_os_str().is_empty() && path.ends_with(source_dir) {
+                return source_dir
+                    .components()
+                    .try_fold(path, |root, _| root.parent())
+                    .unwrap_or(path);
+            }
         }
+        path
     }
+
+    /// Include directories of the project `path` belongs to that exist, in the order they are
+    /// configured.
+    pub fn include_directories(&self, path: &Path) -> Vec<PathBuf> {
+        let root = self.project_root(path);
+        self.include_dirs
+            .iter()
+            .map(|include_dir| root.join(include_dir))
+            .filter(|include_dir| include_dir.is_dir())
+            .collect()
+    }
+}
+
+pub fn get_include_directory_from_path<P: AsRef<Path>>(
+    path: P,
+    layout: &ProjectLayout,
+) -> Result<PathBuf, FsError> {
+    layout
+        .include_directories(path.as_ref())
+        .into_iter()
+        .next()
+        .ok_or_else(|| FsError::NoIncludeDirectory(layout.project_root(path.as_ref()).into()))
 }
 
 pub fn get_mmk_library_file_from_path(path: &Path) -> Result<PathBuf, FsError> {
@@ -25,8 +69,12 @@ pub fn get_mmk_library_file_from_path(path: &Path) -> Result<PathBuf, FsError> {
     }
 }
 
-pub fn is_source_directory<P: AsRef<Path>>(path: P) -> bool {
-    (path.as_ref().ends_with("source") || path.as_ref().ends_with("src")) && path.as_ref().is_dir()
+pub fn is_source_directory<P: AsRef<Path>>(path: P, layout: &ProjectLayout) -> bool {
+    layout
+        .source_dirs
+        .iter()
+        .any(|source_dir| path.as_ref().ends_with(source_dir))
+        && path.as_ref().is_dir()
 }
 
 pub fn is_test_directory<P: AsRef<Path>>(path: P) -> bool {
@@ -39,10 +87,12 @@ pub fn get_head_directory(path: &Path) -> &Path {
 }
 
 // Add test for this function.
-pub fn get_project_top_directory(path: &Path) -> &Path {
+pub fn get_project_top_directory<'a>(path: &'a Path, layout: &ProjectLayout) -> &'a Path {
     let parent = path.parent().unwrap();
-    if is_source_directory(parent) || is_test_directory(parent) {
-        return parent.parent().unwrap();
+    if is_source_directory(parent, layout) {
+        layout.project_root(parent)
+    } else if is_test_directory(parent) {
+        parent.parent().unwrap()
     } else {
         parent
     }
@@ -52,8 +102,110 @@ pub fn directory_exists(path: &Path) -> bool {
     path.exists()
 }
 
+/// Source trees of a build, which may be read-only, such as a checkout in the Nix store or a
+/// mounted snapshot. Everything generated belongs in the build directory, which is written to even
+/// when it is inside one of the source trees, as are the build directories of the projects that
+/// dependencies are imported from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceTreeGuard {
+    source_directories: Vec<PathBuf>,
+    build_directory: PathBuf,
+    external_build_directories: Vec<PathBuf>,
+}
+
+impl SourceTreeGuard {
+    pub fn new(source_directories: &[PathBuf], build_directory: &Path) -> Self {
+        Self {
+            source_directories: source_directories
+                .iter()
+                .map(|directory| resolve_path(directory))
+                .collect(),
+            build_directory: resolve_path(build_directory),
+            external_build_directories: Vec::new(),
+        }
+    }
+
+    /// Guard also allowing writes into `build_directories`, such as those of CMake projects.
+    pub fn with_external_build_directories(mut self, build_directories: &[PathBuf]) -> Self {
+        self.external_build_directories.extend(
+            build_directories
+                .iter()
+                .map(|directory| resolve_path(directory)),
+        );
+        self
+    }
+
+    /// Fails if writing `path` would write into one of the source trees.
+    pub fn check(&self, path: &Path) -> Result<(), FsError> {
+        let path = resolve_path(path);
+        if std::iter::once(&self.build_directory)
+            .chain(&self.external_build_directories)
+            .any(|directory| path.starts_with(directory))
+        {
+            return Ok(());
+        }
+        match self
+            .source_directories
+            .iter()
+            .find(|directory| path.starts_with(directory))
+        {
+            Some(directory) => Err(FsError::WriteIntoSourceTree(path, directory.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    // Guard checked by the functions of this module writing files, once installed by the build.
+    static ref SOURCE_TREE_GUARD: std::sync::Mutex<Option<SourceTreeGuard>> =
+        std::sync::Mutex::new(None);
+}
+
+/// Makes the functions of this module writing files refuse to write into the source trees of
+/// `guard` from now on.
+pub fn guard_source_trees(guard: SourceTreeGuard) {
+    *SOURCE_TREE_GUARD.lock().unwrap() = Some(guard);
+}
+
+fn check_outside_source_trees(path: &Path) -> Result<(), FsError> {
+    match *SOURCE_TREE_GUARD.lock().unwrap() {
+        Some(ref guard) => guard.check(path),
+        None => Ok(()),
+    }
+}
+
+// Absolute `path` with symbolic links of the part of it that exists resolved, so that paths of
+// files yet to be written can be compared with those of directories.
+fn resolve_path(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|directory| directory.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    let mut existing = path.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest
+                .iter()
+                .rev()
+                .fold(canonical, |resolved, component| resolved.join(component));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(file_name)) => {
+                rest.push(file_name.to_os_string());
+                existing = parent;
+            }
+            _ => return path.clone(),
+        }
+    }
+}
+
 pub fn create_dir<D: AsRef<Path>>(dir: D) -> Result<(), FsError> {
     if !dir.as_ref().is_dir() {
+        check_outside_source_trees(dir.as_ref())?;
         std::fs::create_dir_all(dir.as_ref())
             .map_err(|err| FsError::CreateDirectory(dir.as_ref().to_path_buf(), err))?;
     }
@@ -65,6 +217,7 @@ where
     D: AsRef<Path>,
     S: AsRef<Path>,
 {
+    check_outside_source_trees(source.as_ref())?;
     std::os::unix::fs::symlink(destination.as_ref(), source.as_ref()).map_err(|err| {
         FsError::CreateSymlink {
             dest: destination.as_ref().to_path_buf(),
@@ -75,9 +228,164 @@ where
 }
 
 pub fn create_file(file: &Path) -> Result<File, FsError> {
+    check_outside_source_trees(file)?;
     File::create(file).map_err(|err| FsError::CreateFile(file.to_path_buf(), err))
 }
 
+// Tells apart the temporary files of threads writing into the same directory.
+static TEMPORARY_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes `content` to `file` through a temporary file in the same directory, which is synced and
+/// renamed over `file`. A reader, or a crash in the middle of writing, sees either the previous or
+/// the new content of `file`, never parts of it.
+pub fn write_file_atomically(file: &Path, content: &[u8]) -> Result<(), FsError> {
+    check_outside_source_trees(file)?;
+    let directory = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = file.file_name().ok_or_else(|| {
+        FsError::CreateFile(
+            file.to_path_buf(),
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no file name"),
+        )
+    })?;
+    let temporary_file = directory.join(format!(
+        ".{}.{}-{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        TEMPORARY_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = write_and_sync(&temporary_file, content).and_then(|_| {
+        std::fs::rename(&temporary_file, file)
+            .map_err(|err| FsError::Rename(temporary_file.clone(), file.to_path_buf(), err))
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary_file);
+        return result;
+    }
+
+    // The rename itself is only durable once the directory entry is synced. Not every file system
+    // supports syncing a directory, and the file is complete either way, so failing to is not an
+    // error.
+    if let Err(err) = File::open(directory).and_then(|handle| handle.sync_all()) {
+        log::debug!("Failed to sync directory {}: {}", directory.display(), err);
+    }
+    Ok(())
+}
+
+fn write_and_sync(file: &Path, content: &[u8]) -> Result<(), FsError> {
+    let mut handle = create_file(file)?;
+    handle.write_all(content).map_err(FsError::WriteToFile)?;
+    handle
+        .sync_all()
+        .map_err(|err| FsError::SyncToDisk(file.to_path_buf(), err))
+}
+
+/// Files matched by a glob pattern, and the directories searched for them.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GlobMatches {
+    pub files: Vec<PathBuf>,
+    /// Adding or removing a file in one of them may change the files matched.
+    pub directories: Vec<PathBuf>,
+}
+
+/// Files matching `pattern`, a path relative to `root`, in sorted order. `*` and `?` match within
+/// a path component, and a component of `**` matches any number of directories.
+pub fn glob(root: &Path, pattern: &str) -> std::io::Result<GlobMatches> {
+    fn search(
+        directory: &Path,
+        components: &[&str],
+        matches: &mut GlobMatches,
+    ) -> std::io::Result<()> {
+        let (component, rest) = match components.split_first() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+        if !matches
+            .directories
+            .iter()
+            .any(|searched| searched == directory)
+        {
+            matches.directories.push(directory.to_path_buf());
+        }
+        if !component.contains(['*', '?']) {
+            let path = directory.join(component);
+            if rest.is_empty() && path.is_file() {
+                matches.files.push(path);
+            } else if !rest.is_empty() && path.is_dir() {
+                search(&path, rest, matches)?;
+            }
+            return Ok(());
+        }
+        let mut entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<PathBuf>>>()?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        entries.sort();
+        if *component == "**" {
+            search(directory, rest, matches)?;
+            for entry in entries.iter().filter(|entry| entry.is_dir()) {
+                search(entry, components, matches)?;
+            }
+            return Ok(());
+        }
+        for entry in entries {
+            let name = entry
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !matches_wildcard(component, &name) {
+                continue;
+            }
+            if rest.is_empty() && entry.is_file() {
+                matches.files.push(entry);
+            } else if !rest.is_empty() && entry.is_dir() {
+                search(&entry, rest, matches)?;
+            }
+        }
+        Ok(())
+    }
+
+    let components = pattern
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect::<Vec<&str>>();
+    let mut matches = GlobMatches::default();
+    search(root, &components, &mut matches)?;
+    matches.files.sort();
+    matches.files.dedup();
+    Ok(matches)
+}
+
+// Whether `name` matches `pattern`, where `*` matches any number of characters and `?` one.
+fn matches_wildcard(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let name = name.chars().collect::<Vec<char>>();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 // This should be separated into its own "Make" mod.
 pub fn print_full_path(os: &mut String, dir: &str, filename: &str, no_newline: bool) {
     os.push_str(dir);
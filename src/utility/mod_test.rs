@@ -6,7 +6,7 @@ fn get_include_directory_from_path_test() {
     let dir = TempDir::new("example").unwrap();
     let include_dir = dir.path().join("include");
     create_dir(&include_dir).unwrap();
-    let actual = get_include_directory_from_path(dir.path());
+    let actual = get_include_directory_from_path(dir.path(), &ProjectLayout::default());
     assert!(actual.is_ok());
     assert_eq!(actual.unwrap(), include_dir);
 }
@@ -16,7 +16,7 @@ fn get_include_directory_from_path_search_one_directory_up_test() {
     let dir = TempDir::new("example").unwrap();
     let include_dir = dir.path().join("include");
     create_dir(&include_dir).unwrap();
-    let actual = get_include_directory_from_path(dir.path().join("src"));
+    let actual = get_include_directory_from_path(dir.path().join("src"), &ProjectLayout::default());
     assert!(actual.is_ok());
     assert_eq!(actual.unwrap(), include_dir);
 }
@@ -24,7 +24,7 @@ fn get_include_directory_from_path_search_one_directory_up_test() {
 #[test]
 fn get_include_directory_from_path_fails_test() {
     let dir = TempDir::new("example").unwrap();
-    let result = get_include_directory_from_path(dir.path());
+    let result = get_include_directory_from_path(dir.path(), &ProjectLayout::default());
     assert!(result.is_err());
 }
 
@@ -33,7 +33,7 @@ fn is_source_directory_src_test() {
     let dir = TempDir::new("example").unwrap();
     let source_dir = dir.path().join("src");
     create_dir(&source_dir).unwrap();
-    assert!(is_source_directory(source_dir));
+    assert!(is_source_directory(source_dir, &ProjectLayout::default()));
 }
 
 #[test]
@@ -41,13 +41,46 @@ fn is_source_directory_source_test() {
     let dir = TempDir::new("example").unwrap();
     let source_dir = dir.path().join("source");
     create_dir(&source_dir).unwrap();
-    assert!(is_source_directory(source_dir));
+    assert!(is_source_directory(source_dir, &ProjectLayout::default()));
 }
 
 #[test]
 fn is_source_directory_false_test() {
     let source_dir = PathBuf::from("/some/path/without/source/directory");
-    assert!(!is_source_directory(source_dir));
+    assert!(!is_source_directory(source_dir, &ProjectLayout::default()));
+}
+
+#[test]
+fn get_include_directory_from_path_uses_configured_layout_test() {
+    let dir = TempDir::new("example").unwrap();
+    create_dir(dir.path().join("include")).unwrap();
+    let public_dir = dir.path().join("public").join("headers");
+    create_dir(&public_dir).unwrap();
+    let layout = ProjectLayout {
+        source_dirs: vec![PathBuf::from("code/lib")],
+        include_dirs: vec![PathBuf::from("public/headers"), PathBuf::from("include")],
+    };
+    let actual = get_include_directory_from_path(dir.path().join("code").join("lib"), &layout);
+    assert_eq!(actual.unwrap(), public_dir);
+    assert_eq!(
+        layout.include_directories(dir.path()),
+        vec![public_dir, dir.path().join("include")]
+    );
+    assert!(get_include_directory_from_path(dir.path().join("src"), &layout).is_err());
+}
+
+#[test]
+fn is_source_directory_uses_configured_layout_test() {
+    let dir = TempDir::new("example").unwrap();
+    let source_dir = dir.path().join("lib");
+    create_dir(&source_dir).unwrap();
+    create_dir(dir.path().join("src")).unwrap();
+    let layout = ProjectLayout {
+        source_dirs: vec![PathBuf::from("lib")],
+        include_dirs: Vec::new(),
+    };
+    assert!(is_source_directory(&source_dir, &layout));
+    assert!(!is_source_directory(dir.path().join("src"), &layout));
 }
 
 #[test]
@@ -94,3 +127,103 @@ fn print_full_path_with_newline_test() {
     print_full_path(&mut formatted_string, dir_path, filename, no_newline);
     assert_eq!(formatted_string, expected);
 }
+
+#[test]
+fn write_file_atomically_replaces_content_without_leaving_temporary_files() {
+    let dir = TempDir::new("example").unwrap();
+    let file = dir.path().join("Makefile");
+    std::fs::write(&file, "old content which is longer than the new one\n").unwrap();
+    write_file_atomically(&file, b"new\n").unwrap();
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "new\n");
+    let entries = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(entries, vec![std::ffi::OsString::from("Makefile")]);
+}
+
+#[test]
+fn write_file_atomically_removes_temporary_file_on_failure() {
+    let dir = TempDir::new("example").unwrap();
+    // A non-empty directory can not be renamed over, so writing fails after the content is written.
+    let occupied = dir.path().join("build.mk");
+    create_dir(occupied.join("nonempty")).unwrap();
+    assert!(write_file_atomically(&occupied, b"new\n").is_err());
+    assert!(occupied.join("nonempty").is_dir());
+    let entries = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(entries, vec![std::ffi::OsString::from("build.mk")]);
+}
+
+#[test]
+fn glob_matches_files_of_pattern_in_sorted_order() {
+    let dir = TempDir::new("example").unwrap();
+    let root = dir.path();
+    create_dir(root.join("tests/unit")).unwrap();
+    for file in [
+        "tests/vector_test.cpp",
+        "tests/matrix_test.cpp",
+        "tests/helpers.hpp",
+        "tests/unit/parse_test.cpp",
+    ] {
+        File::create(root.join(file)).unwrap();
+    }
+
+    let matches = glob(root, "tests/*_test.cpp").unwrap();
+    assert_eq!(
+        matches.files,
+        vec![
+            root.join("tests/matrix_test.cpp"),
+            root.join("tests/vector_test.cpp")
+        ]
+    );
+    assert_eq!(
+        matches.directories,
+        vec![root.to_path_buf(), root.join("tests")]
+    );
+    assert_eq!(
+        glob(root, "tests/**/*.cpp").unwrap().files,
+        vec![
+            root.join("tests/matrix_test.cpp"),
+            root.join("tests/unit/parse_test.cpp"),
+            root.join("tests/vector_test.cpp"),
+        ]
+    );
+    assert!(glob(root, "missing/*.cpp").unwrap().files.is_empty());
+}
+
+#[test]
+fn source_tree_guard_refuses_writes_into_source_trees_outside_the_build_directory() {
+    let dir = TempDir::new("example").unwrap();
+    let source_directory = dir.path().join("project");
+    let build_directory = source_directory.join("build");
+    create_dir(&build_directory).unwrap();
+    let guard = SourceTreeGuard::new(std::slice::from_ref(&source_directory), &build_directory);
+    assert!(guard.check(&build_directory.join("debug/Makefile")).is_ok());
+    assert!(guard.check(&dir.path().join("elsewhere/file")).is_ok());
+    assert!(matches!(
+        guard.check(&source_directory.join("src/generated.cpp")),
+        Err(FsError::WriteIntoSourceTree(ref path, ref directory))
+            if path.ends_with("project/src/generated.cpp") && directory.ends_with("project")
+    ));
+}
+
+#[test]
+fn source_tree_guard_allows_writes_into_external_build_directories() {
+    let dir = TempDir::new("example").unwrap();
+    let source_directory = dir.path().join("project");
+    let cmake_build_directory = source_directory.join("third_party/zlib/build");
+    let query = cmake_build_directory.join(".cmake/api/v1/query/client-yambs/codemodel-v2");
+    let guard = SourceTreeGuard::new(
+        std::slice::from_ref(&source_directory),
+        &source_directory.join("build"),
+    );
+    assert!(guard.check(&query).is_err());
+    let guard = guard.with_external_build_directories(&[cmake_build_directory]);
+    assert!(guard.check(&query).is_ok());
+    assert!(guard
+        .check(&source_directory.join("third_party/zlib/zconf.h"))
+        .is_err());
+}
@@ -1,3 +1,4 @@
+use crate::environment::Environment;
 use crate::errors::FsError;
 
 pub fn execute_get_stdout<I, S>(exe: &std::path::Path, args: I) -> Result<String, FsError>
@@ -23,7 +24,8 @@ where
     I: std::iter::IntoIterator<Item = S>,
     S: AsRef<std::ffi::OsStr>,
 {
-    let child = std::process::Command::new(exe)
+    let child = Environment::new()
+        .command(exe)
         .args(args)
         .stdout(std::process::Stdio::piped())
         .spawn()
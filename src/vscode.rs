@@ -0,0 +1,433 @@
+// Editor setup of Visual Studio Code for a built project: tasks building the project, each of its
+// targets and running its tests, and debug launch configurations of its executables and of the
+// tests running them. Everything is taken from the most recent build of a build directory, so
+// that the tasks build the project the same way and the debugger runs tests the same way as
+// `yambs test`.
+//
+// Tasks and configurations written by yambs are labeled "yambs: ..." and are replaced when written
+// again, while those added by the user are kept.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::errors::FsError;
+use crate::generator::targets::ProgressDocument;
+use crate::manifest::ParsedManifest;
+use crate::targets::{Command, Target, TestKind};
+use crate::test_runner::BUILD_DIRECTORY_VARIABLE;
+use crate::toolchain::OutputExtensions;
+
+/// Directory of the configuration of a workspace.
+pub const VSCODE_DIRECTORY_NAME: &str = ".vscode";
+pub const TASKS_FILE_NAME: &str = "tasks.json";
+pub const LAUNCH_FILE_NAME: &str = "launch.json";
+
+// Labels of what yambs writes start with this, to tell them from those of the user.
+const LABEL_PREFIX: &str = "yambs: ";
+
+// Pseudo target of the progress document depending on every target.
+const ALL_TARGET_NAME: &str = "all";
+
+#[derive(Debug, thiserror::Error)]
+pub enum VscodeError {
+    #[error(transparent)]
+    Fs(#[from] FsError),
+    #[error(
+        "Failed to parse {0:?}. Comments and trailing commas are not supported. Remove them, or remove the file to have it written from scratch."
+    )]
+    FailedToParse(PathBuf, #[source] serde_json::Error),
+    #[error("{0:?} does not hold a JSON object. Remove the file to have it written from scratch.")]
+    NotAnObject(PathBuf),
+}
+
+/// Program the debugger is launched with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchTarget {
+    pub name: String,
+    /// Target built before the program is launched.
+    pub target: String,
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub working_directory: PathBuf,
+    pub env: BTreeMap<String, String>,
+}
+
+/// Tasks and launch configurations of a built project.
+#[derive(Debug)]
+pub struct VscodeWorkspace {
+    yambs: PathBuf,
+    build_args: Vec<String>,
+    build_environment: BTreeMap<String, String>,
+    targets: Vec<String>,
+    tests: Vec<String>,
+    launch_targets: Vec<LaunchTarget>,
+}
+
+impl VscodeWorkspace {
+    /// Workspace of the project of `manifest`, built by running `yambs` with `build_args` and
+    /// `build_environment` into the build files described by `progress_document`, with
+    /// executables linked in `executable_directory` and named with `output_extensions`.
+    pub fn new(
+        yambs: &Path,
+        build_args: Vec<String>,
+        build_environment: BTreeMap<String, String>,
+        progress_document: &ProgressDocument,
+        manifest: &ParsedManifest,
+        executable_directory: &Path,
+        output_extensions: &OutputExtensions,
+    ) -> Self {
+        let targets = progress_document
+            .targets
+            .iter()
+            .map(|target| target.target.clone())
+            .filter(|target| target != ALL_TARGET_NAME)
+            .collect::<Vec<String>>();
+
+        let manifest_directory = &manifest.manifest.directory;
+        let mut launch_targets = manifest
+            .data
+            .targets
+            .iter()
+            .filter_map(Target::executable)
+            .map(|executable| LaunchTarget {
+                name: format!("{}{}", LABEL_PREFIX, executable.name),
+                target: executable.name.clone(),
+                program: executable_directory
+                    .join(output_extensions.executable_file_name(&executable.name)),
+                args: Vec::new(),
+                working_directory: manifest_directory.clone(),
+                env: BTreeMap::new(),
+            })
+            .collect::<Vec<LaunchTarget>>();
+        // Tests get the environment given by the test runner on top of their own.
+        for test in &manifest.data.tests {
+            if let TestKind::Run(Command::Target {
+                ref target,
+                ref args,
+            }) = test.kind
+            {
+                let mut env = test.env.clone();
+                env.insert(
+                    "PATH".to_string(),
+                    format!("{}:${{env:PATH}}", executable_directory.display()),
+                );
+                env.insert(
+                    BUILD_DIRECTORY_VARIABLE.to_string(),
+                    executable_directory.display().to_string(),
+                );
+                launch_targets.push(LaunchTarget {
+                    name: format!("{}test {}", LABEL_PREFIX, test.name),
+                    target: target.clone(),
+                    program: executable_directory
+                        .join(output_extensions.executable_file_name(target)),
+                    args: args.clone(),
+                    working_directory: test.working_directory.clone(),
+                    env,
+                });
+            }
+        }
+
+        Self {
+            yambs: yambs.to_path_buf(),
+            build_args,
+            build_environment,
+            targets,
+            tests: manifest
+                .data
+                .tests
+                .iter()
+                .map(|test| test.name.clone())
+                .collect(),
+            launch_targets,
+        }
+    }
+
+    pub fn tasks(&self) -> Vec<serde_json::Value> {
+        let task = |label: String, subcommand: &str, extra_args: &[&str]| {
+            let mut args = vec![subcommand.to_string()];
+            args.extend(self.build_args.iter().cloned());
+            args.extend(extra_args.iter().map(|arg| arg.to_string()));
+            serde_json::json!({
+                "label": label,
+                "type": "process",
+                "command": self.yambs,
+                "args": args,
+                "options": { "env": self.build_environment },
+                "problemMatcher": ["$gcc"],
+            })
+        };
+
+        let mut build = task(format!("{}build", LABEL_PREFIX), "build", &[]);
+        build["group"] = serde_json::json!({ "kind": "build", "isDefault": true });
+        let mut tasks = vec![build];
+        for target in &self.targets {
+            tasks.push(task(
+                build_task_label(target),
+                "build",
+                &["--target", target],
+            ));
+        }
+        if !self.tests.is_empty() {
+            let mut test = task(format!("{}test", LABEL_PREFIX), "test", &[]);
+            test["group"] = serde_json::json!({ "kind": "test", "isDefault": true });
+            tasks.push(test);
+            for name in &self.tests {
+                tasks.push(task(
+                    format!("{}test {}", LABEL_PREFIX, name),
+                    "test",
+                    &["--test", name],
+                ));
+            }
+        }
+        tasks
+    }
+
+    pub fn launch_configurations(&self) -> Vec<serde_json::Value> {
+        let debugger = if cfg!(target_os = "macos") {
+            "lldb"
+        } else {
+            "gdb"
+        };
+        self.launch_targets
+            .iter()
+            .map(|launch_target| {
+                serde_json::json!({
+                    "name": launch_target.name,
+                    "type": "cppdbg",
+                    "request": "launch",
+                    "program": launch_target.program,
+                    "args": launch_target.args,
+                    "cwd": launch_target.working_directory,
+                    "environment": launch_target
+                        .env
+                        .iter()
+                        .map(|(name, value)| serde_json::json!({ "name": name, "value": value }))
+                        .collect::<Vec<serde_json::Value>>(),
+                    "MIMode": debugger,
+                    "preLaunchTask": build_task_label(&launch_target.target),
+                })
+            })
+            .collect()
+    }
+
+    /// Writes tasks.json and launch.json to the .vscode directory of `workspace_directory`.
+    /// Returns the files written.
+    pub fn write(&self, workspace_directory: &Path) -> Result<Vec<PathBuf>, VscodeError> {
+        let directory = workspace_directory.join(VSCODE_DIRECTORY_NAME);
+        std::fs::create_dir_all(&directory)
+            .map_err(|e| FsError::CreateDirectory(directory.clone(), e))?;
+
+        let tasks_file = directory.join(TASKS_FILE_NAME);
+        write_merged(&tasks_file, "2.0.0", "tasks", "label", self.tasks())?;
+        let launch_file = directory.join(LAUNCH_FILE_NAME);
+        write_merged(
+            &launch_file,
+            "0.2.0",
+            "configurations",
+            "name",
+            self.launch_configurations(),
+        )?;
+        Ok(vec![tasks_file, launch_file])
+    }
+}
+
+fn build_task_label(target: &str) -> String {
+    format!("{}build {}", LABEL_PREFIX, target)
+}
+
+// Replaces the entries written by yambs in the list `key` of the JSON file `file` with `entries`,
+// keeping everything else of an existing file.
+fn write_merged(
+    file: &Path,
+    version: &str,
+    key: &str,
+    label_key: &str,
+    entries: Vec<serde_json::Value>,
+) -> Result<(), VscodeError> {
+    let mut document = if file.is_file() {
+        let content = std::fs::read_to_string(file)
+            .map_err(|e| FsError::ReadFromFile(file.to_path_buf(), e))?;
+        serde_json::from_str::<serde_json::Value>(&content)
+            .map_err(|e| VscodeError::FailedToParse(file.to_path_buf(), e))?
+    } else {
+        serde_json::json!({ "version": version })
+    };
+    if !document.is_object() {
+        return Err(VscodeError::NotAnObject(file.to_path_buf()));
+    }
+    let is_ours = |entry: &serde_json::Value| {
+        entry[label_key]
+            .as_str()
+            .is_some_and(|label| label.starts_with(LABEL_PREFIX))
+    };
+    let mut merged = document[key]
+        .as_array()
+        .map(|existing| {
+            existing
+                .iter()
+                .filter(|entry| !is_ours(entry))
+                .cloned()
+                .collect::<Vec<serde_json::Value>>()
+        })
+        .unwrap_or_default();
+    merged.extend(entries);
+    document[key] = serde_json::Value::Array(merged);
+
+    let content = serde_json::to_string_pretty(&document).unwrap();
+    std::fs::write(file, content + "\n").map_err(|e| FsError::CreateFile(file.to_path_buf(), e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::targets::ProgressTrackingTarget;
+    use crate::manifest::Manifest;
+    use crate::parser::preprocessor::Preprocessor;
+    use pretty_assertions::assert_eq;
+
+    fn workspace(
+        manifest_directory: &Path,
+        output_extensions: &OutputExtensions,
+    ) -> VscodeWorkspace {
+        std::fs::write(manifest_directory.join("main.cpp"), "").unwrap();
+        let manifest_path = manifest_directory.join(crate::YAMBS_MANIFEST_NAME);
+        let toml = r#"
+            [executable.app]
+            sources = ['main.cpp']
+
+            [test.smoke]
+            command = { target = "app", args = ["--smoke"] }
+            working_directory = "data"
+            env = { LEVEL = "1" }
+            "#;
+        std::fs::write(&manifest_path, toml).unwrap();
+        let data =
+            crate::parser::parse_toml(toml, &manifest_path, &mut Preprocessor::new()).unwrap();
+        let manifest = ParsedManifest {
+            manifest: Manifest::new(manifest_directory),
+            data,
+        };
+        let progress_document = ProgressDocument {
+            targets: ["app", "all"]
+                .iter()
+                .map(|target| ProgressTrackingTarget {
+                    target: target.to_string(),
+                    object_files: Vec::new(),
+                    dependencies: Vec::new(),
+                    output: None,
+                    packages: Vec::new(),
+                })
+                .collect(),
+        };
+        VscodeWorkspace::new(
+            Path::new("/usr/bin/yambs"),
+            vec!["-b".to_string(), "/build".to_string()],
+            BTreeMap::from([("CXX".to_string(), "g++".to_string())]),
+            &progress_document,
+            &manifest,
+            Path::new("/build/debug"),
+            output_extensions,
+        )
+    }
+
+    #[test]
+    fn tests_are_launched_with_the_environment_of_the_test_runner() {
+        let tempdir = tempdir::TempDir::new("vscode").unwrap();
+        let workspace = workspace(tempdir.path(), &OutputExtensions::default());
+        let configurations = workspace.launch_configurations();
+        assert_eq!(configurations.len(), 2);
+        let test = &configurations[1];
+        assert_eq!(test["name"], "yambs: test smoke");
+        assert_eq!(test["program"], "/build/debug/app");
+        assert_eq!(test["args"], serde_json::json!(["--smoke"]));
+        assert_eq!(
+            test["cwd"],
+            tempdir.path().join("data").display().to_string()
+        );
+        assert_eq!(
+            test["environment"],
+            serde_json::json!([
+                { "name": "LEVEL", "value": "1" },
+                { "name": "PATH", "value": "/build/debug:${env:PATH}" },
+                { "name": BUILD_DIRECTORY_VARIABLE, "value": "/build/debug" },
+            ])
+        );
+        assert_eq!(test["preLaunchTask"], "yambs: build app");
+    }
+
+    #[test]
+    fn programs_are_named_with_the_executable_extension_of_the_toolchain() {
+        let tempdir = tempdir::TempDir::new("vscode").unwrap();
+        let output_extensions = OutputExtensions {
+            executable: Some("exe".to_string()),
+            ..OutputExtensions::default()
+        };
+        let workspace = workspace(tempdir.path(), &output_extensions);
+        let programs = workspace
+            .launch_configurations()
+            .iter()
+            .map(|configuration| configuration["program"].clone())
+            .collect::<Vec<serde_json::Value>>();
+        assert_eq!(
+            programs,
+            vec!["/build/debug/app.exe", "/build/debug/app.exe"]
+        );
+    }
+
+    #[test]
+    fn writing_again_replaces_generated_entries_and_keeps_those_of_the_user() {
+        let tempdir = tempdir::TempDir::new("vscode").unwrap();
+        let workspace = workspace(tempdir.path(), &OutputExtensions::default());
+        let vscode_directory = tempdir.path().join(VSCODE_DIRECTORY_NAME);
+        std::fs::create_dir(&vscode_directory).unwrap();
+        std::fs::write(
+            vscode_directory.join(TASKS_FILE_NAME),
+            r#"{ "version": "2.0.0", "tasks": [
+                { "label": "lint", "type": "shell", "command": "clang-tidy" },
+                { "label": "yambs: build removed", "type": "process" }
+            ] }"#,
+        )
+        .unwrap();
+
+        workspace.write(tempdir.path()).unwrap();
+        let tasks = serde_json::from_str::<serde_json::Value>(
+            &std::fs::read_to_string(vscode_directory.join(TASKS_FILE_NAME)).unwrap(),
+        )
+        .unwrap();
+        let labels = tasks["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|task| task["label"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            labels,
+            vec![
+                "lint",
+                "yambs: build",
+                "yambs: build app",
+                "yambs: test",
+                "yambs: test smoke"
+            ]
+        );
+        assert_eq!(
+            tasks["tasks"][2]["args"],
+            serde_json::json!(["build", "-b", "/build", "--target", "app"])
+        );
+        assert_eq!(tasks["tasks"][2]["options"]["env"]["CXX"], "g++");
+    }
+
+    #[test]
+    fn writing_over_a_file_without_an_object_fails() {
+        let tempdir = tempdir::TempDir::new("vscode").unwrap();
+        let workspace = workspace(tempdir.path(), &OutputExtensions::default());
+        let vscode_directory = tempdir.path().join(VSCODE_DIRECTORY_NAME);
+        std::fs::create_dir(&vscode_directory).unwrap();
+        std::fs::write(vscode_directory.join(TASKS_FILE_NAME), "[]").unwrap();
+        assert!(matches!(
+            workspace.write(tempdir.path()),
+            Err(VscodeError::NotAnObject(ref file)) if file.ends_with(TASKS_FILE_NAME)
+        ));
+    }
+}
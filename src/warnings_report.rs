@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::cache::Cacher;
+use crate::errors::FsError;
+
+/// Extension of the file the diagnostics of compiling an object are kept in, next to the object.
+pub const WARNINGS_FILE_EXTENSION: &str = "warnings";
+
+/// File the warnings of the most recent report are written to in the build files directory.
+pub const WARNINGS_REPORT_FILE_NAME: &str = "warnings.json";
+
+lazy_static::lazy_static! {
+    // Warnings as written by GCC and Clang: "<file>:<line>:<column>: warning: <message> [-W<flag>]"
+    static ref WARNING_REGEX: regex::Regex = regex::Regex::new(
+        r"^(?P<file>[^:\s][^:]*):(?P<line>\d+):(?:\d+:)? warning: (?P<message>.*?)(?: \[(?P<flag>-W[^\]]+)\])?$"
+    )
+    .unwrap();
+}
+
+/// File the diagnostics of compiling `object` are kept in.
+pub fn warnings_file(object: &Path) -> PathBuf {
+    let mut file = object.as_os_str().to_os_string();
+    file.push(".");
+    file.push(WARNINGS_FILE_EXTENSION);
+    PathBuf::from(file)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub struct WarningRecord {
+    pub file: PathBuf,
+    pub line: u32,
+    /// Flag enabling the warning, such as -Wunused-variable, if the compiler tells.
+    pub flag: Option<String>,
+    pub message: String,
+}
+
+impl WarningRecord {
+    // Warnings are compared with the baseline by file and flag, as lines move when code is
+    // added or removed above them.
+    fn key(&self) -> (&Path, Option<&str>) {
+        (self.file.as_path(), self.flag.as_deref())
+    }
+}
+
+/// Parses the warnings out of the diagnostics of a compiler. Anything else is ignored.
+pub fn parse_warnings(diagnostics: &str) -> Vec<WarningRecord> {
+    diagnostics
+        .lines()
+        .filter_map(|line| WARNING_REGEX.captures(line))
+        .filter_map(|captures| {
+            Some(WarningRecord {
+                file: PathBuf::from(&captures["file"]),
+                line: captures["line"].parse().ok()?,
+                flag: captures.name("flag").map(|flag| flag.as_str().to_string()),
+                message: captures["message"].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Warnings of the objects of a build tree. Warnings of a header included by several translation
+/// units are only recorded once.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WarningsReport {
+    pub warnings: Vec<WarningRecord>,
+}
+
+impl WarningsReport {
+    pub fn new(warnings: Vec<WarningRecord>) -> Self {
+        let warnings = warnings
+            .into_iter()
+            .collect::<BTreeSet<WarningRecord>>()
+            .into_iter()
+            .collect();
+        Self { warnings }
+    }
+
+    /// Report of the warnings kept next to `objects`. Objects that have not been compiled yet have
+    /// no warnings.
+    pub fn from_objects<'a, I>(objects: I) -> Result<Self, FsError>
+    where
+        I: IntoIterator<Item = &'a PathBuf>,
+    {
+        let mut warnings = Vec::new();
+        for object in objects {
+            let file = warnings_file(object);
+            if !file.is_file() {
+                continue;
+            }
+            let diagnostics =
+                std::fs::read_to_string(&file).map_err(|e| FsError::ReadFromFile(file, e))?;
+            warnings.extend(parse_warnings(&diagnostics));
+        }
+        Ok(Self::new(warnings))
+    }
+
+    /// Number of warnings of each flag.
+    pub fn count_by_flag(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for warning in &self.warnings {
+            let flag = warning
+                .flag
+                .clone()
+                .unwrap_or_else(|| "(no flag)".to_string());
+            *counts.entry(flag).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Warnings in excess of those in `baseline`, for each file and flag. Warnings identical to
+    /// one in the baseline are the last to be considered new.
+    pub fn new_warnings(&self, baseline: &WarningsReport) -> Vec<&WarningRecord> {
+        let baseline_counts = baseline.counts();
+        let mut new_warnings = Vec::new();
+        for (key, mut warnings) in self.group() {
+            let allowed = baseline_counts.get(&key).copied().unwrap_or(0);
+            if warnings.len() <= allowed {
+                continue;
+            }
+            let excess = warnings.len() - allowed;
+            warnings.sort_by_key(|warning| baseline.warnings.contains(warning));
+            new_warnings.extend(warnings.into_iter().take(excess));
+        }
+        new_warnings.sort();
+        new_warnings
+    }
+
+    /// Baseline ratcheted down to the warnings still present, so that fixed warnings can not be
+    /// reintroduced without being reported as new.
+    pub fn ratchet(&self, baseline: &WarningsReport) -> WarningsReport {
+        let counts = self.counts();
+        let mut kept = Vec::new();
+        for (key, mut warnings) in baseline.group() {
+            let allowed = counts.get(&key).copied().unwrap_or(0);
+            warnings.sort_by_key(|warning| !self.warnings.contains(warning));
+            kept.extend(warnings.into_iter().take(allowed).cloned());
+        }
+        WarningsReport::new(kept)
+    }
+
+    fn counts(&self) -> BTreeMap<(&Path, Option<&str>), usize> {
+        self.group()
+            .into_iter()
+            .map(|(key, warnings)| (key, warnings.len()))
+            .collect()
+    }
+
+    fn group(&self) -> BTreeMap<(&Path, Option<&str>), Vec<&WarningRecord>> {
+        let mut groups = BTreeMap::<_, Vec<&WarningRecord>>::new();
+        for warning in &self.warnings {
+            groups.entry(warning.key()).or_default().push(warning);
+        }
+        groups
+    }
+}
+
+/// Baselines that the warnings of later builds are compared with, for each build type.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WarningsBaselines {
+    pub baselines: BTreeMap<String, WarningsReport>,
+}
+
+impl Cacher for WarningsBaselines {
+    const CACHE_FILE_NAME: &'static str = "warnings_baselines";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn warning(file: &str, line: u32, flag: &str) -> WarningRecord {
+        WarningRecord {
+            file: PathBuf::from(file),
+            line,
+            flag: Some(flag.to_string()),
+            message: "message".to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_warnings_of_gcc_and_clang() {
+        let diagnostics = "\
+src/a.cpp: In function 'int main()':
+src/a.cpp:3:9: warning: unused variable 'x' [-Wunused-variable]
+    3 |     int x;
+      |         ^
+src/b.h:10:1: warning: no return statement in function returning non-void [-Wreturn-type]
+src/c.cpp:5:2: error: expected ';' before '}' token
+src/d.cpp:7: warning: something without a flag
+";
+        assert_eq!(
+            parse_warnings(diagnostics),
+            vec![
+                WarningRecord {
+                    file: PathBuf::from("src/a.cpp"),
+                    line: 3,
+                    flag: Some("-Wunused-variable".to_string()),
+                    message: "unused variable 'x'".to_string(),
+                },
+                WarningRecord {
+                    file: PathBuf::from("src/b.h"),
+                    line: 10,
+                    flag: Some("-Wreturn-type".to_string()),
+                    message: "no return statement in function returning non-void".to_string(),
+                },
+                WarningRecord {
+                    file: PathBuf::from("src/d.cpp"),
+                    line: 7,
+                    flag: None,
+                    message: "something without a flag".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn moved_warnings_are_not_new_but_additional_ones_are() {
+        let baseline = WarningsReport::new(vec![
+            warning("a.cpp", 3, "-Wunused-variable"),
+            warning("b.cpp", 8, "-Wshadow"),
+        ]);
+        let current = WarningsReport::new(vec![
+            warning("a.cpp", 5, "-Wunused-variable"),
+            warning("b.cpp", 8, "-Wshadow"),
+            warning("b.cpp", 12, "-Wshadow"),
+        ]);
+        assert_eq!(
+            current.new_warnings(&baseline),
+            vec![&warning("b.cpp", 12, "-Wshadow")]
+        );
+    }
+
+    #[test]
+    fn ratchet_drops_fixed_warnings_from_baseline() {
+        let baseline = WarningsReport::new(vec![
+            warning("a.cpp", 3, "-Wunused-variable"),
+            warning("a.cpp", 9, "-Wunused-variable"),
+            warning("b.cpp", 8, "-Wshadow"),
+        ]);
+        let current = WarningsReport::new(vec![
+            warning("a.cpp", 9, "-Wunused-variable"),
+            warning("c.cpp", 1, "-Wshadow"),
+        ]);
+        assert_eq!(
+            current.ratchet(&baseline),
+            WarningsReport::new(vec![warning("a.cpp", 9, "-Wunused-variable")])
+        );
+    }
+}
@@ -1 +1,37 @@
+use std::process::Command;
 
+#[test]
+fn tests_are_not_run_when_the_build_fails() {
+    let project_directory = tempdir::TempDir::new("yambs-test-build-fails").unwrap();
+    let project_path = project_directory.path();
+    std::fs::write(project_path.join("main.cpp"), "int main() { return }\n").unwrap();
+    std::fs::write(
+        project_path.join("yambs.toml"),
+        r#"
+[executable.app]
+sources = ["main.cpp"]
+
+[test.ran]
+command = ["touch", "test-ran"]
+"#,
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_yambs"))
+        .current_dir(project_path)
+        .env("CXX", "g++")
+        .env("CC", "gcc")
+        .args([
+            "test",
+            "-b",
+            "build",
+            "--std",
+            "c++17",
+            "--manifest-directory",
+        ])
+        .arg(project_path)
+        .status()
+        .unwrap();
+    assert!(!status.success());
+    assert!(!project_path.join("test-ran").exists());
+}